@@ -14,13 +14,17 @@
 //!   In particular, charged with compiling the eFLINT interface to eFLINT JSON before it can be included in the executable.
 //
 
+use std::env;
 use std::env::VarError;
+#[cfg(feature = "sqlite")]
+use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
-use std::{env, fs};
 
+#[cfg(feature = "sqlite")]
 use diesel::{Connection as _, SqliteConnection};
+#[cfg(feature = "sqlite")]
 use diesel_migrations::{FileBasedMigrations, MigrationHarness};
 use eflint_to_json::compile;
 use error_trace::trace;
@@ -106,6 +110,7 @@ fn compile_eflint() {
 }
 
 /// Runs the Diesel migrations for the database.
+#[cfg(feature = "sqlite")]
 fn build_database() {
     // Setup the triggers for running this script
     let src_dir: PathBuf = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
@@ -148,5 +153,6 @@ fn main() {
     compile_eflint();
 
     // 2. Build the database
+    #[cfg(feature = "sqlite")]
     build_database();
 }
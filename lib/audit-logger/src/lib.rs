@@ -6,31 +6,43 @@ use std::hash::{Hash as _, Hasher as _};
 use auth_resolver::AuthContext;
 use deliberation::spec::Verdict;
 use enum_debug::EnumDebug;
-use policy::Policy;
+use policy::{Policy, PolicyArchive};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use state_resolver::State;
 use workflow::Workflow;
+use workflow::canonical::content_hash;
+use workflow::diagnostics::{Diagnostic, InlineStats};
 
-#[derive(Debug)]
-pub enum Error {
-    CouldNotDeliver(String),
-}
+pub mod merkle;
+pub mod payloads;
+pub mod redact;
+pub mod schema;
+pub mod session;
+pub mod subject;
+pub mod timings;
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::CouldNotDeliver(msg) => {
-                write!(f, "Could not deliver: {}", msg)
-            },
-        }
-    }
-}
+pub use payloads::{ArchivedPayload, PayloadKind, PayloadStore};
+pub use session::{SessionSummary, SessionedConnectorAuditLogger};
+pub use timings::{RequestTimer, RequestTimings};
 
-impl std::error::Error for Error {}
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A statement could not be written (or forwarded) by the logger, because of the given error.
+    #[error("Could not deliver statement to the audit log")]
+    CouldNotDeliver(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// A payload could not be written to the [`PayloadStore`], because of the given error.
+    #[error("Could not archive payload")]
+    CouldNotArchive(#[source] std::io::Error),
+}
 
+#[cfg(feature = "warp")]
 impl warp::reject::Reject for Error {}
 
+/// Returns whether a count is zero, to omit it from statements (see [`LogStatement::ReasonerResponse`]).
+#[inline]
+fn is_zero(count: &u32) -> bool { *count == 0 }
+
 pub trait ConnectorContext {
     fn r#type(&self) -> String;
     fn version(&self) -> String;
@@ -67,38 +79,107 @@ pub enum LogStatement<'a> {
     /// A request that asks if a task may be executed has been received.
     ExecuteTask {
         reference: Cow<'a, str>,
-        auth:      Cow<'a, AuthContext>,
-        policy:    i64,
-        state:     Cow<'a, State>,
-        workflow:  Cow<'a, Workflow>,
-        task:      Cow<'a, str>,
+        auth: Cow<'a, AuthContext>,
+        policy: i64,
+        /// The content hashes of the policy version (see [`Policy::content_hashes()`]), to verify which exact policy was used.
+        #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+        policy_hashes: Cow<'a, [String]>,
+        state: Cow<'a, State>,
+        workflow: Cow<'a, Workflow>,
+        /// The content hash of the workflow (see [`workflow::canonical::content_hash()`]).
+        #[serde(default)]
+        workflow_hash: String,
+        /// Information missing from the workflow that made its check weaker (see [`Workflow::diagnostics()`]).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<Diagnostic>,
+        task: Cow<'a, str>,
     },
     /// A request that asks if an asset may be accessed has been received.
     AssetAccess {
         reference: Cow<'a, str>,
-        auth:      Cow<'a, AuthContext>,
-        policy:    i64,
-        state:     Cow<'a, State>,
-        workflow:  Cow<'a, Workflow>,
-        data:      Cow<'a, str>,
+        auth: Cow<'a, AuthContext>,
+        policy: i64,
+        /// The content hashes of the policy version (see [`Policy::content_hashes()`]), to verify which exact policy was used.
+        #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+        policy_hashes: Cow<'a, [String]>,
+        state: Cow<'a, State>,
+        workflow: Cow<'a, Workflow>,
+        /// The content hash of the workflow (see [`workflow::canonical::content_hash()`]).
+        #[serde(default)]
+        workflow_hash: String,
+        /// Information missing from the workflow that made its check weaker (see [`Workflow::diagnostics()`]).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<Diagnostic>,
+        data: Cow<'a, str>,
         #[serde(skip_serializing_if = "Option::is_none")]
-        task:      Option<Cow<'a, str>>,
+        task: Option<Cow<'a, str>>,
     },
     /// A request that asks if a workflow is permitted has been received.
     WorkflowValidate {
         reference: Cow<'a, str>,
-        auth:      Cow<'a, AuthContext>,
-        policy:    i64,
-        state:     Cow<'a, State>,
-        workflow:  Cow<'a, Workflow>,
+        auth: Cow<'a, AuthContext>,
+        policy: i64,
+        /// The content hashes of the policy version (see [`Policy::content_hashes()`]), to verify which exact policy was used.
+        #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+        policy_hashes: Cow<'a, [String]>,
+        state: Cow<'a, State>,
+        workflow: Cow<'a, Workflow>,
+        /// The content hash of the workflow (see [`workflow::canonical::content_hash()`]).
+        #[serde(default)]
+        workflow_hash: String,
+        /// Information missing from the workflow that made its check weaker (see [`Workflow::diagnostics()`]).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<Diagnostic>,
     },
+    /// A query, which asks what holds under a policy instead of whether something is allowed, has been received.
+    Query { reference: Cow<'a, str>, auth: Cow<'a, AuthContext>, policy: i64, state: Cow<'a, State>, query: Cow<'a, Value> },
 
     /// Logs the raw response of a reasoner.
-    ReasonerResponse { reference: Cow<'a, str>, response: Cow<'a, str> },
+    ReasonerResponse {
+        reference: Cow<'a, str>,
+        response:  Cow<'a, str>,
+        /// The number of times the reasoner backend had to be asked again after failing transiently.
+        #[serde(default, skip_serializing_if = "is_zero")]
+        retries:   u32,
+    },
     /// Logs the official response of a reasoner.
     ReasonerVerdict { reference: Cow<'a, str>, verdict: Cow<'a, Verdict> },
+    /// Logs how long the phases of handling a question took, right before its verdict.
+    RequestTimings(Cow<'a, RequestTimings>),
+    /// Logs how much the workflow of a request grew by preprocessing it, right after the request itself.
+    WorkflowPreprocessed { reference: Cow<'a, str>, stats: InlineStats },
+    /// Logs that the reasoner could not be consulted, and which fallback behaviour was used instead.
+    ReasonerFallback { reference: Cow<'a, str>, behaviour: Cow<'a, str>, reason: Cow<'a, str> },
+    /// Logs that the reasoner was not consulted because its circuit breaker is open, and which fallback behaviour was used instead.
+    CircuitOpen {
+        reference: Cow<'a, str>,
+        behaviour: Cow<'a, str>,
+        /// How long until the breaker lets a request through again, in milliseconds.
+        retry_after_ms: u64,
+        reason: Cow<'a, str>,
+    },
+    /// Logs that a verdict transformer altered the answer of the reasoner before it was returned.
+    VerdictTransformed { reference: Cow<'a, str>, transformer: Cow<'a, str>, reason: Cow<'a, str> },
+    /// Logs that a rule of the prefilter answered a request (with `allow` or `deny`), such that the reasoner was not asked.
+    PrefilterMatched { reference: Cow<'a, str>, rule: Cow<'a, str>, effect: Cow<'a, str> },
+    /// Logs that a request is answered retrospectively, i.e., with the policy (and, if known, the state) of a moment in the past.
+    RetrospectiveEvaluation { reference: Cow<'a, str>, as_of: Cow<'a, str>, state_snapshot: bool },
+    /// Logs the timings of a question (or one of its sub-questions) once the reasoner connector is done with it.
+    SessionSummary(Cow<'a, SessionSummary>),
+    /// Logs the answer of a shadow reasoner connector to a question next to that of the connector whose answer counts.
+    ShadowEvaluation {
+        reference: Cow<'a, str>,
+        /// The answer (or error) of the connector whose answer counts.
+        primary:   Cow<'a, Value>,
+        /// The answer (or error) of the shadow connector, which is never returned.
+        shadow:    Cow<'a, Value>,
+        /// Whether both gave the same verdict.
+        agree:     bool,
+    },
+    /// Logs that a payload of a question was written to the payload store, and where.
+    PayloadArchived(Cow<'a, ArchivedPayload>),
 
-    /// Logs the reasoner backend for during startup.
+    /// Logs the reasoner backend for during startup, and whenever its effective context changes on a reload.
     ReasonerContext { connector_context: Value, connector_context_hash: String },
     /// Logs the arrival of a new policy.
     PolicyAdd { auth: Cow<'a, AuthContext>, connector_context_hash: String, policy: Cow<'a, Policy> },
@@ -106,6 +187,70 @@ pub enum LogStatement<'a> {
     PolicyActivate { auth: Cow<'a, AuthContext>, policy: Cow<'a, Policy> },
     /// Logs the deactivation of the current active policy.
     PolicyDeactivate { auth: Cow<'a, AuthContext> },
+    /// Logs that the complete policy store got replaced by an imported archive.
+    PolicyImport { auth: Cow<'a, AuthContext>, versions: Vec<i64>, active: Option<i64> },
+    /// Logs that the emergency lockdown was engaged or lifted, and by whom.
+    Lockdown {
+        auth:    Cow<'a, AuthContext>,
+        engaged: bool,
+        /// The reason for denial given to clients while it is engaged.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason:  Option<Cow<'a, str>>,
+    },
+
+    /// Logs that a request was refused because its credentials could not be authenticated.
+    AuthFailure {
+        /// The path of the request (e.g., `/v1/deliberation/execute-task`).
+        #[serde(default)]
+        route: Cow<'a, str>,
+        reason: Cow<'a, str>,
+        detail: Cow<'a, str>,
+        /// Who the credentials claim to be sent by, if that could be read from them. Never verified, so only a hint.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initiator_hint: Option<Cow<'a, str>>,
+    },
+    /// Logs that the credentials of a request were authenticated, before it is handled.
+    AuthSuccess {
+        auth:  Cow<'a, AuthContext>,
+        /// The path of the request (e.g., `/v1/deliberation/execute-task`).
+        route: Cow<'a, str>,
+    },
+
+    /// Logs that the owner of a dataset was notified of a verdict on a workflow using it (or that notifying them failed).
+    OwnerNotification {
+        reference: Cow<'a, str>,
+        dataset:   Cow<'a, str>,
+        /// How the owner was notified (e.g., `webhook` or `email`).
+        channel:   Cow<'a, str>,
+        recipient: Cow<'a, str>,
+        /// Why the notification could not be delivered, if it couldn't.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error:     Option<Cow<'a, str>>,
+    },
+
+    /// Logs the verdict of a partner checker that a request was forwarded to (or why it could not be obtained).
+    FederatedVerdict {
+        reference: Cow<'a, str>,
+        /// The domain of the partner checker.
+        domain: Cow<'a, str>,
+        /// The verdict reference given by the partner checker, if it answered.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remote_reference: Option<Cow<'a, str>>,
+        allowed: bool,
+        /// Why the partner checker could not be consulted, if it couldn't.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<Cow<'a, str>>,
+    },
+
+    /// Logs that (part of) the summary of the policy was disclosed to someone, and what was withheld from them.
+    PolicyDisclosure {
+        auth:     Cow<'a, AuthContext>,
+        /// The names of the fields that were disclosed.
+        fields:   Cow<'a, [String]>,
+        /// The names of the fields that were asked for but withheld.
+        #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+        withheld: Cow<'a, [String]>,
+    },
 }
 impl<'a> LogStatement<'a> {
     /// Constructor for a [`LogStatement::ExecuteTask`] that makes it a bit more convenient to initialize.
@@ -114,6 +259,7 @@ impl<'a> LogStatement<'a> {
     /// - `reference`: The reference ID for this request.
     /// - `auth`: The [`AuthContext`] that explains who performed the request.
     /// - `policy`: The [`Policy`] used to evaluate the request.
+    /// - `policy_hashes`: The content hashes of that policy (see [`Policy::content_hashes()`]).
     /// - `state`: The [`State`] given to the policy for evaluation.
     /// - `workflow`: The [`Workflow`] that is being evaluated.
     /// - `task`: Which task in the `workflow` we're specifically considering.
@@ -121,13 +267,24 @@ impl<'a> LogStatement<'a> {
     /// # Returns
     /// A new [`LogStatement::ExecuteTask`] that is initialized with the given properties.
     #[inline]
-    pub fn execute_task(reference: &'a str, auth: &'a AuthContext, policy: i64, state: &'a State, workflow: &'a Workflow, task: &'a str) -> Self {
+    pub fn execute_task(
+        reference: &'a str,
+        auth: &'a AuthContext,
+        policy: i64,
+        policy_hashes: &'a [String],
+        state: &'a State,
+        workflow: &'a Workflow,
+        task: &'a str,
+    ) -> Self {
         Self::ExecuteTask {
             reference: Cow::Borrowed(reference),
             auth: Cow::Borrowed(auth),
             policy,
+            policy_hashes: Cow::Borrowed(policy_hashes),
             state: Cow::Borrowed(state),
             workflow: Cow::Borrowed(workflow),
+            workflow_hash: content_hash(workflow),
+            warnings: workflow.diagnostics(),
             task: Cow::Borrowed(task),
         }
     }
@@ -138,6 +295,7 @@ impl<'a> LogStatement<'a> {
     /// - `reference`: The reference ID for this request.
     /// - `auth`: The [`AuthContext`] that explains who performed the request.
     /// - `policy`: The [`Policy`] used to evaluate the request.
+    /// - `policy_hashes`: The content hashes of that policy (see [`Policy::content_hashes()`]).
     /// - `state`: The [`State`] given to the policy for evaluation.
     /// - `workflow`: The [`Workflow`] that is being evaluated.
     /// - `task`: Which task in the `workflow` we're specifically considering.
@@ -145,10 +303,12 @@ impl<'a> LogStatement<'a> {
     /// # Returns
     /// A new [`LogStatement::AssetAccess`] that is initialized with the given properties.
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub fn asset_access(
         reference: &'a str,
         auth: &'a AuthContext,
         policy: i64,
+        policy_hashes: &'a [String],
         state: &'a State,
         workflow: &'a Workflow,
         data: &'a str,
@@ -158,8 +318,11 @@ impl<'a> LogStatement<'a> {
             reference: Cow::Borrowed(reference),
             auth: Cow::Borrowed(auth),
             policy,
+            policy_hashes: Cow::Borrowed(policy_hashes),
             state: Cow::Borrowed(state),
             workflow: Cow::Borrowed(workflow),
+            workflow_hash: content_hash(workflow),
+            warnings: workflow.diagnostics(),
             data: Cow::Borrowed(data),
             task: task.as_ref().map(|t| Cow::Borrowed(t.as_str())),
         }
@@ -171,6 +334,7 @@ impl<'a> LogStatement<'a> {
     /// - `reference`: The reference ID for this request.
     /// - `auth`: The [`AuthContext`] that explains who performed the request.
     /// - `policy`: The [`Policy`] used to evaluate the request.
+    /// - `policy_hashes`: The content hashes of that policy (see [`Policy::content_hashes()`]).
     /// - `state`: The [`State`] given to the policy for evaluation.
     /// - `workflow`: The [`Workflow`] that is being evaluated.
     /// - `task`: Which task in the `workflow` we're specifically considering.
@@ -178,13 +342,45 @@ impl<'a> LogStatement<'a> {
     /// # Returns
     /// A new [`LogStatement::WorkflowValidate`] that is initialized with the given properties.
     #[inline]
-    pub fn workflow_validate(reference: &'a str, auth: &'a AuthContext, policy: i64, state: &'a State, workflow: &'a Workflow) -> Self {
+    pub fn workflow_validate(
+        reference: &'a str,
+        auth: &'a AuthContext,
+        policy: i64,
+        policy_hashes: &'a [String],
+        state: &'a State,
+        workflow: &'a Workflow,
+    ) -> Self {
         Self::WorkflowValidate {
             reference: Cow::Borrowed(reference),
             auth: Cow::Borrowed(auth),
             policy,
+            policy_hashes: Cow::Borrowed(policy_hashes),
             state: Cow::Borrowed(state),
             workflow: Cow::Borrowed(workflow),
+            workflow_hash: content_hash(workflow),
+            warnings: workflow.diagnostics(),
+        }
+    }
+
+    /// Constructor for a [`LogStatement::Query`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `auth`: The [`AuthContext`] that explains who performed the request.
+    /// - `policy`: The [`Policy`] used to answer the query.
+    /// - `state`: The [`State`] given to the policy for evaluation.
+    /// - `query`: The query, in the language of the reasoner.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::Query`] that is initialized with the given properties.
+    #[inline]
+    pub fn query(reference: &'a str, auth: &'a AuthContext, policy: i64, state: &'a State, query: &'a Value) -> Self {
+        Self::Query {
+            reference: Cow::Borrowed(reference),
+            auth: Cow::Borrowed(auth),
+            policy,
+            state: Cow::Borrowed(state),
+            query: Cow::Borrowed(query),
         }
     }
 
@@ -193,12 +389,13 @@ impl<'a> LogStatement<'a> {
     /// # Arguments
     /// - `reference`: The reference ID for this request.
     /// - `response`: The raw response as returned by the reasoner.
+    /// - `retries`: The number of times the reasoner backend had to be asked again after failing transiently.
     ///
     /// # Returns
     /// A new [`LogStatement::ReasonerResponse`] that is initialized with the given properties.
     #[inline]
-    pub fn reasoner_response(reference: &'a str, response: &'a str) -> Self {
-        Self::ReasonerResponse { reference: Cow::Borrowed(reference), response: Cow::Borrowed(response) }
+    pub fn reasoner_response(reference: &'a str, response: &'a str, retries: u32) -> Self {
+        Self::ReasonerResponse { reference: Cow::Borrowed(reference), response: Cow::Borrowed(response), retries }
     }
 
     /// Constructor for a [`LogStatement::ReasonerVerdict`] that makes it a bit more convenient to initialize.
@@ -214,6 +411,139 @@ impl<'a> LogStatement<'a> {
         Self::ReasonerVerdict { reference: Cow::Borrowed(reference), verdict: Cow::Borrowed(verdict) }
     }
 
+    /// Constructor for a [`LogStatement::ReasonerFallback`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `behaviour`: The fallback behaviour that was used to answer the request.
+    /// - `reason`: Why the reasoner could not be consulted.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::ReasonerFallback`] that is initialized with the given properties.
+    #[inline]
+    pub fn reasoner_fallback(reference: &'a str, behaviour: &'a str, reason: &'a str) -> Self {
+        Self::ReasonerFallback { reference: Cow::Borrowed(reference), behaviour: Cow::Borrowed(behaviour), reason: Cow::Borrowed(reason) }
+    }
+
+    /// Constructor for a [`LogStatement::CircuitOpen`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `behaviour`: The fallback behaviour that was used to answer the request.
+    /// - `retry_after_ms`: How long until the breaker lets a request through again, in milliseconds.
+    /// - `reason`: Why the breaker is open.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::CircuitOpen`] that is initialized with the given properties.
+    #[inline]
+    pub fn circuit_open(reference: &'a str, behaviour: &'a str, retry_after_ms: u64, reason: &'a str) -> Self {
+        Self::CircuitOpen { reference: Cow::Borrowed(reference), behaviour: Cow::Borrowed(behaviour), retry_after_ms, reason: Cow::Borrowed(reason) }
+    }
+
+    /// Constructor for a [`LogStatement::VerdictTransformed`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `transformer`: The name of the transformer that altered the answer.
+    /// - `reason`: What the transformer changed and why.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::VerdictTransformed`] that is initialized with the given properties.
+    #[inline]
+    pub fn verdict_transformed(reference: &'a str, transformer: &'a str, reason: &'a str) -> Self {
+        Self::VerdictTransformed {
+            reference:   Cow::Borrowed(reference),
+            transformer: Cow::Borrowed(transformer),
+            reason:      Cow::Borrowed(reason),
+        }
+    }
+
+    /// Constructor for a [`LogStatement::PrefilterMatched`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `rule`: The identifier of the prefilter rule that matched.
+    /// - `effect`: What the rule answered (`allow` or `deny`).
+    ///
+    /// # Returns
+    /// A new [`LogStatement::PrefilterMatched`] that is initialized with the given properties.
+    #[inline]
+    pub fn prefilter_matched(reference: &'a str, rule: &'a str, effect: &'a str) -> Self {
+        Self::PrefilterMatched { reference: Cow::Borrowed(reference), rule: Cow::Borrowed(rule), effect: Cow::Borrowed(effect) }
+    }
+
+    /// Constructor for a [`LogStatement::RetrospectiveEvaluation`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `as_of`: The moment as of which the request is answered.
+    /// - `state_snapshot`: Whether the state of that moment was used (true) or the current one (false).
+    ///
+    /// # Returns
+    /// A new [`LogStatement::RetrospectiveEvaluation`] that is initialized with the given properties.
+    #[inline]
+    pub fn retrospective_evaluation(reference: &'a str, as_of: &'a str, state_snapshot: bool) -> Self {
+        Self::RetrospectiveEvaluation { reference: Cow::Borrowed(reference), as_of: Cow::Borrowed(as_of), state_snapshot }
+    }
+
+    /// Constructor for a [`LogStatement::RequestTimings`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `timings`: The [`RequestTimings`] of the question.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::RequestTimings`] that is initialized with the given properties.
+    #[inline]
+    pub fn request_timings(timings: &'a RequestTimings) -> Self { Self::RequestTimings(Cow::Borrowed(timings)) }
+
+    /// Constructor for a [`LogStatement::WorkflowPreprocessed`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `stats`: The [`InlineStats`] of preprocessing the request's workflow.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::WorkflowPreprocessed`] that is initialized with the given properties.
+    #[inline]
+    pub fn workflow_preprocessed(reference: &'a str, stats: &InlineStats) -> Self {
+        Self::WorkflowPreprocessed { reference: Cow::Borrowed(reference), stats: *stats }
+    }
+
+    /// Constructor for a [`LogStatement::SessionSummary`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `summary`: The [`SessionSummary`] of the session that ended.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::SessionSummary`] that is initialized with the given properties.
+    #[inline]
+    pub fn session_summary(summary: &'a SessionSummary) -> Self { Self::SessionSummary(Cow::Borrowed(summary)) }
+
+    /// Constructor for a [`LogStatement::ShadowEvaluation`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID for this request.
+    /// - `primary`: The answer (or error) of the connector whose answer counts.
+    /// - `shadow`: The answer (or error) of the shadow connector.
+    /// - `agree`: Whether both gave the same verdict.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::ShadowEvaluation`] that is initialized with the given properties.
+    #[inline]
+    pub fn shadow_evaluation(reference: &'a str, primary: &'a Value, shadow: &'a Value, agree: bool) -> Self {
+        Self::ShadowEvaluation { reference: Cow::Borrowed(reference), primary: Cow::Borrowed(primary), shadow: Cow::Borrowed(shadow), agree }
+    }
+
+    /// Constructor for a [`LogStatement::PayloadArchived`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `payload`: The [`ArchivedPayload`] that describes where the payload was written.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::PayloadArchived`] that is initialized with the given properties.
+    #[inline]
+    pub fn payload_archived(payload: &'a ArchivedPayload) -> Self { Self::PayloadArchived(Cow::Borrowed(payload)) }
+
     /// Constructor for a [`LogStatement::ReasonerContext`] that makes it a bit more convenient to initialize.
     ///
     /// # Arguments
@@ -230,6 +560,19 @@ impl<'a> LogStatement<'a> {
         }
     }
 
+    /// Constructor for a [`LogStatement::ReasonerContext`] that logs the effective context of a reconfigured reasoner.
+    ///
+    /// # Arguments
+    /// - `context`: The effective context, i.e., the connector's context together with its current configuration.
+    /// - `hash`: The hash of the effective context.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::ReasonerContext`] that is initialized with the given properties.
+    #[inline]
+    pub fn effective_reasoner_context(context: &Value, hash: &str) -> Self {
+        Self::ReasonerContext { connector_context: context.clone(), connector_context_hash: hash.into() }
+    }
+
     /// Constructor for a [`LogStatement::PolicyAdd`] that makes it a bit more convenient to initialize.
     ///
     /// # Arguments
@@ -266,15 +609,139 @@ impl<'a> LogStatement<'a> {
     /// A new [`LogStatement::PolicyDeactivate`] that is initialized with the given properties.
     #[inline]
     pub fn policy_deactivate(auth: &'a AuthContext) -> Self { Self::PolicyDeactivate { auth: Cow::Borrowed(auth) } }
+
+    /// Constructor for a [`LogStatement::PolicyImport`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `auth`: The [`AuthContext`] that explains who performed the request.
+    /// - `archive`: The [`PolicyArchive`] that replaced the contents of the policy store.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::PolicyImport`] that is initialized with the given properties.
+    #[inline]
+    pub fn policy_import(auth: &'a AuthContext, archive: &PolicyArchive) -> Self {
+        Self::PolicyImport {
+            auth:     Cow::Borrowed(auth),
+            versions: archive.policies.iter().filter_map(|p| p.version.version).collect(),
+            active:   archive.active(),
+        }
+    }
+
+    /// Constructor for a [`LogStatement::Lockdown`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `auth`: The [`AuthContext`] that explains who performed the request.
+    /// - `engaged`: Whether the lockdown got engaged (`true`) or lifted (`false`).
+    /// - `reason`: The reason for denial given to clients while it is engaged, if any.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::Lockdown`] that is initialized with the given properties.
+    #[inline]
+    pub fn lockdown(auth: &'a AuthContext, engaged: bool, reason: Option<&'a str>) -> Self {
+        Self::Lockdown { auth: Cow::Borrowed(auth), engaged, reason: reason.map(Cow::Borrowed) }
+    }
+
+    /// Constructor for a [`LogStatement::AuthFailure`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `route`: The path of the request that was refused.
+    /// - `reason`: The kind of failure (e.g., `expired` or `wrong-audience`).
+    /// - `detail`: A human-readable explanation of what was wrong with the credentials.
+    /// - `initiator_hint`: Who the (unverified) credentials claim to be sent by, if known.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::AuthFailure`] that is initialized with the given properties.
+    #[inline]
+    pub fn auth_failure(route: &'a str, reason: &'a str, detail: &'a str, initiator_hint: Option<&'a str>) -> Self {
+        Self::AuthFailure {
+            route: Cow::Borrowed(route),
+            reason: Cow::Borrowed(reason),
+            detail: Cow::Borrowed(detail),
+            initiator_hint: initiator_hint.map(Cow::Borrowed),
+        }
+    }
+
+    /// Constructor for a [`LogStatement::AuthSuccess`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `auth`: The [`AuthContext`] the request was authenticated as.
+    /// - `route`: The path of the request.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::AuthSuccess`] that is initialized with the given properties.
+    #[inline]
+    pub fn auth_success(auth: &'a AuthContext, route: &'a str) -> Self {
+        Self::AuthSuccess { auth: Cow::Borrowed(auth), route: Cow::Borrowed(route) }
+    }
+
+    /// Constructor for a [`LogStatement::OwnerNotification`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID of the request whose verdict the owner was notified of.
+    /// - `dataset`: The dataset of the owner.
+    /// - `channel`: How the owner was notified (e.g., `webhook` or `email`).
+    /// - `recipient`: Where the notification was sent to.
+    /// - `error`: Why the notification could not be delivered, or [`None`] if it was.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::OwnerNotification`] that is initialized with the given properties.
+    #[inline]
+    pub fn owner_notification(reference: &'a str, dataset: &'a str, channel: &'a str, recipient: &'a str, error: Option<&'a str>) -> Self {
+        Self::OwnerNotification {
+            reference: Cow::Borrowed(reference),
+            dataset:   Cow::Borrowed(dataset),
+            channel:   Cow::Borrowed(channel),
+            recipient: Cow::Borrowed(recipient),
+            error:     error.map(Cow::Borrowed),
+        }
+    }
+
+    /// Constructor for a [`LogStatement::FederatedVerdict`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference ID of the request that was forwarded.
+    /// - `domain`: The domain of the partner checker it was forwarded to.
+    /// - `remote_reference`: The verdict reference given by the partner checker, or [`None`] if it did not answer.
+    /// - `allowed`: Whether the partner checker allowed the request.
+    /// - `error`: Why the partner checker could not be consulted, or [`None`] if it could.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::FederatedVerdict`] that is initialized with the given properties.
+    #[inline]
+    pub fn federated_verdict(reference: &'a str, domain: &'a str, remote_reference: Option<&'a str>, allowed: bool, error: Option<&'a str>) -> Self {
+        Self::FederatedVerdict {
+            reference: Cow::Borrowed(reference),
+            domain: Cow::Borrowed(domain),
+            remote_reference: remote_reference.map(Cow::Borrowed),
+            allowed,
+            error: error.map(Cow::Borrowed),
+        }
+    }
+
+    /// Constructor for a [`LogStatement::PolicyDisclosure`] that makes it a bit more convenient to initialize.
+    ///
+    /// # Arguments
+    /// - `auth`: The [`AuthContext`] of who the summary was disclosed to.
+    /// - `fields`: The names of the fields that were disclosed.
+    /// - `withheld`: The names of the fields that were asked for but withheld.
+    ///
+    /// # Returns
+    /// A new [`LogStatement::PolicyDisclosure`] that is initialized with the given properties.
+    #[inline]
+    pub fn policy_disclosure(auth: &'a AuthContext, fields: &'a [String], withheld: &'a [String]) -> Self {
+        Self::PolicyDisclosure { auth: Cow::Borrowed(auth), fields: Cow::Borrowed(fields), withheld: Cow::Borrowed(withheld) }
+    }
 }
 
 #[async_trait::async_trait]
 pub trait AuditLogger: ReasonerConnectorAuditLogger {
+    #[allow(clippy::too_many_arguments)]
     async fn log_exec_task_request(
         &self,
         reference: &str,
         auth: &AuthContext,
         policy: i64,
+        policy_hashes: &[String],
         state: &State,
         workflow: &Workflow,
         task: &str,
@@ -287,6 +754,7 @@ pub trait AuditLogger: ReasonerConnectorAuditLogger {
         reference: &str,
         auth: &AuthContext,
         policy: i64,
+        policy_hashes: &[String],
         state: &State,
         workflow: &Workflow,
         data: &str,
@@ -298,16 +766,37 @@ pub trait AuditLogger: ReasonerConnectorAuditLogger {
         reference: &str,
         auth: &AuthContext,
         policy: i64,
+        policy_hashes: &[String],
         state: &State,
         workflow: &Workflow,
     ) -> Result<(), Error>;
 
+    /// Logs that a query has been received, i.e., a question about what holds under a policy instead of whether something is allowed.
+    async fn log_query_request(&self, reference: &str, auth: &AuthContext, policy: i64, state: &State, query: &Value) -> Result<(), Error>;
+
     async fn log_verdict(&self, reference: &str, verdict: &Verdict) -> Result<(), Error>;
+    /// Logs how long the phases of handling a question took (see [`RequestTimer`]).
+    async fn log_request_timings(&self, timings: &RequestTimings) -> Result<(), Error>;
+    /// Logs how much the workflow of a request grew by preprocessing it (e.g., by inlining its functions).
+    async fn log_workflow_preprocessed(&self, reference: &str, stats: &InlineStats) -> Result<(), Error>;
+
+    /// Logs that the reasoner could not be consulted for a request, and which fallback behaviour was used to answer it instead.
+    async fn log_reasoner_fallback(&self, reference: &str, behaviour: &str, reason: &str) -> Result<(), Error>;
+    /// Logs that the reasoner was not consulted for a request because its circuit breaker is open, and which fallback behaviour was used instead.
+    async fn log_circuit_open(&self, reference: &str, behaviour: &str, retry_after_ms: u64, reason: &str) -> Result<(), Error>;
+    /// Logs that a verdict transformer altered the answer of the reasoner for a request before it was returned.
+    async fn log_verdict_transformed(&self, reference: &str, transformer: &str, reason: &str) -> Result<(), Error>;
+    /// Logs that a rule of the prefilter answered a request, such that the reasoner was not asked.
+    async fn log_prefilter_matched(&self, reference: &str, rule: &str, effect: &str) -> Result<(), Error>;
+    /// Logs that a request is answered retrospectively, as of a moment in the past, and whether the state of that moment is known.
+    async fn log_retrospective_evaluation(&self, reference: &str, as_of: &str, state_snapshot: bool) -> Result<(), Error>;
 
     /// Dumps the full context of the reasoner on startup.
     ///
     /// Note that it's recommended to use `ReasonerConnector::FullContext` for this, to include the full base specification.
     async fn log_reasoner_context<C: ConnectorWithContext>(&self) -> Result<(), Error>;
+    /// Logs the effective context of the reasoner after it was reloaded with a configuration that changed it.
+    async fn log_effective_reasoner_context(&self, context: &Value, hash: &str) -> Result<(), Error>;
     /// Logs that a new policy has been added, including the full policy.
     ///
     /// Note that it's recommended to use `ReasonerConnector::Context` for this, as the full base spec as already been logged at startup.
@@ -316,21 +805,56 @@ pub trait AuditLogger: ReasonerConnectorAuditLogger {
     async fn log_set_active_version_policy(&self, auth: &AuthContext, policy: &Policy) -> Result<(), Error>;
 
     async fn log_deactivate_policy(&self, auth: &AuthContext) -> Result<(), Error>;
-}
 
-#[async_trait::async_trait]
-pub trait ReasonerConnectorAuditLogger {
-    async fn log_reasoner_response(&self, reference: &str, response: &str) -> Result<(), Error>;
-}
+    /// Logs that the complete policy store got replaced by an imported archive.
+    async fn log_import_policies(&self, auth: &AuthContext, archive: &PolicyArchive) -> Result<(), Error>;
+
+    /// Logs that the emergency lockdown was engaged or lifted, and by whom.
+    async fn log_lockdown(&self, auth: &AuthContext, engaged: bool, reason: Option<&str>) -> Result<(), Error>;
+
+    /// Logs that a request was refused because its credentials could not be authenticated, and why.
+    async fn log_auth_failure(&self, route: &str, reason: &str, detail: &str, initiator_hint: Option<&str>) -> Result<(), Error>;
+
+    /// Logs that the credentials of a request were authenticated, and as whom.
+    async fn log_auth_success(&self, auth: &AuthContext, route: &str) -> Result<(), Error>;
 
-pub struct SessionedConnectorAuditLogger<Logger: ReasonerConnectorAuditLogger> {
-    pub reference: String,
-    logger: Logger,
+    /// Logs that the owner of a dataset was notified of the verdict of a request, or why that failed.
+    async fn log_owner_notification(&self, reference: &str, dataset: &str, channel: &str, recipient: &str, error: Option<&str>) -> Result<(), Error>;
+
+    /// Logs the verdict of a partner checker that a request was forwarded to, or why it could not be obtained.
+    async fn log_federated_verdict(
+        &self,
+        reference: &str,
+        domain: &str,
+        remote_reference: Option<&str>,
+        allowed: bool,
+        error: Option<&str>,
+    ) -> Result<(), Error>;
+
+    /// Logs that (part of) the summary of the policy was disclosed to someone, and which fields were withheld.
+    async fn log_policy_disclosure(&self, auth: &AuthContext, fields: &[String], withheld: &[String]) -> Result<(), Error>;
+
+    /// Confirms that everything logged so far is durably persisted.
+    ///
+    /// A verdict may only be returned to the client after this succeeds for its request, since there would otherwise
+    /// be no guaranteed record of it. Loggers that only return from logging once a statement is persisted can
+    /// simply return `Ok(())`.
+    ///
+    /// # Arguments
+    /// - `reference`: The verdict reference of the request about to be answered.
+    ///
+    /// # Errors
+    /// This function errors if the logger cannot confirm the statements are persisted.
+    async fn flush(&self, reference: &str) -> Result<(), Error>;
 }
-impl<Logger: ReasonerConnectorAuditLogger> SessionedConnectorAuditLogger<Logger> {
-    pub fn new(reference: String, logger: Logger) -> Self { Self { reference, logger } }
 
-    pub async fn log_reasoner_response(&self, response: &str) -> Result<(), Error> {
-        self.logger.log_reasoner_response(&self.reference, response).await
-    }
+#[async_trait::async_trait]
+pub trait ReasonerConnectorAuditLogger {
+    async fn log_reasoner_response(&self, reference: &str, response: &str, retries: u32) -> Result<(), Error>;
+    /// Logs the timings of a session once it ended (see [`SessionedConnectorAuditLogger::end()`]).
+    async fn log_session_summary(&self, summary: &SessionSummary) -> Result<(), Error>;
+    /// Logs the answer of a shadow reasoner connector next to that of the connector whose answer counts.
+    async fn log_shadow_evaluation(&self, reference: &str, primary: &Value, shadow: &Value, agree: bool) -> Result<(), Error>;
+    /// Logs that a payload of a question was written to the [`PayloadStore`], linking it from the audit log.
+    async fn log_payload_archived(&self, payload: &ArchivedPayload) -> Result<(), Error>;
 }
@@ -0,0 +1,486 @@
+//! Implements Merkle-tree anchoring of audit log segments and inclusion proofs for individual entries.
+//!
+//! Periodically, the entries written to the audit log since the last anchor are collected in a segment, and the root of
+//! a Merkle tree over them is computed and recorded as an [`AuditAnchor`] (and, optionally, published to an external
+//! notary). Once a root is out of our hands, nobody can change, remove or reorder entries of its segment without it being
+//! noticed. An [`InclusionProof`] then shows that a particular entry is part of an anchored segment by giving the sibling
+//! hashes needed to recompute the root from that entry alone.
+//!
+//! Like in RFC 6962, leaves are hashed with a `0x00` prefix and inner nodes with a `0x01` prefix, such that a leaf can
+//! never be passed off as an inner node. A node without a sibling is promoted to the next level as-is.
+//!
+//! Once all entries of a segment have outlived their retention, the segment may be moved to cold storage as an
+//! [`ArchivedSegment`]. Every archived segment links to the one archived before it, and the anchors of the live log keep
+//! the anchor of the last one as their head, such that [`verify_continuity()`] can show that no segment went missing
+//! between the archive and the live log.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+/***** ERRORS *****/
+/// Defines how the archived and live segments of an audit log may fail to be continuous.
+#[derive(Debug, thiserror::Error)]
+pub enum ContinuityError {
+    /// A segment does not start where the one before it ended.
+    #[error("Expected a segment starting at entry {expected}, but found one starting at entry {first}")]
+    Gap { expected: usize, first: usize },
+    /// The entries of a segment do not match its root.
+    #[error("The {size} entries of the segment starting at entry {first} do not match its root")]
+    RootMismatch { first: usize, size: usize },
+    /// An archived segment does not link to the root of the one archived before it.
+    #[error("Archived segment starting at entry {first} does not link to the segment archived before it")]
+    BrokenLink { first: usize },
+    /// The head of the live anchors is not the last archived segment.
+    #[error("The head of the live anchors (the segment starting at entry {first}) is not the last archived segment")]
+    HeadMismatch { first: usize },
+}
+
+/***** CONSTANTS *****/
+/// The prefix hashed before a leaf.
+const LEAF_PREFIX: u8 = 0x00;
+/// The prefix hashed before an inner node.
+const NODE_PREFIX: u8 = 0x01;
+
+/***** HELPERS *****/
+/// Hashes two child nodes into their parent.
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/***** LIBRARY *****/
+/// A single SHA-256 hash in the tree.
+pub type Hash = [u8; 32];
+
+/// Hashes an audit log entry into a leaf of the tree.
+///
+/// # Arguments
+/// - `entry`: The entry as written in the log, without the trailing newline.
+///
+/// # Returns
+/// The hash of the leaf.
+pub fn leaf_hash(entry: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(entry);
+    hasher.finalize().into()
+}
+
+/// Extracts the verdict reference from an audit log entry, if it has one.
+///
+/// # Arguments
+/// - `entry`: The entry as written by the `FileLogger`, i.e., `[<identifier>][<timestamp>] <statement>`.
+///
+/// # Returns
+/// The reference in the statement, or [`None`] if the entry has none (or is not valid).
+pub fn entry_reference(entry: &str) -> Option<String> {
+    let start: usize = entry.find("] {")? + 2;
+    let stmt: serde_json::Value = serde_json::from_str(&entry[start..]).ok()?;
+    stmt.get("reference")?.as_str().map(String::from)
+}
+
+/// A Merkle tree over the entries of one audit log segment.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    /// All levels of the tree, starting with the leaves and ending with the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over the given leaves.
+    ///
+    /// # Arguments
+    /// - `leaves`: The hashes of the entries, in the order they appear in the log. See [`leaf_hash()`].
+    ///
+    /// # Returns
+    /// A new MerkleTree.
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        let mut levels: Vec<Vec<Hash>> = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next: Vec<Hash> = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// Returns the number of leaves in the tree.
+    #[inline]
+    pub fn len(&self) -> usize { self.levels[0].len() }
+
+    /// Returns whether the tree has no leaves.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.levels[0].is_empty() }
+
+    /// Returns the root of the tree, or the hash of nothing if the tree is empty.
+    pub fn root(&self) -> Hash {
+        match self.levels.last().unwrap().first() {
+            Some(root) => *root,
+            None => Sha256::digest([]).into(),
+        }
+    }
+
+    /// Computes the path from a leaf to the root.
+    ///
+    /// # Arguments
+    /// - `index`: The index of the leaf to prove.
+    ///
+    /// # Returns
+    /// The siblings needed to recompute the root from the leaf, bottom to top, or [`None`] if there is no such leaf.
+    pub fn path(&self, mut index: usize) -> Option<Vec<ProofStep>> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut path: Vec<ProofStep> = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling: usize = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                path.push(ProofStep { side: if sibling < index { Side::Left } else { Side::Right }, hash: hex::encode(hash) });
+            }
+            index /= 2;
+        }
+        Some(path)
+    }
+}
+
+/// Records the Merkle root of a segment of the audit log.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditAnchor {
+    /// The (zero-indexed) line of the first entry in the segment.
+    pub first: usize,
+    /// The number of entries in the segment.
+    pub size: usize,
+    /// The hex-encoded Merkle root over the entries in the segment.
+    pub root: String,
+    /// When the root was computed.
+    pub anchored_at: String,
+    /// The root originally computed (and possibly published) for the segment, if its entries have since been migrated to
+    /// a newer schema (see [`schema`](crate::schema)). It still proves the original entries, which are kept aside.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migrated_from: Option<String>,
+    /// Where the segment was archived to, if its entries were removed from the live log (see [`ArchivedSegment`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<String>,
+}
+
+impl AuditAnchor {
+    /// Returns whether the given line is part of this segment.
+    #[inline]
+    pub fn contains(&self, line: usize) -> bool { line >= self.first && line < self.first + self.size }
+
+    /// Returns the (zero-indexed) line right after the last entry in the segment.
+    #[inline]
+    pub fn end(&self) -> usize { self.first + self.size }
+}
+
+/// Returns the line of the audit log that the live log starts with, i.e., the line after the last archived segment.
+///
+/// # Arguments
+/// - `anchors`: The [`AuditAnchor`]s recorded for the live log.
+///
+/// # Returns
+/// The (zero-indexed) line of the first entry still in the live log, which is 0 if nothing was archived.
+pub fn live_offset(anchors: &[AuditAnchor]) -> usize {
+    anchors.iter().filter(|anchor| anchor.archived.is_some()).map(AuditAnchor::end).max().unwrap_or(0)
+}
+
+/// Refers to the segment archived before another one.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SegmentLink {
+    /// The hex-encoded Merkle root of the segment.
+    pub root:     String,
+    /// Where the segment was archived to.
+    pub location: String,
+}
+
+/// A segment of the audit log that was moved to cold storage, together with its anchor.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ArchivedSegment {
+    /// The anchor of the segment, as it was recorded when it was still live.
+    pub anchor:   AuditAnchor,
+    /// The segment archived before this one, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<SegmentLink>,
+    /// The entries in the segment, as written in the log.
+    pub entries:  Vec<String>,
+}
+
+impl ArchivedSegment {
+    /// Checks whether the entries of this segment match its anchor.
+    ///
+    /// # Returns
+    /// True if there are as many entries as anchored and their Merkle root is that of the anchor, or false otherwise.
+    pub fn verify(&self) -> bool {
+        self.entries.len() == self.anchor.size
+            && hex::encode(MerkleTree::new(self.entries.iter().map(|entry| leaf_hash(entry.as_bytes())).collect()).root()) == self.anchor.root
+    }
+}
+
+/// Checks that the archived segments of an audit log and its live segments together form an unbroken sequence.
+///
+/// # Arguments
+/// - `archived`: The [`ArchivedSegment`]s, in the order they were archived.
+/// - `anchors`: The [`AuditAnchor`]s recorded for the live log, headed by the anchor of the last archived segment.
+/// - `entries`: The lines of the live log, without trailing newlines.
+///
+/// # Returns
+/// The number of segments checked, archived and live together.
+///
+/// # Errors
+/// This function errors with the first [`ContinuityError`] found: a segment whose entries don't match its root, a gap
+/// between two segments, an archived segment that doesn't link to the one before it, or live anchors whose head is not
+/// the last archived segment.
+pub fn verify_continuity(archived: &[ArchivedSegment], anchors: &[AuditAnchor], entries: &[impl AsRef<str>]) -> Result<usize, ContinuityError> {
+    // Walk the archive first
+    let mut expected: usize = 0;
+    let mut previous: Option<&AuditAnchor> = None;
+    for segment in archived {
+        let anchor: &AuditAnchor = &segment.anchor;
+        if anchor.first != expected {
+            return Err(ContinuityError::Gap { expected, first: anchor.first });
+        }
+        if !segment.verify() {
+            return Err(ContinuityError::RootMismatch { first: anchor.first, size: anchor.size });
+        }
+        if segment.previous.as_ref().map(|link| &link.root) != previous.map(|previous| &previous.root) {
+            return Err(ContinuityError::BrokenLink { first: anchor.first });
+        }
+        expected = anchor.end();
+        previous = Some(anchor);
+    }
+
+    // The live anchors must pick up where the archive ends
+    let head: Option<&AuditAnchor> = anchors.iter().rfind(|anchor| anchor.archived.is_some());
+    match (head, previous) {
+        (None, None) => {},
+        (Some(head), Some(last)) if head.first == last.first && head.size == last.size && head.root == last.root => {},
+        (Some(head), _) => return Err(ContinuityError::HeadMismatch { first: head.first }),
+        (None, Some(last)) => return Err(ContinuityError::HeadMismatch { first: last.first }),
+    }
+    let offset: usize = expected;
+    for anchor in anchors.iter().filter(|anchor| anchor.archived.is_none()) {
+        if anchor.first != expected {
+            return Err(ContinuityError::Gap { expected, first: anchor.first });
+        }
+        let segment = entries.get(anchor.first - offset..anchor.end() - offset).unwrap_or_default();
+        let tree = MerkleTree::new(segment.iter().map(|entry| leaf_hash(entry.as_ref().as_bytes())).collect());
+        if segment.len() != anchor.size || hex::encode(tree.root()) != anchor.root {
+            return Err(ContinuityError::RootMismatch { first: anchor.first, size: anchor.size });
+        }
+        expected = anchor.end();
+    }
+    Ok(archived.len() + anchors.iter().filter(|anchor| anchor.archived.is_none()).count())
+}
+
+/// Which side of the path a sibling hash is on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    /// The sibling is hashed before the running hash.
+    Left,
+    /// The sibling is hashed after the running hash.
+    Right,
+}
+
+/// A single sibling on the path from a leaf to the root.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProofStep {
+    /// Which side the sibling is on.
+    pub side: Side,
+    /// The hex-encoded hash of the sibling.
+    pub hash: String,
+}
+
+/// Proves that an audit log entry is part of an anchored segment.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InclusionProof {
+    /// The verdict reference of the proven entry.
+    pub reference: String,
+    /// The (zero-indexed) line of the entry in the audit log.
+    pub line:      usize,
+    /// The entry itself, as written in the log.
+    pub entry:     String,
+    /// The siblings needed to recompute the root, bottom to top.
+    pub path:      Vec<ProofStep>,
+    /// The anchor of the segment the entry is in.
+    pub anchor:    AuditAnchor,
+}
+
+impl InclusionProof {
+    /// Produces inclusion proofs for all entries with a given verdict reference.
+    ///
+    /// # Arguments
+    /// - `entries`: The lines of the (live) audit log, without trailing newlines.
+    /// - `anchors`: The [`AuditAnchor`]s recorded for the log so far.
+    /// - `reference`: The verdict reference to prove the entries of.
+    ///
+    /// # Returns
+    /// One InclusionProof per anchored entry with the reference. Entries that are not yet anchored, or that were archived,
+    /// are skipped.
+    pub fn for_reference(entries: &[impl AsRef<str>], anchors: &[AuditAnchor], reference: &str) -> Vec<Self> {
+        let offset: usize = live_offset(anchors);
+        let mut proofs: Vec<Self> = Vec::new();
+        for anchor in anchors.iter().filter(|anchor| anchor.archived.is_none()) {
+            let Some(segment) = anchor.first.checked_sub(offset).and_then(|start| entries.get(start..start + anchor.size)) else { continue };
+            let lines: Vec<usize> = (0..segment.len()).filter(|i| entry_reference(segment[*i].as_ref()).as_deref() == Some(reference)).collect();
+            if lines.is_empty() {
+                continue;
+            }
+
+            let tree = MerkleTree::new(segment.iter().map(|entry| leaf_hash(entry.as_ref().as_bytes())).collect());
+            for i in lines {
+                proofs.push(Self {
+                    reference: reference.into(),
+                    line:      anchor.first + i,
+                    entry:     segment[i].as_ref().into(),
+                    path:      tree.path(i).unwrap(),
+                    anchor:    anchor.clone(),
+                });
+            }
+        }
+        proofs
+    }
+
+    /// Checks whether the entry, path and root of this proof agree.
+    ///
+    /// Note that this only shows the entry is part of the segment with the anchor's root; to be convincing, that root
+    /// should be compared to the one published to a notary.
+    ///
+    /// # Returns
+    /// True if recomputing the root from the entry and the path gives the anchor's root, or false otherwise.
+    pub fn verify(&self) -> bool {
+        let mut hash: Hash = leaf_hash(self.entry.as_bytes());
+        for step in &self.path {
+            let mut sibling: Hash = [0; 32];
+            if hex::decode_to_slice(&step.hash, &mut sibling).is_err() {
+                return false;
+            }
+            hash = match step.side {
+                Side::Left => node_hash(&sibling, &hash),
+                Side::Right => node_hash(&hash, &sibling),
+            };
+        }
+        hex::encode(hash) == self.anchor.root
+    }
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an audit log entry as written by the `FileLogger`.
+    fn entry(i: usize, reference: &str) -> String {
+        format!(r#"[policy-reasoner v1.0.0][2026-10-16 12:00:00] {{"kind":"REASONER-VERDICT","reference":"{reference}","i":{i}}}"#)
+    }
+
+    /// Anchors the given entries as a single segment starting at the first line.
+    fn anchor(entries: &[String]) -> AuditAnchor {
+        let tree = MerkleTree::new(entries.iter().map(|entry| leaf_hash(entry.as_bytes())).collect());
+        AuditAnchor {
+            first: 0,
+            size: entries.len(),
+            root: hex::encode(tree.root()),
+            anchored_at: "2026-10-16 12:00:00".into(),
+            migrated_from: None,
+            archived: None,
+        }
+    }
+
+    #[test]
+    fn test_tree_root() {
+        let leaves: Vec<Hash> = (0..3).map(|i| leaf_hash(entry(i, "a").as_bytes())).collect();
+        assert_eq!(MerkleTree::new(Vec::new()).root(), <Hash>::from(Sha256::digest([])));
+        assert_eq!(MerkleTree::new(leaves[..1].to_vec()).root(), leaves[0]);
+        assert_eq!(MerkleTree::new(leaves[..2].to_vec()).root(), node_hash(&leaves[0], &leaves[1]));
+        // The odd leaf out is promoted as-is
+        assert_eq!(MerkleTree::new(leaves.clone()).root(), node_hash(&node_hash(&leaves[0], &leaves[1]), &leaves[2]));
+
+        // Leaves and inner nodes are hashed differently
+        assert_ne!(leaf_hash(&[leaves[0], leaves[1]].concat()), node_hash(&leaves[0], &leaves[1]));
+    }
+
+    #[test]
+    fn test_proofs_verify() {
+        // Every leaf is proven, for trees of both even and odd sizes
+        for size in 1..=9 {
+            let entries: Vec<String> = (0..size).map(|i| entry(i, &format!("ref-{i}"))).collect();
+            let anchors: Vec<AuditAnchor> = vec![anchor(&entries)];
+            for i in 0..size {
+                let proofs: Vec<InclusionProof> = InclusionProof::for_reference(&entries, &anchors, &format!("ref-{i}"));
+                assert_eq!(proofs.len(), 1, "size {size}, leaf {i}");
+                assert_eq!(proofs[0].line, i);
+                assert!(proofs[0].verify(), "size {size}, leaf {i}");
+            }
+            assert!(MerkleTree::new(entries.iter().map(|entry| leaf_hash(entry.as_bytes())).collect()).path(size).is_none());
+        }
+    }
+
+    #[test]
+    fn test_proofs_for_reference() {
+        let entries: Vec<String> = ["a", "b", "a", "c", "a"].iter().enumerate().map(|(i, reference)| entry(i, reference)).collect();
+
+        // Only the entries of the reference are proven, and only once anchored
+        assert!(InclusionProof::for_reference(&entries, &[], "a").is_empty());
+        let anchors: Vec<AuditAnchor> = vec![anchor(&entries[..3]), AuditAnchor { first: 3, ..anchor(&entries[3..]) }];
+        let proofs: Vec<InclusionProof> = InclusionProof::for_reference(&entries, &anchors, "a");
+        assert_eq!(proofs.iter().map(|proof| proof.line).collect::<Vec<usize>>(), vec![0, 2, 4]);
+        assert!(proofs.iter().all(InclusionProof::verify));
+        assert_eq!(proofs[2].anchor, anchors[1]);
+        assert!(InclusionProof::for_reference(&entries, &anchors, "d").is_empty());
+    }
+
+    #[test]
+    fn test_proofs_tampered_leaf() {
+        for size in [1, 2, 5, 8] {
+            let entries: Vec<String> = (0..size).map(|i| entry(i, &format!("ref-{i}"))).collect();
+            let mut proof: InclusionProof = InclusionProof::for_reference(&entries, &[anchor(&entries)], "ref-0").remove(0);
+            proof.entry = proof.entry.replace("REASONER-VERDICT", "REASONER-RESPONSE");
+            assert!(!proof.verify(), "size {size}");
+        }
+
+        // Neither can the proof of one entry be passed off as that of another
+        let entries: Vec<String> = (0..4).map(|i| entry(i, &format!("ref-{i}"))).collect();
+        let mut proof: InclusionProof = InclusionProof::for_reference(&entries, &[anchor(&entries)], "ref-0").remove(0);
+        proof.entry = entries[1].clone();
+        assert!(!proof.verify());
+    }
+
+    #[test]
+    fn test_proofs_wrong_root() {
+        let entries: Vec<String> = (0..5).map(|i| entry(i, &format!("ref-{i}"))).collect();
+        let proof: InclusionProof = InclusionProof::for_reference(&entries, &[anchor(&entries)], "ref-2").remove(0);
+        assert!(proof.verify());
+
+        // The root of another segment
+        let mut wrong: InclusionProof = proof.clone();
+        wrong.anchor.root = anchor(&entries[..4]).root;
+        assert!(!wrong.verify());
+
+        // A sibling that is changed, missing, on the wrong side or no hash at all
+        let mut wrong: InclusionProof = proof.clone();
+        wrong.path[0].hash = hex::encode(leaf_hash(b"forged"));
+        assert!(!wrong.verify());
+        let mut wrong: InclusionProof = proof.clone();
+        wrong.path.pop();
+        assert!(!wrong.verify());
+        let mut wrong: InclusionProof = proof.clone();
+        wrong.path[0].side = if wrong.path[0].side == Side::Left { Side::Right } else { Side::Left };
+        assert!(!wrong.verify());
+        let mut wrong: InclusionProof = proof;
+        wrong.path[0].hash = "not hex".into();
+        assert!(!wrong.verify());
+    }
+}
@@ -0,0 +1,176 @@
+//! Implements archiving the full payloads of questions, for reproducing contested verdicts.
+//!
+//! The audit log records what was asked and answered, but (to keep it small) not always byte-for-byte: workflows are
+//! logged after preprocessing, and raw responses of the reasoner may be redacted. If configured with a [`PayloadStore`],
+//! the body of every deliberation request and every raw request and response exchanged with the reasoner backend are
+//! also written to a directory, one subdirectory per reference (nested for sub-sessions, e.g., `<reference>/retry-1`).
+//!
+//! Every payload written is linked from the audit log with a `PAYLOAD-ARCHIVED` statement describing an
+//! [`ArchivedPayload`]. Payloads larger than the store's maximum size are truncated, but the SHA-256 digest in the
+//! statement is always that of the full payload. Archives of references older than the retention period are removed by
+//! [`PayloadStore::prune()`], which the server runs periodically.
+
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tokio::fs;
+
+/***** HELPERS *****/
+/// Turns a reference into a relative path in the store, one directory per segment of the reference.
+///
+/// Characters other than alphanumerics, `-`, `_` and `.` are replaced by `_`, and so are segments that would escape the
+/// store (`.` and `..`).
+fn reference_path(reference: &str) -> PathBuf {
+    reference
+        .split('/')
+        .map(|segment| match segment {
+            "" | "." | ".." => "_".to_string(),
+            segment => segment.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' }).collect(),
+        })
+        .collect()
+}
+
+/***** AUXILLARY *****/
+/// What an archived payload is.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PayloadKind {
+    /// The body of the request sent to the policy reasoner.
+    Request,
+    /// A raw request sent by the reasoner connector to its backend.
+    ConnectorRequest,
+    /// A raw response of the reasoner backend to the reasoner connector.
+    ConnectorResponse,
+}
+impl PayloadKind {
+    /// Returns the name under which payloads of this kind are stored.
+    #[inline]
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Request => "request",
+            Self::ConnectorRequest => "connector-request",
+            Self::ConnectorResponse => "connector-response",
+        }
+    }
+}
+
+/// Describes a payload written to a [`PayloadStore`], as logged in a `PAYLOAD-ARCHIVED` statement.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ArchivedPayload {
+    /// The reference of the question (or session) the payload belongs to.
+    pub reference: String,
+    /// What the payload is.
+    pub kind:      PayloadKind,
+    /// Where the payload is stored, relative to the directory of the store.
+    pub location:  String,
+    /// The size of the full payload, in bytes.
+    pub size:      u64,
+    /// Whether only the first part of the payload is stored, because it was larger than the maximum size.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// The hex-encoded SHA-256 digest of the full payload.
+    pub sha256:    String,
+}
+
+/***** LIBRARY *****/
+/// Writes the payloads of questions to a directory, bounded in size and age.
+#[derive(Clone, Debug)]
+pub struct PayloadStore {
+    /// The directory to write the payloads to.
+    dir: PathBuf,
+    /// The maximum number of bytes stored of a single payload.
+    max_size: usize,
+    /// How long the payloads of a reference are kept, or [`None`] to keep them forever.
+    retention: Option<Duration>,
+}
+impl PayloadStore {
+    /// Constructor for the PayloadStore.
+    ///
+    /// # Arguments
+    /// - `dir`: The directory to write the payloads to. It is created if it does not exist yet.
+    /// - `max_size`: The maximum number of bytes stored of a single payload. Larger payloads are truncated.
+    /// - `retention`: How long the payloads of a reference are kept, or [`None`] to keep them forever.
+    ///
+    /// # Returns
+    /// A new PayloadStore.
+    #[inline]
+    pub fn new(dir: impl Into<PathBuf>, max_size: usize, retention: Option<Duration>) -> Self { Self { dir: dir.into(), max_size, retention } }
+
+    /// Returns the directory the payloads are written to.
+    #[inline]
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    /// Returns how long the payloads of a reference are kept, or [`None`] if they are kept forever.
+    #[inline]
+    pub fn retention(&self) -> Option<Duration> { self.retention }
+
+    /// Writes a payload to the store.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference of the question (or session) the payload belongs to.
+    /// - `kind`: What the payload is.
+    /// - `index`: Which of the payloads of this kind it is, for kinds that occur multiple times per reference.
+    /// - `payload`: The payload itself.
+    ///
+    /// # Returns
+    /// The [`ArchivedPayload`] that describes where (and how much of) the payload was stored.
+    ///
+    /// # Errors
+    /// This function errors if the payload could not be written.
+    pub async fn store(&self, reference: &str, kind: PayloadKind, index: Option<u32>, payload: &[u8]) -> Result<ArchivedPayload, IoError> {
+        let name: String = match index {
+            Some(index) => format!("{}-{index}.json", kind.name()),
+            None => format!("{}.json", kind.name()),
+        };
+        let location: PathBuf = reference_path(reference).join(name);
+        let path: PathBuf = self.dir.join(&location);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let stored: usize = payload.len().min(self.max_size);
+        fs::write(&path, &payload[..stored]).await?;
+
+        Ok(ArchivedPayload {
+            reference: reference.into(),
+            kind,
+            location: location.to_string_lossy().into_owned(),
+            size: payload.len() as u64,
+            truncated: stored < payload.len(),
+            sha256: hex::encode(Sha256::digest(payload)),
+        })
+    }
+
+    /// Removes the payloads of every reference that were last written to longer ago than the retention period.
+    ///
+    /// # Returns
+    /// The number of references whose payloads were removed.
+    ///
+    /// # Errors
+    /// This function errors if the store could not be read or a reference could not be removed.
+    pub async fn prune(&self) -> Result<usize, IoError> {
+        let Some(retention) = self.retention else { return Ok(0) };
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+
+        let now: SystemTime = SystemTime::now();
+        let mut removed: usize = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if now.duration_since(metadata.modified()?).map(|age| age > retention).unwrap_or(false) {
+                if metadata.is_dir() {
+                    fs::remove_dir_all(entry.path()).await?;
+                } else {
+                    fs::remove_file(entry.path()).await?;
+                }
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
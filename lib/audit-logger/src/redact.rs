@@ -0,0 +1,209 @@
+//! Implements field-level redaction of [`LogStatement`]s before they are written to an audit log.
+//!
+//! Audit logs are regularly shared with (external) auditors, who need to see _what_ happened but not necessarily _who_
+//! it happened to. The [`Redactor`] replaces user names with HMAC-based pseudonyms and can drop raw payloads (workflows
+//! and reasoner responses) altogether. Because the pseudonyms are keyed, they are stable across the log (so requests by
+//! the same user can still be correlated), but can only be linked back to actual users by whoever holds the key, which
+//! is stored separately from the log.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use auth_resolver::AuthContext;
+use hmac::{Hmac, Mac as _};
+use policy::Policy;
+use sha2::Sha256;
+use state_resolver::State;
+use workflow::{Elem, User, Workflow};
+
+use crate::LogStatement;
+
+/***** CONSTANTS *****/
+/// The prefix of every pseudonym, such that they are recognizable as such.
+pub const PSEUDONYM_PREFIX: &'static str = "pseudonym-";
+
+/// What raw payloads are replaced with when dropped.
+pub const REDACTED: &'static str = "<redacted>";
+
+/***** LIBRARY *****/
+/// Redacts personal data from [`LogStatement`]s.
+///
+/// The default Redactor leaves statements untouched.
+#[derive(Clone, Debug, Default)]
+pub struct Redactor {
+    /// The key used to compute pseudonyms, or [`None`] to leave names as-is.
+    key: Option<Vec<u8>>,
+    /// Whether to drop raw workflows and reasoner responses.
+    drop_payloads: bool,
+}
+
+impl Redactor {
+    /// Constructor for the Redactor.
+    ///
+    /// # Arguments
+    /// - `key`: If given, replaces user names with pseudonyms keyed with this key.
+    /// - `drop_payloads`: Whether to drop raw workflows (keeping only their identifiers) and reasoner responses.
+    ///
+    /// # Returns
+    /// A new Redactor.
+    #[inline]
+    pub fn new(key: Option<Vec<u8>>, drop_payloads: bool) -> Self { Self { key, drop_payloads } }
+
+    /// Computes the pseudonym of a name.
+    ///
+    /// # Arguments
+    /// - `name`: The name to pseudonymize.
+    ///
+    /// # Returns
+    /// The pseudonym of the name, or the name itself if this Redactor has no key.
+    pub fn pseudonym(&self, name: &str) -> String {
+        match &self.key {
+            Some(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+                mac.update(name.as_bytes());
+                format!("{PSEUDONYM_PREFIX}{}", hex::encode(&mac.finalize().into_bytes()[..16]))
+            },
+            None => name.into(),
+        }
+    }
+
+    /// Redacts an [`AuthContext`].
+    fn auth<'a>(&self, auth: Cow<'a, AuthContext>) -> Cow<'a, AuthContext> {
+        if self.key.is_none() {
+            return auth;
+        }
+        Cow::Owned(AuthContext {
+            initiator: self.pseudonym(&auth.initiator),
+            system:    auth.system.clone(),
+            actor:     auth.actor.as_deref().map(|actor| self.pseudonym(actor)),
+        })
+    }
+
+    /// Redacts a [`State`].
+    fn state<'a>(&self, state: Cow<'a, State>) -> Cow<'a, State> {
+        if self.key.is_none() {
+            return state;
+        }
+        let mut state: State = state.into_owned();
+        for user in &mut state.users {
+            user.name = self.pseudonym(&user.name);
+        }
+        for owner in &mut state.owners {
+            owner.name = owner.name.as_deref().map(|name| self.pseudonym(name));
+            owner.email = owner.email.as_deref().map(|email| self.pseudonym(email));
+        }
+        if let Some(delegation) = &mut state.delegation {
+            delegation.subject = self.pseudonym(&delegation.subject);
+            delegation.actor = self.pseudonym(&delegation.actor);
+        }
+        Cow::Owned(state)
+    }
+
+    /// Redacts a [`Workflow`].
+    ///
+    /// Note that statements keep the content hash of the original workflow, such that whoever has it can still verify it.
+    fn workflow<'a>(&self, workflow: Cow<'a, Workflow>) -> Cow<'a, Workflow> {
+        if self.drop_payloads {
+            // Only keep what is needed to correlate the statement with others
+            Cow::Owned(Workflow {
+                id: workflow.id.clone(),
+                start: Elem::Stop(HashSet::new()),
+                user: User { name: self.pseudonym(&workflow.user.name) },
+                metadata: Vec::new(),
+                signature: REDACTED.into(),
+            })
+        } else if self.key.is_some() {
+            let mut workflow: Workflow = workflow.into_owned();
+            workflow.user.name = self.pseudonym(&workflow.user.name);
+            Cow::Owned(workflow)
+        } else {
+            workflow
+        }
+    }
+
+    /// Redacts a [`Policy`].
+    fn policy<'a>(&self, policy: Cow<'a, Policy>) -> Cow<'a, Policy> {
+        if self.key.is_none() || policy.version.creator.is_none() {
+            return policy;
+        }
+        let mut policy: Policy = policy.into_owned();
+        policy.version.creator = policy.version.creator.map(|creator| self.pseudonym(&creator));
+        Cow::Owned(policy)
+    }
+
+    /// Redacts a [`LogStatement`].
+    ///
+    /// # Arguments
+    /// - `stmt`: The [`LogStatement`] to redact.
+    ///
+    /// # Returns
+    /// The statement with all personal data pseudonymized and, if configured, raw payloads dropped.
+    pub fn redact<'a>(&self, stmt: LogStatement<'a>) -> LogStatement<'a> {
+        match stmt {
+            LogStatement::ExecuteTask { reference, auth, policy, policy_hashes, state, workflow, workflow_hash, warnings, task } => {
+                LogStatement::ExecuteTask {
+                    reference,
+                    auth: self.auth(auth),
+                    policy,
+                    policy_hashes,
+                    state: self.state(state),
+                    workflow: self.workflow(workflow),
+                    workflow_hash,
+                    warnings,
+                    task,
+                }
+            },
+            LogStatement::AssetAccess { reference, auth, policy, policy_hashes, state, workflow, workflow_hash, warnings, data, task } => {
+                LogStatement::AssetAccess {
+                    reference,
+                    auth: self.auth(auth),
+                    policy,
+                    policy_hashes,
+                    state: self.state(state),
+                    workflow: self.workflow(workflow),
+                    workflow_hash,
+                    warnings,
+                    data,
+                    task,
+                }
+            },
+            LogStatement::WorkflowValidate { reference, auth, policy, policy_hashes, state, workflow, workflow_hash, warnings } => {
+                LogStatement::WorkflowValidate {
+                    reference,
+                    auth: self.auth(auth),
+                    policy,
+                    policy_hashes,
+                    state: self.state(state),
+                    workflow: self.workflow(workflow),
+                    workflow_hash,
+                    warnings,
+                }
+            },
+            LogStatement::Query { reference, auth, policy, state, query } => {
+                LogStatement::Query { reference, auth: self.auth(auth), policy, state: self.state(state), query }
+            },
+
+            LogStatement::ReasonerResponse { reference, retries, .. } if self.drop_payloads => {
+                LogStatement::ReasonerResponse { reference, response: Cow::Borrowed(REDACTED), retries }
+            },
+
+            LogStatement::PolicyAdd { auth, connector_context_hash, policy } => {
+                LogStatement::PolicyAdd { auth: self.auth(auth), connector_context_hash, policy: self.policy(policy) }
+            },
+            LogStatement::PolicyActivate { auth, policy } => LogStatement::PolicyActivate { auth: self.auth(auth), policy: self.policy(policy) },
+            LogStatement::PolicyDeactivate { auth } => LogStatement::PolicyDeactivate { auth: self.auth(auth) },
+            LogStatement::PolicyImport { auth, versions, active } => LogStatement::PolicyImport { auth: self.auth(auth), versions, active },
+            LogStatement::Lockdown { auth, engaged, reason } => LogStatement::Lockdown { auth: self.auth(auth), engaged, reason },
+            LogStatement::AuthFailure { route, reason, detail, initiator_hint } if self.key.is_some() => {
+                LogStatement::AuthFailure { route, reason, detail, initiator_hint: initiator_hint.map(|hint| Cow::Owned(self.pseudonym(&hint))) }
+            },
+            LogStatement::AuthSuccess { auth, route } => LogStatement::AuthSuccess { auth: self.auth(auth), route },
+            LogStatement::PolicyDisclosure { auth, fields, withheld } => LogStatement::PolicyDisclosure { auth: self.auth(auth), fields, withheld },
+            LogStatement::OwnerNotification { reference, dataset, channel, recipient, error } if self.key.is_some() && channel == "email" => {
+                LogStatement::OwnerNotification { reference, dataset, recipient: Cow::Owned(self.pseudonym(&recipient)), channel, error }
+            },
+
+            stmt => stmt,
+        }
+    }
+}
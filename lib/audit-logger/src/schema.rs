@@ -0,0 +1,165 @@
+//! Implements versioning of the format in which [`LogStatement`]s are written.
+//!
+//! Every statement is written with a [`SCHEMA_FIELD`] telling which version of the format it follows, such that readers
+//! (e.g., `checker-client log`) keep understanding logs written by older reasoners. Statements written before the field
+//! existed are of version `0`. [`parse()`] reads statements of any known version by first [`upgrade()`]ing them to the
+//! current [`SCHEMA_VERSION`], one version at a time; to add a version, bump it and append its upgrade to [`UPGRADES`].
+//!
+//! The versions are:
+//! - `0`: Statements without a [`SCHEMA_FIELD`]. Requests may lack the `workflow_hash` of their workflow.
+//! - `1`: Adds the [`SCHEMA_FIELD`]. Requests always have a `workflow_hash`.
+
+use serde::{Deserialize as _, Serialize};
+use serde_json::{Map, Value};
+use workflow::Workflow;
+use workflow::canonical::content_hash;
+
+use crate::LogStatement;
+
+/***** CONSTANTS *****/
+/// The version of the format that statements are written in.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// The field of a serialized statement that holds the version of its format.
+pub const SCHEMA_FIELD: &str = "schema";
+
+/// Upgrades a serialized statement from a version to the next. The `i`th upgrade takes a statement from version `i` to `i + 1`.
+const UPGRADES: [fn(&mut Map<String, Value>); SCHEMA_VERSION as usize] = [upgrade_v0];
+
+/***** ERRORS *****/
+/// Defines errors that originate from reading versioned statements.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The statement is not valid JSON.
+    #[error("Statement is not valid JSON")]
+    Json(#[source] serde_json::Error),
+    /// The statement is not a JSON object.
+    #[error("Statement is not a JSON object")]
+    NotAnObject,
+    /// The version of the statement is not a number.
+    #[error("Statement has illegal schema version '{raw}'")]
+    IllegalVersion { raw: Value },
+    /// The statement was written by a newer reasoner than we know of.
+    #[error("Statement has schema version {version}, but only versions up to {SCHEMA_VERSION} are supported")]
+    Unsupported { version: u64 },
+    /// The (upgraded) statement is not a valid statement.
+    #[error("Statement is not a valid schema version {SCHEMA_VERSION} statement")]
+    Statement(#[source] serde_json::Error),
+}
+
+/***** UPGRADES *****/
+/// Upgrades a statement from version `0` to `1`, computing the `workflow_hash` of requests that lack one.
+///
+/// # Arguments
+/// - `stmt`: The statement to upgrade. Left as-is if its workflow cannot be read; it will then fail to parse anyway.
+fn upgrade_v0(stmt: &mut Map<String, Value>) {
+    if !matches!(stmt.get("kind").and_then(Value::as_str), Some("EXECUTE-TASK" | "ASSET-ACCESS" | "WORKFLOW-VALIDATE")) {
+        return;
+    }
+    if stmt.get("workflow_hash").and_then(Value::as_str).is_some_and(|hash| !hash.is_empty()) {
+        return;
+    }
+    if let Some(Ok(workflow)) = stmt.get("workflow").map(Workflow::deserialize) {
+        stmt.insert("workflow_hash".into(), Value::String(content_hash(&workflow)));
+    }
+}
+
+/***** LIBRARY *****/
+/// Serializes a [`LogStatement`] with the current [`SCHEMA_VERSION`] (see [`LogStatement::versioned()`]).
+#[derive(Serialize)]
+pub struct Versioned<'s, 'a> {
+    /// The statement to serialize.
+    #[serde(flatten)]
+    statement: &'s LogStatement<'a>,
+    /// The version of its format.
+    schema: u64,
+    /// The unique identifier of this statement, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+}
+impl<'s, 'a> Versioned<'s, 'a> {
+    /// Gives the statement a unique identifier, such that it can be referred to across (forwarded copies of) logs.
+    ///
+    /// # Arguments
+    /// - `id`: The identifier (e.g., generated by a [`deliberation::ids::IdGenerator`]).
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+}
+
+impl<'a> LogStatement<'a> {
+    /// Returns a wrapper that serializes this statement together with the current [`SCHEMA_VERSION`].
+    ///
+    /// This is how statements should be written to any audit log, such that [`parse()`] can read them back.
+    #[inline]
+    pub fn versioned(&self) -> Versioned<'_, 'a> { Versioned { statement: self, schema: SCHEMA_VERSION, id: None } }
+}
+
+/// Returns the version of the format of a serialized statement.
+///
+/// # Arguments
+/// - `stmt`: The statement, as a JSON object.
+///
+/// # Errors
+/// This function errors if the statement has a version, but it is not a (non-negative) number.
+pub fn version_of(stmt: &Map<String, Value>) -> Result<u64, Error> {
+    match stmt.get(SCHEMA_FIELD) {
+        Some(raw) => raw.as_u64().ok_or_else(|| Error::IllegalVersion { raw: raw.clone() }),
+        None => Ok(0),
+    }
+}
+
+/// Upgrades a serialized statement to the current [`SCHEMA_VERSION`] in-place.
+///
+/// # Arguments
+/// - `stmt`: The statement to upgrade.
+///
+/// # Returns
+/// The version the statement was in before upgrading it.
+///
+/// # Errors
+/// This function errors if the statement is not an object, or if its version is illegal or newer than we know of.
+pub fn upgrade(stmt: &mut Value) -> Result<u64, Error> {
+    let Value::Object(stmt) = stmt else { return Err(Error::NotAnObject) };
+    let version: u64 = version_of(stmt)?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::Unsupported { version });
+    }
+    for upgrade in &UPGRADES[version as usize..] {
+        upgrade(stmt);
+    }
+    stmt.insert(SCHEMA_FIELD.into(), Value::from(SCHEMA_VERSION));
+    Ok(version)
+}
+
+/// Reads a serialized statement of any known version.
+///
+/// # Arguments
+/// - `stmt`: The statement as a JSON value.
+///
+/// # Returns
+/// The [`LogStatement`], upgraded to the current [`SCHEMA_VERSION`].
+///
+/// # Errors
+/// This function errors if the statement cannot be [`upgrade()`]d or is not a valid statement afterwards.
+pub fn from_value(mut stmt: Value) -> Result<LogStatement<'static>, Error> {
+    upgrade(&mut stmt)?;
+    serde_json::from_value(stmt).map_err(Error::Statement)
+}
+
+/// Reads a serialized statement of any known version.
+///
+/// # Arguments
+/// - `raw`: The statement as JSON text (e.g., the part of a line in the audit log file after its timestamp).
+///
+/// # Returns
+/// The [`LogStatement`], upgraded to the current [`SCHEMA_VERSION`].
+///
+/// # Errors
+/// This function errors if `raw` is not JSON, or see [`from_value()`].
+pub fn parse(raw: &str) -> Result<LogStatement<'static>, Error> { from_value(serde_json::from_str(raw).map_err(Error::Json)?) }
@@ -0,0 +1,257 @@
+//! Implements the session in which a reasoner connector answers a single question.
+//!
+//! A [`SessionedConnectorAuditLogger`] is handed to the connector for every question. Besides telling the connector
+//! under which reference to log, it keeps track of how long the question took and how much of that was spent waiting
+//! on the reasoner. Questions that are asked again (e.g., a retry) or that are made up of smaller questions can get
+//! their own sub-session, whose reference is nested in that of the question (e.g., `<reference>/retry-1`).
+//!
+//! When a session is ended with [`SessionedConnectorAuditLogger::end()`], a `SESSION-SUMMARY` statement with its
+//! timings is logged.
+//!
+//! If the session is given a [`PayloadStore`], the connector can also archive the raw requests and responses it
+//! exchanges with its backend with [`SessionedConnectorAuditLogger::archive_exchange()`].
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::payloads::{ArchivedPayload, PayloadKind, PayloadStore};
+use crate::{Error, ReasonerConnectorAuditLogger, is_zero};
+
+/***** HELPERS *****/
+/// Returns a duration in milliseconds, saturating if it doesn't fit.
+#[inline]
+pub(crate) fn millis(duration: Duration) -> u64 { u64::try_from(duration.as_millis()).unwrap_or(u64::MAX) }
+
+/***** AUXILLARY *****/
+/// Summarizes a session once it ended, as logged in a `SESSION-SUMMARY` statement.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionSummary {
+    /// The reference of the session.
+    pub reference: String,
+    /// The reference of the session this is a sub-session of, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// When the session started.
+    pub started_at: DateTime<Utc>,
+    /// When the session ended.
+    pub ended_at: DateTime<Utc>,
+    /// How long the session took, in milliseconds.
+    pub duration_ms: u64,
+    /// How long the session spent waiting on the reasoner, in milliseconds. Omitted if the connector did not tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoner_latency_ms: Option<u64>,
+    /// The number of responses of the reasoner logged in the session.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub responses: u32,
+    /// The number of times the reasoner had to be asked again after failing transiently.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    /// The number of sub-sessions started in the session.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub sub_sessions: u32,
+}
+
+/// Counts what happened in a session.
+#[derive(Clone, Copy, Debug, Default)]
+struct SessionCounts {
+    /// The number of responses of the reasoner logged.
+    responses:    u32,
+    /// The number of times the reasoner had to be asked again.
+    retries:      u32,
+    /// The number of sub-sessions started.
+    sub_sessions: u32,
+    /// The number of exchanges with the reasoner archived.
+    exchanges:    u32,
+}
+
+/// The timings of a session, shared between its clones.
+#[derive(Debug)]
+struct SessionState {
+    /// When the session started.
+    started_at: DateTime<Utc>,
+    /// When the session started, for measuring how long it took.
+    started: Instant,
+    /// The time spent waiting on the reasoner, if the connector measured any.
+    reasoner_time: Mutex<Option<Duration>>,
+    /// What happened in the session.
+    counts: Mutex<SessionCounts>,
+    /// Whether the summary has been logged already.
+    ended: AtomicBool,
+}
+impl SessionState {
+    /// Starts a new session now.
+    #[inline]
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            started: Instant::now(),
+            reasoner_time: Mutex::new(None),
+            counts: Mutex::new(SessionCounts::default()),
+            ended: AtomicBool::new(false),
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// The audit logger given to a reasoner connector to log the answer to a single question with.
+///
+/// Clones share the same session, such that the one who started it can end it after handing a clone to the connector.
+pub struct SessionedConnectorAuditLogger<Logger: ReasonerConnectorAuditLogger> {
+    /// The reference under which the question is logged.
+    pub reference: String,
+    /// The reference of the session this is a sub-session of, if any.
+    parent: Option<String>,
+    logger: Logger,
+    /// Where to archive the exchanges with the reasoner, if anywhere.
+    payloads: Option<Arc<PayloadStore>>,
+    /// The timings of the session.
+    state: Arc<SessionState>,
+}
+impl<Logger: ReasonerConnectorAuditLogger + Clone> Clone for SessionedConnectorAuditLogger<Logger> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            reference: self.reference.clone(),
+            parent:    self.parent.clone(),
+            logger:    self.logger.clone(),
+            payloads:  self.payloads.clone(),
+            state:     self.state.clone(),
+        }
+    }
+}
+impl<Logger: ReasonerConnectorAuditLogger> SessionedConnectorAuditLogger<Logger> {
+    pub fn new(reference: String, logger: Logger) -> Self {
+        Self { reference, parent: None, logger, payloads: None, state: Arc::new(SessionState::new()) }
+    }
+
+    /// Sets where to archive the exchanges with the reasoner (see [`SessionedConnectorAuditLogger::archive_exchange()`]).
+    ///
+    /// # Arguments
+    /// - `payloads`: The [`PayloadStore`] to archive them in, or [`None`] to not archive them. Inherited by sub-sessions.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_payload_store(mut self, payloads: Option<Arc<PayloadStore>>) -> Self {
+        self.payloads = payloads;
+        self
+    }
+
+    /// Returns the reference of the session this is a sub-session of, if any.
+    #[inline]
+    pub fn parent(&self) -> Option<&str> { self.parent.as_deref() }
+
+    /// Starts a sub-session, e.g., for a retry or a sub-question.
+    ///
+    /// # Arguments
+    /// - `label`: The label that makes the sub-session's reference unique within this session (e.g., `retry-1`).
+    ///
+    /// # Returns
+    /// A new session with reference `<reference>/<label>`. It must be ended on its own.
+    pub fn sub_session(&self, label: &str) -> Self
+    where
+        Logger: Clone,
+    {
+        self.state.counts.lock().unwrap().sub_sessions += 1;
+        Self {
+            reference: format!("{}/{label}", self.reference),
+            parent:    Some(self.reference.clone()),
+            logger:    self.logger.clone(),
+            payloads:  self.payloads.clone(),
+            state:     Arc::new(SessionState::new()),
+        }
+    }
+
+    /// Runs the given future as a call to the reasoner, and adds the time it took to the reasoner latency of this session.
+    ///
+    /// # Arguments
+    /// - `call`: The future that asks the reasoner.
+    ///
+    /// # Returns
+    /// Whatever `call` returns.
+    pub async fn time_reasoner<F: Future>(&self, call: F) -> F::Output {
+        let start: Instant = Instant::now();
+        let res: F::Output = call.await;
+        let mut reasoner_time = self.state.reasoner_time.lock().unwrap();
+        *reasoner_time = Some(reasoner_time.unwrap_or_default() + start.elapsed());
+        res
+    }
+
+    pub async fn log_reasoner_response(&self, response: &str) -> Result<(), Error> { self.log_reasoner_response_after_retries(response, 0).await }
+
+    /// Logs the raw response of a reasoner that had to be asked again after failing transiently.
+    pub async fn log_reasoner_response_after_retries(&self, response: &str, retries: u32) -> Result<(), Error> {
+        {
+            let mut counts = self.state.counts.lock().unwrap();
+            counts.responses += 1;
+            counts.retries += retries;
+        }
+        self.logger.log_reasoner_response(&self.reference, response, retries).await
+    }
+
+    /// Archives a raw request sent to the reasoner and its raw response, if the session has a [`PayloadStore`].
+    ///
+    /// Both are logged as a `PAYLOAD-ARCHIVED` statement, numbered by how many exchanges the session archived before.
+    ///
+    /// # Arguments
+    /// - `request`: The raw request, as sent to the reasoner.
+    /// - `response`: The raw response, as received from the reasoner.
+    ///
+    /// # Errors
+    /// This function errors with [`Error::CouldNotArchive`] if either could not be written (which connectors may want to
+    /// tolerate), or with any other error if they could not be logged.
+    pub async fn archive_exchange(&self, request: &str, response: &str) -> Result<(), Error> {
+        let Some(payloads) = &self.payloads else { return Ok(()) };
+        let index: u32 = {
+            let mut counts = self.state.counts.lock().unwrap();
+            counts.exchanges += 1;
+            counts.exchanges
+        };
+        for (kind, payload) in [(PayloadKind::ConnectorRequest, request), (PayloadKind::ConnectorResponse, response)] {
+            let archived: ArchivedPayload =
+                payloads.store(&self.reference, kind, Some(index), payload.as_bytes()).await.map_err(Error::CouldNotArchive)?;
+            self.logger.log_payload_archived(&archived).await?;
+        }
+        Ok(())
+    }
+
+    /// Logs the answer of a shadow reasoner connector to the question of this session, next to that of the connector
+    /// whose answer counts.
+    pub async fn log_shadow_evaluation(&self, primary: &serde_json::Value, shadow: &serde_json::Value, agree: bool) -> Result<(), Error> {
+        self.logger.log_shadow_evaluation(&self.reference, primary, shadow, agree).await
+    }
+
+    /// Returns the summary of this session as if it ended now.
+    pub fn summary(&self) -> SessionSummary {
+        let counts: SessionCounts = *self.state.counts.lock().unwrap();
+        SessionSummary {
+            reference: self.reference.clone(),
+            parent: self.parent.clone(),
+            started_at: self.state.started_at,
+            ended_at: Utc::now(),
+            duration_ms: millis(self.state.started.elapsed()),
+            reasoner_latency_ms: self.state.reasoner_time.lock().unwrap().map(millis),
+            responses: counts.responses,
+            retries: counts.retries,
+            sub_sessions: counts.sub_sessions,
+        }
+    }
+
+    /// Ends the session, logging its [`SessionSummary`].
+    ///
+    /// The summary is only logged once, even if multiple clones of the session are ended.
+    ///
+    /// # Errors
+    /// This function errors if the summary could not be logged.
+    pub async fn end(self) -> Result<(), Error> {
+        if self.state.ended.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.logger.log_session_summary(&self.summary()).await
+    }
+}
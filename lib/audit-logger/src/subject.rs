@@ -0,0 +1,247 @@
+//! Extracts everything the audit log knows about a single data subject (i.e., a user or a dataset).
+//!
+//! Data protection officers regularly have to answer questions like "which requests involved this person's data, and
+//! on what grounds were they allowed?". This module scans a sequence of [`LogStatement`]s for all deliberation events
+//! that touched a given identifier, pairs them with the verdicts (and fallbacks) they got and the policy versions that
+//! decided them, and collects the result in a chronological [`DataSubjectReport`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use deliberation::spec::Verdict;
+use serde::{Deserialize, Serialize};
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+use workflow::{ElemCommit, ElemTask, Workflow};
+
+use crate::LogStatement;
+
+/***** HELPERS *****/
+/// Collects the names of all datasets read or written by a workflow.
+struct DatasetCollector<'a> {
+    /// The name of the dataset we're looking for.
+    subject: &'a str,
+    /// Whether we found it.
+    found:   bool,
+}
+impl<'a> WorkflowVisitor for DatasetCollector<'a> {
+    fn visit_task(&mut self, task: &ElemTask) {
+        self.found |=
+            task.input.iter().any(|data| data.name == self.subject) || task.output.as_ref().map(|data| data.name == self.subject).unwrap_or(false);
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        self.found |= commit.data_name == self.subject || commit.input.iter().any(|data| data.name == self.subject);
+    }
+}
+
+/// Determines in which ways a deliberation request touched the subject.
+///
+/// # Arguments
+/// - `subject`: The identifier of the user or dataset to look for.
+/// - `initiator`: The initiator of the request.
+/// - `actor`: Who sent the request on behalf of the `initiator`, if it was delegated.
+/// - `workflow`: The [`Workflow`] in question.
+/// - `data`: The dataset that was asked to be accessed, if any.
+///
+/// # Returns
+/// The [`SubjectRole`]s the subject played in the request. Empty if it did not touch the subject at all.
+fn roles_of(subject: &str, initiator: &str, actor: Option<&str>, workflow: &Workflow, data: Option<&str>) -> Vec<SubjectRole> {
+    let mut roles: Vec<SubjectRole> = Vec::new();
+    if initiator == subject {
+        roles.push(SubjectRole::Initiator);
+    }
+    if actor == Some(subject) {
+        roles.push(SubjectRole::Actor);
+    }
+    if workflow.user.name == subject {
+        roles.push(SubjectRole::User);
+    }
+    if data == Some(subject) {
+        roles.push(SubjectRole::AccessedDataset);
+    } else {
+        let mut collector = DatasetCollector { subject, found: false };
+        walk_workflow_preorder(&workflow.start, &mut collector);
+        if collector.found {
+            roles.push(SubjectRole::WorkflowDataset);
+        }
+    }
+    roles
+}
+
+/***** LIBRARY *****/
+/// Describes how a data subject was involved in a deliberation request.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubjectRole {
+    /// The subject is the one who sent the request, or on whose behalf it was sent.
+    Initiator,
+    /// The subject sent the request on behalf of its initiator.
+    Actor,
+    /// The subject is the user on whose behalf the workflow runs.
+    User,
+    /// The subject is the dataset that was asked to be accessed.
+    AccessedDataset,
+    /// The subject is a dataset read or written by the workflow.
+    WorkflowDataset,
+}
+impl Display for SubjectRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Initiator => write!(f, "requester"),
+            Self::Actor => write!(f, "delegate of requester"),
+            Self::User => write!(f, "workflow user"),
+            Self::AccessedDataset => write!(f, "accessed dataset"),
+            Self::WorkflowDataset => write!(f, "dataset used by workflow"),
+        }
+    }
+}
+
+/// A single deliberation request that touched the data subject.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataSubjectEvent {
+    /// When the request was logged, as written in the audit log (if known).
+    pub timestamp: Option<String>,
+    /// The verdict reference of the request.
+    pub reference: String,
+    /// What was asked: `execute-task`, `asset-access` or `workflow-validate`.
+    pub kind: String,
+    /// How the subject was involved.
+    pub roles: Vec<SubjectRole>,
+    /// Who sent the request.
+    pub requested_by: String,
+    /// The identifier of the workflow in question.
+    pub workflow: String,
+    /// The task in question, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    /// The dataset in question, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// The policy version that decided the request.
+    pub policy: i64,
+    /// Whether the request was allowed, or [`None`] if the audit log holds no verdict for it.
+    pub allowed: Option<bool>,
+    /// The reasons for denial that were shared with the requester, if any.
+    pub reasons: Vec<String>,
+    /// The fallback behaviour used because the reasoner could not be consulted, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+}
+
+/// A policy version that decided at least one request touching the data subject.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataSubjectPolicy {
+    /// The version of the policy.
+    pub version: i64,
+    /// The description of the policy, if its addition is in the audit log.
+    pub description: Option<String>,
+    /// The description of the version, if its addition is in the audit log.
+    pub version_description: Option<String>,
+    /// The number of requests touching the subject that this version decided.
+    pub requests: usize,
+}
+
+/// Everything the audit log knows about a single data subject, in chronological order.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataSubjectReport {
+    /// The identifier of the user or dataset the report is about.
+    pub subject:  String,
+    /// The requests that touched the subject, oldest first.
+    pub events:   Vec<DataSubjectEvent>,
+    /// The policy versions that decided those requests, by version.
+    pub policies: Vec<DataSubjectPolicy>,
+}
+impl DataSubjectReport {
+    /// Builds the report for a data subject from the statements in an audit log.
+    ///
+    /// # Arguments
+    /// - `subject`: The identifier of the user or dataset to report on.
+    /// - `statements`: The [`LogStatement`]s read from the audit log, in order, each with the timestamp it was logged at (if known).
+    ///
+    /// # Returns
+    /// A new DataSubjectReport.
+    pub fn new<'s, 'a: 's>(subject: impl Into<String>, statements: impl IntoIterator<Item = (Option<&'s str>, &'s LogStatement<'a>)>) -> Self {
+        let subject: String = subject.into();
+
+        let mut events: Vec<DataSubjectEvent> = Vec::new();
+        let mut by_reference: HashMap<String, usize> = HashMap::new();
+        let mut descriptions: HashMap<i64, (String, String)> = HashMap::new();
+        for (timestamp, stmt) in statements {
+            let (reference, kind, auth, policy, workflow, task, data) = match stmt {
+                LogStatement::ExecuteTask { reference, auth, policy, workflow, task, .. } => {
+                    (reference, "execute-task", auth, *policy, workflow, Some(task.to_string()), None)
+                },
+                LogStatement::AssetAccess { reference, auth, policy, workflow, data, task, .. } => {
+                    (reference, "asset-access", auth, *policy, workflow, task.as_ref().map(|task| task.to_string()), Some(data.to_string()))
+                },
+                LogStatement::WorkflowValidate { reference, auth, policy, workflow, .. } => {
+                    (reference, "workflow-validate", auth, *policy, workflow, None, None)
+                },
+
+                // Pair the outcomes with the requests we already found
+                LogStatement::ReasonerVerdict { reference, verdict } => {
+                    if let Some(event) = by_reference.get(&**reference).map(|i| &mut events[*i]) {
+                        match &**verdict {
+                            Verdict::Allow(_) => event.allowed = Some(true),
+                            Verdict::Deny(deny) => {
+                                event.allowed = Some(false);
+                                event.reasons = deny.reasons_for_denial.clone().unwrap_or_default();
+                            },
+                        }
+                    }
+                    continue;
+                },
+                LogStatement::ReasonerFallback { reference, behaviour, .. } | LogStatement::CircuitOpen { reference, behaviour, .. } => {
+                    if let Some(event) = by_reference.get(&**reference).map(|i| &mut events[*i]) {
+                        event.fallback = Some(behaviour.to_string());
+                    }
+                    continue;
+                },
+
+                // Remember what the policies are about
+                LogStatement::PolicyAdd { policy, .. } => {
+                    if let Some(version) = policy.version.version {
+                        descriptions.insert(version, (policy.description.clone(), policy.version.version_description.clone()));
+                    }
+                    continue;
+                },
+                _ => continue,
+            };
+
+            let roles: Vec<SubjectRole> = roles_of(&subject, &auth.initiator, auth.actor.as_deref(), workflow, data.as_deref());
+            if roles.is_empty() {
+                continue;
+            }
+            by_reference.insert(reference.to_string(), events.len());
+            events.push(DataSubjectEvent {
+                timestamp: timestamp.map(String::from),
+                reference: reference.to_string(),
+                kind: kind.into(),
+                roles,
+                requested_by: auth.initiator.clone(),
+                workflow: workflow.id.clone(),
+                task,
+                data,
+                policy,
+                allowed: None,
+                reasons: Vec::new(),
+                fallback: None,
+            });
+        }
+
+        // Summarize the policies involved
+        let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+        for event in &events {
+            *counts.entry(event.policy).or_default() += 1;
+        }
+        let policies: Vec<DataSubjectPolicy> = counts
+            .into_iter()
+            .map(|(version, requests)| {
+                let (description, version_description) = descriptions.remove(&version).unzip();
+                DataSubjectPolicy { version, description, version_description, requests }
+            })
+            .collect();
+
+        Self { subject, events, policies }
+    }
+}
@@ -0,0 +1,199 @@
+//! Implements the timings of handling a single question, and a report on them for capacity planning.
+//!
+//! Where a `SESSION-SUMMARY` only covers the time a reasoner connector spent on a question, a question also has to wait
+//! for its state to be resolved and for its turn at the reasoner. A [`RequestTimer`] measures each of these phases while
+//! a question is handled, and the resulting [`RequestTimings`] are logged in a `REQUEST-TIMINGS` statement right before
+//! its verdict. A [`LatencyReport`] summarizes the timings of all questions in the audit log, such that the reasoner can
+//! be sized from the audit trail alone.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::LogStatement;
+use crate::session::millis;
+
+/***** HELPERS *****/
+/// Runs the given future, and adds the time it took to a phase.
+///
+/// # Arguments
+/// - `phase`: The time spent in the phase so far, if any.
+/// - `fut`: The future to run.
+///
+/// # Returns
+/// Whatever `fut` returns.
+async fn time<F: Future>(phase: &mut Option<Duration>, fut: F) -> F::Output {
+    let start: Instant = Instant::now();
+    let res: F::Output = fut.await;
+    *phase = Some(phase.unwrap_or_default() + start.elapsed());
+    res
+}
+
+/// Returns the value at a percentile of a sorted list, using the nearest-rank method.
+///
+/// # Arguments
+/// - `sorted`: The values, sorted in ascending order. Must not be empty.
+/// - `percentile`: The percentile to find, between 0 and 100.
+///
+/// # Returns
+/// The smallest value such that at least `percentile` percent of the values is not larger.
+fn percentile(sorted: &[u64], percentile: f64) -> u64 {
+    let rank: usize = ((percentile / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/***** AUXILLARY *****/
+/// The timings of handling a single question, as logged in a `REQUEST-TIMINGS` statement.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RequestTimings {
+    /// The verdict reference of the question.
+    pub reference: String,
+    /// When the question was received.
+    pub received_at: DateTime<Utc>,
+    /// How long it took to resolve the state the question is answered with, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_resolution_ms: Option<u64>,
+    /// How long the question waited for its turn at the reasoner, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_ms: Option<u64>,
+    /// How long the reasoner connector took to answer, in milliseconds. Omitted if it was not consulted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoner_ms: Option<u64>,
+    /// How long handling the question took until its verdict was known, in milliseconds.
+    pub total_ms: u64,
+}
+
+/// Summarizes the latency of a single phase of handling questions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PhaseLatency {
+    /// The name of the phase (e.g., `queue`).
+    pub phase:   String,
+    /// The number of questions that went through the phase.
+    pub samples: usize,
+    /// The average time spent in the phase, in milliseconds.
+    pub mean_ms: f64,
+    /// The median time spent in the phase, in milliseconds.
+    pub p50_ms:  u64,
+    /// The 95th percentile of the time spent in the phase, in milliseconds.
+    pub p95_ms:  u64,
+    /// The 99th percentile of the time spent in the phase, in milliseconds.
+    pub p99_ms:  u64,
+    /// The longest time spent in the phase, in milliseconds.
+    pub max_ms:  u64,
+}
+impl PhaseLatency {
+    /// Summarizes the time spent in a phase.
+    ///
+    /// # Arguments
+    /// - `phase`: The name of the phase.
+    /// - `samples`: The time every question spent in it, in milliseconds.
+    ///
+    /// # Returns
+    /// A new PhaseLatency, or [`None`] if there are no samples.
+    fn new(phase: impl Into<String>, mut samples: Vec<u64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        Some(Self {
+            phase:   phase.into(),
+            samples: samples.len(),
+            mean_ms: samples.iter().map(|ms| *ms as f64).sum::<f64>() / samples.len() as f64,
+            p50_ms:  percentile(&samples, 50.0),
+            p95_ms:  percentile(&samples, 95.0),
+            p99_ms:  percentile(&samples, 99.0),
+            max_ms:  *samples.last().unwrap(),
+        })
+    }
+}
+
+/***** LIBRARY *****/
+/// Measures how long the phases of handling a single question take.
+#[derive(Clone, Debug)]
+pub struct RequestTimer {
+    /// When the question was received.
+    received_at: DateTime<Utc>,
+    /// When the question was received, for measuring how long it took.
+    started: Instant,
+    /// The time spent resolving the state, if it was.
+    state_resolution: Option<Duration>,
+    /// The time spent waiting for the reasoner, if the question got that far.
+    queue: Option<Duration>,
+    /// The time spent in the reasoner connector, if it was consulted.
+    reasoner: Option<Duration>,
+}
+impl Default for RequestTimer {
+    #[inline]
+    fn default() -> Self { Self::new() }
+}
+impl RequestTimer {
+    /// Starts timing a question that is received now.
+    #[inline]
+    pub fn new() -> Self { Self { received_at: Utc::now(), started: Instant::now(), state_resolution: None, queue: None, reasoner: None } }
+
+    /// Runs the given future as the resolution of the state of the question.
+    #[inline]
+    pub async fn time_state_resolution<F: Future>(&mut self, fut: F) -> F::Output { time(&mut self.state_resolution, fut).await }
+
+    /// Runs the given future as waiting for the question's turn at the reasoner.
+    #[inline]
+    pub async fn time_queue<F: Future>(&mut self, fut: F) -> F::Output { time(&mut self.queue, fut).await }
+
+    /// Runs the given future as asking the reasoner connector the question.
+    #[inline]
+    pub async fn time_reasoner<F: Future>(&mut self, fut: F) -> F::Output { time(&mut self.reasoner, fut).await }
+
+    /// Returns the timings of the question as if it was done now.
+    ///
+    /// # Arguments
+    /// - `reference`: The verdict reference of the question.
+    pub fn timings(&self, reference: impl Into<String>) -> RequestTimings {
+        RequestTimings {
+            reference: reference.into(),
+            received_at: self.received_at,
+            state_resolution_ms: self.state_resolution.map(millis),
+            queue_ms: self.queue.map(millis),
+            reasoner_ms: self.reasoner.map(millis),
+            total_ms: millis(self.started.elapsed()),
+        }
+    }
+}
+
+/// Summarizes the timings of all questions in (a part of) the audit log.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LatencyReport {
+    /// The number of questions with timings.
+    pub questions: usize,
+    /// The latency of every phase that any question went through, in the order in which questions go through them.
+    pub phases:    Vec<PhaseLatency>,
+}
+impl LatencyReport {
+    /// Collects the timings of all questions in the given statements.
+    ///
+    /// # Arguments
+    /// - `statements`: The [`LogStatement`]s to scan, e.g., as read from the audit log.
+    ///
+    /// # Returns
+    /// A new LatencyReport. Statements other than `REQUEST-TIMINGS` are ignored.
+    pub fn new<'s, 'a: 's>(statements: impl IntoIterator<Item = &'s LogStatement<'a>>) -> Self {
+        let timings: Vec<&RequestTimings> = statements
+            .into_iter()
+            .filter_map(|stmt| match stmt {
+                LogStatement::RequestTimings(timings) => Some(timings.as_ref()),
+                _ => None,
+            })
+            .collect();
+        let phases: Vec<PhaseLatency> = [
+            PhaseLatency::new("state-resolution", timings.iter().filter_map(|t| t.state_resolution_ms).collect()),
+            PhaseLatency::new("queue", timings.iter().filter_map(|t| t.queue_ms).collect()),
+            PhaseLatency::new("reasoner", timings.iter().filter_map(|t| t.reasoner_ms).collect()),
+            PhaseLatency::new("total", timings.iter().map(|t| t.total_ms).collect()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        Self { questions: timings.len(), phases }
+    }
+}
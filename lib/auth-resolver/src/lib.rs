@@ -2,28 +2,216 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AuthContext {
+    /// The user on whose behalf the request is made.
     pub initiator: String,
     pub system:    String,
+    /// The party that made the request on behalf of the `initiator`, if it was delegated (e.g., a driver system acting
+    /// for an end-user).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor:     Option<String>,
 }
 
-#[derive(Debug)]
-pub struct AuthResolverError {
-    err: String,
+/// Explains why a request could not be authenticated.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthFailureReason {
+    /// The request carries no credentials.
+    MissingCredentials,
+    /// The credentials could not be parsed.
+    Malformed,
+    /// The credentials are signed with a key we don't know.
+    UnknownKey,
+    /// The signature of the credentials is not valid.
+    InvalidSignature,
+    /// The credentials are signed with an algorithm that is not allowed.
+    DisallowedAlgorithm,
+    /// The credentials have expired.
+    Expired,
+    /// The credentials are not valid yet.
+    NotYetValid,
+    /// The credentials were issued by someone else than expected.
+    WrongIssuer,
+    /// The credentials are meant for someone else.
+    WrongAudience,
+    /// The credentials lack a required claim.
+    MissingClaim,
+    /// Any other reason.
+    Other,
 }
-
-impl AuthResolverError {
-    pub fn new(err: String) -> Self { Self { err } }
+impl std::fmt::Display for AuthFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use AuthFailureReason::*;
+        match self {
+            MissingCredentials => write!(f, "missing-credentials"),
+            Malformed => write!(f, "malformed"),
+            UnknownKey => write!(f, "unknown-key"),
+            InvalidSignature => write!(f, "invalid-signature"),
+            DisallowedAlgorithm => write!(f, "disallowed-algorithm"),
+            Expired => write!(f, "expired"),
+            NotYetValid => write!(f, "not-yet-valid"),
+            WrongIssuer => write!(f, "wrong-issuer"),
+            WrongAudience => write!(f, "wrong-audience"),
+            MissingClaim => write!(f, "missing-claim"),
+            Other => write!(f, "other"),
+        }
+    }
 }
 
-impl std::fmt::Display for AuthResolverError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", self.err) }
+/// The error that caused an [`AuthResolverError`], if any (e.g., the one of the JWT library).
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Defines why a request could not be authenticated, or why the resolver could not be set up.
+///
+/// Variants caused by credentials that could be read carry an `initiator_hint`: who the credentials claim to be sent by,
+/// which is never verified and thus only useful for tracing the failure back.
+#[derive(Debug, thiserror::Error)]
+pub enum AuthResolverError {
+    /// The request carries no credentials.
+    #[error("Authorization header not present")]
+    MissingCredentials,
+    /// The credentials could not be parsed.
+    #[error("{what}")]
+    Malformed {
+        what: String,
+        initiator_hint: Option<String>,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// The credentials are signed with a key we don't know.
+    #[error("Could not find key for kid: {kid}")]
+    UnknownKey { kid: String, initiator_hint: Option<String> },
+    /// The signature of the credentials is not valid.
+    #[error("Token signature is invalid")]
+    InvalidSignature {
+        initiator_hint: Option<String>,
+        #[source]
+        source: BoxedError,
+    },
+    /// The credentials are signed with an algorithm that is not allowed.
+    #[error("Token is signed with {alg}, which is not one of the allowed algorithms")]
+    DisallowedAlgorithm {
+        alg: String,
+        initiator_hint: Option<String>,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// The credentials have expired.
+    #[error("Token has expired")]
+    Expired {
+        initiator_hint: Option<String>,
+        #[source]
+        source: BoxedError,
+    },
+    /// The credentials are not valid yet.
+    #[error("Token is not valid yet")]
+    NotYetValid {
+        initiator_hint: Option<String>,
+        #[source]
+        source: BoxedError,
+    },
+    /// The credentials were issued by someone else than expected.
+    #[error("Token was issued by an unexpected issuer")]
+    WrongIssuer {
+        initiator_hint: Option<String>,
+        #[source]
+        source: BoxedError,
+    },
+    /// The credentials are meant for someone else.
+    #[error("Token is meant for another audience")]
+    WrongAudience {
+        initiator_hint: Option<String>,
+        #[source]
+        source: BoxedError,
+    },
+    /// The credentials lack a required claim.
+    #[error("Missing claim: {claim}")]
+    MissingClaim {
+        claim: String,
+        initiator_hint: Option<String>,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// The keys to verify credentials with could not be loaded or used.
+    #[error("{what}")]
+    KeySet {
+        what:   String,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// The credentials were refused for any other reason.
+    #[error("{what}")]
+    Other {
+        what: String,
+        initiator_hint: Option<String>,
+        #[source]
+        source: Option<BoxedError>,
+    },
 }
 
-impl std::error::Error for AuthResolverError {}
+impl AuthResolverError {
+    /// Sets who the (unverified) credentials claim to be sent by, such that the failure can be traced back to them.
+    ///
+    /// Errors that are not about the credentials of a request are returned as-is.
+    #[inline]
+    pub fn with_initiator_hint(mut self, hint: Option<String>) -> Self {
+        use AuthResolverError::*;
+        match &mut self {
+            Malformed { initiator_hint, .. }
+            | UnknownKey { initiator_hint, .. }
+            | InvalidSignature { initiator_hint, .. }
+            | DisallowedAlgorithm { initiator_hint, .. }
+            | Expired { initiator_hint, .. }
+            | NotYetValid { initiator_hint, .. }
+            | WrongIssuer { initiator_hint, .. }
+            | WrongAudience { initiator_hint, .. }
+            | MissingClaim { initiator_hint, .. }
+            | Other { initiator_hint, .. } => *initiator_hint = hint,
+            MissingCredentials | KeySet { .. } => {},
+        }
+        self
+    }
+
+    /// Returns why authentication failed.
+    pub fn reason(&self) -> AuthFailureReason {
+        use AuthResolverError::*;
+        match self {
+            MissingCredentials => AuthFailureReason::MissingCredentials,
+            Malformed { .. } => AuthFailureReason::Malformed,
+            UnknownKey { .. } => AuthFailureReason::UnknownKey,
+            InvalidSignature { .. } => AuthFailureReason::InvalidSignature,
+            DisallowedAlgorithm { .. } => AuthFailureReason::DisallowedAlgorithm,
+            Expired { .. } => AuthFailureReason::Expired,
+            NotYetValid { .. } => AuthFailureReason::NotYetValid,
+            WrongIssuer { .. } => AuthFailureReason::WrongIssuer,
+            WrongAudience { .. } => AuthFailureReason::WrongAudience,
+            MissingClaim { .. } => AuthFailureReason::MissingClaim,
+            KeySet { .. } | Other { .. } => AuthFailureReason::Other,
+        }
+    }
+
+    /// Returns who the credentials claim to be sent by, if known. This is never verified, so only a hint.
+    pub fn initiator_hint(&self) -> Option<&str> {
+        use AuthResolverError::*;
+        match self {
+            Malformed { initiator_hint, .. }
+            | UnknownKey { initiator_hint, .. }
+            | InvalidSignature { initiator_hint, .. }
+            | DisallowedAlgorithm { initiator_hint, .. }
+            | Expired { initiator_hint, .. }
+            | NotYetValid { initiator_hint, .. }
+            | WrongIssuer { initiator_hint, .. }
+            | WrongAudience { initiator_hint, .. }
+            | MissingClaim { initiator_hint, .. }
+            | Other { initiator_hint, .. } => initiator_hint.as_deref(),
+            MissingCredentials | KeySet { .. } => None,
+        }
+    }
+}
 
+#[cfg(feature = "warp")]
 impl warp::reject::Reject for AuthResolverError {}
 
 #[async_trait::async_trait]
 pub trait AuthResolver {
-    async fn authenticate(&self, headers: warp::http::HeaderMap) -> Result<AuthContext, AuthResolverError>;
+    async fn authenticate(&self, headers: http::HeaderMap) -> Result<AuthContext, AuthResolverError>;
 }
@@ -0,0 +1,33 @@
+//! Defines how the reasoner generates the identifiers it hands out.
+//!
+//! Verdict references, the external identifiers of policy versions and the identifiers of audit log statements are all
+//! generated by an [`IdGenerator`], such that deployments can plug in their own scheme (e.g., to match the identifiers
+//! of the rest of their infrastructure). The default is [`UuidV7Generator`], whose identifiers are ordered by the time
+//! they were generated; this keeps database indices on them compact, and makes them sort in the order they were given.
+
+use std::fmt::Debug;
+
+use uuid::Uuid;
+
+/***** LIBRARY *****/
+/// Something that generates unique identifiers.
+pub trait IdGenerator: Debug + Send + Sync {
+    /// Generates a new identifier, which is different from any generated before.
+    fn generate(&self) -> String;
+}
+
+/// Generates version 7 UUIDs, which start with the time they were generated at and are therefore ordered by it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV7Generator;
+impl IdGenerator for UuidV7Generator {
+    #[inline]
+    fn generate(&self) -> String { Uuid::now_v7().to_string() }
+}
+
+/// Generates version 4 (i.e., fully random) UUIDs, as the reasoner used to do for every identifier.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV4Generator;
+impl IdGenerator for UuidV4Generator {
+    #[inline]
+    fn generate(&self) -> String { Uuid::new_v4().to_string() }
+}
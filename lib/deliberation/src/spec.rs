@@ -1,48 +1,281 @@
+#[cfg(feature = "wir")]
 use brane_ast::Workflow;
+#[cfg(feature = "wir")]
 use brane_exe::pc::ProgramCounter;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use workflow::diagnostics::Diagnostic;
+
+/// How urgently a deliberation request should be answered.
+///
+/// When the reasoner is busy, questions with a higher priority are put to it before those with a lower one.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    /// Bulk questions (e.g., by a planner validating many workflows) that may wait.
+    Batch,
+    /// Questions that someone is waiting on.
+    #[default]
+    Interactive,
+}
+
+/// Categorizes why a request was denied, such that clients can act on a denial without parsing its reasons.
+///
+/// Reasoner connectors map the reasons given by their backend onto these, and the reasoner itself adds them for
+/// denials it decides on without consulting the backend (e.g., because there is no active policy). A planner can use
+/// them to, for example, try another location when a [`DenyCategory::LocationForbidden`] is given.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DenyCategory {
+    /// There is no (active) policy that could answer the question.
+    NoApplicablePolicy,
+    /// The owner of (some of) the data involved did not consent to its use.
+    ConsentMissing,
+    /// (Some of) the data or tasks involved may not be at or go to the requested location.
+    LocationForbidden,
+    /// The policy denies the request for any other reason.
+    PermissionDenied,
+    /// The initiator asked or used more than they are allowed to.
+    QuotaExceeded,
+    /// The reasoner backend failed or was unavailable, so the question could not be answered.
+    BackendError,
+    /// The reasoner is in emergency lockdown, and denies everything until it is lifted.
+    Lockdown,
+}
+
+/// How many reasons for denial the reasoner looks for before it answers.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Granularity {
+    /// Stops at the first violation found, which is quick but gives at most one reason.
+    FailFast,
+    /// Collects every violation, which may take the reasoner several questions.
+    Exhaustive,
+}
+
+/// WorkflowReference refers to a workflow uploaded to the reasoner before (with
+/// `PUT /v1/workflows/{id}`), by its identifier and the hash of its content.
+///
+/// Any deliberation request may give a `workflow_ref` with one instead of its
+/// `workflow`, in which case the uploaded workflow is used as if given inline.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct WorkflowReference {
+    /// The identifier of the workflow.
+    pub id:   String,
+    /// The content hash of the workflow, as returned when it was uploaded.
+    pub hash: String,
+}
 
 /// ExecuteTaskRequest represents the question if it is allowed to execute a
 /// certain task on this node
+#[cfg(feature = "wir")]
 #[derive(Serialize, Deserialize)]
 pub struct ExecuteTaskRequest {
     /// Some identifier that allows the policy reasoner to assume a different context.
     ///
     /// Note that not any identifier is accepted. Which are depends on which plugins used.
-    pub use_case: String,
-    /// Workflow definition
-    pub workflow: Workflow,
+    pub use_case:    String,
+    /// Workflow definition. May be given as a `workflow_ref` to an uploaded workflow instead (see [`WorkflowReference`]).
+    pub workflow:    Workflow,
     /// The location of the task we're examining in the given `workflow`.
-    pub task_id:  ProgramCounter,
+    pub task_id:     ProgramCounter,
+    /// If the task is in a function, the locations of the calls through which it is reached, outermost first.
+    ///
+    /// A function that is called more than once has its body inlined for every call, so `task_id` alone does not tell
+    /// which of its copies is meant. Together, they form the structural identifier of the task (e.g., `main:4/func:2`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub call_path:   Vec<ProgramCounter>,
+    /// How urgently the question should be answered. Defaults to [`Priority::Interactive`].
+    #[serde(default)]
+    pub priority:    Priority,
+    /// Whether to explain a denial with a minimal set of facts and policy phrases that cause it (see [`Explanation`]).
+    /// Only reasoner connectors that list `explain` among their capabilities do so, and it asks the reasoner many times.
+    #[serde(default)]
+    pub explain:     bool,
+    /// How many reasons for denial to look for (see [`Granularity`]). Left to the reasoner connector if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granularity: Option<Granularity>,
 }
 
 /// AccessDataRequest represents the question if a certain dataset
 /// can be accessed
+#[cfg(feature = "wir")]
 #[derive(Serialize, Deserialize)]
 pub struct AccessDataRequest {
     /// Some identifier that allows the policy reasoner to assume a different context.
     ///
     /// Note that not any identifier is accepted. Which are depends on which plugins used.
-    pub use_case: String,
-    /// Workflow definition
-    pub workflow: Workflow,
+    pub use_case:    String,
+    /// Workflow definition. May be given as a `workflow_ref` to an uploaded workflow instead (see [`WorkflowReference`]).
+    pub workflow:    Workflow,
     /// Identifier for the requested dataset
-    pub data_id:  String,
+    pub data_id:     String,
     /// The location of the task for which we transfer in the given `workflow`. If omitted, then this transfer should be interpreted as transferring the final result of the workflow.
-    pub task_id:  Option<ProgramCounter>,
+    pub task_id:     Option<ProgramCounter>,
+    /// If the task is in a function, the locations of the calls through which it is reached, outermost first (see
+    /// [`ExecuteTaskRequest::call_path`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub call_path:   Vec<ProgramCounter>,
+    /// How urgently the question should be answered. Defaults to [`Priority::Interactive`].
+    #[serde(default)]
+    pub priority:    Priority,
+    /// Whether to explain a denial with a minimal set of facts and policy phrases that cause it (see [`Explanation`]).
+    /// Only reasoner connectors that list `explain` among their capabilities do so, and it asks the reasoner many times.
+    #[serde(default)]
+    pub explain:     bool,
+    /// How many reasons for denial to look for (see [`Granularity`]). Left to the reasoner connector if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granularity: Option<Granularity>,
 }
 
 /// WorkflowValidationRequest represents the question
 /// if a workflow as a whole is considered valid by the checker.
 /// Used on the 'central' side to enforce 'central' policies
+#[cfg(feature = "wir")]
 #[derive(Serialize, Deserialize)]
 pub struct WorkflowValidationRequest {
+    /// Some identifier that allows the policy reasoner to assume a different context.
+    ///
+    /// Note that not any identifier is accepted. Which are depends on which plugins used.
+    pub use_case:     String,
+    /// Workflow definition. May be given as a `workflow_ref` to an uploaded workflow instead (see [`WorkflowReference`]).
+    pub workflow:     Workflow,
+    /// How urgently the question should be answered. Defaults to [`Priority::Interactive`].
+    #[serde(default)]
+    pub priority:     Priority,
+    /// Whether to explain a denial with a minimal set of facts and policy phrases that cause it (see [`Explanation`]).
+    /// Only reasoner connectors that list `explain` among their capabilities do so, and it asks the reasoner many times.
+    #[serde(default)]
+    pub explain:      bool,
+    /// Whether to annotate the verdict with the outcome for every task of the workflow (see [`NodeResult`]), such that a
+    /// planner can remove only the offending branches. Unless the reasoner connector can tell at once, this asks the
+    /// reasoner about every task separately.
+    #[serde(default)]
+    pub node_results: bool,
+    /// How many reasons for denial to look for (see [`Granularity`]). Left to the reasoner connector if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granularity:  Option<Granularity>,
+}
+
+/// QueryRequest represents a query, i.e., a question about what holds under the
+/// active policy (e.g., which datasets a user may access) instead of whether
+/// something is allowed.
+#[derive(Serialize, Deserialize)]
+pub struct QueryRequest {
     /// Some identifier that allows the policy reasoner to assume a different context.
     ///
     /// Note that not any identifier is accepted. Which are depends on which plugins used.
     pub use_case: String,
-    /// Workflow definition
-    pub workflow: Workflow,
+    /// The query itself, in the language of the reasoner (e.g., an eFLINT JSON expression).
+    pub query:    Value,
+    /// How urgently the query should be answered. Defaults to [`Priority::Interactive`].
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+/// FactOverride changes a single fact of a question asked before, to see whether it changes the verdict.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum FactOverride {
+    /// Plans a task at another location. The task is given by its identifier, with or without the workflow's identifier in front.
+    TaskLocation { task: String, location: String },
+    /// Adds a metadata tag (e.g., one recording consent) to a task, or to the workflow as a whole if no task is given.
+    AddTag {
+        #[serde(default)]
+        task:  Option<String>,
+        owner: String,
+        tag:   String,
+    },
+    /// Removes a metadata tag from a task, or from the workflow as a whole if no task is given.
+    RemoveTag {
+        #[serde(default)]
+        task:  Option<String>,
+        owner: String,
+        tag:   String,
+    },
+    /// Adds a user to the state.
+    AddUser { name: String },
+    /// Removes a user from the state.
+    RemoveUser { name: String },
+    /// Adds a location (i.e., a domain) to the state.
+    AddLocation { name: String },
+    /// Removes a location from the state.
+    RemoveLocation { name: String },
+    /// Adds a dataset to the state.
+    AddDataset { name: String },
+    /// Removes a dataset from the state.
+    RemoveDataset { name: String },
+    /// Considers a duty of the user asking fulfilled, removing it from the state.
+    FulfilDuty { duty: String },
+}
+
+/// WhatIfRequest represents a request to re-evaluate a question asked before with
+/// some of its facts changed, to find out which of them caused its verdict.
+#[derive(Serialize, Deserialize)]
+pub struct WhatIfRequest {
+    /// The verdict reference of the question asked before.
+    pub reference: String,
+    /// The facts to change before re-evaluating the question.
+    pub overrides: Vec<FactOverride>,
+    /// How urgently the question should be answered. Defaults to [`Priority::Interactive`].
+    #[serde(default)]
+    pub priority:  Priority,
+}
+
+/// SessionQuestion represents a single question in an [`EvaluationSessionRequest`],
+/// about the workflow of that session.
+#[cfg(feature = "wir")]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum SessionQuestion {
+    /// Asks if the task at `task_id` (reached through the calls at `call_path`) may be executed, like an [`ExecuteTaskRequest`].
+    ExecuteTask {
+        task_id:   ProgramCounter,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        call_path: Vec<ProgramCounter>,
+    },
+    /// Asks if dataset `data_id` may be accessed, like an [`AccessDataRequest`].
+    AccessData {
+        data_id:   String,
+        task_id:   Option<ProgramCounter>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        call_path: Vec<ProgramCounter>,
+    },
+    /// Asks if the workflow as a whole is considered valid, like a [`WorkflowValidationRequest`].
+    ExecuteWorkflow,
+}
+
+/// EvaluationSessionRequest binds several questions about the same workflow
+/// into one session, such that they are all answered with the same policy
+/// version and state.
+#[cfg(feature = "wir")]
+#[derive(Serialize, Deserialize)]
+pub struct EvaluationSessionRequest {
+    /// Some identifier that allows the policy reasoner to assume a different context.
+    ///
+    /// Note that not any identifier is accepted. Which are depends on which plugins used.
+    pub use_case:  String,
+    /// Workflow definition. May be given as a `workflow_ref` to an uploaded workflow instead (see [`WorkflowReference`]).
+    pub workflow:  Workflow,
+    /// The questions to answer about `workflow`, in order.
+    pub questions: Vec<SessionQuestion>,
+    /// How urgently the questions should be answered. Defaults to [`Priority::Interactive`].
+    #[serde(default)]
+    pub priority:  Priority,
+}
+
+/// EvaluationSessionResponse represents the answers to all questions of an
+/// [`EvaluationSessionRequest`], together with what they were answered against.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EvaluationSessionResponse {
+    /// The identifier of the session.
+    pub session_id: String,
+    /// The version of the policy that answered every question.
+    pub policy_version: i64,
+    /// Identifies the state that every question was answered with (the SHA-256 of it, in hex).
+    pub state_snapshot: String,
+    /// The verdicts, one for every question and in the same order.
+    pub verdicts: Vec<Verdict>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -56,20 +289,54 @@ pub enum Verdict {
     Deny(DeliberationDenyResponse),
 }
 
+/// Identifies what a verdict was evaluated with, such that clients can detect when it was given under an unexpected
+/// policy (or reasoner base specification, or state).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EvaluationContext {
+    /// The version of the policy that gave the verdict, or [`None`] if no policy was consulted (e.g., because none is active).
+    #[serde(default)]
+    pub policy_version:    Option<i64>,
+    /// The hash of the reasoner connector's context (i.e., its base specification) that the verdict was given with.
+    #[serde(default)]
+    pub connector_context: String,
+    /// Identifies the state that the verdict was given with (the SHA-256 of it, in hex).
+    #[serde(default)]
+    pub state_snapshot:    Option<String>,
+}
+
 // DeliberationResponse represents the shared part of the the deliberation repsonses
 // (Allow, Deny)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DeliberationResponse {
     pub verdict_reference: String,
+    /// Information missing from the workflow that made the check weaker than it could have been (e.g., unplanned tasks).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Diagnostic>,
+    /// What the verdict was evaluated with.
+    #[serde(flatten)]
+    pub evaluated_with: EvaluationContext,
+    /// The outcome for every task of the workflow, if asked for (only for workflow validation, see [`NodeResult`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<NodeResult>,
+    /// How many reasons for denial the reasoner looked for, if the reasoner connector tells.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granularity: Option<Granularity>,
 }
 
 // DeliberationResponse represents the answer the checker came up with
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DeliberationAllowResponse {
     #[serde(flatten)]
-    pub shared:    DeliberationResponse,
+    pub shared: DeliberationResponse,
     /// Signature by the checker
     pub signature: String,
+    /// Conditions that come with the permission (e.g., added by a verdict transformer), if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obligations: Vec<String>,
+    /// A short-lived signed token that lets the service providing a dataset verify that accessing it was allowed.
+    /// Only given for data-access questions, and only if the checker is configured to issue them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
 }
 
 // DeliberationResponse represents the answer the checker came up with
@@ -81,6 +348,79 @@ pub struct DeliberationDenyResponse {
     /// Only present if the request is denied and it only contains reasons
     /// the checker wants to share.
     pub reasons_for_denial: Option<Vec<String>>,
+    /// The categories of the reasons that the request is denied, if known.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<DenyCategory>,
+    /// Which facts and policy phrases cause the denial, if asked for and the reasoner could tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+}
+
+/// Explanation lists a minimal set of facts and policy phrases that together still cause a denial.
+///
+/// It is found by delta debugging: the reasoner is asked the same question again and again, each time with some of the
+/// facts (from the state and workflow) and policy phrases left out, keeping only those without which the denial goes
+/// away. Think of it as the unsatisfiable core of the question.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Explanation {
+    /// The reasons for denial that are explained.
+    pub violations:  Vec<String>,
+    /// The facts and policy phrases that cause them, in the language of the reasoner (e.g., eFLINT JSON phrases).
+    pub conflicting: Vec<Value>,
+    /// Whether leaving out any one of `conflicting` takes away the denial. If the search gave up before finding out,
+    /// this is false and `conflicting` may contain phrases that do not matter.
+    pub minimal:     bool,
+}
+
+/// What the checker thinks of a single node of a workflow.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeOutcome {
+    /// The node may be executed.
+    Allowed,
+    /// The node may not be executed.
+    Denied,
+    /// The node may only be executed if certain conditions (given as its reasons) are met.
+    Conditional,
+}
+
+/// NodeResult annotates a workflow validation verdict with the outcome for a single node (i.e., task) of the workflow.
+///
+/// A planner can use these to remove only the branches with denied nodes instead of discarding the whole workflow. Note
+/// that the workflow as a whole may still be denied if every node is allowed, e.g., because of how data flows between them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeResult {
+    /// The identifier of the node in the checker's workflow (i.e., `<workflow id>-<structural id>-task`).
+    pub node:    String,
+    /// Whether the node may be executed.
+    pub outcome: NodeOutcome,
+    /// Why the node is denied (or under which conditions it is allowed), if the checker wants to share it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
+}
+
+/// QueryResponse lists what holds according to the reasoner, as asked by a [`QueryRequest`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueryResponse {
+    /// A UUID that can be traced back in the logs to explain the answer.
+    pub query_reference: String,
+    /// The instances that hold, in the form given by the reasoner backend.
+    pub instances: Vec<Value>,
+    /// What the query was evaluated with.
+    #[serde(flatten)]
+    pub evaluated_with: EvaluationContext,
+}
+
+/// WhatIfResponse puts the verdict of a question asked before next to the one it gets with
+/// the facts changed by a [`WhatIfRequest`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WhatIfResponse {
+    /// The verdict the question originally got.
+    pub original:  Verdict,
+    /// The verdict the question gets with the facts changed. Its reference is that of the original, prefixed with `what-if-`.
+    pub what_if:   Verdict,
+    /// The facts that were changed.
+    pub overrides: Vec<FactOverride>,
 }
 
 pub type TaskExecResponse = DeliberationResponse;
@@ -90,3 +430,4 @@ pub type WorkflowValidationResponse = DeliberationResponse;
 // POST /v1/deliberation/execute-task
 // POST /v1/deliberation/access-data
 // POST /v1/deliberation/execute-workflow
+// POST /v1/deliberation/query
@@ -0,0 +1,267 @@
+//! Implements annotations on the rules in eFLINT files, which are kept in the compiled eFLINT JSON.
+//!
+//! The compiler drops comments, but policy authors often want to say more about a rule than eFLINT can: how bad it is
+//! to violate it, whether clients may know about it and how to tell them. They can do so in `//@` comments right
+//! above the declaration of the rule:
+//!
+//! ```eflint
+//! //@ severity: high
+//! //@ public
+//! //@ message: consent-missing
+//! Invariant no-consent When ...
+//! ```
+//!
+//! Supported are `severity: <low|medium|high|critical>`, `public` or `private` and `message: <key>` (e.g., a key in
+//! the reasoner's message catalog). Other comments and empty lines may occur between the annotations and the
+//! declaration. The annotations of all declarations are added to the compiled policy as an [`ANNOTATIONS_FIELD`] map
+//! from declaration names to [`Annotation`]s, where reasoner connectors can find them with [`annotations_of()`].
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/***** CONSTANTS *****/
+/// The field of the compiled eFLINT JSON policy that holds the annotations.
+pub const ANNOTATIONS_FIELD: &str = "annotations";
+
+/// The prefix of comments that annotate the next declaration.
+const ANNOTATION_PREFIX: &str = "//@";
+/// The keywords that start a declaration that can be annotated.
+const DECLARATION_KEYWORDS: [&str; 6] = ["Fact", "Act", "Duty", "Event", "Invariant", "Predicate"];
+
+/***** ERRORS *****/
+/// Defines errors that originate from reading annotations.
+#[derive(Debug)]
+pub enum Error {
+    /// Annotations were not followed by a declaration.
+    Dangling { line: usize },
+    /// The same annotation was given twice for a declaration.
+    Duplicate { line: usize, key: &'static str },
+    /// A severity was not recognized.
+    IllegalSeverity { line: usize, raw: String },
+    /// An annotation that needs a value was given without one.
+    MissingValue { line: usize, key: &'static str },
+    /// A declaration was annotated more than once.
+    Reannotated { line: usize, name: String },
+    /// An annotation was not recognized.
+    Unknown { line: usize, raw: String },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            Dangling { line } => write!(f, "Annotation on line {line} is not followed by a declaration"),
+            Duplicate { line, key } => write!(f, "Annotation '{key}' on line {line} is given more than once for the same declaration"),
+            IllegalSeverity { line, raw } => {
+                write!(f, "Illegal severity '{raw}' on line {line} (expected 'low', 'medium', 'high' or 'critical')")
+            },
+            MissingValue { line, key } => write!(f, "Annotation '{key}' on line {line} needs a value (as '{key}: <value>')"),
+            Reannotated { line, name } => write!(f, "Declaration '{name}' on line {line} is already annotated elsewhere"),
+            Unknown { line, raw } => write!(f, "Unknown annotation '{raw}' on line {line}"),
+        }
+    }
+}
+impl error::Error for Error {}
+
+/***** HELPER FUNCTIONS *****/
+/// Finds the name of the declaration on a (trimmed) line, if it starts one.
+///
+/// # Arguments
+/// - `line`: The line to analyse.
+///
+/// # Returns
+/// The name of the declared type (also for extensions, like `Extend Act ...`), or [`None`] if the line does not declare anything.
+pub(crate) fn declared_name(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    let mut keyword: &str = words.next()?;
+    if keyword == "Extend" {
+        keyword = words.next()?;
+    }
+    if !DECLARATION_KEYWORDS.contains(&keyword) {
+        return None;
+    }
+    let name: &str = words.next()?.trim_end_matches('.');
+    (!name.is_empty()).then_some(name)
+}
+
+/***** AUXILLARY *****/
+/// How bad it is to violate a rule.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Low => write!(f, "low"),
+            Self::Medium => write!(f, "medium"),
+            Self::High => write!(f, "high"),
+            Self::Critical => write!(f, "critical"),
+        }
+    }
+}
+impl FromStr for Severity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What the author of a policy said about a single declaration.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Annotation {
+    /// How bad it is to violate it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// Whether clients may be told that it was violated. If not given, the reasoner connector decides.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public:   Option<bool>,
+    /// The key of the message to tell clients instead of its name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message:  Option<String>,
+}
+
+/// The annotations in a policy, by the name of the declaration they annotate.
+pub type Annotations = BTreeMap<String, Annotation>;
+
+/***** LIBRARY *****/
+/// Reads the annotations in the source of an eFLINT file.
+///
+/// # Arguments
+/// - `source`: The contents of the file, with any templates expanded.
+/// - `annotations`: The [`Annotations`] to add those found to (e.g., those of the files read before).
+///
+/// # Errors
+/// This function errors if an annotation is malformed, not followed by a declaration or if a declaration is annotated twice.
+pub fn extract(source: &str, annotations: &mut Annotations) -> Result<(), Error> {
+    let mut pending: Option<(usize, Annotation)> = None;
+    for (i, line) in source.lines().enumerate() {
+        let lineno: usize = i + 1;
+        let line: &str = line.trim();
+        if let Some(body) = line.strip_prefix(ANNOTATION_PREFIX) {
+            let annotation: &mut Annotation = &mut pending.get_or_insert_with(|| (lineno, Annotation::default())).1;
+            let (key, value): (&str, Option<&str>) = match body.split_once(':') {
+                Some((key, value)) => (key.trim(), Some(value.trim()).filter(|value| !value.is_empty())),
+                None => (body.trim(), None),
+            };
+            match (key, value) {
+                ("severity", Some(value)) => {
+                    let severity: Severity = value.parse().map_err(|_| Error::IllegalSeverity { line: lineno, raw: value.into() })?;
+                    if annotation.severity.replace(severity).is_some() {
+                        return Err(Error::Duplicate { line: lineno, key: "severity" });
+                    }
+                },
+                ("message", Some(value)) => {
+                    if annotation.message.replace(value.into()).is_some() {
+                        return Err(Error::Duplicate { line: lineno, key: "message" });
+                    }
+                },
+                ("public" | "private", None) => {
+                    if annotation.public.replace(key == "public").is_some() {
+                        return Err(Error::Duplicate { line: lineno, key: "public/private" });
+                    }
+                },
+                ("severity", None) => return Err(Error::MissingValue { line: lineno, key: "severity" }),
+                ("message", None) => return Err(Error::MissingValue { line: lineno, key: "message" }),
+                _ => return Err(Error::Unknown { line: lineno, raw: body.trim().into() }),
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        // Anything else must be the annotated declaration
+        let Some((first, annotation)) = pending.take() else { continue };
+        let Some(name) = declared_name(line) else { return Err(Error::Dangling { line: first }) };
+        if annotations.insert(name.into(), annotation).is_some() {
+            return Err(Error::Reannotated { line: lineno, name: name.into() });
+        }
+    }
+    match pending {
+        Some((first, _)) => Err(Error::Dangling { line: first }),
+        None => Ok(()),
+    }
+}
+
+/// Adds annotations to a compiled eFLINT JSON policy.
+///
+/// # Arguments
+/// - `policy`: The policy to add them to. Left as-is if it is not a JSON object or if there are no annotations.
+/// - `annotations`: The [`Annotations`] to add.
+pub fn attach(policy: &mut Value, annotations: &Annotations) {
+    if annotations.is_empty() {
+        return;
+    }
+    if let (Value::Object(policy), Ok(annotations)) = (policy, serde_json::to_value(annotations)) {
+        policy.insert(ANNOTATIONS_FIELD.into(), annotations);
+    }
+}
+
+/// Reads the annotations from a compiled eFLINT JSON policy.
+///
+/// # Arguments
+/// - `policy`: The policy, as raw JSON.
+///
+/// # Returns
+/// The [`Annotations`] in the policy, which are none if it has none (or is not valid JSON).
+pub fn annotations_of(policy: &str) -> Annotations {
+    #[derive(Deserialize)]
+    struct Annotated {
+        #[serde(default)]
+        annotations: Annotations,
+    }
+    serde_json::from_str::<Annotated>(policy).map(|annotated| annotated.annotations).unwrap_or_default()
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let source = "Fact user.\n//@ severity: high\n// Ordinary comment\n//@ public\n//@ message: consent-missing\n\nInvariant no-consent When \
+                      Holds(user).\n//@ private\nExtend Act share.\n";
+        let mut annotations = Annotations::new();
+        extract(source, &mut annotations).unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations["no-consent"], Annotation {
+            severity: Some(Severity::High),
+            public:   Some(true),
+            message:  Some("consent-missing".into()),
+        });
+        assert_eq!(annotations["share"].public, Some(false));
+
+        assert!(matches!(extract("//@ public\n+user(amy).\n", &mut Annotations::new()), Err(Error::Dangling { line: 1 })));
+        assert!(matches!(extract("//@ severity: dire\nFact x.\n", &mut Annotations::new()), Err(Error::IllegalSeverity { line: 1, .. })));
+        assert!(matches!(extract("//@ public\n//@ private\nFact x.\n", &mut Annotations::new()), Err(Error::Duplicate { line: 2, .. })));
+        assert!(matches!(extract("//@ loud\nFact x.\n", &mut Annotations::new()), Err(Error::Unknown { line: 1, .. })));
+        assert!(matches!(extract("//@ public\nFact x.\n//@ private\nFact x.\n", &mut annotations.clone()), Err(Error::Reannotated { line: 4, .. })));
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut annotations = Annotations::new();
+        annotations.insert("no-consent".into(), Annotation { severity: Some(Severity::Low), ..Default::default() });
+        let mut policy: Value = serde_json::json!({ "version": "0.1.0", "kind": "phrases", "phrases": [] });
+        attach(&mut policy, &annotations);
+        assert_eq!(annotations_of(&policy.to_string()), annotations);
+        assert!(annotations_of("{\"phrases\": []}").is_empty());
+    }
+}
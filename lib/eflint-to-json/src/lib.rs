@@ -15,24 +15,31 @@
 //
 
 // Declare modules
+pub mod annotate;
 pub mod download;
+pub mod resolve;
+pub mod sourcemap;
+pub mod template;
 
 use std::borrow::Cow;
 use std::collections::HashSet;
 use std::error;
 use std::fmt::{Display, Formatter, Result as FResult};
-use std::fs::{self, File, Permissions};
-use std::io::{BufRead as _, BufReader, Read, Write};
+use std::fs::{self, Permissions};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
 
 use console::Style;
 use log::{debug, info};
 use tokio::fs::{self as tfs, File as TFile};
-use tokio::io::{AsyncBufReadExt as _, AsyncReadExt, AsyncWriteExt as _, BufReader as TBufReader};
+use tokio::io::{AsyncReadExt, AsyncWriteExt as _, BufReader as TBufReader};
 use tokio::process::{Child as TChild, ChildStdin as TChildStdin, ChildStdout as TChildStdout, Command as TCommand};
 
+use crate::annotate::Annotations;
 use crate::download::{DownloadSecurity, download_file, download_file_async};
+use crate::resolve::{FileResolver, IncludeResolver, Location};
+use crate::sourcemap::SourceMap;
 
 /***** CONSTANTS *****/
 /// Compiler download URL.
@@ -108,6 +115,8 @@ impl error::Error for ChildStream {}
 /// Defines toplevel errors.
 #[derive(Debug)]
 pub enum Error {
+    /// Failed to read the annotations in a file.
+    Annotation { path: PathBuf, err: crate::annotate::Error },
     /// The child failed
     ChildFailed { cmd: String, status: ExitStatus, output: ChildStreams },
     /// Failed to read from child stdout.
@@ -120,6 +129,10 @@ pub enum Error {
     ///
     /// NOTE: `err` is boxed to not make this variant much larger in memory than the rest.
     CompilerDownload { from: String, to: PathBuf, err: Box<crate::download::Error> },
+    /// Failed to fetch a source.
+    ///
+    /// NOTE: `err` is boxed to not make this variant much larger in memory than the rest.
+    Fetch { location: Location, err: Box<crate::resolve::Error> },
     /// Failed to create the output file.
     FileCreate { path: PathBuf, err: std::io::Error },
     /// Failed to get metadata of file.
@@ -130,14 +143,24 @@ pub enum Error {
     FilePermissions { path: PathBuf, err: std::io::Error },
     /// Failed to read the input file.
     FileRead { path: PathBuf, err: std::io::Error },
+    /// Failed to locate an included source.
+    ///
+    /// NOTE: `err` is boxed to not make this variant much larger in memory than the rest.
+    Include { parent: Location, target: String, err: Box<crate::resolve::Error> },
+    /// A source includes itself, possibly through others.
+    IncludeCycle { chain: Vec<Location> },
     /// Failed to open included file.
     IncludeOpen { parent: PathBuf, path: PathBuf, err: std::io::Error },
     /// Missing a quote in the `#include`-string.
     MissingQuote { parent: PathBuf, raw: String },
+    /// Failed to parse the output of the compiler to add annotations and the source map to it.
+    OutputParse { err: serde_json::Error },
     /// Failed to canonicalize the given path.
     PathCanonicalize { parent: PathBuf, path: PathBuf, err: std::io::Error },
     /// Failed to spawn the eflint-to-json compiler process.
     Spawn { cmd: String, err: std::io::Error },
+    /// Failed to expand the templates in a file.
+    Template { path: PathBuf, err: crate::template::Error },
     /// Failed to write to the output writer.
     WriterWrite { err: std::io::Error },
 }
@@ -145,20 +168,28 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
         use Error::*;
         match self {
+            Annotation { path, .. } => write!(f, "Failed to read annotations in file '{}'", path.display()),
             ChildFailed { cmd, status, .. } => write!(f, "Child process {cmd:?} failed with exit status {status}"),
             ChildRead { .. } => write!(f, "Failed to read from child stdin"),
             ChildWait { .. } => write!(f, "Failed to wait for child"),
             ChildWrite { .. } => write!(f, "Failed to write to child stdin"),
             CompilerDownload { from, to, .. } => write!(f, "Failed to download 'eflint-to-json' compiler from '{}' to '{}'", from, to.display()),
+            Fetch { location, .. } => write!(f, "Failed to fetch source '{location}'"),
             FileCreate { path, .. } => write!(f, "Failed to create output file '{}'", path.display()),
             FileMetadata { path, .. } => write!(f, "Failed to get metadata of file '{}'", path.display()),
             FileOpen { path, .. } => write!(f, "Failed to open input file '{}'", path.display()),
             FilePermissions { path, .. } => write!(f, "Failed to set permissions of file '{}'", path.display()),
             FileRead { path, .. } => write!(f, "Failed to read from input file '{}'", path.display()),
+            Include { parent, target, .. } => write!(f, "Failed to locate included source '{target}' (in source '{parent}')"),
+            IncludeCycle { chain } => {
+                write!(f, "Source includes itself: {}", chain.iter().map(|location| format!("'{location}'")).collect::<Vec<String>>().join(" -> "))
+            },
             IncludeOpen { parent, path, .. } => write!(f, "Failed to open included file '{}' (in file '{}')", path.display(), parent.display()),
             MissingQuote { parent, raw } => write!(f, "Missing quotes (\") in '{}' (in file '{}')", raw, parent.display()),
+            OutputParse { .. } => write!(f, "Failed to parse compiler output as eFLINT JSON"),
             PathCanonicalize { parent, path, .. } => write!(f, "Failed to canonicalize path '{}' (in file '{}')", path.display(), parent.display()),
             Spawn { cmd, .. } => write!(f, "Failed to spawn command {cmd:?}"),
+            Template { path, .. } => write!(f, "Failed to expand templates in file '{}'", path.display()),
             WriterWrite { .. } => write!(f, "Failed to write to output writer"),
         }
     }
@@ -167,39 +198,44 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         use Error::*;
         match self {
+            Annotation { err, .. } => Some(err),
             ChildFailed { output, .. } => Some(output),
             ChildRead { err, .. } => Some(err),
             ChildWait { err, .. } => Some(err),
             ChildWrite { err, .. } => Some(err),
             CompilerDownload { err, .. } => Some(err),
+            Fetch { err, .. } => Some(err),
             FileCreate { err, .. } => Some(err),
             FileMetadata { err, .. } => Some(err),
             FileOpen { err, .. } => Some(err),
             FilePermissions { err, .. } => Some(err),
             FileRead { err, .. } => Some(err),
+            Include { err, .. } => Some(err),
+            IncludeCycle { .. } => None,
             IncludeOpen { err, .. } => Some(err),
             MissingQuote { .. } => None,
+            OutputParse { err } => Some(err),
             PathCanonicalize { err, .. } => Some(err),
             Spawn { err, .. } => Some(err),
+            Template { err, .. } => Some(err),
             WriterWrite { err, .. } => Some(err),
         }
     }
 }
 
 /***** HELPER FUNCTIONS *****/
-/// Analyses a potential `#input(...)` or `#require(...)` line from eFLINT.
+/// Extracts what a potential `#include` or `#require` line from eFLINT includes.
 ///
 /// # Arguments
-/// - `imported`: The set of already imported files (relevant for require).
-/// - `path`: The path of the current file.
+/// - `path`: The path (or other location) of the current file, for debugging purposes.
 /// - `line`: The parsed line.
 ///
 /// # Returns
-/// A handle to the included file (as a tuple of the path + the handle) if any, or else [`None`].
+/// Whether the line is a `#require` and the (quoted) location it includes, or [`None`] if it is neither.
 ///
 /// # Errors
-/// This function can error if we failed to open the included file.
-fn potentially_include(imported: &mut HashSet<PathBuf>, path: &Path, line: &str) -> Result<Option<Option<(PathBuf, File)>>, Error> {
+/// This function errors if the included location is not quoted.
+fn include_target<'l>(path: &Path, line: &'l str) -> Result<Option<(bool, &'l str)>, Error> {
     // Strip whitespace
     let line: &str = line.trim();
 
@@ -214,35 +250,24 @@ fn potentially_include(imported: &mut HashSet<PathBuf>, path: &Path, line: &str)
         None => return Err(Error::MissingQuote { parent: path.into(), raw: line.into() }),
     };
     let equote: usize = match line.rfind('"') {
-        Some(pos) => pos,
-        None => return Err(Error::MissingQuote { parent: path.into(), raw: line.into() }),
-    };
-    let incl_path: PathBuf = PathBuf::from(&line[squote + 1..equote]);
-
-    // Build the path
-    let parent: Option<&Path> = path.parent();
-    // NOTE: Allowing the `is_none()`, `unwrap()` because else we ruin the logic
-    #[allow(clippy::unnecessary_unwrap)]
-    let incl_path: PathBuf = if incl_path.is_absolute() || parent.is_none() { incl_path } else { parent.unwrap().join(incl_path) };
-    let incl_path: PathBuf = match incl_path.canonicalize() {
-        Ok(path) => path,
-        Err(err) => return Err(Error::PathCanonicalize { parent: path.into(), path: incl_path, err }),
+        Some(pos) if pos > squote => pos,
+        _ => return Err(Error::MissingQuote { parent: path.into(), raw: line.into() }),
     };
+    Ok(Some((&line[..8] == "#require", &line[squote + 1..equote])))
+}
 
-    // Check if we've seen this before if it's require
-    if &line[..8] == "#require" && imported.contains(&incl_path) {
-        return Ok(Some(None));
+/// Finds where to start including from, such that includes of it are recognized as cycles.
+///
+/// # Arguments
+/// - `input`: The [`Location`] given to compile or expand.
+///
+/// # Returns
+/// The `input`, canonicalized if it is a file that exists.
+fn root(input: &Location) -> Location {
+    match input {
+        Location::File(path) => Location::File(path.canonicalize().unwrap_or_else(|_| path.clone())),
+        input => input.clone(),
     }
-    imported.insert(incl_path.clone());
-
-    // Build the path and attempt to open it
-    let handle: File = match File::open(&incl_path) {
-        Ok(handle) => handle,
-        Err(err) => return Err(Error::IncludeOpen { parent: path.into(), path: incl_path, err }),
-    };
-
-    // OK
-    Ok(Some(Some((incl_path, handle))))
 }
 
 /// Analyses a potential `#input(...)` or `#require(...)` line from eFLINT.
@@ -258,24 +283,9 @@ fn potentially_include(imported: &mut HashSet<PathBuf>, path: &Path, line: &str)
 /// # Errors
 /// This function can error if we failed to open the included file.
 async fn potentially_include_async(imported: &mut HashSet<PathBuf>, path: &Path, line: &str) -> Result<Option<Option<(PathBuf, TFile)>>, Error> {
-    // Strip whitespace
-    let line: &str = line.trim();
-
-    // Check it's a line
-    if line.len() < 8 || (&line[..8] != "#include" && &line[..8] != "#require") || line.chars().last().map(|c| c != '.').unwrap_or(true) {
-        return Ok(None);
-    }
-
     // Extract the text
-    let squote: usize = match line.find('"') {
-        Some(pos) => pos,
-        None => return Err(Error::MissingQuote { parent: path.into(), raw: line.into() }),
-    };
-    let equote: usize = match line.rfind('"') {
-        Some(pos) => pos,
-        None => return Err(Error::MissingQuote { parent: path.into(), raw: line.into() }),
-    };
-    let incl_path: PathBuf = PathBuf::from(&line[squote + 1..equote]);
+    let Some((require, incl_path)) = include_target(path, line)? else { return Ok(None) };
+    let incl_path: PathBuf = PathBuf::from(incl_path);
 
     // Build the path
     let parent: Option<&Path> = path.parent();
@@ -288,7 +298,7 @@ async fn potentially_include_async(imported: &mut HashSet<PathBuf>, path: &Path,
     };
 
     // Check if we've seen this before if it's require
-    if &line[..8] == "#require" && imported.contains(&incl_path) {
+    if require && imported.contains(&incl_path) {
         return Ok(Some(None));
     }
     imported.insert(incl_path.clone());
@@ -303,34 +313,57 @@ async fn potentially_include_async(imported: &mut HashSet<PathBuf>, path: &Path,
     Ok(Some(Some((incl_path, handle))))
 }
 
-/// Streams the given file's contents to the stdin of the given process, including files as necessary halfway.
+/// Streams the given source to the stdin of the given process, including other sources and expanding templates as necessary halfway.
 ///
 /// # Arguments
-/// - `imported`: The set of already imported files (relevant for require).
-/// - `path`: The path of the file we're currently importing. Only used for debugging purposes.
-/// - `handle`: Handle to the [`File`] we're going to read.
-/// - `child`: The [`ChildStdin`] to write the stream of input files to.
+/// - `resolver`: The [`IncludeResolver`] to fetch the source and those it includes with.
+/// - `imported`: The set of already imported sources (relevant for require).
+/// - `stack`: The sources that are being imported, i.e., those that (transitively) include this one (relevant for cycles).
+/// - `location`: The [`Location`] of the source we're currently importing.
+/// - `child`: The [`Write`]r (e.g., a [`ChildStdin`]) to write the stream of sources to.
+/// - `annotations`: The [`Annotations`] to add those in the source to.
+/// - `source_map`: The [`SourceMap`] to record the lines written to the `child` in.
 ///
 /// # Errors
-/// This function may error if we at any point failed to open/read a file, found `#include`s or `#require`s pointing to non-existant files or if we could not write to the `child`.
-fn load_input(imported: &mut HashSet<PathBuf>, path: &Path, handle: BufReader<File>, child: &mut ChildStdin) -> Result<(), Error> {
-    debug!("Importing file '{}'", path.display());
-
-    // Read the lines for the file
-    for line in handle.lines() {
-        // Unwrap the line
-        let line: String = match line {
-            Ok(line) => line,
-            Err(err) => return Err(Error::FileRead { path: path.into(), err }),
-        };
-
-        // See if a file is included
-        match potentially_include(imported, path, &line)? {
-            Some(Some((child_path, child_handle))) => {
-                load_input(imported, &child_path, BufReader::new(child_handle), child)?;
+/// This function may error if we at any point failed to fetch a source, found `#include`s or `#require`s that include themselves (possibly through others), found malformed templates or annotations or if we could not write to the `child`.
+fn load_source(
+    resolver: &dyn IncludeResolver,
+    imported: &mut HashSet<Location>,
+    stack: &mut Vec<Location>,
+    location: &Location,
+    child: &mut impl Write,
+    annotations: &mut Annotations,
+    source_map: &mut SourceMap,
+) -> Result<(), Error> {
+    debug!("Importing source '{location}'");
+
+    // Fetch the source, expanding any templates in it
+    // NOTE: Templates in sources that are not files use CSV files relative to the current working directory
+    let display: String = location.to_string();
+    let name: PathBuf = PathBuf::from(&display);
+    let source: String = resolver.fetch(location).map_err(|err| Error::Fetch { location: location.clone(), err: Box::new(err) })?;
+    let source: Cow<str> =
+        template::expand(location.as_path().unwrap_or(Path::new("")), &source).map_err(|err| Error::Template { path: name.clone(), err })?;
+    annotate::extract(&source, annotations).map_err(|err| Error::Annotation { path: name.clone(), err })?;
+
+    // Go through the lines of the source
+    stack.push(location.clone());
+    for (i, line) in source.lines().enumerate() {
+        // See if another source is included
+        match include_target(&name, line)? {
+            Some((require, target)) => {
+                let incl: Location =
+                    location.join(target).map_err(|err| Error::Include { parent: location.clone(), target: target.into(), err: Box::new(err) })?;
+                // We don't want to write the line if we already imported it
+                if require && imported.contains(&incl) {
+                    continue;
+                }
+                if stack.contains(&incl) {
+                    return Err(Error::IncludeCycle { chain: stack.iter().cloned().chain([incl]).collect() });
+                }
+                imported.insert(incl.clone());
+                load_source(resolver, imported, stack, &incl, child, annotations, source_map)?;
             },
-            // We don't want to write the line since we already imported it
-            Some(None) => {},
             None => {
                 if let Err(err) = child.write_all(line.as_bytes()) {
                     return Err(Error::ChildWrite { err });
@@ -338,41 +371,60 @@ fn load_input(imported: &mut HashSet<PathBuf>, path: &Path, handle: BufReader<Fi
                 if let Err(err) = child.write_all(b"\n") {
                     return Err(Error::ChildWrite { err });
                 }
+                source_map.record(&display, i + 1, line);
             },
         }
     }
+    stack.pop();
 
     // Done!
     Ok(())
 }
 
-/// Streams the given file's contents to the stdin of the given process, including files as necessary halfway.
+/// Streams the given file's contents to the stdin of the given process, including files and expanding templates as necessary halfway.
 ///
 /// # Arguments
 /// - `imported`: The set of already imported files (relevant for require).
-/// - `path`: The path of the file we're currently importing. Only used for debugging purposes.
+/// - `stack`: The files that are being imported, i.e., those that (transitively) include this one (relevant for cycles).
+/// - `path`: The path of the file we're currently importing. Used to resolve templates and for debugging purposes.
 /// - `handle`: Handle to the [`TFile`]we're going to read.
 /// - `child`: The [`TChildStdin`] to write the stream of input files to.
+/// - `annotations`: The [`Annotations`] to add those in the file to.
+/// - `source_map`: The [`SourceMap`] to record the lines written to the `child` in.
 ///
 /// # Errors
-/// This function may error if we at any point failed to open/read a file, found `#include`s or `#require`s pointing to non-existant files or if we could not write to the `child`.
+/// This function may error if we at any point failed to open/read a file, found `#include`s or `#require`s pointing to non-existant files or including themselves (possibly through others), found malformed templates or annotations or if we could not write to the `child`.
 #[async_recursion::async_recursion]
-async fn load_input_async(imported: &mut HashSet<PathBuf>, path: &Path, handle: TBufReader<TFile>, child: &mut TChildStdin) -> Result<(), Error> {
+async fn load_input_async(
+    imported: &mut HashSet<PathBuf>,
+    stack: &mut Vec<PathBuf>,
+    path: &Path,
+    mut handle: TBufReader<TFile>,
+    child: &mut TChildStdin,
+    annotations: &mut Annotations,
+    source_map: &mut SourceMap,
+) -> Result<(), Error> {
     debug!("Importing file '{}'", path.display());
 
-    // Read the lines for the file
-    let mut lines = handle.lines();
-    while let Some(line) = lines.next_line().await.transpose() {
-        // Unwrap the line
-        let line: String = match line {
-            Ok(line) => line,
-            Err(err) => return Err(Error::FileRead { path: path.into(), err }),
-        };
+    // Read the file, expanding any templates in it
+    let mut source: String = String::new();
+    if let Err(err) = handle.read_to_string(&mut source).await {
+        return Err(Error::FileRead { path: path.into(), err });
+    }
+    let source: String = template::expand(path, &source).map_err(|err| Error::Template { path: path.into(), err })?.into_owned();
+    annotate::extract(&source, annotations).map_err(|err| Error::Annotation { path: path.into(), err })?;
 
+    // Go through the lines of the file
+    let display: String = path.display().to_string();
+    stack.push(path.into());
+    for (i, line) in source.lines().enumerate() {
         // See if a file is included
-        match potentially_include_async(imported, path, &line).await? {
+        match potentially_include_async(imported, path, line).await? {
             Some(Some((child_path, child_handle))) => {
-                load_input_async(imported, &child_path, TBufReader::new(child_handle), child).await?;
+                if stack.contains(&child_path) {
+                    return Err(Error::IncludeCycle { chain: stack.iter().cloned().chain([child_path]).map(Location::File).collect() });
+                }
+                load_input_async(imported, stack, &child_path, TBufReader::new(child_handle), child, annotations, source_map).await?;
             },
             // We don't want to write the line since we already imported it
             Some(None) => {},
@@ -383,18 +435,53 @@ async fn load_input_async(imported: &mut HashSet<PathBuf>, path: &Path, handle:
                 if let Err(err) = child.write_all(b"\n").await {
                     return Err(Error::ChildWrite { err });
                 }
+                source_map.record(&display, i + 1, line);
             },
         }
     }
+    stack.pop();
 
     // Done!
     Ok(())
 }
 
 /***** LIBRARY *****/
+/// Resolves the `#include`s, `#require`s and templates in a (tree of) `.eflint` files, without compiling them.
+///
+/// This is what [`compile()`] gives to the compiler, and is mostly useful to see what templates expand to.
+///
+/// # Arguments
+/// - `input`: The input file to expand. Any `#include`s and `#require`s will be handled, building a tree of files to import.
+/// - `output`: Some writer to write the expanded eFLINT to.
+///
+/// # Errors
+/// This function errors if we failed to read any of the files, if any template is malformed or if we failed to write to the `output`.
+#[inline]
+pub fn expand(input_path: &Path, output: impl Write) -> Result<(), Error> { expand_with(&FileResolver, &Location::File(input_path.into()), output) }
+
+/// Resolves the `#include`s, `#require`s and templates in a (tree of) eFLINT sources, without compiling them.
+///
+/// Like [`expand()`], but fetches the sources with the given [`IncludeResolver`] (see [`resolve`]).
+///
+/// # Arguments
+/// - `resolver`: The [`IncludeResolver`] to fetch the input and the sources it includes with.
+/// - `input`: The [`Location`] of the input to expand.
+/// - `output`: Some writer to write the expanded eFLINT to.
+///
+/// # Errors
+/// This function errors if we failed to fetch any of the sources, if any template is malformed or if we failed to write to the `output`.
+pub fn expand_with(resolver: &dyn IncludeResolver, input: &Location, mut output: impl Write) -> Result<(), Error> {
+    info!("Expanding input at '{input}'");
+
+    let mut included: HashSet<Location> = HashSet::new();
+    load_source(resolver, &mut included, &mut vec![], &root(input), &mut output, &mut Annotations::new(), &mut SourceMap::new())
+}
+
 /// Compiles a (tree of) `.eflint` files using Olaf's `eflint-to-json` compiler.
 ///
-/// Resolves relative paths in the files as relative to the file in which they occur.
+/// Resolves relative paths in the files as relative to the file in which they occur. Any `//@` annotations on
+/// declarations (see [`annotate`]) are added to the output, together with a source map that traces it back to the
+/// lines of the files (see [`sourcemap`]).
 ///
 /// # Arguments
 /// - `input`: The input file to compile. Any `#include`s and `#require`s will be handled, building a tree of files to import.
@@ -403,8 +490,26 @@ async fn load_input_async(imported: &mut HashSet<PathBuf>, path: &Path, handle:
 ///
 /// # Errors
 /// This function may error for a plethora of reasons.
-pub fn compile(input_path: &Path, mut output: impl Write, compiler_path: Option<&Path>) -> Result<(), Error> {
-    info!("Compiling input at '{}'", input_path.display());
+#[inline]
+pub fn compile(input_path: &Path, output: impl Write, compiler_path: Option<&Path>) -> Result<(), Error> {
+    compile_with(&FileResolver, &Location::File(input_path.into()), output, compiler_path)
+}
+
+/// Compiles a (tree of) eFLINT sources using Olaf's `eflint-to-json` compiler.
+///
+/// Like [`compile()`], but fetches the sources with the given [`IncludeResolver`] (see [`resolve`]), e.g., to compile
+/// policies kept in a git repository or sent to a server. Sources are streamed to the compiler as they are fetched.
+///
+/// # Arguments
+/// - `resolver`: The [`IncludeResolver`] to fetch the input and the sources it includes with.
+/// - `input`: The [`Location`] of the input to compile.
+/// - `output`: Some writer to compile to.
+/// - `compiler`: If given, will not download a compiler to `/tmp/eflint-to-json` but will instead use the given one.
+///
+/// # Errors
+/// This function may error for a plethora of reasons.
+pub fn compile_with(resolver: &dyn IncludeResolver, input: &Location, mut output: impl Write, compiler_path: Option<&Path>) -> Result<(), Error> {
+    info!("Compiling input at '{input}'");
 
     // Resolve the compiler
     let compiler_path: Cow<Path> = match compiler_path {
@@ -447,13 +552,6 @@ pub fn compile(input_path: &Path, mut output: impl Write, compiler_path: Option<
     };
     debug!("Using compiler at: '{}'", compiler_path.display());
 
-    // Open the input file
-    debug!("Opening input file '{}'", input_path.display());
-    let input: File = match File::open(input_path) {
-        Ok(input) => input,
-        Err(err) => return Err(Error::FileOpen { path: input_path.into(), err }),
-    };
-
     // Alrighty well open a handle to the compiler
     debug!("Spawning compiler '{}'", compiler_path.display());
     let mut cmd: Command = Command::new(compiler_path.to_string_lossy().as_ref());
@@ -468,8 +566,10 @@ pub fn compile(input_path: &Path, mut output: impl Write, compiler_path: Option<
     // Feed the input to the compiler, analyzing for `#input(...)` and `#require(...)`
     debug!("Reading input to child process...");
     let mut stdin: ChildStdin = handle.stdin.take().unwrap();
-    let mut included: HashSet<PathBuf> = HashSet::new();
-    load_input(&mut included, input_path, BufReader::new(input), &mut stdin)?;
+    let mut included: HashSet<Location> = HashSet::new();
+    let mut annotations: Annotations = Annotations::new();
+    let mut source_map: SourceMap = SourceMap::new();
+    load_source(resolver, &mut included, &mut vec![], &root(input), &mut stdin, &mut annotations, &mut source_map)?;
     drop(stdin);
 
     // Wait until the process is finished
@@ -485,14 +585,23 @@ pub fn compile(input_path: &Path, mut output: impl Write, compiler_path: Option<
             output: ChildStreams(vec![
                 ChildStream::new("stdout", handle.stdout.take().unwrap()),
                 ChildStream::new("stderr", handle.stderr.take().unwrap()),
+                ChildStream("source map", source_map.to_string()),
             ]),
         });
     }
 
     // Alrighty, now it's time to stream the output of the child to the output file
     debug!("Writing child process output to given output...");
-    let mut chunk: [u8; 65535] = [0; 65535];
     let mut stdout: ChildStdout = handle.stdout.take().unwrap();
+    if !annotations.is_empty() || !source_map.is_empty() {
+        // The annotations and source map are added to the output, so it cannot be streamed as-is
+        debug!("Adding {} annotation(s) and source map to child process output...", annotations.len());
+        let mut policy: serde_json::Value = serde_json::from_reader(&mut stdout).map_err(|err| Error::OutputParse { err })?;
+        annotate::attach(&mut policy, &annotations);
+        sourcemap::attach(&mut policy, &source_map);
+        return serde_json::to_writer(&mut output, &policy).map_err(|err| Error::WriterWrite { err: err.into() });
+    }
+    let mut chunk: [u8; 65535] = [0; 65535];
     loop {
         // Read the next chunk
         let chunk_len: usize = match stdout.read(&mut chunk) {
@@ -515,7 +624,9 @@ pub fn compile(input_path: &Path, mut output: impl Write, compiler_path: Option<
 
 /// Compiles a (tree of) `.eflint` files using Olaf's `eflint-to-json` compiler.
 ///
-/// Resolves relative paths in the files as relative to the file in which they occur.
+/// Resolves relative paths in the files as relative to the file in which they occur. Any `//@` annotations on
+/// declarations (see [`annotate`]) are added to the output, together with a source map that traces it back to the
+/// lines of the files (see [`sourcemap`]).
 ///
 /// # Arguments
 /// - `input`: The input file to compile. Any `#include`s and `#require`s will be handled, building a tree of files to import.
@@ -592,7 +703,11 @@ pub async fn compile_async(input_path: &Path, mut output: impl Write, compiler_p
     debug!("Reading input to child process...");
     let mut stdin: TChildStdin = handle.stdin.take().unwrap();
     let mut included: HashSet<PathBuf> = HashSet::new();
-    load_input_async(&mut included, input_path, TBufReader::new(input), &mut stdin).await?;
+    let mut stack: Vec<PathBuf> = vec![];
+    let mut annotations: Annotations = Annotations::new();
+    let mut source_map: SourceMap = SourceMap::new();
+    let root: PathBuf = tfs::canonicalize(input_path).await.unwrap_or_else(|_| input_path.into());
+    load_input_async(&mut included, &mut stack, &root, TBufReader::new(input), &mut stdin, &mut annotations, &mut source_map).await?;
     drop(stdin);
 
     // Wait until the process is finished
@@ -608,14 +723,27 @@ pub async fn compile_async(input_path: &Path, mut output: impl Write, compiler_p
             output: ChildStreams(vec![
                 ChildStream::new_async("stdout", handle.stdout.take().unwrap()).await,
                 ChildStream::new_async("stderr", handle.stderr.take().unwrap()).await,
+                ChildStream("source map", source_map.to_string()),
             ]),
         });
     }
 
     // Alrighty, now it's time to stream the output of the child to the output file
     debug!("Writing child process output to given output...");
-    let mut chunk: [u8; 65535] = [0; 65535];
     let mut stdout: TChildStdout = handle.stdout.take().unwrap();
+    if !annotations.is_empty() || !source_map.is_empty() {
+        // The annotations and source map are added to the output, so it cannot be streamed as-is
+        debug!("Adding {} annotation(s) and source map to child process output...", annotations.len());
+        let mut raw: Vec<u8> = Vec::new();
+        if let Err(err) = stdout.read_to_end(&mut raw).await {
+            return Err(Error::ChildRead { err });
+        }
+        let mut policy: serde_json::Value = serde_json::from_slice(&raw).map_err(|err| Error::OutputParse { err })?;
+        annotate::attach(&mut policy, &annotations);
+        sourcemap::attach(&mut policy, &source_map);
+        return serde_json::to_writer(&mut output, &policy).map_err(|err| Error::WriterWrite { err: err.into() });
+    }
+    let mut chunk: [u8; 65535] = [0; 65535];
     loop {
         // Read the next chunk
         let chunk_len: usize = match stdout.read(&mut chunk).await {
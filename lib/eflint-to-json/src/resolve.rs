@@ -0,0 +1,535 @@
+//! Implements fetching the files that `#include`s and `#require`s refer to from other places than the local disk.
+//!
+//! Every eFLINT source has a [`Location`], written in `#include`s and `#require`s as:
+//! - a path, like `"base.eflint"` or `"/policies/base.eflint"`;
+//! - an HTTP(S) URL, like `"https://example.org/policies/base.eflint"`;
+//! - a file in a git repository, like `"git+https://github.com/org/policies.git#v1.2:eflint/base.eflint"` (i.e.,
+//!   `git+<REPO>#<REVISION>:<PATH>`); or
+//! - a named source kept in memory, like `"mem:base.eflint"`.
+//!
+//! Paths are relative to the source they occur in, whatever kind it is: a file in a git repository including
+//! `"../common.eflint"` gets that file from the same repository and revision. Sources that are not files cannot refer to
+//! files on disk, and never above the root of their host, repository or namespace.
+//!
+//! What a location contains is fetched by an [`IncludeResolver`]. There is one for every kind of location, which can
+//! be combined with [`Resolvers`] and cached with a [`CachingResolver`].
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::Mutex;
+use std::{error, fs};
+
+use log::debug;
+use sha2::{Digest as _, Sha256};
+
+/***** ERRORS *****/
+/// Defines errors that originate from locating or fetching eFLINT sources.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to canonicalize the path of a file.
+    PathCanonicalize { path: PathBuf, err: std::io::Error },
+    /// Failed to read a file.
+    FileRead { path: PathBuf, err: std::io::Error },
+    /// A relative location goes above the root of its host, repository or namespace.
+    Escapes { target: String },
+    /// A `git+` location is not of the form `git+<REPO>#<REVISION>:<PATH>`.
+    IllegalGit { raw: String },
+    /// Refused to download a source over plain HTTP.
+    InsecureUrl { url: String },
+    /// Failed to download a source.
+    Download { url: String, err: reqwest::Error },
+    /// Failed to run git.
+    GitSpawn { cmd: String, err: std::io::Error },
+    /// Git failed.
+    GitFailed { cmd: String, stderr: String },
+    /// A source kept in memory does not exist.
+    UnknownSource { name: String },
+    /// No resolver can fetch this kind of location.
+    Unsupported { location: Location },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            PathCanonicalize { path, .. } => write!(f, "Failed to canonicalize path '{}'", path.display()),
+            FileRead { path, .. } => write!(f, "Failed to read file '{}'", path.display()),
+            Escapes { target } => write!(f, "Location '{target}' goes above the root of the source it occurs in"),
+            IllegalGit { raw } => write!(f, "Location '{raw}' is not of the form 'git+<REPO>#<REVISION>:<PATH>'"),
+            InsecureUrl { url } => write!(f, "Refusing to download '{url}' over plain HTTP"),
+            Download { url, .. } => write!(f, "Failed to download '{url}'"),
+            GitSpawn { cmd, .. } => write!(f, "Failed to spawn command {cmd:?}"),
+            GitFailed { cmd, stderr } => write!(f, "Command {cmd:?} failed: {}", stderr.trim()),
+            UnknownSource { name } => write!(f, "Unknown in-memory source '{name}'"),
+            Unsupported { location } => write!(f, "Cannot fetch '{location}' (no resolver for this kind of location)"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            PathCanonicalize { err, .. } => Some(err),
+            FileRead { err, .. } => Some(err),
+            Escapes { .. } => None,
+            IllegalGit { .. } => None,
+            InsecureUrl { .. } => None,
+            Download { err, .. } => Some(err),
+            GitSpawn { err, .. } => Some(err),
+            GitFailed { .. } => None,
+            UnknownSource { .. } => None,
+            Unsupported { .. } => None,
+        }
+    }
+}
+
+/***** HELPER FUNCTIONS *****/
+/// Returns whether a location is written with a scheme (and is therefore not relative to anything).
+#[inline]
+fn has_scheme(raw: &str) -> bool { ["mem:", "git+", "http://", "https://"].iter().any(|scheme| raw.starts_with(scheme)) }
+
+/// Resolves a `/`-separated path relative to another one, without touching the file system.
+///
+/// # Arguments
+/// - `base`: The path of the source the `target` occurs in.
+/// - `target`: The path to resolve. If it starts with a `/`, it is relative to the root instead of `base`.
+///
+/// # Returns
+/// The normalized path, without a leading `/`.
+///
+/// # Errors
+/// This function errors if the path goes above the root.
+fn relative(base: &str, target: &str) -> Result<String, Error> {
+    let dir: &str = if target.starts_with('/') { "" } else { base.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("") };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in dir.split('/').chain(target.split('/')) {
+        match segment {
+            "" | "." => {},
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(Error::Escapes { target: target.into() });
+                }
+            },
+            segment => segments.push(segment),
+        }
+    }
+    Ok(segments.join("/"))
+}
+
+/// Splits a URL in its origin (e.g., `https://example.org`) and its path.
+fn split_url(url: &str) -> (&str, &str) {
+    let start: usize = url.find("://").map(|pos| pos + 3).unwrap_or(0);
+    match url[start..].find('/') {
+        Some(pos) => url.split_at(start + pos),
+        None => (url, ""),
+    }
+}
+
+/***** AUXILLARY *****/
+/// Identifies an eFLINT source.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Location {
+    /// A file on the local disk.
+    File(PathBuf),
+    /// A file to download over HTTP(S).
+    Url(String),
+    /// A file in a git repository.
+    Git { repo: String, rev: String, path: String },
+    /// A source kept in memory by a [`MemoryResolver`].
+    Memory(String),
+}
+impl Location {
+    /// Parses a location as written in an `#include` or `#require`.
+    ///
+    /// # Arguments
+    /// - `raw`: The location. Anything without a scheme is a path on the local disk.
+    ///
+    /// # Returns
+    /// A new Location. Paths are taken as-is, i.e., relative to the current working directory.
+    ///
+    /// # Errors
+    /// This function errors if a `git+` location is malformed, or if the path of a non-file location goes above its root.
+    pub fn parse(raw: &str) -> Result<Self, Error> {
+        if let Some(name) = raw.strip_prefix("mem:") {
+            return Ok(Self::Memory(relative("", name)?));
+        }
+        if let Some(spec) = raw.strip_prefix("git+") {
+            let (repo, rest): (&str, &str) = spec.rsplit_once('#').ok_or_else(|| Error::IllegalGit { raw: raw.into() })?;
+            let (rev, path): (&str, &str) = rest.split_once(':').ok_or_else(|| Error::IllegalGit { raw: raw.into() })?;
+            if repo.is_empty() || rev.is_empty() || path.is_empty() {
+                return Err(Error::IllegalGit { raw: raw.into() });
+            }
+            return Ok(Self::Git { repo: repo.into(), rev: rev.into(), path: relative("", path)? });
+        }
+        if raw.starts_with("http://") || raw.starts_with("https://") {
+            let (origin, path): (&str, &str) = split_url(raw);
+            return Ok(Self::Url(format!("{origin}/{}", relative("", path)?)));
+        }
+        Ok(Self::File(raw.into()))
+    }
+
+    /// Resolves a location written in an `#include` or `#require` in the source at this location.
+    ///
+    /// # Arguments
+    /// - `target`: The location that is included. Paths are relative to this location.
+    ///
+    /// # Returns
+    /// The Location of the included source.
+    ///
+    /// # Errors
+    /// This function errors if the `target` is malformed, if a file could not be canonicalized or if a path goes above the
+    /// root of the host, repository or namespace of this location.
+    pub fn join(&self, target: &str) -> Result<Self, Error> {
+        if has_scheme(target) {
+            return Self::parse(target);
+        }
+        match self {
+            Self::File(path) => {
+                let target: PathBuf = PathBuf::from(target);
+                let target: PathBuf = match path.parent() {
+                    Some(parent) if !target.is_absolute() => parent.join(target),
+                    _ => target,
+                };
+                target.canonicalize().map(Self::File).map_err(|err| Error::PathCanonicalize { path: target, err })
+            },
+            Self::Url(url) => {
+                let (origin, path): (&str, &str) = split_url(url);
+                Ok(Self::Url(format!("{origin}/{}", relative(path, target)?)))
+            },
+            Self::Git { repo, rev, path } => Ok(Self::Git { repo: repo.clone(), rev: rev.clone(), path: relative(path, target)? }),
+            Self::Memory(name) => Ok(Self::Memory(relative(name, target)?)),
+        }
+    }
+
+    /// Returns the path of this location if it is a file on the local disk.
+    #[inline]
+    pub fn as_path(&self) -> Option<&Path> {
+        match self {
+            Self::File(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Url(url) => write!(f, "{url}"),
+            Self::Git { repo, rev, path } => write!(f, "git+{repo}#{rev}:{path}"),
+            Self::Memory(name) => write!(f, "mem:{name}"),
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// Fetches the eFLINT sources that `#include`s and `#require`s refer to.
+///
+/// Note that resolvers are blocking, so use them from a blocking context (e.g., `tokio::task::spawn_blocking()`) in
+/// async code.
+pub trait IncludeResolver {
+    /// Fetches the source at a location.
+    ///
+    /// # Arguments
+    /// - `location`: The [`Location`] to fetch.
+    ///
+    /// # Returns
+    /// The contents of the source.
+    ///
+    /// # Errors
+    /// This function errors with [`Error::Unsupported`] if the resolver does not fetch this kind of location, or with
+    /// another error if fetching it failed.
+    fn fetch(&self, location: &Location) -> Result<String, Error>;
+}
+impl<R: IncludeResolver + ?Sized> IncludeResolver for &R {
+    #[inline]
+    fn fetch(&self, location: &Location) -> Result<String, Error> { (**self).fetch(location) }
+}
+impl<R: IncludeResolver + ?Sized> IncludeResolver for Box<R> {
+    #[inline]
+    fn fetch(&self, location: &Location) -> Result<String, Error> { (**self).fetch(location) }
+}
+
+/// Fetches files from the local disk, which is what `#include`s have always done.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileResolver;
+impl IncludeResolver for FileResolver {
+    fn fetch(&self, location: &Location) -> Result<String, Error> {
+        let Location::File(path) = location else { return Err(Error::Unsupported { location: location.clone() }) };
+        fs::read_to_string(path).map_err(|err| Error::FileRead { path: path.clone(), err })
+    }
+}
+
+/// Downloads files over HTTPS (or plain HTTP, if allowed).
+#[derive(Clone, Debug, Default)]
+pub struct HttpResolver {
+    /// The client to download with.
+    client:     reqwest::blocking::Client,
+    /// Whether to download over plain HTTP too.
+    allow_http: bool,
+}
+impl HttpResolver {
+    /// Constructor for the HttpResolver that only downloads over HTTPS.
+    ///
+    /// # Returns
+    /// A new HttpResolver.
+    #[inline]
+    pub fn new() -> Self { Self::default() }
+
+    /// Allows downloading over plain HTTP too.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn allow_http(mut self) -> Self {
+        self.allow_http = true;
+        self
+    }
+}
+impl IncludeResolver for HttpResolver {
+    fn fetch(&self, location: &Location) -> Result<String, Error> {
+        let Location::Url(url) = location else { return Err(Error::Unsupported { location: location.clone() }) };
+        if !self.allow_http && !url.starts_with("https://") {
+            return Err(Error::InsecureUrl { url: url.clone() });
+        }
+        debug!("Downloading eFLINT source '{url}'");
+        self.client
+            .get(url)
+            .send()
+            .and_then(|res| res.error_for_status())
+            .and_then(|res| res.text())
+            .map_err(|err| Error::Download { url: url.clone(), err })
+    }
+}
+
+/// Reads files from git repositories, which are cloned (bare) into a directory of its own and kept there.
+#[derive(Clone, Debug)]
+pub struct GitResolver {
+    /// The directory to keep the clones in.
+    clones: PathBuf,
+}
+impl GitResolver {
+    /// Constructor for the GitResolver.
+    ///
+    /// # Arguments
+    /// - `clones`: The directory to keep the clones of repositories in. Git creates it if it does not exist.
+    ///
+    /// # Returns
+    /// A new GitResolver.
+    #[inline]
+    pub fn new(clones: impl Into<PathBuf>) -> Self { Self { clones: clones.into() } }
+
+    /// Runs git.
+    ///
+    /// # Returns
+    /// Its stdout if it succeeded.
+    fn git(cmd: &mut Command) -> Result<Vec<u8>, Error> {
+        let output: Output = cmd.output().map_err(|err| Error::GitSpawn { cmd: format!("{cmd:?}"), err })?;
+        if !output.status.success() {
+            return Err(Error::GitFailed { cmd: format!("{cmd:?}"), stderr: String::from_utf8_lossy(&output.stderr).into() });
+        }
+        Ok(output.stdout)
+    }
+}
+impl IncludeResolver for GitResolver {
+    fn fetch(&self, location: &Location) -> Result<String, Error> {
+        let Location::Git { repo, rev, path } = location else { return Err(Error::Unsupported { location: location.clone() }) };
+
+        // Clone the repository if we haven't yet
+        let clone: PathBuf = self.clones.join(hex::encode(Sha256::digest(repo.as_bytes())));
+        if !clone.exists() {
+            debug!("Cloning git repository '{repo}' to '{}'", clone.display());
+            Self::git(Command::new("git").args(["clone", "--quiet", "--bare", repo.as_str()]).arg(&clone))?;
+        }
+
+        // Read the file, fetching the repository again if the revision is not known (yet)
+        let object: String = format!("{rev}:{path}");
+        let show = || Self::git(Command::new("git").arg("--git-dir").arg(&clone).args(["show", object.as_str()]));
+        let contents: Vec<u8> = match show() {
+            Ok(contents) => contents,
+            Err(_) => {
+                debug!("Fetching git repository '{repo}' to find '{object}'");
+                Self::git(Command::new("git").arg("--git-dir").arg(&clone).args([
+                    "fetch",
+                    "--quiet",
+                    repo.as_str(),
+                    "+refs/heads/*:refs/heads/*",
+                    "+refs/tags/*:refs/tags/*",
+                ]))?;
+                show()?
+            },
+        };
+        Ok(String::from_utf8_lossy(&contents).into_owned())
+    }
+}
+
+/// Keeps sources in memory, e.g., to compile policies that were sent to a server without writing them to disk.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryResolver {
+    /// The sources by (normalized) name.
+    sources: HashMap<String, String>,
+}
+impl MemoryResolver {
+    /// Constructor for the MemoryResolver that does not have any sources yet.
+    ///
+    /// # Returns
+    /// A new MemoryResolver.
+    #[inline]
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a source.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the source, which is included as `"mem:<NAME>"` (or relative to another in-memory source).
+    /// - `source`: Its contents.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    ///
+    /// # Errors
+    /// This function errors if the `name` goes above the root (e.g., `../base.eflint`).
+    pub fn with(mut self, name: &str, source: impl Into<String>) -> Result<Self, Error> {
+        self.sources.insert(relative("", name)?, source.into());
+        Ok(self)
+    }
+}
+impl IncludeResolver for MemoryResolver {
+    fn fetch(&self, location: &Location) -> Result<String, Error> {
+        let Location::Memory(name) = location else { return Err(Error::Unsupported { location: location.clone() }) };
+        self.sources.get(name).cloned().ok_or_else(|| Error::UnknownSource { name: name.clone() })
+    }
+}
+
+/// Combines resolvers for different kinds of locations.
+///
+/// Every location is fetched by the first resolver that supports its kind.
+#[derive(Default)]
+pub struct Resolvers(Vec<Box<dyn IncludeResolver + Send + Sync>>);
+impl Resolvers {
+    /// Constructor for the Resolvers that does not fetch anything yet.
+    ///
+    /// # Returns
+    /// A new Resolvers.
+    #[inline]
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a resolver.
+    ///
+    /// # Arguments
+    /// - `resolver`: The [`IncludeResolver`] to try after those added before.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with(mut self, resolver: impl 'static + IncludeResolver + Send + Sync) -> Self {
+        self.0.push(Box::new(resolver));
+        self
+    }
+}
+impl IncludeResolver for Resolvers {
+    fn fetch(&self, location: &Location) -> Result<String, Error> {
+        for resolver in &self.0 {
+            match resolver.fetch(location) {
+                Err(Error::Unsupported { .. }) => continue,
+                res => return res,
+            }
+        }
+        Err(Error::Unsupported { location: location.clone() })
+    }
+}
+
+/// Remembers what another resolver fetched, such that every source is only fetched once.
+///
+/// Note that sources are never evicted, so use a new CachingResolver for every (batch of) compilation(s) that should see
+/// changes.
+#[derive(Debug, Default)]
+pub struct CachingResolver<R> {
+    /// The resolver to fetch what is not cached yet with.
+    resolver: R,
+    /// The sources fetched so far.
+    cache:    Mutex<HashMap<Location, String>>,
+}
+impl<R> CachingResolver<R> {
+    /// Constructor for the CachingResolver.
+    ///
+    /// # Arguments
+    /// - `resolver`: The [`IncludeResolver`] to fetch what is not cached yet with.
+    ///
+    /// # Returns
+    /// A new CachingResolver with an empty cache.
+    #[inline]
+    pub fn new(resolver: R) -> Self { Self { resolver, cache: Mutex::new(HashMap::new()) } }
+}
+impl<R: IncludeResolver> IncludeResolver for CachingResolver<R> {
+    fn fetch(&self, location: &Location) -> Result<String, Error> {
+        if let Some(source) = self.cache.lock().unwrap_or_else(|err| err.into_inner()).get(location) {
+            debug!("Using cached eFLINT source '{location}'");
+            return Ok(source.clone());
+        }
+        let source: String = self.resolver.fetch(location)?;
+        self.cache.lock().unwrap_or_else(|err| err.into_inner()).insert(location.clone(), source.clone());
+        Ok(source)
+    }
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let base: Location = Location::parse("https://example.org/policies/main.eflint").unwrap();
+        assert_eq!(base.join("common/base.eflint").unwrap(), Location::Url("https://example.org/policies/common/base.eflint".into()));
+        assert_eq!(base.join("/base.eflint").unwrap(), Location::Url("https://example.org/base.eflint".into()));
+        assert!(matches!(base.join("../../base.eflint"), Err(Error::Escapes { .. })));
+
+        let base: Location = Location::parse("git+https://example.org/policies.git#v1.2:eflint/main.eflint").unwrap();
+        assert_eq!(base.join("../common.eflint").unwrap(), Location::Git {
+            repo: "https://example.org/policies.git".into(),
+            rev:  "v1.2".into(),
+            path: "common.eflint".into(),
+        });
+        assert_eq!(base.to_string(), "git+https://example.org/policies.git#v1.2:eflint/main.eflint");
+        assert!(matches!(Location::parse("git+https://example.org/policies.git"), Err(Error::IllegalGit { .. })));
+
+        let base: Location = Location::parse("mem:a/main.eflint").unwrap();
+        assert_eq!(base.join("./b.eflint").unwrap(), Location::Memory("a/b.eflint".into()));
+        assert_eq!(base.join("mem:c.eflint").unwrap(), Location::Memory("c.eflint".into()));
+    }
+
+    #[test]
+    fn test_resolvers() {
+        let resolver = CachingResolver::new(
+            Resolvers::new().with(FileResolver).with(MemoryResolver::new().with("main.eflint", "Fact user.").unwrap()).with(HttpResolver::new()),
+        );
+        assert_eq!(resolver.fetch(&Location::Memory("main.eflint".into())).unwrap(), "Fact user.");
+        assert!(matches!(resolver.fetch(&Location::Memory("other.eflint".into())), Err(Error::UnknownSource { .. })));
+        assert!(matches!(resolver.fetch(&Location::Url("http://example.org/main.eflint".into())), Err(Error::InsecureUrl { .. })));
+        assert!(matches!(
+            resolver.fetch(&Location::Git { repo: "repo".into(), rev: "main".into(), path: "main.eflint".into() }),
+            Err(Error::Unsupported { .. })
+        ));
+    }
+
+    #[test]
+    fn test_expand_with() {
+        let resolver = MemoryResolver::new()
+            .with("main.eflint", "#require \"common/base.eflint\".\n#include \"common/user.eflint\".\n+user(amy).\n")
+            .unwrap()
+            .with("common/base.eflint", "Fact user.\n")
+            .unwrap()
+            .with("common/user.eflint", "#require \"base.eflint\".\n+user(bob).\n")
+            .unwrap()
+            .with("loop.eflint", "#include \"mem:loop.eflint\".\n")
+            .unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        crate::expand_with(&resolver, &Location::Memory("main.eflint".into()), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Fact user.\n+user(bob).\n+user(amy).\n");
+
+        assert!(matches!(
+            crate::expand_with(&resolver, &Location::Memory("loop.eflint".into()), &mut Vec::new()),
+            Err(crate::Error::IncludeCycle { chain }) if chain.len() == 2
+        ));
+    }
+}
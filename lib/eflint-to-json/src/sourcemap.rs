@@ -0,0 +1,187 @@
+//! Implements source maps, which trace compiled eFLINT JSON back to the lines of the `.eflint` files it came from.
+//!
+//! The compiler only sees a single stream of lines, with every `#include` and `#require` already replaced by the
+//! included source. While streaming, every line is recorded in a [`SourceMap`] together with the source and line it
+//! came from, such that errors of the compiler (which refer to lines of the stream) can be traced back with
+//! [`SourceMap::position()`]. The positions of declarations are recorded by name too, such that, e.g., the identifier of
+//! a violated invariant can be traced back with [`SourceMap::declaration()`].
+//!
+//! The source map is added to the compiled policy as a [`SOURCE_MAP_FIELD`], where reasoner connectors can find it with
+//! [`source_map_of()`]. Note that lines in `#foreach` blocks (see [`template`](crate::template)) are those of the
+//! expanded source.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::annotate::declared_name;
+
+/***** CONSTANTS *****/
+/// The field of the compiled eFLINT JSON policy that holds the source map.
+pub const SOURCE_MAP_FIELD: &str = "source_map";
+
+/***** AUXILLARY *****/
+/// A line in an eFLINT source.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SourcePosition {
+    /// The source (e.g., the path of a file).
+    pub source: String,
+    /// The line in the source, starting at 1.
+    pub line:   usize,
+}
+impl Display for SourcePosition {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { write!(f, "{}:{}", self.source, self.line) }
+}
+
+/// Consecutive lines streamed to the compiler that are consecutive lines of the same source too.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SourceLines {
+    /// The first line in the stream, starting at 1.
+    pub first:  usize,
+    /// The number of lines.
+    pub count:  usize,
+    /// The source they came from.
+    pub source: String,
+    /// The line in the source that the first one is, starting at 1.
+    pub line:   usize,
+}
+
+/***** LIBRARY *****/
+/// Traces the lines streamed to the compiler and the declarations in them back to their sources.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SourceMap {
+    /// The lines streamed to the compiler, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<SourceLines>,
+    /// Where everything was declared (first), by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub declarations: BTreeMap<String, SourcePosition>,
+}
+impl SourceMap {
+    /// Constructor for the SourceMap that has not recorded anything yet.
+    ///
+    /// # Returns
+    /// A new SourceMap.
+    #[inline]
+    pub fn new() -> Self { Self::default() }
+
+    /// Returns whether nothing was recorded.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.lines.is_empty() && self.declarations.is_empty() }
+
+    /// Records the next line streamed to the compiler.
+    ///
+    /// # Arguments
+    /// - `source`: The source the line came from.
+    /// - `line`: The line in that source, starting at 1.
+    /// - `text`: The line itself, to find declarations in.
+    pub fn record(&mut self, source: &str, line: usize, text: &str) {
+        let streamed: usize = self.lines.last().map(|last| last.first + last.count).unwrap_or(1);
+        match self.lines.last_mut() {
+            Some(last) if last.source == source && last.line + last.count == line => last.count += 1,
+            _ => self.lines.push(SourceLines { first: streamed, count: 1, source: source.into(), line }),
+        }
+        if let Some(name) = declared_name(text.trim()) {
+            self.declarations.entry(name.into()).or_insert_with(|| SourcePosition { source: source.into(), line });
+        }
+    }
+
+    /// Traces a line streamed to the compiler back to its source.
+    ///
+    /// # Arguments
+    /// - `streamed`: The line in the stream (e.g., as mentioned in an error of the compiler), starting at 1.
+    ///
+    /// # Returns
+    /// The [`SourcePosition`] of the line, or [`None`] if no such line was streamed.
+    pub fn position(&self, streamed: usize) -> Option<SourcePosition> {
+        let i: usize = self.lines.partition_point(|lines| lines.first + lines.count <= streamed);
+        let lines: &SourceLines = self.lines.get(i).filter(|lines| lines.first <= streamed)?;
+        Some(SourcePosition { source: lines.source.clone(), line: lines.line + (streamed - lines.first) })
+    }
+
+    /// Traces a declaration back to its source.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the declared type (e.g., the identifier of a violated invariant).
+    ///
+    /// # Returns
+    /// The [`SourcePosition`] where it was (first) declared, or [`None`] if it is not known.
+    #[inline]
+    pub fn declaration(&self, name: &str) -> Option<&SourcePosition> { self.declarations.get(name) }
+}
+impl Display for SourceMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        for lines in &self.lines {
+            writeln!(
+                f,
+                "lines {}-{} are lines {}-{} of '{}'",
+                lines.first,
+                lines.first + lines.count - 1,
+                lines.line,
+                lines.line + lines.count - 1,
+                lines.source
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds a source map to a compiled eFLINT JSON policy.
+///
+/// # Arguments
+/// - `policy`: The policy to add it to. Left as-is if it is not a JSON object or if the source map is empty.
+/// - `source_map`: The [`SourceMap`] to add.
+pub fn attach(policy: &mut Value, source_map: &SourceMap) {
+    if source_map.is_empty() {
+        return;
+    }
+    if let (Value::Object(policy), Ok(source_map)) = (policy, serde_json::to_value(source_map)) {
+        policy.insert(SOURCE_MAP_FIELD.into(), source_map);
+    }
+}
+
+/// Reads the source map from a compiled eFLINT JSON policy.
+///
+/// # Arguments
+/// - `policy`: The policy, as raw JSON.
+///
+/// # Returns
+/// The [`SourceMap`] in the policy, which is empty if it has none (or is not valid JSON).
+pub fn source_map_of(policy: &str) -> SourceMap {
+    #[derive(Deserialize)]
+    struct Mapped {
+        #[serde(default)]
+        source_map: SourceMap,
+    }
+    serde_json::from_str::<Mapped>(policy).map(|mapped| mapped.source_map).unwrap_or_default()
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut map = SourceMap::new();
+        map.record("main.eflint", 1, "Fact user.");
+        map.record("base.eflint", 1, "Invariant no-consent When Holds(user).");
+        map.record("base.eflint", 2, "+user(amy).");
+        map.record("main.eflint", 3, "Extend Fact user.");
+        assert_eq!(map.lines.len(), 3);
+        assert_eq!(map.position(3), Some(SourcePosition { source: "base.eflint".into(), line: 2 }));
+        assert_eq!(map.position(4).unwrap().to_string(), "main.eflint:3");
+        assert_eq!(map.position(0), None);
+        assert_eq!(map.position(5), None);
+        assert_eq!(map.declaration("no-consent").unwrap().to_string(), "base.eflint:1");
+        assert_eq!(map.declaration("user").unwrap().to_string(), "main.eflint:1");
+
+        let mut policy: Value = serde_json::json!({ "version": "0.1.0", "kind": "phrases", "phrases": [] });
+        attach(&mut policy, &map);
+        assert_eq!(source_map_of(&policy.to_string()), map);
+        assert!(source_map_of("{\"phrases\": []}").is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! Implements expanding templates in eFLINT files before they are compiled.
+//!
+//! Large policies tend to repeat the same snippet for every dataset, user or site they know about. Instead of writing
+//! those out by hand, a snippet can be wrapped in a `#foreach` block that is repeated once for every row of a CSV file:
+//!
+//! ```eflint
+//! #foreach "datasets.csv".
+//! +dataset({{name}}).
+//! +owner-of(user({{owner}}), dataset({{name}})).
+//! #end.
+//! ```
+//!
+//! The first row of the CSV names the columns, which can be used as `{{column}}` placeholders in the block. Paths are
+//! relative to the file the block occurs in. Blocks may be nested, in which case the placeholders of the outer blocks
+//! can be used in the inner ones (including in their paths); a column of an inner block hides an equally-named one of
+//! an outer block.
+//!
+//! Lines outside of any block are left untouched.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::{error, fs};
+
+/***** ERRORS *****/
+/// Defines errors that originate from expanding templates.
+#[derive(Debug)]
+pub enum Error {
+    /// A `#foreach` block was never closed with `#end.`.
+    UnterminatedForeach { line: usize },
+    /// An `#end.` was found outside of any `#foreach` block.
+    UnexpectedEnd { line: usize },
+    /// The path in a `#foreach` was not quoted.
+    MissingQuote { line: usize, raw: String },
+    /// A placeholder in a block does not name any column.
+    UnknownPlaceholder { line: usize, name: String },
+    /// A placeholder was opened with `{{` but never closed.
+    UnterminatedPlaceholder { line: usize },
+    /// Failed to read the CSV file of a block.
+    TableRead { line: usize, path: PathBuf, err: std::io::Error },
+    /// The CSV file of a block is not valid.
+    TableParse { line: usize, path: PathBuf, row: usize, reason: &'static str },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            UnterminatedForeach { line } => write!(f, "Block opened with '#foreach' on line {line} is never closed with '#end.'"),
+            UnexpectedEnd { line } => write!(f, "Found '#end.' on line {line} outside of any '#foreach' block"),
+            MissingQuote { line, raw } => write!(f, "Missing quotes (\") in '{raw}' on line {line}"),
+            UnknownPlaceholder { line, name } => write!(f, "Placeholder '{{{{{name}}}}}' on line {line} does not name any column"),
+            UnterminatedPlaceholder { line } => write!(f, "Placeholder on line {line} is opened with '{{{{' but never closed with '}}}}'"),
+            TableRead { line, path, .. } => write!(f, "Failed to read CSV file '{}' (used on line {line})", path.display()),
+            TableParse { line, path, row, reason } => {
+                write!(f, "Invalid CSV file '{}' (used on line {line}): {reason} in record {row}", path.display())
+            },
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            UnterminatedForeach { .. } => None,
+            UnexpectedEnd { .. } => None,
+            MissingQuote { .. } => None,
+            UnknownPlaceholder { .. } => None,
+            UnterminatedPlaceholder { .. } => None,
+            TableRead { err, .. } => Some(err),
+            TableParse { .. } => None,
+        }
+    }
+}
+
+/***** HELPER FUNCTIONS *****/
+/// Returns whether a (trimmed) line is the given directive.
+#[inline]
+fn is_directive(line: &str, directive: &str) -> bool { line.starts_with(directive) && line.ends_with('.') }
+
+/// Parses the contents of a CSV file.
+///
+/// Supports quoted fields (with `""` as an escaped quote), which may contain commas. Whitespace around unquoted fields
+/// is ignored, as are empty lines.
+///
+/// # Returns
+/// The records in the file, in order.
+///
+/// # Errors
+/// This function errors with the (1-based) record and a reason if the file is not valid CSV. Fields may not contain
+/// line breaks, since they are pasted into a line of eFLINT.
+fn parse_csv(raw: &str) -> Result<Vec<Vec<String>>, (usize, &'static str)> {
+    let mut records: Vec<Vec<String>> = vec![];
+    for line in raw.lines() {
+        let line: &str = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row: usize = records.len() + 1;
+
+        let mut fields: Vec<String> = vec![];
+        let mut chars = line.chars().peekable();
+        loop {
+            // Skip leading whitespace, then see if it's quoted
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            let mut field: String = String::new();
+            if chars.next_if_eq(&'"').is_some() {
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => return Err((row, "unterminated quoted field (fields may not span lines)")),
+                    }
+                }
+                while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+                if chars.peek().is_some_and(|c| *c != ',') {
+                    return Err((row, "unexpected characters after quoted field"));
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != ',') {
+                    if c == '"' {
+                        return Err((row, "unexpected quote in unquoted field"));
+                    }
+                    field.push(c);
+                }
+                field.truncate(field.trim_end().len());
+            }
+            fields.push(field);
+
+            if chars.next().is_none() {
+                break;
+            }
+        }
+
+        if let Some(first) = records.first() {
+            if fields.len() != first.len() {
+                return Err((row, "wrong number of fields"));
+            }
+        }
+        records.push(fields);
+    }
+    Ok(records)
+}
+
+/// Replaces the placeholders in a line with their values.
+///
+/// # Arguments
+/// - `text`: The text to replace the placeholders in.
+/// - `lineno`: The line number of the text, for errors.
+/// - `bindings`: The values of the columns of the enclosing blocks, innermost last.
+///
+/// # Errors
+/// This function errors if a placeholder is not terminated or does not name a column.
+fn substitute<'t>(text: &'t str, lineno: usize, bindings: &[HashMap<String, String>]) -> Result<Cow<'t, str>, Error> {
+    if bindings.is_empty() || !text.contains("{{") {
+        return Ok(Cow::Borrowed(text));
+    }
+
+    let mut res: String = String::with_capacity(text.len());
+    let mut rest: &str = text;
+    while let Some(start) = rest.find("{{") {
+        res.push_str(&rest[..start]);
+        let end: usize = match rest[start + 2..].find("}}") {
+            Some(end) => start + 2 + end,
+            None => return Err(Error::UnterminatedPlaceholder { line: lineno }),
+        };
+        let name: &str = rest[start + 2..end].trim();
+        match bindings.iter().rev().find_map(|row| row.get(name)) {
+            Some(value) => res.push_str(value),
+            None => return Err(Error::UnknownPlaceholder { line: lineno, name: name.into() }),
+        }
+        rest = &rest[end + 2..];
+    }
+    res.push_str(rest);
+    Ok(Cow::Owned(res))
+}
+
+/// Expands the blocks in a list of lines.
+///
+/// # Arguments
+/// - `dir`: The directory to resolve relative paths against.
+/// - `lines`: The lines to expand, with their (1-based) line numbers.
+/// - `bindings`: The values of the columns of the enclosing blocks, innermost last.
+/// - `output`: The string to write the expanded lines to.
+///
+/// # Errors
+/// This function errors if a block is malformed, or if its CSV file could not be read or parsed.
+fn expand_lines(dir: &Path, lines: &[(usize, &str)], bindings: &mut Vec<HashMap<String, String>>, output: &mut String) -> Result<(), Error> {
+    let mut i: usize = 0;
+    while i < lines.len() {
+        let (lineno, line): (usize, Cow<str>) = (lines[i].0, substitute(lines[i].1, lines[i].0, bindings)?);
+        let trimmed: &str = line.trim();
+        if is_directive(trimmed, "#end") {
+            return Err(Error::UnexpectedEnd { line: lineno });
+        }
+        if !is_directive(trimmed, "#foreach") {
+            output.push_str(&line);
+            output.push('\n');
+            i += 1;
+            continue;
+        }
+
+        // Find the end of the block, skipping any nested ones
+        let mut depth: usize = 0;
+        let mut end: Option<usize> = None;
+        for (j, (_, body_line)) in lines.iter().enumerate().skip(i + 1) {
+            let body_line: &str = body_line.trim();
+            if is_directive(body_line, "#foreach") {
+                depth += 1;
+            } else if is_directive(body_line, "#end") {
+                if depth == 0 {
+                    end = Some(j);
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+        let Some(end) = end else { return Err(Error::UnterminatedForeach { line: lineno }) };
+
+        // Read the table
+        let (squote, equote): (usize, usize) = match (trimmed.find('"'), trimmed.rfind('"')) {
+            (Some(squote), Some(equote)) if squote < equote => (squote, equote),
+            _ => return Err(Error::MissingQuote { line: lineno, raw: trimmed.into() }),
+        };
+        let path: PathBuf = dir.join(&trimmed[squote + 1..equote]);
+        let raw: String = fs::read_to_string(&path).map_err(|err| Error::TableRead { line: lineno, path: path.clone(), err })?;
+        let mut records = parse_csv(&raw).map_err(|(row, reason)| Error::TableParse { line: lineno, path: path.clone(), row, reason })?.into_iter();
+        let columns: Vec<String> = records.next().unwrap_or_default();
+
+        // Repeat the body for every row
+        for record in records {
+            bindings.push(columns.iter().cloned().zip(record).collect());
+            let res: Result<(), Error> = expand_lines(dir, &lines[i + 1..end], bindings, output);
+            bindings.pop();
+            res?;
+        }
+        i = end + 1;
+    }
+    Ok(())
+}
+
+/***** LIBRARY *****/
+/// Expands the `#foreach` blocks in the source of an eFLINT file.
+///
+/// # Arguments
+/// - `path`: The path of the file, to resolve the paths of CSV files against.
+/// - `source`: The contents of the file.
+///
+/// # Returns
+/// The source with every block expanded, or the source as-is if it has none.
+///
+/// # Errors
+/// This function errors if a block is malformed, or if its CSV file could not be read or parsed.
+pub fn expand<'s>(path: &Path, source: &'s str) -> Result<Cow<'s, str>, Error> {
+    if !source.lines().any(|line| {
+        let line: &str = line.trim();
+        is_directive(line, "#foreach") || is_directive(line, "#end")
+    }) {
+        return Ok(Cow::Borrowed(source));
+    }
+
+    let dir: &Path = path.parent().unwrap_or_else(|| Path::new("."));
+    let lines: Vec<(usize, &str)> = source.lines().enumerate().map(|(i, line)| (i + 1, line)).collect();
+    let mut output: String = String::with_capacity(source.len());
+    expand_lines(dir, &lines, &mut vec![], &mut output)?;
+    Ok(Cow::Owned(output))
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let records = parse_csv("name, owner\n\"st, antonius\",\"amy \"\"a\"\"\"\n\n umc , bob \n").unwrap();
+        assert_eq!(records, vec![vec!["name", "owner"], vec!["st, antonius", "amy \"a\""], vec!["umc", "bob"]]);
+        assert_eq!(parse_csv("a,b\n1\n"), Err((2, "wrong number of fields")));
+        assert_eq!(parse_csv("a\n\"1\n"), Err((2, "unterminated quoted field (fields may not span lines)")));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("eflint-template-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("datasets.csv"), "name,owner\nd1,amy\nd2,bob\n").unwrap();
+        fs::write(dir.join("sites.csv"), "site\ns1\n").unwrap();
+
+        let source =
+            "Fact dataset.\n#foreach \"datasets.csv\".\n+dataset({{name}}).\n#foreach \"sites.csv\".\n+at({{ name }}, {{site}}).\n#end.\n#end.\n";
+        let expanded = expand(&dir.join("main.eflint"), source).unwrap();
+        assert_eq!(expanded, "Fact dataset.\n+dataset(d1).\n+at(d1, s1).\n+dataset(d2).\n+at(d2, s1).\n");
+
+        // Sources without blocks are left alone, even if they look like placeholders
+        assert!(matches!(expand(&dir.join("main.eflint"), "Fact {{x}}.\n").unwrap(), Cow::Borrowed(_)));
+        assert!(matches!(
+            expand(&dir.join("main.eflint"), "#foreach \"datasets.csv\".\n+x({{nope}}).\n#end.\n"),
+            Err(Error::UnknownPlaceholder { line: 2, .. })
+        ));
+        assert!(matches!(expand(&dir.join("main.eflint"), "#foreach \"datasets.csv\".\n"), Err(Error::UnterminatedForeach { line: 1 })));
+        assert!(matches!(expand(&dir.join("main.eflint"), "#end.\n"), Err(Error::UnexpectedEnd { line: 1 })));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -17,6 +17,8 @@
 #[cfg(feature = "map_parser")]
 pub mod map_parser;
 pub mod spec;
+#[cfg(feature = "typed_parser")]
+pub mod typed_parser;
 
 // Bring some of it into the main namespace
 pub use spec::*;
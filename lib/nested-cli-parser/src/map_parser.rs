@@ -260,28 +260,23 @@ impl MapParser {
         // OK, build self
         Self { keys }
     }
-}
-impl NestedCliParser for MapParser {
-    type Args = HashMap<String, Option<String>>;
-    type ParseError = Error;
 
-    fn help_fmt(&self, name: &str, short: char, long: &str, f: &mut Formatter<'_>) -> FResult {
-        writeln!(f, "{name} nested arguments")?;
-        writeln!(f, "Usage: -{short},--{long} \"[<OPTIONS...>]\"")?;
-        writeln!(f)?;
-        writeln!(f, "Options:")?;
-        for (short, long, desc) in &self.keys {
-            writeln!(f, "  {short}=<VALUE>,{long}=<VALUE>")?;
-            writeln!(f, "      {desc}")?;
-        }
-        writeln!(f)
-    }
-
-    fn parse(&self, args: &str) -> Result<Self::Args, Self::ParseError> {
+    /// Parses the given string as a list of key/value pairs, remembering where each of them was found.
+    ///
+    /// # Arguments
+    /// - `args`: The raw [`&str`] to parse.
+    ///
+    /// # Returns
+    /// A list of (long) keys, their values if the user gave any and the position of the argument within `args`, in the order given.
+    ///
+    /// # Errors
+    /// This function errors if the input was not a valid list of key/value pairs, or if it contained unknown keys.
+    pub fn parse_positioned(&self, args: &str) -> Result<Vec<(String, Option<String>, usize)>, Error> {
         // Parse the arguments using a little state machine to be respectful to quotes
-        let mut parsed_args: HashMap<String, Option<String>> = HashMap::with_capacity(args.chars().filter(|c| *c == ',').count());
+        let mut parsed_args: Vec<(String, Option<String>, usize)> = Vec::with_capacity(args.chars().filter(|c| *c == ',').count() + 1);
         let mut mode: ParseMode = ParseMode::Start;
         let mut buf: String = String::new();
+        let mut arg_pos: usize = 0;
         for (pos, c) in args.grapheme_indices(true) {
             match mode {
                 // Simply parse the contents until we discover a comma
@@ -289,10 +284,12 @@ impl NestedCliParser for MapParser {
                     // Comma indicate the end of one arguments
                     "," => {
                         // `buf` now contains the entire argument, so parse it as such
-                        let (key, value): (String, Option<String>) = parse_arg(&self.keys, &buf, pos)?;
+                        let (key, value): (String, Option<String>) = parse_arg(&self.keys, &buf, arg_pos)?;
 
-                        // Alright, add the key/value pair!
-                        parsed_args.insert(key, value);
+                        // Alright, add the key/value pair and move on to the next
+                        parsed_args.push((key, value, arg_pos));
+                        buf.clear();
+                        arg_pos = pos + 1;
                     },
 
                     // Mode changers
@@ -346,11 +343,32 @@ impl NestedCliParser for MapParser {
 
         // Resolve the remaining buffer, if any
         if !buf.is_empty() {
-            let (key, value): (String, Option<String>) = parse_arg(&self.keys, &buf, args.len() - buf.len())?;
-            parsed_args.insert(key, value);
+            let (key, value): (String, Option<String>) = parse_arg(&self.keys, &buf, arg_pos)?;
+            parsed_args.push((key, value, arg_pos));
         }
 
         // Done, return the parsed arguments!
         Ok(parsed_args)
     }
 }
+impl NestedCliParser for MapParser {
+    type Args = HashMap<String, Option<String>>;
+    type ParseError = Error;
+
+    fn help_fmt(&self, name: &str, short: char, long: &str, f: &mut Formatter<'_>) -> FResult {
+        writeln!(f, "{name} nested arguments")?;
+        writeln!(f, "Usage: -{short},--{long} \"[<OPTIONS...>]\"")?;
+        writeln!(f)?;
+        writeln!(f, "Options:")?;
+        for (short, long, desc) in &self.keys {
+            writeln!(f, "  {short}=<VALUE>,{long}=<VALUE>")?;
+            writeln!(f, "      {desc}")?;
+        }
+        writeln!(f)
+    }
+
+    #[inline]
+    fn parse(&self, args: &str) -> Result<Self::Args, Self::ParseError> {
+        Ok(self.parse_positioned(args)?.into_iter().map(|(key, value, _)| (key, value)).collect())
+    }
+}
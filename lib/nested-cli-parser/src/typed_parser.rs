@@ -0,0 +1,665 @@
+//  TYPED PARSER.rs
+//    by Lut99
+//
+//  Created:
+//    16 Oct 2026, 10:12:31
+//  Last edited:
+//    16 Oct 2026, 11:40:05
+//  Auto updated?
+//    Yes
+//
+//  Description:
+//!   Implements a nested parser on top of the [`MapParser`] that knows
+//!   the types, defaults and constraints of its arguments.
+//!
+//!   Next to the nested CLI string, arguments may be given as an
+//!   environment variable (see [`ArgSpec::with_env()`]) or as a key in a
+//!   config file (see [`ArgSpec::as_config_file()`]). If an argument is
+//!   given in multiple places, the CLI string wins over the environment,
+//!   which wins over the config file, which wins over the default.
+//
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{env, error, fs, io};
+
+use crate::NestedCliParser;
+use crate::map_parser::{Error as MapError, MapParser};
+
+/***** ERRORS *****/
+/// Defines errors that may originate from the [`TypedParser`].
+#[derive(Debug)]
+pub enum Error {
+    /// The arguments could not be read as a list of key/value pairs.
+    Syntax { err: MapError },
+    /// The config file could not be read.
+    ConfigFileRead { path: PathBuf, err: io::Error },
+    /// A line in the config file could not be read as a key/value pair.
+    ConfigFileSyntax { path: PathBuf, line: usize, err: MapError },
+    /// An argument that needs a value was given without one.
+    MissingValue { origin: Origin, key: String, ty: ArgType },
+    /// A value could not be parsed as the type of its argument.
+    IllegalValue { origin: Origin, key: String, ty: ArgType, raw: String },
+    /// A value was rejected by the validator of its argument.
+    InvalidValue { origin: Origin, key: String, raw: String, reason: String },
+    /// A required argument was not given.
+    MissingRequired { key: String, env: Option<String> },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            Syntax { .. } => write!(f, "Failed to read nested arguments as a list of key/value pairs"),
+            ConfigFileRead { path, .. } => write!(f, "Failed to read config file '{}'", path.display()),
+            ConfigFileSyntax { path, line, .. } => write!(f, "Failed to read line {line} of config file '{}' as a key/value pair", path.display()),
+            MissingValue { origin, key, ty } => write!(f, "Expected a value ({ty}) for option '{key}' {origin}"),
+            IllegalValue { origin, key, ty, raw } => write!(f, "Failed to parse '{raw}' as {ty} for option '{key}' {origin}"),
+            InvalidValue { origin, key, raw, reason } => write!(f, "Invalid value '{raw}' for option '{key}' {origin}: {reason}"),
+            MissingRequired { key, env: Some(env) } => write!(f, "Missing required option '{key}' (also not given as environment variable '{env}')"),
+            MissingRequired { key, env: None } => write!(f, "Missing required option '{key}'"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            Syntax { err } => Some(err),
+            ConfigFileRead { err, .. } => Some(err),
+            ConfigFileSyntax { err, .. } => Some(err),
+            MissingValue { .. } | IllegalValue { .. } | InvalidValue { .. } | MissingRequired { .. } => None,
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// Defines where the value of an argument came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Origin {
+    /// The nested CLI string, at the given character.
+    Cli { pos: usize },
+    /// The given environment variable.
+    Env { var: String },
+    /// The given line of the config file.
+    File { path: PathBuf, line: usize },
+    /// The default of the argument.
+    Default,
+}
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Cli { pos } => write!(f, "at character {pos}"),
+            Self::Env { var } => write!(f, "in environment variable '{var}'"),
+            Self::File { path, line } => write!(f, "on line {line} of config file '{}'", path.display()),
+            Self::Default => write!(f, "in its default"),
+        }
+    }
+}
+
+/// Defines the types of values that arguments may have.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArgType {
+    /// Any string.
+    String,
+    /// A (signed) integer.
+    Int,
+    /// A boolean, given as `true`/`false`, `yes`/`no` or `1`/`0`. Giving the key without a value means `true`.
+    Bool,
+    /// A path to a file or directory. Not checked for existence.
+    Path,
+    /// One of the given strings.
+    Enum(Vec<String>),
+}
+impl ArgType {
+    /// Returns the placeholder for values of this type as shown in the help string.
+    pub fn placeholder(&self) -> String {
+        match self {
+            Self::String => "<STRING>".into(),
+            Self::Int => "<INT>".into(),
+            Self::Bool => "<BOOL>".into(),
+            Self::Path => "<PATH>".into(),
+            Self::Enum(variants) => format!("<{}>", variants.join("|")),
+        }
+    }
+
+    /// Parses a raw value as this type.
+    ///
+    /// # Arguments
+    /// - `raw`: The raw value to parse.
+    ///
+    /// # Returns
+    /// The parsed [`Value`], or [`None`] if `raw` is not a valid value of this type.
+    pub fn parse(&self, raw: &str) -> Option<Value> {
+        match self {
+            Self::String => Some(Value::String(raw.into())),
+            Self::Int => raw.parse().ok().map(Value::Int),
+            Self::Bool => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "1" => Some(Value::Bool(true)),
+                "false" | "no" | "0" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            Self::Path => Some(Value::Path(raw.into())),
+            Self::Enum(variants) => variants.iter().find(|variant| *variant == raw).map(|variant| Value::Enum(variant.clone())),
+        }
+    }
+}
+impl Display for ArgType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::String => write!(f, "a string"),
+            Self::Int => write!(f, "an integer"),
+            Self::Bool => write!(f, "a boolean"),
+            Self::Path => write!(f, "a path"),
+            Self::Enum(variants) => write!(f, "one of {}", variants.iter().map(|variant| format!("'{variant}'")).collect::<Vec<_>>().join(", ")),
+        }
+    }
+}
+
+/// Defines a value parsed by the [`TypedParser`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    /// A value of an [`ArgType::String`].
+    String(String),
+    /// A value of an [`ArgType::Int`].
+    Int(i64),
+    /// A value of an [`ArgType::Bool`].
+    Bool(bool),
+    /// A value of an [`ArgType::Path`].
+    Path(PathBuf),
+    /// A value of an [`ArgType::Enum`].
+    Enum(String),
+}
+impl Value {
+    /// Returns the value as a string, if it is a [`Value::String`] or [`Value::Enum`].
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) | Self::Enum(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an integer, if it is a [`Value::Int`].
+    #[inline]
+    pub fn as_int(&self) -> Option<i64> { if let Self::Int(value) = self { Some(*value) } else { None } }
+
+    /// Returns the value as a boolean, if it is a [`Value::Bool`].
+    #[inline]
+    pub fn as_bool(&self) -> Option<bool> { if let Self::Bool(value) = self { Some(*value) } else { None } }
+
+    /// Returns the value as a path, if it is a [`Value::Path`].
+    #[inline]
+    pub fn as_path(&self) -> Option<&Path> { if let Self::Path(value) = self { Some(value) } else { None } }
+}
+
+/// A function that checks whether a parsed value is acceptable, returning why not if it isn't.
+pub type Validator = Arc<dyn Send + Sync + Fn(&Value) -> Result<(), String>>;
+
+/// Returns a [`Validator`] for integers that must be at least the given number.
+///
+/// # Arguments
+/// - `min`: The smallest acceptable value.
+///
+/// # Returns
+/// A [`Validator`] that rejects smaller integers (and accepts anything that is not an integer).
+pub fn at_least(min: i64) -> Validator {
+    Arc::new(move |value: &Value| match value.as_int() {
+        Some(value) if value < min => Err(format!("must be at least {min}")),
+        _ => Ok(()),
+    })
+}
+
+/// Returns a [`Validator`] for strings that must not be empty (or only whitespace).
+pub fn non_empty() -> Validator {
+    Arc::new(|value: &Value| match value.as_str() {
+        Some(value) if value.trim().is_empty() => Err("must not be empty".into()),
+        _ => Ok(()),
+    })
+}
+
+/// Specifies a single argument of the [`TypedParser`].
+#[derive(Clone)]
+pub struct ArgSpec {
+    /// The shortname of the argument.
+    pub short: char,
+    /// The longname of the argument, by which its value is retrieved.
+    pub long: String,
+    /// A human-readable description of the argument.
+    pub description: String,
+    /// The type of the argument's value.
+    pub ty: ArgType,
+    /// The raw value used if the argument is not given, if any.
+    pub default: Option<String>,
+    /// Whether the argument must be given (only meaningful without a default).
+    pub required: bool,
+    /// Checks the value beyond its type, if anything needs to be checked.
+    pub validator: Option<Validator>,
+    /// The environment variable that may give the value if the nested CLI string doesn't, if any.
+    pub env: Option<String>,
+    /// Whether this argument is the path to a config file that may give the values of the other arguments.
+    pub config_file: bool,
+}
+impl ArgSpec {
+    /// Constructor for an optional ArgSpec without a default.
+    ///
+    /// # Arguments
+    /// - `short`: The shortname of the argument.
+    /// - `long`: The longname of the argument, by which its value is retrieved.
+    /// - `description`: A human-readable description of the argument.
+    /// - `ty`: The [`ArgType`] of the argument's value.
+    ///
+    /// # Returns
+    /// A new ArgSpec.
+    #[inline]
+    pub fn new(short: char, long: impl Into<String>, description: impl Into<String>, ty: ArgType) -> Self {
+        Self {
+            short,
+            long: long.into(),
+            description: description.into(),
+            ty,
+            default: None,
+            required: false,
+            validator: None,
+            env: None,
+            config_file: false,
+        }
+    }
+
+    /// Sets the raw value used if the argument is not given.
+    #[inline]
+    pub fn with_default(mut self, default: impl Into<String>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
+    /// Makes the argument required.
+    #[inline]
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Sets a [`Validator`] that checks the value beyond its type.
+    #[inline]
+    pub fn with_validator(mut self, validator: Validator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Sets an environment variable that gives the value if the nested CLI string doesn't.
+    #[inline]
+    pub fn with_env(mut self, var: impl Into<String>) -> Self {
+        self.env = Some(var.into());
+        self
+    }
+
+    /// Makes this argument the path to a config file, in which the other arguments may be given as `<LONGNAME>=<VALUE>`
+    /// lines (empty lines and lines starting with `#` are ignored). Values are quoted and escaped as in the nested CLI
+    /// string.
+    ///
+    /// # Panics
+    /// The [`TypedParser`] panics on construction if this argument is not an [`ArgType::Path`].
+    #[inline]
+    pub fn as_config_file(mut self) -> Self {
+        self.config_file = true;
+        self
+    }
+
+    /// Parses and validates a raw value for this argument.
+    ///
+    /// # Arguments
+    /// - `raw`: The raw value as given by the user, if any.
+    /// - `origin`: Where the value came from, for errors.
+    ///
+    /// # Returns
+    /// The parsed [`Value`].
+    ///
+    /// # Errors
+    /// This function errors if the value is missing, has the wrong type or is rejected by the validator.
+    fn value(&self, raw: Option<&str>, origin: &Origin) -> Result<Value, Error> {
+        let value: Value = match (raw, &self.ty) {
+            (Some(raw), ty) => match ty.parse(raw) {
+                Some(value) => value,
+                None => {
+                    return Err(Error::IllegalValue { origin: origin.clone(), key: self.long.clone(), ty: ty.clone(), raw: raw.into() });
+                },
+            },
+            (None, ArgType::Bool) => Value::Bool(true),
+            (None, ty) => return Err(Error::MissingValue { origin: origin.clone(), key: self.long.clone(), ty: ty.clone() }),
+        };
+        if let Some(validator) = &self.validator {
+            if let Err(reason) = validator(&value) {
+                return Err(Error::InvalidValue { origin: origin.clone(), key: self.long.clone(), raw: raw.unwrap_or_default().into(), reason });
+            }
+        }
+        Ok(value)
+    }
+}
+impl Debug for ArgSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        f.debug_struct("ArgSpec")
+            .field("short", &self.short)
+            .field("long", &self.long)
+            .field("description", &self.description)
+            .field("ty", &self.ty)
+            .field("default", &self.default)
+            .field("required", &self.required)
+            .field("validator", &self.validator.as_ref().map(|_| "<validator>"))
+            .field("env", &self.env)
+            .field("config_file", &self.config_file)
+            .finish()
+    }
+}
+
+/// The arguments parsed by the [`TypedParser`], by longname.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TypedArgs {
+    /// The values of the given (or defaulted) arguments.
+    values:  HashMap<String, Value>,
+    /// Where the values came from.
+    origins: HashMap<String, Origin>,
+}
+impl TypedArgs {
+    /// Returns where the value of an argument came from, if it was given or has a default.
+    #[inline]
+    pub fn origin(&self, key: &str) -> Option<&Origin> { self.origins.get(key) }
+
+    /// Returns the value of an argument, if it was given or has a default.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&Value> { self.values.get(key) }
+
+    /// Returns the value of a string or enum argument.
+    #[inline]
+    pub fn get_str(&self, key: &str) -> Option<&str> { self.values.get(key).and_then(Value::as_str) }
+
+    /// Returns the value of an integer argument.
+    #[inline]
+    pub fn get_int(&self, key: &str) -> Option<i64> { self.values.get(key).and_then(Value::as_int) }
+
+    /// Returns the value of a boolean argument, which is `false` if it was not given and has no default.
+    #[inline]
+    pub fn get_bool(&self, key: &str) -> bool { self.values.get(key).and_then(Value::as_bool).unwrap_or(false) }
+
+    /// Returns the value of a path argument.
+    #[inline]
+    pub fn get_path(&self, key: &str) -> Option<&Path> { self.values.get(key).and_then(Value::as_path) }
+}
+
+/***** LIBRARY *****/
+/// A nested parser that parses a list of key/value pairs like the [`MapParser`], but then gives every value the type
+/// of its argument, fills in defaults and checks constraints.
+#[derive(Debug)]
+pub struct TypedParser {
+    /// The arguments recognized by this parser.
+    specs:  Vec<ArgSpec>,
+    /// The parser that reads the key/value pairs.
+    parser: MapParser,
+}
+impl TypedParser {
+    /// Constructor for the TypedParser.
+    ///
+    /// # Arguments
+    /// - `specs`: The [`ArgSpec`]s of the arguments recognized by the parser.
+    ///
+    /// # Returns
+    /// A new [`TypedParser`] instance.
+    ///
+    /// # Panics
+    /// This function panics if any of the keys are not simple alphanumber strings (only underscores and dashes are allowed), if a default is not a valid value of its argument, or if there is more than one config file argument or it is not an [`ArgType::Path`].
+    pub fn new(specs: impl IntoIterator<Item = ArgSpec>) -> Self {
+        let specs: Vec<ArgSpec> = specs.into_iter().collect();
+        for spec in &specs {
+            if let Some(default) = &spec.default {
+                if let Err(err) = spec.value(Some(default), &Origin::Default) {
+                    panic!("Given default '{default}' for '{}' is not valid: {err}", spec.long);
+                }
+            }
+            if spec.config_file && spec.ty != ArgType::Path {
+                panic!("Given config file argument '{}' is not a path", spec.long);
+            }
+        }
+        if specs.iter().filter(|spec| spec.config_file).count() > 1 {
+            panic!("Given more than one config file argument");
+        }
+        let parser = MapParser::new(specs.iter().map(|spec| (spec.short, spec.long.clone(), spec.description.clone())));
+        Self { specs, parser }
+    }
+
+    /// Reads the raw values of arguments from a config file.
+    ///
+    /// # Arguments
+    /// - `path`: The path to the config file.
+    ///
+    /// # Returns
+    /// The raw values given in the file, with where they were given, by longname.
+    ///
+    /// # Errors
+    /// This function errors if the file could not be read, or if any of its lines is not a valid key/value pair.
+    fn read_config_file(&self, path: &Path) -> Result<HashMap<String, (Option<String>, Origin)>, Error> {
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::ConfigFileRead { path: path.into(), err })?;
+        let mut res: HashMap<String, (Option<String>, Origin)> = HashMap::new();
+        for (i, line) in raw.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let given = self.parser.parse_positioned(line).map_err(|err| Error::ConfigFileSyntax { path: path.into(), line: i + 1, err })?;
+            for (key, value, _) in given {
+                res.insert(key, (value, Origin::File { path: path.into(), line: i + 1 }));
+            }
+        }
+        Ok(res)
+    }
+}
+impl NestedCliParser for TypedParser {
+    type Args = TypedArgs;
+    type ParseError = Error;
+
+    fn help_fmt(&self, name: &str, short: char, long: &str, f: &mut Formatter<'_>) -> FResult {
+        writeln!(f, "{name} nested arguments")?;
+        writeln!(f, "Usage: -{short},--{long} \"[<OPTIONS...>]\"")?;
+        if self.specs.iter().any(|spec| spec.env.is_some() || spec.config_file) {
+            writeln!(f)?;
+            writeln!(f, "Options given here take precedence over environment variables, which take precedence over the config file.")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Options:")?;
+        for spec in &self.specs {
+            let placeholder: String = spec.ty.placeholder();
+            if spec.ty == ArgType::Bool {
+                writeln!(f, "  {short}[={placeholder}],{long}[={placeholder}]", short = spec.short, long = spec.long)?;
+            } else {
+                writeln!(f, "  {short}={placeholder},{long}={placeholder}", short = spec.short, long = spec.long)?;
+            }
+            writeln!(f, "      {}", spec.description)?;
+            if spec.config_file {
+                writeln!(f, "      A file with any of the other options as '<LONGNAME>=<VALUE>' lines")?;
+            }
+            if let Some(var) = &spec.env {
+                writeln!(f, "      Environment: {var}")?;
+            }
+            match (&spec.default, spec.required) {
+                (Some(default), _) => writeln!(f, "      Default: '{default}'")?,
+                (None, true) => writeln!(f, "      Required")?,
+                (None, false) => {},
+            }
+        }
+        writeln!(f)
+    }
+
+    fn parse(&self, args: &str) -> Result<Self::Args, Self::ParseError> {
+        // Read the key/value pairs on the CLI first
+        let mut raws: HashMap<String, (Option<String>, Origin)> = HashMap::with_capacity(self.specs.len());
+        for (key, raw, pos) in self.parser.parse_positioned(args).map_err(|err| Error::Syntax { err })? {
+            raws.insert(key, (raw, Origin::Cli { pos }));
+        }
+
+        // Then fall back to the environment...
+        for spec in &self.specs {
+            if raws.contains_key(&spec.long) {
+                continue;
+            }
+            if let Some(var) = &spec.env {
+                if let Some(raw) = env::var(var).ok().filter(|raw| !raw.is_empty()) {
+                    raws.insert(spec.long.clone(), (Some(raw), Origin::Env { var: var.clone() }));
+                }
+            }
+        }
+
+        // ...and then to the config file, if any
+        if let Some(spec) = self.specs.iter().find(|spec| spec.config_file) {
+            let path: Option<PathBuf> = match raws.get(&spec.long) {
+                Some((raw, origin)) => spec.value(raw.as_deref(), origin)?.as_path().map(PathBuf::from),
+                None => spec.default.as_ref().map(PathBuf::from),
+            };
+            if let Some(path) = path {
+                for (key, raw) in self.read_config_file(&path)? {
+                    raws.entry(key).or_insert(raw);
+                }
+            }
+        }
+
+        // Give them their types, filling in the defaults of the rest
+        let mut values: HashMap<String, Value> = HashMap::with_capacity(self.specs.len());
+        let mut origins: HashMap<String, Origin> = HashMap::with_capacity(self.specs.len());
+        for spec in &self.specs {
+            let (raw, origin): (Option<String>, Origin) = match raws.remove(&spec.long) {
+                Some(raw) => raw,
+                None => match &spec.default {
+                    Some(default) => (Some(default.clone()), Origin::Default),
+                    None if spec.required => return Err(Error::MissingRequired { key: spec.long.clone(), env: spec.env.clone() }),
+                    None => continue,
+                },
+            };
+            values.insert(spec.long.clone(), spec.value(raw.as_deref(), &origin)?);
+            origins.insert(spec.long.clone(), origin);
+        }
+        Ok(TypedArgs { values, origins })
+    }
+}
+
+
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a parser with an address (which may also be given in the given environment variable), a port and a config file.
+    fn parser(var: &str) -> TypedParser {
+        TypedParser::new([
+            ArgSpec::new('a', "address", "The address", ArgType::String).with_default("localhost").with_env(var),
+            ArgSpec::new('p', "port", "The port", ArgType::Int).with_default("8080").with_validator(at_least(1)),
+            ArgSpec::new('c', "config", "The config file", ArgType::Path).as_config_file(),
+        ])
+    }
+
+    /// Writes a config file with the given contents to a fresh path.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path: PathBuf = env::temp_dir().join(format!("nested-cli-parser-{}-{name}.conf", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_precedence() {
+        let var: &str = "NESTED_CLI_PARSER_TEST_PRECEDENCE_ADDRESS";
+        let parser: TypedParser = parser(var);
+        let path: PathBuf = config_file("precedence", "# The address\n\naddress=file\nport=1\n");
+
+        // Defaults, if nothing else is given
+        let args: TypedArgs = parser.parse("").unwrap();
+        assert_eq!(args.get_str("address"), Some("localhost"));
+        assert_eq!(args.origin("address"), Some(&Origin::Default));
+        assert_eq!(args.get_int("port"), Some(8080));
+        assert_eq!(args.get_path("config"), None);
+
+        // The config file wins over the defaults
+        let cli: String = format!("config={}", path.display());
+        let args: TypedArgs = parser.parse(&cli).unwrap();
+        assert_eq!(args.get_str("address"), Some("file"));
+        assert_eq!(args.origin("address"), Some(&Origin::File { path: path.clone(), line: 3 }));
+        assert_eq!(args.get_int("port"), Some(1));
+
+        // The environment wins over the config file
+        env::set_var(var, "env");
+        let args: TypedArgs = parser.parse(&cli).unwrap();
+        assert_eq!(args.get_str("address"), Some("env"));
+        assert_eq!(args.origin("address"), Some(&Origin::Env { var: var.into() }));
+        assert_eq!(args.get_int("port"), Some(1));
+
+        // The CLI wins over everything
+        let args: TypedArgs = parser.parse(&format!("a=cli,{cli}")).unwrap();
+        assert_eq!(args.get_str("address"), Some("cli"));
+        assert_eq!(args.origin("address"), Some(&Origin::Cli { pos: 0 }));
+        assert_eq!(args.origin("config"), Some(&Origin::Cli { pos: 6 }));
+        env::remove_var(var);
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let parser: TypedParser = parser("NESTED_CLI_PARSER_TEST_INVALID_VALUE_ADDRESS");
+
+        // Values that are not of the argument's type, or rejected by its validator, are reported where they were given
+        assert!(matches!(
+            parser.parse("address=x,port=abc"),
+            Err(Error::IllegalValue { origin: Origin::Cli { pos: 10 }, key, ty: ArgType::Int, raw }) if key == "port" && raw == "abc"
+        ));
+        assert!(matches!(
+            parser.parse("port=0"),
+            Err(Error::InvalidValue { origin: Origin::Cli { pos: 0 }, key, raw, .. }) if key == "port" && raw == "0"
+        ));
+        let path: PathBuf = config_file("invalid-value", "address=file\nport=abc\n");
+        match parser.parse(&format!("config={}", path.display())) {
+            Err(Error::IllegalValue { origin: Origin::File { path: file, line: 2 }, key, .. }) => {
+                assert_eq!(file, path);
+                assert_eq!(key, "port");
+            },
+            res => panic!("Expected an illegal value on line 2 of the config file, got {res:?}"),
+        }
+
+        // Arguments other than booleans need a value
+        assert!(matches!(parser.parse("address"), Err(Error::MissingValue { origin: Origin::Cli { pos: 0 }, key, .. }) if key == "address"));
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let var: &str = "NESTED_CLI_PARSER_TEST_MISSING_REQUIRED_TOKEN";
+        let parser: TypedParser = TypedParser::new([
+            ArgSpec::new('t', "token", "The token", ArgType::String).required().with_env(var),
+            ArgSpec::new('v', "verbose", "Whether to be verbose", ArgType::Bool),
+        ]);
+        assert!(matches!(
+            parser.parse("verbose"),
+            Err(Error::MissingRequired { key, env: Some(env) }) if key == "token" && env == var
+        ));
+
+        // Given anywhere is enough
+        let args: TypedArgs = parser.parse("t=secret").unwrap();
+        assert_eq!(args.get_str("token"), Some("secret"));
+        assert!(!args.get_bool("verbose"));
+    }
+
+    #[test]
+    fn test_config_file_syntax() {
+        let parser: TypedParser = parser("NESTED_CLI_PARSER_TEST_CONFIG_FILE_SYNTAX_ADDRESS");
+
+        // Malformed lines and unknown keys are reported with their line
+        let path: PathBuf = config_file("syntax", "address=file\nport=1=2\n");
+        assert!(matches!(
+            parser.parse(&format!("config={}", path.display())),
+            Err(Error::ConfigFileSyntax { line: 2, err: MapError::DuplicateEquals { .. }, .. })
+        ));
+        let path: PathBuf = config_file("unknown-key", "\n# Comments and empty lines count too\nnope=1\n");
+        assert!(matches!(
+            parser.parse(&format!("config={}", path.display())),
+            Err(Error::ConfigFileSyntax { line: 3, err: MapError::UnknownKey { key, .. }, .. }) if key == "nope"
+        ));
+
+        // Files that cannot be read at all are reported as such
+        let path: PathBuf = env::temp_dir().join(format!("nested-cli-parser-{}-missing.conf", std::process::id()));
+        assert!(matches!(parser.parse(&format!("config={}", path.display())), Err(Error::ConfigFileRead { .. })));
+    }
+}
@@ -0,0 +1,507 @@
+//! An async client for the deliberation and management APIs of the checker.
+//!
+//! Brane components, the tools in this repository and third-party tools can use this to talk to a checker with the
+//! same request and response models as the checker itself (see [`srv::models`]), instead of building requests by hand.
+//!
+//! # Example
+//! ```ignore
+//! use policy_reasoner_client::CheckerClient;
+//!
+//! let client = CheckerClient::new("localhost", 3030).with_token(jwt);
+//! let policy = client.get_active_policy().await?;
+//! println!("Active policy is version {:?}", policy.version.version);
+//! ```
+
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+
+use deliberation::spec::{
+    AccessDataRequest, ExecuteTaskRequest, QueryRequest, QueryResponse, Verdict, WhatIfRequest, WhatIfResponse, WorkflowValidationRequest,
+};
+use log::debug;
+use policy::{Policy, PolicyMetadata, PolicyVersion, PolicyVersionFilter};
+use reqwest::{Body, Client, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use srv::archive::SignedPolicyArchive;
+use srv::models::{
+    AddPolicyPostModel, AddPolicyStreamQueryModel, ClonePolicyPostModel, ComposePostModel, ImportResultModel, PatchPolicyModel,
+    PolicyContentPostModel, SetVersionPostModel, SimulatePostModel,
+};
+use srv::simulation::SimulationReport;
+use tokio::fs::{self as tfs, File as TFile};
+
+/***** CONSTANTS *****/
+/// The endpoint to list policy versions at.
+pub const GET_POLICY_VERSIONS_PATH: (Method, &'static str) = (Method::GET, "v1/management/policies");
+/// The endpoint to get a specific policy version at. `{version}` is replaced by the version.
+pub const GET_POLICY_VERSION_PATH: (Method, &'static str) = (Method::GET, "v1/management/policies/{version}");
+/// The endpoint to add new policy versions at.
+pub const ADD_POLICY_PATH: (Method, &'static str) = (Method::POST, "v1/management/policies");
+/// The endpoint to clone a policy version at. `{version}` is replaced by the version.
+pub const CLONE_POLICY_PATH: (Method, &'static str) = (Method::POST, "v1/management/policies/{version}/clone");
+/// The endpoint to derive a new policy version from an existing one at. `{version}` is replaced by the version.
+pub const PATCH_POLICY_PATH: (Method, &'static str) = (Method::PATCH, "v1/management/policies/{version}");
+/// The endpoint to get the active policy at.
+pub const GET_ACTIVE_POLICY_PATH: (Method, &'static str) = (Method::GET, "v1/management/policies/active");
+/// The endpoint to set the active policy at.
+pub const SET_ACTIVE_POLICY_PATH: (Method, &'static str) = (Method::PUT, "v1/management/policies/active");
+/// The endpoint to deactivate the active policy at.
+pub const DEACTIVATE_POLICY_PATH: (Method, &'static str) = (Method::DELETE, "v1/management/policies/active");
+/// The endpoint to activate a composition of policy versions at.
+pub const COMPOSE_ACTIVE_POLICY_PATH: (Method, &'static str) = (Method::PUT, "v1/management/policies/active/composed");
+/// The endpoint to simulate a policy version at. `{version}` is replaced by the version.
+pub const SIMULATE_POLICY_PATH: (Method, &'static str) = (Method::POST, "v1/management/policies/{version}/simulate");
+/// The endpoint to export the policy store at.
+pub const EXPORT_PATH: (Method, &'static str) = (Method::GET, "v1/management/export");
+/// The endpoint to import a policy store at.
+pub const IMPORT_PATH: (Method, &'static str) = (Method::POST, "v1/management/import");
+/// The endpoint to ask whether a task may be executed at.
+pub const EXECUTE_TASK_PATH: (Method, &'static str) = (Method::POST, "v1/deliberation/execute-task");
+/// The endpoint to ask whether data may be accessed at.
+pub const ACCESS_DATA_PATH: (Method, &'static str) = (Method::POST, "v1/deliberation/access-data");
+/// The endpoint to ask whether a workflow may be executed at.
+pub const EXECUTE_WORKFLOW_PATH: (Method, &'static str) = (Method::POST, "v1/deliberation/execute-workflow");
+/// The path to send queries to.
+pub const QUERY_PATH: (Method, &'static str) = (Method::POST, "v1/deliberation/query");
+/// The path to re-evaluate questions with changed facts at.
+pub const WHAT_IF_PATH: (Method, &'static str) = (Method::POST, "v1/deliberation/what-if");
+
+/// The reasoner that eFLINT JSON policies are meant for.
+const EFLINT_REASONER: &str = "eflint";
+/// The version of the reasoner that eFLINT JSON policies are meant for.
+const EFLINT_REASONER_VERSION: &str = "0.1.0";
+
+/***** ERRORS *****/
+/// Defines errors that originate from talking to the checker.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to open the policy file.
+    FileOpen { path: PathBuf, err: std::io::Error },
+    /// Failed to get the metadata of the policy file.
+    FileMetadata { path: PathBuf, err: std::io::Error },
+    /// Failed to read the policy file.
+    FileRead { path: PathBuf, err: std::io::Error },
+    /// The policy file is not JSON.
+    PolicyParse { path: PathBuf, err: serde_json::Error },
+    /// Failed to build the request.
+    RequestBuild { addr: String, err: reqwest::Error },
+    /// Failed to send the request.
+    RequestSend { addr: String, err: reqwest::Error },
+    /// The checker refused the request.
+    RequestFailed { addr: String, status: StatusCode, response: Option<String> },
+    /// Failed to download the body of the checker's reply.
+    ResponseDownload { addr: String, err: reqwest::Error },
+    /// The body of the checker's reply was not what we expected.
+    ResponseParse { addr: String, what: &'static str, raw: String, err: serde_json::Error },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            FileOpen { path, .. } => write!(f, "Failed to open eFLINT JSON file '{}'", path.display()),
+            FileMetadata { path, .. } => write!(f, "Failed to get metadata of eFLINT JSON file '{}'", path.display()),
+            FileRead { path, .. } => write!(f, "Failed to read eFLINT JSON file '{}'", path.display()),
+            PolicyParse { path, .. } => write!(f, "Failed to parse eFLINT JSON file as JSON '{}'", path.display()),
+            RequestBuild { addr, .. } => write!(f, "Failed to build request to '{addr}'"),
+            RequestSend { addr, .. } => write!(f, "Failed to execute request to '{addr}'"),
+            RequestFailed { addr, status, response } => {
+                write!(f, "Request to '{}' failed with {} ({})", addr, status.as_u16(), status.canonical_reason().unwrap_or("???"))?;
+                if let Some(response) = response {
+                    let line: String = (0..80).map(|_| '-').collect();
+                    write!(f, "\n\nResponse:\n{line}\n{response}\n{line}\n")?;
+                }
+                Ok(())
+            },
+            ResponseDownload { addr, .. } => write!(f, "Failed to download response body from '{addr}'"),
+            ResponseParse { addr, what, raw, .. } => {
+                let line: String = (0..80).map(|_| '-').collect();
+                write!(f, "Failed to parse response from '{addr}' as {what}\n\nResponse:\n{line}\n{raw}\n{line}\n")
+            },
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            FileOpen { err, .. } => Some(err),
+            FileMetadata { err, .. } => Some(err),
+            FileRead { err, .. } => Some(err),
+            PolicyParse { err, .. } => Some(err),
+            RequestBuild { err, .. } => Some(err),
+            RequestSend { err, .. } => Some(err),
+            RequestFailed { .. } => None,
+            ResponseDownload { err, .. } => Some(err),
+            ResponseParse { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// Describes a compiled eFLINT JSON policy file to add as a new policy version.
+#[derive(Clone, Debug, Default)]
+pub struct PolicyPush {
+    /// The description of the new version.
+    pub version_description: String,
+    /// The tags, jurisdiction and department of the new version.
+    pub metadata: PolicyMetadata,
+    /// Whether to stream the policy file to the checker as-is instead of reading it into memory first.
+    ///
+    /// Use this for very large policies. The file is then only validated by the checker.
+    pub stream: bool,
+}
+
+/***** LIBRARY *****/
+/// Talks to the APIs of a checker.
+#[derive(Clone, Debug)]
+pub struct CheckerClient {
+    /// The client to send requests with.
+    client: Client,
+    /// The URL of the checker, without trailing slash (e.g., `http://localhost:3030`).
+    base:   String,
+    /// The JWT to authenticate with, if any.
+    token:  Option<String>,
+}
+impl CheckerClient {
+    /// Constructor for the CheckerClient that talks to a checker over plain HTTP.
+    ///
+    /// # Arguments
+    /// - `address`: The address of the checker.
+    /// - `port`: The port of the checker.
+    ///
+    /// # Returns
+    /// A new CheckerClient that does not authenticate yet (see [`CheckerClient::with_token()`]).
+    #[inline]
+    pub fn new(address: &str, port: u16) -> Self { Self::from_url(format!("http://{address}:{port}")) }
+
+    /// Constructor for the CheckerClient that talks to a checker at the given URL (e.g., `https://checker.example.com`).
+    ///
+    /// # Arguments
+    /// - `base`: The URL of the checker, to which the API paths are appended.
+    ///
+    /// # Returns
+    /// A new CheckerClient that does not authenticate yet (see [`CheckerClient::with_token()`]).
+    #[inline]
+    pub fn from_url(base: impl Into<String>) -> Self {
+        let mut base: String = base.into();
+        while base.ends_with('/') {
+            base.pop();
+        }
+        Self { client: Client::new(), base, token: None }
+    }
+
+    /// Sets the JWT with which this client authenticates with the checker.
+    ///
+    /// Note that the deliberation and management APIs are typically protected with different keys, so you may need a
+    /// separate client for either.
+    ///
+    /// # Arguments
+    /// - `token`: The JWT to send as bearer token in every request.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Returns the URL of an endpoint of the checker.
+    #[inline]
+    pub fn url(&self, path: &str) -> String { format!("{}/{path}", self.base) }
+
+    /// Starts a request to an endpoint of the checker, authenticated if we have a token.
+    fn request(&self, method: Method, addr: &str) -> RequestBuilder {
+        let builder: RequestBuilder = self.client.request(method, addr);
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    /// Sends a request to the checker and parses its (successful) reply.
+    ///
+    /// # Arguments
+    /// - `addr`: The address the request goes to, for debugging.
+    /// - `builder`: The [`RequestBuilder`] of the request to send.
+    /// - `what`: What we expect in the reply, for debugging.
+    ///
+    /// # Errors
+    /// This function errors if the request could not be sent, if the checker did not reply with a success, or if the
+    /// reply was not of the expected type.
+    async fn send<R: DeserializeOwned>(&self, addr: String, builder: RequestBuilder, what: &'static str) -> Result<R, Error> {
+        debug!("Sending request to checker '{addr}'...");
+        let req = builder.build().map_err(|err| Error::RequestBuild { addr: addr.clone(), err })?;
+        let res: Response = self.client.execute(req).await.map_err(|err| Error::RequestSend { addr: addr.clone(), err })?;
+        let status: StatusCode = res.status();
+        if !status.is_success() {
+            return Err(Error::RequestFailed { addr, status, response: res.text().await.ok() });
+        }
+
+        // Parse the reply
+        debug!("Parsing checker response as {what}...");
+        let raw: String = res.text().await.map_err(|err| Error::ResponseDownload { addr: addr.clone(), err })?;
+        serde_json::from_str(&raw).map_err(|err| Error::ResponseParse { addr, what, raw, err })
+    }
+
+    /// Lists the policy versions in the checker.
+    ///
+    /// # Arguments
+    /// - `filter`: A [`PolicyVersionFilter`] that only lists matching versions.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn get_policy_versions(&self, filter: &PolicyVersionFilter) -> Result<Vec<PolicyVersion>, Error> {
+        let addr: String = self.url(GET_POLICY_VERSIONS_PATH.1);
+        self.send(addr.clone(), self.request(GET_POLICY_VERSIONS_PATH.0, &addr).query(filter), "a list of policy versions").await
+    }
+
+    /// Gets a specific policy version from the checker.
+    ///
+    /// # Arguments
+    /// - `version`: The version to get.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]), e.g., because the version does not exist.
+    pub async fn get_policy(&self, version: i64) -> Result<Policy, Error> {
+        let addr: String = self.url(&GET_POLICY_VERSION_PATH.1.replace("{version}", &version.to_string()));
+        self.send(addr.clone(), self.request(GET_POLICY_VERSION_PATH.0, &addr), "a policy").await
+    }
+
+    /// Adds a new policy version to the checker.
+    ///
+    /// # Arguments
+    /// - `body`: The [`AddPolicyPostModel`] describing the new version.
+    ///
+    /// # Returns
+    /// The new [`Policy`], including its version number.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn add_policy(&self, body: &AddPolicyPostModel) -> Result<Policy, Error> {
+        let addr: String = self.url(ADD_POLICY_PATH.1);
+        self.send(addr.clone(), self.request(ADD_POLICY_PATH.0, &addr).json(body), "a policy").await
+    }
+
+    /// Adds a compiled eFLINT JSON policy file as a new policy version to the checker.
+    ///
+    /// # Arguments
+    /// - `path`: The path to the eFLINT JSON file.
+    /// - `push`: The [`PolicyPush`] describing the new version.
+    ///
+    /// # Returns
+    /// The new [`Policy`], including its version number.
+    ///
+    /// # Errors
+    /// This function errors if the policy file could not be read (or is not JSON, unless streamed), or if the request
+    /// failed (see [`Error`]).
+    pub async fn add_policy_file(&self, path: &Path, push: PolicyPush) -> Result<Policy, Error> {
+        let addr: String = self.url(ADD_POLICY_PATH.1);
+        let builder: RequestBuilder = if push.stream {
+            // Have the file streamed to the checker as-is, instead of reading (and re-serializing) it first
+            debug!("Opening policy file '{}'...", path.display());
+            let handle: TFile = TFile::open(path).await.map_err(|err| Error::FileOpen { path: path.into(), err })?;
+            let len: u64 = handle.metadata().await.map_err(|err| Error::FileMetadata { path: path.into(), err })?.len();
+
+            // The rest of the request goes in the query
+            let PolicyMetadata { tags, jurisdiction, department } = push.metadata;
+            let query = AddPolicyStreamQueryModel {
+                description: None,
+                version_description: push.version_description,
+                reasoner: EFLINT_REASONER.into(),
+                reasoner_version: EFLINT_REASONER_VERSION.into(),
+                tags: if tags.is_empty() { None } else { Some(tags.join(",")) },
+                jurisdiction,
+                department,
+            };
+            self.request(ADD_POLICY_PATH.0, &addr)
+                .query(&query)
+                .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+                .header(reqwest::header::CONTENT_LENGTH, len)
+                .body(Body::from(handle))
+        } else {
+            // Read the file in its entirety, and check it's JSON
+            debug!("Opening policy file '{}'...", path.display());
+            let policy: String = tfs::read_to_string(path).await.map_err(|err| Error::FileRead { path: path.into(), err })?;
+            let policy: Box<RawValue> = serde_json::from_str(&policy).map_err(|err| Error::PolicyParse { path: path.into(), err })?;
+
+            // Wrap it in the request
+            let body = AddPolicyPostModel {
+                description: None,
+                version_description: push.version_description,
+                content: vec![PolicyContentPostModel {
+                    reasoner: EFLINT_REASONER.into(),
+                    reasoner_version: EFLINT_REASONER_VERSION.into(),
+                    content: policy,
+                }],
+                metadata: push.metadata,
+            };
+            self.request(ADD_POLICY_PATH.0, &addr).json(&body)
+        };
+        self.send(addr, builder, "a policy").await
+    }
+
+    /// Adds a new policy version to the checker with the same contents as an existing one.
+    ///
+    /// # Arguments
+    /// - `version`: The version to clone.
+    /// - `body`: The [`ClonePolicyPostModel`] describing the new version.
+    ///
+    /// # Returns
+    /// The new [`Policy`], including its version number.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]), e.g., because the version does not exist.
+    pub async fn clone_policy(&self, version: i64, body: &ClonePolicyPostModel) -> Result<Policy, Error> {
+        let addr: String = self.url(&CLONE_POLICY_PATH.1.replace("{version}", &version.to_string()));
+        self.send(addr.clone(), self.request(CLONE_POLICY_PATH.0, &addr).json(body), "a policy").await
+    }
+
+    /// Adds a new policy version to the checker that changes only some contents of an existing one.
+    ///
+    /// # Arguments
+    /// - `version`: The version to derive the new one from.
+    /// - `body`: The [`PatchPolicyModel`] with the contents to change.
+    ///
+    /// # Returns
+    /// The new [`Policy`], including its version number.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]), e.g., because the version does not exist.
+    pub async fn patch_policy(&self, version: i64, body: &PatchPolicyModel) -> Result<Policy, Error> {
+        let addr: String = self.url(&PATCH_POLICY_PATH.1.replace("{version}", &version.to_string()));
+        self.send(addr.clone(), self.request(PATCH_POLICY_PATH.0, &addr).json(body), "a policy").await
+    }
+
+    /// Gets the active policy from the checker.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]), e.g., because no policy is active.
+    pub async fn get_active_policy(&self) -> Result<Policy, Error> {
+        let addr: String = self.url(GET_ACTIVE_POLICY_PATH.1);
+        self.send(addr.clone(), self.request(GET_ACTIVE_POLICY_PATH.0, &addr), "a policy").await
+    }
+
+    /// Makes a policy version the active policy of the checker.
+    ///
+    /// # Arguments
+    /// - `version`: The version to activate.
+    ///
+    /// # Returns
+    /// The now active [`Policy`].
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn set_active_policy(&self, version: i64) -> Result<Policy, Error> {
+        let addr: String = self.url(SET_ACTIVE_POLICY_PATH.1);
+        self.send(addr.clone(), self.request(SET_ACTIVE_POLICY_PATH.0, &addr).json(&SetVersionPostModel { version }), "a policy").await
+    }
+
+    /// Deactivates the active policy of the checker, after which it denies everything.
+    ///
+    /// # Returns
+    /// The [`Policy`] that was active.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn deactivate_policy(&self) -> Result<Policy, Error> {
+        let addr: String = self.url(DEACTIVATE_POLICY_PATH.1);
+        self.send(addr.clone(), self.request(DEACTIVATE_POLICY_PATH.0, &addr), "a policy").await
+    }
+
+    /// Composes policy versions into a new version, and makes that the active policy of the checker.
+    ///
+    /// # Arguments
+    /// - `body`: The [`ComposePostModel`] describing which versions to compose.
+    ///
+    /// # Returns
+    /// The new, active [`Policy`].
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn compose_active_policy(&self, body: &ComposePostModel) -> Result<Policy, Error> {
+        let addr: String = self.url(COMPOSE_ACTIVE_POLICY_PATH.1);
+        self.send(addr.clone(), self.request(COMPOSE_ACTIVE_POLICY_PATH.0, &addr).json(body), "a policy").await
+    }
+
+    /// Replays recorded questions against a policy version, without activating it.
+    ///
+    /// # Arguments
+    /// - `version`: The version to simulate.
+    /// - `body`: The [`SimulatePostModel`] with the questions to replay.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn simulate_policy(&self, version: i64, body: &SimulatePostModel) -> Result<SimulationReport, Error> {
+        let addr: String = self.url(&SIMULATE_POLICY_PATH.1.replace("{version}", &version.to_string()));
+        self.send(addr.clone(), self.request(SIMULATE_POLICY_PATH.0, &addr).json(body), "a simulation report").await
+    }
+
+    /// Exports the policy store of the checker.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn export(&self) -> Result<SignedPolicyArchive, Error> {
+        let addr: String = self.url(EXPORT_PATH.1);
+        self.send(addr.clone(), self.request(EXPORT_PATH.0, &addr), "a signed policy archive").await
+    }
+
+    /// Imports a policy store into the checker, replacing its policy versions.
+    ///
+    /// # Arguments
+    /// - `archive`: The [`SignedPolicyArchive`] to import, e.g., as given by [`CheckerClient::export()`].
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]).
+    pub async fn import(&self, archive: &SignedPolicyArchive) -> Result<ImportResultModel, Error> {
+        let addr: String = self.url(IMPORT_PATH.1);
+        self.send(addr.clone(), self.request(IMPORT_PATH.0, &addr).json(archive), "an import result").await
+    }
+
+    /// Asks the checker whether a task may be executed.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]). Note that a denial is not an error.
+    pub async fn execute_task(&self, body: &ExecuteTaskRequest) -> Result<Verdict, Error> {
+        let addr: String = self.url(EXECUTE_TASK_PATH.1);
+        self.send(addr.clone(), self.request(EXECUTE_TASK_PATH.0, &addr).json(body), "a verdict").await
+    }
+
+    /// Asks the checker whether a dataset may be accessed.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]). Note that a denial is not an error.
+    pub async fn access_data(&self, body: &AccessDataRequest) -> Result<Verdict, Error> {
+        let addr: String = self.url(ACCESS_DATA_PATH.1);
+        self.send(addr.clone(), self.request(ACCESS_DATA_PATH.0, &addr).json(body), "a verdict").await
+    }
+
+    /// Asks the checker whether a workflow may be executed.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]). Note that a denial is not an error.
+    pub async fn execute_workflow(&self, body: &WorkflowValidationRequest) -> Result<Verdict, Error> {
+        let addr: String = self.url(EXECUTE_WORKFLOW_PATH.1);
+        self.send(addr.clone(), self.request(EXECUTE_WORKFLOW_PATH.0, &addr).json(body), "a verdict").await
+    }
+
+    /// Asks the checker what holds under its active policy.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]), including if the checker's reasoner does not answer queries.
+    pub async fn query(&self, body: &QueryRequest) -> Result<QueryResponse, Error> {
+        let addr: String = self.url(QUERY_PATH.1);
+        self.send(addr.clone(), self.request(QUERY_PATH.0, &addr).json(body), "a query answer").await
+    }
+
+    /// Asks the checker to re-evaluate a question it answered recently with some of its facts changed.
+    ///
+    /// # Errors
+    /// This function errors if the request failed (see [`Error`]), including if the checker no longer remembers the question.
+    pub async fn what_if(&self, body: &WhatIfRequest) -> Result<WhatIfResponse, Error> {
+        let addr: String = self.url(WHAT_IF_PATH.1);
+        self.send(addr.clone(), self.request(WHAT_IF_PATH.0, &addr).json(body), "a what-if answer").await
+    }
+}
@@ -0,0 +1,330 @@
+//! Python bindings for the [`policy_reasoner_client`], such that data stewards and researchers can script against a
+//! checker without hand-crafting HTTP requests.
+//!
+//! Requests and replies are given and returned as plain Python objects (dicts, lists, strings, ...) in the same shape
+//! as the JSON the checker speaks, but they are checked against the checker's models before being sent.
+//!
+//! Build and install the module with [maturin](https://www.maturin.rs) from this crate's directory:
+//! ```bash
+//! maturin develop --release
+//! ```
+//! after which it can be used as:
+//! ```python
+//! import policy_reasoner
+//!
+//! client = policy_reasoner.CheckerClient("localhost", 3030, token=jwt)
+//! workflow = policy_reasoner.compile_workflow(open("workflow.bs").read(), "packages", "data", user="amy")
+//! print(client.execute_workflow("central", workflow)["verdict"])
+//! ```
+
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+use brane_ast::{CompileResult, ParserOptions, Workflow};
+use deliberation::spec::{AccessDataRequest, ExecuteTaskRequest, QueryRequest, WhatIfRequest, WorkflowValidationRequest};
+use error_trace::ErrorTrace as _;
+use policy::{PolicyMetadata, PolicyVersionFilter};
+use policy_reasoner_client::PolicyPush;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use specifications::data::DataIndex;
+use specifications::package::PackageIndex;
+use srv::models::{AddPolicyPostModel, ComposePostModel, SimulatePostModel};
+use tokio::runtime::{Builder, Runtime};
+
+/***** ERRORS *****/
+create_exception!(policy_reasoner, CheckerError, PyException, "Raised when a request to the checker fails (including when it refuses it).");
+create_exception!(policy_reasoner, WorkflowError, PyException, "Raised when a workflow could not be compiled.");
+
+/***** HELPERS *****/
+/// Converts a Python object to one of the checker's models by going through JSON.
+///
+/// # Arguments
+/// - `obj`: The Python object to convert.
+/// - `what`: What we're converting to, for debugging.
+///
+/// # Errors
+/// This function errors with a [`PyValueError`] if the object is not JSON or not in the shape of the model.
+fn from_py<T: DeserializeOwned>(obj: &Bound<PyAny>, what: &str) -> PyResult<T> {
+    let raw: String = obj.py().import_bound("json")?.call_method1("dumps", (obj,))?.extract()?;
+    serde_json::from_str(&raw).map_err(|err| PyValueError::new_err(format!("Given object is not a valid {what}: {err}")))
+}
+
+/// Converts one of the checker's models to a Python object by going through JSON.
+///
+/// # Arguments
+/// - `py`: The Python interpreter to create the object in.
+/// - `value`: The value to convert.
+///
+/// # Errors
+/// This function errors with a [`PyValueError`] if the value could not be serialized.
+fn to_py(py: Python, value: &impl Serialize) -> PyResult<PyObject> {
+    let raw: String = serde_json::to_string(value).map_err(|err| PyValueError::new_err(format!("Failed to serialize checker reply: {err}")))?;
+    Ok(py.import_bound("json")?.call_method1("loads", (raw,))?.unbind())
+}
+
+/// Checks a request assembled from Python arguments against its model.
+///
+/// # Errors
+/// This function errors with a [`PyValueError`] if the request is not in the shape of the model.
+fn request<T: DeserializeOwned>(value: Value) -> PyResult<T> {
+    serde_json::from_value(value).map_err(|err| PyValueError::new_err(format!("Invalid request: {err}")))
+}
+
+/***** LIBRARY *****/
+/// Talks to the deliberation and management APIs of a checker.
+///
+/// Every method blocks until the checker replied, and raises a `CheckerError` if it did not reply with a success.
+#[pyclass(module = "policy_reasoner", frozen)]
+struct CheckerClient {
+    /// The actual client.
+    client:  policy_reasoner_client::CheckerClient,
+    /// The runtime on which we run its requests.
+    runtime: Runtime,
+}
+impl CheckerClient {
+    /// Wraps a client in a runtime.
+    fn wrap(client: policy_reasoner_client::CheckerClient, token: Option<String>) -> PyResult<Self> {
+        let client = match token {
+            Some(token) => client.with_token(token),
+            None => client,
+        };
+        let runtime: Runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| PyException::new_err(format!("Failed to create runtime for talking to the checker: {err}")))?;
+        Ok(Self { client, runtime })
+    }
+
+    /// Runs a request to completion (without holding the GIL), and converts its reply to Python.
+    fn run<'s, T, F>(&'s self, py: Python, req: impl Send + FnOnce(&'s policy_reasoner_client::CheckerClient) -> F) -> PyResult<PyObject>
+    where
+        T: Serialize,
+        F: Future<Output = Result<T, policy_reasoner_client::Error>>,
+    {
+        let res: T = py.allow_threads(|| self.runtime.block_on(req(&self.client))).map_err(|err| CheckerError::new_err(err.trace().to_string()))?;
+        to_py(py, &res)
+    }
+}
+#[pymethods]
+impl CheckerClient {
+    /// Creates a client for the checker at `http://<address>:<port>`, authenticating with `token` if given.
+    #[new]
+    #[pyo3(signature = (address, port, token = None))]
+    fn new(address: &str, port: u16, token: Option<String>) -> PyResult<Self> {
+        Self::wrap(policy_reasoner_client::CheckerClient::new(address, port), token)
+    }
+
+    /// Creates a client for the checker at the given URL (e.g., `https://checker.example.com`).
+    #[staticmethod]
+    #[pyo3(signature = (url, token = None))]
+    fn from_url(url: String, token: Option<String>) -> PyResult<Self> { Self::wrap(policy_reasoner_client::CheckerClient::from_url(url), token) }
+
+    /// Lists the policy versions in the checker, optionally only those with the given tag, jurisdiction and/or department.
+    #[pyo3(signature = (tag = None, jurisdiction = None, department = None))]
+    fn get_policy_versions(&self, py: Python, tag: Option<String>, jurisdiction: Option<String>, department: Option<String>) -> PyResult<PyObject> {
+        let filter = PolicyVersionFilter { tag, jurisdiction, department };
+        self.run(py, |c| async move { c.get_policy_versions(&filter).await })
+    }
+
+    /// Gets a specific policy version.
+    fn get_policy(&self, py: Python, version: i64) -> PyResult<PyObject> { self.run(py, |c| c.get_policy(version)) }
+
+    /// Adds a new policy version, given as a dict in the shape of an add-policy request.
+    fn add_policy(&self, py: Python, body: &Bound<PyAny>) -> PyResult<PyObject> {
+        let body: AddPolicyPostModel = from_py(body, "add-policy request")?;
+        self.run(py, |c| async move { c.add_policy(&body).await })
+    }
+
+    /// Adds a compiled eFLINT JSON policy file as a new policy version.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (path, version_description, tags = Vec::new(), jurisdiction = None, department = None, stream = false))]
+    fn add_policy_file(
+        &self,
+        py: Python,
+        path: String,
+        version_description: String,
+        tags: Vec<String>,
+        jurisdiction: Option<String>,
+        department: Option<String>,
+        stream: bool,
+    ) -> PyResult<PyObject> {
+        let push = PolicyPush { version_description, metadata: PolicyMetadata { tags, jurisdiction, department }, stream };
+        self.run(py, |c| async move { c.add_policy_file(Path::new(&path), push).await })
+    }
+
+    /// Gets the active policy.
+    fn get_active_policy(&self, py: Python) -> PyResult<PyObject> { self.run(py, |c| c.get_active_policy()) }
+
+    /// Makes a policy version the active policy.
+    fn set_active_policy(&self, py: Python, version: i64) -> PyResult<PyObject> { self.run(py, |c| c.set_active_policy(version)) }
+
+    /// Deactivates the active policy, after which the checker denies everything.
+    fn deactivate_policy(&self, py: Python) -> PyResult<PyObject> { self.run(py, |c| c.deactivate_policy()) }
+
+    /// Composes the given policy versions into a new version, and makes that the active policy.
+    #[pyo3(signature = (versions, description = None))]
+    fn compose_active_policy(&self, py: Python, versions: Vec<i64>, description: Option<String>) -> PyResult<PyObject> {
+        let body = ComposePostModel { fragments: versions, description };
+        self.run(py, |c| async move { c.compose_active_policy(&body).await })
+    }
+
+    /// Replays recorded questions (a list of dicts) against a policy version, without activating it.
+    fn simulate_policy(&self, py: Python, version: i64, questions: &Bound<PyAny>) -> PyResult<PyObject> {
+        let body = SimulatePostModel { questions: from_py(questions, "list of recorded questions")? };
+        self.run(py, |c| async move { c.simulate_policy(version, &body).await })
+    }
+
+    /// Exports the policy store as a signed archive.
+    fn export(&self, py: Python) -> PyResult<PyObject> { self.run(py, |c| c.export()) }
+
+    /// Imports a signed archive (as given by `export()`) into the policy store.
+    fn import_archive(&self, py: Python, archive: &Bound<PyAny>) -> PyResult<PyObject> {
+        let archive: srv::archive::SignedPolicyArchive = from_py(archive, "signed policy archive")?;
+        self.run(py, |c| async move { c.import(&archive).await })
+    }
+
+    /// Asks whether the task at `task_id` in the given workflow may be executed; `explain` asks to explain a denial, and
+    /// `granularity` (`"fail-fast"` or `"exhaustive"`) how many reasons for it to look for.
+    #[pyo3(signature = (use_case, workflow, task_id, priority = "interactive", explain = false, granularity = None))]
+    fn execute_task(
+        &self,
+        py: Python,
+        use_case: String,
+        workflow: &Bound<PyAny>,
+        task_id: &Bound<PyAny>,
+        priority: &str,
+        explain: bool,
+        granularity: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let body: ExecuteTaskRequest = request(serde_json::json!({
+            "use_case": use_case,
+            "workflow": from_py::<Value>(workflow, "workflow")?,
+            "task_id": from_py::<Value>(task_id, "task ID")?,
+            "priority": priority,
+            "explain": explain,
+            "granularity": granularity,
+        }))?;
+        self.run(py, |c| async move { c.execute_task(&body).await })
+    }
+
+    /// Asks whether the dataset `data_id` may be accessed, for the task at `task_id` or (if omitted) as result of the workflow.
+    #[pyo3(signature = (use_case, workflow, data_id, task_id = None, priority = "interactive", explain = false, granularity = None))]
+    fn access_data(
+        &self,
+        py: Python,
+        use_case: String,
+        workflow: &Bound<PyAny>,
+        data_id: String,
+        task_id: Option<&Bound<PyAny>>,
+        priority: &str,
+        explain: bool,
+        granularity: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let body: AccessDataRequest = request(serde_json::json!({
+            "use_case": use_case,
+            "workflow": from_py::<Value>(workflow, "workflow")?,
+            "data_id": data_id,
+            "task_id": task_id.map(|t| from_py::<Value>(t, "task ID")).transpose()?,
+            "priority": priority,
+            "explain": explain,
+            "granularity": granularity,
+        }))?;
+        self.run(py, |c| async move { c.access_data(&body).await })
+    }
+
+    /// Asks whether the given workflow as a whole may be executed; `node_results` asks whether every task may be, too.
+    #[pyo3(signature = (use_case, workflow, priority = "interactive", explain = false, node_results = false, granularity = None))]
+    fn execute_workflow(
+        &self,
+        py: Python,
+        use_case: String,
+        workflow: &Bound<PyAny>,
+        priority: &str,
+        explain: bool,
+        node_results: bool,
+        granularity: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let body: WorkflowValidationRequest = request(serde_json::json!({
+            "use_case": use_case,
+            "workflow": from_py::<Value>(workflow, "workflow")?,
+            "priority": priority,
+            "explain": explain,
+            "node_results": node_results,
+            "granularity": granularity,
+        }))?;
+        self.run(py, |c| async move { c.execute_workflow(&body).await })
+    }
+
+    /// Asks what holds under the active policy; `query` is given in the language of the reasoner (e.g., eFLINT JSON).
+    #[pyo3(signature = (use_case, query, priority = "interactive"))]
+    fn query(&self, py: Python, use_case: String, query: &Bound<PyAny>, priority: &str) -> PyResult<PyObject> {
+        let body: QueryRequest = request(serde_json::json!({
+            "use_case": use_case,
+            "query": from_py::<Value>(query, "query")?,
+            "priority": priority,
+        }))?;
+        self.run(py, |c| async move { c.query(&body).await })
+    }
+
+    /// Re-evaluates a question answered recently with some of its facts changed; `overrides` is a list of dicts with a `kind`.
+    #[pyo3(signature = (reference, overrides, priority = "interactive"))]
+    fn what_if(&self, py: Python, reference: String, overrides: &Bound<PyAny>, priority: &str) -> PyResult<PyObject> {
+        let body: WhatIfRequest = request(serde_json::json!({
+            "reference": reference,
+            "overrides": from_py::<Value>(overrides, "overrides")?,
+            "priority": priority,
+        }))?;
+        self.run(py, |c| async move { c.what_if(&body).await })
+    }
+}
+
+/// Compiles a BraneScript workflow to the Brane WIR that the checker reasons about.
+///
+/// The packages and datasets it uses are read from the given directories. If `user` is given, it is set as the user
+/// receiving the workflow's result (which the checker requires).
+#[pyfunction]
+#[pyo3(signature = (source, packages, data, user = None))]
+fn compile_workflow(py: Python, source: &str, packages: &str, data: &str, user: Option<String>) -> PyResult<PyObject> {
+    let pindex: PackageIndex = brane_shr::utilities::create_package_index_from(packages);
+    let dindex: DataIndex = brane_shr::utilities::create_data_index_from(data);
+    let mut workflow: Workflow = match brane_ast::compile_program(source.as_bytes(), &pindex, &dindex, &ParserOptions::bscript()) {
+        CompileResult::Workflow(workflow, warns) => {
+            for warn in warns {
+                warn.prettyprint("<workflow>", source);
+            }
+            workflow
+        },
+        CompileResult::Err(errs) => {
+            for err in errs {
+                err.prettyprint("<workflow>", source);
+            }
+            return Err(WorkflowError::new_err("Failed to compile workflow (see output above)"));
+        },
+        CompileResult::Eof(err) => {
+            err.prettyprint("<workflow>", source);
+            return Err(WorkflowError::new_err("Failed to compile workflow (see output above)"));
+        },
+
+        CompileResult::Program(_, _) | CompileResult::Unresolved(_, _) => unreachable!(),
+    };
+    if let Some(user) = user {
+        workflow.user = Arc::new(Some(user));
+    }
+    to_py(py, &workflow)
+}
+
+/// The `policy_reasoner` Python module.
+#[pymodule]
+fn policy_reasoner(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<CheckerClient>()?;
+    m.add_function(wrap_pyfunction!(compile_workflow, m)?)?;
+    m.add("CheckerError", m.py().get_type_bound::<CheckerError>())?;
+    m.add("WorkflowError", m.py().get_type_bound::<WorkflowError>())?;
+    Ok(())
+}
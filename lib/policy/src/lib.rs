@@ -1,15 +1,70 @@
 use core::fmt::Debug;
 use std::fmt::Display;
 use std::future::Future;
+use std::sync::Arc;
 
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest as _, Sha256};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PolicyContent {
     pub reasoner: String,
     pub reasoner_version: String,
-    pub content: Box<serde_json::value::RawValue>,
+    /// The policy itself, in the reasoner's language. Shared, because policies may be megabytes in size and are passed
+    /// around (to the store, the audit log and the reasoner) a lot; cloning a [`PolicyContent`] never copies it.
+    pub content: Arc<RawValue>,
+}
+
+impl PolicyContent {
+    /// Computes the hash of this content, by which stores may deduplicate it and anyone can verify it.
+    ///
+    /// This is the hex-encoded SHA-256 of the reasoner, its version and the content as given (i.e., whitespace matters).
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [self.reasoner.as_str(), self.reasoner_version.as_str(), self.content.get()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Searchable metadata that helps organizing many policy versions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicyMetadata {
+    /// Free-form tags attached to the version.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The jurisdiction (e.g., country or legal framework) the policy applies to.
+    #[serde(default)]
+    pub jurisdiction: Option<String>,
+    /// The department owning the policy.
+    #[serde(default)]
+    pub department: Option<String>,
+}
+
+/// Filters the policy versions returned by [`PolicyDataAccess::get_versions()`].
+///
+/// Every field that is given must match; fields that are omitted match anything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PolicyVersionFilter {
+    /// Only return versions with this tag.
+    pub tag: Option<String>,
+    /// Only return versions for this jurisdiction.
+    pub jurisdiction: Option<String>,
+    /// Only return versions owned by this department.
+    pub department: Option<String>,
+}
+
+impl PolicyVersionFilter {
+    /// Checks whether the given metadata matches this filter.
+    pub fn matches(&self, metadata: &PolicyMetadata) -> bool {
+        self.tag.as_ref().map(|tag| metadata.tags.contains(tag)).unwrap_or(true)
+            && self.jurisdiction.as_ref().map(|j| metadata.jurisdiction.as_ref() == Some(j)).unwrap_or(true)
+            && self.department.as_ref().map(|d| metadata.department.as_ref() == Some(d)).unwrap_or(true)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -20,6 +75,17 @@ pub struct PolicyVersion {
     pub version_description: String,
     /// reasoner_connector_context contains the hash of the reasoner connector's base definitions
     pub reasoner_connector_context: String,
+    /// Searchable metadata of this version
+    #[serde(flatten)]
+    pub metadata: PolicyMetadata,
+    /// The hashes of the contents of this version (see [`PolicyContent::hash()`]), in order. Filled in by the store, such
+    /// that anyone can verify that a version is byte-identical to what they reviewed without retrieving its contents.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_hashes: Vec<String>,
+    /// The globally unique identifier of this version, which (unlike `version`) stays the same when it is moved to
+    /// another store. Assigned by the store; versions stored before these existed have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -35,6 +101,72 @@ pub struct Policy {
     pub content:     Vec<PolicyContent>,
 }
 
+impl Policy {
+    /// Computes the hashes of the contents of this policy (see [`PolicyContent::hash()`]), in order.
+    pub fn content_hashes(&self) -> Vec<String> { self.content.iter().map(PolicyContent::hash).collect() }
+}
+
+/// A single entry in the activation history of the policy store.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PolicyActivation {
+    /// The version that was activated.
+    pub version: i64,
+    pub activated_on: DateTime<Local>,
+    pub activated_by: String,
+    /// When the version got deactivated again, if it did. Note that activating another version does not set this.
+    pub deactivated_on: Option<DateTime<Local>>,
+    pub deactivated_by: Option<String>,
+}
+
+/// The complete contents of a policy store, used to back it up or to move it to another environment.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PolicyArchive {
+    /// The version of the archive format. See [`PolicyArchive::FORMAT`].
+    pub format:      u32,
+    pub exported_at: DateTime<Local>,
+    /// All policy versions in the store, including their content and metadata.
+    pub policies:    Vec<Policy>,
+    /// The activation history of the store, oldest first.
+    pub activations: Vec<PolicyActivation>,
+}
+
+impl PolicyArchive {
+    /// The current version of the archive format.
+    pub const FORMAT: u32 = 1;
+
+    /// Constructor for a PolicyArchive that is exported now.
+    pub fn new(policies: Vec<Policy>, activations: Vec<PolicyActivation>) -> Self {
+        Self { format: Self::FORMAT, exported_at: Local::now(), policies, activations }
+    }
+
+    /// Returns the stored versions that this archive differs from or lacks, i.e., the versions that importing it would
+    /// modify or drop.
+    ///
+    /// Versions differ if their contents (by hash), the reasoner connector context they were written for or their external
+    /// identifiers (if both have one) differ. Versions only in the archive are not returned, as importing it adds those.
+    pub fn modified_versions(&self, stored: &[PolicyVersion]) -> Vec<i64> {
+        let mut modified: Vec<i64> = stored
+            .iter()
+            .filter_map(|old| {
+                let version: i64 = old.version?;
+                let Some(policy) = self.policies.iter().find(|policy| policy.version.version == Some(version)) else { return Some(version) };
+                let modified: bool = old.content_hashes != policy.content_hashes()
+                    || old.reasoner_connector_context != policy.version.reasoner_connector_context
+                    || matches!((&old.external_id, &policy.version.external_id), (Some(old), Some(new)) if old != new);
+                if modified { Some(version) } else { None }
+            })
+            .collect();
+        modified.sort_unstable();
+        modified
+    }
+
+    /// Returns the version that is active according to the archived activation history, if any.
+    pub fn active(&self) -> Option<i64> {
+        let last: &PolicyActivation = self.activations.iter().max_by_key(|a| a.activated_on)?;
+        if last.deactivated_on.is_some() { None } else { Some(last.version) }
+    }
+}
+
 #[derive(Debug)]
 pub enum PolicyDataError {
     NotFound,
@@ -52,6 +184,7 @@ impl Display for PolicyDataError {
 
 impl std::error::Error for PolicyDataError {}
 
+#[cfg(feature = "warp")]
 impl warp::reject::Reject for PolicyDataError {}
 
 // impl std::error::Error for PolicyDataError {
@@ -90,6 +223,12 @@ pub struct Context {
     pub initiator: String,
 }
 
+/// Stores policy versions and which of them is active.
+///
+/// Stored versions are immutable: once [`PolicyDataAccess::add_version()`] has given a version its number, the contents
+/// under that number never change. Changes are made by adding a new version instead, and
+/// [`PolicyDataAccess::import_store()`] refuses archives that would modify or drop stored versions (see
+/// [`PolicyArchive::modified_versions()`]). Stores fill in the [`PolicyVersion::content_hashes`] of the versions they return.
 #[async_trait::async_trait]
 pub trait PolicyDataAccess {
     type Error;
@@ -102,8 +241,11 @@ pub trait PolicyDataAccess {
     ) -> Result<Policy, PolicyDataError>;
     async fn get_version(&self, version: i64) -> Result<Policy, PolicyDataError>;
     async fn get_most_recent(&self) -> Result<Policy, PolicyDataError>;
-    async fn get_versions(&self) -> Result<Vec<PolicyVersion>, PolicyDataError>;
+    async fn get_versions(&self, filter: &PolicyVersionFilter) -> Result<Vec<PolicyVersion>, PolicyDataError>;
     async fn get_active(&self) -> Result<Policy, PolicyDataError>;
+    /// Returns the policy that was active at the given moment, i.e., the version most recently activated before it (unless it was deactivated
+    /// again before it, too).
+    async fn get_active_at(&self, at: DateTime<Local>) -> Result<Policy, PolicyDataError>;
     #[must_use]
     async fn set_active<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
         &self,
@@ -111,6 +253,18 @@ pub trait PolicyDataAccess {
         context: Context,
         transaction: impl 'static + Send + FnOnce(Policy) -> F,
     ) -> Result<Policy, PolicyDataError>;
+    /// Returns the complete contents of the store, including the activation history.
+    async fn export_store(&self) -> Result<PolicyArchive, PolicyDataError>;
+    /// Adds the versions in the given archive that are not stored yet, and replaces the activation history with the archived one.
+    ///
+    /// The archive must contain every stored version as-is (see [`PolicyArchive::modified_versions()`]).
+    #[must_use]
+    async fn import_store<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
+        &self,
+        archive: PolicyArchive,
+        context: Context,
+        transaction: impl 'static + Send + FnOnce(PolicyArchive) -> F,
+    ) -> Result<(), PolicyDataError>;
     #[must_use]
     async fn deactivate_policy<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
         &self,
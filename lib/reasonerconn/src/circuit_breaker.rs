@@ -0,0 +1,341 @@
+//! Implements a circuit breaker that can be put in front of any [`ReasonerConnector`].
+//!
+//! When a reasoner backend goes down (or becomes painfully slow), every question still waits for it to time out
+//! before failing. The [`CircuitBreaker`] keeps track of consecutive failures and latency SLO breaches, and once too
+//! many have occurred, it "opens": requests are then failed immediately with a
+//! [`ReasonerConnError::CircuitOpen`](crate::ReasonerConnError::CircuitOpen) error without bothering the
+//! backend. After a while, it "half-opens" and lets a single probe request through to see if the backend recovered.
+
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use audit_logger::{ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::{Explanation, NodeResult};
+use log::{debug, info, warn};
+use policy::{Policy, PolicyContent};
+use state_resolver::State;
+use workflow::spec::Workflow;
+
+use crate::{
+    PolicyCompositionError, QuestionKind, ReasonerCapabilities, ReasonerConnError, ReasonerConnector, ReasonerMetric, ReasonerQueryResponse,
+    ReasonerResponse,
+};
+
+/***** AUXILLARY *****/
+/// Configures when a [`CircuitBreaker`] opens and how long it stays open.
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerConfig {
+    /// The number of consecutive failures (or SLO breaches) after which the breaker opens. `0` disables the breaker.
+    pub failure_threshold: u32,
+    /// If given, answers that take longer than this count as failures too (although they are still returned).
+    pub latency_slo: Option<Duration>,
+    /// The time the breaker stays open before letting a probe request through.
+    pub open_duration: Duration,
+}
+impl Default for CircuitBreakerConfig {
+    #[inline]
+    fn default() -> Self { Self { failure_threshold: 5, latency_slo: None, open_duration: Duration::from_secs(30) } }
+}
+
+/// The state of a [`CircuitBreaker`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CircuitState {
+    /// Requests go through to the backend as usual.
+    Closed { consecutive_failures: u32 },
+    /// Requests are short-circuited until the given time has passed.
+    Open { since: Instant },
+    /// A single probe request is underway since the given time; all others are short-circuited until it returns.
+    HalfOpen { since: Instant },
+}
+impl CircuitState {
+    /// Returns a machine-friendly name of the state, as used in the metrics.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Closed { .. } => "closed",
+            Self::Open { .. } => "open",
+            Self::HalfOpen { .. } => "half-open",
+        }
+    }
+}
+impl Display for CircuitState {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { write!(f, "{}", self.name()) }
+}
+
+/***** LIBRARY *****/
+/// Wraps a [`ReasonerConnector`] such that it is no longer consulted while it keeps failing.
+///
+/// Note that the breaker is transparent w.r.t. the connector's context, so wrapping a connector does not invalidate
+/// any active policy.
+#[derive(Debug)]
+pub struct CircuitBreaker<C> {
+    /// The connector we protect.
+    inner: C,
+    /// Determines when we open and for how long.
+    config: CircuitBreakerConfig,
+    /// The current state of the breaker.
+    state: Mutex<CircuitState>,
+    /// The number of requests we short-circuited so far.
+    short_circuited: AtomicU64,
+    /// The number of times we opened so far.
+    opened: AtomicU64,
+}
+
+impl<C> CircuitBreaker<C> {
+    /// Constructor for the CircuitBreaker.
+    ///
+    /// # Arguments
+    /// - `inner`: The [`ReasonerConnector`] to protect.
+    /// - `config`: The [`CircuitBreakerConfig`] that determines when to open.
+    ///
+    /// # Returns
+    /// A new CircuitBreaker that starts closed.
+    #[inline]
+    pub fn new(inner: C, config: CircuitBreakerConfig) -> Self {
+        Self {
+            inner,
+            config,
+            state: Mutex::new(CircuitState::Closed { consecutive_failures: 0 }),
+            short_circuited: AtomicU64::new(0),
+            opened: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the current state of the breaker.
+    #[inline]
+    pub fn state(&self) -> CircuitState { *self.state.lock().unwrap() }
+
+    /// Returns the wrapped connector.
+    #[inline]
+    pub fn inner(&self) -> &C { &self.inner }
+
+    /// Decides whether a request may go through to the backend.
+    ///
+    /// # Errors
+    /// This function errors with a [`ReasonerConnError::CircuitOpen`] error if the breaker is open.
+    fn acquire(&self) -> Result<(), ReasonerConnError> {
+        if self.config.failure_threshold == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            CircuitState::Closed { .. } => Ok(()),
+            // Also send a new probe if the previous one never returned (e.g., because the client hung up)
+            CircuitState::Open { since } | CircuitState::HalfOpen { since } if since.elapsed() >= self.config.open_duration => {
+                info!("Circuit breaker half-opens; letting a probe request through to the reasoner backend");
+                *state = CircuitState::HalfOpen { since: Instant::now() };
+                Ok(())
+            },
+            CircuitState::Open { since } | CircuitState::HalfOpen { since } => {
+                self.short_circuited.fetch_add(1, Ordering::Relaxed);
+                Err(ReasonerConnError::circuit_open(
+                    format!(
+                        "Circuit breaker is {}; not consulting the reasoner backend after {} consecutive failure(s)",
+                        state.name(),
+                        self.config.failure_threshold
+                    ),
+                    self.config.open_duration.saturating_sub(since.elapsed()),
+                ))
+            },
+        }
+    }
+
+    /// Records the outcome of a request that went through to the backend.
+    ///
+    /// # Arguments
+    /// - `success`: Whether the backend answered (in time).
+    fn record(&self, success: bool) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let next: CircuitState = match (*state, success) {
+            (CircuitState::HalfOpen { .. }, true) => {
+                info!("Circuit breaker closes; reasoner backend recovered");
+                CircuitState::Closed { consecutive_failures: 0 }
+            },
+            (_, true) => CircuitState::Closed { consecutive_failures: 0 },
+            (CircuitState::Closed { consecutive_failures }, false) if consecutive_failures + 1 < self.config.failure_threshold => {
+                CircuitState::Closed { consecutive_failures: consecutive_failures + 1 }
+            },
+            // Another request failed while we were already open; don't extend the open period for it
+            (CircuitState::Open { since }, false) => CircuitState::Open { since },
+            (_, false) => {
+                warn!("Circuit breaker opens; short-circuiting the reasoner backend for {:?}", self.config.open_duration);
+                self.opened.fetch_add(1, Ordering::Relaxed);
+                CircuitState::Open { since: Instant::now() }
+            },
+        };
+        *state = next;
+    }
+
+    /// Runs a request to the backend through the breaker.
+    ///
+    /// # Arguments
+    /// - `request`: The future that consults the backend.
+    ///
+    /// # Returns
+    /// The backend's answer.
+    ///
+    /// # Errors
+    /// This function errors if the breaker is open, or if the backend failed.
+    async fn guard<R>(&self, request: impl Future<Output = Result<R, ReasonerConnError>>) -> Result<R, ReasonerConnError> {
+        self.acquire()?;
+
+        let start: Instant = Instant::now();
+        let res: Result<R, ReasonerConnError> = request.await;
+        let elapsed: Duration = start.elapsed();
+
+        let breached: bool = self.config.latency_slo.map(|slo| elapsed > slo).unwrap_or(false);
+        if breached {
+            debug!("Reasoner backend took {elapsed:?}, which breaches the latency SLO of {:?}", self.config.latency_slo.unwrap());
+        }
+        // Questions that cannot be answered say nothing about the backend
+        let failed: bool = matches!(&res, Err(err) if !matches!(err, ReasonerConnError::Invalid { .. }));
+        self.record(!failed && !breached);
+        res
+    }
+}
+
+impl<C: ConnectorWithContext> ConnectorWithContext for CircuitBreaker<C> {
+    type Context = C::Context;
+
+    #[inline]
+    fn hash() -> String { C::hash() }
+
+    #[inline]
+    fn context() -> Self::Context { C::context() }
+}
+
+#[async_trait::async_trait]
+impl<L, C> ReasonerConnector<L> for CircuitBreaker<C>
+where
+    L: ReasonerConnectorAuditLogger + Send + Sync + 'static,
+    C: ReasonerConnector<L> + Send + Sync,
+{
+    async fn execute_task(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        task: String,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        self.guard(self.inner.execute_task(logger, policy, state, workflow, task)).await
+    }
+
+    async fn access_data_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        data: String,
+        task: Option<String>,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        self.guard(self.inner.access_data_request(logger, policy, state, workflow, data, task)).await
+    }
+
+    async fn workflow_validation_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        self.guard(self.inner.workflow_validation_request(logger, policy, state, workflow)).await
+    }
+
+    async fn query(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        query: serde_json::Value,
+    ) -> Result<ReasonerQueryResponse, ReasonerConnError> {
+        self.guard(self.inner.query(logger, policy, state, query)).await
+    }
+
+    async fn explain(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        question: QuestionKind,
+    ) -> Result<Explanation, ReasonerConnError> {
+        self.guard(self.inner.explain(logger, policy, state, workflow, question)).await
+    }
+
+    async fn node_results(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<Vec<NodeResult>, ReasonerConnError> {
+        self.guard(self.inner.node_results(logger, policy, state, workflow)).await
+    }
+
+    #[inline]
+    fn compose(&self, fragments: &[Policy]) -> Result<Vec<PolicyContent>, PolicyCompositionError> { self.inner.compose(fragments) }
+
+    #[inline]
+    fn capabilities(&self) -> ReasonerCapabilities { self.inner.capabilities() }
+
+    #[inline]
+    async fn debug_bundle(&self, reference: &str) -> Result<Option<serde_json::Value>, ReasonerConnError> { self.inner.debug_bundle(reference).await }
+
+    #[inline]
+    async fn reload(&self) -> Result<(), ReasonerConnError> { self.inner.reload().await }
+
+    #[inline]
+    async fn on_policy_activated(&self, policy: &Policy) -> Result<(), ReasonerConnError> { self.inner.on_policy_activated(policy).await }
+
+    #[inline]
+    async fn on_policy_deactivated(&self) -> Result<(), ReasonerConnError> { self.inner.on_policy_deactivated().await }
+
+    #[inline]
+    fn configuration(&self) -> serde_json::Value { self.inner.configuration() }
+
+    fn metrics(&self) -> Vec<ReasonerMetric> {
+        let state: CircuitState = self.state();
+        let mut metrics: Vec<ReasonerMetric> = ["closed", "open", "half-open"]
+            .into_iter()
+            .map(|name| {
+                ReasonerMetric::new(
+                    "policy_reasoner_circuit_breaker_state",
+                    "Whether the circuit breaker in front of the reasoner backend is in the given state (1) or not (0).",
+                    if state.name() == name { 1.0 } else { 0.0 },
+                )
+                .with_label("state", name)
+            })
+            .collect();
+        metrics.push(ReasonerMetric::new(
+            "policy_reasoner_circuit_breaker_consecutive_failures",
+            "The number of consecutive failures or latency SLO breaches of the reasoner backend.",
+            match state {
+                CircuitState::Closed { consecutive_failures } => consecutive_failures as f64,
+                _ => self.config.failure_threshold as f64,
+            },
+        ));
+        metrics.push(ReasonerMetric::new(
+            "policy_reasoner_circuit_breaker_opened_total",
+            "The number of times the circuit breaker opened.",
+            self.opened.load(Ordering::Relaxed) as f64,
+        ));
+        metrics.push(ReasonerMetric::new(
+            "policy_reasoner_circuit_breaker_short_circuited_total",
+            "The number of requests that were failed without consulting the reasoner backend.",
+            self.short_circuited.load(Ordering::Relaxed) as f64,
+        ));
+        metrics.extend(self.inner.metrics());
+        metrics
+    }
+}
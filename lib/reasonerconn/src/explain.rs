@@ -0,0 +1,147 @@
+//! Implements the search for explanations of denials (see [`ReasonerConnector::explain()`](crate::ReasonerConnector::explain())).
+//!
+//! A reasoner typically tells _which_ rule a question violates, but not which facts make it do so. To find out,
+//! connectors can ask the same question again and again with parts of it (facts, policy phrases) left out, until only
+//! those remain without which the violation goes away. [`minimize()`] implements this search generically using delta
+//! debugging (Zeller's `ddmin`), such that connectors only have to say how to ask the question with a subset of its parts.
+
+use std::future::Future;
+
+use log::debug;
+
+/***** CONSTANTS *****/
+/// The number of times a connector may ask its backend to explain a single denial, unless configured otherwise.
+pub const DEFAULT_EXPLAIN_BUDGET: usize = 64;
+
+/***** HELPERS *****/
+/// Splits a list into a number of chunks of (nearly) equal size.
+///
+/// # Arguments
+/// - `items`: The list to split.
+/// - `n`: The number of chunks to split it in. Must not be larger than the list, such that no chunk is empty.
+///
+/// # Returns
+/// The chunks, in order.
+fn split<T: Clone>(items: &[T], n: usize) -> Vec<Vec<T>> {
+    let mut chunks: Vec<Vec<T>> = Vec::with_capacity(n);
+    let mut start: usize = 0;
+    for i in 0..n {
+        let end: usize = start + (items.len() - start) / (n - i);
+        chunks.push(items[start..end].to_vec());
+        start = end;
+    }
+    chunks
+}
+
+/***** LIBRARY *****/
+/// Finds a minimal subset of items that still reproduces something (e.g., a violation), using delta debugging.
+///
+/// The subset found is 1-minimal: leaving out any single one of its items no longer reproduces it. Note that the
+/// order of the items is kept in every subset tried.
+///
+/// # Arguments
+/// - `items`: The items to minimize, which together are assumed to reproduce it.
+/// - `budget`: The maximum number of times to call `reproduces`.
+/// - `reproduces`: Decides whether a subset of the items still reproduces it.
+///
+/// # Returns
+/// The smallest subset found, and whether it is minimal. It is not if the budget ran out before the search finished.
+pub async fn minimize<T, F, Fut>(mut items: Vec<T>, budget: usize, mut reproduces: F) -> (Vec<T>, bool)
+where
+    T: Clone,
+    F: FnMut(Vec<T>) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    if budget == 0 {
+        return (items, false);
+    }
+    // If nothing is needed at all, we're done quickly
+    if reproduces(Vec::new()).await {
+        return (Vec::new(), true);
+    }
+
+    let mut tests: usize = 1;
+    let mut granularity: usize = 2;
+    while items.len() >= 2 {
+        let chunks: Vec<Vec<T>> = split(&items, granularity);
+        debug!("Minimizing {} item(s) in {} chunk(s)...", items.len(), chunks.len());
+
+        // Try every chunk on its own first, as that shrinks the fastest
+        let mut next: Option<(Vec<T>, usize)> = None;
+        for chunk in &chunks {
+            if tests >= budget {
+                return (items, false);
+            }
+            tests += 1;
+            if reproduces(chunk.clone()).await {
+                next = Some((chunk.clone(), 2));
+                break;
+            }
+        }
+        // Then try leaving out every chunk (which are the same as the chunks themselves for two of them)
+        if next.is_none() && chunks.len() > 2 {
+            for i in 0..chunks.len() {
+                if tests >= budget {
+                    return (items, false);
+                }
+                tests += 1;
+                let complement: Vec<T> = chunks.iter().enumerate().filter(|(j, _)| *j != i).flat_map(|(_, chunk)| chunk.iter().cloned()).collect();
+                if reproduces(complement.clone()).await {
+                    next = Some((complement, (granularity - 1).max(2)));
+                    break;
+                }
+            }
+        }
+
+        match next {
+            Some((subset, g)) => {
+                items = subset;
+                granularity = g;
+            },
+            // Nothing could be left out at this granularity, so try smaller chunks (unless they are single items already)
+            None if granularity < items.len() => granularity = (granularity * 2).min(items.len()),
+            None => break,
+        }
+    }
+    (items, true)
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(&[1, 2, 3, 4, 5], 2), vec![vec![1, 2], vec![3, 4, 5]]);
+        assert_eq!(split(&[1, 2, 3], 3), vec![vec![1], vec![2], vec![3]]);
+    }
+
+    #[tokio::test]
+    async fn test_minimize() {
+        // Only 3 and 11 together reproduce it
+        let (subset, minimal) =
+            minimize((0..16).collect(), DEFAULT_EXPLAIN_BUDGET, |subset: Vec<u32>| async move { subset.contains(&3) && subset.contains(&11) }).await;
+        assert_eq!(subset, vec![3, 11]);
+        assert!(minimal);
+
+        // Nothing is needed to reproduce it
+        let (subset, minimal) = minimize((0..16).collect(), DEFAULT_EXPLAIN_BUDGET, |_: Vec<u32>| async move { true }).await;
+        assert!(subset.is_empty());
+        assert!(minimal);
+    }
+
+    #[tokio::test]
+    async fn test_minimize_budget() {
+        // The search gives up, but never loses what is needed
+        let mut calls: usize = 0;
+        let (subset, minimal) = minimize((0..64).collect(), 4, |subset: Vec<u32>| {
+            calls += 1;
+            async move { subset.contains(&3) && subset.contains(&60) }
+        })
+        .await;
+        assert!(!minimal);
+        assert!(subset.contains(&3) && subset.contains(&60));
+        assert_eq!(calls, 4);
+    }
+}
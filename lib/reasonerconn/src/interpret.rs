@@ -0,0 +1,311 @@
+//! Interprets what a reasoner backend answered as a [`ReasonerResponse`].
+//!
+//! Connectors talk to very different backends, but should all draw the same conclusions from similar answers. They
+//! therefore map whatever their backend returns onto a [`BackendAnswer`], and leave deciding on the verdict to
+//! [`interpret()`]. Its semantics are:
+//! - The backend must give at least one [`Answer`]. Only the last one answers the question; any before it are about
+//!   setting up the question (e.g., adding the state and workflow to an eFLINT instance).
+//! - A [`Answer::Boolean`] allows if it is true, and a [`Answer::StateChange`] allows if it violated nothing.
+//!   An [`Answer::Instances`] is not a verdict, and means the question was asked wrongly.
+//! - Queries are the opposite: they must be answered with an [`Answer::Instances`], which is listed by
+//!   [`interpret_query()`] as-is.
+//! - If the backend reports that it failed, the question is denied, whatever its answers say.
+//! - Violations are given as the reasons of the verdict; warnings are only logged.
+//! - A denial is categorized by the [`DenyCategory`]s the backend gave, or else by those its violations look like
+//!   (see [`categorize()`]). A denial because the backend failed is always a [`DenyCategory::BackendError`].
+
+use deliberation::spec::DenyCategory;
+#[cfg(feature = "eflint")]
+use eflint_json::spec::{PhraseResult, ResponsePhrases};
+use log::{debug, warn};
+use serde_json::Value;
+
+use crate::{ReasonerConnError, ReasonerQueryResponse, ReasonerResponse};
+
+/***** AUXILLARY *****/
+/// A single answer of a reasoner backend.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Answer {
+    /// The answer to a yes/no question.
+    Boolean(bool),
+    /// The result of changing the backend's state, which may have violated something.
+    StateChange { violated: bool },
+    /// A list of instances, as given for queries that are not yes/no questions.
+    Instances,
+}
+
+/// Everything a reasoner backend answered to a question, in a backend-independent form.
+#[derive(Clone, Debug, Default)]
+pub struct BackendAnswer {
+    /// Whether the backend reports that it processed the question successfully.
+    pub success:    bool,
+    /// The answers of the backend, in the order given.
+    pub answers:    Vec<Answer>,
+    /// The violations the backend reported, to be shared as reasons for denial.
+    pub violations: Vec<String>,
+    /// Any warnings the backend gave, which do not affect the verdict.
+    pub warnings:   Vec<String>,
+    /// The categories of the violations, if the backend knows them. Derived from the violations if empty.
+    pub categories: Vec<DenyCategory>,
+    /// The instances listed by the last [`Answer::Instances`], if any.
+    pub instances:  Vec<Value>,
+}
+impl BackendAnswer {
+    /// Constructor for a BackendAnswer from the response of an eFLINT reasoner.
+    ///
+    /// # Arguments
+    /// - `response`: The eFLINT JSON [`ResponsePhrases`] to read.
+    /// - `violations`: The violations to share, as extracted from the response by the connector.
+    ///
+    /// # Returns
+    /// A new BackendAnswer without any warnings, explicit categories or instances (see [`eflint_instances()`]).
+    #[cfg(feature = "eflint")]
+    pub fn from_eflint(response: &ResponsePhrases, violations: Vec<String>) -> Self {
+        let answers: Vec<Answer> = response
+            .results
+            .iter()
+            .map(|result| match result {
+                PhraseResult::BooleanQuery(result) => Answer::Boolean(result.result),
+                PhraseResult::InstanceQuery(_) => Answer::Instances,
+                PhraseResult::StateChange(result) => Answer::StateChange { violated: result.violated },
+            })
+            .collect();
+        Self { success: response.common.success, answers, violations, warnings: vec![], categories: vec![], instances: vec![] }
+    }
+}
+
+/***** LIBRARY *****/
+/// Lists the instances in the last result of an eFLINT response, for [`BackendAnswer::instances`].
+///
+/// The response is read as raw JSON, such that the instances are kept exactly as the reasoner gave them.
+///
+/// # Arguments
+/// - `response`: The raw eFLINT JSON response.
+///
+/// # Returns
+/// The instances, or none if the last result is not that of an instance query. Depending on the version of the
+/// specification, they are listed as either `instances` or `results`.
+pub fn eflint_instances(response: &Value) -> Vec<Value> {
+    let Some(last) = response.get("results").and_then(Value::as_array).and_then(|results| results.last()) else { return vec![] };
+    match last.get("instances").or_else(|| last.get("results")) {
+        Some(Value::Array(instances)) => instances.clone(),
+        _ => vec![],
+    }
+}
+
+/// Guesses the [`DenyCategory`] of a reason for denial given by a backend that does not categorize its own.
+///
+/// This looks for keywords in the reason (e.g., an eFLINT violation called `pub-no-consent` is a
+/// [`DenyCategory::ConsentMissing`]), and falls back to [`DenyCategory::PermissionDenied`].
+///
+/// # Arguments
+/// - `reason`: The reason to categorize.
+///
+/// # Returns
+/// The [`DenyCategory`] the reason most likely falls in.
+pub fn categorize(reason: &str) -> DenyCategory {
+    let reason: String = reason.to_lowercase();
+    if reason.contains("consent") {
+        DenyCategory::ConsentMissing
+    } else if ["location", "domain", "site"].iter().any(|word| reason.contains(word)) {
+        DenyCategory::LocationForbidden
+    } else if ["quota", "budget", "limit"].iter().any(|word| reason.contains(word)) {
+        DenyCategory::QuotaExceeded
+    } else {
+        DenyCategory::PermissionDenied
+    }
+}
+
+/// Decides on the verdict of a reasoner backend's answer.
+///
+/// See the [module-level documentation](self) for the semantics.
+///
+/// # Arguments
+/// - `answer`: The [`BackendAnswer`] to interpret.
+///
+/// # Returns
+/// The [`ReasonerResponse`] to give for the question.
+///
+/// # Errors
+/// This function errors if the backend gave no answer at all, or answered with something that is not a verdict.
+pub fn interpret(answer: &BackendAnswer) -> Result<ReasonerResponse, ReasonerConnError> {
+    for warning in &answer.warnings {
+        warn!("Reasoner backend warned: {warning}");
+    }
+
+    let Some(last) = answer.answers.last() else {
+        return Err(ReasonerConnError::new("Reasoner backend gave no answer"));
+    };
+    if answer.answers.len() > 1 {
+        debug!("Ignoring {} answer(s) before the last one", answer.answers.len() - 1);
+    }
+    let allowed: bool = match last {
+        Answer::Boolean(result) => *result,
+        Answer::StateChange { violated } => !violated,
+        Answer::Instances => return Err(ReasonerConnError::new("Invalid query: reasoner backend answered with instances instead of a verdict")),
+    };
+
+    debug!("Response judged as: {} ({} && {})", if allowed && answer.success { "success" } else { "violated" }, allowed, answer.success);
+    let mut categories: Vec<DenyCategory> = Vec::new();
+    if !answer.success {
+        categories.push(DenyCategory::BackendError);
+    } else if !allowed {
+        let found: Vec<DenyCategory> =
+            if answer.categories.is_empty() { answer.violations.iter().map(|v| categorize(v)).collect() } else { answer.categories.clone() };
+        for category in found {
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        if categories.is_empty() {
+            categories.push(DenyCategory::PermissionDenied);
+        }
+    }
+    Ok(ReasonerResponse::new(allowed && answer.success, answer.violations.clone()).with_categories(categories))
+}
+
+/// Lists what a reasoner backend answered to a query.
+///
+/// See the [module-level documentation](self) for the semantics.
+///
+/// # Arguments
+/// - `answer`: The [`BackendAnswer`] to interpret.
+///
+/// # Returns
+/// The [`ReasonerQueryResponse`] to give for the query.
+///
+/// # Errors
+/// This function errors if the backend failed, gave no answer at all, or answered with a verdict instead of instances.
+pub fn interpret_query(answer: &BackendAnswer) -> Result<ReasonerQueryResponse, ReasonerConnError> {
+    for warning in &answer.warnings {
+        warn!("Reasoner backend warned: {warning}");
+    }
+    if !answer.success {
+        return Err(ReasonerConnError::new("Reasoner backend failed to answer query"));
+    }
+    match answer.answers.last() {
+        Some(Answer::Instances) => {
+            debug!("Query answered with {} instance(s)", answer.instances.len());
+            Ok(ReasonerQueryResponse { instances: answer.instances.clone() })
+        },
+        Some(_) => Err(ReasonerConnError::invalid("Invalid query: reasoner backend answered with a verdict instead of instances")),
+        None => Err(ReasonerConnError::new("Reasoner backend gave no answer")),
+    }
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shorthand for a successful [`BackendAnswer`] with the given answers.
+    fn answer(answers: Vec<Answer>) -> BackendAnswer { BackendAnswer { success: true, answers, ..Default::default() } }
+
+    #[test]
+    fn test_interpret_empty() {
+        assert!(interpret(&answer(vec![])).is_err());
+        assert!(interpret(&BackendAnswer { success: false, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_interpret_single() {
+        assert!(interpret(&answer(vec![Answer::Boolean(true)])).unwrap().success);
+        assert!(!interpret(&answer(vec![Answer::Boolean(false)])).unwrap().success);
+        assert!(interpret(&answer(vec![Answer::StateChange { violated: false }])).unwrap().success);
+        assert!(!interpret(&answer(vec![Answer::StateChange { violated: true }])).unwrap().success);
+        assert!(interpret(&answer(vec![Answer::Instances])).is_err());
+    }
+
+    #[test]
+    fn test_interpret_multiple() {
+        // Only the last answer counts, whatever came before
+        let res = interpret(&answer(vec![Answer::StateChange { violated: true }, Answer::Instances, Answer::Boolean(true)])).unwrap();
+        assert!(res.success);
+        let res = interpret(&answer(vec![Answer::Boolean(true), Answer::StateChange { violated: true }])).unwrap();
+        assert!(!res.success);
+        assert!(interpret(&answer(vec![Answer::Boolean(true), Answer::Instances])).is_err());
+    }
+
+    #[test]
+    fn test_interpret_backend_failure() {
+        let res = interpret(&BackendAnswer { success: false, answers: vec![Answer::Boolean(true)], ..Default::default() }).unwrap();
+        assert!(!res.success);
+    }
+
+    #[test]
+    fn test_interpret_violations_and_warnings() {
+        let res = interpret(&BackendAnswer {
+            success:    true,
+            answers:    vec![Answer::StateChange { violated: true }],
+            violations: vec!["pub-no-consent".into()],
+            warnings:   vec!["Unknown fact 'foo'".into()],
+            categories: vec![],
+            instances:  vec![],
+        })
+        .unwrap();
+        assert!(!res.success);
+        assert_eq!(res.errors, vec!["pub-no-consent".to_string()]);
+
+        // Warnings never deny
+        let res = interpret(&BackendAnswer { warnings: vec!["Unknown fact 'foo'".into()], ..answer(vec![Answer::Boolean(true)]) }).unwrap();
+        assert!(res.success);
+        assert!(res.errors.is_empty());
+    }
+
+    #[test]
+    fn test_interpret_query() {
+        let instances: Vec<Value> = vec![serde_json::json!({ "identifier": "user", "operands": ["Amy"] })];
+        let res = interpret_query(&BackendAnswer {
+            instances: instances.clone(),
+            ..answer(vec![Answer::StateChange { violated: true }, Answer::Instances])
+        })
+        .unwrap();
+        assert_eq!(res.instances, instances);
+
+        // Verdicts are no answer to a query, and failing backends none at all
+        let err = interpret_query(&answer(vec![Answer::Instances, Answer::Boolean(true)])).unwrap_err();
+        assert!(matches!(err, ReasonerConnError::Invalid { .. }));
+        assert!(interpret_query(&answer(vec![])).is_err());
+        assert!(interpret_query(&BackendAnswer { success: false, answers: vec![Answer::Instances], ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_eflint_instances() {
+        let response = serde_json::json!({
+            "success": true,
+            "results": [{ "success": true, "result": true }, { "success": true, "instances": ["Amy", "Bob"] }],
+        });
+        assert_eq!(eflint_instances(&response), vec![serde_json::json!("Amy"), serde_json::json!("Bob")]);
+        assert!(eflint_instances(&serde_json::json!({ "success": true, "results": [{ "success": true, "result": true }] })).is_empty());
+        assert!(eflint_instances(&serde_json::json!({ "success": false })).is_empty());
+    }
+
+    #[test]
+    fn test_interpret_categories() {
+        // Allowed requests have none
+        assert!(interpret(&answer(vec![Answer::Boolean(true)])).unwrap().categories.is_empty());
+
+        // Denials are categorized by their violations, without duplicates
+        let res = interpret(&BackendAnswer {
+            violations: vec!["pub-no-consent".into(), "pub-forbidden-location".into(), "pub-consent-revoked".into()],
+            ..answer(vec![Answer::StateChange { violated: true }])
+        })
+        .unwrap();
+        assert_eq!(res.categories, vec![DenyCategory::ConsentMissing, DenyCategory::LocationForbidden]);
+        let res = interpret(&answer(vec![Answer::Boolean(false)])).unwrap();
+        assert_eq!(res.categories, vec![DenyCategory::PermissionDenied]);
+
+        // Unless the backend categorized them itself
+        let res = interpret(&BackendAnswer {
+            violations: vec!["pub-no-consent".into()],
+            categories: vec![DenyCategory::QuotaExceeded],
+            ..answer(vec![Answer::Boolean(false)])
+        })
+        .unwrap();
+        assert_eq!(res.categories, vec![DenyCategory::QuotaExceeded]);
+
+        // And failing backends always deny with a backend error
+        let res = interpret(&BackendAnswer { success: false, answers: vec![Answer::Boolean(true)], ..Default::default() }).unwrap();
+        assert_eq!(res.categories, vec![DenyCategory::BackendError]);
+    }
+}
@@ -1,38 +1,268 @@
+use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
-use audit_logger::{ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
-use policy::Policy;
+use audit_logger::{ConnectorContext, ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::{DenyCategory, Explanation, Granularity, NodeResult};
+use policy::{Policy, PolicyContent};
 use serde::{Deserialize, Serialize};
 use state_resolver::State;
 use workflow::spec::Workflow;
 
-#[derive(Debug)]
-pub struct ReasonerConnError {
-    err: String,
-}
+pub mod circuit_breaker;
+pub mod explain;
+pub mod interpret;
+pub mod policy_cache;
+pub mod retry;
+pub mod shadow;
 
-impl fmt::Display for ReasonerConnError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.err.fmt(f) }
+/// The error that caused a [`ReasonerConnError`], if any.
+pub type BoxedError = Box<dyn 'static + Error + Send + Sync>;
+
+/// Defines why a [`ReasonerConnector`] failed to answer.
+#[derive(Debug, thiserror::Error)]
+pub enum ReasonerConnError {
+    /// The backend failed to answer (or answered garbage).
+    #[error("{what}")]
+    Backend {
+        what:   String,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// The backend failed in a way that may go away when asked again (e.g., a dropped connection or a `502 Bad Gateway`).
+    #[error("{what}")]
+    Transient {
+        what:   String,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// The backend was not consulted at all, because the [`CircuitBreaker`](circuit_breaker::CircuitBreaker) in front of it is open.
+    #[error("{what}")]
+    CircuitOpen {
+        what: String,
+        /// How long until the breaker lets a request through again.
+        retry_after: Duration,
+    },
+    /// The question itself cannot be answered (e.g., a malformed query, or a query to a connector that doesn't support them).
+    #[error("{what}")]
+    Invalid {
+        what:   String,
+        #[source]
+        source: Option<BoxedError>,
+    },
+    /// Another error, as-is (i.e., with its message and its sources).
+    #[error(transparent)]
+    Other(BoxedError),
 }
 
 impl ReasonerConnError {
-    pub fn new<T: Into<String>>(t: T) -> Self { Self { err: t.into() } }
+    /// Creates an error signalling that the backend failed to answer.
+    #[inline]
+    pub fn new<T: Into<String>>(t: T) -> Self { Self::Backend { what: t.into(), source: None } }
 
-    pub fn from<T: std::error::Error>(t: T) -> Self { Self { err: format!("{}", t) } }
-}
+    /// Wraps another error as-is, i.e., with its message and its sources.
+    #[inline]
+    pub fn from<T: 'static + Error + Send + Sync>(t: T) -> Self { Self::Other(Box::new(t)) }
+
+    /// Creates an error signalling that the backend was short-circuited by an open circuit breaker.
+    #[inline]
+    pub fn circuit_open<T: Into<String>>(t: T, retry_after: Duration) -> Self { Self::CircuitOpen { what: t.into(), retry_after } }
+
+    /// Creates an error signalling that the backend failed in a way that may go away when asked again (see [`retry`]).
+    #[inline]
+    pub fn transient<T: Into<String>>(t: T) -> Self { Self::Transient { what: t.into(), source: None } }
+
+    /// Creates an error signalling that the question itself cannot be answered, whichever backend is asked.
+    #[inline]
+    pub fn invalid<T: Into<String>>(t: T) -> Self { Self::Invalid { what: t.into(), source: None } }
 
-impl std::error::Error for ReasonerConnError {
-    fn description(&self) -> &str { &self.err }
+    /// Sets the error that caused this one, such that it ends up in the audit log and in problems.
+    ///
+    /// Errors that cannot have a cause (i.e., [`ReasonerConnError::CircuitOpen`] and [`ReasonerConnError::Other`]) are
+    /// returned as-is.
+    ///
+    /// # Arguments
+    /// - `source`: The underlying error (e.g., the [`std::io::Error`] of a dropped connection).
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_source(mut self, source: impl 'static + Error + Send + Sync) -> Self {
+        match &mut self {
+            Self::Backend { source: cause, .. } | Self::Transient { source: cause, .. } | Self::Invalid { source: cause, .. } => {
+                *cause = Some(Box::new(source))
+            },
+            Self::CircuitOpen { .. } | Self::Other(_) => {},
+        }
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ReasonerResponse {
-    pub success: bool,
-    pub errors:  Vec<String>,
+    pub success:     bool,
+    pub errors:      Vec<String>,
+    /// The [`DenyCategory`]s that the `errors` fall in, if the request is denied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories:  Vec<DenyCategory>,
+    /// How many reasons for denial were looked for, if the connector tells (see [`State::exhaustive`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granularity: Option<Granularity>,
 }
 
 impl ReasonerResponse {
-    pub fn new(success: bool, errors: Vec<String>) -> Self { ReasonerResponse { success, errors } }
+    pub fn new(success: bool, errors: Vec<String>) -> Self { ReasonerResponse { success, errors, categories: vec![], granularity: None } }
+
+    /// Sets the categories of the reasons for denial.
+    #[inline]
+    pub fn with_categories(mut self, categories: Vec<DenyCategory>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    /// Sets how many reasons for denial were looked for.
+    #[inline]
+    pub fn with_granularity(mut self, granularity: Granularity) -> Self {
+        self.granularity = Some(granularity);
+        self
+    }
+}
+
+/// Defines the kinds of questions a [`ReasonerConnector`] answers, for when they are handled alike (e.g., replayed or explained).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum QuestionKind {
+    /// Asks whether a task may be executed.
+    ExecuteTask { task: String },
+    /// Asks whether a dataset may be accessed.
+    AccessData { data: String, task: Option<String> },
+    /// Asks whether a workflow as a whole is permitted.
+    ValidateWorkflow,
+}
+
+/// The answer of a [`ReasonerConnector`] to a query (see [`ReasonerConnector::query()`]).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ReasonerQueryResponse {
+    /// The instances that hold, in the form given by the backend.
+    pub instances: Vec<serde_json::Value>,
+}
+
+/// Explains why a set of policy fragments could not be composed into a single policy.
+#[derive(Debug)]
+pub struct PolicyCompositionError {
+    /// The conflicts found between the fragments, in human-readable form.
+    pub conflicts: Vec<String>,
+}
+
+impl fmt::Display for PolicyCompositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Policy fragments conflict: {}", self.conflicts.join("; ")) }
+}
+
+impl std::error::Error for PolicyCompositionError {}
+
+/// A single measurement exposed by a [`ReasonerConnector`] on the metrics endpoint.
+#[derive(Clone, Debug)]
+pub struct ReasonerMetric {
+    /// The name of the metric, e.g., `policy_reasoner_circuit_breaker_state`.
+    pub name:   String,
+    /// A human-readable description of what the metric measures.
+    pub help:   String,
+    /// Any labels (key/value pairs) that further identify this measurement.
+    pub labels: Vec<(String, String)>,
+    /// The measured value.
+    pub value:  f64,
+}
+
+impl ReasonerMetric {
+    pub fn new(name: impl Into<String>, help: impl Into<String>, value: f64) -> Self {
+        Self { name: name.into(), help: help.into(), labels: Vec::new(), value }
+    }
+
+    /// Adds a label to this metric.
+    pub fn with_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+}
+
+/// The kinds of questions a [`ReasonerConnector`] can be asked, as named by the deliberation API.
+pub const QUESTION_KINDS: [&str; 3] = ["execute-task", "access-data", "execute-workflow"];
+/// The kind of question named by the deliberation API for queries, which only some [`ReasonerConnector`]s answer.
+pub const QUERY_KIND: &str = "query";
+/// The name under which [`ReasonerConnector`]s that explain denials list it among their capabilities.
+pub const EXPLAIN_KIND: &str = "explain";
+
+/// A policy language, i.e., a kind of [`PolicyContent`], understood by a [`ReasonerConnector`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PolicyLanguage {
+    /// The identifier of the language, as used in [`PolicyContent::reasoner`].
+    pub reasoner: String,
+    /// The version of the language, as used in [`PolicyContent::reasoner_version`].
+    pub reasoner_version: String,
+}
+
+/// A nested CLI option accepted by a [`ReasonerConnector`] (i.e., part of `--reasoner-connector`).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReasonerOption {
+    /// The short name of the option.
+    pub short: char,
+    /// The long name of the option.
+    pub long: String,
+    /// What the option does.
+    pub description: String,
+}
+
+/// Describes what a [`ReasonerConnector`] can do, such that clients can adapt to it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReasonerCapabilities {
+    /// The kinds of questions the connector answers (see [`QUESTION_KINDS`]).
+    pub questions: Vec<String>,
+    /// The policy languages the connector understands.
+    pub languages: Vec<PolicyLanguage>,
+    /// The nested CLI options the connector accepts.
+    pub options:   Vec<ReasonerOption>,
+}
+
+impl ReasonerCapabilities {
+    /// Constructor for ReasonerCapabilities that answers all questions in the language named by the connector's context.
+    ///
+    /// # Arguments
+    /// - `context`: The [`ConnectorContext`] of the connector.
+    ///
+    /// # Returns
+    /// New ReasonerCapabilities without any options.
+    pub fn new(context: &impl ConnectorContext) -> Self {
+        Self {
+            questions: QUESTION_KINDS.iter().map(|kind| kind.to_string()).collect(),
+            languages: vec![PolicyLanguage { reasoner: context.r#type(), reasoner_version: context.version() }],
+            options:   Vec::new(),
+        }
+    }
+
+    /// Adds queries (see [`QUERY_KIND`]) to the questions the connector answers.
+    pub fn with_queries(mut self) -> Self {
+        self.questions.push(QUERY_KIND.into());
+        self
+    }
+
+    /// Adds explanations of denials (see [`EXPLAIN_KIND`]) to what the connector does.
+    pub fn with_explanations(mut self) -> Self {
+        self.questions.push(EXPLAIN_KIND.into());
+        self
+    }
+
+    /// Adds the connector's nested CLI options.
+    ///
+    /// # Arguments
+    /// - `options`: The options, in the same form as given to a `MapParser`.
+    pub fn with_options<'s>(mut self, options: impl IntoIterator<Item = (char, &'s str, &'s str)>) -> Self {
+        self.options.extend(options.into_iter().map(|(short, long, description)| ReasonerOption {
+            short,
+            long: long.into(),
+            description: description.into(),
+        }));
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -61,6 +291,167 @@ pub trait ReasonerConnector<L: ReasonerConnectorAuditLogger>: ConnectorWithConte
         state: State,
         workflow: Workflow,
     ) -> Result<ReasonerResponse, ReasonerConnError>;
+
+    /// Answers a query, i.e., lists what holds under the given policy and state instead of deciding whether something is allowed.
+    ///
+    /// Connectors whose backend cannot be queried can rely on the default, which refuses every query.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the backend's raw response with.
+    /// - `policy`: The [`Policy`] to query.
+    /// - `state`: The [`State`] to query the policy with.
+    /// - `query`: The query, in the connector's own language.
+    ///
+    /// # Errors
+    /// This function errors with a [`ReasonerConnError::Invalid`] error if the query is not understood, or with any
+    /// other error if the backend failed to answer it.
+    async fn query(
+        &self,
+        _logger: SessionedConnectorAuditLogger<L>,
+        _policy: Policy,
+        _state: State,
+        _query: serde_json::Value,
+    ) -> Result<ReasonerQueryResponse, ReasonerConnError>
+    where
+        L: 'static + Send + Sync,
+    {
+        Err(ReasonerConnError::invalid("This reasoner connector does not answer queries"))
+    }
+
+    /// Explains a denial by finding a minimal set of facts and policy phrases that still cause it (see [`explain`]).
+    ///
+    /// Connectors that cannot do so can rely on the default, which refuses to explain anything.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the backend's raw responses with.
+    /// - `policy`: The [`Policy`] that denied the question.
+    /// - `state`: The [`State`] the question was denied with.
+    /// - `workflow`: The [`Workflow`] in question.
+    /// - `question`: What was asked.
+    ///
+    /// # Errors
+    /// This function errors with a [`ReasonerConnError::Invalid`] error if the connector does not explain denials
+    /// (or the question isn't denied), or with any other error if the backend failed to answer.
+    async fn explain(
+        &self,
+        _logger: SessionedConnectorAuditLogger<L>,
+        _policy: Policy,
+        _state: State,
+        _workflow: Workflow,
+        _question: QuestionKind,
+    ) -> Result<Explanation, ReasonerConnError>
+    where
+        L: 'static + Send + Sync,
+    {
+        Err(ReasonerConnError::invalid("This reasoner connector does not explain denials"))
+    }
+
+    /// Judges every node (i.e., task) of a workflow separately, such that a planner can remove only the offending ones.
+    ///
+    /// Connectors whose backend cannot do so at once can rely on the default, which refuses. The reasoner then asks
+    /// [`ReasonerConnector::execute_task()`] about every task instead.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the backend's raw responses with.
+    /// - `policy`: The [`Policy`] to judge the nodes with.
+    /// - `state`: The [`State`] to judge the nodes with.
+    /// - `workflow`: The [`Workflow`] whose nodes to judge.
+    ///
+    /// # Returns
+    /// A [`NodeResult`] for every task in the workflow.
+    ///
+    /// # Errors
+    /// This function errors with a [`ReasonerConnError::Invalid`] error if the connector cannot judge all nodes at
+    /// once, or with any other error if the backend failed to answer.
+    async fn node_results(
+        &self,
+        _logger: SessionedConnectorAuditLogger<L>,
+        _policy: Policy,
+        _state: State,
+        _workflow: Workflow,
+    ) -> Result<Vec<NodeResult>, ReasonerConnError>
+    where
+        L: 'static + Send + Sync,
+    {
+        Err(ReasonerConnError::invalid("This reasoner connector does not judge the nodes of a workflow at once"))
+    }
+
+    /// Composes independently managed policy fragments (e.g., one per department) into the content of a single policy.
+    ///
+    /// The composition must be deterministic, i.e., the same fragments in the same order must always yield the same
+    /// content. Connectors that don't know how to merge their policies can rely on the default, which only accepts a
+    /// single fragment.
+    ///
+    /// # Errors
+    /// This function errors with all conflicts between the fragments if they cannot be composed.
+    fn compose(&self, fragments: &[Policy]) -> Result<Vec<PolicyContent>, PolicyCompositionError> {
+        match fragments {
+            [fragment] => Ok(fragment.content.clone()),
+            _ => {
+                Err(PolicyCompositionError { conflicts: vec!["This reasoner connector does not support composing multiple policy fragments".into()] })
+            },
+        }
+    }
+
+    /// Returns the connector's current measurements, to be exposed on the metrics endpoint.
+    ///
+    /// Connectors without anything interesting to report can rely on the default, which reports nothing.
+    fn metrics(&self) -> Vec<ReasonerMetric> { Vec::new() }
+
+    /// Describes what the connector can do, to be exposed on the capabilities endpoint.
+    ///
+    /// Connectors that answer all questions in the language named by their context and take no options can rely on the default.
+    fn capabilities(&self) -> ReasonerCapabilities { ReasonerCapabilities::new(&Self::context()) }
+
+    /// Returns what was exchanged with the reasoner backend to answer a question, such that it can be replayed offline.
+    ///
+    /// Connectors that don't keep such bundles can rely on the default, which never finds any.
+    ///
+    /// # Arguments
+    /// - `reference`: The verdict reference of the question.
+    ///
+    /// # Returns
+    /// The bundle as JSON, or [`None`] if there is none for this question.
+    ///
+    /// # Errors
+    /// This function errors if a bundle exists but could not be read.
+    async fn debug_bundle(&self, _reference: &str) -> Result<Option<serde_json::Value>, ReasonerConnError> { Ok(None) }
+
+    /// Re-reads the connector's configuration and rebuilds whatever parts of it can be changed without a restart.
+    ///
+    /// Questions that are already being answered finish with the configuration they started with. Connectors that cannot be
+    /// reconfigured can rely on the default, which keeps everything as-is.
+    ///
+    /// # Errors
+    /// This function errors if the new configuration could not be read, in which case the old one is kept.
+    async fn reload(&self) -> Result<(), ReasonerConnError> { Ok(()) }
+
+    /// Tells the connector that a policy was activated, such that it can prepare for answering questions with it (e.g., by
+    /// compiling it, see [`policy_cache::PolicyCache`]) instead of finding out on the next question.
+    ///
+    /// This is also called on startup with the policy that is active then, if any. Connectors that keep nothing per policy
+    /// can rely on the default, which does nothing.
+    ///
+    /// # Arguments
+    /// - `policy`: The [`Policy`] that is active now.
+    ///
+    /// # Errors
+    /// This function errors if the connector failed to prepare for the policy. It is active anyway; the error is only logged.
+    async fn on_policy_activated(&self, _policy: &Policy) -> Result<(), ReasonerConnError> { Ok(()) }
+
+    /// Tells the connector that no policy is active anymore, such that it can drop whatever it prepared for the last one.
+    ///
+    /// Connectors that keep nothing per policy can rely on the default, which does nothing.
+    ///
+    /// # Errors
+    /// This function errors if the connector failed to clean up. The policy is deactivated anyway; the error is only logged.
+    async fn on_policy_deactivated(&self) -> Result<(), ReasonerConnError> { Ok(()) }
+
+    /// Returns the parts of the connector's configuration that may change on a [`ReasonerConnector::reload()`], for the audit log.
+    ///
+    /// Together with [`ConnectorWithContext::context()`], this makes up the connector's effective context. Connectors that
+    /// cannot be reconfigured can rely on the default, which reports nothing.
+    fn configuration(&self) -> serde_json::Value { serde_json::Value::Null }
 }
 
 // #[async_trait::async_trait]
@@ -0,0 +1,212 @@
+//! Implements a cache of policies in the form a [`ReasonerConnector`](crate::ReasonerConnector) works with.
+//!
+//! Many connectors transform a [`Policy`] before they can use it (e.g., the POSIX reasoner parses its JSON, and the
+//! eFLINT reasoner extracts its phrases and annotations). As the same policy is used for question after question, a
+//! connector can keep the result in a [`PolicyCache`] instead of redoing this every time. Entries are keyed by the
+//! policy's version and the hash of its content, such that a stale entry is never used; still, connectors should
+//! [invalidate](PolicyCache::invalidate()) the cache when another policy is activated (see
+//! [`ReasonerConnector::on_policy_activated()`](crate::ReasonerConnector::on_policy_activated())), such that compiled
+//! policies do not linger after they are last used.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use policy::Policy;
+
+use crate::ReasonerMetric;
+
+/***** CONSTANTS *****/
+/// The number of compiled policies a [`PolicyCache`] keeps by default.
+pub const DEFAULT_POLICY_CACHE_CAPACITY: usize = 8;
+
+/***** HELPER FUNCTIONS *****/
+/// Identifies a policy by its version and the hash of its content.
+///
+/// # Arguments
+/// - `policy`: The [`Policy`] to identify.
+///
+/// # Returns
+/// The key of the policy in a [`PolicyCache`].
+fn key_of(policy: &Policy) -> (Option<i64>, String) {
+    (policy.version.version, policy.content.iter().map(|content| content.hash()).collect::<Vec<String>>().join(":"))
+}
+
+/***** LIBRARY *****/
+/// Keeps the policies a connector compiled, by policy version and content hash.
+///
+/// When full, the least recently used policy is dropped to make room.
+#[derive(Debug)]
+pub struct PolicyCache<T> {
+    /// The maximum number of compiled policies kept.
+    capacity: usize,
+    /// The compiled policies by key, with when they were last used.
+    entries: Mutex<HashMap<(Option<i64>, String), (u64, Arc<T>)>>,
+    /// Counts the uses of the cache, to tell which entry was used least recently.
+    clock: AtomicU64,
+    /// The number of policies found compiled already.
+    hits: AtomicU64,
+    /// The number of policies that had to be compiled.
+    misses: AtomicU64,
+    /// The number of times the cache was invalidated.
+    invalidations: AtomicU64,
+}
+impl<T> Default for PolicyCache<T> {
+    #[inline]
+    fn default() -> Self { Self::new(DEFAULT_POLICY_CACHE_CAPACITY) }
+}
+impl<T> PolicyCache<T> {
+    /// Constructor for the PolicyCache.
+    ///
+    /// # Arguments
+    /// - `capacity`: The maximum number of compiled policies to keep. `0` disables the cache.
+    ///
+    /// # Returns
+    /// A new, empty PolicyCache.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the compiled form of a policy, compiling it if it is not cached yet.
+    ///
+    /// # Arguments
+    /// - `policy`: The [`Policy`] to get the compiled form of.
+    /// - `compile`: Compiles the policy if needed. Not called while the cache is locked, so other questions never wait for it.
+    ///
+    /// # Returns
+    /// The compiled policy.
+    ///
+    /// # Errors
+    /// This function errors if the policy was not cached and `compile` failed, in which case nothing is cached.
+    pub fn get_or_try_compile<E>(&self, policy: &Policy, compile: impl FnOnce(&Policy) -> Result<T, E>) -> Result<Arc<T>, E> {
+        let key: (Option<i64>, String) = key_of(policy);
+        let now: u64 = self.clock.fetch_add(1, Ordering::Relaxed);
+        if let Some((used, compiled)) = self.entries.lock().unwrap().get_mut(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            *used = now;
+            return Ok(compiled.clone());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        debug!("Compiling policy version {} for the policy cache", key.0.unwrap_or(-1));
+        let compiled: Arc<T> = Arc::new(compile(policy)?);
+        if self.capacity > 0 {
+            let mut entries = self.entries.lock().unwrap();
+            if !entries.contains_key(&key) && entries.len() >= self.capacity {
+                if let Some(oldest) = entries.iter().min_by_key(|(_, (used, _))| *used).map(|(key, _)| key.clone()) {
+                    entries.remove(&oldest);
+                }
+            }
+            entries.insert(key, (now, compiled.clone()));
+        }
+        Ok(compiled)
+    }
+
+    /// Returns the compiled form of a policy, compiling it if it is not cached yet.
+    ///
+    /// Like [`PolicyCache::get_or_try_compile()`], but for compilations that cannot fail.
+    #[inline]
+    pub fn get_or_compile(&self, policy: &Policy, compile: impl FnOnce(&Policy) -> T) -> Arc<T> {
+        self.get_or_try_compile(policy, |policy| Ok::<T, Infallible>(compile(policy))).unwrap_or_else(|never| match never {})
+    }
+
+    /// Drops all compiled policies, e.g., because another policy was activated.
+    pub fn invalidate(&self) {
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Returns the number of compiled policies kept.
+    #[inline]
+    pub fn len(&self) -> usize { self.entries.lock().unwrap().len() }
+
+    /// Returns whether no compiled policies are kept.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Describes how well the cache does, for the metrics endpoint.
+    pub fn metrics(&self) -> Vec<ReasonerMetric> {
+        vec![
+            ReasonerMetric::new(
+                "policy_reasoner_policy_cache_hits_total",
+                "The number of questions for which the policy was compiled already.",
+                self.hits.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_policy_cache_misses_total",
+                "The number of questions for which the policy had to be compiled.",
+                self.misses.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_policy_cache_invalidations_total",
+                "The number of times the compiled policies were dropped because another policy was activated.",
+                self.invalidations.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new("policy_reasoner_policy_cache_entries", "The number of compiled policies kept.", self.len() as f64),
+        ]
+    }
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use policy::{PolicyContent, PolicyMetadata, PolicyVersion};
+    use serde_json::value::RawValue;
+
+    use super::*;
+
+    fn policy(version: i64, content: &str) -> Policy {
+        Policy {
+            description: String::new(),
+            version:     PolicyVersion {
+                creator: None,
+                created_at: Local::now(),
+                version: Some(version),
+                version_description: String::new(),
+                reasoner_connector_context: String::new(),
+                metadata: PolicyMetadata::default(),
+                content_hashes: Vec::new(),
+                external_id: None,
+            },
+            content:     vec![PolicyContent {
+                reasoner: "test".into(),
+                reasoner_version: "0.1.0".into(),
+                content: Arc::from(RawValue::from_string(content.into()).unwrap()),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_policy_cache() {
+        let cache: PolicyCache<String> = PolicyCache::new(2);
+        let compile = |policy: &Policy| policy.content[0].content.get().to_string();
+        assert_eq!(*cache.get_or_compile(&policy(1, "1"), compile), "1");
+        assert_eq!(*cache.get_or_compile(&policy(1, "1"), |_| unreachable!()), "1");
+        // Same version, other content: never served from the cache
+        assert_eq!(*cache.get_or_compile(&policy(1, "2"), compile), "2");
+        assert_eq!(cache.len(), 2);
+
+        // The least recently used policy makes room
+        cache.get_or_compile(&policy(1, "2"), |_| unreachable!());
+        cache.get_or_compile(&policy(2, "3"), compile);
+        assert_eq!(*cache.get_or_compile(&policy(1, "2"), |_| unreachable!()), "2");
+        assert_eq!(*cache.get_or_compile(&policy(1, "1"), |_| "recompiled".to_string()), "recompiled");
+
+        // Failures are not cached, and invalidating empties it
+        assert!(cache.get_or_try_compile(&policy(3, "4"), |_| Err(())).is_err());
+        assert_eq!(cache.len(), 2);
+        cache.invalidate();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+//! Implements retrying calls to a reasoner backend that failed transiently.
+//!
+//! A dropped connection or a `502 Bad Gateway` from a proxy in front of the backend says nothing about the question,
+//! and asking again a moment later usually works. Connectors can wrap their backend calls in [`RetryConfig::run()`],
+//! which retries calls that failed with a
+//! [`ReasonerConnError::Transient`](crate::ReasonerConnError::Transient) error with exponential backoff and
+//! full jitter, such that many questions failing at once don't all come back at the same moment.
+
+use std::future::Future;
+use std::time::Duration;
+
+use log::{debug, warn};
+use rand::Rng as _;
+
+use crate::ReasonerConnError;
+
+/***** AUXILLARY *****/
+/// Configures how often and how quickly failed backend calls are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryConfig {
+    /// The maximum number of times a backend call is attempted in total. `1` disables retrying.
+    pub max_attempts: u32,
+    /// The backoff before the first retry, doubled for every next one.
+    pub base_backoff: Duration,
+    /// The maximum backoff before any retry.
+    pub max_backoff:  Duration,
+}
+impl Default for RetryConfig {
+    #[inline]
+    fn default() -> Self { Self { max_attempts: 3, base_backoff: Duration::from_millis(100), max_backoff: Duration::from_secs(2) } }
+}
+impl RetryConfig {
+    /// Computes how long to wait before a retry.
+    ///
+    /// # Arguments
+    /// - `retry`: The number of retries done before this one.
+    ///
+    /// # Returns
+    /// A random [`Duration`] between zero and the (capped) exponential backoff for this retry.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let cap: Duration = self.base_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff);
+        Duration::from_micros(rand::thread_rng().gen_range(0..=cap.as_micros() as u64))
+    }
+
+    /// Calls a reasoner backend, retrying transient failures.
+    ///
+    /// # Arguments
+    /// - `call`: A closure doing the backend call. Called once per attempt.
+    ///
+    /// # Returns
+    /// The result of the last attempt, together with the number of retries it took (i.e., `0` if the first attempt
+    /// was the last).
+    pub async fn run<T, F, R>(&self, mut call: F) -> (Result<T, ReasonerConnError>, u32)
+    where
+        F: FnMut() -> R,
+        R: Future<Output = Result<T, ReasonerConnError>>,
+    {
+        let mut retries: u32 = 0;
+        loop {
+            match call().await {
+                Err(err @ ReasonerConnError::Transient { .. }) if retries + 1 < self.max_attempts => {
+                    let backoff: Duration = self.backoff(retries);
+                    warn!("Reasoner backend failed transiently ({err}); retrying in {}ms", backoff.as_millis());
+                    tokio::time::sleep(backoff).await;
+                    retries += 1;
+                },
+                res => {
+                    if retries > 0 {
+                        debug!("Reasoner backend call took {retries} retries");
+                    }
+                    return (res, retries);
+                },
+            }
+        }
+    }
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config that retries without waiting.
+    const IMMEDIATE: RetryConfig = RetryConfig { max_attempts: 3, base_backoff: Duration::ZERO, max_backoff: Duration::ZERO };
+
+    #[test]
+    fn test_retry_backoff() {
+        let config = RetryConfig { max_attempts: 10, base_backoff: Duration::from_millis(100), max_backoff: Duration::from_secs(1) };
+        for retry in 0..10 {
+            let backoff: Duration = config.backoff(retry);
+            assert!(backoff <= Duration::from_millis(100 * 2u64.pow(retry)).min(Duration::from_secs(1)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_run() {
+        // Transient failures are retried until the attempts run out
+        let mut calls: u32 = 0;
+        let (res, retries) = IMMEDIATE
+            .run(|| {
+                calls += 1;
+                async { Err::<(), _>(ReasonerConnError::transient("connection reset")) }
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!((calls, retries), (3, 2));
+
+        // Other failures are not
+        let mut calls: u32 = 0;
+        let (res, retries) = IMMEDIATE
+            .run(|| {
+                calls += 1;
+                async { Err::<(), _>(ReasonerConnError::new("invalid query")) }
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!((calls, retries), (1, 0));
+
+        // And retrying stops as soon as it works
+        let mut calls: u32 = 0;
+        let (res, retries) = IMMEDIATE
+            .run(|| {
+                calls += 1;
+                let attempt: u32 = calls;
+                async move { if attempt < 2 { Err(ReasonerConnError::transient("502 Bad Gateway")) } else { Ok(attempt) } }
+            })
+            .await;
+        assert_eq!(res.unwrap(), 2);
+        assert_eq!(retries, 1);
+    }
+}
@@ -0,0 +1,293 @@
+//! Implements a wrapper that asks a second [`ReasonerConnector`] the same questions, but only to log its answers.
+//!
+//! Moving to another reasoner (e.g., from the POSIX reasoner to eFLINT) is risky when it is not known how often the new
+//! one would have answered differently. The [`Shadow`] connector returns the verdicts of the connector in use as-is,
+//! but also asks a shadow connector every question in the background. Both answers are written to the audit log as a
+//! [`LogStatement::ShadowEvaluation`](audit_logger::LogStatement::ShadowEvaluation), together with whether they agree,
+//! such that the new reasoner can be evaluated on real traffic without affecting any outcome.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use audit_logger::{ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::{Explanation, NodeResult};
+use log::{debug, warn};
+use policy::{Policy, PolicyContent};
+use serde_json::Value;
+use state_resolver::State;
+use workflow::spec::Workflow;
+
+use crate::{
+    PolicyCompositionError, QuestionKind, ReasonerCapabilities, ReasonerConnError, ReasonerConnector, ReasonerMetric, ReasonerQueryResponse,
+    ReasonerResponse,
+};
+
+/***** CONSTANTS *****/
+/// The label of the sub-session in which the shadow connector is asked.
+pub const SHADOW_SESSION_LABEL: &str = "shadow";
+
+/***** HELPER FUNCTIONS *****/
+/// Serializes the answer of a connector for the audit log.
+///
+/// # Arguments
+/// - `res`: The answer, or the reason why there is none.
+///
+/// # Returns
+/// The serialized [`ReasonerResponse`], or an object with only an `error`-field if the connector failed.
+fn answer_json(res: &Result<ReasonerResponse, ReasonerConnError>) -> Value {
+    match res {
+        Ok(response) => {
+            serde_json::to_value(response).unwrap_or_else(|err| serde_json::json!({ "error": format!("Failed to serialize answer: {err}") }))
+        },
+        Err(err) => serde_json::json!({ "error": err.to_string() }),
+    }
+}
+
+/***** AUXILLARY *****/
+/// Counts how the shadow connector did so far.
+#[derive(Debug, Default)]
+struct ShadowCounters {
+    /// The number of questions the shadow connector was asked.
+    evaluated: AtomicU64,
+    /// The number of questions for which the shadow connector gave another verdict than the primary one.
+    disagreed: AtomicU64,
+    /// The number of questions the shadow connector failed to answer.
+    failed:    AtomicU64,
+}
+
+/***** LIBRARY *****/
+/// Wraps a [`ReasonerConnector`] such that a second connector is asked the same questions, whose answers are only logged.
+///
+/// Only the questions about workflows (tasks, data access and whole workflows) are asked to the shadow connector; all
+/// other requests only go to the primary one. Note that the wrapper is transparent w.r.t. the primary connector's
+/// context, and that policies must have content for both reasoners for the shadow connector to answer sensibly.
+#[derive(Debug)]
+pub struct Shadow<P, S> {
+    /// The connector whose answers are returned.
+    primary:  P,
+    /// The connector whose answers are only logged.
+    shadow:   Arc<S>,
+    /// Keeps track of how the shadow connector does.
+    counters: Arc<ShadowCounters>,
+}
+
+impl<P, S> Shadow<P, S> {
+    /// Constructor for the Shadow.
+    ///
+    /// # Arguments
+    /// - `primary`: The [`ReasonerConnector`] whose answers are returned.
+    /// - `shadow`: The [`ReasonerConnector`] that is asked the same questions, but whose answers are only logged.
+    ///
+    /// # Returns
+    /// A new Shadow connector.
+    #[inline]
+    pub fn new(primary: P, shadow: S) -> Self { Self { primary, shadow: Arc::new(shadow), counters: Arc::new(ShadowCounters::default()) } }
+
+    /// Returns the connector whose answers are returned.
+    #[inline]
+    pub fn primary(&self) -> &P { &self.primary }
+
+    /// Returns the connector whose answers are only logged.
+    #[inline]
+    pub fn shadow(&self) -> &S { &self.shadow }
+
+    /// Asks the shadow connector a question in the background, and logs its answer next to the primary one.
+    ///
+    /// # Arguments
+    /// - `logger`: The session of the question, in which the comparison is logged. The shadow connector is asked in a
+    ///   sub-session of it.
+    /// - `primary`: The answer of the primary connector.
+    /// - `question`: Asks the shadow connector the question, given it and the sub-session to ask it in.
+    fn evaluate<L, Q, F>(&self, logger: SessionedConnectorAuditLogger<L>, primary: &Result<ReasonerResponse, ReasonerConnError>, question: Q)
+    where
+        L: ReasonerConnectorAuditLogger + Clone + Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        Q: FnOnce(Arc<S>, SessionedConnectorAuditLogger<L>) -> F + Send + 'static,
+        F: Future<Output = Result<ReasonerResponse, ReasonerConnError>> + Send + 'static,
+    {
+        let primary_verdict: Option<bool> = primary.as_ref().ok().map(|response| response.success);
+        let primary: Value = answer_json(primary);
+        let shadow: Arc<S> = self.shadow.clone();
+        let counters: Arc<ShadowCounters> = self.counters.clone();
+        let session: SessionedConnectorAuditLogger<L> = logger.sub_session(SHADOW_SESSION_LABEL);
+        tokio::spawn(async move {
+            let res: Result<ReasonerResponse, ReasonerConnError> = question(shadow, session.clone()).await;
+            if let Err(err) = session.end().await {
+                warn!("Failed to end shadow session of request '{}': {err}", logger.reference);
+            }
+
+            // Compare the verdicts; failures never agree with anything
+            counters.evaluated.fetch_add(1, Ordering::Relaxed);
+            let shadow_verdict: Option<bool> = res.as_ref().ok().map(|response| response.success);
+            if shadow_verdict.is_none() {
+                counters.failed.fetch_add(1, Ordering::Relaxed);
+            }
+            let agree: bool = primary_verdict.is_some() && primary_verdict == shadow_verdict;
+            if !agree {
+                counters.disagreed.fetch_add(1, Ordering::Relaxed);
+                debug!("Shadow reasoner disagrees with the primary one on request '{}'", logger.reference);
+            }
+            if let Err(err) = logger.log_shadow_evaluation(&primary, &answer_json(&res), agree).await {
+                warn!("Failed to log shadow evaluation of request '{}': {err}", logger.reference);
+            }
+        });
+    }
+}
+
+impl<P: ConnectorWithContext, S> ConnectorWithContext for Shadow<P, S> {
+    type Context = P::Context;
+
+    #[inline]
+    fn hash() -> String { P::hash() }
+
+    #[inline]
+    fn context() -> Self::Context { P::context() }
+}
+
+#[async_trait::async_trait]
+impl<L, P, S> ReasonerConnector<L> for Shadow<P, S>
+where
+    L: ReasonerConnectorAuditLogger + Clone + Send + Sync + 'static,
+    P: ReasonerConnector<L> + Send + Sync,
+    S: ReasonerConnector<L> + Send + Sync + 'static,
+{
+    async fn execute_task(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        task: String,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        let session: SessionedConnectorAuditLogger<L> = logger.clone();
+        let question = (policy.clone(), state.clone(), workflow.clone(), task.clone());
+        let res: Result<ReasonerResponse, ReasonerConnError> = self.primary.execute_task(logger, policy, state, workflow, task).await;
+        self.evaluate(session, &res, move |shadow: Arc<S>, logger| async move {
+            let (policy, state, workflow, task) = question;
+            shadow.execute_task(logger, policy, state, workflow, task).await
+        });
+        res
+    }
+
+    async fn access_data_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        data: String,
+        task: Option<String>,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        let session: SessionedConnectorAuditLogger<L> = logger.clone();
+        let question = (policy.clone(), state.clone(), workflow.clone(), data.clone(), task.clone());
+        let res: Result<ReasonerResponse, ReasonerConnError> = self.primary.access_data_request(logger, policy, state, workflow, data, task).await;
+        self.evaluate(session, &res, move |shadow: Arc<S>, logger| async move {
+            let (policy, state, workflow, data, task) = question;
+            shadow.access_data_request(logger, policy, state, workflow, data, task).await
+        });
+        res
+    }
+
+    async fn workflow_validation_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        let session: SessionedConnectorAuditLogger<L> = logger.clone();
+        let question = (policy.clone(), state.clone(), workflow.clone());
+        let res: Result<ReasonerResponse, ReasonerConnError> = self.primary.workflow_validation_request(logger, policy, state, workflow).await;
+        self.evaluate(session, &res, move |shadow: Arc<S>, logger| async move {
+            let (policy, state, workflow) = question;
+            shadow.workflow_validation_request(logger, policy, state, workflow).await
+        });
+        res
+    }
+
+    async fn query(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        query: serde_json::Value,
+    ) -> Result<ReasonerQueryResponse, ReasonerConnError> {
+        self.primary.query(logger, policy, state, query).await
+    }
+
+    async fn explain(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        question: QuestionKind,
+    ) -> Result<Explanation, ReasonerConnError> {
+        self.primary.explain(logger, policy, state, workflow, question).await
+    }
+
+    async fn node_results(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<Vec<NodeResult>, ReasonerConnError> {
+        self.primary.node_results(logger, policy, state, workflow).await
+    }
+
+    #[inline]
+    fn compose(&self, fragments: &[Policy]) -> Result<Vec<PolicyContent>, PolicyCompositionError> { self.primary.compose(fragments) }
+
+    #[inline]
+    fn capabilities(&self) -> ReasonerCapabilities { self.primary.capabilities() }
+
+    #[inline]
+    async fn debug_bundle(&self, reference: &str) -> Result<Option<serde_json::Value>, ReasonerConnError> {
+        self.primary.debug_bundle(reference).await
+    }
+
+    #[inline]
+    async fn reload(&self) -> Result<(), ReasonerConnError> { self.primary.reload().await }
+
+    async fn on_policy_activated(&self, policy: &Policy) -> Result<(), ReasonerConnError> {
+        // Both answer with the same policies, but only the primary one may fail the notification
+        if let Err(err) = self.shadow.on_policy_activated(policy).await {
+            warn!("Shadow reasoner failed to prepare for policy version {}: {err}", policy.version.version.unwrap_or(-1));
+        }
+        self.primary.on_policy_activated(policy).await
+    }
+
+    async fn on_policy_deactivated(&self) -> Result<(), ReasonerConnError> {
+        if let Err(err) = self.shadow.on_policy_deactivated().await {
+            warn!("Shadow reasoner failed to handle policy deactivation: {err}");
+        }
+        self.primary.on_policy_deactivated().await
+    }
+
+    #[inline]
+    fn configuration(&self) -> serde_json::Value { self.primary.configuration() }
+
+    fn metrics(&self) -> Vec<ReasonerMetric> {
+        let mut metrics: Vec<ReasonerMetric> = vec![
+            ReasonerMetric::new(
+                "policy_reasoner_shadow_evaluated_total",
+                "The number of questions that the shadow reasoner was asked.",
+                self.counters.evaluated.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_shadow_disagreed_total",
+                "The number of questions for which the shadow reasoner gave another verdict than the primary one (or either failed).",
+                self.counters.disagreed.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_shadow_failed_total",
+                "The number of questions that the shadow reasoner failed to answer.",
+                self.counters.failed.load(Ordering::Relaxed) as f64,
+            ),
+        ];
+        metrics.extend(self.primary.metrics());
+        metrics
+    }
+}
@@ -0,0 +1,170 @@
+//! Implements the access tokens that come with allowed data-access questions.
+//!
+//! The reasoner only says whether a dataset may be accessed; it is up to the service providing the dataset to actually
+//! refuse access otherwise. To close that gap, the server can hand out a short-lived, signed capability with every
+//! allowed data-access verdict. The service providing the dataset can verify it offline with the reasoner's public key,
+//! and check that it names the dataset, user and workflow of the access before granting it.
+//!
+//! Tokens are compact JWSs, signed with Ed25519 (`EdDSA`) or ECDSA P-256 (`ES256`). Their `jti` is the verdict
+//! reference, such that every token can be traced back to its verdict in the audit log.
+
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::time::Duration;
+
+use chrono::Utc;
+use deliberation::spec::Verdict;
+use error_trace::ErrorTrace as _;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+/***** CONSTANTS *****/
+/// The issuer named in every access token.
+pub const ACCESS_TOKEN_ISSUER: &str = "policy-reasoner";
+
+/***** ERRORS *****/
+/// Defines errors that originate from issuing access tokens.
+#[derive(Debug)]
+pub enum AccessTokenError {
+    /// The key was not a PKCS#8 PEM-encoded Ed25519 or ECDSA P-256 private key.
+    UnsupportedKey,
+    /// Failed to sign a token.
+    Sign { err: jsonwebtoken::errors::Error },
+}
+impl Display for AccessTokenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use AccessTokenError::*;
+        match self {
+            UnsupportedKey => write!(f, "Access token key is not a PEM-encoded (PKCS#8) Ed25519 or ECDSA P-256 private key"),
+            Sign { .. } => write!(f, "Failed to sign access token"),
+        }
+    }
+}
+impl error::Error for AccessTokenError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use AccessTokenError::*;
+        match self {
+            UnsupportedKey => None,
+            Sign { err } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// The claims in an access token.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccessTokenClaims {
+    /// Who issued the token (always [`ACCESS_TOKEN_ISSUER`]).
+    pub iss:      String,
+    /// The user that may access the dataset.
+    pub sub:      String,
+    /// The verdict reference of the question that allowed the access.
+    pub jti:      String,
+    /// When the token was issued, in seconds since the Unix epoch.
+    pub iat:      i64,
+    /// When the token expires, in seconds since the Unix epoch.
+    pub exp:      i64,
+    /// The dataset that may be accessed.
+    pub dataset:  String,
+    /// The identifier of the workflow for which it may be accessed.
+    pub workflow: String,
+    /// The task for which it may be accessed, or [`None`] if it is the result of the workflow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task:     Option<String>,
+}
+
+/***** LIBRARY *****/
+/// Signs the access tokens that come with allowed data-access verdicts.
+pub struct AccessTokenIssuer {
+    /// The header of every token.
+    header: Header,
+    /// The key to sign tokens with.
+    key:    EncodingKey,
+    /// How long tokens are valid.
+    ttl:    Duration,
+}
+impl AccessTokenIssuer {
+    /// Constructor for the AccessTokenIssuer.
+    ///
+    /// # Arguments
+    /// - `pem`: The PEM-encoded (PKCS#8) Ed25519 or ECDSA P-256 private key to sign tokens with.
+    /// - `key_id`: The identifier to put in the `kid` of every token, if any, for services that know multiple keys.
+    /// - `ttl`: How long tokens are valid after they are issued.
+    ///
+    /// # Errors
+    /// This function errors if the key is not supported or cannot sign.
+    pub fn from_pem(pem: &[u8], key_id: Option<String>, ttl: Duration) -> Result<Self, AccessTokenError> {
+        let (algorithm, key): (Algorithm, EncodingKey) = match EncodingKey::from_ed_pem(pem) {
+            Ok(key) => (Algorithm::EdDSA, key),
+            Err(_) => match EncodingKey::from_ec_pem(pem) {
+                Ok(key) => (Algorithm::ES256, key),
+                Err(_) => return Err(AccessTokenError::UnsupportedKey),
+            },
+        };
+        let mut header: Header = Header::new(algorithm);
+        header.kid = key_id;
+        let this: Self = Self { header, key, ttl };
+
+        // Make sure the key can actually sign (e.g., it's not an ECDSA key on another curve)
+        this.issue("probe", "probe", "probe", "probe", None).map_err(|_| AccessTokenError::UnsupportedKey)?;
+        Ok(this)
+    }
+
+    /// Issues an access token for an allowed data-access question.
+    ///
+    /// # Arguments
+    /// - `reference`: The verdict reference of the question.
+    /// - `user`: The user that asked.
+    /// - `workflow`: The identifier of the workflow the question was about.
+    /// - `dataset`: The dataset that may be accessed.
+    /// - `task`: The task for which it may be accessed, if any.
+    ///
+    /// # Returns
+    /// The compact JWS of the token.
+    ///
+    /// # Errors
+    /// This function errors if the token could not be signed.
+    pub fn issue(&self, reference: &str, user: &str, workflow: &str, dataset: &str, task: Option<&str>) -> Result<String, AccessTokenError> {
+        let iat: i64 = Utc::now().timestamp();
+        let claims = AccessTokenClaims {
+            iss: ACCESS_TOKEN_ISSUER.into(),
+            sub: user.into(),
+            jti: reference.into(),
+            iat,
+            exp: iat.saturating_add(i64::try_from(self.ttl.as_secs()).unwrap_or(i64::MAX)),
+            dataset: dataset.into(),
+            workflow: workflow.into(),
+            task: task.map(String::from),
+        };
+        jsonwebtoken::encode(&self.header, &claims, &self.key).map_err(|err| AccessTokenError::Sign { err })
+    }
+}
+
+/***** HELPERS *****/
+/// Attaches an access token to a data-access verdict if it allows the access and the server issues tokens.
+///
+/// Failing to issue a token does not affect the verdict; it is only logged, and the service providing the dataset will
+/// refuse the access for lack of a token.
+///
+/// # Arguments
+/// - `issuer`: The [`AccessTokenIssuer`] of the server, if it issues tokens.
+/// - `verdict`: The [`Verdict`] to attach the token to.
+/// - `user`: The user that asked.
+/// - `workflow`: The identifier of the workflow the question was about.
+/// - `dataset`: The dataset that may be accessed.
+/// - `task`: The task for which it may be accessed, if any.
+pub(crate) fn attach_access_token(
+    issuer: Option<&AccessTokenIssuer>,
+    verdict: &mut Verdict,
+    user: &str,
+    workflow: &str,
+    dataset: &str,
+    task: Option<&str>,
+) {
+    let (Some(issuer), Verdict::Allow(allow)) = (issuer, verdict) else { return };
+    match issuer.issue(&allow.shared.verdict_reference, user, workflow, dataset, task) {
+        Ok(token) => allow.access_token = Some(token),
+        Err(err) => error!("{} | request id: {}", err.trace(), allow.shared.verdict_reference),
+    }
+}
@@ -0,0 +1,266 @@
+//! Implements exporting and importing the complete policy store as a signed archive.
+//!
+//! An archive contains all policy versions (with their content and metadata) and the full activation history, such
+//! that it can be used for backups, disaster recovery or to promote policies from one environment to another (e.g.,
+//! staging to production). To make sure an archive was not tampered with in transit, it is signed with a secret key
+//! using HMAC-SHA256; importing it only succeeds if the importing reasoner knows the same key.
+
+use std::error;
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+use std::sync::Arc;
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use base64ct::{Base64, Encoding as _};
+use error_trace::ErrorTrace as _;
+use hmac::{Hmac, Mac as _};
+use log::info;
+use policy::{Context, PolicyArchive, PolicyDataAccess, PolicyDataError};
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use state_resolver::StateResolver;
+use warp::Filter;
+
+use crate::problem::Problem;
+use crate::{Srv, models};
+
+/***** CONSTANTS *****/
+/// The only signing algorithm we currently support.
+pub const ARCHIVE_SIGNATURE_ALGORITHM: &'static str = "HS256";
+
+/***** ERRORS *****/
+/// Defines errors that originate from signing or verifying policy archives.
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The archive was signed with an algorithm we don't know.
+    UnsupportedAlgorithm { algorithm: String },
+    /// The archive was made with a newer version of the format.
+    UnsupportedFormat { format: u32 },
+    /// The signature was not valid Base64.
+    SignatureDecode { err: base64ct::Error },
+    /// The signature did not match the archive (or the key is wrong).
+    SignatureMismatch,
+    /// Failed to serialize the archive.
+    Serialize { err: serde_json::Error },
+    /// Failed to deserialize the signed archive.
+    Deserialize { err: serde_json::Error },
+}
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use ArchiveError::*;
+        match self {
+            UnsupportedAlgorithm { algorithm } => {
+                write!(f, "Unsupported archive signature algorithm '{algorithm}' (expected '{ARCHIVE_SIGNATURE_ALGORITHM}')")
+            },
+            UnsupportedFormat { format } => write!(f, "Unsupported archive format version {format} (expected {} or lower)", PolicyArchive::FORMAT),
+            SignatureDecode { .. } => write!(f, "Failed to decode archive signature as Base64"),
+            SignatureMismatch => write!(f, "Archive signature does not match its contents"),
+            Serialize { .. } => write!(f, "Failed to serialize policy archive"),
+            Deserialize { .. } => write!(f, "Failed to deserialize policy archive"),
+        }
+    }
+}
+impl error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use ArchiveError::*;
+        match self {
+            UnsupportedAlgorithm { .. } => None,
+            UnsupportedFormat { .. } => None,
+            SignatureDecode { err } => Some(err),
+            SignatureMismatch => None,
+            Serialize { err } => Some(err),
+            Deserialize { err } => Some(err),
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// A [`PolicyArchive`] together with its signature.
+///
+/// The archive is kept as the serialized string that was signed, such that re-serializing it cannot break the signature.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SignedPolicyArchive {
+    /// The algorithm used to sign the archive. See [`ARCHIVE_SIGNATURE_ALGORITHM`].
+    pub algorithm: String,
+    /// The [`PolicyArchive`], serialized as JSON.
+    pub archive:   String,
+    /// The Base64-encoded signature over `archive`.
+    pub signature: String,
+}
+
+impl SignedPolicyArchive {
+    /// Serializes and signs a [`PolicyArchive`].
+    ///
+    /// # Arguments
+    /// - `key`: The secret key to sign the archive with.
+    /// - `archive`: The [`PolicyArchive`] to sign.
+    ///
+    /// # Returns
+    /// A new SignedPolicyArchive.
+    ///
+    /// # Errors
+    /// This function errors if we failed to serialize the archive.
+    pub fn sign(key: &[u8], archive: &PolicyArchive) -> Result<Self, ArchiveError> {
+        let archive: String = serde_json::to_string(archive).map_err(|err| ArchiveError::Serialize { err })?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(archive.as_bytes());
+        Ok(Self { algorithm: ARCHIVE_SIGNATURE_ALGORITHM.into(), archive, signature: Base64::encode_string(&mac.finalize().into_bytes()) })
+    }
+
+    /// Verifies the signature of the archive and deserializes it.
+    ///
+    /// # Arguments
+    /// - `key`: The secret key the archive should have been signed with.
+    ///
+    /// # Returns
+    /// The signed [`PolicyArchive`].
+    ///
+    /// # Errors
+    /// This function errors if the signature does not match, or if the archive is not a valid [`PolicyArchive`].
+    pub fn verify(&self, key: &[u8]) -> Result<PolicyArchive, ArchiveError> {
+        if self.algorithm != ARCHIVE_SIGNATURE_ALGORITHM {
+            return Err(ArchiveError::UnsupportedAlgorithm { algorithm: self.algorithm.clone() });
+        }
+        let signature: Vec<u8> = Base64::decode_vec(&self.signature).map_err(|err| ArchiveError::SignatureDecode { err })?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(self.archive.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| ArchiveError::SignatureMismatch)?;
+
+        let archive: PolicyArchive = serde_json::from_str(&self.archive).map_err(|err| ArchiveError::Deserialize { err })?;
+        if archive.format > PolicyArchive::FORMAT {
+            return Err(ArchiveError::UnsupportedFormat { format: archive.format });
+        }
+        Ok(archive)
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Returns the key to sign archives with, or rejects with a problem if none is configured.
+    fn require_archive_key(&self) -> Result<&[u8], warp::reject::Rejection> {
+        match &self.archive_key {
+            Some(key) => Ok(key),
+            None => {
+                let p = ProblemDetails::new()
+                    .with_status(warp::http::StatusCode::NOT_IMPLEMENTED)
+                    .with_detail("No archive key is configured for this reasoner; cannot export or import the policy store");
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    // Export the complete policy store
+    // GET /v1/management/export
+    // out:
+    //  200 SignedPolicyArchive
+    //  501 problem+json
+
+    async fn handle_export(_auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let key: &[u8] = this.require_archive_key()?;
+        let archive: PolicyArchive = match this.policystore.export_store().await {
+            Ok(archive) => archive,
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(PolicyDataError::GeneralError(msg)) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+
+        match SignedPolicyArchive::sign(key, &archive) {
+            Ok(signed) => Ok(warp::reply::json(&signed)),
+            Err(err) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string());
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    // Add the versions of an exported policy store that are new, and take over its activation history
+    // POST /v1/management/import
+    // in: SignedPolicyArchive
+    // out:
+    //  200 ImportResultModel
+    //  400 problem+json
+    //  501 problem+json
+
+    async fn handle_import(auth_ctx: AuthContext, this: Arc<Self>, body: SignedPolicyArchive) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let archive: PolicyArchive = match body.verify(this.require_archive_key()?) {
+            Ok(archive) => archive,
+            Err(err) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(err.to_string());
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+
+        // Reject archives whose active policy cannot be used by this reasoner
+        let conn_hash = C::hash();
+        let active: Option<i64> = archive.active();
+        if let Some(policy) = active.and_then(|active| archive.policies.iter().find(|p| p.version.version == Some(active))) {
+            if policy.version.reasoner_connector_context != conn_hash {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(format!(
+                    "Cannot import archive whose active policy has a different base policy than the reasoner connector's base. Policy base defs \
+                     hash is '{}' and connector's base defs hash is '{}'",
+                    policy.version.reasoner_connector_context, conn_hash
+                ));
+                return Err(warp::reject::custom(Problem::from(p)));
+            }
+        }
+
+        let result = models::ImportResultModel { versions: archive.policies.iter().filter_map(|p| p.version.version).collect(), active };
+        let t = this.clone();
+        match this
+            .policystore
+            .import_store(archive, Context { initiator: auth_ctx.initiator.clone() }, |archive| async move {
+                t.logger.log_import_policies(&auth_ctx, &archive).await.map_err(|err| PolicyDataError::GeneralError(err.trace().to_string()))
+            })
+            .await
+        {
+            Ok(()) => {
+                // Version numbers may now refer to different policies, so forget what we remember about the old ones
+                this.verdict_cache.clear();
+                info!("Imported policy store with {} version(s) (active: {:?})", result.versions.len(), result.active);
+                Ok(warp::reply::json(&result))
+            },
+            Err(err) => match err {
+                PolicyDataError::NotFound => {
+                    let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND);
+                    Err(warp::reject::custom(Problem::from(p)))
+                },
+                PolicyDataError::GeneralError(msg) => {
+                    let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                    Err(warp::reject::custom(Problem::from(p)))
+                },
+            },
+        }
+    }
+
+    pub fn archive_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let export = warp::get()
+            .and(warp::path!("export"))
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_export);
+
+        let import = warp::post()
+            .and(warp::path!("import"))
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_import);
+
+        warp::path("v1").and(warp::path("management")).and(export.or(import))
+    }
+}
@@ -0,0 +1,145 @@
+//! Implements read-only access to the state the reasoner reasons about.
+//!
+//! Tools that help write policies or requests (such as the Policy Reasoner GUI) need to know which locations, datasets,
+//! functions and users exist. Rather than having them resolve that themselves, the catalog API exposes the [`State`] as
+//! resolved by the reasoner's own [`StateResolver`] for a particular use-case.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use log::{error, info};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::Serialize;
+use state_resolver::{State, StateResolver, StateResolverError as _};
+use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::HeaderMap;
+use warp::reject::Rejection;
+
+use crate::Srv;
+use crate::problem::Problem;
+
+/***** AUXILLARY *****/
+/// The parts of a [`State`] that can be browsed separately.
+#[derive(Clone, Copy, Debug)]
+enum CatalogPart {
+    Locations,
+    Datasets,
+    Functions,
+    Images,
+    Users,
+}
+
+/***** LIBRARY *****/
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Resolves the state of a use-case.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the state resolver to use.
+    /// - `use_case`: The use-case to resolve the state of.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `404 Not Found` if the use-case is unknown, or a
+    /// `500 Internal Server Error` if the state could not be resolved otherwise.
+    async fn resolve_catalog(this: &Self, use_case: String) -> Result<State, Rejection> {
+        match this.stateresolver.get_state(use_case).await {
+            Ok(state) => Ok(state),
+            Err(err) => {
+                let p = if let Some(use_case) = err.try_as_unknown_use_case() {
+                    ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND).with_detail(format!("Unknown use-case '{use_case}'"))
+                } else {
+                    error!("Could not retrieve state for catalog: {err}");
+                    ProblemDetails::new().with_status(warp::http::StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string())
+                };
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    // Get the complete state of a use-case
+    // GET /v1/catalog/{use_case}
+    // out:
+    //  200 State
+    //  404 problem+json (unknown use-case)
+
+    async fn handle_get_catalog(use_case: String, _auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling catalog request for use-case '{use_case}'");
+        let state: State = Self::resolve_catalog(&this, use_case).await?;
+        Ok(warp::reply::json(&state))
+    }
+
+    // Get one part of the state of a use-case
+    // GET /v1/catalog/{use_case}/locations
+    // GET /v1/catalog/{use_case}/datasets
+    // GET /v1/catalog/{use_case}/functions
+    // GET /v1/catalog/{use_case}/images
+    // GET /v1/catalog/{use_case}/users
+    // out:
+    //  200 Vec<User>, Vec<Dataset> or Vec<PackageImage>
+    //  404 problem+json (unknown use-case)
+
+    async fn handle_get_catalog_part(
+        use_case: String,
+        part: CatalogPart,
+        _auth_ctx: AuthContext,
+        this: Arc<Self>,
+    ) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling catalog request for the {part:?} of use-case '{use_case}'");
+        let state: State = Self::resolve_catalog(&this, use_case).await?;
+        Ok(match part {
+            CatalogPart::Locations => warp::reply::json(&state.locations),
+            CatalogPart::Datasets => warp::reply::json(&state.datasets),
+            CatalogPart::Functions => warp::reply::json(&state.functions),
+            CatalogPart::Images => warp::reply::json(&state.images),
+            CatalogPart::Users => warp::reply::json(&state.users),
+        })
+    }
+
+    pub fn catalog_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let get_catalog = warp::get()
+            .and(warp::path!(String))
+            .and(Self::with_catalog_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_catalog);
+
+        let part = warp::path!(String / "locations")
+            .map(|use_case: String| (use_case, CatalogPart::Locations))
+            .or(warp::path!(String / "datasets").map(|use_case: String| (use_case, CatalogPart::Datasets)))
+            .unify()
+            .or(warp::path!(String / "functions").map(|use_case: String| (use_case, CatalogPart::Functions)))
+            .unify()
+            .or(warp::path!(String / "images").map(|use_case: String| (use_case, CatalogPart::Images)))
+            .unify()
+            .or(warp::path!(String / "users").map(|use_case: String| (use_case, CatalogPart::Users)))
+            .unify()
+            .untuple_one();
+        let get_catalog_part = warp::get()
+            .and(part)
+            .and(Self::with_catalog_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_catalog_part);
+
+        warp::path("v1").and(warp::path("catalog")).and(get_catalog.or(get_catalog_part))
+    }
+
+    fn with_catalog_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.pauthresolver, route, headers).await
+            },
+        )
+    }
+}
@@ -0,0 +1,98 @@
+//! Implements counting the usage that the reasoner permits, and exposing it to policies.
+//!
+//! With [`UsageCounters`] configured, every task the reasoner allows to execute counts as a
+//! [`TASK_EXECUTIONS`](state_resolver::counters::TASK_EXECUTIONS) for the user asking and for every dataset it takes as
+//! input, and every dataset it allows to be accessed counts as a [`DATA_ACCESSES`](state_resolver::counters::DATA_ACCESSES)
+//! for the user asking and for the dataset. The counters of the current period are given to the reasoner as part of the
+//! [`State`], such that policies can limit usage (e.g., "at most 5 analyses of dataset D per month").
+//!
+//! Note that the counters count permissions, not actual executions: the reasoner cannot know whether a permitted task
+//! was actually run.
+
+use chrono::Local;
+use log::{debug, error};
+use problem_details::ProblemDetails;
+use state_resolver::State;
+use state_resolver::counters::{UsageCounters, period};
+use warp::http::StatusCode;
+use warp::reject::Rejection;
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+use workflow::{ElemTask, Workflow};
+
+use crate::problem::Problem;
+
+/***** HELPERS *****/
+/// Finds the inputs of a particular task in a [`Workflow`].
+struct TaskInputs<'t> {
+    /// The identifier of the task to find.
+    task:   &'t str,
+    /// The names of its input datasets, once found.
+    inputs: Vec<String>,
+}
+impl<'t> WorkflowVisitor for TaskInputs<'t> {
+    fn visit_task(&mut self, task: &ElemTask) {
+        if task.id == self.task {
+            self.inputs.extend(task.input.iter().map(|data| data.name.clone()));
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// Finds the names of the input datasets of a task.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] that contains the task.
+/// - `task`: The identifier of the task.
+///
+/// # Returns
+/// The names of the datasets the task may take as input. Empty if the task does not exist.
+pub(crate) fn task_inputs(workflow: &Workflow, task: &str) -> Vec<String> {
+    let mut finder = TaskInputs { task, inputs: vec![] };
+    walk_workflow_preorder(&workflow.start, &mut finder);
+    finder.inputs
+}
+
+/// Adds the counters of the current period to a state, if usage is counted.
+///
+/// # Arguments
+/// - `counters`: The [`UsageCounters`] to read, if any.
+/// - `state`: The [`State`] to add the counters to.
+/// - `reference`: The verdict reference of the request, for logging.
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `500 Internal Server Error` if the counters could not be read, as
+/// policies relying on them could otherwise permit too much.
+pub(crate) async fn attach_counters(counters: Option<&dyn UsageCounters>, state: &mut State, reference: &str) -> Result<(), Rejection> {
+    let Some(counters) = counters else { return Ok(()) };
+    match counters.get_counters(&period(Local::now())).await {
+        Ok(current) => {
+            debug!("Got {} usage counters | request id: {reference}", current.len());
+            state.counters = current;
+            Ok(())
+        },
+        Err(err) => {
+            error!("Could not retrieve usage counters: {err} | request id: {reference}");
+            let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string());
+            Err(warp::reject::custom(Problem::from(p)))
+        },
+    }
+}
+
+/// Counts permitted usage, if usage is counted.
+///
+/// Failing to count does not affect the verdict, which is already given; it is only logged.
+///
+/// # Arguments
+/// - `counters`: The [`UsageCounters`] to update, if any.
+/// - `counter`: The kind of usage to count.
+/// - `subjects`: The users and datasets to count the usage for.
+/// - `reference`: The verdict reference of the request, for logging.
+pub(crate) async fn count_usage(counters: Option<&dyn UsageCounters>, counter: &str, subjects: &[String], reference: &str) {
+    let Some(counters) = counters else { return };
+    let period: String = period(Local::now());
+    for subject in subjects {
+        if let Err(err) = counters.increment(subject, counter, &period, 1).await {
+            error!("Could not count {counter} of '{subject}': {err} | request id: {reference}");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Analyses how well a policy covers the questions asked to it.
+//!
+//! Policies grow over time, and their authors lose track of which rules still matter and what they never said anything
+//! about. Given a policy in eFLINT JSON and a corpus of questions, this module reports:
+//! - which rules (invariants, acts, duties and events) never fired, i.e., were never violated nor triggered in the
+//!   reasoner responses recorded for the questions; and
+//! - which facts the workflows assert (e.g., `node-at`, where a task runs) that no rule depends on, directly or through
+//!   the facts derived from them. Whatever those facts say, the policy cannot tell the difference.
+//!
+//! The corpus consists of the questions recorded in an audit log (see [`Coverage::record_log()`]) and any other workflows
+//! (see [`Coverage::record_workflow()`]). Only the former tells which rules fired, since that requires the reasoner.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use audit_logger::LogStatement;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use workflow::Workflow;
+
+use crate::simulation::{RecordedQuestion, collect_questions};
+
+/***** CONSTANTS *****/
+/// The maximum number of example workflows listed for every fact that no rule depends on.
+pub const MAX_EXAMPLES: usize = 5;
+
+/// The fields of eFLINT JSON declarations that give the conditions under which something holds, is derived or is violated.
+const CONDITION_FIELDS: [&'static str; 5] = ["expression", "holds-when", "derived-from", "conditioned-by", "violated-when"];
+/// The fields of eFLINT JSON declarations that list the types something is made of.
+const PART_FIELDS: [&'static str; 6] = ["identified-by", "actor", "recipient", "holder", "claimant", "related-to"];
+
+/***** HELPER FUNCTIONS *****/
+/// Finds the type an eFLINT variable ranges over, by stripping the decorations it may have (e.g., `dataset1` or `task'`).
+fn type_of(var: &str) -> &str { var.trim_end_matches(|c: char| c.is_ascii_digit() || c == '\'') }
+
+/// Lists the type names in a field that is either a single name or a list of them (e.g., `identified-by`).
+fn names_in(field: &Value) -> Vec<String> {
+    match field {
+        Value::String(name) => vec![type_of(name).into()],
+        Value::Array(names) => names.iter().filter_map(Value::as_str).map(|name| type_of(name).into()).collect(),
+        _ => vec![],
+    }
+}
+
+/// Collects the types referenced in an eFLINT JSON expression.
+///
+/// # Arguments
+/// - `expr`: The expression to search.
+/// - `refs`: The set to add the names of the referenced types to.
+fn references(expr: &Value, refs: &mut HashSet<String>) {
+    match expr {
+        Value::Array(items) => {
+            // A list with a single name is a variable (e.g., `["task"]`)
+            if let [Value::String(var)] = items.as_slice() {
+                refs.insert(type_of(var).into());
+                return;
+            }
+            for item in items {
+                references(item, refs);
+            }
+        },
+        Value::Object(fields) => {
+            if let Some(Value::String(name)) = fields.get("identifier") {
+                refs.insert(name.clone());
+            }
+            if let Some(Value::String(param)) = fields.get("parameter") {
+                refs.insert(type_of(param).into());
+            }
+            if let Some(binds) = fields.get("binds") {
+                refs.extend(names_in(binds));
+            }
+            for value in fields.values() {
+                references(value, refs);
+            }
+        },
+        _ => {},
+    }
+}
+
+/***** AUXILLARY *****/
+/// The kinds of policy declarations that count as rules.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// A predicate that must always hold.
+    Invariant,
+    /// Something an actor may do.
+    Act,
+    /// Something a holder must do.
+    Duty,
+    /// Something that happens.
+    Event,
+}
+impl Display for RuleKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Invariant => write!(f, "invariant"),
+            Self::Act => write!(f, "act"),
+            Self::Duty => write!(f, "duty"),
+            Self::Event => write!(f, "event"),
+        }
+    }
+}
+
+/// How often a single rule fired.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RuleCoverage {
+    /// The name of the rule.
+    pub name:  String,
+    /// What kind of rule it is.
+    pub kind:  RuleKind,
+    /// The number of reasoner responses in which it was violated or triggered.
+    pub fired: usize,
+}
+
+/// A fact asserted by workflows that no rule depends on.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PatternCoverage {
+    /// The name of the fact (e.g., `node-at`).
+    pub fact: String,
+    /// The number of times it was asserted, over all workflows.
+    pub occurrences: usize,
+    /// The identifiers of (at most [`MAX_EXAMPLES`]) workflows asserting it.
+    pub examples: Vec<String>,
+}
+
+/// Summarizes how well a policy covers a corpus of questions.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CoverageReport {
+    /// The number of (distinct) workflows in the corpus.
+    pub workflows: usize,
+    /// The number of reasoner responses read.
+    pub responses: usize,
+    /// Every rule in the policy, with how often it fired.
+    pub rules: Vec<RuleCoverage>,
+    /// The names of the rules that never fired, or [`None`] if no reasoner responses were read (so it is unknown).
+    pub never_fired: Option<Vec<String>>,
+    /// The facts asserted by workflows that no rule depends on, most asserted first.
+    pub unconstrained: Vec<PatternCoverage>,
+}
+
+/***** LIBRARY *****/
+/// Lists the phrases in an eFLINT JSON policy.
+///
+/// # Arguments
+/// - `policy`: The policy, either as a full request (e.g., as compiled by `eflint-to-json`) or as a bare list of phrases (as stored by the checker).
+///
+/// # Returns
+/// The phrases of the policy, or none if it is neither.
+pub fn policy_phrases(policy: &Value) -> Vec<Value> {
+    match policy {
+        Value::Array(phrases) => phrases.clone(),
+        Value::Object(request) => request.get("phrases").and_then(Value::as_array).cloned().unwrap_or_default(),
+        _ => vec![],
+    }
+}
+
+/// Collects which rules of a policy fire and which facts it depends on, while workflows and reasoner responses are recorded.
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    /// The rules in the policy, in the order they are declared.
+    rules: Vec<(String, RuleKind)>,
+    /// The names of everything the rules depend on.
+    constrained: HashSet<String>,
+    /// The number of responses in which every rule fired, by name.
+    fired: HashMap<String, usize>,
+    /// The number of times every fact was asserted by workflows, with examples of workflows asserting it.
+    patterns: BTreeMap<String, (usize, Vec<String>)>,
+    /// The number of workflows recorded.
+    workflows: usize,
+    /// The number of reasoner responses recorded.
+    responses: usize,
+}
+impl Coverage {
+    /// Constructor for the Coverage that analyses the given policy.
+    ///
+    /// # Arguments
+    /// - `policy`: The eFLINT JSON phrases of the policy (see [`policy_phrases()`]).
+    ///
+    /// # Returns
+    /// A new Coverage without any workflows or responses recorded.
+    pub fn new(policy: &[Value]) -> Self {
+        // Find the rules, and what every declaration depends on
+        let mut rules: Vec<(String, RuleKind)> = Vec::new();
+        let mut dependencies: HashMap<String, HashSet<String>> = HashMap::new();
+        for phrase in policy {
+            let Some(kind) = phrase.get("kind").and_then(Value::as_str) else { continue };
+            // Placeholders are another name for a type
+            if kind == "placeholder" {
+                let parts: Vec<String> = phrase.get("for").map(names_in).unwrap_or_default();
+                for name in phrase.get("name").map(names_in).unwrap_or_default() {
+                    dependencies.entry(name).or_default().extend(parts.iter().cloned());
+                }
+                continue;
+            }
+            let Some(name) = phrase.get("name").and_then(Value::as_str) else { continue };
+
+            // Note that extensions (`Extend ...`) add to the dependencies of what they extend
+            let deps: &mut HashSet<String> = dependencies.entry(name.into()).or_default();
+            for field in CONDITION_FIELDS.iter().filter_map(|field| phrase.get(*field)) {
+                references(field, deps);
+            }
+            for field in PART_FIELDS.iter().filter_map(|field| phrase.get(*field)) {
+                deps.extend(names_in(field));
+            }
+
+            let rule: Option<RuleKind> = match kind {
+                "predicate" if phrase.get("is-invariant").and_then(Value::as_bool).unwrap_or(false) => Some(RuleKind::Invariant),
+                "act" => Some(RuleKind::Act),
+                "duty" => Some(RuleKind::Duty),
+                "event" => Some(RuleKind::Event),
+                _ => None,
+            };
+            if let Some(rule) = rule {
+                rules.push((name.into(), rule));
+            }
+        }
+
+        // Everything the rules depend on, directly or through what it is derived from or made of
+        let mut constrained: HashSet<String> = HashSet::new();
+        let mut todo: Vec<String> = rules.iter().map(|(name, _)| name.clone()).collect();
+        while let Some(name) = todo.pop() {
+            if let Some(deps) = dependencies.get(&name) {
+                todo.extend(deps.iter().filter(|dep| !constrained.contains(*dep)).cloned());
+            }
+            constrained.insert(name);
+        }
+        debug!("Policy has {} rule(s) depending on {} declaration(s)", rules.len(), constrained.len());
+
+        Self { rules, constrained, fired: HashMap::new(), patterns: BTreeMap::new(), workflows: 0, responses: 0 }
+    }
+
+    /// Records the questions in an audit log, together with the reasoner responses to them.
+    ///
+    /// Every distinct workflow asked about is recorded once. Responses to simulated, explained or what-if questions are
+    /// skipped, since they are not about the policy that was active.
+    ///
+    /// # Arguments
+    /// - `statements`: The [`LogStatement`]s read from the audit log, in order.
+    /// - `version`: If given, only records questions answered by this policy version.
+    ///
+    /// # Returns
+    /// The number of questions recorded.
+    pub fn record_log(&mut self, statements: &[LogStatement], version: Option<i64>) -> usize {
+        let questions: Vec<RecordedQuestion> = collect_questions(statements, None, None)
+            .into_iter()
+            .filter(|question| version.map_or(true, |version| question.policy == version))
+            .collect();
+        debug!("Analysing coverage of {} recorded question(s)...", questions.len());
+
+        let mut workflows: HashSet<&str> = HashSet::new();
+        for question in &questions {
+            if workflows.insert(question.workflow.id.as_str()) {
+                self.record_workflow(&question.workflow);
+            }
+        }
+
+        let references: HashSet<&str> = questions.iter().map(|question| question.reference.as_str()).collect();
+        for stmt in statements {
+            let LogStatement::ReasonerResponse { reference, response, .. } = stmt else { continue };
+            if !references.contains(&**reference) {
+                continue;
+            }
+            match serde_json::from_str::<Value>(response) {
+                Ok(response) => self.record_response(&response),
+                Err(err) => debug!("Skipping reasoner response to question '{reference}' that is not JSON: {err}"),
+            }
+        }
+        questions.len()
+    }
+
+    /// Records the facts a workflow asserts when it is asked about.
+    ///
+    /// # Arguments
+    /// - `workflow`: The [`Workflow`] to record.
+    pub fn record_workflow(&mut self, workflow: &Workflow) {
+        self.workflows += 1;
+        let mut seen: HashSet<String> = HashSet::new();
+        for phrase in workflow.to_eflint().iter().filter_map(|phrase| serde_json::to_value(phrase).ok()) {
+            if phrase.get("kind").and_then(Value::as_str) != Some("create") {
+                continue;
+            }
+            let Some(fact) = phrase.get("operand").and_then(|operand| operand.get("identifier")).and_then(Value::as_str) else { continue };
+            let (occurrences, examples) = self.patterns.entry(fact.into()).or_default();
+            *occurrences += 1;
+            if seen.insert(fact.into()) && examples.len() < MAX_EXAMPLES {
+                examples.push(workflow.id.clone());
+            }
+        }
+    }
+
+    /// Records which rules fired in the response of an eFLINT reasoner.
+    ///
+    /// A rule fires if it is triggered by any of the phrases, or if it is violated after the last one (i.e., if it
+    /// contributed to the verdict). Rules are counted once per response.
+    ///
+    /// # Arguments
+    /// - `response`: The raw eFLINT JSON response.
+    pub fn record_response(&mut self, response: &Value) {
+        self.responses += 1;
+        let results: &[Value] = response.get("results").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+        let triggers = results.iter().filter_map(|result| result.get("triggers")).filter_map(Value::as_array).flatten();
+        let violations = results.last().and_then(|result| result.get("violations")).and_then(Value::as_array).into_iter().flatten();
+
+        let mut fired: HashSet<&str> = HashSet::new();
+        for entry in triggers.chain(violations) {
+            // Triggers may wrap the instance that fired
+            let instance: &Value = entry.get("operand").unwrap_or(entry);
+            if let Some(name) = instance.get("identifier").and_then(Value::as_str) {
+                fired.insert(name);
+            }
+        }
+        for name in fired {
+            *self.fired.entry(name.into()).or_default() += 1;
+        }
+    }
+
+    /// Summarizes what has been recorded so far.
+    ///
+    /// # Returns
+    /// A [`CoverageReport`] for the policy and everything recorded.
+    pub fn report(&self) -> CoverageReport {
+        let rules: Vec<RuleCoverage> = self
+            .rules
+            .iter()
+            .map(|(name, kind)| RuleCoverage { name: name.clone(), kind: *kind, fired: self.fired.get(name).copied().unwrap_or(0) })
+            .collect();
+        let never_fired: Option<Vec<String>> =
+            (self.responses > 0).then(|| rules.iter().filter(|rule| rule.fired == 0).map(|rule| rule.name.clone()).collect());
+
+        let mut unconstrained: Vec<PatternCoverage> = self
+            .patterns
+            .iter()
+            .filter(|(fact, _)| !self.constrained.contains(*fact))
+            .map(|(fact, (occurrences, examples))| PatternCoverage { fact: fact.clone(), occurrences: *occurrences, examples: examples.clone() })
+            .collect();
+        unconstrained.sort_by(|lhs, rhs| rhs.occurrences.cmp(&lhs.occurrences));
+
+        CoverageReport { workflows: self.workflows, responses: self.responses, rules, never_fired, unconstrained }
+    }
+}
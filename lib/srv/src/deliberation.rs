@@ -15,34 +15,476 @@
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter, Result as FResult};
 use std::sync::Arc;
+use std::time::Duration;
 
-use audit_logger::{AuditLogger, SessionedConnectorAuditLogger};
+use audit_logger::{
+    ArchivedPayload, AuditLogger, PayloadKind, PayloadStore, ReasonerConnectorAuditLogger, RequestTimer, SessionedConnectorAuditLogger,
+};
 use auth_resolver::{AuthContext, AuthResolver};
 use brane_ast::SymTable;
+use chrono::Utc;
 use deliberation::spec::{
-    AccessDataRequest, DataAccessResponse, DeliberationAllowResponse, DeliberationDenyResponse, DeliberationResponse, ExecuteTaskRequest,
-    TaskExecResponse, Verdict, WorkflowValidationRequest, WorkflowValidationResponse,
+    AccessDataRequest, DeliberationAllowResponse, DeliberationDenyResponse, DeliberationResponse, DenyCategory, EvaluationContext,
+    ExecuteTaskRequest, Explanation, Granularity, NodeOutcome, NodeResult, Verdict, WorkflowValidationRequest,
 };
 use error_trace::ErrorTrace as _;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use policy::{Policy, PolicyDataAccess, PolicyDataError};
-use reasonerconn::ReasonerConnector;
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
 use serde::Serialize;
-use state_resolver::StateResolver;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use state_resolver::counters::{DATA_ACCESSES, TASK_EXECUTIONS};
+use state_resolver::{DatasetOwner, Delegation, State, StateResolver};
 use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::HeaderMap;
 use warp::hyper::StatusCode;
+use warp::hyper::body::Bytes;
 use warp::reject::{Reject, Rejection};
 use warp::reply::{Json, WithStatus};
-use workflow::Workflow;
+use workflow::canonical::{content_hash, relative_id};
+use workflow::diagnostics::{Diagnostic, InlineStats};
+use workflow::lint::{self, Finding, LintConfig, LintLevel};
+use workflow::preprocess::{self, WorkflowLimits};
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+use workflow::{ElemTask, Workflow, compile};
+
+use crate::access_tokens::attach_access_token;
+use crate::counters::{attach_counters, count_usage, task_inputs};
+use crate::duties::{attach_duties, record_duties};
+use crate::fallback::{CIRCUIT_OPEN_PROBLEM, FallbackBehaviour, REASONER_UNAVAILABLE_PROBLEM, VerdictCache};
+use crate::federation::{FEDERATION_HEADER, Forward, federate};
+use crate::i18n::MessageCatalog;
+use crate::lockdown::Lockdown;
+use crate::maintenance::{MaintenanceBehaviour, MaintenanceSchedule};
+use crate::notifications::{notify_owners, owners_involved};
+use crate::postprocess::{Decision, Question};
+use crate::precheck::check_references;
+use crate::prefilter::{Prefilter, PrefilterEffect};
+use crate::problem::{Problem, causes};
+use crate::queue::QueuePermit;
+use crate::signatures::verify_images;
+use crate::simulation::{QuestionKind, RecordedQuestion};
+use crate::workflows::{WorkflowStore, upgrade_wir};
+use crate::{Srv, models};
+
+/***** CONSTANTS *****/
+/// The problem type returned to clients when the audit log cannot confirm their request was recorded.
+pub const AUDIT_LOG_UNAVAILABLE_PROBLEM: &'static str = "/problems/audit-log-unavailable";
+/// The problem type returned to clients when their workflow is refused by a lint rule.
+pub const WORKFLOW_LINT_PROBLEM: &'static str = "/problems/workflow-lint";
+/// The problem type returned to clients when their workflow is too large or complex to check.
+pub const WORKFLOW_LIMITS_PROBLEM: &'static str = "/problems/workflow-limits";
+/// The prefix given to the verdict references of the questions asked to explain a denial, such that they are distinguishable in the audit log.
+pub const EXPLANATION_REFERENCE_PREFIX: &'static str = "explanation-";
+/// The prefix given to the verdict references of the questions asked to judge the nodes of a workflow, such that they are distinguishable in the audit log.
+pub const NODES_REFERENCE_PREFIX: &'static str = "nodes-";
+
+/// How often archived payloads older than the retention period of the [`PayloadStore`] are removed.
+const PAYLOAD_PRUNE_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
-use crate::Srv;
+/***** AUXILLARY *****/
+/// Collects the identifiers of all tasks in a workflow, in order and without duplicates.
+struct TaskIds(Vec<String>);
+impl WorkflowVisitor for TaskIds {
+    fn visit_task(&mut self, task: &ElemTask) {
+        if !self.0.contains(&task.id) {
+            self.0.push(task.id.clone());
+        }
+    }
+}
 
 /***** HELPER FUNCTIONS *****/
+/// Builds the rejection for a request whose statements could not be written to the audit log.
+///
+/// No verdict may be given without it being recorded, so such requests are always rejected.
+///
+/// # Arguments
+/// - `reference`: The verdict reference of the request.
+/// - `err`: The [`audit_logger::Error`] that occurred.
+///
+/// # Returns
+/// A [`Rejection`] carrying a `503 Service Unavailable` problem of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`].
+pub(crate) fn audit_log_unavailable(reference: &str, err: audit_logger::Error) -> Rejection {
+    error!("Rejecting request because the audit log is unavailable: {} | request id: {reference}", err.trace());
+    let p = ProblemDetails::new()
+        .with_type(warp::http::Uri::from_static(AUDIT_LOG_UNAVAILABLE_PROBLEM))
+        .with_title("Audit log unavailable")
+        .with_status(StatusCode::SERVICE_UNAVAILABLE)
+        .with_detail(format!("The request could not be recorded in the audit log, so no verdict is given (verdict reference: {reference})"));
+    warp::reject::custom(Problem::from(p))
+}
+
+/// Lints the workflow of a request before deliberating on it.
+///
+/// Findings of rules at [`LintLevel::Warn`] are only logged; findings of rules at [`LintLevel::Deny`] refuse the workflow.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to lint.
+/// - `config`: The [`LintConfig`] that decides which rules to run.
+/// - `messages`: The [`MessageCatalog`] to translate the findings given to the client with.
+/// - `language`: The language to translate them into, if any.
+/// - `reference`: The verdict reference of the request.
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `400 Bad Request` problem of type [`WORKFLOW_LINT_PROBLEM`] if any rule denies the workflow.
+pub(crate) fn lint_workflow(
+    workflow: &Workflow,
+    config: &LintConfig,
+    messages: &MessageCatalog,
+    language: Option<&str>,
+    reference: &str,
+) -> Result<(), Rejection> {
+    let findings: Vec<Finding> = workflow.lint(config);
+    for finding in findings.iter().filter(|finding| finding.level == LintLevel::Warn) {
+        warn!("Workflow '{}' has lint finding: {finding} | request id: {reference}", workflow.id);
+    }
+    if !lint::denies(&findings) {
+        return Ok(());
+    }
+
+    let denied: Vec<String> =
+        findings.iter().filter(|finding| finding.level == LintLevel::Deny).map(|finding| messages.localize_finding(language, finding)).collect();
+    info!("Refusing workflow '{}' because of {} lint finding(s) | request id: {reference}", workflow.id, denied.len());
+    let p = ProblemDetails::new()
+        .with_type(warp::http::Uri::from_static(WORKFLOW_LINT_PROBLEM))
+        .with_title("Workflow refused by lint rules")
+        .with_status(StatusCode::BAD_REQUEST)
+        .with_detail(denied.join("\n"));
+    Err(warp::reject::custom(Problem::from(p)))
+}
+
+/// Compiles the WIR workflow of a request to the workflow the reasoner checks, refusing it if it is too large or complex.
+///
+/// # Arguments
+/// - `wir`: The [WIR](brane_ast::Workflow) workflow to compile.
+/// - `limits`: The [`WorkflowLimits`] the workflow must stay within.
+///
+/// # Returns
+/// The compiled [`Workflow`] and the [`InlineStats`] of preprocessing it, or why it could not be compiled if the workflow is otherwise
+/// invalid (which handlers report themselves).
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `400 Bad Request` problem of type [`WORKFLOW_LIMITS_PROBLEM`] if the workflow
+/// exceeds the limits.
+pub(crate) fn compile_workflow(
+    wir: brane_ast::Workflow,
+    limits: &WorkflowLimits,
+) -> Result<Result<(Workflow, InlineStats), compile::Error>, Rejection> {
+    match Workflow::from_wir_with_stats(wir, limits) {
+        Err(err) if err.exceeds_limits() => {
+            info!("Refusing workflow because it exceeds the workflow limits: {}", err.trace());
+            let p = ProblemDetails::new()
+                .with_type(warp::http::Uri::from_static(WORKFLOW_LIMITS_PROBLEM))
+                .with_title("Workflow too large or complex")
+                .with_status(StatusCode::BAD_REQUEST)
+                .with_detail(err.trace().to_string());
+            Err(warp::reject::custom(Problem::from(p)))
+        },
+        res => Ok(res),
+    }
+}
+
+/// Reads the body of a deliberation request, first upgrading its WIR workflow to the version we understand.
+///
+/// If the body refers to an uploaded workflow with a `workflow_ref` instead, that workflow is used (see [`WorkflowStore`]).
+///
+/// # Arguments
+/// - `workflows`: The [`WorkflowStore`] with the uploaded workflows.
+///
+/// # Returns
+/// A [`Filter`] that extracts the request, or rejects with a `400 Bad Request` problem if it cannot be read.
+pub(crate) fn with_wir_body<T: 'static + DeserializeOwned + Send>(
+    workflows: Arc<WorkflowStore>,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    with_raw_wir_body(workflows).map(|body: T, _raw: Bytes| body)
+}
+
+/// Reads the body of a deliberation request like [`with_wir_body()`], but also extracts the body as it was received.
+///
+/// # Arguments
+/// - `workflows`: The [`WorkflowStore`] with the uploaded workflows.
+///
+/// # Returns
+/// A [`Filter`] that extracts the request and its original bytes, or rejects with a `400 Bad Request` problem if it
+/// cannot be read.
+pub(crate) fn with_raw_wir_body<T: 'static + DeserializeOwned + Send>(
+    workflows: Arc<WorkflowStore>,
+) -> impl Filter<Extract = (T, Bytes), Error = Rejection> + Clone {
+    warp::body::bytes()
+        .and_then(move |raw: Bytes| {
+            let workflows: Arc<WorkflowStore> = workflows.clone();
+            async move {
+                let mut body: Value = serde_json::from_slice(&raw).map_err(|err| {
+                    let p = ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail(format!("Invalid request body: {err}"));
+                    warp::reject::custom(Problem::from(p))
+                })?;
+                workflows.resolve(&mut body)?;
+                if let Some(wir) = body.get_mut("workflow") {
+                    upgrade_wir(wir)?;
+                }
+                let body: T = serde_json::from_value(body).map_err(|err| {
+                    let p = ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail(format!("Invalid request body: {err}"));
+                    warp::reject::custom(Problem::from(p))
+                })?;
+                Ok::<_, Rejection>((body, raw))
+            }
+        })
+        .untuple_one()
+}
+
+/// Waits until the audit log confirms that everything about a request is persisted.
+///
+/// # Arguments
+/// - `logger`: The [`AuditLogger`] to flush.
+/// - `reference`: The verdict reference of the request about to be answered.
+///
+/// # Errors
+/// This function errors (= rejects the request) with an [`AUDIT_LOG_UNAVAILABLE_PROBLEM`] if the logger cannot confirm it.
+pub(crate) async fn confirm_logged<L: AuditLogger>(logger: &L, reference: &str) -> Result<(), Rejection> {
+    logger.flush(reference).await.map_err(|err| audit_log_unavailable(reference, err))
+}
+
+/// Ends the session in which the reasoner was consulted for a request, which logs its summary.
+///
+/// # Arguments
+/// - `session`: The [`SessionedConnectorAuditLogger`] that was given to the reasoner connector.
+///
+/// # Errors
+/// This function errors with a rejection of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`] if the summary could not be logged.
+pub(crate) async fn end_session<L: ReasonerConnectorAuditLogger>(session: SessionedConnectorAuditLogger<L>) -> Result<(), Rejection> {
+    let reference: String = session.reference.clone();
+    session.end().await.map_err(|err| audit_log_unavailable(&reference, err))
+}
+
+/// Logs how long handling a question took so far, right before its verdict is logged.
+///
+/// # Arguments
+/// - `logger`: The [`AuditLogger`] to log the timings with.
+/// - `reference`: The verdict reference of the question.
+/// - `timer`: The [`RequestTimer`] that timed the question.
+///
+/// # Errors
+/// This function errors with a rejection of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`] if the timings could not be logged.
+pub(crate) async fn log_timings<L: AuditLogger>(logger: &L, reference: &str, timer: &RequestTimer) -> Result<(), Rejection> {
+    logger.log_request_timings(&timer.timings(reference)).await.map_err(|err| audit_log_unavailable(reference, err))
+}
+
+/// Logs how much the workflow of a question grew by preprocessing it, right after the question itself is logged.
+///
+/// # Arguments
+/// - `logger`: The [`AuditLogger`] to log the statistics with.
+/// - `reference`: The verdict reference of the question.
+/// - `stats`: The [`InlineStats`] of preprocessing the question's workflow.
+///
+/// # Errors
+/// This function errors with a rejection of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`] if the statistics could not be logged.
+pub(crate) async fn log_preprocessed<L: AuditLogger>(logger: &L, reference: &str, stats: &InlineStats) -> Result<(), Rejection> {
+    logger.log_workflow_preprocessed(reference, stats).await.map_err(|err| audit_log_unavailable(reference, err))
+}
+
+/// Archives the full body of a request in the [`PayloadStore`] (if any), right after the question itself is logged.
+///
+/// # Arguments
+/// - `payloads`: The [`PayloadStore`] of the server, if payloads are archived.
+/// - `logger`: The [`AuditLogger`] to link the archived body from.
+/// - `reference`: The verdict reference of the question.
+/// - `body`: The body of the request exactly as it was received.
+///
+/// # Errors
+/// This function errors with a rejection of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`] if the archived body could not be
+/// logged. Failing to write the body is only warned about, since the question itself is still logged.
+pub(crate) async fn archive_request<L: AuditLogger>(
+    payloads: Option<&PayloadStore>,
+    logger: &L,
+    reference: &str,
+    body: &[u8],
+) -> Result<(), Rejection> {
+    let Some(payloads) = payloads else { return Ok(()) };
+    let archived: ArchivedPayload = match payloads.store(reference, PayloadKind::Request, None, body).await {
+        Ok(archived) => archived,
+        Err(err) => {
+            warn!("Failed to archive request body in '{}': {err} | request id: {reference}", payloads.dir().display());
+            return Ok(());
+        },
+    };
+    logger.log_payload_archived(&archived).await.map_err(|err| audit_log_unavailable(reference, err))
+}
+
+/// Removes archived payloads older than the retention period of the [`PayloadStore`], every [`PAYLOAD_PRUNE_INTERVAL`].
+///
+/// Runs until the server stops, or returns immediately if payloads are kept forever.
+///
+/// # Arguments
+/// - `payloads`: The [`PayloadStore`] of the server.
+pub(crate) async fn prune_payloads(payloads: Arc<PayloadStore>) {
+    if payloads.retention().is_none() {
+        return;
+    }
+    loop {
+        match payloads.prune().await {
+            Ok(0) => {},
+            Ok(removed) => debug!("Removed the archived payloads of {removed} reference(s) from '{}'", payloads.dir().display()),
+            Err(err) => warn!("Failed to remove old archived payloads from '{}': {err}", payloads.dir().display()),
+        }
+        tokio::time::sleep(PAYLOAD_PRUNE_INTERVAL).await;
+    }
+}
+
+/// Answers a question with the [`Prefilter`] if any of its rules matches it, such that the reasoner need not be asked.
+///
+/// While the [`Lockdown`] is engaged, every question is denied before the prefilter is even consulted. Likewise, while
+/// a window of the [`MaintenanceSchedule`] is open, questions are answered (or refused) as it says, which is logged as
+/// a fallback.
+///
+/// # Arguments
+/// - `lockdown`: The [`Lockdown`] of the server.
+/// - `maintenance`: The [`MaintenanceSchedule`] of the server.
+/// - `prefilter`: The [`Prefilter`] of the server.
+/// - `logger`: The [`AuditLogger`] to log the match with.
+/// - `reference`: The verdict reference of the question.
+/// - `kind`: The kind of question (one of [`reasonerconn::QUESTION_KINDS`]).
+/// - `workflow`: The [`Workflow`] in question.
+/// - `question`: What is asked about it.
+///
+/// # Returns
+/// The answer of the rule that matched, or [`None`] if the reasoner should be asked.
+///
+/// # Errors
+/// This function errors with a rejection of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`] if the match could not be logged, or
+/// of type [`MAINTENANCE_PROBLEM`](crate::maintenance::MAINTENANCE_PROBLEM) if a maintenance window says to retry later.
+pub(crate) async fn prefilter<L: AuditLogger>(
+    lockdown: &Lockdown,
+    maintenance: &MaintenanceSchedule,
+    prefilter: &Prefilter,
+    logger: &L,
+    reference: &str,
+    kind: &str,
+    workflow: &Workflow,
+    question: &QuestionKind,
+) -> Result<Option<ReasonerResponse>, Rejection> {
+    if let Some(denial) = lockdown.denial() {
+        info!("Denying request because of the emergency lockdown; not asking the reasoner | request id: {reference}");
+        return Ok(Some(denial));
+    }
+    if let Some(window) = maintenance.active(Utc::now()) {
+        info!(
+            "Answering request with '{}' during maintenance window '{}'; not asking the reasoner | request id: {reference}",
+            window.behaviour, window.name
+        );
+        let reason: String = format!("Maintenance window '{}' is open until {}", window.name, window.until);
+        logger.log_reasoner_fallback(reference, &format!("maintenance-{}", window.behaviour), &reason).await.map_err(|err| {
+            debug!("Could not log reasoner fallback to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(reference, err)
+        })?;
+        return match window.behaviour {
+            MaintenanceBehaviour::Allow => Ok(Some(ReasonerResponse::new(true, vec![]))),
+            MaintenanceBehaviour::Deny => {
+                Ok(Some(ReasonerResponse::new(false, vec![window.reason]).with_categories(vec![DenyCategory::BackendError])))
+            },
+            MaintenanceBehaviour::RetryLater => Err(window.problem()),
+        };
+    }
+    let Some(rule) = prefilter.check(kind, workflow, question) else { return Ok(None) };
+    let (effect, response): (&str, ReasonerResponse) = match rule.effect {
+        PrefilterEffect::Allow => ("allow", ReasonerResponse::new(true, vec![])),
+        PrefilterEffect::Deny => {
+            ("deny", ReasonerResponse::new(false, rule.reason.iter().cloned().collect()).with_categories(vec![DenyCategory::PermissionDenied]))
+        },
+    };
+    info!("Prefilter rule '{}' answered with {effect}; not asking the reasoner | request id: {reference}", rule.id);
+    logger.log_prefilter_matched(reference, &rule.id, effect).await.map_err(|err| {
+        debug!("Could not log prefilter match to audit log : {:?} | request id: {}", err, reference);
+        audit_log_unavailable(reference, err)
+    })?;
+    Ok(Some(response))
+}
+
+/// Tells the reasoner who asks on behalf of whom, if a request was delegated (see [`AuthContext::actor`]).
+///
+/// # Arguments
+/// - `state`: The [`State`] to add the delegation to.
+/// - `auth_ctx`: The [`AuthContext`] of the request.
+pub(crate) fn attach_delegation(state: &mut State, auth_ctx: &AuthContext) {
+    state.delegation = auth_ctx.actor.as_ref().map(|actor| Delegation { subject: auth_ctx.initiator.clone(), actor: actor.clone() });
+}
+
+/// Tells the reasoner how many reasons for denial to look for, if the client asked for a particular [`Granularity`].
+///
+/// # Arguments
+/// - `state`: The [`State`] to add the granularity to.
+/// - `granularity`: The [`Granularity`] asked for, if any.
+pub(crate) fn attach_granularity(state: &mut State, granularity: Option<Granularity>) {
+    state.exhaustive = granularity.map(|granularity| granularity == Granularity::Exhaustive);
+}
+
+/// Computes the identifier of the state that a question is answered with.
+///
+/// # Arguments
+/// - `state`: The [`State`] to identify.
+///
+/// # Returns
+/// The SHA-256 of the state, as hex.
+pub(crate) fn state_snapshot_id(state: &State) -> String {
+    let raw: Vec<u8> = serde_json::to_vec(state).unwrap_or_default();
+    Sha256::digest(&raw).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Builds the part of a [`Verdict`] that is shared between allowing and denying it.
+///
+/// # Arguments
+/// - `reference`: The verdict reference of the request.
+/// - `warnings`: The [`Diagnostic`]s of the request's workflow, to include in the verdict.
+/// - `evaluated_with`: The [`EvaluationContext`] that the verdict was given with.
+///
+/// # Returns
+/// A new [`DeliberationResponse`].
+pub(crate) fn shared_response(reference: &str, warnings: &[Diagnostic], evaluated_with: &EvaluationContext) -> DeliberationResponse {
+    DeliberationResponse {
+        verdict_reference: reference.into(),
+        warnings: warnings.to_vec(),
+        evaluated_with: evaluated_with.clone(),
+        nodes: vec![],
+        granularity: None,
+    }
+}
+
+/// Turns a (post-processed) [`Decision`] into the [`Verdict`] returned to the client.
+///
+/// # Arguments
+/// - `reference`: The verdict reference of the request.
+/// - `warnings`: The [`Diagnostic`]s of the request's workflow, to include in the verdict.
+/// - `evaluated_with`: The [`EvaluationContext`] that the verdict was given with.
+/// - `decision`: The [`Decision`] to turn into a verdict.
+///
+/// # Returns
+/// A new [`Verdict`].
+pub(crate) fn verdict_of(reference: &str, warnings: &[Diagnostic], evaluated_with: &EvaluationContext, decision: Decision) -> Verdict {
+    let mut shared = shared_response(reference, warnings, evaluated_with);
+    shared.granularity = decision.granularity;
+    if !decision.allow {
+        Verdict::Deny(DeliberationDenyResponse {
+            shared,
+            reasons_for_denial: Some(decision.reasons),
+            categories: decision.categories,
+            explanation: None,
+        })
+    } else {
+        // TODO implement signature
+        Verdict::Allow(DeliberationAllowResponse { shared, signature: "signature".into(), obligations: decision.obligations, access_token: None })
+    }
+}
+
 /// Retrieves the currently active policy, or immediately denies the request if there is no such policy.
 ///
 /// # Arguments
 /// - `logger`: A [`SessionedConnectorAuditLogger`] on which to log the verdict if we deny because no active policy was found.
 /// - `reference`: The UUID that the policy expert can use to recognize that this verdict belongs to a particular request, if any.
+/// - `warnings`: The [`Diagnostic`]s of the request's workflow, to include in the verdict.
+/// - `evaluated_with`: The [`EvaluationContext`] of the request, to include in the verdict.
 /// - `policystore`: The story with [`PolicyDataAccess`] from which we'll try to retrieve the active policy.
 ///
 /// # Errors
@@ -50,6 +492,8 @@ use crate::Srv;
 async fn get_active_policy<L: AuditLogger, P: PolicyDataAccess>(
     logger: &L,
     reference: &str,
+    warnings: &[Diagnostic],
+    evaluated_with: &EvaluationContext,
     policystore: &P,
 ) -> Result<Result<Policy, WithStatus<Json>>, Rejection> {
     // Attempt to get the policy first
@@ -60,19 +504,22 @@ async fn get_active_policy<L: AuditLogger, P: PolicyDataAccess>(
 
             // Create the verdict
             let verdict = Verdict::Deny(DeliberationDenyResponse {
-                shared: DeliberationResponse { verdict_reference: reference.into() },
+                shared: shared_response(reference, warnings, evaluated_with),
                 reasons_for_denial: None,
+                categories: vec![DenyCategory::NoApplicablePolicy],
+                explanation: None,
             });
 
             // Log it: first, the "actual response" with the reason and then the verdict returned to the user
-            logger.log_reasoner_response(reference, "<reasoner not queried because no active policy is present>").await.map_err(|err| {
+            logger.log_reasoner_response(reference, "<reasoner not queried because no active policy is present>", 0).await.map_err(|err| {
                 debug!("Could not log \"reasoner response\" to audit log : {:?} | request id: {}", err, reference);
-                warp::reject::custom(err)
+                audit_log_unavailable(reference, err)
             })?;
             logger.log_verdict(reference, &verdict).await.map_err(|err| {
                 debug!("Could not log verdict to audit log : {:?} | request id: {}", err, reference);
-                warp::reject::custom(err)
+                audit_log_unavailable(reference, err)
             })?;
+            confirm_logged(logger, reference).await?;
 
             // Then send it to the user as promised
             Ok(Err(warp::reply::with_status(warp::reply::json(&verdict), StatusCode::OK)))
@@ -117,33 +564,267 @@ where
     DA: 'static + AuthResolver + Send + Sync,
     C::Context: Send + Sync + Debug + Serialize,
 {
+    /// Passes the answer of the reasoner through the configured [`VerdictPipeline`](crate::postprocess::VerdictPipeline), logging every change made to it.
+    ///
+    /// While the [`Lockdown`] is engaged, the answer is replaced by its denial afterwards, whatever the reasoner said.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the pipeline.
+    /// - `question`: The [`Question`] that the reasoner answered.
+    /// - `response`: The [`ReasonerResponse`] given by the reasoner.
+    ///
+    /// # Returns
+    /// The [`Decision`] to return to the client.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) if we failed to log a change in the audit log.
+    pub(crate) async fn post_process(this: &Self, question: &Question<'_>, response: ReasonerResponse) -> Result<Decision, Rejection> {
+        let mut decision: Decision = Decision::from(response);
+        for alteration in this.verdict_pipeline.apply(question, &mut decision) {
+            info!("Verdict transformer '{}' altered the answer: {} | request id: {}", alteration.transformer, alteration.reason, question.reference);
+            this.logger.log_verdict_transformed(question.reference, &alteration.transformer, &alteration.reason).await.map_err(|err| {
+                debug!("Could not log verdict transformation to audit log : {:?} | request id: {}", err, question.reference);
+                audit_log_unavailable(question.reference, err)
+            })?;
+        }
+        if let Some(denial) = this.lockdown.denial().map(Decision::from).filter(|denial| *denial != decision) {
+            info!("Emergency lockdown replaced the answer with a denial | request id: {}", question.reference);
+            this.logger.log_verdict_transformed(question.reference, "lockdown", "Denied request during emergency lockdown").await.map_err(|err| {
+                debug!("Could not log verdict transformation to audit log : {:?} | request id: {}", err, question.reference);
+                audit_log_unavailable(question.reference, err)
+            })?;
+            decision = denial;
+        }
+        Ok(decision)
+    }
+
+    /// Explains a denial with a minimal set of facts and policy phrases causing it, if the reasoner connector is able to.
+    ///
+    /// Failing to explain does not affect the verdict; it is only logged.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the reasoner connector to ask.
+    /// - `policy`: The [`Policy`] that gave the verdict, or [`None`] if the client did not ask for an explanation.
+    /// - `question`: The [`RecordedQuestion`] that the verdict was given to.
+    /// - `verdict`: The [`Verdict`] to add the explanation to. Left as-is if it is not a denial.
+    pub(crate) async fn explain_denial(this: &Self, policy: Option<Policy>, question: &RecordedQuestion, verdict: &mut Verdict) {
+        let (Some(policy), Verdict::Deny(deny)) = (policy, verdict) else { return };
+        debug!("Explaining denial... | request id: {}", question.reference);
+        let session = SessionedConnectorAuditLogger::new(format!("{EXPLANATION_REFERENCE_PREFIX}{}", question.reference), this.logger.clone())
+            .with_payload_store(this.payloads.clone());
+        let res: Result<Explanation, ReasonerConnError> =
+            this.reasonerconn.explain(session.clone(), policy, question.state.clone(), question.workflow.clone(), question.kind.clone()).await;
+        if let Err(err) = session.end().await {
+            warn!("Failed to log the session summary of explaining a denial: {err} | request id: {}", question.reference);
+        }
+        match res {
+            Ok(explanation) => deny.explanation = Some(explanation),
+            Err(err @ ReasonerConnError::Invalid { .. }) => debug!("Not explaining denial: {err} | request id: {}", question.reference),
+            Err(err) => warn!("Failed to explain denial: {err} | request id: {}", question.reference),
+        }
+    }
+
+    /// Annotates a workflow validation verdict with the outcome for every task of the workflow.
+    ///
+    /// The reasoner connector is asked to judge all tasks at once; if it cannot, it is asked about every task separately.
+    /// Failing to judge them does not affect the verdict; it is only logged, and the verdict is left without any.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the reasoner connector to ask.
+    /// - `policy`: The [`Policy`] that gave the verdict, or [`None`] if the client did not ask for node results.
+    /// - `question`: The [`RecordedQuestion`] that the verdict was given to.
+    /// - `verdict`: The [`Verdict`] to add the node results to.
+    pub(crate) async fn annotate_nodes(this: &Self, policy: Option<Policy>, question: &RecordedQuestion, verdict: &mut Verdict) {
+        let Some(policy) = policy else { return };
+        debug!("Judging the nodes of the workflow... | request id: {}", question.reference);
+        let session = SessionedConnectorAuditLogger::new(format!("{NODES_REFERENCE_PREFIX}{}", question.reference), this.logger.clone())
+            .with_payload_store(this.payloads.clone());
+        let res: Result<Vec<NodeResult>, ReasonerConnError> =
+            match this.reasonerconn.node_results(session.clone(), policy.clone(), question.state.clone(), question.workflow.clone()).await {
+                Err(err @ ReasonerConnError::Invalid { .. }) => {
+                    debug!("Asking about every task separately: {err} | request id: {}", question.reference);
+                    let mut tasks = TaskIds(vec![]);
+                    walk_workflow_preorder(&question.workflow.start, &mut tasks);
+                    let mut nodes: Vec<NodeResult> = Vec::with_capacity(tasks.0.len());
+                    for task in tasks.0 {
+                        match this
+                            .reasonerconn
+                            .execute_task(session.clone(), policy.clone(), question.state.clone(), question.workflow.clone(), task.clone())
+                            .await
+                        {
+                            Ok(res) => nodes.push(NodeResult {
+                                node:    task,
+                                outcome: if res.success { NodeOutcome::Allowed } else { NodeOutcome::Denied },
+                                reasons: res.errors,
+                            }),
+                            Err(err) => {
+                                nodes.clear();
+                                warn!("Failed to judge task '{task}' of the workflow: {err} | request id: {}", question.reference);
+                                break;
+                            },
+                        }
+                    }
+                    Ok(nodes)
+                },
+                res => res,
+            };
+        if let Err(err) = session.end().await {
+            warn!("Failed to log the session summary of judging the nodes of a workflow: {err} | request id: {}", question.reference);
+        }
+        match (res, verdict) {
+            (Ok(nodes), Verdict::Allow(allow)) => allow.shared.nodes = nodes,
+            (Ok(nodes), Verdict::Deny(deny)) => deny.shared.nodes = nodes,
+            (Err(err), _) => warn!("Failed to judge the nodes of the workflow: {err} | request id: {}", question.reference),
+        }
+    }
+
+    /// Answers a request for which the reasoner connector failed, according to the configured [`FallbackBehaviour`].
+    ///
+    /// # Arguments
+    /// - `this`: The server with the fallback configuration and verdict cache.
+    /// - `reference`: The verdict reference of the request.
+    /// - `warnings`: The [`Diagnostic`]s of the request's workflow, to include in the verdict.
+    /// - `evaluated_with`: The [`EvaluationContext`] of the request, to include in the verdict.
+    /// - `cache_key`: The key of the question in the verdict cache, if any.
+    /// - `err`: The [`ReasonerConnError`] that caused us to fall back.
+    ///
+    /// # Returns
+    /// The reply to send to the client.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) if we deny it, or if we failed to log the fallback in the audit log.
+    async fn handle_reasoner_failure(
+        this: &Self,
+        reference: &str,
+        warnings: &[Diagnostic],
+        evaluated_with: &EvaluationContext,
+        language: Option<&str>,
+        cache_key: Option<&str>,
+        err: ReasonerConnError,
+    ) -> Result<WithStatus<Json>, Rejection> {
+        let mut reason: String = causes(&err).join(": ");
+        error!("Failed to consult reasoner: {reason} | request id: {reference}");
+
+        // Decide what to answer (never anything but a denial while in lockdown)
+        let fallback: FallbackBehaviour = if this.lockdown.is_engaged() { FallbackBehaviour::Deny } else { this.fallback };
+        let cached: Option<(bool, Vec<String>, Vec<DenyCategory>)> = match fallback {
+            FallbackBehaviour::Cache => cache_key.and_then(|key| this.verdict_cache.get(key)),
+            _ => None,
+        };
+        let shared = shared_response(reference, warnings, evaluated_with);
+        let (behaviour, verdict): (FallbackBehaviour, Verdict) = match (fallback, cached) {
+            (FallbackBehaviour::Cache, Some((success, errors, categories))) => {
+                debug!("Answering request from verdict cache | request id: {reference}");
+                let verdict = if success {
+                    Verdict::Allow(DeliberationAllowResponse { shared, signature: "signature".into(), obligations: vec![], access_token: None })
+                } else {
+                    Verdict::Deny(DeliberationDenyResponse { shared, reasons_for_denial: Some(errors), categories, explanation: None })
+                };
+                (FallbackBehaviour::Cache, verdict)
+            },
+            (FallbackBehaviour::AllowWithWarning, _) => {
+                warn!("Allowing request even though the reasoner could not be consulted | request id: {reference}");
+                (
+                    FallbackBehaviour::AllowWithWarning,
+                    Verdict::Allow(DeliberationAllowResponse { shared, signature: "signature".into(), obligations: vec![], access_token: None }),
+                )
+            },
+            (fallback, _) => {
+                if fallback == FallbackBehaviour::Cache {
+                    reason.push_str(" (no cached verdict available)");
+                }
+                (
+                    FallbackBehaviour::Deny,
+                    Verdict::Deny(DeliberationDenyResponse {
+                        shared,
+                        reasons_for_denial: None,
+                        categories: vec![DenyCategory::BackendError],
+                        explanation: None,
+                    }),
+                )
+            },
+        };
+
+        // Log what we did, telling an open circuit breaker apart from a backend that failed just now
+        let logged = match &err {
+            ReasonerConnError::CircuitOpen { retry_after, .. } => {
+                this.logger.log_circuit_open(reference, &behaviour.to_string(), retry_after.as_millis() as u64, &reason).await
+            },
+            _ => this.logger.log_reasoner_fallback(reference, &behaviour.to_string(), &reason).await,
+        };
+        logged.map_err(|err| {
+            debug!("Could not log reasoner fallback to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(reference, err)
+        })?;
+        this.logger.log_verdict(reference, &verdict).await.map_err(|err| {
+            debug!("Could not log verdict to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(reference, err)
+        })?;
+        confirm_logged(&this.logger, reference).await?;
+
+        // Deny with a problem telling the client why, or else send the verdict as usual
+        if behaviour == FallbackBehaviour::Deny {
+            if let ReasonerConnError::CircuitOpen { retry_after, .. } = &err {
+                let p = ProblemDetails::new()
+                    .with_type(warp::http::Uri::from_static(CIRCUIT_OPEN_PROBLEM))
+                    .with_title("Reasoner circuit open")
+                    .with_status(StatusCode::SERVICE_UNAVAILABLE)
+                    .with_detail(format!(
+                        "The reasoner is not consulted while it keeps failing, so the request is denied (verdict reference: {reference})"
+                    ));
+                let mut problem = Problem::from(p).with_causes(&err);
+                // Rounded up, such that clients asking again right on time find the breaker half-open
+                let retry_after: u64 = retry_after.as_millis().div_ceil(1000).max(1) as u64;
+                problem.0.extensions.insert("retry_after".into(), Value::from(retry_after));
+                return Err(warp::reject::custom(problem));
+            }
+            let p = ProblemDetails::new()
+                .with_type(warp::http::Uri::from_static(REASONER_UNAVAILABLE_PROBLEM))
+                .with_title("Reasoner unavailable")
+                .with_status(StatusCode::SERVICE_UNAVAILABLE)
+                .with_detail(format!("The reasoner could not be consulted, so the request is denied by default (verdict reference: {reference})"));
+            return Err(warp::reject::custom(Problem::from(p).with_causes(&err)));
+        }
+        Ok(warp::reply::with_status(warp::reply::json(&this.messages.localize_verdict(language, verdict)), StatusCode::OK))
+    }
+
     // POST /v1/deliberation/execute-task
     async fn handle_execute_task_request(
         auth_ctx: AuthContext,
         this: Arc<Self>,
         body: ExecuteTaskRequest,
+        raw: Bytes,
+        accept_language: Option<String>,
+        federated_by: Option<String>,
     ) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::reject::Rejection> {
         info!("Handling exec-task request");
-        let ExecuteTaskRequest { use_case, workflow, task_id } = body;
-        let verdict_reference: String = uuid::Uuid::new_v4().into();
+        let mut timer = RequestTimer::new();
+        let forwardable: Option<Value> = this.federation.forwardable(&body, federated_by.as_deref());
+        let ExecuteTaskRequest { use_case, workflow, task_id, call_path, priority, explain, granularity } = body;
+        let language: Option<String> = this.messages.negotiate(accept_language.as_deref()).map(String::from);
+        let verdict_reference: String = this.ids.generate();
 
-        // First, resolve the task ID in the workflow to the ProgramCounter ID needed for `task_id` below (and before we pass it by ownership to be converted)
+        // First, resolve the task ID in the workflow to the structural ID needed for `task_id` below (before we pass it by ownership to be converted)
         debug!("Compiling WIR workflow to Checker Workflow...");
-        let task_pc: String = task_id.resolved(&workflow.table).to_string();
+        let task_pc: String = preprocess::structural_id(&workflow.table, call_path.iter().chain([&task_id]));
 
         // Read the body's workflow as a Checker Workflow
-        let workflow: Workflow = match Workflow::try_from(workflow) {
-            Ok(workflow) => workflow,
+        let (mut workflow, stats): (Workflow, InlineStats) = match compile_workflow(workflow, &this.workflow_limits)? {
+            Ok(res) => res,
             Err(err) => {
                 return Ok(warp::reply::with_status(warp::reply::json(&err.to_string()), warp::hyper::StatusCode::BAD_REQUEST));
             },
         };
+        // Tell the client about anything missing from the workflow that makes its check weaker
+        let warnings: Vec<Diagnostic> = workflow.diagnostics();
+        // Refuse workflows with obvious mistakes before asking anything about them
+        lint_workflow(&workflow, &this.lint, &this.messages, language.as_deref(), &verdict_reference)?;
         // Get the task ID based on the request's target ID
         let task_id = format!("{}-{}-task", workflow.id, task_pc);
         debug!("Considering task '{}' in workflow '{}'", task_id, workflow.id);
 
         debug!("Retrieving state...");
-        let state = match this.stateresolver.get_state(use_case).await {
+        let mut state = match timer.time_state_resolution(this.stateresolver.get_state(use_case.clone())).await {
             Ok(state) => state,
             Err(err) => {
                 error!("Could not retrieve state: {err} | request id: {verdict_reference}");
@@ -157,55 +838,163 @@ where
             state.locations.len(),
             state.users.len()
         );
+        // Refuse workflows that refer to datasets, locations or users the state doesn't know about
+        check_references(&workflow, &state, this.state_check, &verdict_reference)?;
+        // Tell the reasoner which code runs in every task
+        let unknown_images: usize = workflow.resolve_images(&state.images);
+        if unknown_images > 0 {
+            debug!("Image of {unknown_images} task(s) in workflow '{}' is unknown", workflow.id);
+        }
+        verify_images(this.image_verifier.as_deref(), &mut workflow, &verdict_reference);
+        // Tell the reasoner how much has been used so far
+        attach_counters(this.usage_counters.as_deref(), &mut state, &verdict_reference).await?;
+        // Tell the reasoner which duties the user asking has yet to fulfil
+        attach_duties(this.duties.as_deref(), &mut state, &auth_ctx.initiator, &verdict_reference).await?;
+        // Tell the reasoner who sent the request, if not the user themselves
+        attach_delegation(&mut state, &auth_ctx);
+        attach_granularity(&mut state, granularity);
+        // Tell the client what the verdict is evaluated with
+        let mut evaluated_with =
+            EvaluationContext { policy_version: None, connector_context: C::hash(), state_snapshot: Some(state_snapshot_id(&state)) };
 
-        let verdict_reference: String = uuid::Uuid::new_v4().into();
+        let verdict_reference: String = this.ids.generate();
         debug!("Generated verdict_reference: {}", verdict_reference);
 
         debug!("Retrieving active policy...");
-        let policy: Policy = match get_active_policy(&this.logger, &verdict_reference, &this.policystore).await? {
+        let policy: Policy = match get_active_policy(&this.logger, &verdict_reference, &warnings, &evaluated_with, &this.policystore).await? {
             Ok(policy) => policy,
             Err(err) => return Ok(err),
         };
         // let policy = this.policystore.get_active().await.unwrap();
         debug!("Got policy with {} bodies", policy.content.len());
+        evaluated_with.policy_version = policy.version.version;
 
         this.logger
-            .log_exec_task_request(&verdict_reference, &auth_ctx, policy.version.version.unwrap(), &state, &workflow, &task_id)
+            .log_exec_task_request(
+                &verdict_reference,
+                &auth_ctx,
+                policy.version.version.unwrap(),
+                &policy.content_hashes(),
+                &state,
+                &workflow,
+                &task_id,
+            )
             .await
             .map_err(|err| {
                 debug!("Could not log exec task request to audit log : {:?} | request id: {}", err, verdict_reference);
-                warp::reject::custom(err)
+                audit_log_unavailable(&verdict_reference, err)
             })?;
+        log_preprocessed(&this.logger, &verdict_reference, &stats).await?;
+        archive_request(this.payloads.as_deref(), &this.logger, &verdict_reference, &raw).await?;
+
+        // Answer obvious questions without bothering the reasoner
+        let prefiltered: Option<ReasonerResponse> = prefilter(
+            &this.lockdown,
+            &this.maintenance,
+            &this.prefilter,
+            &this.logger,
+            &verdict_reference,
+            "execute-task",
+            &workflow,
+            &QuestionKind::ExecuteTask { task: task_id.clone() },
+        )
+        .await?;
+        // Ask partner checkers about their datasets too (once our reasoner allowed it)
+        let forward: Option<Forward> = this.federation.involved(forwardable, &workflow, &QuestionKind::ExecuteTask { task: task_id.clone() });
+
+        let cache_key: Option<String> = match this.fallback {
+            FallbackBehaviour::Cache => VerdictCache::key(&("execute-task", policy.version.version, &state, content_hash(&workflow), &task_pc)),
+            _ => None,
+        };
+
+        // Wait for our turn at the reasoner (unless it need not be asked); the permit is handed to the next question once this request is done
+        let _permit: Option<QueuePermit> = match prefiltered {
+            Some(_) => None,
+            None => match timer.time_queue(this.question_queue.enter(priority)).await {
+                Ok(permit) => Some(permit),
+                Err(err) => {
+                    log_timings(&this.logger, &verdict_reference, &timer).await?;
+                    return Self::handle_reasoner_failure(
+                        &this,
+                        &verdict_reference,
+                        &warnings,
+                        &evaluated_with,
+                        language.as_deref(),
+                        cache_key.as_deref(),
+                        ReasonerConnError::from(err),
+                    )
+                    .await;
+                },
+            },
+        };
 
         debug!("Consulting reasoner connector...");
+        let workflow_id: String = workflow.id.clone();
+        let usage: Vec<String> = std::iter::once(auth_ctx.initiator.clone()).chain(task_inputs(&workflow, &task_id)).collect();
+        let owners: Vec<DatasetOwner> = owners_involved(this.notifier.as_ref(), &state, &workflow, None);
+        let recorded = RecordedQuestion {
+            reference: verdict_reference.clone(),
+            policy:    policy.version.version.unwrap_or(-1),
+            kind:      QuestionKind::ExecuteTask { task: task_id.clone() },
+            state:     state.clone(),
+            workflow:  workflow.clone(),
+            original:  None,
+        };
+        let explain_policy: Option<Policy> = explain.then(|| policy.clone());
 
-        match this
-            .reasonerconn
-            .execute_task(SessionedConnectorAuditLogger::new(verdict_reference.clone(), this.logger.clone()), policy, state, workflow, task_id)
-            .await
-        {
+        let res: Result<ReasonerResponse, ReasonerConnError> = match prefiltered {
+            Some(response) => Ok(response),
+            None => {
+                let session =
+                    SessionedConnectorAuditLogger::new(verdict_reference.clone(), this.logger.clone()).with_payload_store(this.payloads.clone());
+                let res: Result<ReasonerResponse, ReasonerConnError> =
+                    timer.time_reasoner(this.reasonerconn.execute_task(session.clone(), policy, state, workflow, task_id)).await;
+                end_session(session).await?;
+                res
+            },
+        };
+        match res {
             Ok(v) => {
-                let resp: Verdict = if !v.success {
-                    Verdict::Deny(DeliberationDenyResponse {
-                        shared: TaskExecResponse { verdict_reference: verdict_reference.clone() },
-                        reasons_for_denial: Some(v.errors),
-                    })
-                } else {
-                    Verdict::Allow(DeliberationAllowResponse {
-                        shared:    TaskExecResponse { verdict_reference: verdict_reference.clone() },
-                        // TODO implement signature
-                        signature: "signature".into(),
-                    })
+                if let Some(key) = cache_key {
+                    this.verdict_cache.insert(key, v.success, v.errors.clone(), v.categories.clone());
+                }
+                let v: ReasonerResponse = federate(&this.federation, &this.logger, &verdict_reference, "execute-task", forward, v).await?;
+                let question = Question {
+                    kind:      "execute-task",
+                    reference: &verdict_reference,
+                    initiator: &auth_ctx.initiator,
+                    use_case:  &use_case,
+                    workflow:  &workflow_id,
                 };
+                let decision: Decision = Self::post_process(&this, &question, v).await?;
+                let allowed: bool = decision.allow;
+                let mut resp: Verdict = verdict_of(&verdict_reference, &warnings, &evaluated_with, decision);
+                Self::explain_denial(&this, explain_policy, &recorded, &mut resp).await;
+                log_timings(&this.logger, &verdict_reference, &timer).await?;
 
                 this.logger.log_verdict(&verdict_reference, &resp).await.map_err(|err| {
                     debug!("Could not log execute task verdict to audit log : {:?} | request id: {}", err, verdict_reference);
-                    warp::reject::custom(err)
+                    audit_log_unavailable(&verdict_reference, err)
                 })?;
 
-                Ok(warp::reply::with_status(warp::reply::json(&resp), warp::hyper::StatusCode::OK))
+                confirm_logged(&this.logger, &verdict_reference).await?;
+                record_duties(&*this.ids, this.duties.as_deref(), &resp, &auth_ctx.initiator, &verdict_reference).await;
+                this.recent_questions.remember(&auth_ctx.initiator, recorded, &resp);
+                if allowed {
+                    count_usage(this.usage_counters.as_deref(), TASK_EXECUTIONS, &usage, &verdict_reference).await;
+                }
+                notify_owners(this.notifier.as_ref(), &this.logger, &question, &resp, owners);
+
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&this.messages.localize_verdict(language.as_deref(), resp)),
+                    warp::hyper::StatusCode::OK,
+                ))
+            },
+            Err(err) => {
+                log_timings(&this.logger, &verdict_reference, &timer).await?;
+                Self::handle_reasoner_failure(&this, &verdict_reference, &warnings, &evaluated_with, language.as_deref(), cache_key.as_deref(), err)
+                    .await
             },
-            Err(err) => Ok(warp::reply::with_status(warp::reply::json(&format!("{}", err)), warp::hyper::StatusCode::OK)),
         }
     }
 
@@ -214,26 +1003,36 @@ where
         auth_ctx: AuthContext,
         this: Arc<Self>,
         body: AccessDataRequest,
+        raw: Bytes,
+        accept_language: Option<String>,
+        federated_by: Option<String>,
     ) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::reject::Rejection> {
         info!("Handling access-data request");
-        let AccessDataRequest { use_case, workflow, data_id, task_id } = body;
+        let mut timer = RequestTimer::new();
+        let forwardable: Option<Value> = this.federation.forwardable(&body, federated_by.as_deref());
+        let AccessDataRequest { use_case, workflow, data_id, task_id, call_path, priority, explain, granularity } = body;
+        let language: Option<String> = this.messages.negotiate(accept_language.as_deref()).map(String::from);
 
-        let verdict_reference: String = uuid::Uuid::new_v4().into();
+        let verdict_reference: String = this.ids.generate();
 
         debug!("Compiling WIR workflow to Checker Workflow...");
 
         // Read the body's workflow as a Checker Workflow
         // NOTE: We need the deep clone of the table here to ensure that the `Arc` in the WIR is not duplicated. Nice design, Tim!
         let table: SymTable = (*workflow.table).clone();
-        let workflow: Workflow = match Workflow::try_from(workflow) {
-            Ok(workflow) => workflow,
+        let (mut workflow, stats): (Workflow, InlineStats) = match compile_workflow(workflow, &this.workflow_limits)? {
+            Ok(res) => res,
             Err(err) => {
                 return Ok(warp::reply::with_status(warp::reply::json(&err.trace().to_string()), warp::hyper::StatusCode::BAD_REQUEST));
             },
         };
+        // Tell the client about anything missing from the workflow that makes its check weaker
+        let warnings: Vec<Diagnostic> = workflow.diagnostics();
+        // Refuse workflows with obvious mistakes before asking anything about them
+        lint_workflow(&workflow, &this.lint, &this.messages, language.as_deref(), &verdict_reference)?;
 
         debug!("Retrieving state...");
-        let state = match this.stateresolver.get_state(use_case).await {
+        let mut state = match timer.time_state_resolution(this.stateresolver.get_state(use_case.clone())).await {
             Ok(state) => state,
             Err(err) => {
                 error!("Could not retrieve state: {err} | request id: {verdict_reference}");
@@ -247,18 +1046,37 @@ where
             state.locations.len(),
             state.users.len()
         );
+        // Refuse workflows that refer to datasets, locations or users the state doesn't know about
+        check_references(&workflow, &state, this.state_check, &verdict_reference)?;
+        // Tell the reasoner which code runs in every task
+        let unknown_images: usize = workflow.resolve_images(&state.images);
+        if unknown_images > 0 {
+            debug!("Image of {unknown_images} task(s) in workflow '{}' is unknown", workflow.id);
+        }
+        verify_images(this.image_verifier.as_deref(), &mut workflow, &verdict_reference);
+        // Tell the reasoner how much has been used so far
+        attach_counters(this.usage_counters.as_deref(), &mut state, &verdict_reference).await?;
+        // Tell the reasoner which duties the user asking has yet to fulfil
+        attach_duties(this.duties.as_deref(), &mut state, &auth_ctx.initiator, &verdict_reference).await?;
+        // Tell the reasoner who sent the request, if not the user themselves
+        attach_delegation(&mut state, &auth_ctx);
+        attach_granularity(&mut state, granularity);
+        // Tell the client what the verdict is evaluated with
+        let mut evaluated_with =
+            EvaluationContext { policy_version: None, connector_context: C::hash(), state_snapshot: Some(state_snapshot_id(&state)) };
 
         debug!("Retrieving active policy...");
-        let policy = match get_active_policy(&this.logger, &verdict_reference, &this.policystore).await? {
+        let policy = match get_active_policy(&this.logger, &verdict_reference, &warnings, &evaluated_with, &this.policystore).await? {
             Ok(policy) => policy,
             Err(err) => return Ok(err),
         };
         debug!("Got policy with {} bodies", policy.content.len());
+        evaluated_with.policy_version = policy.version.version;
 
         let task_id: Option<String> = match task_id {
             Some(task_id) => {
-                // First, resolve the task ID in the workflow to the ProgramCounter ID needed for `task_id` below (and before we pass it by ownership to be converted)
-                let task_pc: String = task_id.resolved(&table).to_string();
+                // First, resolve the task ID in the workflow to the structural ID needed for `task_id` below
+                let task_pc: String = preprocess::structural_id(&table, call_path.iter().chain([&task_id]));
 
                 // Get the task ID based on the request's target ID
                 let task_id = format!("{}-{}-task", workflow.id, task_pc);
@@ -273,71 +1091,168 @@ where
             Ok(p) => p,
             Err(_) => {
                 let resp = Verdict::Deny(DeliberationDenyResponse {
-                    shared: DataAccessResponse { verdict_reference: verdict_reference.clone() },
+                    shared: shared_response(&verdict_reference, &warnings, &evaluated_with),
                     reasons_for_denial: vec![].into(),
+                    categories: vec![DenyCategory::NoApplicablePolicy],
+                    explanation: None,
                 });
 
-                this.logger.log_data_access_request(&verdict_reference, &auth_ctx, -1, &state, &workflow, &data_id, &task_id).await.map_err(
+                this.logger.log_data_access_request(&verdict_reference, &auth_ctx, -1, &[], &state, &workflow, &data_id, &task_id).await.map_err(
                     |err| {
                         debug!("Could not log data access request to audit log : {:?} | request id: {}", err, verdict_reference);
-                        warp::reject::custom(err)
+                        audit_log_unavailable(&verdict_reference, err)
                     },
                 )?;
+                log_preprocessed(&this.logger, &verdict_reference, &stats).await?;
+                archive_request(this.payloads.as_deref(), &this.logger, &verdict_reference, &raw).await?;
 
                 this.logger.log_verdict(&verdict_reference, &resp).await.map_err(|err| {
                     debug!("Could not log data access verdict to audit log : {:?} | request id: {}", err, verdict_reference);
-                    warp::reject::custom(err)
+                    audit_log_unavailable(&verdict_reference, err)
                 })?;
 
+                confirm_logged(&this.logger, &verdict_reference).await?;
+
                 return Ok(warp::reply::with_status(warp::reply::json(&resp), warp::hyper::StatusCode::OK));
             },
         };
         debug!("Got policy with {} bodies", policy.content.len());
+        evaluated_with.policy_version = policy.version.version;
 
         this.logger
-            .log_data_access_request(&verdict_reference, &auth_ctx, policy.version.version.unwrap(), &state, &workflow, &data_id, &task_id)
+            .log_data_access_request(
+                &verdict_reference,
+                &auth_ctx,
+                policy.version.version.unwrap(),
+                &policy.content_hashes(),
+                &state,
+                &workflow,
+                &data_id,
+                &task_id,
+            )
             .await
             .map_err(|err| {
                 debug!("Could not log data access request to audit log : {:?} | request id: {}", err, verdict_reference);
-                warp::reject::custom(err)
+                audit_log_unavailable(&verdict_reference, err)
             })?;
+        log_preprocessed(&this.logger, &verdict_reference, &stats).await?;
+        archive_request(this.payloads.as_deref(), &this.logger, &verdict_reference, &raw).await?;
+
+        // Answer obvious questions without bothering the reasoner
+        let prefiltered: Option<ReasonerResponse> = prefilter(
+            &this.lockdown,
+            &this.maintenance,
+            &this.prefilter,
+            &this.logger,
+            &verdict_reference,
+            "access-data",
+            &workflow,
+            &QuestionKind::AccessData { data: data_id.clone(), task: task_id.clone() },
+        )
+        .await?;
+        // Ask partner checkers about their datasets too (once our reasoner allowed it)
+        let forward: Option<Forward> =
+            this.federation.involved(forwardable, &workflow, &QuestionKind::AccessData { data: data_id.clone(), task: task_id.clone() });
+
+        let cache_key: Option<String> = match this.fallback {
+            FallbackBehaviour::Cache => {
+                let task_id: Option<String> = task_id.as_ref().map(|task_id| relative_id(&workflow.id, task_id));
+                VerdictCache::key(&("access-data", policy.version.version, &state, content_hash(&workflow), &data_id, task_id))
+            },
+            _ => None,
+        };
+
+        // Wait for our turn at the reasoner (unless it need not be asked); the permit is handed to the next question once this request is done
+        let _permit: Option<QueuePermit> = match prefiltered {
+            Some(_) => None,
+            None => match timer.time_queue(this.question_queue.enter(priority)).await {
+                Ok(permit) => Some(permit),
+                Err(err) => {
+                    log_timings(&this.logger, &verdict_reference, &timer).await?;
+                    return Self::handle_reasoner_failure(
+                        &this,
+                        &verdict_reference,
+                        &warnings,
+                        &evaluated_with,
+                        language.as_deref(),
+                        cache_key.as_deref(),
+                        ReasonerConnError::from(err),
+                    )
+                    .await;
+                },
+            },
+        };
 
         debug!("Consulting reasoner connector...");
+        let workflow_id: String = workflow.id.clone();
+        let usage: Vec<String> = vec![auth_ctx.initiator.clone(), data_id.clone()];
+        let (dataset, task): (String, Option<String>) = (data_id.clone(), task_id.clone());
+        let owners: Vec<DatasetOwner> = owners_involved(this.notifier.as_ref(), &state, &workflow, Some(data_id.as_str()));
+        let recorded = RecordedQuestion {
+            reference: verdict_reference.clone(),
+            policy:    policy.version.version.unwrap_or(-1),
+            kind:      QuestionKind::AccessData { data: data_id.clone(), task: task_id.clone() },
+            state:     state.clone(),
+            workflow:  workflow.clone(),
+            original:  None,
+        };
+        let explain_policy: Option<Policy> = explain.then(|| policy.clone());
 
-        match this
-            .reasonerconn
-            .access_data_request(
-                SessionedConnectorAuditLogger::new(verdict_reference.clone(), this.logger.clone()),
-                policy,
-                state,
-                workflow,
-                data_id,
-                task_id,
-            )
-            .await
-        {
+        let res: Result<ReasonerResponse, ReasonerConnError> = match prefiltered {
+            Some(response) => Ok(response),
+            None => {
+                let session =
+                    SessionedConnectorAuditLogger::new(verdict_reference.clone(), this.logger.clone()).with_payload_store(this.payloads.clone());
+                let res: Result<ReasonerResponse, ReasonerConnError> =
+                    timer.time_reasoner(this.reasonerconn.access_data_request(session.clone(), policy, state, workflow, data_id, task_id)).await;
+                end_session(session).await?;
+                res
+            },
+        };
+        match res {
             Ok(v) => {
-                let resp: Verdict = if !v.success {
-                    Verdict::Deny(DeliberationDenyResponse {
-                        shared: DataAccessResponse { verdict_reference: verdict_reference.clone() },
-                        reasons_for_denial: Some(v.errors),
-                    })
-                } else {
-                    Verdict::Allow(DeliberationAllowResponse {
-                        shared:    DataAccessResponse { verdict_reference: verdict_reference.clone() },
-                        // TODO implement signature
-                        signature: "signature".into(),
-                    })
+                if let Some(key) = cache_key {
+                    this.verdict_cache.insert(key, v.success, v.errors.clone(), v.categories.clone());
+                }
+                let v: ReasonerResponse = federate(&this.federation, &this.logger, &verdict_reference, "access-data", forward, v).await?;
+                let question = Question {
+                    kind:      "access-data",
+                    reference: &verdict_reference,
+                    initiator: &auth_ctx.initiator,
+                    use_case:  &use_case,
+                    workflow:  &workflow_id,
                 };
+                let decision: Decision = Self::post_process(&this, &question, v).await?;
+                let allowed: bool = decision.allow;
+                let mut resp: Verdict = verdict_of(&verdict_reference, &warnings, &evaluated_with, decision);
+                Self::explain_denial(&this, explain_policy, &recorded, &mut resp).await;
+                log_timings(&this.logger, &verdict_reference, &timer).await?;
 
                 this.logger.log_verdict(&verdict_reference, &resp).await.map_err(|err| {
                     debug!("Could not log data access verdict to audit log : {:?} | request id: {}", err, verdict_reference);
-                    warp::reject::custom(err)
+                    audit_log_unavailable(&verdict_reference, err)
                 })?;
 
-                Ok(warp::reply::with_status(warp::reply::json(&resp), warp::hyper::StatusCode::OK))
+                confirm_logged(&this.logger, &verdict_reference).await?;
+                record_duties(&*this.ids, this.duties.as_deref(), &resp, &auth_ctx.initiator, &verdict_reference).await;
+                this.recent_questions.remember(&auth_ctx.initiator, recorded, &resp);
+                if allowed {
+                    count_usage(this.usage_counters.as_deref(), DATA_ACCESSES, &usage, &verdict_reference).await;
+                }
+                notify_owners(this.notifier.as_ref(), &this.logger, &question, &resp, owners);
+                // Only given after logging, since the token is a credential that doesn't belong in the audit log
+                attach_access_token(this.access_tokens.as_ref(), &mut resp, &auth_ctx.initiator, &workflow_id, &dataset, task.as_deref());
+
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&this.messages.localize_verdict(language.as_deref(), resp)),
+                    warp::hyper::StatusCode::OK,
+                ))
+            },
+            Err(err) => {
+                log_timings(&this.logger, &verdict_reference, &timer).await?;
+                Self::handle_reasoner_failure(&this, &verdict_reference, &warnings, &evaluated_with, language.as_deref(), cache_key.as_deref(), err)
+                    .await
             },
-            Err(err) => Ok(warp::reply::with_status(warp::reply::json(&format!("{}", err)), warp::hyper::StatusCode::OK)),
         }
     }
 
@@ -346,23 +1261,33 @@ where
         auth_ctx: AuthContext,
         this: Arc<Self>,
         body: WorkflowValidationRequest,
+        raw: Bytes,
+        accept_language: Option<String>,
+        federated_by: Option<String>,
     ) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::reject::Rejection> {
         info!("Handling validate request");
-        let WorkflowValidationRequest { use_case, workflow } = body;
+        let mut timer = RequestTimer::new();
+        let forwardable: Option<Value> = this.federation.forwardable(&body, federated_by.as_deref());
+        let WorkflowValidationRequest { use_case, workflow, priority, explain, node_results, granularity } = body;
+        let language: Option<String> = this.messages.negotiate(accept_language.as_deref()).map(String::from);
 
-        let verdict_reference: String = uuid::Uuid::new_v4().into();
+        let verdict_reference: String = this.ids.generate();
 
         debug!("Compiling WIR workflow to Checker Workflow...");
         // Read the body's workflow as a Checker Workflow
-        let workflow: Workflow = match Workflow::try_from(workflow) {
-            Ok(workflow) => workflow,
+        let (mut workflow, stats): (Workflow, InlineStats) = match compile_workflow(workflow, &this.workflow_limits)? {
+            Ok(res) => res,
             Err(err) => {
                 return Ok(warp::reply::with_status(warp::reply::json(&err.to_string()), warp::hyper::StatusCode::BAD_REQUEST));
             },
         };
+        // Tell the client about anything missing from the workflow that makes its check weaker
+        let warnings: Vec<Diagnostic> = workflow.diagnostics();
+        // Refuse workflows with obvious mistakes before asking anything about them
+        lint_workflow(&workflow, &this.lint, &this.messages, language.as_deref(), &verdict_reference)?;
 
         debug!("Retrieving state...");
-        let state = match this.stateresolver.get_state(use_case).await {
+        let mut state = match timer.time_state_resolution(this.stateresolver.get_state(use_case.clone())).await {
             Ok(state) => state,
             Err(err) => {
                 error!("Could not retrieve state: {err} | request id: {verdict_reference}");
@@ -376,53 +1301,158 @@ where
             state.locations.len(),
             state.users.len()
         );
+        // Refuse workflows that refer to datasets, locations or users the state doesn't know about
+        check_references(&workflow, &state, this.state_check, &verdict_reference)?;
+        // Tell the reasoner which code runs in every task
+        let unknown_images: usize = workflow.resolve_images(&state.images);
+        if unknown_images > 0 {
+            debug!("Image of {unknown_images} task(s) in workflow '{}' is unknown", workflow.id);
+        }
+        verify_images(this.image_verifier.as_deref(), &mut workflow, &verdict_reference);
+        // Tell the reasoner how much has been used so far
+        attach_counters(this.usage_counters.as_deref(), &mut state, &verdict_reference).await?;
+        // Tell the reasoner which duties the user asking has yet to fulfil
+        attach_duties(this.duties.as_deref(), &mut state, &auth_ctx.initiator, &verdict_reference).await?;
+        // Tell the reasoner who sent the request, if not the user themselves
+        attach_delegation(&mut state, &auth_ctx);
+        attach_granularity(&mut state, granularity);
+        // Tell the client what the verdict is evaluated with
+        let mut evaluated_with =
+            EvaluationContext { policy_version: None, connector_context: C::hash(), state_snapshot: Some(state_snapshot_id(&state)) };
 
-        let verdict_reference: String = uuid::Uuid::new_v4().into();
+        let verdict_reference: String = this.ids.generate();
         debug!("Generated verdict_reference: {}", verdict_reference);
 
         debug!("Retrieving active policy...");
-        let policy = match get_active_policy(&this.logger, &verdict_reference, &this.policystore).await? {
+        let policy = match get_active_policy(&this.logger, &verdict_reference, &warnings, &evaluated_with, &this.policystore).await? {
             Ok(policy) => policy,
             Err(err) => return Ok(err),
         };
         debug!("Got policy with {} bodies", policy.content.len());
+        evaluated_with.policy_version = policy.version.version;
 
-        this.logger.log_validate_workflow_request(&verdict_reference, &auth_ctx, policy.version.version.unwrap(), &state, &workflow).await.map_err(
-            |err| {
+        this.logger
+            .log_validate_workflow_request(
+                &verdict_reference,
+                &auth_ctx,
+                policy.version.version.unwrap(),
+                &policy.content_hashes(),
+                &state,
+                &workflow,
+            )
+            .await
+            .map_err(|err| {
                 debug!("Could not log validate workflow request to audit log : {:?} | request id: {}", err, verdict_reference);
-                warp::reject::custom(err)
+                audit_log_unavailable(&verdict_reference, err)
+            })?;
+        log_preprocessed(&this.logger, &verdict_reference, &stats).await?;
+        archive_request(this.payloads.as_deref(), &this.logger, &verdict_reference, &raw).await?;
+
+        // Answer obvious questions without bothering the reasoner
+        let prefiltered: Option<ReasonerResponse> = prefilter(
+            &this.lockdown,
+            &this.maintenance,
+            &this.prefilter,
+            &this.logger,
+            &verdict_reference,
+            "execute-workflow",
+            &workflow,
+            &QuestionKind::ValidateWorkflow,
+        )
+        .await?;
+        // Ask partner checkers about their datasets too (once our reasoner allowed it)
+        let forward: Option<Forward> = this.federation.involved(forwardable, &workflow, &QuestionKind::ValidateWorkflow);
+
+        let cache_key: Option<String> = match this.fallback {
+            FallbackBehaviour::Cache => VerdictCache::key(&("validate-workflow", policy.version.version, &state, content_hash(&workflow))),
+            _ => None,
+        };
+
+        // Wait for our turn at the reasoner (unless it need not be asked); the permit is handed to the next question once this request is done
+        let _permit: Option<QueuePermit> = match prefiltered {
+            Some(_) => None,
+            None => match timer.time_queue(this.question_queue.enter(priority)).await {
+                Ok(permit) => Some(permit),
+                Err(err) => {
+                    log_timings(&this.logger, &verdict_reference, &timer).await?;
+                    return Self::handle_reasoner_failure(
+                        &this,
+                        &verdict_reference,
+                        &warnings,
+                        &evaluated_with,
+                        language.as_deref(),
+                        cache_key.as_deref(),
+                        ReasonerConnError::from(err),
+                    )
+                    .await;
+                },
             },
-        )?;
+        };
 
         debug!("Consulting reasoner connector...");
+        let workflow_id: String = workflow.id.clone();
+        let owners: Vec<DatasetOwner> = owners_involved(this.notifier.as_ref(), &state, &workflow, None);
+        let recorded = RecordedQuestion {
+            reference: verdict_reference.clone(),
+            policy:    policy.version.version.unwrap_or(-1),
+            kind:      QuestionKind::ValidateWorkflow,
+            state:     state.clone(),
+            workflow:  workflow.clone(),
+            original:  None,
+        };
+        let explain_policy: Option<Policy> = explain.then(|| policy.clone());
+        // Nodes are not judged if the reasoner wasn't asked about the workflow as a whole either (e.g., during maintenance)
+        let nodes_policy: Option<Policy> = (node_results && prefiltered.is_none()).then(|| policy.clone());
 
-        match this
-            .reasonerconn
-            .workflow_validation_request(SessionedConnectorAuditLogger::new(verdict_reference.clone(), this.logger.clone()), policy, state, workflow)
-            .await
-        {
+        let res: Result<ReasonerResponse, ReasonerConnError> = match prefiltered {
+            Some(response) => Ok(response),
+            None => {
+                let session =
+                    SessionedConnectorAuditLogger::new(verdict_reference.clone(), this.logger.clone()).with_payload_store(this.payloads.clone());
+                let res: Result<ReasonerResponse, ReasonerConnError> =
+                    timer.time_reasoner(this.reasonerconn.workflow_validation_request(session.clone(), policy, state, workflow)).await;
+                end_session(session).await?;
+                res
+            },
+        };
+        match res {
             Ok(v) => {
-                let resp: Verdict = if !v.success {
-                    Verdict::Deny(DeliberationDenyResponse {
-                        shared: WorkflowValidationResponse { verdict_reference: verdict_reference.clone() },
-                        reasons_for_denial: Some(v.errors),
-                    })
-                } else {
-                    Verdict::Allow(DeliberationAllowResponse {
-                        shared:    WorkflowValidationResponse { verdict_reference: verdict_reference.clone() },
-                        // TODO implement signature
-                        signature: "signature".into(),
-                    })
+                if let Some(key) = cache_key {
+                    this.verdict_cache.insert(key, v.success, v.errors.clone(), v.categories.clone());
+                }
+                let v: ReasonerResponse = federate(&this.federation, &this.logger, &verdict_reference, "execute-workflow", forward, v).await?;
+                let question = Question {
+                    kind:      "execute-workflow",
+                    reference: &verdict_reference,
+                    initiator: &auth_ctx.initiator,
+                    use_case:  &use_case,
+                    workflow:  &workflow_id,
                 };
+                let mut resp: Verdict = verdict_of(&verdict_reference, &warnings, &evaluated_with, Self::post_process(&this, &question, v).await?);
+                Self::explain_denial(&this, explain_policy, &recorded, &mut resp).await;
+                Self::annotate_nodes(&this, nodes_policy, &recorded, &mut resp).await;
+                log_timings(&this.logger, &verdict_reference, &timer).await?;
 
                 this.logger.log_verdict(&verdict_reference, &resp).await.map_err(|err| {
                     debug!("Could not log workflow validation verdict to audit log : {:?} | request id: {}", err, verdict_reference);
-                    warp::reject::custom(err)
+                    audit_log_unavailable(&verdict_reference, err)
                 })?;
 
-                Ok(warp::reply::with_status(warp::reply::json(&resp), warp::hyper::StatusCode::OK))
+                confirm_logged(&this.logger, &verdict_reference).await?;
+                record_duties(&*this.ids, this.duties.as_deref(), &resp, &auth_ctx.initiator, &verdict_reference).await;
+                this.recent_questions.remember(&auth_ctx.initiator, recorded, &resp);
+                notify_owners(this.notifier.as_ref(), &this.logger, &question, &resp, owners);
+
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&this.messages.localize_verdict(language.as_deref(), resp)),
+                    warp::hyper::StatusCode::OK,
+                ))
+            },
+            Err(err) => {
+                log_timings(&this.logger, &verdict_reference, &timer).await?;
+                Self::handle_reasoner_failure(&this, &verdict_reference, &warnings, &evaluated_with, language.as_deref(), cache_key.as_deref(), err)
+                    .await
             },
-            Err(err) => Ok(warp::reply::with_status(warp::reply::json(&format!("{}", err)), warp::hyper::StatusCode::OK)),
         }
     }
 
@@ -431,32 +1461,63 @@ where
             .and(warp::path!("execute-task"))
             .and(Self::with_deliberation_api_auth(this.clone()))
             .and(Self::with_self(this.clone()))
-            .and(warp::body::json())
-            .and_then(Self::handle_execute_task_request);
+            .and(with_raw_wir_body(this.workflows.clone()))
+            .and(warp::query::<models::AsyncQuery>())
+            .and(warp::header::optional::<String>("accept-language"))
+            .and(warp::header::optional::<String>(FEDERATION_HEADER))
+            .and_then(|auth_ctx: AuthContext, this: Arc<Self>, body, raw, query, language, federated_by| {
+                Self::answer_maybe_async(
+                    this.clone(),
+                    auth_ctx.initiator.clone(),
+                    query,
+                    Self::handle_execute_task_request(auth_ctx, this, body, raw, language, federated_by),
+                )
+            });
 
         let access_data = warp::post()
             .and(warp::path!("access-data"))
             .and(Self::with_deliberation_api_auth(this.clone()))
             .and(Self::with_self(this.clone()))
-            .and(warp::body::json())
-            .and_then(Self::handle_access_data_request);
+            .and(with_raw_wir_body(this.workflows.clone()))
+            .and(warp::query::<models::AsyncQuery>())
+            .and(warp::header::optional::<String>("accept-language"))
+            .and(warp::header::optional::<String>(FEDERATION_HEADER))
+            .and_then(|auth_ctx: AuthContext, this: Arc<Self>, body, raw, query, language, federated_by| {
+                Self::answer_maybe_async(
+                    this.clone(),
+                    auth_ctx.initiator.clone(),
+                    query,
+                    Self::handle_access_data_request(auth_ctx, this, body, raw, language, federated_by),
+                )
+            });
 
         let execute_workflow = warp::post()
             .and(warp::path!("execute-workflow"))
             .and(Self::with_deliberation_api_auth(this.clone()))
             .and(Self::with_self(this.clone()))
-            .and(warp::body::json())
-            .and_then(Self::handle_validate_workflow_request);
+            .and(with_raw_wir_body(this.workflows.clone()))
+            .and(warp::query::<models::AsyncQuery>())
+            .and(warp::header::optional::<String>("accept-language"))
+            .and(warp::header::optional::<String>(FEDERATION_HEADER))
+            .and_then(|auth_ctx: AuthContext, this: Arc<Self>, body, raw, query, language, federated_by| {
+                Self::answer_maybe_async(
+                    this.clone(),
+                    auth_ctx.initiator.clone(),
+                    query,
+                    Self::handle_validate_workflow_request(auth_ctx, this, body, raw, language, federated_by),
+                )
+            });
 
-        warp::path("v1").and(warp::path("deliberation")).and(exec_task.or(access_data).or(execute_workflow))
+        warp::path("v1")
+            .and(warp::path("deliberation"))
+            .and(exec_task.or(access_data).or(execute_workflow).or(Self::async_result_handlers(this.clone())))
     }
 
     pub fn with_deliberation_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
-        Self::with_self(this.clone()).and(warp::header::headers_cloned()).and_then(|this: Arc<Self>, headers| async move {
-            match this.dauthresolver.authenticate(headers).await {
-                Ok(v) => Ok(v),
-                Err(err) => Err(warp::reject::custom(err)),
-            }
-        })
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.dauthresolver, route, headers).await
+            },
+        )
     }
 }
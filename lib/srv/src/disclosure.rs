@@ -0,0 +1,230 @@
+//! Implements sharing redacted summaries of the policy with the planners and checkers of other domains.
+//!
+//! In a federation (see [`Federation`](crate::federation::Federation)), a planner of one domain would rather not plan a
+//! workflow that a checker of another domain is bound to deny, e.g., because it reads a class of datasets that domain
+//! never releases. Yet policies themselves are usually confidential. Operators can therefore write a
+//! [`DisclosureConfig`] with a summary of the policy as named fields (such as `never-released`), each with its own
+//! [`Visibility`]: to everyone allowed to ask questions, only to partner domains, or to no one.
+//!
+//! Clients either retrieve everything they may see with `GET /v1/disclosure`, or negotiate for particular fields with
+//! `POST /v1/disclosure`, in which case they are told which of them are withheld. Every disclosure is recorded in the
+//! audit log with who asked and which fields were disclosed.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use log::{debug, info};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use state_resolver::StateResolver;
+use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderMap, StatusCode};
+use warp::reject::Rejection;
+
+use crate::Srv;
+use crate::problem::Problem;
+
+/***** AUXILLARY *****/
+/// Decides who may see a field of the policy summary.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Visibility {
+    /// No one; the field is only kept for the operator's reference.
+    #[default]
+    Private,
+    /// Only the partner domains named in the [`DisclosureConfig`].
+    Partners,
+    /// Everyone who may ask the reasoner questions.
+    Public,
+}
+
+/// A field of the policy summary.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DisclosedField {
+    /// What is disclosed (e.g., a list of dataset classes).
+    pub value:      Value,
+    /// Who may see it.
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+/// Configures what is disclosed about the policy, and to whom.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DisclosureConfig {
+    /// The name of our own domain, which is included in every summary.
+    pub domain:   String,
+    /// The initiators (as authenticated by the deliberation API) that are partner domains.
+    #[serde(default)]
+    pub partners: Vec<String>,
+    /// The fields of the summary by name.
+    #[serde(default)]
+    pub fields:   BTreeMap<String, DisclosedField>,
+}
+
+/// The body of a `POST /v1/disclosure`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DisclosureRequest {
+    /// The names of the fields asked for.
+    pub fields: Vec<String>,
+}
+
+/// The answer to a disclosure request.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DisclosureResponse {
+    /// The domain that the summary is of.
+    pub domain:   String,
+    /// The fields disclosed, by name.
+    pub fields:   BTreeMap<String, Value>,
+    /// The fields asked for that are withheld. Fields that do not exist are indistinguishable from ones that may not be seen.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub withheld: Vec<String>,
+}
+
+/***** LIBRARY *****/
+/// Keeps the redacted summary of the policy that is shared with other domains.
+///
+/// The default Disclosure has no fields, and discloses nothing but an empty domain.
+#[derive(Clone, Debug, Default)]
+pub struct Disclosure {
+    /// What is disclosed, and to whom.
+    config: DisclosureConfig,
+}
+impl Disclosure {
+    /// Constructor for the Disclosure.
+    ///
+    /// # Arguments
+    /// - `config`: The [`DisclosureConfig`] with the summary and who may see which fields.
+    ///
+    /// # Returns
+    /// A new Disclosure.
+    #[inline]
+    pub fn new(config: DisclosureConfig) -> Self { Self { config } }
+
+    /// Decides what someone may see of the summary.
+    ///
+    /// # Arguments
+    /// - `initiator`: Who asks.
+    ///
+    /// # Returns
+    /// The most restrictive [`Visibility`] of fields they may see.
+    pub fn clearance(&self, initiator: &str) -> Visibility {
+        if self.config.partners.iter().any(|partner| partner == initiator) { Visibility::Partners } else { Visibility::Public }
+    }
+
+    /// Discloses (part of) the summary.
+    ///
+    /// # Arguments
+    /// - `clearance`: The [`Visibility`] of fields the asker may see (see [`Disclosure::clearance()`]).
+    /// - `fields`: The names of the fields asked for, or [`None`] to ask for all of them.
+    ///
+    /// # Returns
+    /// A [`DisclosureResponse`] with the fields that may be seen, and the rest of those asked for as withheld.
+    pub fn disclose(&self, clearance: Visibility, fields: Option<&[String]>) -> DisclosureResponse {
+        let visible = |field: &DisclosedField| field.visibility != Visibility::Private && field.visibility >= clearance;
+        let mut res = DisclosureResponse { domain: self.config.domain.clone(), fields: BTreeMap::new(), withheld: vec![] };
+        match fields {
+            Some(names) => {
+                for name in names {
+                    match self.config.fields.get(name).filter(|field| visible(field)) {
+                        Some(field) => {
+                            res.fields.insert(name.clone(), field.value.clone());
+                        },
+                        None => res.withheld.push(name.clone()),
+                    }
+                }
+            },
+            None => {
+                res.fields =
+                    self.config.fields.iter().filter(|(_, field)| visible(field)).map(|(name, field)| (name.clone(), field.value.clone())).collect();
+            },
+        }
+        res
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Discloses (part of) the policy summary to someone, logging what was disclosed.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the summary.
+    /// - `auth_ctx`: The [`AuthContext`] of who asks.
+    /// - `fields`: The names of the fields asked for, or [`None`] to ask for all they may see.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `500 Internal Server Error` if the disclosure could not be
+    /// logged, in which case nothing is disclosed.
+    async fn disclose(this: &Self, auth_ctx: &AuthContext, fields: Option<&[String]>) -> Result<warp::reply::Json, Rejection> {
+        let clearance: Visibility = this.disclosure.clearance(&auth_ctx.initiator);
+        let res: DisclosureResponse = this.disclosure.disclose(clearance, fields);
+        let disclosed: Vec<String> = res.fields.keys().cloned().collect();
+        info!("Disclosing {} policy summary field(s) to '{}' ({} withheld)", disclosed.len(), auth_ctx.initiator, res.withheld.len());
+        if let Err(err) = this.logger.log_policy_disclosure(auth_ctx, &disclosed, &res.withheld).await {
+            debug!("Could not log policy disclosure to audit log : {:?}", err);
+            let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(format!("Failed to log disclosure: {err}"));
+            return Err(warp::reject::custom(Problem::from(p)));
+        }
+        Ok(warp::reply::json(&res))
+    }
+
+    // Get everything of the policy summary that the client may see
+    // GET /v1/disclosure
+    // out:
+    //  200 DisclosureResponse
+    //  500 problem+json (failed to log the disclosure)
+
+    async fn handle_get_disclosure(auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        Self::disclose(&this, &auth_ctx, None).await
+    }
+
+    // Ask for particular fields of the policy summary
+    // POST /v1/disclosure
+    // in: DisclosureRequest
+    // out:
+    //  200 DisclosureResponse
+    //  500 problem+json (failed to log the disclosure)
+
+    async fn handle_negotiate_disclosure(auth_ctx: AuthContext, this: Arc<Self>, body: DisclosureRequest) -> Result<warp::reply::Json, Rejection> {
+        Self::disclose(&this, &auth_ctx, Some(&body.fields)).await
+    }
+
+    pub fn disclosure_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let get_disclosure = warp::get()
+            .and(warp::path::end())
+            .and(Self::with_disclosure_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_disclosure);
+
+        let negotiate_disclosure = warp::post()
+            .and(warp::path::end())
+            .and(Self::with_disclosure_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_negotiate_disclosure);
+
+        warp::path("v1").and(warp::path("disclosure")).and(get_disclosure.or(negotiate_disclosure))
+    }
+
+    fn with_disclosure_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.dauthresolver, route, headers).await
+            },
+        )
+    }
+}
@@ -0,0 +1,487 @@
+//! Implements drafts: policies that are still being written, which are checked on every change before they become a version.
+//!
+//! Policy editors (e.g., a GUI) would rather tell their users that a policy is broken while they are writing it than
+//! when they try to activate it. A [`Draft`] holds work-in-progress contents, together with a test suite of questions
+//! and the verdicts they are expected to get. It is created with `POST /v1/management/drafts` and updated incrementally
+//! with `PATCH /v1/management/drafts/:id`, without creating a policy version. Every change validates the contents
+//! against what the reasoner connector understands and, if they are valid, runs the test suite against them (see
+//! [`simulate()`](crate::simulation::simulate())). The resulting [`DraftFeedback`] is returned with the draft.
+//!
+//! Once satisfied, editors commit the draft with `POST /v1/management/drafts/:id/commit`, which stores it as a new
+//! policy version (only if its feedback is clean) and discards the draft. Drafts are only kept in memory, so they do not
+//! survive a restart, and at most [`DRAFT_STORE_CAPACITY`] of them are kept at once.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use chrono::{DateTime, Local};
+use log::{debug, info};
+use policy::{Policy, PolicyContent, PolicyDataAccess, PolicyMetadata, PolicyVersion};
+use problem_details::ProblemDetails;
+use reasonerconn::{PolicyLanguage, QuestionKind, ReasonerConnector};
+use serde::{Deserialize, Serialize};
+use state_resolver::{State, StateResolver};
+use warp::Filter;
+use warp::http::StatusCode;
+use warp::reject::Rejection;
+use workflow::Workflow;
+
+use crate::Srv;
+use crate::models::{PatchPolicyContentModel, PolicyContentPostModel, patch_contents};
+use crate::problem::Problem;
+use crate::simulation::{self, RecordedQuestion, SimulationReport};
+
+/***** CONSTANTS *****/
+/// The maximum number of drafts kept in the [`DraftStore`] at once.
+pub const DRAFT_STORE_CAPACITY: usize = 256;
+
+/***** HELPERS *****/
+/// Builds a problem to reject a draft request with.
+#[inline]
+fn draft_problem(status: StatusCode, detail: impl Into<String>) -> Rejection {
+    warp::reject::custom(Problem::from(ProblemDetails::new().with_status(status).with_detail(detail.into())))
+}
+
+/// Checks whether the contents of a draft can be understood by the reasoner connector.
+///
+/// # Arguments
+/// - `languages`: The [`PolicyLanguage`]s the reasoner connector understands.
+/// - `content`: The contents of the draft.
+///
+/// # Returns
+/// A (human-readable) description of every problem found, or an empty list if there are none.
+fn validate(languages: &[PolicyLanguage], content: &[PolicyContent]) -> Vec<String> {
+    let mut issues: Vec<String> = Vec::new();
+    if content.is_empty() {
+        issues.push("Draft has no content".into());
+    }
+    for (i, c) in content.iter().enumerate() {
+        if content[..i].iter().any(|other| other.reasoner == c.reasoner) {
+            issues.push(format!("Draft has more than one content for reasoner '{}'", c.reasoner));
+            continue;
+        }
+        match languages.iter().find(|lang| lang.reasoner == c.reasoner) {
+            Some(lang) if lang.reasoner_version != c.reasoner_version => issues.push(format!(
+                "Content for reasoner '{}' is written for version '{}', but the reasoner understands version '{}'",
+                c.reasoner, c.reasoner_version, lang.reasoner_version
+            )),
+            Some(_) => {},
+            None => issues.push(format!(
+                "Content for reasoner '{}' is not understood by the reasoner (it understands {})",
+                c.reasoner,
+                languages.iter().map(|lang| format!("'{}'", lang.reasoner)).collect::<Vec<String>>().join(", ")
+            )),
+        }
+    }
+    issues
+}
+
+/***** AUXILLARY *****/
+/// A question in the test suite of a [`Draft`], with the verdict it is expected to get.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DraftTest {
+    /// The name of the test, which identifies it in the [`DraftFeedback`].
+    pub name:     String,
+    /// What is asked.
+    #[serde(flatten)]
+    pub kind:     QuestionKind,
+    /// The state given to the policy.
+    pub state:    State,
+    /// The workflow in question.
+    pub workflow: Workflow,
+    /// Whether the question is expected to be allowed.
+    pub expected: bool,
+}
+impl DraftTest {
+    /// Turns the test into a question to replay against the draft, as if it was recorded with the expected verdict.
+    ///
+    /// # Arguments
+    /// - `draft`: The identifier of the draft the test belongs to, which prefixes its reference in the audit log.
+    fn to_question(&self, draft: &str) -> RecordedQuestion {
+        RecordedQuestion {
+            reference: format!("draft-{draft}-{}", self.name),
+            policy:    0,
+            kind:      self.kind.clone(),
+            state:     self.state.clone(),
+            workflow:  self.workflow.clone(),
+            original:  Some(self.expected),
+        }
+    }
+}
+
+/// What is wrong with (the current revision of) a [`Draft`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DraftFeedback {
+    /// The problems found when validating the contents of the draft.
+    pub issues: Vec<String>,
+    /// The outcome of running the test suite, or [`None`] if it was not run because validation failed. Tests that got
+    /// another verdict than expected are listed as `newly_allowed` or `newly_denied`.
+    pub tests:  Option<SimulationReport>,
+    /// Whether the draft is valid and passes all of its tests, i.e., whether it may be committed.
+    pub passed: bool,
+}
+
+/// A policy that is still being written.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Draft {
+    /// The identifier of the draft.
+    pub id: String,
+    /// Who created the draft.
+    pub creator: String,
+    /// Counts the changes made to the draft, starting at 1 for a new one.
+    pub revision: u64,
+    /// When the draft was last changed.
+    pub updated_at: DateTime<Local>,
+    /// The description the policy gets when committed.
+    pub description: String,
+    /// The metadata the version gets when committed.
+    #[serde(flatten)]
+    pub metadata: PolicyMetadata,
+    /// The contents of the policy.
+    pub content: Vec<PolicyContent>,
+    /// The questions the policy is tested with.
+    pub tests: Vec<DraftTest>,
+    /// What is wrong with this revision of the draft.
+    pub feedback: DraftFeedback,
+}
+impl Draft {
+    /// Turns the draft into a (not yet stored) [`Policy`].
+    ///
+    /// # Arguments
+    /// - `version_description`: The version description of the policy.
+    pub fn to_policy(&self, version_description: impl Into<String>) -> Policy {
+        Policy {
+            description: self.description.clone(),
+            version:     PolicyVersion {
+                creator: None,
+                created_at: chrono::Local::now(),
+                version: None,
+                version_description: version_description.into(),
+                reasoner_connector_context: "".into(),
+                metadata: self.metadata.clone(),
+                content_hashes: Vec::new(),
+                external_id: None,
+            },
+            content:     self.content.clone(),
+        }
+    }
+}
+
+/// The body of a `POST /v1/management/drafts`.
+#[derive(Deserialize, Serialize)]
+pub struct DraftPostModel {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub content: Vec<PolicyContentPostModel>,
+    #[serde(flatten)]
+    pub metadata: PolicyMetadata,
+    /// The test suite of the draft.
+    #[serde(default)]
+    pub tests: Vec<DraftTest>,
+}
+
+/// The body of a `PATCH /v1/management/drafts/:id`, which changes only some of a draft.
+#[derive(Deserialize, Serialize)]
+pub struct DraftPatchModel {
+    /// The revision the changes were made to. If given, the changes are refused if the draft has been changed since.
+    pub revision: Option<u64>,
+    /// The new description of the draft. Omit to keep it.
+    pub description: Option<String>,
+    /// The new metadata of the draft. Omit to keep it.
+    pub metadata: Option<PolicyMetadata>,
+    /// The contents to add, or to replace those for the same reasoner with.
+    #[serde(default)]
+    pub content: Vec<PatchPolicyContentModel>,
+    /// The reasoners whose contents to remove.
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /// The test suite to replace that of the draft with. Omit to keep it.
+    pub tests: Option<Vec<DraftTest>>,
+}
+
+/// The body of a `POST /v1/management/drafts/:id/commit`.
+#[derive(Deserialize, Serialize)]
+pub struct DraftCommitModel {
+    pub version_description: String,
+}
+
+/***** LIBRARY *****/
+/// Keeps the drafts being written, by identifier.
+#[derive(Debug, Default)]
+pub struct DraftStore {
+    /// The drafts by identifier.
+    drafts: Mutex<HashMap<String, Draft>>,
+}
+impl DraftStore {
+    /// Stores a new draft.
+    ///
+    /// # Returns
+    /// Whether the draft was stored, which it isn't if there are already [`DRAFT_STORE_CAPACITY`] drafts.
+    pub fn insert(&self, draft: Draft) -> bool {
+        let mut drafts = self.drafts.lock().unwrap();
+        if drafts.len() >= DRAFT_STORE_CAPACITY {
+            return false;
+        }
+        drafts.insert(draft.id.clone(), draft);
+        true
+    }
+
+    /// Retrieves a draft.
+    #[inline]
+    pub fn get(&self, id: &str) -> Option<Draft> { self.drafts.lock().unwrap().get(id).cloned() }
+
+    /// Retrieves all drafts, ordered by when they were last changed (most recent first).
+    pub fn list(&self) -> Vec<Draft> {
+        let mut drafts: Vec<Draft> = self.drafts.lock().unwrap().values().cloned().collect();
+        drafts.sort_by(|lhs, rhs| rhs.updated_at.cmp(&lhs.updated_at));
+        drafts
+    }
+
+    /// Replaces a draft with a newer revision of it.
+    ///
+    /// # Arguments
+    /// - `draft`: The new revision of the draft.
+    /// - `base`: The revision that the new one was derived from.
+    ///
+    /// # Returns
+    /// Whether the draft was replaced, which it isn't if it was removed or changed by someone else in the meantime.
+    pub fn update(&self, draft: Draft, base: u64) -> bool {
+        let mut drafts = self.drafts.lock().unwrap();
+        match drafts.get_mut(&draft.id) {
+            Some(old) if old.revision == base => {
+                *old = draft;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Removes a draft.
+    ///
+    /// # Returns
+    /// The removed draft, or [`None`] if there was no such draft.
+    #[inline]
+    pub fn remove(&self, id: &str) -> Option<Draft> { self.drafts.lock().unwrap().remove(id) }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Validates a draft and, if it is valid, runs its test suite.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the reasoner to validate and test with.
+    /// - `draft`: The [`Draft`] to check.
+    ///
+    /// # Returns
+    /// The [`DraftFeedback`] on the draft.
+    async fn draft_feedback(this: &Self, draft: &Draft) -> DraftFeedback {
+        let issues: Vec<String> = validate(&this.reasonerconn.capabilities().languages, &draft.content);
+        if !issues.is_empty() {
+            debug!("Draft '{}' (revision {}) has {} issue(s); not running its tests", draft.id, draft.revision, issues.len());
+            return DraftFeedback { issues, tests: None, passed: false };
+        }
+
+        let questions: Vec<RecordedQuestion> = draft.tests.iter().map(|test| test.to_question(&draft.id)).collect();
+        let policy: Policy = draft.to_policy(format!("Draft {} (revision {})", draft.id, draft.revision));
+        let report: SimulationReport = simulation::simulate(&this.reasonerconn, &this.logger, &policy, questions).await;
+        let passed: bool = report.newly_allowed.is_empty() && report.newly_denied.is_empty() && report.failed.is_empty();
+        DraftFeedback { issues, tests: Some(report), passed }
+    }
+
+    /// Gets a draft.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `404 Not Found` if there is no such draft.
+    fn get_draft(this: &Self, id: &str) -> Result<Draft, Rejection> {
+        this.drafts.get(id).ok_or_else(|| draft_problem(StatusCode::NOT_FOUND, format!("Unknown draft: {id}")))
+    }
+
+    // List the drafts being written
+    // GET /v1/management/drafts
+    // out:
+    //  200 [Draft]
+
+    async fn handle_list_drafts(_auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        Ok(warp::reply::json(&this.drafts.list()))
+    }
+
+    // Start a new draft
+    // POST /v1/management/drafts
+    // in: DraftPostModel
+    // out:
+    //  200 Draft. id and feedback in body
+    //  409 problem+json (too many drafts)
+
+    async fn handle_create_draft(auth_ctx: AuthContext, this: Arc<Self>, body: DraftPostModel) -> Result<warp::reply::Json, Rejection> {
+        let mut draft = Draft {
+            id: this.ids.generate(),
+            creator: auth_ctx.initiator,
+            revision: 1,
+            updated_at: chrono::Local::now(),
+            description: body.description.unwrap_or_default(),
+            metadata: body.metadata,
+            content: body
+                .content
+                .into_iter()
+                .map(|c| PolicyContent { reasoner: c.reasoner, reasoner_version: c.reasoner_version, content: c.content.into() })
+                .collect(),
+            tests: body.tests,
+            feedback: DraftFeedback::default(),
+        };
+        draft.feedback = Self::draft_feedback(&this, &draft).await;
+
+        info!("Created draft '{}' for '{}' (passed: {})", draft.id, draft.creator, draft.feedback.passed);
+        if !this.drafts.insert(draft.clone()) {
+            return Err(draft_problem(
+                StatusCode::CONFLICT,
+                format!("There are already {DRAFT_STORE_CAPACITY} drafts; commit or discard some before starting another"),
+            ));
+        }
+        Ok(warp::reply::json(&draft))
+    }
+
+    // Get a draft, with the feedback on its latest revision
+    // GET /v1/management/drafts/:id
+    // out:
+    //  200 Draft
+    //  404 problem+json
+
+    async fn handle_get_draft(_auth_ctx: AuthContext, id: String, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        Ok(warp::reply::json(&Self::get_draft(&this, &id)?))
+    }
+
+    // Change part of a draft, and check it again
+    // PATCH /v1/management/drafts/:id
+    // in: DraftPatchModel
+    // out:
+    //  200 Draft. new revision and feedback in body
+    //  400 problem+json (ambiguous content changes)
+    //  404 problem+json
+    //  409 problem+json (draft was changed since the given revision)
+
+    async fn handle_patch_draft(_auth_ctx: AuthContext, id: String, this: Arc<Self>, body: DraftPatchModel) -> Result<warp::reply::Json, Rejection> {
+        let mut draft: Draft = Self::get_draft(&this, &id)?;
+        let base: u64 = draft.revision;
+        if let Some(revision) = body.revision {
+            if revision != base {
+                return Err(draft_problem(StatusCode::CONFLICT, format!("Draft '{id}' is at revision {base}, not {revision}")));
+            }
+        }
+
+        draft.content = patch_contents(draft.content, body.content, &body.remove).map_err(|detail| draft_problem(StatusCode::BAD_REQUEST, detail))?;
+        if let Some(description) = body.description {
+            draft.description = description;
+        }
+        if let Some(metadata) = body.metadata {
+            draft.metadata = metadata;
+        }
+        if let Some(tests) = body.tests {
+            draft.tests = tests;
+        }
+        draft.revision += 1;
+        draft.updated_at = chrono::Local::now();
+        draft.feedback = Self::draft_feedback(&this, &draft).await;
+
+        debug!("Updated draft '{id}' to revision {} (passed: {})", draft.revision, draft.feedback.passed);
+        if !this.drafts.update(draft.clone(), base) {
+            return Err(draft_problem(
+                StatusCode::CONFLICT,
+                format!("Draft '{id}' was changed or discarded while checking revision {}", draft.revision),
+            ));
+        }
+        Ok(warp::reply::json(&draft))
+    }
+
+    // Commit a draft as a new policy version, discarding the draft
+    // POST /v1/management/drafts/:id/commit
+    // in: DraftCommitModel
+    // out:
+    //  200 Policy. version in body
+    //  400 problem+json
+    //  404 problem+json
+    //  409 problem+json (draft has issues or failing tests)
+
+    async fn handle_commit_draft(auth_ctx: AuthContext, id: String, this: Arc<Self>, body: DraftCommitModel) -> Result<warp::reply::Json, Rejection> {
+        let draft: Draft = Self::get_draft(&this, &id)?;
+        if !draft.feedback.passed {
+            return Err(draft_problem(
+                StatusCode::CONFLICT,
+                format!("Draft '{id}' (revision {}) has issues or failing tests, and cannot be committed", draft.revision),
+            ));
+        }
+
+        let res: warp::reply::Json = Self::add_policy(auth_ctx, this.clone(), draft.to_policy(body.version_description)).await?;
+        info!("Committed draft '{id}' (revision {})", draft.revision);
+        this.drafts.remove(&id);
+        Ok(res)
+    }
+
+    // Discard a draft
+    // DELETE /v1/management/drafts/:id
+    // out:
+    //  200 Draft. the discarded draft
+    //  404 problem+json
+
+    async fn handle_discard_draft(auth_ctx: AuthContext, id: String, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        let draft: Draft = this.drafts.remove(&id).ok_or_else(|| draft_problem(StatusCode::NOT_FOUND, format!("Unknown draft: {id}")))?;
+        info!("Draft '{id}' discarded by '{}'", auth_ctx.initiator);
+        Ok(warp::reply::json(&draft))
+    }
+
+    pub fn drafts_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let list_drafts = warp::get()
+            .and(warp::path::end())
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_list_drafts);
+
+        let create_draft = warp::post()
+            .and(warp::path::end())
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_create_draft);
+
+        let get_draft = warp::get()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(String))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_draft);
+
+        let patch_draft = warp::patch()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(String))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_patch_draft);
+
+        let commit_draft = warp::post()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(String / "commit"))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_commit_draft);
+
+        let discard_draft = warp::delete()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(String))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_discard_draft);
+
+        warp::path("v1")
+            .and(warp::path("management"))
+            .and(warp::path("drafts"))
+            .and(list_drafts.or(create_draft).or(get_draft).or(patch_draft).or(commit_draft).or(discard_draft))
+    }
+}
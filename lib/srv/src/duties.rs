@@ -0,0 +1,177 @@
+//! Implements tracking the duties that come with the verdicts of the reasoner.
+//!
+//! With a [`DutyStore`] configured, every obligation of an allowed verdict (see the
+//! [`Obligation`](crate::postprocess::Obligation) transformer) is recorded as an open [`Duty`] of the user that asked.
+//! The open duties of the user asking are given to the reasoner as part of the [`State`], such that policies can deny
+//! requests while duties are outstanding. The duties API lists open duties and accepts reports of their fulfilment.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use chrono::Local;
+use deliberation::ids::IdGenerator;
+use deliberation::spec::Verdict;
+use log::{debug, error, info};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::Serialize;
+use state_resolver::duties::{Duty, DutyStore};
+use state_resolver::{State, StateResolver};
+use warp::Filter;
+use warp::http::StatusCode;
+use warp::reject::Rejection;
+
+use crate::problem::Problem;
+use crate::{Srv, models};
+
+/***** HELPERS *****/
+/// Builds the rejection for a request that could not access the duties.
+///
+/// # Arguments
+/// - `status`: The [`StatusCode`] to reject with.
+/// - `detail`: What went wrong.
+///
+/// # Returns
+/// A [`Rejection`] carrying a problem with the given status.
+fn duties_problem(status: StatusCode, detail: impl Into<String>) -> Rejection {
+    warp::reject::custom(Problem::from(ProblemDetails::new().with_status(status).with_detail(detail.into())))
+}
+
+/***** LIBRARY *****/
+/// Adds the open duties of a user to a state, if duties are tracked.
+///
+/// # Arguments
+/// - `duties`: The [`DutyStore`] to read, if any.
+/// - `state`: The [`State`] to add the duties to.
+/// - `holder`: The user asking, of whom to add the open duties.
+/// - `reference`: The verdict reference of the request, for logging.
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `500 Internal Server Error` if the duties could not be read, as
+/// policies relying on them could otherwise permit too much.
+pub(crate) async fn attach_duties(duties: Option<&dyn DutyStore>, state: &mut State, holder: &str, reference: &str) -> Result<(), Rejection> {
+    let Some(duties) = duties else { return Ok(()) };
+    match duties.get_open_duties(Some(holder)).await {
+        Ok(open) => {
+            debug!("User '{holder}' has {} open duties | request id: {reference}", open.len());
+            state.duties = open;
+            Ok(())
+        },
+        Err(err) => {
+            error!("Could not retrieve duties: {err} | request id: {reference}");
+            Err(duties_problem(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+        },
+    }
+}
+
+/// Records the obligations of an allowed verdict as open duties of the user asking, if duties are tracked.
+///
+/// Failing to record a duty does not affect the verdict, which is already given; it is only logged.
+///
+/// # Arguments
+/// - `ids`: The [`IdGenerator`] that generates the identifiers of the duties.
+/// - `duties`: The [`DutyStore`] to record in, if any.
+/// - `verdict`: The [`Verdict`] of which to record the obligations.
+/// - `holder`: The user asking, who has to fulfil the duties.
+/// - `reference`: The verdict reference of the request.
+pub(crate) async fn record_duties(ids: &dyn IdGenerator, duties: Option<&dyn DutyStore>, verdict: &Verdict, holder: &str, reference: &str) {
+    let Some(duties) = duties else { return };
+    let Verdict::Allow(allow) = verdict else { return };
+    for obligation in &allow.obligations {
+        let duty = Duty {
+            id: ids.generate(),
+            verdict_reference: reference.into(),
+            holder: holder.into(),
+            duty: obligation.clone(),
+            created_at: Local::now(),
+            fulfilled_at: None,
+            report: None,
+        };
+        let id: String = duty.id.clone();
+        match duties.add_duty(duty).await {
+            Ok(()) => info!("Recorded duty '{id}' of '{holder}' to '{obligation}' | request id: {reference}"),
+            Err(err) => error!("Could not record duty of '{holder}' to '{obligation}': {err} | request id: {reference}"),
+        }
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Returns the duty store of the server.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `404 Not Found` if duties are not tracked.
+    fn duty_store(this: &Self) -> Result<&dyn DutyStore, Rejection> {
+        this.duties.as_deref().ok_or_else(|| duties_problem(StatusCode::NOT_FOUND, "This reasoner does not track duties"))
+    }
+
+    // Get the open duties
+    // GET /v1/duties?holder={user}
+    // out:
+    //  200 Vec<Duty>
+    //  404 problem+json (duties are not tracked)
+
+    async fn handle_get_duties(query: models::DutiesQuery, _auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling request for open duties{}", query.holder.as_ref().map(|holder| format!(" of '{holder}'")).unwrap_or_default());
+        match Self::duty_store(&this)?.get_open_duties(query.holder.as_deref()).await {
+            Ok(duties) => Ok(warp::reply::json(&duties)),
+            Err(err) => {
+                error!("Could not retrieve duties: {err}");
+                Err(duties_problem(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+            },
+        }
+    }
+
+    // Report that a duty was fulfilled
+    // POST /v1/duties/{id}/fulfil
+    // in: DutyFulfilPostModel
+    // out:
+    //  200 Duty
+    //  404 problem+json (unknown duty or duties are not tracked)
+
+    async fn handle_fulfil_duty(
+        id: String,
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: models::DutyFulfilPostModel,
+    ) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling fulfilment of duty '{id}' reported by '{}'", auth_ctx.initiator);
+        match Self::duty_store(&this)?.fulfil_duty(&id, body.report).await {
+            Ok(Some(duty)) => Ok(warp::reply::json(&duty)),
+            Ok(None) => Err(duties_problem(StatusCode::NOT_FOUND, format!("Unknown duty '{id}'"))),
+            Err(err) => {
+                error!("Could not fulfil duty '{id}': {err}");
+                Err(duties_problem(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+            },
+        }
+    }
+
+    pub fn duties_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let get_duties = warp::get()
+            .and(warp::path::end())
+            .and(warp::query::<models::DutiesQuery>())
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_duties);
+
+        let fulfil_duty = warp::post()
+            .and(warp::path!(String / "fulfil"))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_fulfil_duty);
+
+        warp::path("v1").and(warp::path("duties")).and(get_duties.or(fulfil_duty))
+    }
+}
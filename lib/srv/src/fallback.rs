@@ -0,0 +1,121 @@
+//! Defines what the [`Srv`](crate::Srv) does when the reasoner backend cannot be consulted.
+//!
+//! Backends like an eFLINT server may be down or unreachable, in which case the reasoner connector errors instead of
+//! giving an answer. Rather than leaking that error to the client, the server falls back to a configured
+//! [`FallbackBehaviour`], which is also recorded in the audit log for every affected request.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use deliberation::spec::DenyCategory;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+/***** CONSTANTS *****/
+/// The problem type returned to clients when the reasoner is unavailable and we deny by default.
+pub const REASONER_UNAVAILABLE_PROBLEM: &'static str = "/problems/reasoner-unavailable";
+/// The problem type returned to clients when the reasoner was not consulted because its circuit breaker is open, and we deny by default.
+pub const CIRCUIT_OPEN_PROBLEM: &'static str = "/problems/circuit-open";
+
+/// The maximum number of verdicts kept in the [`VerdictCache`].
+const VERDICT_CACHE_CAPACITY: usize = 4096;
+
+/***** LIBRARY *****/
+/// Defines what to do when the reasoner connector fails to give an answer.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FallbackBehaviour {
+    /// Deny the request, returning a problem with type [`REASONER_UNAVAILABLE_PROBLEM`] to the client.
+    #[default]
+    Deny,
+    /// Answer with the verdict given last time the same question was asked, or deny if it wasn't asked before.
+    Cache,
+    /// Allow the request anyway, logging a warning.
+    AllowWithWarning,
+}
+impl Display for FallbackBehaviour {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Deny => write!(f, "deny"),
+            Self::Cache => write!(f, "cache"),
+            Self::AllowWithWarning => write!(f, "allow-with-warning"),
+        }
+    }
+}
+impl FromStr for FallbackBehaviour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deny" => Ok(Self::Deny),
+            "cache" => Ok(Self::Cache),
+            "allow-with-warning" => Ok(Self::AllowWithWarning),
+            raw => Err(format!("Unknown fallback behaviour '{raw}' (expected 'deny', 'cache' or 'allow-with-warning')")),
+        }
+    }
+}
+
+/// Remembers the most recent answers of the reasoner such that they can be served when it is unavailable.
+///
+/// Only the last [`VERDICT_CACHE_CAPACITY`] questions are remembered; older ones are evicted first.
+#[derive(Debug, Default)]
+pub struct VerdictCache {
+    /// The cached answers (success, reasons and their categories) by question key, and the order in which they were inserted.
+    entries: Mutex<(HashMap<String, (bool, Vec<String>, Vec<DenyCategory>)>, VecDeque<String>)>,
+}
+impl VerdictCache {
+    /// Computes the key under which the answer to a question is cached.
+    ///
+    /// # Arguments
+    /// - `question`: Anything that uniquely identifies the question asked (e.g., its kind, the policy version, the state, the workflow's content hash and the task).
+    ///
+    /// # Returns
+    /// A hex-encoded SHA-256 digest of the question, or [`None`] if it could not be serialized.
+    pub fn key(question: &impl Serialize) -> Option<String> {
+        let raw: Vec<u8> = serde_json::to_vec(question).ok()?;
+        Some(Sha256::digest(&raw).iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    /// Remembers the answer to a question.
+    ///
+    /// # Arguments
+    /// - `key`: The key of the question as returned by [`VerdictCache::key()`].
+    /// - `success`: Whether the reasoner allowed the request.
+    /// - `errors`: The reasons for denial given by the reasoner, if any.
+    /// - `categories`: The [`DenyCategory`]s of the reasons for denial, if any.
+    ///
+    /// Denials because of the emergency lockdown are not remembered, since they say nothing about the question.
+    pub fn insert(&self, key: String, success: bool, errors: Vec<String>, categories: Vec<DenyCategory>) {
+        if categories.contains(&DenyCategory::Lockdown) {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let (answers, order) = &mut *entries;
+        if answers.insert(key.clone(), (success, errors, categories)).is_none() {
+            order.push_back(key);
+        }
+        while order.len() > VERDICT_CACHE_CAPACITY {
+            if let Some(old) = order.pop_front() {
+                answers.remove(&old);
+            }
+        }
+    }
+
+    /// Retrieves the last answer to a question.
+    ///
+    /// # Arguments
+    /// - `key`: The key of the question as returned by [`VerdictCache::key()`].
+    ///
+    /// # Returns
+    /// A tuple of whether the request was allowed, the reasons for denial and their categories, or [`None`] if the question was never answered before.
+    pub fn get(&self, key: &str) -> Option<(bool, Vec<String>, Vec<DenyCategory>)> { self.entries.lock().unwrap().0.get(key).cloned() }
+
+    /// Forgets all remembered answers (e.g., because the policy versions they refer to were replaced).
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.0.clear();
+        entries.1.clear();
+    }
+}
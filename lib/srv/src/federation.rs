@@ -0,0 +1,276 @@
+//! Implements forwarding questions about datasets of other organizations to the checkers governing them.
+//!
+//! In multi-domain settings, a workflow may read datasets that are governed by another organization's checker rather
+//! than by ours. A [`Federation`] knows these partner checkers (see [`FederationPartner`]), by the locations and
+//! datasets they govern. If our reasoner allows a question that involves any of them, the same question is forwarded
+//! to each partner involved, and the request is only allowed if all of them allow it too; otherwise, their reasons for
+//! denial are combined with ours. Every remote verdict (or why it could not be obtained) is recorded in the audit log
+//! together with the partner's verdict reference.
+//!
+//! Forwarded requests carry a [`FEDERATION_HEADER`] with our domain, and are never forwarded again, such that two
+//! checkers that consider each other's datasets foreign do not ask each other forever.
+
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::time::Duration;
+
+use audit_logger::AuditLogger;
+use deliberation::spec::{DenyCategory, Verdict};
+use futures_util::future::join_all;
+use log::{debug, info, warn};
+use reasonerconn::{QuestionKind, ReasonerResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use warp::reject::Rejection;
+use workflow::spec::Workflow;
+
+use crate::deliberation::audit_log_unavailable;
+use crate::prefilter::{Access, accesses_of};
+use crate::problem::causes;
+
+/***** CONSTANTS *****/
+/// The header that marks a deliberation request as forwarded by another checker, carrying its domain.
+pub const FEDERATION_HEADER: &str = "x-federated-by";
+
+/// How long a partner checker gets to answer a forwarded question.
+const FEDERATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/***** ERRORS *****/
+/// Defines errors that originate from asking a partner checker.
+#[derive(Debug)]
+pub enum FederationError {
+    /// Failed to send the question to the partner, or it did not answer with a verdict.
+    Request { domain: String, err: reqwest::Error },
+    /// The partner's answer was not a verdict.
+    Verdict { domain: String, err: reqwest::Error },
+}
+impl Display for FederationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use FederationError::*;
+        match self {
+            Request { domain, .. } => write!(f, "Failed to ask partner checker of domain '{domain}'"),
+            Verdict { domain, .. } => write!(f, "Partner checker of domain '{domain}' did not answer with a verdict"),
+        }
+    }
+}
+impl error::Error for FederationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use FederationError::*;
+        match self {
+            Request { err, .. } => Some(err),
+            Verdict { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// A checker of another organization, which governs some of the locations and datasets that workflows may involve.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FederationPartner {
+    /// The name of the partner's domain, used in the audit log and in reasons for denial.
+    pub domain:    String,
+    /// The base address of the partner's checker (e.g., `https://checker.example.org:3030`).
+    pub address:   String,
+    /// The token to authenticate to the partner's deliberation API with, if any.
+    #[serde(default, skip_serializing)]
+    pub token:     Option<String>,
+    /// The locations the partner governs. Datasets read there (and tasks running there) are the partner's concern.
+    #[serde(default)]
+    pub locations: Vec<String>,
+    /// The datasets the partner governs, wherever they are read.
+    #[serde(default)]
+    pub datasets:  Vec<String>,
+}
+impl FederationPartner {
+    /// Checks whether the partner governs an access.
+    fn governs(&self, access: &Access) -> bool {
+        access.location.as_ref().is_some_and(|location| self.locations.contains(location))
+            || access.dataset.as_ref().is_some_and(|dataset| self.datasets.contains(dataset))
+    }
+}
+
+/// Configures a [`Federation`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FederationConfig {
+    /// The name of our own domain, which partners see in the [`FEDERATION_HEADER`] of forwarded questions.
+    pub domain:   String,
+    /// The partner checkers to forward questions to.
+    #[serde(default)]
+    pub partners: Vec<FederationPartner>,
+}
+
+/// A question to forward to partner checkers once our reasoner allowed it.
+#[derive(Clone, Debug)]
+pub struct Forward {
+    /// The body of the deliberation request, which is forwarded as-is.
+    body:     Value,
+    /// The partners involved in the question.
+    partners: Vec<FederationPartner>,
+}
+
+/***** LIBRARY *****/
+/// Forwards questions about foreign datasets to the partner checkers governing them.
+///
+/// The default Federation has no partners, and never forwards anything.
+#[derive(Debug, Default)]
+pub struct Federation {
+    /// The name of our own domain.
+    domain:   String,
+    /// The partner checkers.
+    partners: Vec<FederationPartner>,
+    /// The client used to ask them.
+    client:   reqwest::Client,
+}
+impl Federation {
+    /// Constructor for the Federation.
+    ///
+    /// # Arguments
+    /// - `config`: The [`FederationConfig`] with our domain and the partner checkers.
+    ///
+    /// # Returns
+    /// A new Federation.
+    pub fn new(config: FederationConfig) -> Self {
+        let client: reqwest::Client = reqwest::Client::builder().timeout(FEDERATION_TIMEOUT).build().unwrap_or_default();
+        Self { domain: config.domain, partners: config.partners, client }
+    }
+
+    /// Returns whether this federation has no partners.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.partners.is_empty() }
+
+    /// Keeps the body of a deliberation request, if it may be forwarded to partners.
+    ///
+    /// # Arguments
+    /// - `body`: The body of the request.
+    /// - `federated_by`: The [`FEDERATION_HEADER`] of the request, if it was forwarded to us by a partner.
+    ///
+    /// # Returns
+    /// The body as JSON, or [`None`] if there are no partners or the request was forwarded to us already.
+    pub(crate) fn forwardable(&self, body: &impl Serialize, federated_by: Option<&str>) -> Option<Value> {
+        if let Some(domain) = federated_by {
+            debug!("Not forwarding request that was forwarded by domain '{domain}'");
+            return None;
+        }
+        if self.partners.is_empty() {
+            return None;
+        }
+        serde_json::to_value(body).ok()
+    }
+
+    /// Decides whether a question is to be forwarded to any partners.
+    ///
+    /// # Arguments
+    /// - `body`: The body of the deliberation request, or [`None`] if it may not be forwarded (because partners
+    ///   forwarded it to us, or there are no partners to forward it to).
+    /// - `workflow`: The [`Workflow`] in question.
+    /// - `question`: What is asked about it.
+    ///
+    /// # Returns
+    /// The [`Forward`] to [`federate()`] once our reasoner answered, or [`None`] if no partner is involved.
+    pub(crate) fn involved(&self, body: Option<Value>, workflow: &Workflow, question: &QuestionKind) -> Option<Forward> {
+        let body: Value = body?;
+        let accesses: Vec<Access> = accesses_of(workflow, question);
+        let partners: Vec<FederationPartner> =
+            self.partners.iter().filter(|partner| accesses.iter().any(|access| partner.governs(access))).cloned().collect();
+        if partners.is_empty() { None } else { Some(Forward { body, partners }) }
+    }
+
+    /// Asks a single partner checker the same question.
+    ///
+    /// # Arguments
+    /// - `partner`: The [`FederationPartner`] to ask.
+    /// - `kind`: The kind of question (one of [`reasonerconn::QUESTION_KINDS`]), which is also the path to post it to.
+    /// - `body`: The body of the deliberation request.
+    ///
+    /// # Returns
+    /// The [`Verdict`] of the partner.
+    ///
+    /// # Errors
+    /// This function errors if the partner could not be reached or did not answer with a verdict.
+    async fn ask(&self, partner: &FederationPartner, kind: &str, body: &Value) -> Result<Verdict, FederationError> {
+        let url: String = format!("{}/v1/deliberation/{kind}", partner.address.trim_end_matches('/'));
+        let mut req = self.client.post(&url).header(FEDERATION_HEADER, &self.domain).json(body);
+        if let Some(token) = &partner.token {
+            req = req.bearer_auth(token);
+        }
+        let res = req
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|err| FederationError::Request { domain: partner.domain.clone(), err })?;
+        res.json().await.map_err(|err| FederationError::Verdict { domain: partner.domain.clone(), err })
+    }
+}
+
+/// Combines our reasoner's answer to a question with those of the partner checkers involved in it.
+///
+/// Partners are only asked if our reasoner allowed the question, since their answers cannot undo our denial.
+///
+/// # Arguments
+/// - `federation`: The [`Federation`] of the server.
+/// - `logger`: The [`AuditLogger`] to log the remote verdicts with.
+/// - `reference`: The verdict reference of the question.
+/// - `kind`: The kind of question (one of [`reasonerconn::QUESTION_KINDS`]).
+/// - `forward`: The [`Forward`] found by [`Federation::involved()`], if any.
+/// - `local`: The [`ReasonerResponse`] of our own reasoner.
+///
+/// # Returns
+/// The combined answer: allowed only if we and all partners involved allow it, and denied with all reasons otherwise.
+/// Partners that could not be asked deny with a [`DenyCategory::BackendError`].
+///
+/// # Errors
+/// This function errors with a rejection of type [`AUDIT_LOG_UNAVAILABLE_PROBLEM`](crate::deliberation::AUDIT_LOG_UNAVAILABLE_PROBLEM)
+/// if a remote verdict could not be logged.
+pub(crate) async fn federate<L: AuditLogger>(
+    federation: &Federation,
+    logger: &L,
+    reference: &str,
+    kind: &str,
+    forward: Option<Forward>,
+    local: ReasonerResponse,
+) -> Result<ReasonerResponse, Rejection> {
+    let Some(Forward { body, partners }) = forward else { return Ok(local) };
+    if !local.success {
+        debug!("Not forwarding denied request to {} partner checker(s) | request id: {reference}", partners.len());
+        return Ok(local);
+    }
+
+    // Ask all partners at once
+    info!("Forwarding request to {} partner checker(s) | request id: {reference}", partners.len());
+    let verdicts: Vec<Result<Verdict, FederationError>> = join_all(partners.iter().map(|partner| federation.ask(partner, kind, &body))).await;
+
+    // Combine their answers with ours, logging each
+    let mut combined: ReasonerResponse = local;
+    for (partner, verdict) in partners.iter().zip(verdicts) {
+        let (remote_reference, allowed, error): (Option<String>, bool, Option<String>) = match verdict {
+            Ok(Verdict::Allow(allow)) => (Some(allow.shared.verdict_reference), true, None),
+            Ok(Verdict::Deny(deny)) => {
+                combined.success = false;
+                for reason in deny.reasons_for_denial.unwrap_or_default() {
+                    combined.errors.push(format!("{}: {reason}", partner.domain));
+                }
+                for category in deny.categories {
+                    if !combined.categories.contains(&category) {
+                        combined.categories.push(category);
+                    }
+                }
+                (Some(deny.shared.verdict_reference), false, None)
+            },
+            Err(err) => {
+                let error: String = causes(&err).join(": ");
+                warn!("{error} | request id: {reference}");
+                combined.success = false;
+                combined.errors.push(format!("{}: the partner checker could not be consulted", partner.domain));
+                if !combined.categories.contains(&DenyCategory::BackendError) {
+                    combined.categories.push(DenyCategory::BackendError);
+                }
+                (None, false, Some(error))
+            },
+        };
+        logger.log_federated_verdict(reference, &partner.domain, remote_reference.as_deref(), allowed, error.as_deref()).await.map_err(|err| {
+            debug!("Could not log federated verdict to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(reference, err)
+        })?;
+    }
+    Ok(combined)
+}
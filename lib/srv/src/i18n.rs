@@ -0,0 +1,161 @@
+//! Implements translating the human-readable reasons in the reasoner's answers to the language of the client.
+//!
+//! Connectors and lint rules phrase their reasons in English (or as keys, like eFLINT violations called
+//! `pub-no-consent`), which is what the audit log keeps. Clients that show denials to end-users can ask for them in
+//! another language with an `Accept-Language` header. The reasoner then looks up every reason in a
+//! [`MessageCatalog`] of key → template maps per language, and answers with the translation if it finds one.
+//!
+//! A reason is looked up by its full text first. Reasons of the form `<key>: <detail>` (e.g., `We do not have
+//! sufficient permissions for dataset: foo`) may also be translated by a template for `<key>`, in which `{detail}` is
+//! replaced by the rest of the reason. Lint findings are looked up as `lint.<rule>` (e.g.,
+//! `lint.task-without-location`), in which `{node}` and `{message}` are replaced by where and what was found.
+
+use std::collections::HashMap;
+
+use deliberation::spec::Verdict;
+use serde::{Deserialize, Serialize};
+use workflow::lint::Finding;
+
+/***** HELPERS *****/
+/// Fills in the placeholders in a template.
+///
+/// # Arguments
+/// - `template`: The template to fill in, with placeholders like `{name}`.
+/// - `args`: The values of the placeholders by name.
+///
+/// # Returns
+/// The template with every known placeholder replaced.
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut res: String = template.into();
+    for (name, value) in args {
+        res = res.replace(&format!("{{{name}}}"), value);
+    }
+    res
+}
+
+/***** LIBRARY *****/
+/// Maps the canonical reasons of the reasoner to their translations, per language.
+///
+/// Deserialized from a map of language tags (e.g., `nl` or `pt-BR`) to maps of keys to templates.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MessageCatalog {
+    /// The templates by key, by language tag.
+    languages: HashMap<String, HashMap<String, String>>,
+}
+impl MessageCatalog {
+    /// Constructor for the MessageCatalog.
+    ///
+    /// # Arguments
+    /// - `languages`: The templates by key, by language tag.
+    ///
+    /// # Returns
+    /// A new MessageCatalog.
+    #[inline]
+    pub fn new(languages: HashMap<String, HashMap<String, String>>) -> Self { Self { languages } }
+
+    /// Picks the language to answer in from the value of an `Accept-Language` header.
+    ///
+    /// Languages are tried in order of preference (their `q`-value), and match a language in the catalog if they are
+    /// the same or if the catalog only has their primary language (e.g., `nl-BE` matches `nl`).
+    ///
+    /// # Arguments
+    /// - `accept_language`: The value of the header, if the client gave one.
+    ///
+    /// # Returns
+    /// The tag of the language in the catalog to answer in, or [`None`] to answer with the canonical reasons.
+    pub fn negotiate(&self, accept_language: Option<&str>) -> Option<&str> {
+        let mut preferences: Vec<(&str, f32)> = accept_language?
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';').map(str::trim);
+                let tag: &str = parts.next().filter(|tag| !tag.is_empty())?;
+                let q: f32 = parts.find_map(|param| param.strip_prefix("q=")).and_then(|q| q.parse().ok()).unwrap_or(1.0);
+                (q > 0.0).then_some((tag, q))
+            })
+            .collect();
+        preferences.sort_by(|(_, lhs), (_, rhs)| rhs.total_cmp(lhs));
+
+        for (tag, _) in preferences {
+            let primary: &str = tag.split('-').next().unwrap_or(tag);
+            let found = self
+                .languages
+                .keys()
+                .find(|lang| lang.eq_ignore_ascii_case(tag))
+                .or_else(|| self.languages.keys().find(|lang| lang.eq_ignore_ascii_case(primary)));
+            if let Some(lang) = found {
+                return Some(lang);
+            }
+        }
+        None
+    }
+
+    /// Translates a key into the given language.
+    ///
+    /// # Arguments
+    /// - `language`: The tag of the language to translate into, as returned by [`MessageCatalog::negotiate()`].
+    /// - `key`: The key to translate.
+    /// - `args`: The values of the placeholders in the template, by name.
+    ///
+    /// # Returns
+    /// The filled-in template, or [`None`] if the catalog has no translation for the key.
+    pub fn translate(&self, language: &str, key: &str, args: &[(&str, &str)]) -> Option<String> {
+        self.languages.get(language)?.get(key).map(|template| fill(template, args))
+    }
+
+    /// Translates a reason for denial into the given language.
+    ///
+    /// # Arguments
+    /// - `language`: The tag of the language to translate into.
+    /// - `reason`: The canonical reason to translate.
+    ///
+    /// # Returns
+    /// The translated reason, or the canonical one if the catalog has no translation for it.
+    pub fn localize_reason(&self, language: &str, reason: &str) -> String {
+        if let Some(res) = self.translate(language, reason, &[]) {
+            return res;
+        }
+        if let Some((key, detail)) = reason.split_once(": ") {
+            if let Some(res) = self.translate(language, key, &[("detail", detail)]) {
+                return res;
+            }
+        }
+        reason.into()
+    }
+
+    /// Translates a lint finding into the given language.
+    ///
+    /// # Arguments
+    /// - `language`: The tag of the language to translate into, if any.
+    /// - `finding`: The [`Finding`] to translate.
+    ///
+    /// # Returns
+    /// The finding as shown to clients, with its message translated if the catalog has a translation for its rule.
+    pub fn localize_finding(&self, language: Option<&str>, finding: &Finding) -> String {
+        let node: &str = finding.node.as_deref().unwrap_or_default();
+        match language.and_then(|lang| self.translate(lang, &format!("lint.{}", finding.rule), &[("node", node), ("message", &finding.message)])) {
+            Some(message) => format!("{}[{}]: {}", finding.level, finding.rule, message),
+            None => finding.to_string(),
+        }
+    }
+
+    /// Translates the reasons of a verdict into the given language.
+    ///
+    /// # Arguments
+    /// - `language`: The tag of the language to translate into, if any.
+    /// - `verdict`: The [`Verdict`] to translate.
+    ///
+    /// # Returns
+    /// The verdict with its reasons for denial translated where possible.
+    pub fn localize_verdict(&self, language: Option<&str>, mut verdict: Verdict) -> Verdict {
+        let Some(language) = language else { return verdict };
+        if let Verdict::Deny(deny) = &mut verdict {
+            if let Some(reasons) = &mut deny.reasons_for_denial {
+                for reason in reasons.iter_mut() {
+                    *reason = self.localize_reason(language, reason);
+                }
+            }
+        }
+        verdict
+    }
+}
@@ -5,25 +5,89 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use ::policy::{Context, PolicyDataAccess, PolicyDataError};
-use audit_logger::AuditLogger;
-use auth_resolver::{AuthContext, AuthResolver};
+use audit_logger::{AuditLogger, PayloadStore};
+use auth_resolver::{AuthContext, AuthFailureReason, AuthResolver, AuthResolverError};
+use deliberation::ids::{IdGenerator, UuidV7Generator};
 use error_trace::trace;
 use log::{debug, error, info, warn};
 use reasonerconn::ReasonerConnector;
 use serde::{Deserialize, Serialize};
 use state_resolver::StateResolver;
+use state_resolver::counters::UsageCounters;
+use state_resolver::duties::DutyStore;
 use tokio::signal::unix::{Signal, SignalKind, signal};
 use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderMap, StatusCode};
 use warp::reject::Rejection;
 use warp::reply::Reply;
+use workflow::lint::{LintConfig, LintLevel};
+use workflow::preprocess::WorkflowLimits;
 
-use crate::problem::Problem;
+use crate::access_tokens::AccessTokenIssuer;
+use crate::disclosure::Disclosure;
+use crate::drafts::DraftStore;
+use crate::fallback::{FallbackBehaviour, VerdictCache};
+use crate::federation::Federation;
+use crate::i18n::MessageCatalog;
+use crate::lockdown::Lockdown;
+use crate::maintenance::{ActiveMaintenance, MaintenanceSchedule};
+use crate::notifications::Notifier;
+use crate::postprocess::VerdictPipeline;
+use crate::prefilter::Prefilter;
+use crate::problem::{Problem, causes, unauthorized};
+use crate::queue::{QuestionQueue, QuestionQueueConfig};
+use crate::results::AsyncResults;
+use crate::signatures::ImageVerifier;
+use crate::what_if::RecentQuestions;
+use crate::workflows::WorkflowStore;
 
+pub mod access_tokens;
+pub mod archive;
+pub mod catalog;
+pub mod counters;
+pub mod coverage;
 pub mod deliberation;
+pub mod disclosure;
+pub mod drafts;
+pub mod duties;
+pub mod fallback;
+pub mod federation;
+pub mod i18n;
+pub mod lockdown;
+pub mod maintenance;
+pub mod metrics;
 pub mod models;
+pub mod notifications;
 pub mod policy;
+pub mod postprocess;
+pub mod precheck;
+pub mod prefilter;
 pub mod problem;
+pub mod query;
+pub mod queue;
 pub mod reasoner_conn_ctx;
+pub mod reload;
+pub mod results;
+pub mod retrospective;
+pub mod session;
+pub mod signatures;
+pub mod simulation;
+pub mod tools;
+pub mod what_if;
+pub mod workflows;
+
+/// A request whose credentials could not be authenticated, as rejected by the authentication filters of the APIs.
+///
+/// It is recovered from by answering with a `401 Unauthorized` problem, after recording the failure in the audit log.
+#[derive(Debug)]
+struct AuthRejection {
+    /// The path of the request (e.g., `/v1/deliberation/execute-task`).
+    route: String,
+    /// Why authentication failed.
+    err:   AuthResolverError,
+}
+impl warp::reject::Reject for AuthRejection {}
 
 /// Function that returns a future that only returns if either SIGTERM or SIGINT has been sent to this process.
 ///
@@ -87,6 +151,60 @@ pub struct Srv<L, C, P, S, PA, DA> {
     stateresolver: S,
     pauthresolver: PA,
     dauthresolver: DA,
+    /// Generates the verdict references and other identifiers handed out by the server.
+    ids: Arc<dyn IdGenerator>,
+    /// What to do when the reasoner connector fails to answer.
+    fallback: FallbackBehaviour,
+    /// The most recent answers of the reasoner, used by [`FallbackBehaviour::Cache`].
+    verdict_cache: VerdictCache,
+    /// The most recently answered questions, such that they can be re-evaluated with changed facts.
+    recent_questions: RecentQuestions,
+    /// The secret key used to sign and verify exported policy archives, if any.
+    archive_key: Option<Vec<u8>>,
+    /// Signs the access tokens given with allowed data-access verdicts, if any are given.
+    access_tokens: Option<AccessTokenIssuer>,
+    /// Notifies the owners of datasets about verdicts on workflows using them, if owners are notified.
+    notifier: Option<Arc<Notifier>>,
+    /// Archives the full payloads of deliberation requests and of the exchanges with the reasoner, if they are archived.
+    payloads: Option<Arc<PayloadStore>>,
+    /// The results of questions answered in the background.
+    async_results: AsyncResults,
+    /// The workflows uploaded by clients, which deliberation requests may refer to instead of giving them inline.
+    workflows: Arc<WorkflowStore>,
+    /// The policies being written, which are checked on every change before they are committed as a version.
+    drafts: DraftStore,
+    /// Limits the number of questions put to the reasoner at once, letting the rest wait by priority.
+    question_queue: QuestionQueue,
+    /// The transformers that may alter the reasoner's answers before they are returned.
+    verdict_pipeline: VerdictPipeline,
+    /// The allow/deny rules that may answer questions before the reasoner is asked.
+    prefilter: Prefilter,
+    /// Whether the reasoner is in emergency lockdown, denying every question.
+    lockdown: Lockdown,
+    /// The recurring maintenance windows of the reasoner backend, during which it is not asked.
+    maintenance: MaintenanceSchedule,
+    /// The partner checkers that questions about their datasets are forwarded to, if any.
+    federation: Federation,
+    /// The redacted summary of the policy that is disclosed to other domains.
+    disclosure: Disclosure,
+    /// Decides which lint rules are run on workflows before deliberating on them, and which of them refuse a workflow.
+    lint: LintConfig,
+    /// Decides what happens to workflows that refer to datasets, locations or users the resolved state doesn't know about.
+    state_check: LintLevel,
+    /// Limits the size and complexity of the workflows the reasoner is asked about.
+    workflow_limits: WorkflowLimits,
+    /// The largest policy that may be pushed, in bytes.
+    max_policy_size: u64,
+    /// Verifies the signatures of the images that run the tasks in a workflow, if any.
+    image_verifier: Option<Box<dyn ImageVerifier>>,
+    /// Counts the usage the reasoner permits, if usage is counted.
+    usage_counters: Option<Box<dyn UsageCounters>>,
+    /// Keeps the duties that come with verdicts, if duties are tracked.
+    duties: Option<Box<dyn DutyStore>>,
+    /// The translations of reasons to the languages clients may ask for.
+    messages: MessageCatalog,
+    /// The hash of the effective context of the reasoner as last logged. Also keeps reloads from running concurrently.
+    effective_context_hash: tokio::sync::Mutex<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -95,6 +213,15 @@ struct PingResponse {
     ping:    String,
 }
 
+#[derive(Serialize)]
+struct HealthResponse {
+    /// `ok`, or `maintenance` while a maintenance window is open.
+    status:      &'static str,
+    /// The maintenance window that is open, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maintenance: Option<ActiveMaintenance>,
+}
+
 impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
 where
     L: 'static + AuditLogger + Send + Sync + Clone,
@@ -114,34 +241,439 @@ where
         pauthresolver: PA,
         dauthresolver: DA,
     ) -> Self {
-        Srv { addr: addr.into(), logger, reasonerconn, policystore, stateresolver, pauthresolver, dauthresolver }
+        let effective_context_hash: String = reload::effective_context_hash::<L, C>(&reasonerconn);
+        Srv {
+            addr: addr.into(),
+            logger,
+            reasonerconn,
+            policystore,
+            stateresolver,
+            pauthresolver,
+            dauthresolver,
+            ids: Arc::new(UuidV7Generator),
+            fallback: FallbackBehaviour::default(),
+            verdict_cache: VerdictCache::default(),
+            recent_questions: RecentQuestions::default(),
+            archive_key: None,
+            access_tokens: None,
+            notifier: None,
+            payloads: None,
+            async_results: AsyncResults::default(),
+            workflows: Arc::new(WorkflowStore::default()),
+            drafts: DraftStore::default(),
+            question_queue: QuestionQueue::default(),
+            verdict_pipeline: VerdictPipeline::default(),
+            prefilter: Prefilter::default(),
+            lockdown: Lockdown::default(),
+            maintenance: MaintenanceSchedule::default(),
+            federation: Federation::default(),
+            disclosure: Disclosure::default(),
+            lint: LintConfig::default(),
+            state_check: LintLevel::Allow,
+            workflow_limits: WorkflowLimits::default(),
+            max_policy_size: crate::policy::DEFAULT_MAX_POLICY_SIZE,
+            image_verifier: None,
+            usage_counters: None,
+            duties: None,
+            messages: MessageCatalog::default(),
+            effective_context_hash: tokio::sync::Mutex::new(effective_context_hash),
+        }
+    }
+
+    /// Sets what the server does when the reasoner connector fails to answer a question.
+    ///
+    /// # Arguments
+    /// - `fallback`: The [`FallbackBehaviour`] to use. Defaults to [`FallbackBehaviour::Deny`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_fallback(mut self, fallback: FallbackBehaviour) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Sets how the verdict references (and other identifiers) handed out by the server are generated.
+    ///
+    /// # Arguments
+    /// - `ids`: The [`IdGenerator`] to use. Defaults to a [`UuidV7Generator`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_id_generator(mut self, ids: impl 'static + IdGenerator) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
+
+    /// Sets the secret key used to sign exported policy archives and to verify imported ones.
+    ///
+    /// Without a key, the export and import endpoints are disabled.
+    ///
+    /// # Arguments
+    /// - `key`: The secret key to sign archives with, if any.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_archive_key(mut self, key: Option<Vec<u8>>) -> Self {
+        self.archive_key = key;
+        self
+    }
+
+    /// Sets the issuer of the access tokens given with allowed data-access verdicts.
+    ///
+    /// Without an issuer, no access tokens are given.
+    ///
+    /// # Arguments
+    /// - `issuer`: The [`AccessTokenIssuer`] to sign tokens with, if any.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_access_token_issuer(mut self, issuer: Option<AccessTokenIssuer>) -> Self {
+        self.access_tokens = issuer;
+        self
+    }
+
+    /// Sets the notifier that tells the owners of datasets about verdicts on workflows using them.
+    ///
+    /// Without a notifier, owners are not notified.
+    ///
+    /// # Arguments
+    /// - `notifier`: The [`Notifier`] to notify owners with, if any.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_notifier(mut self, notifier: Option<Notifier>) -> Self {
+        self.notifier = notifier.map(Arc::new);
+        self
+    }
+
+    /// Sets where the full payloads of deliberation requests, and of the exchanges with the reasoner they lead to, are
+    /// archived.
+    ///
+    /// Without a store, payloads are only logged as far as the audit log logs them.
+    ///
+    /// # Arguments
+    /// - `payloads`: The [`PayloadStore`] to archive them in, if any.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_payload_store(mut self, payloads: Option<PayloadStore>) -> Self {
+        self.payloads = payloads.map(Arc::new);
+        self
+    }
+
+    /// Sets the origins that the results of questions answered in the background may be POSTed to (see [`results`]).
+    ///
+    /// Without any, clients can only poll for results.
+    ///
+    /// # Arguments
+    /// - `origins`: The origins (e.g., `https://example.com`) that callbacks may be on.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_callback_origins(mut self, origins: Vec<String>) -> Self {
+        self.async_results = AsyncResults::new(origins);
+        self
+    }
+
+    /// Sets how many questions are put to the reasoner at once, and how many may wait for their turn.
+    ///
+    /// # Arguments
+    /// - `config`: The [`QuestionQueueConfig`] to use.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_question_queue(mut self, config: QuestionQueueConfig) -> Self {
+        self.question_queue = QuestionQueue::new(config);
+        self
+    }
+
+    /// Sets the transformers that may alter the reasoner's answers before they are returned.
+    ///
+    /// # Arguments
+    /// - `pipeline`: The [`VerdictPipeline`] to pass every answer through. Defaults to an empty one.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_verdict_pipeline(mut self, pipeline: VerdictPipeline) -> Self {
+        self.verdict_pipeline = pipeline;
+        self
+    }
+
+    /// Sets the allow/deny rules that may answer questions before the reasoner is asked.
+    ///
+    /// # Arguments
+    /// - `prefilter`: The [`Prefilter`] to check every question against. Defaults to one without rules.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_prefilter(mut self, prefilter: Prefilter) -> Self {
+        self.prefilter = prefilter;
+        self
+    }
+
+    /// Sets the recurring maintenance windows during which questions are answered without asking the reasoner.
+    ///
+    /// # Arguments
+    /// - `maintenance`: The [`MaintenanceSchedule`] to follow. Defaults to one without windows.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_maintenance(mut self, maintenance: MaintenanceSchedule) -> Self {
+        self.maintenance = maintenance;
+        self
+    }
+
+    /// Sets the partner checkers that questions about datasets of other organizations are forwarded to.
+    ///
+    /// # Arguments
+    /// - `federation`: The [`Federation`] of partner checkers. Defaults to one without partners.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_federation(mut self, federation: Federation) -> Self {
+        self.federation = federation;
+        self
+    }
+
+    /// Sets the redacted summary of the policy that is disclosed to the planners and checkers of other domains.
+    ///
+    /// # Arguments
+    /// - `disclosure`: The [`Disclosure`] with the summary and who may see which of its fields. Defaults to one that discloses nothing.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_disclosure(mut self, disclosure: Disclosure) -> Self {
+        self.disclosure = disclosure;
+        self
+    }
+
+    /// Sets the lint rules that are run on workflows before deliberating on them.
+    ///
+    /// # Arguments
+    /// - `config`: The [`LintConfig`] to lint with. Defaults to running all rules as warnings.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_lint_config(mut self, config: LintConfig) -> Self {
+        self.lint = config;
+        self
+    }
+
+    /// Sets what happens to workflows that refer to datasets, locations or users the resolved state doesn't know about
+    /// (see [`precheck`]).
+    ///
+    /// # Arguments
+    /// - `level`: The [`LintLevel`] to check at: [`LintLevel::Allow`] (don't check; the default), [`LintLevel::Warn`] (log
+    ///   them) or [`LintLevel::Deny`] (refuse the workflow).
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_state_check(mut self, level: LintLevel) -> Self {
+        self.state_check = level;
+        self
+    }
+
+    /// Sets the limits on the size and complexity of workflows, beyond which they are refused without asking the reasoner.
+    ///
+    /// # Arguments
+    /// - `limits`: The [`WorkflowLimits`] workflows must stay within. Defaults to [`WorkflowLimits::default()`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_workflow_limits(mut self, limits: WorkflowLimits) -> Self {
+        self.workflow_limits = limits;
+        self
+    }
+
+    /// Sets the largest policy that may be pushed, beyond which it is refused with a `413 Payload Too Large`.
+    ///
+    /// # Arguments
+    /// - `bytes`: The largest size of a pushed policy (or of the request pushing it), in bytes. Defaults to
+    ///   [`DEFAULT_MAX_POLICY_SIZE`](crate::policy::DEFAULT_MAX_POLICY_SIZE).
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_max_policy_size(mut self, bytes: u64) -> Self {
+        self.max_policy_size = bytes;
+        self
+    }
+
+    /// Sets how the signatures of the images that run the tasks in a workflow are verified.
+    ///
+    /// # Arguments
+    /// - `verifier`: The [`ImageVerifier`] to verify with. If none is set, no image is considered signed.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_image_verifier(mut self, verifier: impl 'static + ImageVerifier) -> Self {
+        self.image_verifier = Some(Box::new(verifier));
+        self
+    }
+
+    /// Enables counting the usage the reasoner permits, and exposing it to policies in the state.
+    ///
+    /// # Arguments
+    /// - `counters`: The [`UsageCounters`] to keep the usage in.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_usage_counters(mut self, counters: impl 'static + UsageCounters) -> Self {
+        self.usage_counters = Some(Box::new(counters));
+        self
+    }
+
+    /// Enables tracking the duties that come with verdicts, and exposing the open ones to policies in the state.
+    ///
+    /// # Arguments
+    /// - `duties`: The [`DutyStore`] to keep the duties in.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_duty_store(mut self, duties: impl 'static + DutyStore) -> Self {
+        self.duties = Some(Box::new(duties));
+        self
+    }
+
+    /// Sets the translations of the reasons given to clients, which they can ask for with an `Accept-Language` header.
+    ///
+    /// # Arguments
+    /// - `messages`: The [`MessageCatalog`] to translate with. Defaults to an empty one, i.e., reasons are never translated.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_message_catalog(mut self, messages: MessageCatalog) -> Self {
+        self.messages = messages;
+        self
     }
 
     fn with_self(this: Arc<Self>) -> impl Filter<Extract = (Arc<Self>,), Error = Infallible> + Clone { warp::any().map(move || this.clone()) }
 
+    /// Authenticates a request, recording as whom in the audit log.
+    ///
+    /// # Arguments
+    /// - `this`: The server, whose audit log to record the authentication in.
+    /// - `resolver`: The [`AuthResolver`] of the API the request is for.
+    /// - `route`: The path of the request.
+    /// - `headers`: The headers of the request, with its credentials.
+    ///
+    /// # Returns
+    /// The [`AuthContext`] of the request.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) if the credentials could not be authenticated. That is recorded in the
+    /// audit log once the rejection is answered.
+    async fn authenticate<A: AuthResolver>(this: &Self, resolver: &A, route: FullPath, headers: HeaderMap) -> Result<AuthContext, Rejection> {
+        match resolver.authenticate(headers).await {
+            Ok(auth_ctx) => {
+                if let Err(err) = this.logger.log_auth_success(&auth_ctx, route.as_str()).await {
+                    error!("{}", trace!(("Failed to log authentication success"), err));
+                }
+                Ok(auth_ctx)
+            },
+            Err(err) => Err(warp::reject::custom(AuthRejection { route: route.as_str().into(), err })),
+        }
+    }
+
     pub async fn run(self) {
         let addr: SocketAddr = self.addr;
         let this_arc: Arc<Self> = Arc::new(self);
 
         let ping = warp::get().and(warp::path("ping")).map(|| warp::reply::json(&PingResponse { success: true, ping: String::from("pong") }));
+        let health = warp::get().and(warp::path("health")).and(Self::with_self(this_arc.clone())).map(|this: Arc<Self>| {
+            let maintenance: Option<ActiveMaintenance> = this.maintenance.active(chrono::Utc::now());
+            warp::reply::json(&HealthResponse { status: if maintenance.is_some() { "maintenance" } else { "ok" }, maintenance })
+        });
         let policy_api = Self::policy_handlers(this_arc.clone());
+        let drafts_api = Self::drafts_handlers(this_arc.clone());
         let reasoner_conn_api = Self::reasoner_connector_handlers(this_arc.clone());
         let deliberation_api = Self::deliberation_handlers(this_arc.clone());
+        let metrics_api = Self::metrics_handlers(this_arc.clone());
+        let archive_api = Self::archive_handlers(this_arc.clone());
+        let tools_api = Self::tools_handlers(this_arc.clone());
+        let catalog_api = Self::catalog_handlers(this_arc.clone());
+        let retrospective_api = Self::retrospective_handlers(this_arc.clone());
+        let duties_api = Self::duties_handlers(this_arc.clone());
+        let reload_api = Self::reload_handlers(this_arc.clone());
+        let session_api = Self::session_handlers(this_arc.clone());
+        let query_api = Self::query_handlers(this_arc.clone());
+        let what_if_api = Self::what_if_handlers(this_arc.clone());
+        let workflows_api = Self::workflows_handlers(this_arc.clone());
+        let lockdown_api = Self::lockdown_handlers(this_arc.clone());
+        let disclosure_api = Self::disclosure_handlers(this_arc.clone());
 
-        let index = warp::any().and(deliberation_api.or(policy_api).or(reasoner_conn_api).or(ping)).recover(|err: Rejection| async move {
-            debug!("err: {:?}", err);
-            let res: Result<Box<dyn Reply>, Rejection> = if let Some(auth_resolver::AuthResolverError { .. }) = err.find() {
-                Ok(Box::new(warp::reply::with_status(warp::reply::reply(), warp::http::StatusCode::UNAUTHORIZED)))
-            } else if let Some(audit_logger::Error::CouldNotDeliver { .. }) = err.find() {
-                Ok(Box::new(warp::reply::with_status(warp::reply::reply(), warp::http::StatusCode::INTERNAL_SERVER_ERROR)))
-            } else if let Some(problem) = err.find::<Problem>() {
-                Ok(Box::new(warp::reply::with_status(warp::reply::json(&problem.0), problem.0.status.unwrap())))
-            } else {
-                debug!("Got err: {:?}", err);
-                Err(err)
-            };
-            res
-        });
+        let logger: L = this_arc.logger.clone();
+        let index = warp::any()
+            .and(
+                deliberation_api
+                    .or(session_api)
+                    .or(query_api)
+                    .or(what_if_api)
+                    .or(workflows_api)
+                    .or(policy_api)
+                    .or(drafts_api)
+                    .or(reasoner_conn_api)
+                    .or(metrics_api)
+                    .or(archive_api)
+                    .or(tools_api)
+                    .or(catalog_api)
+                    .or(retrospective_api)
+                    .or(duties_api)
+                    .or(reload_api)
+                    .or(lockdown_api)
+                    .or(disclosure_api)
+                    .or(ping)
+                    .or(health),
+            )
+            .recover(move |err: Rejection| {
+                let logger: L = logger.clone();
+                async move {
+                    debug!("err: {:?}", err);
+                    let res: Result<Box<dyn Reply>, Rejection> = if let Some(AuthRejection { route, err }) = err.find::<AuthRejection>() {
+                        // Keep the underlying cause (e.g., why a token did not decode) in both the audit log and the problem
+                        let (reason, detail): (AuthFailureReason, String) = (err.reason(), causes(err).join(": "));
+                        info!("Refusing unauthenticated request to '{route}' ({reason}): {detail}");
+                        if let Err(err) = logger.log_auth_failure(route, &reason.to_string(), &detail, err.initiator_hint()).await {
+                            error!("{}", trace!(("Failed to log authentication failure"), err));
+                        }
+                        let p = unauthorized(reason, detail);
+                        Ok(Box::new(warp::reply::with_status(warp::reply::json(&p), StatusCode::UNAUTHORIZED)))
+                    } else if let Some(audit_logger::Error::CouldNotDeliver { .. }) = err.find() {
+                        Ok(Box::new(warp::reply::with_status(warp::reply::reply(), warp::http::StatusCode::INTERNAL_SERVER_ERROR)))
+                    } else if let Some(problem) = err.find::<Problem>() {
+                        let reply = warp::reply::with_status(warp::reply::json(&problem.0), problem.0.status.unwrap());
+                        // Problems saying when to ask again (e.g., during maintenance) say so in the standard header too
+                        match problem.0.extensions.get("retry_after").and_then(serde_json::Value::as_u64) {
+                            Some(retry_after) => Ok(Box::new(warp::reply::with_header(reply, "Retry-After", retry_after.to_string()))),
+                            None => Ok(Box::new(reply)),
+                        }
+                    } else {
+                        debug!("Got err: {:?}", err);
+                        Err(err)
+                    };
+                    res
+                }
+            });
 
         // Log reasoner connector context
         let ctx_hash = C::hash();
@@ -150,17 +682,32 @@ where
             Err(err) => panic!("Failed to log reasoner context on startup {:?}", err),
         }
 
-        // Disable active policy if base definitions changed
+        // Reload the configuration on SIGHUP from now on
+        tokio::spawn(Self::reload_on_hangup(this_arc.clone()));
+        // Keep track of the maintenance windows in the background
+        tokio::spawn(Self::watch_maintenance(this_arc.clone()));
+        // Remove archived payloads once they are too old
+        if let Some(payloads) = &this_arc.payloads {
+            tokio::spawn(crate::deliberation::prune_payloads(payloads.clone()));
+        }
+
+        // Disable active policy if base definitions changed or the reasoner connector cannot prepare for it
         if let Ok(v) = this_arc.policystore.get_active().await {
             let t = this_arc.clone();
-            if v.version.reasoner_connector_context != ctx_hash {
-                let ap = this_arc.policystore.get_active().await.unwrap();
+            let unusable: Option<String> = if v.version.reasoner_connector_context != ctx_hash {
+                Some(format!("base definition changed; hash changed from '{}' to '{ctx_hash}'", v.version.reasoner_connector_context))
+            } else if let Err(err) = t.reasonerconn.on_policy_activated(&v).await {
+                Some(trace!(("reasoner connector cannot prepare for it"), err).to_string())
+            } else {
+                None
+            };
+            if let Some(reason) = unusable {
                 let result = t
                     .policystore
                     .deactivate_policy(Context { initiator: "system".into() }, || async move {
                         this_arc
                             .logger
-                            .log_deactivate_policy(&AuthContext { initiator: "system".into(), system: "self".into() })
+                            .log_deactivate_policy(&AuthContext { initiator: "system".into(), system: "self".into(), actor: None })
                             .await
                             .map_err(|err| PolicyDataError::GeneralError(err.to_string()))
                     })
@@ -169,14 +716,11 @@ where
                 match result {
                     Ok(_) => {},
                     Err(err) => {
-                        panic!("Could not deactivate policy because of changed base definition: {:?}", err);
+                        panic!("Could not deactivate policy because {reason}: {:?}", err);
                     },
                 }
 
-                debug!(
-                    "Deactivated policy because of changed base definition; hash changed from '{}' to '{}'",
-                    ap.version.reasoner_connector_context, ctx_hash
-                )
+                warn!("Deactivated policy version {} because {reason}", v.version.version.unwrap_or(-1))
             }
         }
 
@@ -0,0 +1,201 @@
+//! Implements an emergency lockdown that denies every deliberation request until it is lifted.
+//!
+//! During an incident (e.g., a compromised worker or a leaked policy), administrators may want to stop all data
+//! processing at once, without first writing and activating a policy that denies everything. A
+//! `POST /v1/management/lockdown` engages (or lifts) the [`Lockdown`]; while engaged, every question is denied with a
+//! [`DenyCategory::Lockdown`] without asking the reasoner, whatever the policy says. Engaging and lifting it is recorded
+//! in the audit log together with who did so. Note that the lockdown is not persisted, so restarting the reasoner lifts
+//! it.
+
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use chrono::{DateTime, Utc};
+use deliberation::spec::DenyCategory;
+use log::{debug, info, warn};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnector, ReasonerResponse};
+use serde::{Deserialize, Serialize};
+use state_resolver::StateResolver;
+use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderMap, StatusCode};
+use warp::reject::Rejection;
+
+use crate::Srv;
+use crate::problem::Problem;
+
+/***** CONSTANTS *****/
+/// The reason for denial given to clients while the lockdown is engaged, unless another one is given.
+pub const DEFAULT_LOCKDOWN_REASON: &str = "The policy reasoner is in emergency lockdown";
+
+/***** AUXILLARY *****/
+/// Describes whether the lockdown is engaged, and by whom.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct LockdownStatus {
+    /// Whether all requests are denied.
+    pub engaged:    bool,
+    /// Who engaged the lockdown, if it is engaged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engaged_by: Option<String>,
+    /// When the lockdown was engaged, if it is engaged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since:      Option<DateTime<Utc>>,
+    /// The reason for denial given to clients, if it is engaged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason:     Option<String>,
+}
+
+/// The body of a `POST /v1/management/lockdown`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LockdownRequest {
+    /// Whether to engage (`true`) or lift (`false`) the lockdown.
+    pub engaged: bool,
+    /// The reason for denial given to clients while it is engaged. Defaults to [`DEFAULT_LOCKDOWN_REASON`].
+    #[serde(default)]
+    pub reason:  Option<String>,
+}
+
+/***** HELPERS *****/
+/// Logs that the lockdown was engaged or lifted.
+///
+/// # Arguments
+/// - `logger`: The [`AuditLogger`] to log to.
+/// - `auth_ctx`: The [`AuthContext`] of who engaged or lifted it.
+/// - `status`: The new [`LockdownStatus`].
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `500 Internal Server Error` if the change could not be logged.
+async fn log_lockdown<L: AuditLogger>(logger: &L, auth_ctx: &AuthContext, status: &LockdownStatus) -> Result<(), Rejection> {
+    logger.log_lockdown(auth_ctx, status.engaged, status.reason.as_deref()).await.map_err(|err| {
+        debug!("Could not log lockdown to audit log : {:?}", err);
+        let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(format!("Failed to log lockdown: {err}"));
+        warp::reject::custom(Problem::from(p))
+    })
+}
+
+/***** LIBRARY *****/
+/// Keeps whether the reasoner is in emergency lockdown.
+#[derive(Debug, Default)]
+pub struct Lockdown {
+    /// The current status.
+    status: RwLock<LockdownStatus>,
+}
+impl Lockdown {
+    /// Returns the current status of the lockdown.
+    #[inline]
+    pub fn status(&self) -> LockdownStatus { self.status.read().unwrap_or_else(|err| err.into_inner()).clone() }
+
+    /// Returns whether the lockdown is engaged.
+    #[inline]
+    pub fn is_engaged(&self) -> bool { self.status.read().unwrap_or_else(|err| err.into_inner()).engaged }
+
+    /// Engages or lifts the lockdown.
+    ///
+    /// # Arguments
+    /// - `initiator`: Who engages or lifts it.
+    /// - `request`: The [`LockdownRequest`] saying what to do.
+    ///
+    /// # Returns
+    /// The new [`LockdownStatus`].
+    pub fn set(&self, initiator: &str, request: LockdownRequest) -> LockdownStatus {
+        let status: LockdownStatus = if request.engaged {
+            LockdownStatus {
+                engaged:    true,
+                engaged_by: Some(initiator.into()),
+                since:      Some(Utc::now()),
+                reason:     Some(request.reason.unwrap_or_else(|| DEFAULT_LOCKDOWN_REASON.into())),
+            }
+        } else {
+            LockdownStatus::default()
+        };
+        *self.status.write().unwrap_or_else(|err| err.into_inner()) = status.clone();
+        status
+    }
+
+    /// Returns the answer to every question while the lockdown is engaged.
+    ///
+    /// # Returns
+    /// A denial with a [`DenyCategory::Lockdown`], or [`None`] if the lockdown is not engaged.
+    pub fn denial(&self) -> Option<ReasonerResponse> {
+        let status: LockdownStatus = self.status();
+        if !status.engaged {
+            return None;
+        }
+        Some(ReasonerResponse::new(false, status.reason.into_iter().collect()).with_categories(vec![DenyCategory::Lockdown]))
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // Get whether the reasoner is in emergency lockdown
+    // GET /v1/management/lockdown
+    // out:
+    // 200 LockdownStatus
+
+    async fn handle_get_lockdown(_auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        Ok(warp::reply::json(&this.lockdown.status()))
+    }
+
+    // Engage or lift the emergency lockdown
+    // POST /v1/management/lockdown
+    // in: LockdownRequest
+    // out:
+    // 200 LockdownStatus
+    // 500 problem+json (failed to log the change; an engaged lockdown is engaged anyway, but it is never lifted)
+
+    async fn handle_set_lockdown(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: LockdownRequest,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        // Engage before logging, such that no request slips through while the audit log is slow; but only lift once logged
+        let status: LockdownStatus = if body.engaged {
+            warn!("Emergency lockdown engaged by '{}'; denying all requests until it is lifted", auth_ctx.initiator);
+            let status: LockdownStatus = this.lockdown.set(&auth_ctx.initiator, body);
+            log_lockdown(&this.logger, &auth_ctx, &status).await?;
+            status
+        } else {
+            info!("Handling request by '{}' to lift the emergency lockdown", auth_ctx.initiator);
+            log_lockdown(&this.logger, &auth_ctx, &LockdownStatus::default()).await?;
+            this.lockdown.set(&auth_ctx.initiator, body)
+        };
+        Ok(warp::reply::json(&status))
+    }
+
+    pub fn lockdown_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let get_lockdown = warp::get()
+            .and(warp::path!("management" / "lockdown"))
+            .and(Self::with_lockdown_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_lockdown);
+
+        let set_lockdown = warp::post()
+            .and(warp::path!("management" / "lockdown"))
+            .and(Self::with_lockdown_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_set_lockdown);
+
+        warp::path("v1").and(get_lockdown.or(set_lockdown))
+    }
+
+    fn with_lockdown_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.pauthresolver, route, headers).await
+            },
+        )
+    }
+}
@@ -0,0 +1,213 @@
+//! Implements recurring maintenance windows, during which questions are answered without the reasoner backend.
+//!
+//! Backends like an eFLINT server are sometimes taken down on purpose (e.g., every Sunday night to be updated). Rather
+//! than letting every question during that time fail with a confusing [`REASONER_UNAVAILABLE_PROBLEM`](crate::fallback::REASONER_UNAVAILABLE_PROBLEM),
+//! operators can describe the downtime as a [`MaintenanceSchedule`] of [`RecurringMaintenance`] windows. While one is
+//! open, questions are answered according to its [`MaintenanceBehaviour`] without asking the reasoner, which is
+//! recorded in the audit log as a fallback. The window that is open (if any) is also reported by `GET /health`, and a
+//! background task logs whenever one opens or closes.
+//!
+//! Note that the one-off maintenance window of the [`VerdictPipeline`](crate::postprocess::VerdictPipeline) still asks
+//! the reasoner, and only denies what it allows.
+
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+use std::sync::Arc;
+use std::time::Duration;
+
+use audit_logger::AuditLogger;
+use auth_resolver::AuthResolver;
+use chrono::{DateTime, Datelike as _, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use log::{info, warn};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use state_resolver::StateResolver;
+use warp::http::StatusCode;
+use warp::reject::Rejection;
+
+use crate::Srv;
+use crate::problem::Problem;
+
+/***** CONSTANTS *****/
+/// The problem type returned to clients that should ask again once a maintenance window closes.
+pub const MAINTENANCE_PROBLEM: &'static str = "/problems/maintenance";
+
+/// How often the background task checks whether a maintenance window opened or closed.
+const MAINTENANCE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/***** AUXILLARY *****/
+/// Defines how questions are answered while a maintenance window is open.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaintenanceBehaviour {
+    /// Allow every question.
+    Allow,
+    /// Deny every question, with the reason of the window.
+    Deny,
+    /// Refuse to answer, returning a `503 Service Unavailable` problem of type [`MAINTENANCE_PROBLEM`] that says when to ask again.
+    #[default]
+    RetryLater,
+}
+impl Display for MaintenanceBehaviour {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Deny => write!(f, "deny"),
+            Self::RetryLater => write!(f, "retry-later"),
+        }
+    }
+}
+
+/// A maintenance window that opens at the same time on given days of the week.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecurringMaintenance {
+    /// Identifies the window in logs and in `GET /health`.
+    pub name: String,
+    /// The days of the week (e.g., `Sun`) on which the window opens. Empty means every day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// The time (in UTC) at which the window opens, e.g., `"22:00:00"`.
+    pub start: NaiveTime,
+    /// How long the window stays open, in minutes. Windows may extend past midnight.
+    pub duration_mins: u32,
+    /// How questions are answered while the window is open.
+    #[serde(default)]
+    pub behaviour: MaintenanceBehaviour,
+    /// The reason given to clients while the window is open, if any.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+impl RecurringMaintenance {
+    /// Finds when the window closes, if it is open.
+    ///
+    /// # Arguments
+    /// - `now`: The moment to check.
+    ///
+    /// # Returns
+    /// The moment the window closes, or [`None`] if it is not open at `now`.
+    pub fn open_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let duration: TimeDelta = TimeDelta::minutes(self.duration_mins.into());
+        // The window may have opened on an earlier day if it is longer than the time since midnight
+        (0..=duration.num_days() + 1).find_map(|days_ago| {
+            let date: NaiveDate = now.date_naive() - TimeDelta::days(days_ago);
+            if !self.days.is_empty() && !self.days.contains(&date.weekday()) {
+                return None;
+            }
+            let from: DateTime<Utc> = date.and_time(self.start).and_utc();
+            let until: DateTime<Utc> = from + duration;
+            (from <= now && now < until).then_some(until)
+        })
+    }
+}
+
+/// Describes the maintenance window that is open at some moment.
+#[derive(Clone, Debug, Serialize)]
+pub struct ActiveMaintenance {
+    /// The name of the window.
+    pub name:      String,
+    /// How questions are answered while it is open.
+    pub behaviour: MaintenanceBehaviour,
+    /// The moment it closes.
+    pub until:     DateTime<Utc>,
+    /// The reason given to clients.
+    pub reason:    String,
+}
+impl ActiveMaintenance {
+    /// Builds the rejection for a question while this window is open with [`MaintenanceBehaviour::RetryLater`].
+    ///
+    /// # Returns
+    /// A [`Rejection`] carrying a `503 Service Unavailable` problem of type [`MAINTENANCE_PROBLEM`], which says when to
+    /// ask again in its `retry_after` member (in seconds).
+    pub fn problem(&self) -> Rejection {
+        let retry_after: i64 = (self.until - Utc::now()).num_seconds().max(1);
+        let mut problem = Problem::from(
+            ProblemDetails::new()
+                .with_type(warp::http::Uri::from_static(MAINTENANCE_PROBLEM))
+                .with_title("Under maintenance")
+                .with_status(StatusCode::SERVICE_UNAVAILABLE)
+                .with_detail(format!("{} (maintenance window '{}' closes at {})", self.reason, self.name, self.until)),
+        );
+        problem.0.extensions.insert("retry_after".into(), Value::from(retry_after));
+        warp::reject::custom(problem)
+    }
+}
+
+/***** LIBRARY *****/
+/// Keeps the recurring maintenance windows of the reasoner backend.
+///
+/// The default MaintenanceSchedule has no windows, and never answers a question.
+#[derive(Clone, Debug, Default)]
+pub struct MaintenanceSchedule {
+    /// The windows, in order of precedence.
+    windows: Vec<RecurringMaintenance>,
+}
+impl MaintenanceSchedule {
+    /// Constructor for the MaintenanceSchedule.
+    ///
+    /// # Arguments
+    /// - `windows`: The [`RecurringMaintenance`] windows. If several are open at once, the first one decides.
+    ///
+    /// # Returns
+    /// A new MaintenanceSchedule.
+    #[inline]
+    pub fn new(windows: impl IntoIterator<Item = RecurringMaintenance>) -> Self { Self { windows: windows.into_iter().collect() } }
+
+    /// Returns whether this schedule has no windows.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.windows.is_empty() }
+
+    /// Finds the window that is open at some moment, if any.
+    ///
+    /// # Arguments
+    /// - `now`: The moment to check.
+    ///
+    /// # Returns
+    /// The [`ActiveMaintenance`] of the first window that is open, or [`None`] if the reasoner should be asked as usual.
+    pub fn active(&self, now: DateTime<Utc>) -> Option<ActiveMaintenance> {
+        self.windows.iter().find_map(|window| {
+            let until: DateTime<Utc> = window.open_until(now)?;
+            Some(ActiveMaintenance {
+                name: window.name.clone(),
+                behaviour: window.behaviour,
+                until,
+                reason: window.reason.clone().unwrap_or_else(|| format!("The policy reasoner is under maintenance until {until}")),
+            })
+        })
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Logs whenever a maintenance window opens or closes.
+    ///
+    /// Runs until the server stops, or returns immediately if there are no maintenance windows.
+    pub(crate) async fn watch_maintenance(this: Arc<Self>) {
+        if this.maintenance.is_empty() {
+            return;
+        }
+        let mut open: Option<String> = None;
+        loop {
+            let active: Option<ActiveMaintenance> = this.maintenance.active(Utc::now());
+            if active.as_ref().map(|window| &window.name) != open.as_ref() {
+                if let Some(name) = &open {
+                    info!("Maintenance window '{name}' closed; asking the reasoner again");
+                }
+                if let Some(window) = &active {
+                    warn!("Maintenance window '{}' opened until {}; answering questions with '{}'", window.name, window.until, window.behaviour);
+                }
+                open = active.map(|window| window.name);
+            }
+            tokio::time::sleep(MAINTENANCE_CHECK_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::{Debug, Write as _};
+use std::sync::Arc;
+
+use audit_logger::AuditLogger;
+use auth_resolver::AuthResolver;
+use policy::PolicyDataAccess;
+use reasonerconn::{ReasonerConnector, ReasonerMetric};
+use serde::Serialize;
+use state_resolver::StateResolver;
+use warp::Filter;
+
+use crate::Srv;
+
+/// Renders metrics in the Prometheus text exposition format.
+///
+/// # Arguments
+/// - `metrics`: The [`ReasonerMetric`]s to render. Metrics with the same name are expected to be adjacent.
+///
+/// # Returns
+/// The rendered metrics.
+fn render(metrics: &[ReasonerMetric]) -> String {
+    let mut out: String = String::new();
+    let mut last: Option<&str> = None;
+    for metric in metrics {
+        if last != Some(metric.name.as_str()) {
+            let kind: &str = if metric.name.ends_with("_total") { "counter" } else { "gauge" };
+            let _ = writeln!(out, "# HELP {} {}", metric.name, metric.help);
+            let _ = writeln!(out, "# TYPE {} {}", metric.name, kind);
+            last = Some(&metric.name);
+        }
+        out.push_str(&metric.name);
+        if !metric.labels.is_empty() {
+            let labels: Vec<String> =
+                metric.labels.iter().map(|(key, value)| format!("{key}=\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))).collect();
+            let _ = write!(out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(out, " {}", metric.value);
+    }
+    out
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // Get the current metrics (e.g., the state of the reasoner's circuit breaker and the question queue)
+    // GET /metrics
+    // out:
+    // 200 (Prometheus text format)
+
+    async fn handle_metrics(this: Arc<Self>) -> Result<warp::reply::WithHeader<String>, warp::reject::Rejection> {
+        let mut metrics: Vec<ReasonerMetric> = this.reasonerconn.metrics();
+        metrics.extend(this.question_queue.metrics());
+        Ok(warp::reply::with_header(render(&metrics), "Content-Type", "text/plain; version=0.0.4"))
+    }
+
+    pub fn metrics_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::get().and(warp::path("metrics")).and(warp::path::end()).and(Self::with_self(this.clone())).and_then(Self::handle_metrics)
+    }
+}
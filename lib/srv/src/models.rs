@@ -1,11 +1,85 @@
-use policy::{Policy, PolicyContent, PolicyVersion};
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use deliberation::spec::Verdict;
+use policy::{Policy, PolicyContent, PolicyMetadata, PolicyVersion};
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::results::AsyncResult;
+use crate::simulation::RecordedQuestion;
 
 #[derive(Deserialize, Serialize)]
 pub struct SetVersionPostModel {
     pub version: i64,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct ComposePostModel {
+    /// The versions of the policy fragments to compose, typically one per department.
+    pub fragments:   Vec<i64>,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportResultModel {
+    /// The policy versions now in the store.
+    pub versions: Vec<i64>,
+    /// The version that is now active, if any.
+    pub active:   Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AsyncQuery {
+    /// Whether to answer the question in the background instead of right away.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
+    /// Where to POST the [`AsyncResultModel`] to once the question has been answered, if anywhere.
+    pub callback:     Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AsyncAcceptedModel {
+    /// The reference under which the result can be retrieved.
+    pub reference: String,
+    /// Where the result can be retrieved.
+    pub location:  String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AsyncResultModel {
+    pub reference: String,
+    #[serde(flatten)]
+    pub result:    AsyncResult,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SimulatePostModel {
+    pub questions: Vec<RecordedQuestion>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RetrospectivePostModel<R> {
+    /// The moment as of which to answer the question.
+    pub as_of:   DateTime<Local>,
+    /// The question, as it would be asked to the deliberation API.
+    #[serde(flatten)]
+    pub request: R,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RetrospectiveResultModel {
+    pub as_of: DateTime<Local>,
+    /// The policy version that was active at `as_of`.
+    pub policy: i64,
+    /// Whether the state of `as_of` was used (true), or the current one because no snapshot of it is known (false).
+    pub state_snapshot: bool,
+    /// Whether the reasoner's base specification changed since the policy was active, which may affect the verdict.
+    pub connector_context_changed: bool,
+    #[serde(flatten)]
+    pub verdict: Verdict,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct PolicyContentPostModel {
     pub reasoner: String,
@@ -18,27 +92,225 @@ pub struct AddPolicyPostModel {
     pub description: Option<String>,
     pub version_description: String,
     pub content: Vec<PolicyContentPostModel>,
+    #[serde(flatten)]
+    pub metadata: PolicyMetadata,
 }
 
 impl AddPolicyPostModel {
-    pub fn to_domain(&self) -> Policy {
+    /// Converts the request into a [`Policy`], moving the (potentially large) contents instead of copying them.
+    pub fn into_domain(self) -> Policy {
         Policy {
-            description: match self.description.clone() {
-                Some(d) => d,
-                None => "".into(),
-            },
+            description: self.description.unwrap_or_default(),
             version:     PolicyVersion {
                 creator: None,
                 created_at: chrono::Local::now(),
                 version: None,
-                version_description: self.version_description.clone(),
+                version_description: self.version_description,
                 reasoner_connector_context: "".into(),
+                metadata: self.metadata,
+                content_hashes: Vec::new(),
+                external_id: None,
             },
             content:     self
                 .content
-                .iter()
-                .map(|c| PolicyContent { reasoner: c.reasoner.clone(), reasoner_version: c.reasoner_version.clone(), content: c.content.clone() })
+                .into_iter()
+                .map(|c| PolicyContent { reasoner: c.reasoner, reasoner_version: c.reasoner_version, content: c.content.into() })
                 .collect(),
         }
     }
 }
+
+/// The query of a streamed `POST /v1/management/policies`, which carries everything of an [`AddPolicyPostModel`] except
+/// for its (single) content. That is the body of the request instead.
+#[derive(Deserialize, Serialize)]
+pub struct AddPolicyStreamQueryModel {
+    pub description: Option<String>,
+    pub version_description: String,
+    pub reasoner: String,
+    pub reasoner_version: String,
+    /// The tags to attach to the version, separated by commas.
+    pub tags: Option<String>,
+    pub jurisdiction: Option<String>,
+    pub department: Option<String>,
+}
+
+impl AddPolicyStreamQueryModel {
+    /// Converts the request into a [`Policy`] with the given, streamed `content`.
+    pub fn into_domain(self, content: Arc<RawValue>) -> Policy {
+        Policy {
+            description: self.description.unwrap_or_default(),
+            version:     PolicyVersion {
+                creator: None,
+                created_at: chrono::Local::now(),
+                version: None,
+                version_description: self.version_description,
+                reasoner_connector_context: "".into(),
+                metadata: PolicyMetadata {
+                    tags: self.tags.iter().flat_map(|tags| tags.split(',')).filter(|tag| !tag.is_empty()).map(String::from).collect(),
+                    jurisdiction: self.jurisdiction,
+                    department: self.department,
+                },
+                content_hashes: Vec::new(),
+                external_id: None,
+            },
+            content:     vec![PolicyContent { reasoner: self.reasoner, reasoner_version: self.reasoner_version, content }],
+        }
+    }
+}
+
+/// Builds the version of a policy derived from `base`, which is only numbered once it's stored.
+fn derived_version(base: PolicyVersion, version_description: String) -> PolicyVersion {
+    PolicyVersion {
+        creator: None,
+        created_at: chrono::Local::now(),
+        version: None,
+        version_description,
+        reasoner_connector_context: "".into(),
+        metadata: base.metadata,
+        content_hashes: Vec::new(),
+        external_id: None,
+    }
+}
+
+/// The body of a `POST /v1/management/policies/:version/clone`.
+#[derive(Default, Deserialize, Serialize)]
+pub struct ClonePolicyPostModel {
+    /// The description of the clone. Omit to keep that of the original.
+    pub description: Option<String>,
+    /// The version description of the clone. Omit to generate one from the original's ID.
+    pub version_description: Option<String>,
+}
+
+impl ClonePolicyPostModel {
+    /// Turns a stored policy into a new [`Policy`] with the same contents and metadata.
+    pub fn apply(self, base: Policy) -> Policy {
+        let version_description: String = self
+            .version_description
+            .unwrap_or_else(|| format!("Clone of version {}", base.version.version.map(|v| v.to_string()).unwrap_or_default()));
+        Policy {
+            description: self.description.unwrap_or(base.description),
+            version:     derived_version(base.version, version_description),
+            content:     base.content,
+        }
+    }
+}
+
+/// A content of a policy to add or replace with a `PATCH /v1/management/policies/:version`.
+#[derive(Deserialize, Serialize)]
+pub struct PatchPolicyContentModel {
+    /// The reasoner of the content. Any content for the same reasoner is replaced.
+    pub reasoner: String,
+    /// The version of the reasoner. Omit to keep that of the replaced content.
+    pub reasoner_version: Option<String>,
+    pub content: Box<serde_json::value::RawValue>,
+}
+
+/// The body of a `PATCH /v1/management/policies/:version`, which derives a new version from an existing one by only
+/// changing some of its contents.
+#[derive(Deserialize, Serialize)]
+pub struct PatchPolicyModel {
+    /// The description of the new version. Omit to keep that of the original.
+    pub description: Option<String>,
+    pub version_description: String,
+    /// The contents to add, or to replace those for the same reasoner with.
+    #[serde(default)]
+    pub content: Vec<PatchPolicyContentModel>,
+    /// The reasoners whose contents to remove.
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Adds, replaces and removes the contents of a policy by reasoner.
+///
+/// # Errors
+/// This function errors with a reason to give to the client if the changes are ambiguous (e.g., they change the same
+/// content twice) or remove content that isn't there.
+pub(crate) fn patch_contents(
+    mut content: Vec<PolicyContent>,
+    patch: Vec<PatchPolicyContentModel>,
+    remove: &[String],
+) -> Result<Vec<PolicyContent>, String> {
+    for reasoner in remove {
+        let n: usize = content.len();
+        content.retain(|c| &c.reasoner != reasoner);
+        if content.len() == n {
+            return Err(format!("Cannot remove content for reasoner '{reasoner}', as the policy has none"));
+        }
+    }
+    let mut patched: Vec<String> = Vec::with_capacity(patch.len());
+    for c in patch {
+        if remove.contains(&c.reasoner) || patched.contains(&c.reasoner) {
+            return Err(format!("Patch changes the content for reasoner '{}' more than once", c.reasoner));
+        }
+        let reasoner_version: String = match (c.reasoner_version, content.iter().find(|old| old.reasoner == c.reasoner)) {
+            (Some(reasoner_version), _) => reasoner_version,
+            (None, Some(old)) => old.reasoner_version.clone(),
+            (None, None) => return Err(format!("Patch adds content for reasoner '{}' without a reasoner version", c.reasoner)),
+        };
+        patched.push(c.reasoner.clone());
+        let new = PolicyContent { reasoner: c.reasoner, reasoner_version, content: c.content.into() };
+        match content.iter_mut().find(|old| old.reasoner == new.reasoner) {
+            Some(old) => *old = new,
+            None => content.push(new),
+        }
+    }
+    Ok(content)
+}
+
+impl PatchPolicyModel {
+    /// Applies the patch to a stored policy, giving the new [`Policy`] with the same metadata.
+    ///
+    /// # Errors
+    /// This function errors with a reason to give to the client if the patch is ambiguous (e.g., it changes the same
+    /// content twice), removes content that isn't there or would leave the policy without any.
+    pub fn apply(self, base: Policy) -> Result<Policy, String> {
+        if self.content.is_empty() && self.remove.is_empty() {
+            return Err("Patch does not change any content".into());
+        }
+
+        let content: Vec<PolicyContent> = patch_contents(base.content, self.content, &self.remove)?;
+        if content.is_empty() {
+            return Err("Patch removes all content of the policy".into());
+        }
+
+        Ok(Policy {
+            description: self.description.unwrap_or(base.description),
+            version: derived_version(base.version, self.version_description),
+            content,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PreprocessPostModel {
+    pub workflow: brane_ast::Workflow,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PreprocessResultModel {
+    /// The WIR after simplification (e.g., with functions inlined), if that succeeded.
+    pub simplified: Option<brane_ast::Workflow>,
+    /// How much the workflow grew by simplifying it, if that succeeded.
+    pub stats:      Option<workflow::diagnostics::InlineStats>,
+    /// The workflow as the reasoner sees it, if compiling it succeeded.
+    pub workflow:   Option<workflow::Workflow>,
+    /// A human-friendly rendering of `workflow`.
+    pub visualized: Option<String>,
+    /// Things that compiled, but probably not the way the client intended.
+    pub warnings:   Vec<String>,
+    /// Why the workflow could not be compiled, if it couldn't. Such workflows are rejected by the deliberation API.
+    pub error:      Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DutiesQuery {
+    /// Only lists the open duties of this user, if given.
+    pub holder: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DutyFulfilPostModel {
+    /// What the holder wants to report about fulfilling the duty, if anything.
+    #[serde(default)]
+    pub report: Option<String>,
+}
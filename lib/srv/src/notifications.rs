@@ -0,0 +1,404 @@
+//! Implements notifying the owners of datasets about verdicts on workflows that use their data.
+//!
+//! Datasets may name an owner in the state (see [`DatasetOwner`]), together with an e-mail address and/or a webhook
+//! to reach them at. A [`Notifier`] holds the [`NotificationRule`]s that decide which verdicts are worth telling an
+//! owner about (e.g., "every denied access to `patients`" or "every verdict on a workflow using any dataset"), and
+//! through which channels.
+//!
+//! Notifications are sent in the background once the verdict is logged, so they never hold up or alter the answer to
+//! the client. Every attempt is logged to the audit log as an `OWNER-NOTIFICATION` statement, including why it failed
+//! if it did.
+//!
+//! If the Notifier has a signing key, webhooks can check that notifications really come from the reasoner: every POST
+//! carries a [`WEBHOOK_SIGNATURE_HEADER`] with the Base64-encoded HMAC-SHA256 of the body.
+
+use std::collections::HashSet;
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+use audit_logger::AuditLogger;
+use base64ct::{Base64, Encoding as _};
+use deliberation::spec::Verdict;
+use error_trace::ErrorTrace as _;
+use hmac::{Hmac, Mac as _};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use state_resolver::{DatasetOwner, State};
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::{Child, Command};
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+use workflow::{ElemCommit, ElemTask, Workflow};
+
+use crate::postprocess::Question;
+
+/***** CONSTANTS *****/
+/// The sendmail binary used to send e-mails if none is given.
+pub const DEFAULT_SENDMAIL: &str = "/usr/sbin/sendmail";
+
+/// The header of webhook notifications with their signature, if the [`Notifier`] has a signing key.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Policy-Reasoner-Signature";
+
+/// How long a webhook gets to accept a notification.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/***** ERRORS *****/
+/// Defines errors that originate from notifying a single owner.
+#[derive(Debug)]
+pub enum NotificationError {
+    /// The recipient cannot safely be handed to the channel (e.g., an address with a newline in it).
+    InvalidRecipient { recipient: String },
+    /// Failed to POST the notification to a webhook.
+    WebhookSend { url: String, err: reqwest::Error },
+    /// Failed to start sendmail.
+    SendmailSpawn { path: PathBuf, err: std::io::Error },
+    /// Failed to write the e-mail to sendmail.
+    SendmailWrite { path: PathBuf, err: std::io::Error },
+    /// Sendmail did not accept the e-mail.
+    SendmailFailed { path: PathBuf, status: ExitStatus },
+}
+impl Display for NotificationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use NotificationError::*;
+        match self {
+            InvalidRecipient { recipient } => write!(f, "Refusing to notify invalid recipient {recipient:?}"),
+            WebhookSend { url, .. } => write!(f, "Failed to deliver notification to webhook '{url}'"),
+            SendmailSpawn { path, .. } => write!(f, "Failed to start sendmail '{}'", path.display()),
+            SendmailWrite { path, .. } => write!(f, "Failed to write e-mail to sendmail '{}'", path.display()),
+            SendmailFailed { path, status } => write!(f, "Sendmail '{}' did not accept e-mail ({status})", path.display()),
+        }
+    }
+}
+impl error::Error for NotificationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use NotificationError::*;
+        match self {
+            InvalidRecipient { .. } => None,
+            WebhookSend { err, .. } => Some(err),
+            SendmailSpawn { err, .. } => Some(err),
+            SendmailWrite { err, .. } => Some(err),
+            SendmailFailed { .. } => None,
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// The channels through which an owner can be notified.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationChannel {
+    /// POSTs the notification as JSON to the owner's webhook.
+    Webhook,
+    /// E-mails the notification to the owner through sendmail.
+    Email,
+}
+impl Display for NotificationChannel {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Webhook => write!(f, "webhook"),
+            Self::Email => write!(f, "email"),
+        }
+    }
+}
+
+/// The kinds of verdict a [`NotificationRule`] can trigger on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VerdictKind {
+    Allow,
+    Deny,
+}
+impl Display for VerdictKind {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// Decides which verdicts an owner is notified of, and how.
+///
+/// Every filter that is left empty matches everything.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct NotificationRule {
+    /// The verdicts to notify of.
+    #[serde(default)]
+    pub verdicts:  Vec<VerdictKind>,
+    /// The kinds of question to notify of (e.g., `access-data`).
+    #[serde(default)]
+    pub questions: Vec<String>,
+    /// The datasets whose owners are notified.
+    #[serde(default)]
+    pub datasets:  Vec<String>,
+    /// The channels to notify through. Owners are only notified through the channels they have an address for.
+    #[serde(default)]
+    pub channels:  Vec<NotificationChannel>,
+}
+impl NotificationRule {
+    /// Returns whether this rule notifies the owner of a dataset through a channel of a verdict on a question.
+    fn matches(&self, verdict: VerdictKind, question: &str, dataset: &str, channel: NotificationChannel) -> bool {
+        (self.verdicts.is_empty() || self.verdicts.contains(&verdict))
+            && (self.questions.is_empty() || self.questions.iter().any(|q| q == question))
+            && (self.datasets.is_empty() || self.datasets.iter().any(|d| d == dataset))
+            && (self.channels.is_empty() || self.channels.contains(&channel))
+    }
+}
+
+/// What an owner is told about a verdict.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OwnerNotice {
+    /// The verdict reference of the question.
+    pub reference: String,
+    /// The verdict on the question.
+    pub verdict:   VerdictKind,
+    /// The kind of question (e.g., `access-data`).
+    pub question:  String,
+    /// The one who asked the question.
+    pub initiator: String,
+    /// The use-case the question was asked in.
+    pub use_case:  String,
+    /// The identifier of the workflow the question was about.
+    pub workflow:  String,
+    /// The dataset of the owner that the workflow uses.
+    pub dataset:   String,
+}
+
+/// Collects the names of all datasets a [`Workflow`] uses or produces.
+#[derive(Default)]
+struct WorkflowDatasets {
+    names: HashSet<String>,
+}
+impl WorkflowVisitor for WorkflowDatasets {
+    fn visit_task(&mut self, task: &ElemTask) {
+        self.names.extend(task.input.iter().map(|data| data.name.clone()));
+        self.names.extend(task.output.iter().map(|data| data.name.clone()));
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        self.names.insert(commit.data_name.clone());
+        self.names.extend(commit.input.iter().map(|data| data.name.clone()));
+    }
+}
+
+/***** HELPERS *****/
+/// Removes control characters from a value put in an e-mail header, such that it cannot add headers of its own.
+fn header_value(value: &str) -> String { value.chars().filter(|c| !c.is_control()).collect() }
+
+/// Returns whether a recipient can safely be handed to a channel.
+fn valid_recipient(recipient: &str) -> bool { !recipient.is_empty() && !recipient.starts_with('-') && !recipient.chars().any(char::is_control) }
+
+/***** LIBRARY *****/
+/// Notifies the owners of datasets about verdicts, according to a set of [`NotificationRule`]s.
+pub struct Notifier {
+    /// The rules that decide who is notified of what.
+    rules: Vec<NotificationRule>,
+    /// The sendmail binary used to send e-mails.
+    sendmail: PathBuf,
+    /// The client used to call webhooks.
+    client: reqwest::Client,
+    /// The secret key to sign webhook notifications with, if any.
+    signing_key: Option<Vec<u8>>,
+}
+impl Notifier {
+    /// Constructor for the Notifier.
+    ///
+    /// # Arguments
+    /// - `rules`: The [`NotificationRule`]s that decide who is notified of what.
+    /// - `sendmail`: The sendmail binary used to send e-mails (e.g., [`DEFAULT_SENDMAIL`]).
+    ///
+    /// # Returns
+    /// A new Notifier.
+    pub fn new(rules: Vec<NotificationRule>, sendmail: impl Into<PathBuf>) -> Self {
+        let client: reqwest::Client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build().unwrap_or_default();
+        Self { rules, sendmail: sendmail.into(), client, signing_key: None }
+    }
+
+    /// Makes the Notifier sign every webhook notification.
+    ///
+    /// # Arguments
+    /// - `signing_key`: The secret key to sign notifications with, or [`None`] to leave them unsigned.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_signing_key(mut self, signing_key: Option<Vec<u8>>) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
+    /// Returns the channels through which an owner is to be notified of a verdict.
+    fn channels(&self, verdict: VerdictKind, question: &str, owner: &DatasetOwner) -> Vec<(NotificationChannel, String)> {
+        let mut channels: Vec<(NotificationChannel, String)> = Vec::with_capacity(2);
+        for (channel, recipient) in [(NotificationChannel::Webhook, &owner.webhook), (NotificationChannel::Email, &owner.email)] {
+            let Some(recipient) = recipient else { continue };
+            if self.rules.iter().any(|rule| rule.matches(verdict, question, &owner.dataset, channel)) {
+                channels.push((channel, recipient.clone()));
+            }
+        }
+        channels
+    }
+
+    /// POSTs a notice to a webhook.
+    async fn send_webhook(&self, url: &str, notice: &OwnerNotice) -> Result<(), NotificationError> {
+        let body: String = serde_json::to_string(notice).unwrap_or_default();
+        let mut req = self.client.post(url).header("Content-Type", "application/json");
+        if let Some(key) = &self.signing_key {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+            mac.update(body.as_bytes());
+            req = req.header(WEBHOOK_SIGNATURE_HEADER, Base64::encode_string(&mac.finalize().into_bytes()));
+        }
+        req.body(body)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map(|_| ())
+            .map_err(|err| NotificationError::WebhookSend { url: url.into(), err })
+    }
+
+    /// E-mails a notice through sendmail.
+    async fn send_email(&self, address: &str, notice: &OwnerNotice) -> Result<(), NotificationError> {
+        let verdict: &str = match notice.verdict {
+            VerdictKind::Allow => "Allowed",
+            VerdictKind::Deny => "Denied",
+        };
+        let mail: String = format!(
+            "To: {}\r\nSubject: [policy-reasoner] {verdict} {} on workflow '{}' using '{}'\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nThe \
+             policy reasoner gave a verdict on a workflow that uses your dataset.\r\n\r\nVerdict:   {}\r\nQuestion:  {}\r\nDataset:   \
+             {}\r\nWorkflow:  {}\r\nInitiator: {}\r\nUse-case:  {}\r\nReference: {}\r\n",
+            header_value(address),
+            header_value(&notice.question),
+            header_value(&notice.workflow),
+            header_value(&notice.dataset),
+            notice.verdict,
+            notice.question,
+            notice.dataset,
+            notice.workflow,
+            notice.initiator,
+            notice.use_case,
+            notice.reference,
+        );
+
+        // The recipient is given on the command line rather than read from the headers, so the mail can't add any
+        let mut child: Child = Command::new(&self.sendmail)
+            .args(["-oi", "--", address])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| NotificationError::SendmailSpawn { path: self.sendmail.clone(), err })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(mail.as_bytes()).await.map_err(|err| NotificationError::SendmailWrite { path: self.sendmail.clone(), err })?;
+        }
+        let status: ExitStatus = child.wait().await.map_err(|err| NotificationError::SendmailWrite { path: self.sendmail.clone(), err })?;
+        if !status.success() {
+            return Err(NotificationError::SendmailFailed { path: self.sendmail.clone(), status });
+        }
+        Ok(())
+    }
+
+    /// Sends a notice through a channel.
+    async fn send(&self, channel: NotificationChannel, recipient: &str, notice: &OwnerNotice) -> Result<(), NotificationError> {
+        if !valid_recipient(recipient) {
+            return Err(NotificationError::InvalidRecipient { recipient: recipient.into() });
+        }
+        match channel {
+            NotificationChannel::Webhook => self.send_webhook(recipient, notice).await,
+            NotificationChannel::Email => self.send_email(recipient, notice).await,
+        }
+    }
+}
+
+/// Finds the owners of the datasets used by a workflow, if owners are notified at all.
+///
+/// Must be called before the state and workflow are handed to the reasoner connector.
+///
+/// # Arguments
+/// - `notifier`: The [`Notifier`] of the server, if any.
+/// - `state`: The [`State`] that knows the owners.
+/// - `workflow`: The [`Workflow`] the question is about.
+/// - `accessed`: The dataset the question asks to access, if any, in case the workflow does not mention it.
+///
+/// # Returns
+/// The owners of the datasets involved. Empty if owners are not notified.
+pub(crate) fn owners_involved(notifier: Option<&Arc<Notifier>>, state: &State, workflow: &Workflow, accessed: Option<&str>) -> Vec<DatasetOwner> {
+    if notifier.is_none() || state.owners.is_empty() {
+        return vec![];
+    }
+    let mut datasets = WorkflowDatasets::default();
+    walk_workflow_preorder(&workflow.start, &mut datasets);
+    datasets.names.extend(accessed.map(String::from));
+    state.owners.iter().filter(|owner| datasets.names.contains(&owner.dataset)).cloned().collect()
+}
+
+/// Notifies the owners of the datasets involved in a question of its verdict, if the rules say so.
+///
+/// Notifications are sent in the background, and do not affect the verdict. Every attempt is logged to the audit log.
+///
+/// # Arguments
+/// - `notifier`: The [`Notifier`] of the server, if any.
+/// - `logger`: The [`AuditLogger`] to log the notifications to.
+/// - `question`: The [`Question`] that was answered.
+/// - `verdict`: The [`Verdict`] on it.
+/// - `owners`: The owners of the datasets involved, as found by [`owners_involved()`].
+pub(crate) fn notify_owners<L: 'static + AuditLogger + Send + Sync + Clone>(
+    notifier: Option<&Arc<Notifier>>,
+    logger: &L,
+    question: &Question,
+    verdict: &Verdict,
+    owners: Vec<DatasetOwner>,
+) {
+    let Some(notifier) = notifier else { return };
+    let kind: VerdictKind = match verdict {
+        Verdict::Allow(_) => VerdictKind::Allow,
+        Verdict::Deny(_) => VerdictKind::Deny,
+    };
+
+    // Decide who to notify now, so we only spawn if there's anything to do
+    let mut seen: HashSet<(NotificationChannel, String)> = HashSet::new();
+    let mut todo: Vec<(NotificationChannel, String, OwnerNotice)> = vec![];
+    for owner in owners {
+        for (channel, recipient) in notifier.channels(kind, question.kind, &owner) {
+            // Owners of multiple datasets in the same workflow get only one notification per channel
+            if !seen.insert((channel, recipient.clone())) {
+                continue;
+            }
+            let notice = OwnerNotice {
+                reference: question.reference.into(),
+                verdict:   kind,
+                question:  question.kind.into(),
+                initiator: question.initiator.into(),
+                use_case:  question.use_case.into(),
+                workflow:  question.workflow.into(),
+                dataset:   owner.dataset.clone(),
+            };
+            todo.push((channel, recipient, notice));
+        }
+    }
+    if todo.is_empty() {
+        return;
+    }
+    debug!("Notifying {} owner(s) of verdict | request id: {}", todo.len(), question.reference);
+
+    let (notifier, logger): (Arc<Notifier>, L) = (notifier.clone(), logger.clone());
+    tokio::spawn(async move {
+        for (channel, recipient, notice) in todo {
+            let err: Option<String> = match notifier.send(channel, &recipient, &notice).await {
+                Ok(()) => None,
+                Err(err) => {
+                    warn!("{} | request id: {}", err.trace(), notice.reference);
+                    Some(err.trace().to_string())
+                },
+            };
+            let channel: String = channel.to_string();
+            if let Err(err) = logger.log_owner_notification(&notice.reference, &notice.dataset, &channel, &recipient, err.as_deref()).await {
+                error!("Could not log owner notification to audit log: {err} | request id: {}", notice.reference);
+            }
+        }
+    });
+}
@@ -1,17 +1,124 @@
 use std::fmt::Debug;
+use std::io::Read;
+use std::pin::pin;
 use std::sync::Arc;
 
 use audit_logger::AuditLogger;
 use auth_resolver::{AuthContext, AuthResolver};
-use policy::{Context, PolicyDataAccess, PolicyDataError};
+use error_trace::{ErrorTrace as _, trace};
+use futures_util::{Stream, StreamExt as _};
+use log::warn;
+use policy::{Context, Policy, PolicyDataAccess, PolicyDataError, PolicyMetadata, PolicyVersion, PolicyVersionFilter};
 use problem_details::ProblemDetails;
 use reasonerconn::ReasonerConnector;
 use serde::Serialize;
+use serde_json::value::RawValue;
 use state_resolver::StateResolver;
+use tokio::sync::mpsc;
 use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderMap, StatusCode};
+use warp::hyper::body::{Buf, Bytes};
 
 use crate::problem::Problem;
-use crate::{Srv, models};
+use crate::{Srv, models, simulation};
+
+/// The largest policy accepted by default, in bytes.
+pub const DEFAULT_MAX_POLICY_SIZE: u64 = 512 * 1024 * 1024;
+
+/// The number of received chunks of a streamed policy that may wait to be parsed.
+const STREAM_CHUNK_BACKLOG: usize = 16;
+
+/// Reads the chunks of a streamed policy as they are received, such that it is parsed while the rest is still underway.
+struct ChunkReader {
+    /// The chunks still to come. Closed when the last one was received.
+    chunks:  mpsc::Receiver<Bytes>,
+    /// What is left of the chunk being read.
+    current: Bytes,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while !self.current.has_remaining() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n: usize = buf.len().min(self.current.remaining());
+        self.current.copy_to_slice(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Receives the content of a streamed policy, parsing it as it comes in.
+///
+/// The content is refused as soon as it is clear that it's not a JSON object or array, that it is not valid JSON or that
+/// it is larger than allowed, without receiving the rest of it.
+///
+/// # Arguments
+/// - `body`: The stream of chunks of the request body.
+/// - `length`: The length of the content, if the client told us.
+/// - `max_size`: The largest content accepted, in bytes.
+///
+/// # Returns
+/// The received content.
+///
+/// # Errors
+/// This function errors with a [`Problem`] to give to the client if receiving the content failed, if it is too large or
+/// if it is not valid JSON.
+async fn receive_policy_stream(
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+    length: Option<u64>,
+    max_size: u64,
+) -> Result<Arc<RawValue>, Problem> {
+    let too_large = || {
+        Problem::from(
+            ProblemDetails::new()
+                .with_status(StatusCode::PAYLOAD_TOO_LARGE)
+                .with_detail(format!("Policy content is larger than the maximum of {max_size} byte(s)")),
+        )
+    };
+    let bad_request = |detail: String| Problem::from(ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail(detail));
+    if length.is_some_and(|length| length > max_size) {
+        return Err(too_large());
+    }
+
+    // Parse on a blocking thread, fed with the chunks as they arrive
+    let (sender, receiver) = mpsc::channel::<Bytes>(STREAM_CHUNK_BACKLOG);
+    let parser =
+        tokio::task::spawn_blocking(move || serde_json::from_reader::<_, Box<RawValue>>(ChunkReader { chunks: receiver, current: Bytes::new() }));
+    let mut body = pin!(body);
+    let mut received: u64 = 0;
+    let mut started: bool = false;
+    while let Some(chunk) = body.next().await {
+        let mut chunk = chunk.map_err(|err| bad_request(format!("Failed to receive policy content: {err}")))?;
+        received += chunk.remaining() as u64;
+        if received > max_size {
+            return Err(too_large());
+        }
+        let bytes: Bytes = chunk.copy_to_bytes(chunk.remaining());
+        if !started {
+            if let Some(first) = bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+                if *first != b'{' && *first != b'[' {
+                    return Err(bad_request("Policy content is not a JSON object or array".into()));
+                }
+                started = true;
+            }
+        }
+        // If the parser hung up, it found the content to be invalid already; no need to receive the rest
+        if sender.send(bytes).await.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+
+    let content: Box<RawValue> = parser
+        .await
+        .map_err(|err| bad_request(format!("Failed to parse policy content: {err}")))?
+        .map_err(|err| bad_request(format!("Policy content is not valid JSON: {err}")))?;
+    Ok(Arc::from(content))
+}
 
 impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
 where
@@ -23,6 +130,25 @@ where
     DA: 'static + AuthResolver + Send + Sync,
     C::Context: Send + Sync + Debug + Serialize,
 {
+    /// Lets the reasoner connector prepare for a policy that is being activated.
+    ///
+    /// This is done as part of the activation, such that a policy the reasoner connector cannot use is never activated.
+    async fn prepare_activation(&self, policy: &Policy) -> Result<(), PolicyDataError> {
+        self.reasonerconn
+            .on_policy_activated(policy)
+            .await
+            .map_err(|err| PolicyDataError::GeneralError(trace!(("Reasoner connector cannot prepare for this policy"), err).to_string()))
+    }
+
+    /// Tells the reasoner connector that no policy is active anymore.
+    ///
+    /// Failing to do so does not undo the deactivation; it is only logged.
+    async fn notify_policy_deactivated(&self) {
+        if let Err(err) = self.reasonerconn.on_policy_deactivated().await {
+            warn!("{}", trace!(("Reasoner connector failed to handle the deactivation of the active policy"), err));
+        }
+    }
+
     // GET specific version
     // GET /v1/policies/:version
     // out:
@@ -35,32 +161,36 @@ where
             Err(err) => match err {
                 PolicyDataError::NotFound => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
                 PolicyDataError::GeneralError(msg) => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
             },
         }
     }
 
     // List policy's versions
-    // GET /v1/policies
+    // GET /v1/policies?tag=..&jurisdiction=..&department=..
     // out:
     // - 200 Vec<PolicyVersionDescription>
 
-    async fn handle_get_all_policies(_auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, warp::reject::Rejection> {
-        match this.policystore.get_versions().await {
+    async fn handle_get_all_policies(
+        _auth_ctx: AuthContext,
+        this: Arc<Self>,
+        filter: PolicyVersionFilter,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        match this.policystore.get_versions(&filter).await {
             Ok(v) => Ok(warp::reply::json(&v)),
             Err(err) => match err {
                 PolicyDataError::NotFound => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
                 PolicyDataError::GeneralError(msg) => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
             },
         }
@@ -78,15 +208,36 @@ where
         this: Arc<Self>,
         body: models::AddPolicyPostModel,
     ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        Self::add_policy(auth_ctx, this, body.into_domain()).await
+    }
+
+    // Create new version of policy, streaming its (single) content instead of embedding it
+    // POST /v1/policies?version_description=..&reasoner=..&reasoner_version=..[&description=..][&tags=..,..][&jurisdiction=..][&department=..]
+    // Content-Type: application/octet-stream
+    // in: the policy content
+    // out:
+    //  - 201 Policy. version in body
+    //  - 400 problem+json
+
+    async fn handle_add_policy_stream(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        query: models::AddPolicyStreamQueryModel,
+        length: Option<u64>,
+        body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let content: Arc<RawValue> = receive_policy_stream(body, length, this.max_policy_size).await.map_err(warp::reject::custom)?;
+        Self::add_policy(auth_ctx, this, query.into_domain(content)).await
+    }
+
+    /// Stores a new version of the policy, logging that it did.
+    pub(crate) async fn add_policy(auth_ctx: AuthContext, this: Arc<Self>, mut model: Policy) -> Result<warp::reply::Json, warp::reject::Rejection> {
         let t: Arc<Self> = this.clone();
-        let mut model = body.to_domain();
         model.version.reasoner_connector_context = C::hash();
         match this
             .policystore
             .add_version(model, Context { initiator: auth_ctx.initiator.clone() }, |policy| async move {
-                t.logger.log_add_policy_request::<C>(&auth_ctx, &policy).await.map_err(|err| match err {
-                    audit_logger::Error::CouldNotDeliver(err) => PolicyDataError::GeneralError(err),
-                })
+                t.logger.log_add_policy_request::<C>(&auth_ctx, &policy).await.map_err(|err| PolicyDataError::GeneralError(err.trace().to_string()))
             })
             .await
         {
@@ -94,16 +245,77 @@ where
             Err(err) => match err {
                 PolicyDataError::NotFound => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
                 PolicyDataError::GeneralError(msg) => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
             },
         }
     }
 
+    /// Gets the stored version that a new one is derived from.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `404 Not Found` if the version does not exist.
+    async fn get_base_version(this: &Self, version: i64) -> Result<Policy, warp::reject::Rejection> {
+        match this.policystore.get_version(version).await {
+            Ok(policy) => Ok(policy),
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND).with_detail(format!("Invalid version: {}", version));
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+            Err(PolicyDataError::GeneralError(msg)) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    // Create new version of policy with the same contents as an existing one
+    // POST /v1/policies/:version/clone
+    // in: ClonePolicyPostModel
+    // out:
+    //  - 201 Policy. version in body
+    //  - 400 problem+json
+    //  - 404 problem+json
+
+    async fn handle_clone_policy(
+        auth_ctx: AuthContext,
+        version: i64,
+        this: Arc<Self>,
+        body: models::ClonePolicyPostModel,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let base: Policy = Self::get_base_version(&this, version).await?;
+        Self::add_policy(auth_ctx, this, body.apply(base)).await
+    }
+
+    // Create new version of policy by changing only some contents of an existing one
+    // PATCH /v1/policies/:version
+    // in: PatchPolicyModel
+    // out:
+    //  - 201 Policy. version in body
+    //  - 400 problem+json
+    //  - 404 problem+json
+
+    async fn handle_patch_policy(
+        auth_ctx: AuthContext,
+        version: i64,
+        this: Arc<Self>,
+        body: models::PatchPolicyModel,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let base: Policy = Self::get_base_version(&this, version).await?;
+        let model: Policy = match body.apply(base) {
+            Ok(model) => model,
+            Err(detail) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(detail);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        Self::add_policy(auth_ctx, this, model).await
+    }
+
     // Show active policy
     // GET /v1/policies/active
     // out: 200 {version: string}
@@ -114,11 +326,11 @@ where
             Err(err) => match err {
                 PolicyDataError::NotFound => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND).with_detail("No version currently active");
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
                 PolicyDataError::GeneralError(msg) => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
             },
         }
@@ -136,45 +348,215 @@ where
         this: Arc<Self>,
         body: models::SetVersionPostModel,
     ) -> Result<warp::reply::Json, warp::reject::Rejection> {
-        // Reject activation of policy with invalid base defs
+        let t = this.clone();
+        match this
+            .policystore
+            .set_active(body.version, Context { initiator: auth_ctx.initiator.clone() }, |policy| async move {
+                // Reject activation of policy with invalid base defs (which rolls back the activation)
+                let conn_hash = C::hash();
+                if policy.version.reasoner_connector_context != conn_hash {
+                    return Err(PolicyDataError::GeneralError(format!(
+                        "Cannot activate policy which has a different base policy than current the reasoners connector's base. Policy base defs \
+                         hash is '{}' and connector's base defs hash is '{}'",
+                        policy.version.reasoner_connector_context, conn_hash
+                    )));
+                }
+
+                t.prepare_activation(&policy).await?;
+                t.logger.log_set_active_version_policy(&auth_ctx, &policy).await.map_err(|err| PolicyDataError::GeneralError(err.trace().to_string()))
+            })
+            .await
+        {
+            Ok(policy) => Ok(warp::reply::json(&policy)),
+            Err(err) => match err {
+                PolicyDataError::NotFound => {
+                    let p = ProblemDetails::new()
+                        .with_status(warp::http::StatusCode::BAD_REQUEST)
+                        .with_detail(format!("Invalid version: {}", body.version));
+                    Err(warp::reject::custom(Problem::from(p)))
+                },
+                PolicyDataError::GeneralError(msg) => {
+                    let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                    Err(warp::reject::custom(Problem::from(p)))
+                },
+            },
+        }
+    }
+
+    // Compose the active policy from per-domain fragments and activate it
+    // PUT /v1/policies/active/composed
+    // in: ComposePostModel
+    // out:
+    //  200 Policy. the new, composed version
+    //  400 problem+json
+    //  404 problem+json
+    //  409 problem+json. the conflicts between the fragments
+
+    async fn handle_compose_active_policy(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: models::ComposePostModel,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        if body.fragments.is_empty() {
+            let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail("No policy fragments given");
+            return Err(warp::reject::custom(Problem::from(p)));
+        }
+
+        // Collect the fragments, rejecting any that were written for another reasoner connector
         let conn_hash = C::hash();
-        if let Ok(policy) = this.policystore.get_version(body.version).await {
-            if policy.version.reasoner_connector_context != conn_hash {
+        let mut fragments: Vec<Policy> = Vec::with_capacity(body.fragments.len());
+        for version in &body.fragments {
+            let fragment = match this.policystore.get_version(*version).await {
+                Ok(fragment) => fragment,
+                Err(PolicyDataError::NotFound) => {
+                    let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND).with_detail(format!("Invalid version: {}", version));
+                    return Err(warp::reject::custom(Problem::from(p)));
+                },
+                Err(PolicyDataError::GeneralError(msg)) => {
+                    let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                    return Err(warp::reject::custom(Problem::from(p)));
+                },
+            };
+            if fragment.version.reasoner_connector_context != conn_hash {
                 let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(format!(
-                    "Cannot activate policy which has a different base policy than current the reasoners connector's base. Policy base defs hash is \
-                     '{}' and connector's base defs hash is '{}'",
-                    policy.version.reasoner_connector_context, conn_hash
+                    "Cannot compose policy version {} which has a different base policy than the reasoner connector's base",
+                    version
                 ));
-                return Err(warp::reject::custom(Problem(p)));
+                return Err(warp::reject::custom(Problem::from(p)));
             }
+            fragments.push(fragment);
         }
 
-        let t = this.clone();
+        // Every domain may contribute at most one fragment; order them by domain to make the composition deterministic
+        let mut conflicts: Vec<String> = Vec::new();
+        fragments.sort_by(|lhs, rhs| {
+            (&lhs.version.metadata.department, lhs.version.version).cmp(&(&rhs.version.metadata.department, rhs.version.version))
+        });
+        for pair in fragments.windows(2) {
+            if pair[0].version.metadata.department.is_some() && pair[0].version.metadata.department == pair[1].version.metadata.department {
+                conflicts.push(format!(
+                    "Versions {} and {} both belong to department '{}'",
+                    pair[0].version.version.unwrap_or(-1),
+                    pair[1].version.version.unwrap_or(-1),
+                    pair[0].version.metadata.department.as_deref().unwrap_or("")
+                ));
+            }
+        }
+        let content = match this.reasonerconn.compose(&fragments) {
+            Ok(content) if conflicts.is_empty() => content,
+            Ok(_) => Vec::new(),
+            Err(err) => {
+                conflicts.extend(err.conflicts);
+                Vec::new()
+            },
+        };
+        if !conflicts.is_empty() {
+            let p = ProblemDetails::new()
+                .with_status(warp::http::StatusCode::CONFLICT)
+                .with_title("Policy fragments conflict")
+                .with_detail(conflicts.join("; "));
+            return Err(warp::reject::custom(Problem::from(p)));
+        }
+
+        // Store the composition as a new version...
+        let versions: Vec<String> = fragments.iter().map(|f| f.version.version.unwrap_or(-1).to_string()).collect();
+        let mut tags: Vec<String> = fragments.iter().flat_map(|f| f.version.metadata.tags.iter().cloned()).collect();
+        tags.push("composed".into());
+        tags.sort();
+        tags.dedup();
+        let jurisdiction: Option<String> = fragments[0].version.metadata.jurisdiction.clone();
+        let jurisdiction: Option<String> =
+            if fragments.iter().all(|f| f.version.metadata.jurisdiction == jurisdiction) { jurisdiction } else { None };
+        let model = Policy {
+            description: body.description.clone().unwrap_or_else(|| format!("Composition of versions {}", versions.join(", "))),
+            version: PolicyVersion {
+                creator: None,
+                created_at: chrono::Local::now(),
+                version: None,
+                version_description: format!("Composed from versions {}", versions.join(", ")),
+                reasoner_connector_context: conn_hash,
+                metadata: PolicyMetadata { tags, jurisdiction, department: None },
+                content_hashes: Vec::new(),
+                external_id: None,
+            },
+            content,
+        };
+
+        let t: Arc<Self> = this.clone();
+        let add_ctx = auth_ctx.clone();
+        let policy = match this
+            .policystore
+            .add_version(model, Context { initiator: auth_ctx.initiator.clone() }, |policy| async move {
+                t.logger.log_add_policy_request::<C>(&add_ctx, &policy).await.map_err(|err| PolicyDataError::GeneralError(err.trace().to_string()))
+            })
+            .await
+        {
+            Ok(policy) => policy,
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(PolicyDataError::GeneralError(msg)) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+
+        // ...and activate it
+        let version: i64 = policy.version.version.unwrap_or(-1);
+        let t: Arc<Self> = this.clone();
         match this
             .policystore
-            .set_active(body.version, Context { initiator: auth_ctx.initiator.clone() }, |policy| async move {
-                t.logger.log_set_active_version_policy(&auth_ctx, &policy).await.map_err(|err| match err {
-                    audit_logger::Error::CouldNotDeliver(err) => PolicyDataError::GeneralError(err),
-                })
+            .set_active(version, Context { initiator: auth_ctx.initiator.clone() }, |policy| async move {
+                t.prepare_activation(&policy).await?;
+                t.logger.log_set_active_version_policy(&auth_ctx, &policy).await.map_err(|err| PolicyDataError::GeneralError(err.trace().to_string()))
             })
             .await
         {
             Ok(policy) => Ok(warp::reply::json(&policy)),
             Err(err) => match err {
                 PolicyDataError::NotFound => {
-                    let p = ProblemDetails::new()
-                        .with_status(warp::http::StatusCode::BAD_REQUEST)
-                        .with_detail(format!("Invalid version: {}", body.version));
-                    Err(warp::reject::custom(Problem(p)))
+                    let p =
+                        ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(format!("Invalid version: {}", version));
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
                 PolicyDataError::GeneralError(msg) => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
             },
         }
     }
 
+    // Replay recorded questions against a (non-active) policy version
+    // POST /v1/policies/:version/simulate
+    // in: SimulatePostModel
+    // out:
+    //  200 SimulationReport
+    //  404 problem+json
+
+    async fn handle_simulate_policy(
+        _auth_ctx: AuthContext,
+        version: i64,
+        this: Arc<Self>,
+        body: models::SimulatePostModel,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let policy = match this.policystore.get_version(version).await {
+            Ok(policy) => policy,
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::NOT_FOUND).with_detail(format!("Invalid version: {}", version));
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(PolicyDataError::GeneralError(msg)) => {
+                let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+
+        let report = simulation::simulate(&this.reasonerconn, &this.logger, &policy, body.questions).await;
+        Ok(warp::reply::json(&report))
+    }
+
     // Set active policy
     // DELETE /v1/policies/active
     // out:
@@ -186,21 +568,22 @@ where
         match this
             .policystore
             .deactivate_policy(Context { initiator: auth_ctx.initiator.clone() }, || async move {
-                t.logger.log_deactivate_policy(&auth_ctx).await.map_err(|err| match err {
-                    audit_logger::Error::CouldNotDeliver(err) => PolicyDataError::GeneralError(err),
-                })
+                t.logger.log_deactivate_policy(&auth_ctx).await.map_err(|err| PolicyDataError::GeneralError(err.trace().to_string()))
             })
             .await
         {
-            Ok(policy) => Ok(warp::reply::json(&policy)),
+            Ok(policy) => {
+                this.notify_policy_deactivated().await;
+                Ok(warp::reply::json(&policy))
+            },
             Err(err) => match err {
                 PolicyDataError::NotFound => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail("No active version to deactivate");
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
                 PolicyDataError::GeneralError(msg) => {
                     let p = ProblemDetails::new().with_status(warp::http::StatusCode::BAD_REQUEST).with_detail(msg);
-                    Err(warp::reject::custom(Problem(p)))
+                    Err(warp::reject::custom(Problem::from(p)))
                 },
             },
         }
@@ -211,19 +594,45 @@ where
             .and(warp::path::end())
             .and(Self::with_policy_api_auth(this.clone()))
             .and(Self::with_self(this.clone()))
+            .and(warp::body::content_length_limit(this.max_policy_size))
             .and(warp::body::json())
             .and_then(Self::handle_add_policy);
 
+        let add_version_stream = warp::post()
+            .and(warp::path::end())
+            .and(warp::header::exact_ignore_case("content-type", "application/octet-stream"))
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::query::<models::AddPolicyStreamQueryModel>())
+            .and(warp::header::optional::<u64>("content-length"))
+            .and(warp::body::stream())
+            .and_then(Self::handle_add_policy_stream);
+
         let get_version = warp::get()
             .and(Self::with_policy_api_auth(this.clone()))
             .and(warp::path!(i64))
             .and(Self::with_self(this.clone()))
             .and_then(Self::handle_get_policy_version);
 
+        let clone_version = warp::post()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(i64 / "clone"))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_clone_policy);
+
+        let patch_version = warp::patch()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(i64))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_patch_policy);
+
         let get_all = warp::get()
             .and(warp::path::end())
             .and(Self::with_policy_api_auth(this.clone()))
             .and(Self::with_self(this.clone()))
+            .and(warp::query::<PolicyVersionFilter>())
             .and_then(Self::handle_get_all_policies);
 
         let get_active = warp::get()
@@ -239,24 +648,46 @@ where
             .and(warp::body::json())
             .and_then(Self::handle_set_active_policy);
 
+        let compose_active = warp::put()
+            .and(warp::path!("active" / "composed"))
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_compose_active_policy);
+
+        let simulate = warp::post()
+            .and(Self::with_policy_api_auth(this.clone()))
+            .and(warp::path!(i64 / "simulate"))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_simulate_policy);
+
         let deactivate = warp::delete()
             .and(warp::path!("active"))
             .and(Self::with_policy_api_auth(this.clone()))
             .and(Self::with_self(this.clone()))
             .and_then(Self::handle_deactivate_policy);
 
-        warp::path("v1")
-            .and(warp::path("management"))
-            .and(warp::path("policies"))
-            .and(get_version.or(get_all).or(get_active).or(set_active).or(add_version).or(deactivate))
+        warp::path("v1").and(warp::path("management")).and(warp::path("policies")).and(
+            get_version
+                .or(get_all)
+                .or(get_active)
+                .or(set_active)
+                .or(compose_active)
+                .or(add_version_stream)
+                .or(add_version)
+                .or(clone_version)
+                .or(patch_version)
+                .or(simulate)
+                .or(deactivate),
+        )
     }
 
-    fn with_policy_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
-        Self::with_self(this.clone()).and(warp::header::headers_cloned()).and_then(|this: Arc<Self>, headers| async move {
-            match this.pauthresolver.authenticate(headers).await {
-                Ok(v) => Ok(v),
-                Err(err) => Err(warp::reject::custom(err)),
-            }
-        })
+    pub(crate) fn with_policy_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.pauthresolver, route, headers).await
+            },
+        )
     }
 }
@@ -0,0 +1,281 @@
+//! Implements a chain of [`VerdictTransformer`]s that may alter the reasoner's answer before it is returned.
+//!
+//! The reasoner only knows about the policy, but deployments sometimes need to decide on things the policy does not
+//! capture: denying everything during a maintenance window, limiting how often someone may ask, or attaching
+//! obligations to every permission. Rather than encoding these in every policy, they are configured as a
+//! [`VerdictPipeline`] that post-processes each [`Decision`]. Every change a transformer makes is recorded in the
+//! audit log.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use deliberation::spec::{DenyCategory, Granularity};
+use reasonerconn::ReasonerResponse;
+use serde::{Deserialize, Serialize};
+
+/***** AUXILLARY *****/
+/// Describes the question that a [`Decision`] answers, for transformers that only apply to some questions.
+#[derive(Clone, Copy, Debug)]
+pub struct Question<'a> {
+    /// The kind of question (one of [`reasonerconn::QUESTION_KINDS`]).
+    pub kind:      &'a str,
+    /// The verdict reference of the request.
+    pub reference: &'a str,
+    /// The one who asked the question.
+    pub initiator: &'a str,
+    /// The use-case the question is asked in.
+    pub use_case:  &'a str,
+    /// The identifier of the workflow the question is about.
+    pub workflow:  &'a str,
+}
+
+/// The answer to a question as it passes through a [`VerdictPipeline`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Decision {
+    /// Whether the request is allowed.
+    pub allow: bool,
+    /// The reasons for denial, if any.
+    pub reasons: Vec<String>,
+    /// The categories of the reasons for denial, if any.
+    pub categories: Vec<DenyCategory>,
+    /// The obligations that come with an allowed request, if any.
+    pub obligations: Vec<String>,
+    /// How many reasons for denial the reasoner looked for, if it tells. Not known for decisions made by transformers.
+    pub granularity: Option<Granularity>,
+}
+impl From<ReasonerResponse> for Decision {
+    #[inline]
+    fn from(value: ReasonerResponse) -> Self {
+        Self { allow: value.success, reasons: value.errors, categories: value.categories, obligations: vec![], granularity: value.granularity }
+    }
+}
+
+/// Records that a [`VerdictTransformer`] altered a [`Decision`].
+#[derive(Clone, Debug)]
+pub struct Alteration {
+    /// The name of the transformer.
+    pub transformer: String,
+    /// What it changed and why.
+    pub reason:      String,
+}
+
+/***** LIBRARY *****/
+/// Inspects and possibly modifies the answer of the reasoner before it is returned.
+pub trait VerdictTransformer: Debug + Send + Sync {
+    /// Returns a name for this transformer, used to identify it in the audit log.
+    fn name(&self) -> &str;
+
+    /// Inspects and possibly modifies a decision.
+    ///
+    /// # Arguments
+    /// - `question`: The [`Question`] that the decision answers.
+    /// - `decision`: The [`Decision`] to transform.
+    ///
+    /// # Returns
+    /// A description of what was changed and why, or [`None`] if the decision was left untouched.
+    fn transform(&self, question: &Question, decision: &mut Decision) -> Option<String>;
+}
+
+/// Denies all requests that arrive within a given time window.
+#[derive(Debug)]
+pub struct MaintenanceWindow {
+    /// The moment the window opens.
+    pub from:   DateTime<Utc>,
+    /// The moment the window closes.
+    pub until:  DateTime<Utc>,
+    /// The reason for denial given to clients.
+    pub reason: String,
+}
+impl VerdictTransformer for MaintenanceWindow {
+    fn name(&self) -> &str { "maintenance-window" }
+
+    fn transform(&self, _question: &Question, decision: &mut Decision) -> Option<String> {
+        let now: DateTime<Utc> = Utc::now();
+        if !decision.allow || now < self.from || now >= self.until {
+            return None;
+        }
+        *decision = Decision {
+            allow: false,
+            reasons: vec![self.reason.clone()],
+            categories: vec![DenyCategory::BackendError],
+            obligations: vec![],
+            granularity: None,
+        };
+        Some(format!("Denied allowed request during maintenance window {} - {}", self.from, self.until))
+    }
+}
+
+/// Denies requests of initiators who ask too many questions.
+#[derive(Debug)]
+pub struct RateLimit {
+    /// The maximum number of questions an initiator may ask within `window`.
+    max_requests: usize,
+    /// The sliding window over which questions are counted.
+    window: Duration,
+    /// When each initiator asked their recent questions.
+    history: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+impl RateLimit {
+    /// Constructor for the RateLimit.
+    ///
+    /// # Arguments
+    /// - `max_requests`: The maximum number of questions an initiator may ask within `window`.
+    /// - `window`: The sliding window over which questions are counted.
+    ///
+    /// # Returns
+    /// A new RateLimit.
+    #[inline]
+    pub fn new(max_requests: usize, window: Duration) -> Self { Self { max_requests, window, history: Mutex::new(HashMap::new()) } }
+}
+impl VerdictTransformer for RateLimit {
+    fn name(&self) -> &str { "rate-limit" }
+
+    fn transform(&self, question: &Question, decision: &mut Decision) -> Option<String> {
+        // Count this question, forgetting those that fell out of the window
+        let now: Instant = Instant::now();
+        let count: usize = {
+            let mut history = self.history.lock().unwrap();
+            history.retain(|_, asked| asked.back().map(|last| now.duration_since(*last) < self.window).unwrap_or(false));
+            let asked: &mut VecDeque<Instant> = history.entry(question.initiator.into()).or_default();
+            while asked.front().map(|first| now.duration_since(*first) >= self.window).unwrap_or(false) {
+                asked.pop_front();
+            }
+            asked.push_back(now);
+            asked.len()
+        };
+
+        if !decision.allow || count <= self.max_requests {
+            return None;
+        }
+        *decision = Decision {
+            allow: false,
+            reasons: vec![format!("Rate limit exceeded ({} requests per {} seconds)", self.max_requests, self.window.as_secs())],
+            categories: vec![DenyCategory::QuotaExceeded],
+            obligations: vec![],
+            granularity: None,
+        };
+        Some(format!(
+            "Denied allowed request of '{}' because it asked {} questions within {} seconds",
+            question.initiator,
+            count,
+            self.window.as_secs()
+        ))
+    }
+}
+
+/// Attaches an obligation to allowed requests.
+#[derive(Debug)]
+pub struct Obligation {
+    /// The obligation to attach.
+    pub obligation: String,
+    /// The kinds of questions to attach it to. Empty means all of them.
+    pub questions:  Vec<String>,
+}
+impl VerdictTransformer for Obligation {
+    fn name(&self) -> &str { "obligation" }
+
+    fn transform(&self, question: &Question, decision: &mut Decision) -> Option<String> {
+        if !decision.allow || (!self.questions.is_empty() && !self.questions.iter().any(|kind| kind == question.kind)) {
+            return None;
+        }
+        decision.obligations.push(self.obligation.clone());
+        Some(format!("Added obligation '{}'", self.obligation))
+    }
+}
+
+/// Declaratively configures one of the built-in [`VerdictTransformer`]s.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum VerdictTransformerConfig {
+    /// Configures a [`MaintenanceWindow`].
+    MaintenanceWindow {
+        from:   DateTime<Utc>,
+        until:  DateTime<Utc>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Configures a [`RateLimit`].
+    RateLimit { max_requests: usize, window_secs: u64 },
+    /// Configures an [`Obligation`].
+    Obligation {
+        obligation: String,
+        #[serde(default)]
+        questions:  Vec<String>,
+    },
+}
+impl VerdictTransformerConfig {
+    /// Builds the transformer described by this configuration.
+    ///
+    /// # Returns
+    /// A new [`VerdictTransformer`].
+    pub fn build(self) -> Box<dyn VerdictTransformer> {
+        match self {
+            Self::MaintenanceWindow { from, until, reason } => Box::new(MaintenanceWindow {
+                from,
+                until,
+                reason: reason.unwrap_or_else(|| format!("The policy reasoner is under maintenance until {until}")),
+            }),
+            Self::RateLimit { max_requests, window_secs } => Box::new(RateLimit::new(max_requests, Duration::from_secs(window_secs))),
+            Self::Obligation { obligation, questions } => Box::new(Obligation { obligation, questions }),
+        }
+    }
+}
+
+/// Passes the reasoner's answers through a chain of [`VerdictTransformer`]s.
+///
+/// The default VerdictPipeline is empty, and leaves answers untouched.
+#[derive(Debug, Default)]
+pub struct VerdictPipeline {
+    /// The transformers to apply, in order.
+    transformers: Vec<Box<dyn VerdictTransformer>>,
+}
+impl VerdictPipeline {
+    /// Constructor for the VerdictPipeline.
+    ///
+    /// # Arguments
+    /// - `transformers`: The [`VerdictTransformer`]s to apply, in order.
+    ///
+    /// # Returns
+    /// A new VerdictPipeline.
+    #[inline]
+    pub fn new(transformers: impl IntoIterator<Item = Box<dyn VerdictTransformer>>) -> Self {
+        Self { transformers: transformers.into_iter().collect() }
+    }
+
+    /// Constructor for the VerdictPipeline that builds the built-in transformers from their configuration.
+    ///
+    /// # Arguments
+    /// - `configs`: The [`VerdictTransformerConfig`]s of the transformers to apply, in order.
+    ///
+    /// # Returns
+    /// A new VerdictPipeline.
+    #[inline]
+    pub fn from_config(configs: impl IntoIterator<Item = VerdictTransformerConfig>) -> Self {
+        Self::new(configs.into_iter().map(VerdictTransformerConfig::build))
+    }
+
+    /// Returns whether this pipeline has no transformers.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.transformers.is_empty() }
+
+    /// Passes a decision through all transformers, in order.
+    ///
+    /// # Arguments
+    /// - `question`: The [`Question`] that the decision answers.
+    /// - `decision`: The [`Decision`] to transform.
+    ///
+    /// # Returns
+    /// An [`Alteration`] for every transformer that changed the decision, in order.
+    pub fn apply(&self, question: &Question, decision: &mut Decision) -> Vec<Alteration> {
+        let mut alterations: Vec<Alteration> = Vec::new();
+        for transformer in &self.transformers {
+            if let Some(reason) = transformer.transform(question, decision) {
+                alterations.push(Alteration { transformer: transformer.name().into(), reason });
+            }
+        }
+        alterations
+    }
+}
@@ -0,0 +1,181 @@
+//! Implements checking the workflow of a deliberation request against the resolved [`State`], before the reasoner is
+//! asked about it.
+//!
+//! Reasoner connectors handle workflows that refer to things the state doesn't know about poorly: eFLINT silently
+//! creates the unknown datasets, users and locations (so the policy judges them as if they exist), while POSIX only
+//! reports an opaque `UnknownDataset`. If configured with [`Srv::with_state_check()`](crate::Srv::with_state_check), the
+//! server instead finds every [`UnknownReference`] in the workflow itself, and refuses the workflow with a problem that
+//! says precisely what is unknown and where.
+//!
+//! Datasets produced by the workflow itself (i.e., intermediate results and committed datasets) need not be known.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use log::{info, warn};
+use problem_details::ProblemDetails;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use state_resolver::State;
+use warp::http::StatusCode;
+use warp::reject::Rejection;
+use workflow::Workflow;
+use workflow::lint::LintLevel;
+use workflow::spec::{Dataset, ElemCommit, ElemTask};
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+
+use crate::problem::Problem;
+
+/***** CONSTANTS *****/
+/// The problem type returned to clients when their workflow refers to things the resolved state doesn't know about.
+pub const UNKNOWN_REFERENCES_PROBLEM: &'static str = "/problems/unknown-references";
+
+/***** HELPERS *****/
+/// Collects everything a workflow refers to, and the datasets it produces itself.
+#[derive(Default)]
+struct References {
+    /// The datasets read, by the node reading them.
+    datasets:  Vec<(String, String)>,
+    /// The locations tasks run at, commits commit at or datasets are transferred from, by the node involved.
+    locations: Vec<(String, String)>,
+    /// The datasets produced by the workflow.
+    produced:  HashSet<String>,
+}
+impl References {
+    /// Records the inputs of a task or commit.
+    fn inputs(&mut self, node: &str, input: &[Dataset]) {
+        for i in input {
+            self.datasets.push((node.into(), i.name.clone()));
+            if let Some(from) = &i.from {
+                self.locations.push((node.into(), from.clone()));
+            }
+        }
+    }
+}
+impl WorkflowVisitor for References {
+    fn visit_task(&mut self, task: &ElemTask) {
+        self.inputs(&task.id, &task.input);
+        if let Some(output) = &task.output {
+            self.produced.insert(output.name.clone());
+        }
+        if let Some(location) = &task.location {
+            self.locations.push((task.id.clone(), location.clone()));
+        }
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        self.inputs(&commit.id, &commit.input);
+        self.produced.insert(commit.data_name.clone());
+        if let Some(location) = &commit.location {
+            self.locations.push((commit.id.clone(), location.clone()));
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// Describes something a workflow refers to that the resolved [`State`] doesn't know about.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum UnknownReference {
+    /// A task or commit reads a dataset that neither exists nor is produced by the workflow.
+    Dataset {
+        /// The identifier of the task or commit.
+        node:    String,
+        /// The name of the dataset.
+        dataset: String,
+    },
+    /// A task runs at, a commit commits at or a dataset is transferred from a location that does not exist.
+    Location {
+        /// The identifier of the task or commit.
+        node:     String,
+        /// The name of the location.
+        location: String,
+    },
+    /// The workflow is submitted by (or returns its result to) a user that does not exist.
+    User {
+        /// The name of the user.
+        user: String,
+    },
+}
+impl Display for UnknownReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Dataset { node, dataset } => write!(f, "Unknown dataset '{dataset}' at node '{node}'"),
+            Self::Location { node, location } => write!(f, "Unknown location '{location}' at node '{node}'"),
+            Self::User { user } => write!(f, "Unknown user '{user}'"),
+        }
+    }
+}
+
+/// Finds everything a workflow refers to that the resolved state doesn't know about.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to check.
+/// - `state`: The [`State`] it is checked against.
+///
+/// # Returns
+/// Every [`UnknownReference`], in the order of the workflow and without duplicates.
+pub fn unknown_references(workflow: &Workflow, state: &State) -> Vec<UnknownReference> {
+    let mut refs = References::default();
+    walk_workflow_preorder(&workflow.start, &mut refs);
+
+    let mut unknown: Vec<UnknownReference> = Vec::new();
+    let mut push = |reference: UnknownReference| {
+        if !unknown.contains(&reference) {
+            unknown.push(reference);
+        }
+    };
+    if !workflow.user.name.is_empty() && !state.users.iter().any(|user| user.name == workflow.user.name) {
+        push(UnknownReference::User { user: workflow.user.name.clone() });
+    }
+    for (node, dataset) in refs.datasets {
+        if !refs.produced.contains(&dataset) && !state.datasets.iter().any(|known| known.name == dataset) {
+            push(UnknownReference::Dataset { node, dataset });
+        }
+    }
+    for (node, location) in refs.locations {
+        if !state.locations.iter().any(|known| known.name == location) {
+            push(UnknownReference::Location { node, location });
+        }
+    }
+    unknown
+}
+
+/// Checks the workflow of a request against the resolved state, before deliberating on it.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to check.
+/// - `state`: The [`State`] it is checked against.
+/// - `level`: What to do with unknown references: nothing ([`LintLevel::Allow`]), log them ([`LintLevel::Warn`]) or refuse
+///   the workflow ([`LintLevel::Deny`]).
+/// - `reference`: The verdict reference of the request.
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `400 Bad Request` problem of type [`UNKNOWN_REFERENCES_PROBLEM`] if
+/// the workflow refers to anything unknown and `level` is [`LintLevel::Deny`]. The problem lists every
+/// [`UnknownReference`] as an `unknown` member.
+pub(crate) fn check_references(workflow: &Workflow, state: &State, level: LintLevel, reference: &str) -> Result<(), Rejection> {
+    if level == LintLevel::Allow {
+        return Ok(());
+    }
+    let unknown: Vec<UnknownReference> = unknown_references(workflow, state);
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    if level == LintLevel::Warn {
+        for r in &unknown {
+            warn!("Workflow '{}' refers to something not in the state: {r} | request id: {reference}", workflow.id);
+        }
+        return Ok(());
+    }
+
+    info!("Refusing workflow '{}' because it refers to {} unknown thing(s) | request id: {reference}", workflow.id, unknown.len());
+    let p = ProblemDetails::new()
+        .with_type(warp::http::Uri::from_static(UNKNOWN_REFERENCES_PROBLEM))
+        .with_title("Workflow refers to unknown datasets, locations or users")
+        .with_status(StatusCode::BAD_REQUEST)
+        .with_detail(unknown.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n"));
+    let mut problem = Problem::from(p);
+    problem.0.extensions.insert("unknown".into(), serde_json::to_value(&unknown).unwrap_or(Value::Null));
+    Err(warp::reject::custom(problem))
+}
@@ -0,0 +1,181 @@
+//! Implements a [`Prefilter`] of allow/deny rules that may answer a question before the reasoner is asked.
+//!
+//! Where the [`VerdictPipeline`](crate::postprocess::VerdictPipeline) alters the reasoner's answers afterwards, the
+//! prefilter is consulted before the reasoner connector is. It is configured with [`PrefilterRule`]s on the user of a
+//! workflow and the datasets and locations it accesses; the first rule that matches a question answers it, such that
+//! the reasoner is not bothered at all. This spares the reasoner obvious questions, and gives operators an emergency
+//! kill-switch to deny everything involving a particular dataset without touching the policy. Every match is recorded
+//! in the audit log with the identifier of the rule.
+
+use reasonerconn::QuestionKind;
+use serde::{Deserialize, Serialize};
+use workflow::spec::{Dataset, ElemCommit, ElemTask, Workflow};
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+
+/***** HELPERS *****/
+/// A single access to a dataset that a question is about.
+#[derive(Clone, Debug)]
+pub(crate) struct Access {
+    /// The location where the dataset is read, if known (results are sent to the user, who is not at a location).
+    pub(crate) location: Option<String>,
+    /// The dataset read, if any (a task without inputs reads none).
+    pub(crate) dataset:  Option<String>,
+}
+
+/// Collects the accesses in the part of a workflow that a question is about.
+struct AccessCollector<'q> {
+    /// The task to collect the accesses of, or [`None`] to collect those of the whole workflow.
+    task:     Option<&'q str>,
+    /// The dataset to collect the accesses of, or [`None`] to collect all inputs.
+    dataset:  Option<&'q str>,
+    /// The accesses found so far.
+    accesses: Vec<Access>,
+}
+impl AccessCollector<'_> {
+    /// Records that an element reads its inputs, which happens where they are pulled from or else where the element is.
+    fn read(&mut self, location: Option<&String>, inputs: &[Dataset]) {
+        let before: usize = self.accesses.len();
+        for input in inputs.iter().filter(|input| self.dataset.is_none_or(|dataset| input.name == dataset)) {
+            self.accesses.push(Access { location: input.from.as_ref().or(location).cloned(), dataset: Some(input.name.clone()) });
+        }
+        // Datasets not planned as input live wherever the element is
+        if self.accesses.len() == before {
+            self.accesses.push(Access { location: location.cloned(), dataset: self.dataset.map(String::from) });
+        }
+    }
+}
+impl WorkflowVisitor for AccessCollector<'_> {
+    fn visit_task(&mut self, task: &ElemTask) {
+        if self.task.is_none_or(|id| task.id == id) {
+            self.read(task.location.as_ref(), &task.input);
+        }
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        if self.task.is_none() {
+            self.read(commit.location.as_ref(), &commit.input);
+        }
+    }
+}
+
+/// Finds the accesses that a question is about.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] in question.
+/// - `question`: What is asked.
+///
+/// # Returns
+/// The datasets read and where. Empty if the task asked about does not occur in the workflow.
+pub(crate) fn accesses_of(workflow: &Workflow, question: &QuestionKind) -> Vec<Access> {
+    let mut collector = AccessCollector { task: None, dataset: None, accesses: Vec::new() };
+    match question {
+        QuestionKind::ExecuteTask { task } => collector.task = Some(task),
+        QuestionKind::AccessData { data, task: Some(task) } => {
+            collector.task = Some(task);
+            collector.dataset = Some(data);
+        },
+        QuestionKind::AccessData { data, task: None } => return vec![Access { location: None, dataset: Some(data.clone()) }],
+        QuestionKind::ValidateWorkflow => {},
+    }
+    walk_workflow_preorder(&workflow.start, &mut collector);
+    collector.accesses
+}
+
+/***** LIBRARY *****/
+/// What a [`PrefilterRule`] answers the questions it matches with.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrefilterEffect {
+    /// Allows the question.
+    Allow,
+    /// Denies the question.
+    Deny,
+}
+
+/// A rule of the [`Prefilter`]. Every condition that is non-empty must hold for the rule to match.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PrefilterRule {
+    /// Identifies the rule in the audit log.
+    pub id: String,
+    /// What to answer the questions the rule matches with.
+    pub effect: PrefilterEffect,
+    /// The users instigating the workflow that the rule applies to.
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// The datasets that the rule applies to.
+    #[serde(default)]
+    pub datasets: Vec<String>,
+    /// The locations where datasets are read that the rule applies to.
+    #[serde(default)]
+    pub locations: Vec<String>,
+    /// The kinds of questions that the rule applies to (see [`reasonerconn::QUESTION_KINDS`]).
+    #[serde(default)]
+    pub questions: Vec<String>,
+    /// The reason for denial given to clients, if any. Meaningless for rules that allow.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+impl PrefilterRule {
+    /// Checks whether the rule matches a single access.
+    fn matches_access(&self, access: &Access) -> bool {
+        (self.datasets.is_empty() || access.dataset.as_ref().is_some_and(|dataset| self.datasets.contains(dataset)))
+            && (self.locations.is_empty() || access.location.as_ref().is_some_and(|location| self.locations.contains(location)))
+    }
+
+    /// Checks whether the rule matches a question.
+    ///
+    /// Rules that deny match if any of the accesses of the question matches, but rules that allow only match if all of
+    /// them do, such that allowing one dataset never lets another through.
+    fn matches(&self, kind: &str, user: &str, accesses: &[Access]) -> bool {
+        if (!self.questions.is_empty() && !self.questions.iter().any(|question| question == kind))
+            || (!self.users.is_empty() && !self.users.iter().any(|u| u == user))
+        {
+            return false;
+        }
+        match self.effect {
+            PrefilterEffect::Allow => !accesses.is_empty() && accesses.iter().all(|access| self.matches_access(access)),
+            PrefilterEffect::Deny => accesses.iter().any(|access| self.matches_access(access)),
+        }
+    }
+}
+
+/// Answers questions with static allow/deny rules before the reasoner is asked.
+///
+/// The default Prefilter has no rules, and leaves every question to the reasoner.
+#[derive(Clone, Debug, Default)]
+pub struct Prefilter {
+    /// The rules to check, in order.
+    rules: Vec<PrefilterRule>,
+}
+impl Prefilter {
+    /// Constructor for the Prefilter.
+    ///
+    /// # Arguments
+    /// - `rules`: The [`PrefilterRule`]s to check, in order. The first that matches a question answers it.
+    ///
+    /// # Returns
+    /// A new Prefilter.
+    #[inline]
+    pub fn new(rules: impl IntoIterator<Item = PrefilterRule>) -> Self { Self { rules: rules.into_iter().collect() } }
+
+    /// Returns whether this prefilter has no rules.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.rules.is_empty() }
+
+    /// Finds the rule that answers a question, if any.
+    ///
+    /// # Arguments
+    /// - `kind`: The kind of question (one of [`reasonerconn::QUESTION_KINDS`]).
+    /// - `workflow`: The [`Workflow`] in question.
+    /// - `question`: What is asked about it.
+    ///
+    /// # Returns
+    /// The first [`PrefilterRule`] that matches the question, or [`None`] if the reasoner should be asked.
+    pub fn check(&self, kind: &str, workflow: &Workflow, question: &QuestionKind) -> Option<&PrefilterRule> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let accesses: Vec<Access> = accesses_of(workflow, question);
+        self.rules.iter().find(|rule| rule.matches(kind, &workflow.user.name, &accesses))
+    }
+}
@@ -1,6 +1,73 @@
+use std::error::Error;
+
+use auth_resolver::AuthFailureReason;
 use problem_details::ProblemDetails;
+use serde_json::{Map, Value};
+use warp::http::{StatusCode, Uri};
+
+/// The extension members of a [`Problem`], next to the standard ones.
+pub type ProblemExtensions = Map<String, Value>;
 
 #[derive(Debug)]
-pub struct Problem(pub ProblemDetails);
+pub struct Problem(pub ProblemDetails<ProblemExtensions>);
+
+impl Problem {
+    /// Adds the chain of causes of an error to the problem, as a `causes` member.
+    ///
+    /// # Arguments
+    /// - `err`: The error whose [`causes()`] to add. Nothing is added if it has no source.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    pub fn with_causes(mut self, err: &(dyn Error + 'static)) -> Self {
+        let causes: Vec<String> = causes(err).into_iter().skip(1).collect();
+        if !causes.is_empty() {
+            self.0.extensions.insert("causes".into(), Value::from(causes));
+        }
+        self
+    }
+}
+
+impl From<ProblemDetails> for Problem {
+    #[inline]
+    fn from(value: ProblemDetails) -> Self { Self(value.with_extensions(ProblemExtensions::new())) }
+}
 
 impl warp::reject::Reject for Problem {}
+
+/// Collects the messages of an error and all of its sources.
+///
+/// # Arguments
+/// - `err`: The error to start with.
+///
+/// # Returns
+/// The message of `err`, followed by that of its source, that of the source's source, etc.
+pub fn causes(err: &(dyn Error + 'static)) -> Vec<String> {
+    std::iter::successors(Some(err), |err| err.source()).map(|err| err.to_string()).collect()
+}
+
+/// Builds the problem for a request that could not be authenticated.
+///
+/// # Arguments
+/// - `reason`: Why authentication failed, which determines the type of the problem (e.g., `/problems/unauthorized/expired`).
+/// - `detail`: A human-readable explanation of what was wrong with the credentials.
+///
+/// # Returns
+/// A `401 Unauthorized` [`ProblemDetails`].
+pub fn unauthorized(reason: AuthFailureReason, detail: String) -> ProblemDetails {
+    use AuthFailureReason::*;
+    let ty: &'static str = match reason {
+        MissingCredentials => "/problems/unauthorized/missing-credentials",
+        Malformed => "/problems/unauthorized/malformed",
+        UnknownKey => "/problems/unauthorized/unknown-key",
+        InvalidSignature => "/problems/unauthorized/invalid-signature",
+        DisallowedAlgorithm => "/problems/unauthorized/disallowed-algorithm",
+        Expired => "/problems/unauthorized/expired",
+        NotYetValid => "/problems/unauthorized/not-yet-valid",
+        WrongIssuer => "/problems/unauthorized/wrong-issuer",
+        WrongAudience => "/problems/unauthorized/wrong-audience",
+        MissingClaim => "/problems/unauthorized/missing-claim",
+        Other => "/problems/unauthorized",
+    };
+    ProblemDetails::new().with_type(Uri::from_static(ty)).with_title("Unauthorized").with_status(StatusCode::UNAUTHORIZED).with_detail(detail)
+}
@@ -0,0 +1,129 @@
+//! Implements queries, which ask what holds under the active policy instead of whether something is allowed.
+//!
+//! Policy exploration tools want to ask questions like "which datasets may user U access?", which have no single
+//! verdict as answer. A query is given in the language of the reasoner (e.g., an eFLINT JSON expression) and answered
+//! with the instances that hold under the active policy and the current state, as listed by the reasoner backend. Only
+//! some reasoner connectors answer queries; they list `query` among their capabilities.
+//!
+//! Like any other question, queries are recorded in the audit log. They never grant anything, so they are not subject to
+//! the fallback behaviour: if the reasoner cannot be consulted, the query fails.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::{AuditLogger, SessionedConnectorAuditLogger};
+use auth_resolver::{AuthContext, AuthResolver};
+use deliberation::spec::{EvaluationContext, QueryRequest, QueryResponse};
+use log::{debug, error, info};
+use policy::{Policy, PolicyDataAccess, PolicyDataError};
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerQueryResponse};
+use serde::Serialize;
+use state_resolver::{State, StateResolver};
+use warp::Filter;
+use warp::hyper::StatusCode;
+use warp::reject::Rejection;
+
+use crate::Srv;
+use crate::counters::attach_counters;
+use crate::deliberation::{attach_delegation, audit_log_unavailable, confirm_logged, end_session, state_snapshot_id};
+use crate::duties::attach_duties;
+use crate::problem::Problem;
+use crate::queue::QueuePermit;
+use crate::retrospective::reasoner_unavailable;
+
+/***** LIBRARY *****/
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // List what holds under the active policy
+    // POST /v1/deliberation/query
+    // out:
+    //  200 QueryResponse
+    //  400 problem+json (query not understood by the reasoner)
+    //  404 problem+json (no active policy)
+    //  503 problem+json (reasoner or audit log unavailable)
+
+    async fn handle_query(auth_ctx: AuthContext, this: Arc<Self>, body: QueryRequest) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling query request");
+        let QueryRequest { use_case, query, priority } = body;
+        let reference: String = this.ids.generate();
+
+        debug!("Retrieving state...");
+        let mut state: State = match this.stateresolver.get_state(use_case).await {
+            Ok(state) => state,
+            Err(err) => {
+                error!("Could not retrieve state: {err} | request id: {reference}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string());
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        attach_counters(this.usage_counters.as_deref(), &mut state, &reference).await?;
+        attach_duties(this.duties.as_deref(), &mut state, &auth_ctx.initiator, &reference).await?;
+        attach_delegation(&mut state, &auth_ctx);
+
+        debug!("Retrieving active policy...");
+        let policy: Policy = match this.policystore.get_active().await {
+            Ok(policy) => policy,
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new().with_status(StatusCode::NOT_FOUND).with_detail("There is no active policy to query");
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(PolicyDataError::GeneralError(err)) => {
+                error!("Failed to get active policy: {err} | request id: {reference}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        let version: i64 = policy.version.version.unwrap_or(-1);
+        let evaluated_with = EvaluationContext {
+            policy_version:    policy.version.version,
+            connector_context: C::hash(),
+            state_snapshot:    Some(state_snapshot_id(&state)),
+        };
+
+        this.logger.log_query_request(&reference, &auth_ctx, version, &state, &query).await.map_err(|err| {
+            debug!("Could not log query request to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(&reference, err)
+        })?;
+
+        // Wait for our turn at the reasoner, like any other question
+        let _permit: QueuePermit =
+            this.question_queue.enter(priority).await.map_err(|err| reasoner_unavailable(&reference, ReasonerConnError::from(err)))?;
+
+        debug!("Consulting reasoner connector...");
+        let session = SessionedConnectorAuditLogger::new(reference.clone(), this.logger.clone());
+        let res: Result<ReasonerQueryResponse, ReasonerConnError> = this.reasonerconn.query(session.clone(), policy, state, query).await;
+        end_session(session).await?;
+        let instances: Vec<serde_json::Value> = match res {
+            Ok(res) => res.instances,
+            Err(err @ ReasonerConnError::Invalid { .. }) => {
+                debug!("Reasoner refused query: {err} | request id: {reference}");
+                let p = ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail(err.to_string());
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(err) => return Err(reasoner_unavailable(&reference, err)),
+        };
+        confirm_logged(&this.logger, &reference).await?;
+
+        Ok(warp::reply::json(&QueryResponse { query_reference: reference, instances, evaluated_with }))
+    }
+
+    pub fn query_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let query = warp::post()
+            .and(warp::path!("query"))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_query);
+
+        warp::path("v1").and(warp::path("deliberation")).and(query)
+    }
+}
@@ -0,0 +1,183 @@
+//! Implements a bounded priority queue in front of the reasoner connector.
+//!
+//! Reasoner backends can only answer so many questions at once. Without a queue, a planner validating hundreds of
+//! workflows in bulk would happily take all of that capacity, leaving someone waiting on an interactive check to wait
+//! behind it. The [`QuestionQueue`] limits how many questions are put to the reasoner at the same time and lets the
+//! rest wait in order of their [`Priority`], first-come first-served within the same priority. When too many questions
+//! are waiting already, new ones are turned away immediately instead.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::sync::{Arc, Mutex};
+
+use deliberation::spec::Priority;
+use log::debug;
+use reasonerconn::ReasonerMetric;
+use tokio::sync::oneshot;
+
+/***** ERRORS *****/
+/// Occurs when a question cannot be queued because too many are waiting already.
+#[derive(Debug)]
+pub struct QueueFull {
+    /// The maximum number of waiting questions.
+    pub capacity: usize,
+}
+impl Display for QueueFull {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "Too many questions are waiting for the reasoner (at most {} may wait)", self.capacity)
+    }
+}
+impl Error for QueueFull {}
+
+/***** AUXILLARY *****/
+/// Configures how many questions a [`QuestionQueue`] lets through and how many may wait.
+#[derive(Clone, Copy, Debug)]
+pub struct QuestionQueueConfig {
+    /// The maximum number of questions put to the reasoner at the same time. `0` lets all questions through immediately.
+    pub concurrency: usize,
+    /// The maximum number of questions waiting for their turn.
+    pub capacity:    usize,
+}
+impl Default for QuestionQueueConfig {
+    #[inline]
+    fn default() -> Self { Self { concurrency: 16, capacity: 1024 } }
+}
+
+/// The shared state of a [`QuestionQueue`] and its [`QueuePermit`]s.
+#[derive(Debug, Default)]
+struct QueueState {
+    /// The number of questions currently being answered.
+    running: usize,
+    /// The questions waiting for their turn, by priority, in order of arrival.
+    waiting: BTreeMap<Priority, VecDeque<oneshot::Sender<QueuePermit>>>,
+}
+impl QueueState {
+    /// Forgets about the questions that stopped waiting (e.g., because the client went away).
+    fn prune(&mut self) {
+        for queue in self.waiting.values_mut() {
+            queue.retain(|tx| !tx.is_closed());
+        }
+    }
+
+    /// Returns the number of questions waiting with the given priority.
+    fn depth(&self, priority: Priority) -> usize {
+        self.waiting.get(&priority).map(|queue| queue.iter().filter(|tx| !tx.is_closed()).count()).unwrap_or(0)
+    }
+}
+
+/***** LIBRARY *****/
+/// Gives whoever holds it the right to put a question to the reasoner.
+///
+/// Dropping it hands the right over to the next question in the [`QuestionQueue`].
+#[derive(Debug)]
+pub struct QueuePermit {
+    /// The state of the queue we came from.
+    state:   Arc<Mutex<QueueState>>,
+    /// Whether this permit was never handed out, and so should not be handed over either.
+    defused: bool,
+}
+impl Drop for QueuePermit {
+    fn drop(&mut self) {
+        if self.defused {
+            return;
+        }
+
+        // Hand our place over to the longest waiting question with the highest priority, if any
+        let mut state = self.state.lock().unwrap();
+        while let Some(tx) = state.waiting.values_mut().rev().find_map(VecDeque::pop_front) {
+            match tx.send(QueuePermit { state: self.state.clone(), defused: false }) {
+                Ok(()) => return,
+                Err(mut permit) => permit.defused = true,
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+/// Limits the number of questions put to the reasoner at the same time, letting the rest wait by priority.
+#[derive(Debug)]
+pub struct QuestionQueue {
+    /// Determines how many questions we let through and how many may wait.
+    config: QuestionQueueConfig,
+    /// The questions running and waiting.
+    state:  Arc<Mutex<QueueState>>,
+}
+
+impl Default for QuestionQueue {
+    #[inline]
+    fn default() -> Self { Self::new(QuestionQueueConfig::default()) }
+}
+
+impl QuestionQueue {
+    /// Constructor for the QuestionQueue.
+    ///
+    /// # Arguments
+    /// - `config`: The [`QuestionQueueConfig`] that determines how many questions we let through and how many may wait.
+    ///
+    /// # Returns
+    /// A new, empty QuestionQueue.
+    #[inline]
+    pub fn new(config: QuestionQueueConfig) -> Self { Self { config, state: Arc::new(Mutex::new(QueueState::default())) } }
+
+    /// Waits until a question may be put to the reasoner.
+    ///
+    /// # Arguments
+    /// - `priority`: The [`Priority`] of the question.
+    ///
+    /// # Returns
+    /// A [`QueuePermit`] that should be held for as long as the reasoner is answering the question.
+    ///
+    /// # Errors
+    /// This function errors if the question cannot wait because the queue is full.
+    pub async fn enter(&self, priority: Priority) -> Result<QueuePermit, QueueFull> {
+        let rx: oneshot::Receiver<QueuePermit> = {
+            let mut state = self.state.lock().unwrap();
+            state.prune();
+
+            // Go ahead if there is room and nobody is before us
+            let before_us: bool = state.waiting.range(priority..).any(|(_, queue)| !queue.is_empty());
+            if self.config.concurrency == 0 || (state.running < self.config.concurrency && !before_us) {
+                state.running += 1;
+                return Ok(QueuePermit { state: self.state.clone(), defused: false });
+            }
+
+            // Otherwise, wait for our turn
+            if state.waiting.values().map(VecDeque::len).sum::<usize>() >= self.config.capacity {
+                return Err(QueueFull { capacity: self.config.capacity });
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting.entry(priority).or_default().push_back(tx);
+            rx
+        };
+        debug!("Waiting for a turn at the reasoner with priority {priority:?}...");
+
+        // Senders are only dropped unsent once we stopped listening, so this always gives us a permit
+        Ok(rx.await.expect("Queued question lost its place in the queue"))
+    }
+
+    /// Returns the queue's current measurements, to be exposed on the metrics endpoint.
+    pub fn metrics(&self) -> Vec<ReasonerMetric> {
+        let state = self.state.lock().unwrap();
+        vec![
+            ReasonerMetric::new(
+                "policy_reasoner_queue_depth",
+                "The number of questions waiting for their turn at the reasoner.",
+                state.depth(Priority::Interactive) as f64,
+            )
+            .with_label("priority", "interactive"),
+            ReasonerMetric::new(
+                "policy_reasoner_queue_depth",
+                "The number of questions waiting for their turn at the reasoner.",
+                state.depth(Priority::Batch) as f64,
+            )
+            .with_label("priority", "batch"),
+            ReasonerMetric::new(
+                "policy_reasoner_queue_running",
+                "The number of questions currently being answered by the reasoner.",
+                state.running as f64,
+            ),
+        ]
+    }
+}
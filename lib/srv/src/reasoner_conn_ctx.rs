@@ -1,15 +1,20 @@
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use audit_logger::AuditLogger;
+use audit_logger::{AuditLogger, ConnectorContext as _};
 use auth_resolver::{AuthContext, AuthResolver};
+use log::{error, info};
 use policy::PolicyDataAccess;
-use reasonerconn::ReasonerConnector;
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerCapabilities, ReasonerConnector};
 use serde::Serialize;
 use state_resolver::StateResolver;
 use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderMap, StatusCode};
 
 use crate::Srv;
+use crate::problem::Problem;
 
 #[derive(Serialize)]
 struct ConnectorContextViewModel<T> {
@@ -17,6 +22,15 @@ struct ConnectorContextViewModel<T> {
     hash:    String,
 }
 
+#[derive(Serialize)]
+struct CapabilitiesViewModel {
+    id: String,
+    version: String,
+    context_hash: String,
+    #[serde(flatten)]
+    capabilities: ReasonerCapabilities,
+}
+
 impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
 where
     L: 'static + AuditLogger + Send + Sync + Clone,
@@ -36,6 +50,51 @@ where
         Ok(warp::reply::json(&ConnectorContextViewModel { context: Box::new(C::context()), hash: C::hash() }))
     }
 
+    // Get what the reasoner connector can do
+    // GET /v1/reasoner/capabilities
+    // out:
+    // 200 CapabilitiesViewModel
+
+    async fn handle_reasoner_capabilities(_: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        let context = C::context();
+        Ok(warp::reply::json(&CapabilitiesViewModel {
+            id: context.r#type(),
+            version: context.version(),
+            context_hash: C::hash(),
+            capabilities: this.reasonerconn.capabilities(),
+        }))
+    }
+
+    // Get what was exchanged with the reasoner backend for a question, for offline replay
+    // GET /v1/management/reasoner-connector/debug/{reference}
+    // out:
+    // 200 (connector-specific bundle, as attachment)
+    // 404 problem+json (no bundle kept for this question)
+
+    async fn handle_reasoner_debug_bundle(
+        reference: String,
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+    ) -> Result<warp::reply::WithHeader<warp::reply::Json>, warp::reject::Rejection> {
+        info!("Handling request by '{}' for the debug bundle of '{reference}'", auth_ctx.initiator);
+        match this.reasonerconn.debug_bundle(&reference).await {
+            Ok(Some(bundle)) => {
+                Ok(warp::reply::with_header(warp::reply::json(&bundle), "Content-Disposition", format!("attachment; filename=\"{reference}.json\"")))
+            },
+            Ok(None) => {
+                let p = ProblemDetails::new()
+                    .with_status(StatusCode::NOT_FOUND)
+                    .with_detail(format!("No debug bundle is kept for verdict reference '{reference}'"));
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+            Err(err) => {
+                error!("Could not retrieve debug bundle of '{reference}': {err}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string());
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
     pub fn reasoner_connector_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         let get_context = warp::get()
             .and(warp::path!("management" / "reasoner-connector-context"))
@@ -43,15 +102,27 @@ where
             .and(Self::with_self(this.clone()))
             .and_then(Self::handle_reasoner_conn_ctx);
 
-        warp::path("v1").and(get_context)
+        // Both policy experts and deliberation clients may want to know what they're dealing with
+        let get_capabilities = warp::get()
+            .and(warp::path!("reasoner" / "capabilities"))
+            .and(Self::with_reasoner_connector_api_auth(this.clone()).or(Self::with_deliberation_api_auth(this.clone())).unify())
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_reasoner_capabilities);
+
+        let get_debug_bundle = warp::get()
+            .and(warp::path!("management" / "reasoner-connector" / "debug" / String))
+            .and(Self::with_reasoner_connector_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_reasoner_debug_bundle);
+
+        warp::path("v1").and(get_context.or(get_capabilities).or(get_debug_bundle))
     }
 
     fn with_reasoner_connector_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
-        Self::with_self(this.clone()).and(warp::header::headers_cloned()).and_then(|this: Arc<Self>, headers| async move {
-            match this.pauthresolver.authenticate(headers).await {
-                Ok(v) => Ok(v),
-                Err(err) => Err(warp::reject::custom(err)),
-            }
-        })
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.pauthresolver, route, headers).await
+            },
+        )
     }
 }
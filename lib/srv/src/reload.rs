@@ -0,0 +1,193 @@
+//! Implements reloading the configuration of the reasoner connector and state resolver while the server runs.
+//!
+//! A reload is triggered by a `SIGHUP` or by a `POST /v1/management/reload`. It asks the reasoner connector and the
+//! state resolver to re-read their configuration (see [`ReasonerConnector::reload()`] and [`StateResolver::reload()`]).
+//! Questions that are being answered at that moment finish with the configuration they started with.
+//!
+//! The connector's context together with its reloadable configuration make up its _effective context_. Whenever a
+//! reload changes it, the new effective context is logged as a `REASONER-CONTEXT` statement, such that the audit log
+//! tells which configuration answered every question after it.
+
+use std::collections::hash_map::DefaultHasher;
+use std::error;
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+use std::hash::{Hash as _, Hasher as _};
+use std::sync::Arc;
+
+use audit_logger::{AuditLogger, ConnectorWithContext, ReasonerConnectorAuditLogger};
+use auth_resolver::{AuthContext, AuthResolver};
+use error_trace::ErrorTrace as _;
+use log::{error, info, warn};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnError, ReasonerConnector};
+use serde::Serialize;
+use serde_json::Value;
+use state_resolver::StateResolver;
+use tokio::signal::unix::{SignalKind, signal};
+use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderMap, StatusCode};
+
+use crate::Srv;
+use crate::problem::Problem;
+
+/***** ERRORS *****/
+/// Defines errors that may occur when reloading the configuration.
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The reasoner connector failed to reload.
+    ReasonerConnector { err: ReasonerConnError },
+    /// The state resolver failed to reload.
+    StateResolver { err: Box<dyn 'static + Send + Sync + error::Error> },
+    /// Failed to log the new effective context of the reasoner.
+    AuditLog { err: audit_logger::Error },
+}
+impl Display for ReloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use ReloadError::*;
+        match self {
+            ReasonerConnector { .. } => write!(f, "Failed to reload the reasoner connector"),
+            StateResolver { .. } => write!(f, "Failed to reload the state resolver"),
+            AuditLog { .. } => write!(f, "Failed to log the new effective context of the reasoner"),
+        }
+    }
+}
+impl error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use ReloadError::*;
+        match self {
+            ReasonerConnector { err } => Some(err),
+            StateResolver { err } => Some(&**err),
+            AuditLog { err } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// Reports the outcome of a reload.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReloadResult {
+    /// The hash of the effective context of the reasoner after the reload.
+    pub context_hash:    String,
+    /// Whether the reload changed the effective context of the reasoner (and logged it).
+    pub context_changed: bool,
+}
+
+/***** HELPERS *****/
+/// Computes the hash of the effective context of a reasoner connector.
+///
+/// # Arguments
+/// - `reasonerconn`: The [`ReasonerConnector`] to compute the hash of.
+///
+/// # Returns
+/// The hash of its context if it has no reloadable configuration, or else the hash of its context and configuration.
+pub(crate) fn effective_context_hash<L: ReasonerConnectorAuditLogger, C: ReasonerConnector<L>>(reasonerconn: &C) -> String {
+    let configuration: Value = reasonerconn.configuration();
+    if configuration.is_null() {
+        return C::hash();
+    }
+    let mut hasher = DefaultHasher::new();
+    C::hash().hash(&mut hasher);
+    configuration.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/***** LIBRARY *****/
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Reloads the configuration of the reasoner connector and state resolver.
+    ///
+    /// Reloads never run concurrently. If the effective context of the reasoner changed, it is logged.
+    ///
+    /// # Returns
+    /// A [`ReloadResult`] describing the effective context after the reload.
+    ///
+    /// # Errors
+    /// This function errors if the connector or resolver failed to reload (in which case they keep their old
+    /// configuration), or if the new effective context could not be logged.
+    pub async fn reload(&self) -> Result<ReloadResult, ReloadError> {
+        let mut last_hash = self.effective_context_hash.lock().await;
+        info!("Reloading configuration...");
+        self.reasonerconn.reload().await.map_err(|err| ReloadError::ReasonerConnector { err })?;
+        self.stateresolver.reload().await.map_err(|err| ReloadError::StateResolver { err: Box::new(err) })?;
+
+        // Tell the audit log if that changed anything about the reasoner
+        let context_hash: String = effective_context_hash::<L, C>(&self.reasonerconn);
+        let context_changed: bool = *last_hash != context_hash;
+        if context_changed {
+            info!("Effective reasoner context changed from '{}' to '{context_hash}'", *last_hash);
+            let context: Value = serde_json::json!({
+                "context": C::context(),
+                "configuration": self.reasonerconn.configuration(),
+            });
+            self.logger.log_effective_reasoner_context(&context, &context_hash).await.map_err(|err| ReloadError::AuditLog { err })?;
+            *last_hash = context_hash.clone();
+        }
+        Ok(ReloadResult { context_hash, context_changed })
+    }
+
+    /// Reloads the configuration whenever the process receives a `SIGHUP`.
+    ///
+    /// Only works on Unix. Runs until the server stops.
+    pub(crate) async fn reload_on_hangup(this: Arc<Self>) {
+        let mut hup = match signal(SignalKind::hangup()) {
+            Ok(handler) => handler,
+            Err(err) => {
+                error!("{}", error_trace::trace!(("Failed to register SIGHUP signal handler"), err));
+                warn!("Service will NOT reload its configuration on SIGHUP");
+                return;
+            },
+        };
+        while hup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading configuration...");
+            if let Err(err) = this.reload().await {
+                error!("{}", err.trace());
+            }
+        }
+    }
+
+    // Reload the configuration of the reasoner connector and state resolver
+    // POST /v1/management/reload
+    // out:
+    // 200 ReloadResult
+    // 500 problem+json (failed to reload; the old configuration is kept)
+
+    async fn handle_reload(auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        info!("Handling request by '{}' to reload the configuration", auth_ctx.initiator);
+        match this.reload().await {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(err) => {
+                error!("{}", err.trace());
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.trace().to_string());
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    pub fn reload_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let reload = warp::post()
+            .and(warp::path!("management" / "reload"))
+            .and(Self::with_reload_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_reload);
+
+        warp::path("v1").and(reload)
+    }
+
+    fn with_reload_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.pauthresolver, route, headers).await
+            },
+        )
+    }
+}
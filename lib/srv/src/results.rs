@@ -0,0 +1,280 @@
+//! Implements asynchronous deliberation, for questions that take the reasoner a long time to answer.
+//!
+//! Instead of keeping the connection open until the reasoner is done, clients can add `?async=true` to any deliberation
+//! request. They then immediately get a reference back, while the question is answered in the background. The result
+//! can be polled at `GET /v1/deliberation/results/{reference}`, or, if the client also gave `&callback=<URL>`, it is
+//! POSTed to that URL as soon as it is known. Callbacks are only accepted to the origins the server was configured with
+//! (see [`Srv::with_callback_origins()`]), such that clients cannot make the reasoner send requests wherever they like.
+//!
+//! Results are only kept for the last [`ASYNC_RESULTS_CAPACITY`] questions, and are only shown to whoever asked them.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use log::{debug, info, warn};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use state_resolver::StateResolver;
+use warp::Filter;
+use warp::hyper::StatusCode;
+use warp::reject::Rejection;
+use warp::reply::{Json, Reply, WithStatus};
+
+use crate::problem::Problem;
+use crate::{Srv, models};
+
+/***** CONSTANTS *****/
+/// The maximum number of asynchronous results kept in the [`AsyncResults`].
+const ASYNC_RESULTS_CAPACITY: usize = 4096;
+
+/// How long a callback gets to accept the result of a question.
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/***** HELPERS *****/
+/// Determines the origin (`<scheme>://<host>[:<port>]`) of a URL, leaving out the port if it is the default one of the scheme.
+///
+/// # Arguments
+/// - `url`: The URL to determine the origin of.
+///
+/// # Returns
+/// The origin, or [`None`] if the URL is not a valid URL with a host.
+fn origin_of(url: &str) -> Option<String> {
+    let origin: reqwest::Url = reqwest::Url::parse(url).ok()?;
+    if origin.host().is_none() {
+        return None;
+    }
+    Some(origin.origin().ascii_serialization())
+}
+
+/// Turns the outcome of a deliberation handler into the status code and body it would have sent.
+async fn into_result(res: Result<WithStatus<Json>, Rejection>) -> AsyncResult {
+    match res {
+        Ok(reply) => {
+            let res = reply.into_response();
+            let code: u16 = res.status().as_u16();
+            let body: Value = match warp::hyper::body::to_bytes(res.into_body()).await {
+                Ok(raw) => serde_json::from_slice(&raw).unwrap_or(Value::Null),
+                Err(_) => Value::Null,
+            };
+            AsyncResult::Done { code, body }
+        },
+        Err(err) => match err.find::<Problem>() {
+            Some(problem) => AsyncResult::Done {
+                code: problem.0.status.map(|status| status.as_u16()).unwrap_or(500),
+                body: serde_json::to_value(&problem.0).unwrap_or(Value::Null),
+            },
+            None => AsyncResult::Done { code: 500, body: Value::Null },
+        },
+    }
+}
+
+/***** LIBRARY *****/
+/// The state of a question answered in the background.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum AsyncResult {
+    /// The reasoner is still working on it.
+    Pending,
+    /// The question has been answered.
+    Done {
+        /// The status code the answer would have had if asked synchronously.
+        code: u16,
+        /// The body the answer would have had if asked synchronously (a verdict or a problem).
+        body: Value,
+    },
+}
+
+/// Keeps the results of the questions answered in the background.
+///
+/// Only the last [`ASYNC_RESULTS_CAPACITY`] questions are remembered; older ones are evicted first.
+#[derive(Debug)]
+pub struct AsyncResults {
+    /// The results (and who asked for them) by reference, and the order in which they were asked.
+    entries: Mutex<(HashMap<String, (String, AsyncResult)>, VecDeque<String>)>,
+    /// The origins that results may be POSTed to.
+    callback_origins: Vec<String>,
+    /// The client used to POST results to callbacks.
+    client: reqwest::Client,
+}
+impl Default for AsyncResults {
+    #[inline]
+    fn default() -> Self { Self::new(Vec::new()) }
+}
+impl AsyncResults {
+    /// Constructor for the AsyncResults.
+    ///
+    /// # Arguments
+    /// - `callback_origins`: The origins (e.g., `https://example.com`) that results may be POSTed to. Without any, callbacks are refused.
+    ///
+    /// # Returns
+    /// A new AsyncResults without any results.
+    pub fn new(callback_origins: Vec<String>) -> Self {
+        let callback_origins: Vec<String> = callback_origins
+            .into_iter()
+            .filter_map(|origin| match origin_of(&origin) {
+                Some(origin) => Some(origin),
+                None => {
+                    warn!("Ignoring callback origin '{origin}' because it is not a valid URL");
+                    None
+                },
+            })
+            .collect();
+        // Don't follow redirects, which could lead away from the allowed origins
+        let client: reqwest::Client =
+            reqwest::Client::builder().timeout(CALLBACK_TIMEOUT).redirect(reqwest::redirect::Policy::none()).build().unwrap_or_default();
+        Self { entries: Mutex::default(), callback_origins, client }
+    }
+
+    /// Checks whether results may be POSTed to the given callback.
+    ///
+    /// # Arguments
+    /// - `callback`: The URL given by the client.
+    ///
+    /// # Returns
+    /// True if the callback is on one of the allowed origins, or false otherwise.
+    pub fn allows_callback(&self, callback: &str) -> bool {
+        match origin_of(callback) {
+            Some(origin) => self.callback_origins.contains(&origin),
+            None => false,
+        }
+    }
+
+    /// Stores the result of a question.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference given to the client.
+    /// - `initiator`: Who asked the question.
+    /// - `result`: The [`AsyncResult`] to store.
+    pub fn insert(&self, reference: String, initiator: String, result: AsyncResult) {
+        let mut entries = self.entries.lock().unwrap();
+        let (results, order) = &mut *entries;
+        if results.insert(reference.clone(), (initiator, result)).is_none() {
+            order.push_back(reference);
+        }
+        while order.len() > ASYNC_RESULTS_CAPACITY {
+            if let Some(old) = order.pop_front() {
+                results.remove(&old);
+            }
+        }
+    }
+
+    /// Retrieves the result of a question.
+    ///
+    /// # Arguments
+    /// - `reference`: The reference given to the client.
+    /// - `initiator`: Who is asking for the result.
+    ///
+    /// # Returns
+    /// The [`AsyncResult`], or [`None`] if there is no such question or it was asked by someone else.
+    pub fn get(&self, reference: &str, initiator: &str) -> Option<AsyncResult> {
+        match self.entries.lock().unwrap().0.get(reference) {
+            Some((asker, result)) if asker == initiator => Some(result.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Answers a deliberation request either right away, or in the background if the client asked for that.
+    ///
+    /// # Arguments
+    /// - `this`: The server to store the result in.
+    /// - `initiator`: Who asked the question.
+    /// - `query`: The [`models::AsyncQuery`] that determines whether to answer in the background.
+    /// - `handler`: The future that answers the question.
+    ///
+    /// # Returns
+    /// The answer itself, or a `202 Accepted` with an [`models::AsyncAcceptedModel`] if answered in the background.
+    ///
+    /// # Errors
+    /// This function rejects with a `400 Bad Request` if the client gave a callback that is not on one of the allowed origins.
+    pub(crate) async fn answer_maybe_async(
+        this: Arc<Self>,
+        initiator: String,
+        query: models::AsyncQuery,
+        handler: impl 'static + Send + Future<Output = Result<WithStatus<Json>, Rejection>>,
+    ) -> Result<Box<dyn Reply>, Rejection> {
+        if !query.asynchronous {
+            return handler.await.map(|reply| Box::new(reply) as Box<dyn Reply>);
+        }
+        if let Some(callback) = &query.callback {
+            if !this.async_results.allows_callback(callback) {
+                debug!("Refusing callback '{callback}' because its origin is not allowed");
+                let p = ProblemDetails::new()
+                    .with_status(warp::http::StatusCode::BAD_REQUEST)
+                    .with_detail(format!("Callback '{callback}' is not on one of the origins that results may be sent to"));
+                return Err(warp::reject::custom(Problem::from(p)));
+            }
+        }
+
+        let reference: String = this.ids.generate();
+        info!("Answering question '{reference}' in the background");
+        this.async_results.insert(reference.clone(), initiator.clone(), AsyncResult::Pending);
+
+        let accepted = models::AsyncAcceptedModel { reference: reference.clone(), location: format!("/v1/deliberation/results/{reference}") };
+        tokio::spawn(async move {
+            let result: AsyncResult = into_result(handler.await).await;
+            debug!("Question '{reference}' answered in the background");
+            this.async_results.insert(reference.clone(), initiator, result.clone());
+
+            // Let the client know if it asked for that
+            if let Some(callback) = query.callback {
+                let res = this
+                    .async_results
+                    .client
+                    .post(&callback)
+                    .json(&models::AsyncResultModel { reference: reference.clone(), result })
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status());
+                if let Err(err) = res {
+                    warn!("Failed to deliver result of question '{reference}' to callback '{callback}': {err}");
+                }
+            }
+        });
+        Ok(Box::new(warp::reply::with_status(warp::reply::json(&accepted), StatusCode::ACCEPTED)))
+    }
+
+    // Retrieve the result of a question answered in the background
+    // GET /v1/deliberation/results/{reference}
+    // out:
+    //  200 AsyncResultModel (pending or done)
+    //  404 problem+json
+
+    async fn handle_get_async_result(reference: String, auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        match this.async_results.get(&reference, &auth_ctx.initiator) {
+            Some(result) => Ok(warp::reply::json(&models::AsyncResultModel { reference, result })),
+            None => {
+                let p = ProblemDetails::new()
+                    .with_status(warp::http::StatusCode::NOT_FOUND)
+                    .with_detail(format!("No result for question '{reference}' (it may have expired)"));
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    pub fn async_result_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::get()
+            .and(warp::path!("results" / String))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_async_result)
+    }
+}
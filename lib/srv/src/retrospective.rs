@@ -0,0 +1,324 @@
+//! Implements answering questions "as of" a moment in the past.
+//!
+//! Disputes about past requests ("would this have been allowed last March?") cannot be settled with the currently
+//! active policy. The retrospective API instead answers a question with the policy version that was active at the given
+//! moment and, if the [`StateResolver`] keeps snapshots, the state of that moment. Such answers never count as actual
+//! permissions: they get a verdict reference prefixed with [`RETROSPECTIVE_REFERENCE_PREFIX`] and are flagged as
+//! retrospective in the audit log.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::{AuditLogger, SessionedConnectorAuditLogger};
+use auth_resolver::{AuthContext, AuthResolver};
+use brane_ast::SymTable;
+use chrono::{DateTime, Local};
+use deliberation::spec::{AccessDataRequest, EvaluationContext, ExecuteTaskRequest, Priority, Verdict, WorkflowValidationRequest};
+use error_trace::ErrorTrace as _;
+use log::{debug, error, info, warn};
+use policy::{Policy, PolicyDataAccess, PolicyDataError};
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
+use serde::Serialize;
+use state_resolver::{State, StateResolver, StateResolverError as _};
+use warp::Filter;
+use warp::filters::path::FullPath;
+use warp::http::HeaderMap;
+use warp::hyper::StatusCode;
+use warp::reject::Rejection;
+use workflow::diagnostics::{Diagnostic, InlineStats};
+use workflow::{Workflow, preprocess};
+
+use crate::deliberation::{
+    audit_log_unavailable, compile_workflow, confirm_logged, end_session, log_preprocessed, state_snapshot_id, verdict_of, with_wir_body,
+};
+use crate::fallback::REASONER_UNAVAILABLE_PROBLEM;
+use crate::postprocess::Decision;
+use crate::problem::{Problem, causes};
+use crate::queue::QueuePermit;
+use crate::signatures::verify_images;
+use crate::{Srv, models};
+
+/***** CONSTANTS *****/
+/// The prefix given to the verdict references of retrospective questions, such that they are distinguishable in the audit log.
+pub const RETROSPECTIVE_REFERENCE_PREFIX: &'static str = "retrospective-";
+
+/***** AUXILLARY *****/
+/// The kinds of questions that can be answered retrospectively, with their task identifiers already resolved.
+#[derive(Debug)]
+enum RetrospectiveQuestion {
+    ExecuteTask { task_pc: String },
+    AccessData { data_id: String, task_pc: Option<String> },
+    ExecuteWorkflow,
+}
+
+/***** HELPERS *****/
+/// Builds the rejection for a retrospective question that the reasoner failed to answer.
+///
+/// Unlike for actual requests, there is no point in falling back to another answer.
+///
+/// # Arguments
+/// - `reference`: The verdict reference of the question.
+/// - `err`: The [`ReasonerConnError`] that occurred.
+///
+/// # Returns
+/// A [`Rejection`] carrying a `503 Service Unavailable` problem of type [`REASONER_UNAVAILABLE_PROBLEM`].
+pub(crate) fn reasoner_unavailable(reference: &str, err: ReasonerConnError) -> Rejection {
+    error!("Failed to consult reasoner: {} | request id: {reference}", causes(&err).join(": "));
+    let p = ProblemDetails::new()
+        .with_type(warp::http::Uri::from_static(REASONER_UNAVAILABLE_PROBLEM))
+        .with_title("Reasoner unavailable")
+        .with_status(StatusCode::SERVICE_UNAVAILABLE)
+        .with_detail(format!("The reasoner could not be consulted (verdict reference: {reference})"));
+    warp::reject::custom(Problem::from(p).with_causes(&err))
+}
+
+/***** LIBRARY *****/
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    /// Resolves the policy and state as of a moment in the past.
+    ///
+    /// # Arguments
+    /// - `this`: The server with the policy store and state resolver to use.
+    /// - `as_of`: The moment to resolve the policy and state of.
+    /// - `use_case`: The use-case to resolve the state of.
+    ///
+    /// # Returns
+    /// The policy active at `as_of`, the state and whether that state is a snapshot of `as_of` (or the current one otherwise).
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `404 Not Found` if no policy was active at `as_of` or the use-case is
+    /// unknown, or a `500 Internal Server Error` if either could not be retrieved otherwise.
+    async fn resolve_as_of(this: &Self, as_of: DateTime<Local>, use_case: String) -> Result<(Policy, State, bool), Rejection> {
+        let policy: Policy = match this.policystore.get_active_at(as_of).await {
+            Ok(policy) => policy,
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new().with_status(StatusCode::NOT_FOUND).with_detail(format!("No policy was active at {as_of}"));
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(PolicyDataError::GeneralError(err)) => {
+                error!("Failed to get policy active at {as_of}: {err}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+
+        // Use the state of the time if we know it, or else the current one
+        let state: Result<(State, bool), S::Error> = match this.stateresolver.get_state_at(use_case.clone(), as_of).await {
+            Ok(Some(state)) => Ok((state, true)),
+            Ok(None) => this.stateresolver.get_state(use_case).await.map(|state| (state, false)),
+            Err(err) => Err(err),
+        };
+        match state {
+            Ok((state, snapshot)) => Ok((policy, state, snapshot)),
+            Err(err) => {
+                let p = if let Some(use_case) = err.try_as_unknown_use_case() {
+                    ProblemDetails::new().with_status(StatusCode::NOT_FOUND).with_detail(format!("Unknown use-case '{use_case}'"))
+                } else {
+                    error!("Could not retrieve state: {}", err.trace());
+                    ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string())
+                };
+                Err(warp::reject::custom(Problem::from(p)))
+            },
+        }
+    }
+
+    /// Answers a question with the policy (and, if known, the state) of a moment in the past.
+    ///
+    /// # Arguments
+    /// - `auth_ctx`: The [`AuthContext`] of whoever asked.
+    /// - `this`: The server to answer with.
+    /// - `as_of`: The moment as of which to answer the question.
+    /// - `use_case`: The use-case the question is asked in.
+    /// - `workflow`: The WIR workflow the question is about.
+    /// - `priority`: How urgently the question should be answered.
+    /// - `question`: What is asked about `workflow`.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) if the workflow is invalid, the policy or state could not be resolved,
+    /// the reasoner failed or the question could not be recorded in the audit log.
+    async fn answer_retrospectively(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        as_of: DateTime<Local>,
+        use_case: String,
+        workflow: brane_ast::Workflow,
+        priority: Priority,
+        question: RetrospectiveQuestion,
+    ) -> Result<warp::reply::Json, Rejection> {
+        let reference: String = format!("{RETROSPECTIVE_REFERENCE_PREFIX}{}", this.ids.generate());
+        debug!("Answering {question:?} as of {as_of} | request id: {reference}");
+
+        // Compile the workflow
+        let (mut workflow, stats): (Workflow, InlineStats) = match compile_workflow(workflow, &this.workflow_limits)? {
+            Ok(res) => res,
+            Err(err) => {
+                let p = ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail(err.trace().to_string());
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        let warnings: Vec<Diagnostic> = workflow.diagnostics();
+
+        // Find out what applied back then
+        let (policy, state, state_snapshot): (Policy, State, bool) = Self::resolve_as_of(&this, as_of, use_case).await?;
+        workflow.resolve_images(&state.images);
+        verify_images(this.image_verifier.as_deref(), &mut workflow, &reference);
+        let evaluated_with = EvaluationContext {
+            policy_version:    policy.version.version,
+            connector_context: C::hash(),
+            state_snapshot:    Some(state_snapshot_id(&state)),
+        };
+        let version: i64 = policy.version.version.unwrap_or(-1);
+        let policy_hashes: Vec<String> = policy.content_hashes();
+        let connector_context_changed: bool = policy.version.reasoner_connector_context != C::hash();
+        if connector_context_changed {
+            warn!(
+                "Policy {version} was written for another reasoner base specification; its retrospective verdict may differ | request id: \
+                 {reference}"
+            );
+        }
+
+        // Record the question as retrospective before anything else
+        this.logger.log_retrospective_evaluation(&reference, &as_of.to_rfc3339(), state_snapshot).await.map_err(|err| {
+            debug!("Could not log retrospective evaluation to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(&reference, err)
+        })?;
+        let workflow_id: String = workflow.id.clone();
+        let task_id = |task_pc: &str| format!("{workflow_id}-{task_pc}-task");
+        let logged = match &question {
+            RetrospectiveQuestion::ExecuteTask { task_pc } => {
+                this.logger.log_exec_task_request(&reference, &auth_ctx, version, &policy_hashes, &state, &workflow, &task_id(task_pc)).await
+            },
+            RetrospectiveQuestion::AccessData { data_id, task_pc } => {
+                let task_id: Option<String> = task_pc.as_deref().map(task_id);
+                this.logger.log_data_access_request(&reference, &auth_ctx, version, &policy_hashes, &state, &workflow, data_id, &task_id).await
+            },
+            RetrospectiveQuestion::ExecuteWorkflow => {
+                this.logger.log_validate_workflow_request(&reference, &auth_ctx, version, &policy_hashes, &state, &workflow).await
+            },
+        };
+        logged.map_err(|err| {
+            debug!("Could not log retrospective request to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(&reference, err)
+        })?;
+        log_preprocessed(&this.logger, &reference, &stats).await?;
+
+        // Wait for our turn at the reasoner, like any other question
+        let _permit: QueuePermit =
+            this.question_queue.enter(priority).await.map_err(|err| reasoner_unavailable(&reference, ReasonerConnError::from(err)))?;
+
+        debug!("Consulting reasoner connector...");
+        let session = SessionedConnectorAuditLogger::new(reference.clone(), this.logger.clone());
+        let res: Result<ReasonerResponse, ReasonerConnError> = match question {
+            RetrospectiveQuestion::ExecuteTask { task_pc } => {
+                let task_id: String = task_id(&task_pc);
+                this.reasonerconn.execute_task(session.clone(), policy, state, workflow, task_id).await
+            },
+            RetrospectiveQuestion::AccessData { data_id, task_pc } => {
+                let task_id: Option<String> = task_pc.as_deref().map(task_id);
+                this.reasonerconn.access_data_request(session.clone(), policy, state, workflow, data_id, task_id).await
+            },
+            RetrospectiveQuestion::ExecuteWorkflow => this.reasonerconn.workflow_validation_request(session.clone(), policy, state, workflow).await,
+        };
+        end_session(session).await?;
+        let verdict: Verdict = match res {
+            Ok(v) => verdict_of(&reference, &warnings, &evaluated_with, Decision::from(v)),
+            Err(err) => return Err(reasoner_unavailable(&reference, err)),
+        };
+
+        this.logger.log_verdict(&reference, &verdict).await.map_err(|err| {
+            debug!("Could not log retrospective verdict to audit log : {:?} | request id: {}", err, reference);
+            audit_log_unavailable(&reference, err)
+        })?;
+        confirm_logged(&this.logger, &reference).await?;
+
+        Ok(warp::reply::json(&models::RetrospectiveResultModel { as_of, policy: version, state_snapshot, connector_context_changed, verdict }))
+    }
+
+    // Answer questions with the policy (and state) of a moment in the past
+    // POST /v1/retrospective/execute-task
+    // POST /v1/retrospective/access-data
+    // POST /v1/retrospective/execute-workflow
+    // out:
+    //  200 RetrospectiveResultModel
+    //  400 problem+json (invalid workflow)
+    //  404 problem+json (no policy active at that moment, or unknown use-case)
+    //  503 problem+json (reasoner or audit log unavailable)
+
+    async fn handle_retrospective_execute_task(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: models::RetrospectivePostModel<ExecuteTaskRequest>,
+    ) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling retrospective exec-task request");
+        let models::RetrospectivePostModel { as_of, request: ExecuteTaskRequest { use_case, workflow, task_id, call_path, priority, .. } } = body;
+        let task_pc: String = preprocess::structural_id(&workflow.table, call_path.iter().chain([&task_id]));
+        Self::answer_retrospectively(auth_ctx, this, as_of, use_case, workflow, priority, RetrospectiveQuestion::ExecuteTask { task_pc }).await
+    }
+
+    async fn handle_retrospective_access_data(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: models::RetrospectivePostModel<AccessDataRequest>,
+    ) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling retrospective access-data request");
+        let models::RetrospectivePostModel { as_of, request: AccessDataRequest { use_case, workflow, data_id, task_id, call_path, priority, .. } } =
+            body;
+        // NOTE: Deep clone of the table, for the same reason as in the deliberation API
+        let table: SymTable = (*workflow.table).clone();
+        let task_pc: Option<String> = task_id.map(|task_id| preprocess::structural_id(&table, call_path.iter().chain([&task_id])));
+        Self::answer_retrospectively(auth_ctx, this, as_of, use_case, workflow, priority, RetrospectiveQuestion::AccessData { data_id, task_pc })
+            .await
+    }
+
+    async fn handle_retrospective_execute_workflow(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: models::RetrospectivePostModel<WorkflowValidationRequest>,
+    ) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling retrospective validate request");
+        let models::RetrospectivePostModel { as_of, request: WorkflowValidationRequest { use_case, workflow, priority, .. } } = body;
+        Self::answer_retrospectively(auth_ctx, this, as_of, use_case, workflow, priority, RetrospectiveQuestion::ExecuteWorkflow).await
+    }
+
+    pub fn retrospective_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let exec_task = warp::post()
+            .and(warp::path!("execute-task"))
+            .and(Self::with_retrospective_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(with_wir_body(this.workflows.clone()))
+            .and_then(Self::handle_retrospective_execute_task);
+
+        let access_data = warp::post()
+            .and(warp::path!("access-data"))
+            .and(Self::with_retrospective_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(with_wir_body(this.workflows.clone()))
+            .and_then(Self::handle_retrospective_access_data);
+
+        let execute_workflow = warp::post()
+            .and(warp::path!("execute-workflow"))
+            .and(Self::with_retrospective_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(with_wir_body(this.workflows.clone()))
+            .and_then(Self::handle_retrospective_execute_workflow);
+
+        warp::path("v1").and(warp::path("retrospective")).and(exec_task.or(access_data).or(execute_workflow))
+    }
+
+    fn with_retrospective_api_auth(this: Arc<Self>) -> impl Filter<Extract = (AuthContext,), Error = warp::Rejection> + Clone {
+        Self::with_self(this.clone()).and(warp::path::full()).and(warp::header::headers_cloned()).and_then(
+            |this: Arc<Self>, route: FullPath, headers: HeaderMap| async move {
+                Self::authenticate(&this, &this.pauthresolver, route, headers).await
+            },
+        )
+    }
+}
@@ -0,0 +1,347 @@
+//! Implements evaluation sessions, which answer several questions about the same workflow in one go.
+//!
+//! Clients that need to ask more than one question about a workflow (e.g., whether each of its tasks may be executed)
+//! cannot tell whether separate requests were answered with the same policy: a policy may be activated in between. A
+//! session resolves the active policy and the state once, and answers all of its questions with them. The response
+//! tells which policy version and state that was, and the audit log records every question of the session against that
+//! same version.
+//!
+//! Sessions are all-or-nothing: if the reasoner fails to answer any of the questions, no verdict is given for any of them.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::{AuditLogger, SessionedConnectorAuditLogger};
+use auth_resolver::{AuthContext, AuthResolver};
+use brane_ast::SymTable;
+use deliberation::spec::{
+    DeliberationDenyResponse, DenyCategory, EvaluationContext, EvaluationSessionRequest, EvaluationSessionResponse, SessionQuestion, Verdict,
+};
+use log::{debug, error, info};
+use policy::{Policy, PolicyDataAccess, PolicyDataError};
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
+use serde::Serialize;
+use state_resolver::counters::{DATA_ACCESSES, TASK_EXECUTIONS};
+use state_resolver::{State, StateResolver};
+use warp::Filter;
+use warp::hyper::StatusCode;
+use warp::reject::Rejection;
+use warp::reply::{Json, WithStatus};
+use workflow::diagnostics::{Diagnostic, InlineStats};
+use workflow::{Workflow, preprocess};
+
+use crate::access_tokens::attach_access_token;
+use crate::counters::{attach_counters, count_usage, task_inputs};
+use crate::deliberation::{
+    attach_delegation, audit_log_unavailable, compile_workflow, confirm_logged, end_session, lint_workflow, log_preprocessed, shared_response,
+    state_snapshot_id, verdict_of, with_wir_body,
+};
+use crate::duties::{attach_duties, record_duties};
+use crate::fallback::REASONER_UNAVAILABLE_PROBLEM;
+use crate::notifications::{notify_owners, owners_involved};
+use crate::postprocess::{Decision, Question};
+use crate::precheck::check_references;
+use crate::problem::{Problem, causes};
+use crate::queue::QueuePermit;
+use crate::signatures::verify_images;
+use crate::{Srv, models};
+
+/***** AUXILLARY *****/
+/// A question of a session, with its task identifiers already resolved.
+#[derive(Debug)]
+enum ResolvedQuestion {
+    ExecuteTask { task_id: String },
+    AccessData { data_id: String, task_id: Option<String> },
+    ExecuteWorkflow,
+}
+impl ResolvedQuestion {
+    /// Returns the kind of question (one of [`reasonerconn::QUESTION_KINDS`]).
+    #[inline]
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::ExecuteTask { .. } => "execute-task",
+            Self::AccessData { .. } => "access-data",
+            Self::ExecuteWorkflow => "execute-workflow",
+        }
+    }
+}
+
+/***** HELPERS *****/
+/// Builds the rejection for a session of which the reasoner failed to answer a question.
+///
+/// # Arguments
+/// - `session_id`: The identifier of the session.
+/// - `err`: The [`ReasonerConnError`] that occurred.
+///
+/// # Returns
+/// A [`Rejection`] carrying a `503 Service Unavailable` problem of type [`REASONER_UNAVAILABLE_PROBLEM`].
+fn reasoner_unavailable(session_id: &str, err: ReasonerConnError) -> Rejection {
+    error!("Failed to consult reasoner: {} | session id: {session_id}", causes(&err).join(": "));
+    let p = ProblemDetails::new()
+        .with_type(warp::http::Uri::from_static(REASONER_UNAVAILABLE_PROBLEM))
+        .with_title("Reasoner unavailable")
+        .with_status(StatusCode::SERVICE_UNAVAILABLE)
+        .with_detail(format!("The reasoner could not be consulted, so none of the questions in session '{session_id}' are answered"));
+    warp::reject::custom(Problem::from(p).with_causes(&err))
+}
+
+/***** LIBRARY *****/
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // Answer several questions about the same workflow with the same policy and state
+    // POST /v1/deliberation/session
+    // out:
+    //  200 EvaluationSessionResponse
+    //  400 problem+json (invalid workflow, or no questions)
+    //  503 problem+json (reasoner or audit log unavailable)
+
+    async fn handle_evaluation_session(
+        auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: EvaluationSessionRequest,
+        accept_language: Option<String>,
+    ) -> Result<WithStatus<Json>, Rejection> {
+        info!("Handling evaluation session request");
+        let EvaluationSessionRequest { use_case, workflow, questions, priority } = body;
+        let language: Option<String> = this.messages.negotiate(accept_language.as_deref()).map(String::from);
+        let session_id: String = this.ids.generate();
+        if questions.is_empty() {
+            let p = ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail("An evaluation session must ask at least one question");
+            return Err(warp::reject::custom(Problem::from(p)));
+        }
+
+        // Resolve the task IDs in the workflow before we pass it by ownership to be converted
+        // NOTE: Deep clone of the table, for the same reason as in the deliberation API
+        let table: SymTable = (*workflow.table).clone();
+        let task_pcs: Vec<(SessionQuestion, Option<String>)> = questions
+            .into_iter()
+            .map(|question| {
+                let task_pc: Option<String> = match &question {
+                    SessionQuestion::ExecuteTask { task_id, call_path } => Some(preprocess::structural_id(&table, call_path.iter().chain([task_id]))),
+                    SessionQuestion::AccessData { task_id, call_path, .. } => {
+                        task_id.as_ref().map(|task_id| preprocess::structural_id(&table, call_path.iter().chain([task_id])))
+                    },
+                    SessionQuestion::ExecuteWorkflow => None,
+                };
+                (question, task_pc)
+            })
+            .collect();
+
+        debug!("Compiling WIR workflow to Checker Workflow...");
+        let (mut workflow, stats): (Workflow, InlineStats) = match compile_workflow(workflow, &this.workflow_limits)? {
+            Ok(res) => res,
+            Err(err) => {
+                return Ok(warp::reply::with_status(warp::reply::json(&err.to_string()), StatusCode::BAD_REQUEST));
+            },
+        };
+        let warnings: Vec<Diagnostic> = workflow.diagnostics();
+        lint_workflow(&workflow, &this.lint, &this.messages, language.as_deref(), &session_id)?;
+        let questions: Vec<(String, ResolvedQuestion)> = task_pcs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (question, task_pc))| {
+                let task_id: Option<String> = task_pc.map(|task_pc| format!("{}-{}-task", workflow.id, task_pc));
+                let question: ResolvedQuestion = match question {
+                    SessionQuestion::ExecuteTask { .. } => ResolvedQuestion::ExecuteTask { task_id: task_id.unwrap_or_default() },
+                    SessionQuestion::AccessData { data_id, .. } => ResolvedQuestion::AccessData { data_id, task_id },
+                    SessionQuestion::ExecuteWorkflow => ResolvedQuestion::ExecuteWorkflow,
+                };
+                (format!("{session_id}-{i}"), question)
+            })
+            .collect();
+
+        // Resolve the state and policy once, for all questions
+        debug!("Retrieving state...");
+        let mut state: State = match this.stateresolver.get_state(use_case.clone()).await {
+            Ok(state) => state,
+            Err(err) => {
+                error!("Could not retrieve state: {err} | session id: {session_id}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err.to_string());
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        check_references(&workflow, &state, this.state_check, &session_id)?;
+        let unknown_images: usize = workflow.resolve_images(&state.images);
+        if unknown_images > 0 {
+            debug!("Image of {unknown_images} task(s) in workflow '{}' is unknown", workflow.id);
+        }
+        verify_images(this.image_verifier.as_deref(), &mut workflow, &session_id);
+        attach_counters(this.usage_counters.as_deref(), &mut state, &session_id).await?;
+        attach_duties(this.duties.as_deref(), &mut state, &auth_ctx.initiator, &session_id).await?;
+        attach_delegation(&mut state, &auth_ctx);
+        let state_snapshot: String = state_snapshot_id(&state);
+
+        debug!("Retrieving active policy...");
+        let policy: Option<Policy> = match this.policystore.get_active().await {
+            Ok(policy) => Some(policy),
+            Err(PolicyDataError::NotFound) => None,
+            Err(PolicyDataError::GeneralError(err)) => {
+                error!("Failed to get currently active policy: {err}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        let policy_version: i64 = policy.as_ref().and_then(|policy| policy.version.version).unwrap_or(-1);
+        let policy_hashes: Vec<String> = policy.as_ref().map(Policy::content_hashes).unwrap_or_default();
+        info!("Answering {} question(s) with policy {policy_version} and state {state_snapshot} | session id: {session_id}", questions.len());
+        let evaluated_with = EvaluationContext {
+            policy_version:    policy.as_ref().and_then(|policy| policy.version.version),
+            connector_context: C::hash(),
+            state_snapshot:    Some(state_snapshot.clone()),
+        };
+
+        // Record every question against the same snapshot before anything else
+        for (reference, question) in &questions {
+            let logged = match question {
+                ResolvedQuestion::ExecuteTask { task_id } => {
+                    this.logger.log_exec_task_request(reference, &auth_ctx, policy_version, &policy_hashes, &state, &workflow, task_id).await
+                },
+                ResolvedQuestion::AccessData { data_id, task_id } => {
+                    this.logger
+                        .log_data_access_request(reference, &auth_ctx, policy_version, &policy_hashes, &state, &workflow, data_id, task_id)
+                        .await
+                },
+                ResolvedQuestion::ExecuteWorkflow => {
+                    this.logger.log_validate_workflow_request(reference, &auth_ctx, policy_version, &policy_hashes, &state, &workflow).await
+                },
+            };
+            logged.map_err(|err| {
+                debug!("Could not log session request to audit log : {:?} | request id: {}", err, reference);
+                audit_log_unavailable(reference, err)
+            })?;
+            log_preprocessed(&this.logger, reference, &stats).await?;
+        }
+
+        // Without a policy, everything is denied by default
+        let mut verdicts: Vec<Verdict> = Vec::with_capacity(questions.len());
+        let Some(policy) = policy else {
+            debug!("Denying all questions in session by default (no active policy found) | session id: {session_id}");
+            for (reference, _) in &questions {
+                let verdict = Verdict::Deny(DeliberationDenyResponse {
+                    shared: shared_response(reference, &warnings, &evaluated_with),
+                    reasons_for_denial: None,
+                    categories: vec![DenyCategory::NoApplicablePolicy],
+                    explanation: None,
+                });
+                this.logger.log_reasoner_response(reference, "<reasoner not queried because no active policy is present>", 0).await.map_err(
+                    |err| {
+                        debug!("Could not log \"reasoner response\" to audit log : {:?} | request id: {}", err, reference);
+                        audit_log_unavailable(reference, err)
+                    },
+                )?;
+                this.logger.log_verdict(reference, &verdict).await.map_err(|err| {
+                    debug!("Could not log verdict to audit log : {:?} | request id: {}", err, reference);
+                    audit_log_unavailable(reference, err)
+                })?;
+                confirm_logged(&this.logger, reference).await?;
+                verdicts.push(verdict);
+            }
+            let res = EvaluationSessionResponse { session_id, policy_version, state_snapshot, verdicts };
+            return Ok(warp::reply::with_status(warp::reply::json(&res), StatusCode::OK));
+        };
+
+        // Ask all questions in one turn at the reasoner, and only give verdicts if all of them are answered
+        let _permit: QueuePermit =
+            this.question_queue.enter(priority).await.map_err(|err| reasoner_unavailable(&session_id, ReasonerConnError::from(err)))?;
+        debug!("Consulting reasoner connector for {} question(s)...", questions.len());
+        let mut responses: Vec<ReasonerResponse> = Vec::with_capacity(questions.len());
+        for (reference, question) in &questions {
+            let session = SessionedConnectorAuditLogger::new(reference.clone(), this.logger.clone());
+            let res: Result<ReasonerResponse, ReasonerConnError> = match question {
+                ResolvedQuestion::ExecuteTask { task_id } => {
+                    this.reasonerconn.execute_task(session.clone(), policy.clone(), state.clone(), workflow.clone(), task_id.clone()).await
+                },
+                ResolvedQuestion::AccessData { data_id, task_id } => {
+                    this.reasonerconn
+                        .access_data_request(session.clone(), policy.clone(), state.clone(), workflow.clone(), data_id.clone(), task_id.clone())
+                        .await
+                },
+                ResolvedQuestion::ExecuteWorkflow => {
+                    this.reasonerconn.workflow_validation_request(session.clone(), policy.clone(), state.clone(), workflow.clone()).await
+                },
+            };
+            end_session(session).await?;
+            responses.push(res.map_err(|err| reasoner_unavailable(&session_id, err))?);
+        }
+
+        // Give the verdicts
+        for ((reference, question), response) in questions.iter().zip(responses) {
+            let q = Question {
+                kind:      question.kind(),
+                reference: reference.as_str(),
+                initiator: &auth_ctx.initiator,
+                use_case:  &use_case,
+                workflow:  &workflow.id,
+            };
+            let decision: Decision = Self::post_process(&this, &q, response).await?;
+            let allowed: bool = decision.allow;
+            let mut verdict: Verdict = verdict_of(reference, &warnings, &evaluated_with, decision);
+
+            this.logger.log_verdict(reference, &verdict).await.map_err(|err| {
+                debug!("Could not log session verdict to audit log : {:?} | request id: {}", err, reference);
+                audit_log_unavailable(reference, err)
+            })?;
+            confirm_logged(&this.logger, reference).await?;
+            record_duties(&*this.ids, this.duties.as_deref(), &verdict, &auth_ctx.initiator, reference).await;
+            let accessed: Option<&str> = match question {
+                ResolvedQuestion::AccessData { data_id, .. } => Some(data_id.as_str()),
+                _ => None,
+            };
+            notify_owners(this.notifier.as_ref(), &this.logger, &q, &verdict, owners_involved(this.notifier.as_ref(), &state, &workflow, accessed));
+            if allowed {
+                match question {
+                    ResolvedQuestion::ExecuteTask { task_id } => {
+                        let usage: Vec<String> = std::iter::once(auth_ctx.initiator.clone()).chain(task_inputs(&workflow, task_id)).collect();
+                        count_usage(this.usage_counters.as_deref(), TASK_EXECUTIONS, &usage, reference).await;
+                    },
+                    ResolvedQuestion::AccessData { data_id, task_id } => {
+                        let usage: Vec<String> = vec![auth_ctx.initiator.clone(), data_id.clone()];
+                        count_usage(this.usage_counters.as_deref(), DATA_ACCESSES, &usage, reference).await;
+                        attach_access_token(
+                            this.access_tokens.as_ref(),
+                            &mut verdict,
+                            &auth_ctx.initiator,
+                            &workflow.id,
+                            data_id,
+                            task_id.as_deref(),
+                        );
+                    },
+                    ResolvedQuestion::ExecuteWorkflow => {},
+                }
+            }
+            verdicts.push(this.messages.localize_verdict(language.as_deref(), verdict));
+        }
+
+        let res = EvaluationSessionResponse { session_id, policy_version, state_snapshot, verdicts };
+        Ok(warp::reply::with_status(warp::reply::json(&res), StatusCode::OK))
+    }
+
+    pub fn session_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let session = warp::post()
+            .and(warp::path!("session"))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(with_wir_body(this.workflows.clone()))
+            .and(warp::query::<models::AsyncQuery>())
+            .and(warp::header::optional::<String>("accept-language"))
+            .and_then(|auth_ctx: AuthContext, this: Arc<Self>, body, query, language| {
+                Self::answer_maybe_async(
+                    this.clone(),
+                    auth_ctx.initiator.clone(),
+                    query,
+                    Self::handle_evaluation_session(auth_ctx, this, body, language),
+                )
+            });
+
+        warp::path("v1").and(warp::path("deliberation")).and(session)
+    }
+}
@@ -0,0 +1,172 @@
+//! Implements verifying the signatures of the container images that run the tasks in a workflow.
+//!
+//! Policies such as "only signed code may process sensitive data" need to know whether the code of a task is signed,
+//! and by whom. Rather than having every reasoner verify signatures itself, the server verifies the signatures that the
+//! state claims for every [`Image`] with an [`ImageVerifier`] before deliberating, and records the names of the keys
+//! that verified in [`Image::signed_by`]. Reasoner connectors can then use those as plain facts, and since the workflow
+//! is logged with every request, so does the audit log.
+//!
+//! The [`StaticKeySet`] verifies detached signatures over the image digest, such as those produced by
+//! `cosign sign-blob`, against a fixed set of Ed25519 or ECDSA P-256 public keys.
+
+use std::error;
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+
+use base64ct::{Base64, Encoding as _};
+use log::{debug, warn};
+use ring::signature::{ECDSA_P256_SHA256_ASN1, ED25519, UnparsedPublicKey, VerificationAlgorithm};
+use workflow::{Image, Workflow};
+
+/***** CONSTANTS *****/
+/// The DER prefix of a SubjectPublicKeyInfo carrying an Ed25519 key (followed by the 32-byte key).
+const SPKI_ED25519_PREFIX: [u8; 12] = [0x30, 0x2A, 0x30, 0x05, 0x06, 0x03, 0x2B, 0x65, 0x70, 0x03, 0x21, 0x00];
+/// The DER prefix of a SubjectPublicKeyInfo carrying an ECDSA P-256 key (followed by the 65-byte uncompressed point).
+const SPKI_P256_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01, 0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07, 0x03,
+    0x42, 0x00,
+];
+
+/***** ERRORS *****/
+/// Defines errors that originate from reading public keys.
+#[derive(Debug)]
+pub enum SignatureError {
+    /// The key was not a PEM `PUBLIC KEY` block.
+    NotPem { name: String },
+    /// The key's PEM block was not valid Base64.
+    KeyDecode { name: String, err: base64ct::Error },
+    /// The key was neither an Ed25519 nor an ECDSA P-256 key.
+    UnsupportedKey { name: String },
+}
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use SignatureError::*;
+        match self {
+            NotPem { name } => write!(f, "Public key '{name}' is not a PEM-encoded 'PUBLIC KEY'"),
+            KeyDecode { name, .. } => write!(f, "Failed to decode public key '{name}' as Base64"),
+            UnsupportedKey { name } => write!(f, "Public key '{name}' is not an Ed25519 or ECDSA P-256 key"),
+        }
+    }
+}
+impl error::Error for SignatureError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use SignatureError::*;
+        match self {
+            NotPem { .. } | UnsupportedKey { .. } => None,
+            KeyDecode { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// A public key in a [`StaticKeySet`].
+struct PublicKey {
+    /// The name under which policies know the key.
+    name: String,
+    /// The algorithm the key signs with.
+    algorithm: &'static dyn VerificationAlgorithm,
+    /// The raw key, as expected by `algorithm`.
+    key: Vec<u8>,
+}
+impl Debug for PublicKey {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { f.debug_struct("PublicKey").field("name", &self.name).finish_non_exhaustive() }
+}
+
+/***** LIBRARY *****/
+/// Verifies the signatures claimed for an [`Image`].
+pub trait ImageVerifier: Debug + Send + Sync {
+    /// Verifies the signatures claimed for an image.
+    ///
+    /// # Arguments
+    /// - `image`: The [`Image`] of which to verify the [`Image::signatures`].
+    ///
+    /// # Returns
+    /// The names of the keys of which a valid signature was found. Empty if the image is not (validly) signed.
+    fn verify(&self, image: &Image) -> Vec<String>;
+}
+
+/// Verifies detached signatures over image digests against a static set of public keys.
+#[derive(Debug, Default)]
+pub struct StaticKeySet {
+    /// The keys to verify with.
+    keys: Vec<PublicKey>,
+}
+impl StaticKeySet {
+    /// Constructor for the StaticKeySet that reads its keys from PEM.
+    ///
+    /// # Arguments
+    /// - `keys`: Pairs of the name under which policies know a key and the PEM-encoded `PUBLIC KEY` (e.g., a `cosign.pub`).
+    ///
+    /// # Returns
+    /// A new StaticKeySet.
+    ///
+    /// # Errors
+    /// This function errors if any of the keys is not a PEM-encoded Ed25519 or ECDSA P-256 public key.
+    pub fn from_pem(keys: impl IntoIterator<Item = (String, String)>) -> Result<Self, SignatureError> {
+        let mut res: Vec<PublicKey> = vec![];
+        for (name, pem) in keys {
+            let pem: &str = pem.trim();
+            let Some(body) = pem.strip_prefix("-----BEGIN PUBLIC KEY-----").and_then(|pem| pem.strip_suffix("-----END PUBLIC KEY-----")) else {
+                return Err(SignatureError::NotPem { name });
+            };
+            let body: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+            let der: Vec<u8> = match Base64::decode_vec(&body) {
+                Ok(der) => der,
+                Err(err) => return Err(SignatureError::KeyDecode { name, err }),
+            };
+
+            let (algorithm, key): (&'static dyn VerificationAlgorithm, &[u8]) = if let Some(key) = der.strip_prefix(&SPKI_ED25519_PREFIX[..]) {
+                (&ED25519, key)
+            } else if let Some(key) = der.strip_prefix(&SPKI_P256_PREFIX[..]) {
+                (&ECDSA_P256_SHA256_ASN1, key)
+            } else {
+                return Err(SignatureError::UnsupportedKey { name });
+            };
+            res.push(PublicKey { name, algorithm, key: key.to_vec() });
+        }
+        Ok(Self { keys: res })
+    }
+
+    /// Returns whether this key set has no keys.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.keys.is_empty() }
+}
+impl ImageVerifier for StaticKeySet {
+    fn verify(&self, image: &Image) -> Vec<String> {
+        let signatures: Vec<Vec<u8>> = image
+            .signatures
+            .iter()
+            .filter_map(|signature| match Base64::decode_vec(signature.trim()) {
+                Ok(signature) => Some(signature),
+                Err(err) => {
+                    warn!("Ignoring signature of image '{}' that is not valid Base64: {err}", image.digest);
+                    None
+                },
+            })
+            .collect();
+
+        self.keys
+            .iter()
+            .filter(|key| {
+                let public: UnparsedPublicKey<&[u8]> = UnparsedPublicKey::new(key.algorithm, &key.key);
+                signatures.iter().any(|signature| public.verify(image.digest.as_bytes(), signature).is_ok())
+            })
+            .map(|key| key.name.clone())
+            .collect()
+    }
+}
+
+/// Verifies the signatures of the images of all tasks in a workflow.
+///
+/// Without a verifier, nothing is verified and no image is considered signed.
+///
+/// # Arguments
+/// - `verifier`: The [`ImageVerifier`] to verify with, if any.
+/// - `workflow`: The [`Workflow`] of which to set the [`Image::signed_by`] of every image.
+/// - `reference`: The verdict reference of the request, for logging.
+pub(crate) fn verify_images(verifier: Option<&dyn ImageVerifier>, workflow: &mut Workflow, reference: &str) {
+    workflow.for_each_image_mut(|task, image| {
+        image.signed_by = verifier.map(|verifier| verifier.verify(image)).unwrap_or_default();
+        debug!("Image '{}' of task '{task}' is signed by {:?} | request id: {reference}", image.digest, image.signed_by);
+    });
+}
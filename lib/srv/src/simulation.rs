@@ -0,0 +1,233 @@
+//! Replays recorded questions against a candidate policy to see how its verdicts differ.
+//!
+//! Before activating a new policy version, policy authors typically want to know its "blast radius": which requests
+//! that were allowed before would now be denied, and vice versa. This module extracts the questions (and the verdicts
+//! given to them) from an audit log, replays them against a candidate [`Policy`] using any [`ReasonerConnector`] and
+//! summarizes the differences in a [`SimulationReport`].
+//!
+//! Replaying never changes the active policy; it only consults the reasoner.
+
+use std::collections::HashMap;
+
+use audit_logger::{LogStatement, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::Verdict;
+use log::{debug, warn};
+use policy::Policy;
+pub use reasonerconn::QuestionKind;
+use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
+use serde::{Deserialize, Serialize};
+use state_resolver::State;
+use workflow::Workflow;
+
+/***** CONSTANTS *****/
+/// The prefix given to the verdict references of replayed questions, such that they are distinguishable in the audit log.
+pub const SIMULATION_REFERENCE_PREFIX: &'static str = "simulation-";
+
+/***** LIBRARY *****/
+/// A question as recorded in the audit log, together with the verdict it originally got.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedQuestion {
+    /// The verdict reference of the original request.
+    pub reference: String,
+    /// The policy version that originally answered the question.
+    pub policy:    i64,
+    /// What was asked.
+    #[serde(flatten)]
+    pub kind:      QuestionKind,
+    /// The state given to the policy.
+    pub state:     State,
+    /// The workflow in question.
+    pub workflow:  Workflow,
+    /// Whether the question was originally allowed, or [`None`] if the audit log holds no verdict for it.
+    pub original:  Option<bool>,
+}
+
+/// The outcome of replaying a single [`RecordedQuestion`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SimulationOutcome {
+    /// The verdict reference of the original request.
+    pub reference: String,
+    /// Whether the question was originally allowed (if known).
+    pub original:  Option<bool>,
+    /// Whether the candidate policy allows the question, or [`None`] if the reasoner failed.
+    pub simulated: Option<bool>,
+    /// The reasons for denial given by the reasoner under the candidate policy, if any.
+    pub reasons:   Vec<String>,
+    /// The error of the reasoner if it failed to answer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error:     Option<String>,
+}
+
+/// Summarizes how the verdicts of a candidate policy differ from the recorded ones.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SimulationReport {
+    /// The version of the candidate policy.
+    pub policy: Option<i64>,
+    /// The number of questions replayed.
+    pub total: usize,
+    /// The number of questions that got the same verdict as before.
+    pub unchanged: usize,
+    /// Questions that were denied before, but are allowed by the candidate policy.
+    pub newly_allowed: Vec<SimulationOutcome>,
+    /// Questions that were allowed before, but are denied by the candidate policy.
+    pub newly_denied: Vec<SimulationOutcome>,
+    /// Questions for which no original verdict was recorded, so no comparison could be made.
+    pub unknown: Vec<SimulationOutcome>,
+    /// Questions that the reasoner failed to answer under the candidate policy.
+    pub failed: Vec<SimulationOutcome>,
+}
+impl SimulationReport {
+    /// Adds the outcome of a replayed question to the report.
+    ///
+    /// # Arguments
+    /// - `outcome`: The [`SimulationOutcome`] to categorize.
+    pub fn push(&mut self, outcome: SimulationOutcome) {
+        self.total += 1;
+        match (outcome.original, outcome.simulated) {
+            (_, None) => self.failed.push(outcome),
+            (None, Some(_)) => self.unknown.push(outcome),
+            (Some(false), Some(true)) => self.newly_allowed.push(outcome),
+            (Some(true), Some(false)) => self.newly_denied.push(outcome),
+            (Some(_), Some(_)) => self.unchanged += 1,
+        }
+    }
+}
+
+/// Extracts the questions from a list of audit log statements.
+///
+/// # Arguments
+/// - `statements`: The [`LogStatement`]s read from the audit log, in order.
+/// - `from`: If given, skips all questions before the one with this reference.
+/// - `to`: If given, skips all questions after the one with this reference.
+///
+/// # Returns
+/// The [`RecordedQuestion`]s in the given range, in the order they were asked.
+pub fn collect_questions(statements: &[LogStatement], from: Option<&str>, to: Option<&str>) -> Vec<RecordedQuestion> {
+    // Collect the verdicts first, since they appear after the questions
+    let verdicts: HashMap<&str, bool> = statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            LogStatement::ReasonerVerdict { reference, verdict } => Some((&**reference, matches!(**verdict, Verdict::Allow(_)))),
+            _ => None,
+        })
+        .collect();
+
+    let mut questions: Vec<RecordedQuestion> = Vec::new();
+    let mut in_range: bool = from.is_none();
+    for stmt in statements {
+        let (reference, policy, kind, state, workflow) = match stmt {
+            LogStatement::ExecuteTask { reference, policy, state, workflow, task, .. } => {
+                (reference, *policy, QuestionKind::ExecuteTask { task: task.to_string() }, state, workflow)
+            },
+            LogStatement::AssetAccess { reference, policy, state, workflow, data, task, .. } => (
+                reference,
+                *policy,
+                QuestionKind::AccessData { data: data.to_string(), task: task.as_ref().map(|task| task.to_string()) },
+                state,
+                workflow,
+            ),
+            LogStatement::WorkflowValidate { reference, policy, state, workflow, .. } => {
+                (reference, *policy, QuestionKind::ValidateWorkflow, state, workflow)
+            },
+            _ => continue,
+        };
+
+        // Check the range
+        if !in_range && from == Some(&**reference) {
+            in_range = true;
+        }
+        if in_range {
+            questions.push(RecordedQuestion {
+                reference: reference.to_string(),
+                policy,
+                kind,
+                state: State::clone(state),
+                workflow: Workflow::clone(workflow),
+                original: verdicts.get(&**reference).copied(),
+            });
+        }
+        if to == Some(&**reference) {
+            break;
+        }
+    }
+    questions
+}
+
+/// Puts a question to a reasoner connector.
+///
+/// # Arguments
+/// - `connector`: The [`ReasonerConnector`] to consult.
+/// - `session`: The [`SessionedConnectorAuditLogger`] given to the connector to log raw reasoner responses with.
+/// - `policy`: The [`Policy`] to evaluate the question with.
+/// - `kind`: What is asked.
+/// - `state`: The [`State`] to give to the policy.
+/// - `workflow`: The [`Workflow`] in question.
+///
+/// # Returns
+/// The [`ReasonerResponse`] of the connector.
+///
+/// # Errors
+/// This function errors if the connector failed to answer.
+pub(crate) async fn ask<L, C>(
+    connector: &C,
+    session: SessionedConnectorAuditLogger<L>,
+    policy: Policy,
+    kind: QuestionKind,
+    state: State,
+    workflow: Workflow,
+) -> Result<ReasonerResponse, ReasonerConnError>
+where
+    L: ReasonerConnectorAuditLogger + Clone,
+    C: ReasonerConnector<L>,
+{
+    match kind {
+        QuestionKind::ExecuteTask { task } => connector.execute_task(session, policy, state, workflow, task).await,
+        QuestionKind::AccessData { data, task } => connector.access_data_request(session, policy, state, workflow, data, task).await,
+        QuestionKind::ValidateWorkflow => connector.workflow_validation_request(session, policy, state, workflow).await,
+    }
+}
+
+/// Replays recorded questions against a candidate policy.
+///
+/// # Arguments
+/// - `connector`: The [`ReasonerConnector`] to consult.
+/// - `logger`: The logger given to the connector to log raw reasoner responses with. References are prefixed with [`SIMULATION_REFERENCE_PREFIX`].
+/// - `policy`: The candidate [`Policy`] to evaluate the questions with.
+/// - `questions`: The [`RecordedQuestion`]s to replay.
+///
+/// # Returns
+/// A [`SimulationReport`] that summarizes the differences w.r.t. the original verdicts.
+pub async fn simulate<L, C>(connector: &C, logger: &L, policy: &Policy, questions: Vec<RecordedQuestion>) -> SimulationReport
+where
+    L: ReasonerConnectorAuditLogger + Clone,
+    C: ReasonerConnector<L>,
+{
+    let mut report: SimulationReport = SimulationReport { policy: policy.version.version, ..Default::default() };
+    for question in questions {
+        debug!("Replaying question '{}' against policy {:?}", question.reference, policy.version.version);
+        let session = SessionedConnectorAuditLogger::new(format!("{SIMULATION_REFERENCE_PREFIX}{}", question.reference), logger.clone());
+        let res: Result<ReasonerResponse, ReasonerConnError> =
+            ask(connector, session.clone(), policy.clone(), question.kind, question.state, question.workflow).await;
+        if let Err(err) = session.end().await {
+            warn!("Failed to log the session summary of simulated question '{}': {err}", question.reference);
+        }
+
+        report.push(match res {
+            Ok(res) => SimulationOutcome {
+                reference: question.reference,
+                original:  question.original,
+                simulated: Some(res.success),
+                reasons:   res.errors,
+                error:     None,
+            },
+            Err(err) => SimulationOutcome {
+                reference: question.reference,
+                original:  question.original,
+                simulated: None,
+                reasons:   Vec::new(),
+                error:     Some(err.to_string()),
+            },
+        });
+    }
+    report
+}
@@ -0,0 +1,127 @@
+//! Implements tools that help clients debug their requests before asking for a verdict.
+//!
+//! The reasoner does not reason about the WIR directly, but about a simplified workflow compiled from it (see the
+//! `workflow` crate). When that compilation fails or produces something unexpected, the verdict alone doesn't tell a
+//! client much. The tools API exposes the intermediate steps instead.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use brane_ast::ast;
+use brane_ast::func_id::FunctionId;
+use brane_ast::spec::BuiltinFunctions;
+use brane_exe::pc::ProgramCounter;
+use error_trace::ErrorTrace as _;
+use log::info;
+use policy::PolicyDataAccess;
+use reasonerconn::ReasonerConnector;
+use serde::Serialize;
+use state_resolver::StateResolver;
+use warp::Filter;
+use workflow::{Workflow, preprocess};
+
+use crate::deliberation::with_wir_body;
+use crate::{Srv, models};
+
+/***** HELPERS *****/
+/// Collects a warning for every call in a simplified WIR that is still there, i.e., that could not be inlined.
+///
+/// # Arguments
+/// - `wir`: The simplified [`ast::Workflow`] to inspect.
+/// - `calls`: The functions called by the calls in `wir`, as found by [`preprocess::simplify()`].
+/// - `warnings`: The list to push the warnings to.
+fn remaining_calls(wir: &ast::Workflow, calls: &HashMap<ProgramCounter, usize>, warnings: &mut Vec<String>) {
+    let builtins: [&str; 4] =
+        [BuiltinFunctions::CommitResult.name(), BuiltinFunctions::Print.name(), BuiltinFunctions::PrintLn.name(), BuiltinFunctions::Len.name()];
+
+    let bodies = std::iter::once((FunctionId::Main, &*wir.graph)).chain(wir.funcs.iter().map(|(id, body)| (FunctionId::Func(*id), body)));
+    for (func_id, body) in bodies {
+        for (i, edge) in body.iter().enumerate() {
+            if !matches!(edge, ast::Edge::Call { .. }) {
+                continue;
+            }
+            let pc = ProgramCounter::new(func_id, i);
+            match calls.get(&pc).and_then(|id| wir.table.funcs.get(*id)) {
+                Some(def) if builtins.contains(&def.name.as_str()) => {},
+                Some(def) => warnings.push(format!(
+                    "Call to function '{}' at {} could not be inlined (it is probably recursive); the reasoner does not support such calls",
+                    def.name,
+                    pc.resolved(&wir.table)
+                )),
+                None => warnings.push(format!("Could not determine which function is called at {}", pc.resolved(&wir.table))),
+            }
+        }
+    }
+}
+
+/***** LIBRARY *****/
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // Preprocess a workflow the way the deliberation API would, without asking for a verdict
+    // POST /v1/tools/preprocess-workflow
+    // out:
+    //  200 PreprocessResultModel (also if the workflow could not be compiled)
+    //  400 problem+json (if the body is not a (supported) workflow)
+
+    async fn handle_preprocess_workflow(
+        _auth_ctx: AuthContext,
+        this: Arc<Self>,
+        body: models::PreprocessPostModel,
+    ) -> Result<warp::reply::Json, warp::reject::Rejection> {
+        info!("Handling preprocess-workflow request");
+        let mut res = models::PreprocessResultModel {
+            simplified: None,
+            stats:      None,
+            workflow:   None,
+            visualized: None,
+            warnings:   vec![],
+            error:      None,
+        };
+
+        // Show the simplified WIR first, as compiling might fail on it
+        match preprocess::simplify_with_limits(body.workflow.clone(), &this.workflow_limits) {
+            Ok((wir, calls, _, stats)) => {
+                remaining_calls(&wir, &calls, &mut res.warnings);
+                res.simplified = Some(wir);
+                res.stats = Some(stats);
+            },
+            Err(err) => {
+                res.error = Some(err.trace().to_string());
+                return Ok(warp::reply::json(&res));
+            },
+        }
+
+        // Then compile it to what the reasoner sees
+        match Workflow::from_wir(body.workflow, &this.workflow_limits) {
+            Ok(workflow) => {
+                res.warnings.extend(workflow.diagnostics().iter().map(ToString::to_string));
+                res.visualized = Some(workflow.visualize().to_string());
+                res.workflow = Some(workflow);
+            },
+            Err(err) => res.error = Some(err.trace().to_string()),
+        }
+        Ok(warp::reply::json(&res))
+    }
+
+    pub fn tools_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let preprocess_workflow = warp::post()
+            .and(warp::path!("preprocess-workflow"))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(with_wir_body(this.workflows.clone()))
+            .and_then(Self::handle_preprocess_workflow);
+
+        warp::path("v1").and(warp::path("tools")).and(preprocess_workflow)
+    }
+}
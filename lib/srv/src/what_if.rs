@@ -0,0 +1,313 @@
+//! Implements what-if analysis, which re-evaluates a question asked before with some of its facts changed.
+//!
+//! A denial tells users _that_ they may not do something, but not always which condition they should change to be
+//! allowed. By changing a few facts of the question (e.g., planning a task at another location, or adding a tag that
+//! records consent) and asking again, users can find out exactly which condition caused the denial. The server
+//! remembers the most recent questions for this purpose, such that a what-if only needs to name a verdict reference.
+//!
+//! What-ifs are answered by the same policy version as the original question. They never grant anything, so only the
+//! reasoner's own answer is given (i.e., verdict transformers are not applied) and nothing is counted or recorded as
+//! duty.
+
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+use std::sync::{Arc, Mutex};
+
+use audit_logger::{AuditLogger, SessionedConnectorAuditLogger};
+use auth_resolver::{AuthContext, AuthResolver};
+use deliberation::spec::{EvaluationContext, FactOverride, Verdict, WhatIfRequest, WhatIfResponse};
+use log::{debug, error, info};
+use policy::{Policy, PolicyDataAccess, PolicyDataError};
+use problem_details::ProblemDetails;
+use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
+use serde::Serialize;
+use state_resolver::{State, StateResolver};
+use warp::Filter;
+use warp::hyper::StatusCode;
+use warp::reject::Rejection;
+use workflow::Workflow;
+use workflow::spec::{Dataset, Elem, ElemBranch, ElemParallel, ElemTask, Metadata, User};
+
+use crate::Srv;
+use crate::deliberation::{end_session, state_snapshot_id, verdict_of};
+use crate::postprocess::Decision;
+use crate::problem::Problem;
+use crate::queue::QueuePermit;
+use crate::retrospective::reasoner_unavailable;
+use crate::simulation::{self, RecordedQuestion};
+
+/***** CONSTANTS *****/
+/// The prefix given to the verdict references of re-evaluated questions, such that they are distinguishable in the audit log.
+pub const WHAT_IF_REFERENCE_PREFIX: &'static str = "what-if-";
+
+/// The maximum number of questions kept in the [`RecentQuestions`].
+const RECENT_QUESTIONS_CAPACITY: usize = 1024;
+
+/***** ERRORS *****/
+/// Defines errors that occur when applying [`FactOverride`]s.
+#[derive(Debug)]
+pub enum OverrideError {
+    /// An override names a task that is not in the workflow.
+    UnknownTask { task: String },
+}
+impl Display for OverrideError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use OverrideError::*;
+        match self {
+            UnknownTask { task } => write!(f, "Workflow has no task '{task}'"),
+        }
+    }
+}
+impl Error for OverrideError {}
+
+/***** HELPERS *****/
+/// Finds a task in an element.
+///
+/// # Arguments
+/// - `elem`: The [`Elem`] to search.
+/// - `id`: The (full) identifier of the task.
+///
+/// # Returns
+/// The [`ElemTask`] with the given identifier, or [`None`] if there is no such task.
+fn find_task<'e>(elem: &'e mut Elem, id: &str) -> Option<&'e mut ElemTask> {
+    match elem {
+        Elem::Task(task) => {
+            if task.id == id {
+                Some(task)
+            } else {
+                find_task(&mut task.next, id)
+            }
+        },
+        Elem::Commit(commit) => find_task(&mut commit.next, id),
+
+        Elem::Branch(ElemBranch { branches, next }) | Elem::Parallel(ElemParallel { branches, next, .. }) => {
+            match branches.iter_mut().find_map(|branch| find_task(branch, id)) {
+                Some(task) => Some(task),
+                None => find_task(next, id),
+            }
+        },
+        Elem::Loop(l) => match find_task(&mut l.body, id) {
+            Some(task) => Some(task),
+            None => find_task(&mut l.next, id),
+        },
+
+        Elem::Next | Elem::Stop(_) => None,
+    }
+}
+
+/// Finds the metadata of a task in a workflow, or that of the workflow itself.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to search.
+/// - `task`: The identifier of the task, with or without the workflow's identifier in front, or [`None`] for the workflow itself.
+///
+/// # Errors
+/// This function errors if the workflow has no such task.
+fn metadata_of<'w>(workflow: &'w mut Workflow, task: Option<&str>) -> Result<&'w mut Vec<Metadata>, OverrideError> {
+    match task {
+        Some(task) => Ok(&mut task_of(workflow, task)?.metadata),
+        None => Ok(&mut workflow.metadata),
+    }
+}
+
+/// Finds a task in a workflow.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to search.
+/// - `task`: The identifier of the task, with or without the workflow's identifier in front.
+///
+/// # Errors
+/// This function errors if the workflow has no such task.
+fn task_of<'w>(workflow: &'w mut Workflow, task: &str) -> Result<&'w mut ElemTask, OverrideError> {
+    let id: String = if task.starts_with(&format!("{}-", workflow.id)) { task.into() } else { format!("{}-{task}", workflow.id) };
+    find_task(&mut workflow.start, &id).ok_or_else(|| OverrideError::UnknownTask { task: task.into() })
+}
+
+/// Adds a user to a list of them if it isn't in there yet.
+fn add_user(users: &mut Vec<User>, name: &str) {
+    if !users.iter().any(|user| user.name == name) {
+        users.push(User { name: name.into() });
+    }
+}
+
+/***** LIBRARY *****/
+/// Changes the facts of a question.
+///
+/// # Arguments
+/// - `overrides`: The [`FactOverride`]s to apply, in order.
+/// - `state`: The [`State`] of the question.
+/// - `workflow`: The [`Workflow`] of the question.
+///
+/// # Errors
+/// This function errors if an override refers to something that is not in the question (e.g., an unknown task).
+pub fn apply_overrides(overrides: &[FactOverride], state: &mut State, workflow: &mut Workflow) -> Result<(), OverrideError> {
+    for fact in overrides {
+        match fact {
+            FactOverride::TaskLocation { task, location } => task_of(workflow, task)?.location = Some(location.clone()),
+            FactOverride::AddTag { task, owner, tag } => {
+                let metadata: &mut Vec<Metadata> = metadata_of(workflow, task.as_deref())?;
+                if !metadata.iter().any(|md| md.owner == *owner && md.tag == *tag) {
+                    metadata.push(Metadata { owner: owner.clone(), tag: tag.clone(), signature: None });
+                }
+            },
+            FactOverride::RemoveTag { task, owner, tag } => {
+                metadata_of(workflow, task.as_deref())?.retain(|md| md.owner != *owner || md.tag != *tag);
+            },
+            FactOverride::AddUser { name } => add_user(&mut state.users, name),
+            FactOverride::RemoveUser { name } => state.users.retain(|user| user.name != *name),
+            FactOverride::AddLocation { name } => add_user(&mut state.locations, name),
+            FactOverride::RemoveLocation { name } => state.locations.retain(|location| location.name != *name),
+            FactOverride::AddDataset { name } => {
+                if !state.datasets.iter().any(|dataset| dataset.name == *name) {
+                    state.datasets.push(Dataset::new(name.clone(), None));
+                }
+            },
+            FactOverride::RemoveDataset { name } => state.datasets.retain(|dataset| dataset.name != *name),
+            FactOverride::FulfilDuty { duty } => state.duties.retain(|open| open.duty != *duty),
+        }
+    }
+    Ok(())
+}
+
+/// A question answered recently, remembered such that it can be re-evaluated with changed facts.
+#[derive(Clone, Debug)]
+pub struct RecentQuestion {
+    /// The user that asked the question.
+    pub initiator: String,
+    /// The question itself.
+    pub question:  RecordedQuestion,
+    /// The verdict given to the question.
+    pub verdict:   Verdict,
+}
+
+/// Remembers the most recently answered questions by their verdict reference.
+///
+/// Only the last [`RECENT_QUESTIONS_CAPACITY`] questions are remembered; older ones are evicted first.
+#[derive(Debug, Default)]
+pub struct RecentQuestions {
+    /// The remembered questions by reference, and the order in which they were inserted.
+    entries: Mutex<(HashMap<String, RecentQuestion>, VecDeque<String>)>,
+}
+impl RecentQuestions {
+    /// Remembers an answered question.
+    ///
+    /// # Arguments
+    /// - `initiator`: The user that asked the question.
+    /// - `question`: The [`RecordedQuestion`] asked. Its original verdict is taken from `verdict`.
+    /// - `verdict`: The [`Verdict`] given to the question.
+    pub fn remember(&self, initiator: &str, mut question: RecordedQuestion, verdict: &Verdict) {
+        question.original = Some(matches!(verdict, Verdict::Allow(_)));
+        let reference: String = question.reference.clone();
+
+        let mut entries = self.entries.lock().unwrap();
+        let (questions, order) = &mut *entries;
+        if questions.insert(reference.clone(), RecentQuestion { initiator: initiator.into(), question, verdict: verdict.clone() }).is_none() {
+            order.push_back(reference);
+        }
+        while order.len() > RECENT_QUESTIONS_CAPACITY {
+            if let Some(old) = order.pop_front() {
+                questions.remove(&old);
+            }
+        }
+    }
+
+    /// Retrieves a recently answered question.
+    ///
+    /// # Arguments
+    /// - `reference`: The verdict reference of the question.
+    ///
+    /// # Returns
+    /// The [`RecentQuestion`], or [`None`] if it was never asked or has been forgotten since.
+    pub fn get(&self, reference: &str) -> Option<RecentQuestion> { self.entries.lock().unwrap().0.get(reference).cloned() }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // Re-evaluate a recent question with some of its facts changed
+    // POST /v1/deliberation/what-if
+    // out:
+    //  200 WhatIfResponse
+    //  400 problem+json (override does not apply to the question)
+    //  404 problem+json (question unknown, or its policy version is gone)
+    //  503 problem+json (reasoner or audit log unavailable)
+
+    async fn handle_what_if(auth_ctx: AuthContext, this: Arc<Self>, body: WhatIfRequest) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling what-if request for question '{}'", body.reference);
+        let WhatIfRequest { reference, overrides, priority } = body;
+
+        // Only the user that asked a question may see it again
+        let RecentQuestion { question, verdict: original, .. } = match this.recent_questions.get(&reference) {
+            Some(recent) if recent.initiator == auth_ctx.initiator => recent,
+            _ => {
+                let p = ProblemDetails::new()
+                    .with_status(StatusCode::NOT_FOUND)
+                    .with_detail(format!("No recent question with reference '{reference}' asked by you"));
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        let RecordedQuestion { policy: version, kind, mut state, mut workflow, .. } = question;
+        if let Err(err) = apply_overrides(&overrides, &mut state, &mut workflow) {
+            let p = ProblemDetails::new().with_status(StatusCode::BAD_REQUEST).with_detail(err.to_string());
+            return Err(warp::reject::custom(Problem::from(p)));
+        }
+
+        debug!("Retrieving policy {version}...");
+        let policy: Policy = match this.policystore.get_version(version).await {
+            Ok(policy) => policy,
+            Err(PolicyDataError::NotFound) => {
+                let p = ProblemDetails::new()
+                    .with_status(StatusCode::NOT_FOUND)
+                    .with_detail(format!("Policy version {version} that answered question '{reference}' no longer exists"));
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+            Err(PolicyDataError::GeneralError(err)) => {
+                error!("Failed to get policy {version}: {err} | request id: {reference}");
+                let p = ProblemDetails::new().with_status(StatusCode::INTERNAL_SERVER_ERROR).with_detail(err);
+                return Err(warp::reject::custom(Problem::from(p)));
+            },
+        };
+        let what_if_reference: String = format!("{WHAT_IF_REFERENCE_PREFIX}{reference}");
+        let evaluated_with = EvaluationContext {
+            policy_version:    policy.version.version,
+            connector_context: C::hash(),
+            state_snapshot:    Some(state_snapshot_id(&state)),
+        };
+
+        // Wait for our turn at the reasoner, like any other question
+        let _permit: QueuePermit =
+            this.question_queue.enter(priority).await.map_err(|err| reasoner_unavailable(&what_if_reference, ReasonerConnError::from(err)))?;
+
+        debug!("Consulting reasoner connector...");
+        let session = SessionedConnectorAuditLogger::new(what_if_reference.clone(), this.logger.clone());
+        let res: Result<ReasonerResponse, ReasonerConnError> =
+            simulation::ask(&this.reasonerconn, session.clone(), policy, kind, state, workflow).await;
+        end_session(session).await?;
+        let decision: Decision = match res {
+            Ok(res) => Decision::from(res),
+            Err(err) => return Err(reasoner_unavailable(&what_if_reference, err)),
+        };
+
+        let what_if: Verdict = verdict_of(&what_if_reference, &[], &evaluated_with, decision);
+        Ok(warp::reply::json(&WhatIfResponse { original, what_if, overrides }))
+    }
+
+    pub fn what_if_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let what_if = warp::post()
+            .and(warp::path!("what-if"))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_what_if);
+
+        warp::path("v1").and(warp::path("deliberation")).and(what_if)
+    }
+}
@@ -0,0 +1,224 @@
+//! Implements uploading workflows once, and referring to them in deliberation requests afterwards.
+//!
+//! Planners typically ask about many tasks of the same workflow, each time sending its full WIR. Instead, they can
+//! upload it once with `PUT /v1/workflows/{id}`, and then give a `workflow_ref` with its id and content hash in place of
+//! the `workflow` of any deliberation request (see [`WorkflowReference`]). The reasoner substitutes the uploaded
+//! workflow before reading the request, so the questions are answered exactly as if the workflow was given inline.
+//!
+//! Workflows are kept by the SHA-256 of their (upgraded) WIR, such that a reference always denotes the same workflow,
+//! and an uploaded workflow can be retrieved for auditing with `GET /v1/workflows/{id}/{hash}`. Only the last
+//! [`WORKFLOW_STORE_CAPACITY`] workflows are kept.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use audit_logger::AuditLogger;
+use auth_resolver::{AuthContext, AuthResolver};
+use deliberation::spec::WorkflowReference;
+use error_trace::ErrorTrace as _;
+use log::{debug, info};
+use policy::PolicyDataAccess;
+use problem_details::ProblemDetails;
+use reasonerconn::ReasonerConnector;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use state_resolver::StateResolver;
+use warp::Filter;
+use warp::hyper::StatusCode;
+use warp::reject::Rejection;
+use workflow::ingest::{self, WirVersion};
+
+use crate::Srv;
+use crate::problem::Problem;
+
+/***** CONSTANTS *****/
+/// The maximum number of workflows kept in the [`WorkflowStore`].
+const WORKFLOW_STORE_CAPACITY: usize = 1024;
+
+/// The problem type given when a deliberation request refers to a workflow that is not (or no longer) uploaded.
+pub const UNKNOWN_WORKFLOW_PROBLEM: &'static str = "/problems/unknown-workflow";
+
+/***** HELPERS *****/
+/// Builds the rejection for a request with an unusable workflow.
+///
+/// # Arguments
+/// - `status`: The [`StatusCode`] to reject with.
+/// - `detail`: What went wrong.
+///
+/// # Returns
+/// A [`Rejection`] carrying a problem with the given status.
+fn workflow_problem(status: StatusCode, detail: impl Into<String>) -> Rejection {
+    warp::reject::custom(Problem::from(ProblemDetails::new().with_status(status).with_detail(detail.into())))
+}
+
+/// Computes the content hash of a WIR workflow.
+///
+/// # Arguments
+/// - `wir`: The workflow, as JSON. Object keys are always serialized in order, so equal workflows have equal hashes.
+///
+/// # Returns
+/// The SHA-256 of the workflow, as hex.
+fn wir_hash(wir: &Value) -> String {
+    let raw: Vec<u8> = serde_json::to_vec(wir).unwrap_or_default();
+    Sha256::digest(&raw).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Upgrades a WIR workflow to the version we understand.
+///
+/// # Arguments
+/// - `wir`: The workflow to upgrade in-place.
+///
+/// # Errors
+/// This function errors (= rejects the request) with a `400 Bad Request` problem if the workflow cannot be upgraded.
+pub(crate) fn upgrade_wir(wir: &mut Value) -> Result<(), Rejection> {
+    match ingest::upgrade(wir) {
+        Ok(WirVersion::Current) => Ok(()),
+        Ok(version) => {
+            debug!("Upgraded {version} WIR workflow");
+            Ok(())
+        },
+        Err(err) => {
+            let p =
+                ProblemDetails::new().with_title("Unsupported workflow").with_status(StatusCode::BAD_REQUEST).with_detail(err.trace().to_string());
+            Err(warp::reject::custom(Problem::from(p)))
+        },
+    }
+}
+
+/***** LIBRARY *****/
+/// Keeps the workflows uploaded by clients, by their content hash.
+///
+/// Only the last [`WORKFLOW_STORE_CAPACITY`] workflows are remembered; older ones are evicted first.
+#[derive(Debug, Default)]
+pub struct WorkflowStore {
+    /// The (upgraded) WIR of the workflows by hash, and the order in which they were uploaded.
+    entries: Mutex<(HashMap<String, Arc<Value>>, VecDeque<String>)>,
+}
+impl WorkflowStore {
+    /// Stores a workflow.
+    ///
+    /// # Arguments
+    /// - `wir`: The (upgraded) WIR of the workflow to store.
+    ///
+    /// # Returns
+    /// The content hash by which the workflow can be referred to.
+    pub fn insert(&self, wir: Value) -> String {
+        let hash: String = wir_hash(&wir);
+        let mut entries = self.entries.lock().unwrap();
+        let (workflows, order) = &mut *entries;
+        if workflows.insert(hash.clone(), Arc::new(wir)).is_none() {
+            order.push_back(hash.clone());
+        }
+        while order.len() > WORKFLOW_STORE_CAPACITY {
+            if let Some(old) = order.pop_front() {
+                workflows.remove(&old);
+            }
+        }
+        hash
+    }
+
+    /// Retrieves a workflow.
+    ///
+    /// # Arguments
+    /// - `reference`: The [`WorkflowReference`] to the workflow.
+    ///
+    /// # Returns
+    /// The (upgraded) WIR of the workflow, or [`None`] if there is no such workflow or it has another id.
+    pub fn get(&self, reference: &WorkflowReference) -> Option<Arc<Value>> {
+        let entries = self.entries.lock().unwrap();
+        entries.0.get(&reference.hash).filter(|wir| wir.get("id").and_then(Value::as_str) == Some(reference.id.as_str())).cloned()
+    }
+
+    /// Replaces a reference to a workflow in the body of a deliberation request by the workflow itself.
+    ///
+    /// # Arguments
+    /// - `body`: The body of the request. Left as-is if it has no `workflow_ref`.
+    ///
+    /// # Errors
+    /// This function errors (= rejects the request) with a `400 Bad Request` problem if the body has both a `workflow`
+    /// and a `workflow_ref` or if the reference is malformed, or with a `404 Not Found` problem of type
+    /// [`UNKNOWN_WORKFLOW_PROBLEM`] if no such workflow was uploaded.
+    pub(crate) fn resolve(&self, body: &mut Value) -> Result<(), Rejection> {
+        let Some(raw) = body.as_object_mut().and_then(|body| body.remove("workflow_ref")) else { return Ok(()) };
+        if body.get("workflow").is_some() {
+            return Err(workflow_problem(StatusCode::BAD_REQUEST, "Invalid request body: give either a workflow or a workflow_ref, not both"));
+        }
+        let reference: WorkflowReference = serde_json::from_value(raw)
+            .map_err(|err| workflow_problem(StatusCode::BAD_REQUEST, format!("Invalid request body: illegal workflow_ref: {err}")))?;
+        let Some(wir) = self.get(&reference) else {
+            let p = ProblemDetails::new()
+                .with_type(warp::http::Uri::from_static(UNKNOWN_WORKFLOW_PROBLEM))
+                .with_title("Unknown workflow")
+                .with_status(StatusCode::NOT_FOUND)
+                .with_detail(format!("No workflow '{}' with hash '{}' was uploaded (it may have expired)", reference.id, reference.hash));
+            return Err(warp::reject::custom(Problem::from(p)));
+        };
+        debug!("Using uploaded workflow '{}' ({})", reference.id, reference.hash);
+        body["workflow"] = (*wir).clone();
+        Ok(())
+    }
+}
+
+impl<L, C, P, S, PA, DA> Srv<L, C, P, S, PA, DA>
+where
+    L: 'static + AuditLogger + Send + Sync + Clone,
+    C: 'static + ReasonerConnector<L> + Send + Sync,
+    P: 'static + PolicyDataAccess + Send + Sync,
+    S: 'static + StateResolver + Send + Sync,
+    PA: 'static + AuthResolver + Send + Sync,
+    DA: 'static + AuthResolver + Send + Sync,
+    C::Context: Send + Sync + Debug + Serialize,
+{
+    // Upload a workflow to refer to in deliberation requests
+    // PUT /v1/workflows/{id}
+    // in: WIR workflow
+    // out:
+    //  200 WorkflowReference
+    //  400 problem+json (unsupported workflow, or its id is not the one in the path)
+
+    async fn handle_put_workflow(id: String, auth_ctx: AuthContext, this: Arc<Self>, mut wir: Value) -> Result<warp::reply::Json, Rejection> {
+        info!("Handling upload of workflow '{id}' by '{}'", auth_ctx.initiator);
+        upgrade_wir(&mut wir)?;
+        let workflow: brane_ast::Workflow =
+            serde_json::from_value(wir.clone()).map_err(|err| workflow_problem(StatusCode::BAD_REQUEST, format!("Invalid workflow: {err}")))?;
+        if workflow.id != id {
+            return Err(workflow_problem(StatusCode::BAD_REQUEST, format!("Workflow has id '{}', but was uploaded as '{id}'", workflow.id)));
+        }
+
+        let hash: String = this.workflows.insert(wir);
+        debug!("Stored workflow '{id}' as '{hash}'");
+        Ok(warp::reply::json(&WorkflowReference { id, hash }))
+    }
+
+    // Retrieve an uploaded workflow
+    // GET /v1/workflows/{id}/{hash}
+    // out:
+    //  200 WIR workflow
+    //  404 problem+json
+
+    async fn handle_get_workflow(id: String, hash: String, _auth_ctx: AuthContext, this: Arc<Self>) -> Result<warp::reply::Json, Rejection> {
+        match this.workflows.get(&WorkflowReference { id: id.clone(), hash: hash.clone() }) {
+            Some(wir) => Ok(warp::reply::json(&*wir)),
+            None => Err(workflow_problem(StatusCode::NOT_FOUND, format!("No workflow '{id}' with hash '{hash}' was uploaded (it may have expired)"))),
+        }
+    }
+
+    pub fn workflows_handlers(this: Arc<Self>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let put_workflow = warp::put()
+            .and(warp::path!(String))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and(warp::body::json())
+            .and_then(Self::handle_put_workflow);
+
+        let get_workflow = warp::get()
+            .and(warp::path!(String / String))
+            .and(Self::with_deliberation_api_auth(this.clone()))
+            .and(Self::with_self(this.clone()))
+            .and_then(Self::handle_get_workflow);
+
+        warp::path("v1").and(warp::path("workflows")).and(put_workflow.or(get_workflow))
+    }
+}
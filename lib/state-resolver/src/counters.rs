@@ -0,0 +1,81 @@
+//! Defines counters of cumulative usage, for stateful policies such as "at most 5 analyses of dataset D per month".
+//!
+//! Counters are kept per subject (a user or a dataset), per kind of usage and per [`period()`], by an implementation of
+//! [`UsageCounters`]. The counters of the current period are exposed in the [`State`](crate::State).
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/***** CONSTANTS *****/
+/// The counter of tasks that were allowed to execute (counted for the user asking and for every input dataset).
+pub const TASK_EXECUTIONS: &'static str = "task-executions";
+/// The counter of datasets that were allowed to be accessed (counted for the user asking and for the dataset).
+pub const DATA_ACCESSES: &'static str = "data-accesses";
+
+/***** ERRORS *****/
+/// Defines errors that originate from reading or updating counters.
+#[derive(Debug)]
+pub struct UsageCountersError(pub String);
+impl Display for UsageCountersError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { write!(f, "Failed to access usage counters: {}", self.0) }
+}
+impl Error for UsageCountersError {}
+
+/***** AUXILLARY *****/
+/// The cumulative usage of something by a particular subject in a particular period.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Counter {
+    /// The user or dataset that the usage is about.
+    pub subject: String,
+    /// The kind of usage (e.g., [`TASK_EXECUTIONS`]).
+    pub counter: String,
+    /// The period in which the usage took place (see [`period()`]).
+    pub period:  String,
+    /// The usage so far.
+    pub value:   u64,
+}
+
+/***** LIBRARY *****/
+/// Returns the period in which a moment falls.
+///
+/// Counters are kept per calendar month.
+///
+/// # Arguments
+/// - `at`: The moment to find the period of.
+///
+/// # Returns
+/// The period as `YYYY-MM`.
+#[inline]
+pub fn period(at: DateTime<Local>) -> String { at.format("%Y-%m").to_string() }
+
+/// Keeps track of cumulative usage.
+#[async_trait::async_trait]
+pub trait UsageCounters: Send + Sync {
+    /// Adds to a counter, creating it if it doesn't exist yet.
+    ///
+    /// # Arguments
+    /// - `subject`: The user or dataset that the usage is about.
+    /// - `counter`: The kind of usage.
+    /// - `period`: The period in which the usage took place (see [`period()`]).
+    /// - `by`: The amount of usage to add.
+    ///
+    /// # Errors
+    /// This function errors if the counter could not be updated.
+    async fn increment(&self, subject: &str, counter: &str, period: &str, by: u64) -> Result<(), UsageCountersError>;
+
+    /// Retrieves all counters of a period.
+    ///
+    /// # Arguments
+    /// - `period`: The period to retrieve the counters of (see [`period()`]).
+    ///
+    /// # Returns
+    /// The [`Counter`]s of that period. Usage that was never counted is not returned.
+    ///
+    /// # Errors
+    /// This function errors if the counters could not be read.
+    async fn get_counters(&self, period: &str) -> Result<Vec<Counter>, UsageCountersError>;
+}
@@ -0,0 +1,89 @@
+//! Defines duties that users take on when the reasoner permits them something, such that subsequent requests can be denied
+//! while they are outstanding.
+//!
+//! Every obligation attached to an allowed verdict (e.g., "notify data owner") becomes a [`Duty`] of the user that asked,
+//! kept by an implementation of [`DutyStore`] until it is reported as fulfilled. The open duties of the user asking are
+//! exposed in the [`State`](crate::State).
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/***** ERRORS *****/
+/// Defines errors that originate from reading or updating duties.
+#[derive(Debug)]
+pub struct DutiesError(pub String);
+impl Display for DutiesError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { write!(f, "Failed to access duties: {}", self.0) }
+}
+impl Error for DutiesError {}
+
+/***** AUXILLARY *****/
+/// Something a user has to do because the reasoner permitted them something.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Duty {
+    /// The identifier of the duty.
+    pub id: String,
+    /// The reference of the verdict that created the duty.
+    pub verdict_reference: String,
+    /// The user that has to fulfil the duty.
+    pub holder: String,
+    /// What the user has to do (i.e., the obligation of the verdict).
+    pub duty: String,
+    /// When the duty was created.
+    pub created_at: DateTime<Local>,
+    /// When the duty was reported as fulfilled, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fulfilled_at: Option<DateTime<Local>>,
+    /// What the holder reported when fulfilling the duty, if anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+}
+impl Duty {
+    /// Returns whether the duty has not been fulfilled yet.
+    #[inline]
+    pub fn is_open(&self) -> bool { self.fulfilled_at.is_none() }
+}
+
+/***** LIBRARY *****/
+/// Keeps track of duties.
+#[async_trait::async_trait]
+pub trait DutyStore: Send + Sync {
+    /// Records a new, open duty.
+    ///
+    /// # Arguments
+    /// - `duty`: The [`Duty`] to record.
+    ///
+    /// # Errors
+    /// This function errors if the duty could not be recorded.
+    async fn add_duty(&self, duty: Duty) -> Result<(), DutiesError>;
+
+    /// Retrieves the duties that have not been fulfilled yet.
+    ///
+    /// # Arguments
+    /// - `holder`: If given, only returns the open duties of this user.
+    ///
+    /// # Returns
+    /// The open [`Duty`]s, oldest first.
+    ///
+    /// # Errors
+    /// This function errors if the duties could not be read.
+    async fn get_open_duties(&self, holder: Option<&str>) -> Result<Vec<Duty>, DutiesError>;
+
+    /// Marks a duty as fulfilled.
+    ///
+    /// # Arguments
+    /// - `id`: The identifier of the duty to fulfil.
+    /// - `report`: What the holder reported about fulfilling it, if anything.
+    ///
+    /// # Returns
+    /// The fulfilled [`Duty`], or [`None`] if no duty with that identifier exists. Fulfilling a duty that is already
+    /// fulfilled returns it unchanged.
+    ///
+    /// # Errors
+    /// This function errors if the duty could not be updated.
+    async fn fulfil_duty(&self, id: &str, report: Option<String>) -> Result<Option<Duty>, DutiesError>;
+}
@@ -1,7 +1,14 @@
 use std::error::Error;
 
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
-use workflow::spec::{Dataset, User};
+use workflow::spec::{Dataset, PackageImage, User};
+
+use crate::counters::Counter;
+use crate::duties::Duty;
+
+pub mod counters;
+pub mod duties;
 
 /***** ERRORS *****/
 /// Defines some errors being constructable in the type used in the [`StateResolver`].
@@ -23,18 +30,64 @@ impl StateResolverError for std::convert::Infallible {
 }
 
 /***** AUXILLARY *****/
+/// Tells who owns a dataset and how to reach them, such that they can be notified of verdicts on workflows using it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DatasetOwner {
+    /// The name of the dataset.
+    pub dataset: String,
+    /// The name of the owner, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name:    Option<String>,
+    /// The e-mail address of the owner, if they want to be notified by e-mail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email:   Option<String>,
+    /// The URL to POST notifications to, if the owner wants to be notified by webhook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+/// Tells that a request is made by one party on behalf of another (see `AuthContext::actor`).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Delegation {
+    /// The user on whose behalf the request is made.
+    pub subject: String,
+    /// The party that made the request, e.g., a driver system submitting workflows for its users.
+    pub actor:   String,
+}
+
 /// The state that captures runtime context, returned by a [`StateResolver`] dynamically.
 ///
 /// This defines everything a policy gets to know about the state of the system at the time a policy is being checked.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct State {
     // Only scientists for now
-    pub users:     Vec<User>,
-    pub locations: Vec<User>,
-    pub datasets:  Vec<Dataset>,
-    pub functions: Vec<Dataset>,
+    pub users:      Vec<User>,
+    pub locations:  Vec<User>,
+    pub datasets:   Vec<Dataset>,
+    pub functions:  Vec<Dataset>,
+    /// The container images that implement the known package versions, used to tell policies which code runs in a task.
+    #[serde(default)]
+    pub images:     Vec<PackageImage>,
+    /// The usage of users and datasets in the current period, if usage is counted (see [`counters`]).
+    #[serde(default)]
+    pub counters:   Vec<Counter>,
+    /// The duties that the user asking has not fulfilled yet, if duties are tracked (see [`duties`]).
+    #[serde(default)]
+    pub duties:     Vec<Duty>,
+    /// The owners of datasets and how to reach them, if known.
+    #[serde(default)]
+    pub owners:     Vec<DatasetOwner>,
+    /// Who asks on behalf of whom, if the request was delegated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegation: Option<Delegation>,
+    /// Whether the request asked to collect every reason for denial (`true`) or to stop at the first (`false`). Left to
+    /// the reasoner connector if [`None`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exhaustive: Option<bool>,
+    /// The use-case this state was resolved for (see [`StateResolver::get_state()`]).
+    #[serde(default)]
+    pub use_case:   String,
     // TODO: Somehow add events / audit trail
-    // TODO: Somehow add duties or duty policies, maybe encode in Dataset?
 }
 
 /***** LIBRARY *****/
@@ -59,4 +112,28 @@ pub trait StateResolver {
     /// # Errors
     /// This function may error whenever it likes. However, it's recommended to trigger the errors specified in the [`StateResolverError`] trait if applicable.
     async fn get_state(&self, use_case: String) -> Result<State, Self::Error>;
+
+    /// Retrieves the reasoner state as it was at a given moment, if this resolver keeps snapshots of it.
+    ///
+    /// This is used to answer questions retrospectively (e.g., to resolve disputes about past requests).
+    ///
+    /// # Arguments
+    /// - `use_case`: Some identifier that allows the state resolver to assume a different state depending on the use-case used.
+    /// - `at`: The moment to retrieve the state of.
+    ///
+    /// # Returns
+    /// The [`State`] that applied at `at`, or [`None`] if no snapshot of it is known. The default implementation keeps no snapshots.
+    ///
+    /// # Errors
+    /// This function may error whenever it likes. However, it's recommended to trigger the errors specified in the [`StateResolverError`] trait if applicable.
+    async fn get_state_at(&self, _use_case: String, _at: DateTime<Local>) -> Result<Option<State>, Self::Error> { Ok(None) }
+
+    /// Re-reads the resolver's configuration and whatever state it resolves from, without a restart.
+    ///
+    /// States that were already resolved are not affected. Resolvers that cannot be reconfigured can rely on the default,
+    /// which keeps everything as-is.
+    ///
+    /// # Errors
+    /// This function errors if the new configuration or state could not be read, in which case the old ones are kept.
+    async fn reload(&self) -> Result<(), Self::Error> { Ok(()) }
 }
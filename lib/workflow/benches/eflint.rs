@@ -0,0 +1,71 @@
+//! Benchmarks compiling large workflows to eFLINT, both on a single thread and with branches compiled concurrently.
+//!
+//! Run with `cargo bench -p workflow --features eflint`.
+
+use std::collections::HashSet;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use specifications::version::Version;
+use workflow::{Dataset, Elem, ElemParallel, ElemTask, MergeStrategy, Metadata, User, Workflow};
+
+/***** CONSTANTS *****/
+/// The number of tasks in the benchmarked workflows.
+const N_TASKS: usize = 10_000;
+/// The numbers of parallel branches over which the tasks are divided.
+const N_BRANCHES: [usize; 3] = [2, 8, 32];
+
+/***** HELPERS *****/
+/// Generates a workflow with [`N_TASKS`] tasks, divided over `n_branches` parallel branches of chained tasks.
+///
+/// # Arguments
+/// - `n_branches`: The number of parallel branches to generate.
+///
+/// # Returns
+/// A new [`Workflow`].
+fn generate_workflow(n_branches: usize) -> Workflow {
+    let branches: Vec<Elem> = (0..n_branches)
+        .map(|b| {
+            let mut elem: Elem = Elem::Next;
+            for t in (0..N_TASKS / n_branches).rev() {
+                elem = Elem::Task(ElemTask {
+                    id: format!("bench-{b}-{t}-task"),
+                    name: "process".into(),
+                    package: "bench".into(),
+                    version: Version::new(1, 0, 0),
+                    input: vec![Dataset::new(format!("data-{b}-{t}"), Some("amy".into()))],
+                    output: Some(Dataset::new(format!("data-{b}-{}", t + 1), None)),
+                    location: Some("bob".into()),
+                    image: None,
+                    metadata: vec![Metadata { owner: "amy".into(), tag: "bench".into(), signature: None }],
+                    next: Box::new(elem),
+                });
+            }
+            elem
+        })
+        .collect();
+
+    Workflow {
+        id: "bench".into(),
+        start: Elem::Parallel(ElemParallel { branches, merge: MergeStrategy::None, next: Box::new(Elem::Stop(HashSet::new())) }),
+        user: User { name: "amy".into() },
+        metadata: vec![],
+        signature: String::new(),
+    }
+}
+
+/***** BENCHMARKS *****/
+fn bench_to_eflint(c: &mut Criterion) {
+    let single: rayon::ThreadPool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+    let mut group = c.benchmark_group("to_eflint");
+    group.sample_size(20);
+    for n_branches in N_BRANCHES {
+        let wf: Workflow = generate_workflow(n_branches);
+        group.bench_with_input(BenchmarkId::new("sequential", n_branches), &wf, |b, wf| b.iter(|| single.install(|| wf.to_eflint())));
+        group.bench_with_input(BenchmarkId::new("concurrent", n_branches), &wf, |b, wf| b.iter(|| wf.to_eflint()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_to_eflint);
+criterion_main!(benches);
@@ -0,0 +1,162 @@
+//! Defines a canonical serialization of the checker [`Workflow`], and a content hash based on it.
+//!
+//! Two requests about the same workflow typically still differ in their serialization: the workflow gets a fresh
+//! identifier (which is also baked into the identifiers of its tasks and commits), the order of sets (input datasets,
+//! metadata, the datasets carried by an [`Elem::Stop`]) is arbitrary, and so is the order of the fields of objects. The
+//! canonical form strips the workflow's identifier and signature, makes all element identifiers relative to the
+//! workflow, and sorts all sets and all fields, such that identical workflows serialize (and thus hash) identically.
+
+use serde_json::{Map, Value};
+use sha2::{Digest as _, Sha256};
+
+use crate::spec::{Dataset, Elem, ElemBranch, ElemCommit, ElemLoop, ElemParallel, ElemTask, Metadata, Workflow};
+
+/***** HELPER FUNCTIONS *****/
+/// Sorts a set of datasets by name.
+fn sorted_datasets<'d>(datasets: impl IntoIterator<Item = &'d Dataset>) -> Vec<Dataset> {
+    let mut datasets: Vec<Dataset> = datasets.into_iter().cloned().collect();
+    datasets.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name).then_with(|| lhs.from.cmp(&rhs.from)));
+    datasets
+}
+
+/// Sorts a set of metadata by owner and tag.
+fn sorted_metadata(metadata: &[Metadata]) -> Vec<Metadata> {
+    let mut metadata: Vec<Metadata> = metadata.to_vec();
+    metadata.sort_by(|lhs, rhs| (&lhs.owner, &lhs.tag, &lhs.signature).cmp(&(&rhs.owner, &rhs.tag, &rhs.signature)));
+    metadata
+}
+
+/// Recursively brings an [`Elem`] in canonical form.
+///
+/// # Arguments
+/// - `wf_id`: The identifier of the workflow the element is in.
+/// - `elem`: The [`Elem`] to canonicalize.
+///
+/// # Returns
+/// The canonical version of `elem`.
+fn canonical_elem(wf_id: &str, elem: &Elem) -> Elem {
+    match elem {
+        Elem::Task(ElemTask { id, name, package, version, input, output, location, image, metadata, next }) => Elem::Task(ElemTask {
+            id: relative_id(wf_id, id),
+            name: name.clone(),
+            package: package.clone(),
+            version: version.clone(),
+            input: sorted_datasets(input),
+            output: output.clone(),
+            location: location.clone(),
+            image: image.clone(),
+            metadata: sorted_metadata(metadata),
+            next: Box::new(canonical_elem(wf_id, next)),
+        }),
+        Elem::Commit(ElemCommit { id, data_name, location, input, next }) => Elem::Commit(ElemCommit {
+            id: relative_id(wf_id, id),
+            data_name: data_name.clone(),
+            location: location.clone(),
+            input: sorted_datasets(input),
+            next: Box::new(canonical_elem(wf_id, next)),
+        }),
+
+        // Note that the order of branches is kept, as it's meaningful to the workflow
+        Elem::Branch(ElemBranch { branches, next }) => Elem::Branch(ElemBranch {
+            branches: branches.iter().map(|branch| canonical_elem(wf_id, branch)).collect(),
+            next:     Box::new(canonical_elem(wf_id, next)),
+        }),
+        Elem::Parallel(ElemParallel { branches, merge, next }) => Elem::Parallel(ElemParallel {
+            branches: branches.iter().map(|branch| canonical_elem(wf_id, branch)).collect(),
+            merge:    merge.clone(),
+            next:     Box::new(canonical_elem(wf_id, next)),
+        }),
+        Elem::Loop(ElemLoop { body, next }) => {
+            Elem::Loop(ElemLoop { body: Box::new(canonical_elem(wf_id, body)), next: Box::new(canonical_elem(wf_id, next)) })
+        },
+
+        Elem::Next => Elem::Next,
+        // The datasets live in a set, so they are only sorted once serialized (see `sorted_value()`)
+        Elem::Stop(datasets) => Elem::Stop(datasets.clone()),
+    }
+}
+
+/// Recursively sorts the fields of all objects in a [`Value`].
+///
+/// Also makes sure the datasets of any [`Elem::Stop`] are sorted, as these are serialized from a set.
+fn sorted_value(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut fields: Vec<(String, Value)> = map.into_iter().collect();
+            fields.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            let mut sorted: Map<String, Value> = Map::with_capacity(fields.len());
+            for (key, value) in fields {
+                let value: Value = match (key.as_str(), value) {
+                    ("Stop", Value::Array(mut datasets)) => {
+                        datasets.sort_by_key(|dataset| dataset.to_string());
+                        Value::Array(datasets)
+                    },
+                    (_, value) => value,
+                };
+                sorted.insert(key, sorted_value(value));
+            }
+            Value::Object(sorted)
+        },
+        Value::Array(values) => Value::Array(values.into_iter().map(sorted_value).collect()),
+        value => value,
+    }
+}
+
+/***** LIBRARY *****/
+/// Makes an element identifier relative to the workflow it is in.
+///
+/// # Arguments
+/// - `wf_id`: The identifier of the workflow.
+/// - `id`: The identifier of the element, typically `<wf_id>-<pc>-<kind>`.
+///
+/// # Returns
+/// The identifier without the workflow's identifier in it.
+pub fn relative_id(wf_id: &str, id: &str) -> String {
+    match id.strip_prefix(wf_id).and_then(|id| id.strip_prefix('-')) {
+        Some(id) => id.into(),
+        None => id.into(),
+    }
+}
+
+/// Brings a [`Workflow`] in canonical form.
+///
+/// This strips the workflow's identifier and signature, makes all element identifiers relative to the workflow and
+/// sorts all sets of datasets and metadata. Note that the datasets of an [`Elem::Stop`] are only sorted by
+/// [`canonical_json()`], as they live in a set.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to canonicalize.
+///
+/// # Returns
+/// A new [`Workflow`] that is identical for all workflows with the same contents.
+pub fn canonicalize(workflow: &Workflow) -> Workflow {
+    Workflow {
+        id: String::new(),
+        start: canonical_elem(&workflow.id, &workflow.start),
+        user: workflow.user.clone(),
+        metadata: sorted_metadata(&workflow.metadata),
+        signature: String::new(),
+    }
+}
+
+/// Serializes a [`Workflow`] in canonical form.
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to serialize.
+///
+/// # Returns
+/// A compact JSON string that is identical for all workflows with the same contents. See [`canonicalize()`].
+pub fn canonical_json(workflow: &Workflow) -> String {
+    // Serializing a workflow to a `Value` cannot fail, as it only has string keys
+    let value: Value = serde_json::to_value(canonicalize(workflow)).expect("Failed to serialize workflow");
+    sorted_value(value).to_string()
+}
+
+/// Computes the content hash of a [`Workflow`].
+///
+/// # Arguments
+/// - `workflow`: The [`Workflow`] to hash.
+///
+/// # Returns
+/// The hex-encoded SHA-256 hash of the workflow's [canonical serialization](canonical_json()).
+pub fn content_hash(workflow: &Workflow) -> String { hex::encode(Sha256::digest(canonical_json(workflow).as_bytes())) }
@@ -17,7 +17,6 @@ use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::error;
 use std::fmt::{Display, Formatter, Result as FResult};
-use std::panic::catch_unwind;
 
 use brane_ast::spec::BuiltinFunctions;
 use brane_ast::{MergeStrategy, ast};
@@ -26,8 +25,10 @@ use enum_debug::EnumDebug as _;
 use log::{Level, debug, trace};
 use specifications::data::{AvailabilityKind, DataName, PreprocessKind};
 
-use super::preprocess;
+use super::preprocess::{Origins, WorkflowLimits};
 use super::spec::{Dataset, Elem, ElemBranch, ElemCommit, ElemLoop, ElemParallel, ElemTask, User, Workflow};
+use super::{optimize, preprocess};
+use crate::diagnostics::InlineStats;
 use crate::{Metadata, utils};
 
 /***** ERRORS *****/
@@ -46,6 +47,14 @@ pub enum Error {
     ParallelWithNonJoin { pc: ResolvedProgramCounter, merge: ResolvedProgramCounter, got: String },
     /// Found a join that wasn't paired with a parallel edge.
     StrayJoin { pc: ResolvedProgramCounter },
+    /// A node refers to a task that is not in the workflow's symbol table.
+    UnknownTask { pc: ResolvedProgramCounter, task: usize },
+    /// A node refers to a task that is not a compute task.
+    UnsupportedTask { pc: ResolvedProgramCounter, task: usize },
+    /// A call refers to a function that is not in the workflow's symbol table.
+    UnknownFunction { pc: ResolvedProgramCounter, func: usize },
+    /// A call was found of which the function could not be determined.
+    UnresolvedCall { pc: ResolvedProgramCounter },
     /// A call was performed to a non-builtin
     IllegalCall { pc: ResolvedProgramCounter, name: String },
     /// A `commit_result()` was found that returns more than 1 result.
@@ -75,6 +84,12 @@ impl Display for Error {
                 write!(f, "Parallel edge at {pc}'s merge edge (at {merge}) was not an Edge::Join, but an Edge::{got}")
             },
             StrayJoin { pc } => write!(f, "Found Join-edge without preceding Parallel-edge at {pc}"),
+            UnknownTask { pc, task } => write!(f, "Node at {pc} refers to unknown task {task}"),
+            UnsupportedTask { pc, task } => {
+                write!(f, "Node at {pc} refers to task {task}, which is not a compute task (only compute tasks are supported)")
+            },
+            UnknownFunction { pc, func } => write!(f, "Call at {pc} refers to unknown function {func}"),
+            UnresolvedCall { pc } => write!(f, "Could not determine which function is called at {pc}"),
             IllegalCall { pc, name } => {
                 write!(f, "Encountered illegal call to function '{name}' at {pc} (calls to non-task, non-builtin functions are not supported)")
             },
@@ -88,6 +103,11 @@ impl Display for Error {
         }
     }
 }
+impl Error {
+    /// Returns whether this error is about a workflow exceeding its [`WorkflowLimits`], rather than about it being incoherent.
+    #[inline]
+    pub fn exceeds_limits(&self) -> bool { matches!(self, Self::Preprocess { err } if err.exceeds_limits()) }
+}
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         use Error::*;
@@ -98,6 +118,10 @@ impl error::Error for Error {
             | ParallelMergeOutOfBounds { .. }
             | ParallelWithNonJoin { .. }
             | StrayJoin { .. }
+            | UnknownTask { .. }
+            | UnsupportedTask { .. }
+            | UnknownFunction { .. }
+            | UnresolvedCall { .. }
             | IllegalCall { .. }
             | CommitTooMuchOutput { .. }
             | CommitNoOutput { .. }
@@ -216,12 +240,30 @@ fn analyse_data_lkls(lkls: &mut HashMap<DataName, HashSet<String>>, wir: &ast::W
     }
 }
 
+/// Computes the identifier of the element compiled from an edge, without the workflow's identifier.
+///
+/// # Arguments
+/// - `wir`: The [`ast::Workflow`] with the edge.
+/// - `origins`: The [`Origins`] of the edges inlined by the preprocessing.
+/// - `pc`: The program-counter-index of the edge.
+///
+/// # Returns
+/// The [structural identifier](preprocess::structural_id()) of the edge's origin, such that an element can be found by
+/// where it is in the original WIR (e.g., `main:4/func:2`) even if it was inlined.
+fn elem_id(wir: &ast::Workflow, origins: &Origins, pc: ProgramCounter) -> String {
+    match origins.get(&pc) {
+        Some(origin) => preprocess::structural_id(&wir.table, origin),
+        None => pc.resolved(&wir.table).to_string(),
+    }
+}
+
 /// Reconstructs the workflow graph to [`Elem`]s instead of [`ast::Edge`]s.
 ///
 /// # Arguments
 /// - `wir`: The [`ast::Workflow`] to analyse.
 /// - `wf_id`: The identifier of the workflow we're compiling in.
 /// - `calls`: The map of Call program-counter-indices to function IDs called.
+/// - `origins`: The [`Origins`] of the edges inlined by the preprocessing, which identify the elements compiled from them.
 /// - `lkls`: The map of program counter/dataset pairs that map to the locations where we last saw them. Mutable to update it as we make decisions for commits.
 /// - `pc`: The program-counter-index of the edge to analyse. These are pairs of `(function, edge_idx)`, where main is referred to by [`usize::MAX`](usize).
 /// - `plug`: The element to write when we reached the (implicit) end of a branch.
@@ -232,10 +274,12 @@ fn analyse_data_lkls(lkls: &mut HashMap<DataName, HashSet<String>>, wir: &ast::W
 ///
 /// # Errors
 /// This function errors if a definition in the Workflow was unknown.
+#[allow(clippy::too_many_arguments)]
 fn reconstruct_graph(
     wir: &ast::Workflow,
     wf_id: &str,
     calls: &HashMap<ProgramCounter, usize>,
+    origins: &Origins,
     lkls: &mut HashMap<DataName, HashSet<String>>,
     pc: ProgramCounter,
     plug: Elem,
@@ -259,25 +303,20 @@ fn reconstruct_graph(
     match edge {
         ast::Edge::Linear { next, .. } => {
             // Simply skip to the next, as linear connectors are no longer interesting
-            reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*next), plug, breakpoint)
+            reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*next), plug, breakpoint)
         },
 
         ast::Edge::Node { task, locs: _, at, input, result, metadata, next } => {
             // Resolve the task definition
-            let def: &ast::ComputeTaskDef = match catch_unwind(|| wir.table.task(*task)) {
-                Ok(def) => {
-                    if let ast::TaskDef::Compute(c) = def {
-                        c
-                    } else {
-                        unimplemented!();
-                    }
-                },
-                Err(_) => panic!("Encountered unknown task '{task}' after preprocessing"),
+            let def: &ast::ComputeTaskDef = match wir.table.tasks.get(*task) {
+                Some(ast::TaskDef::Compute(def)) => def,
+                Some(_) => return Err(Error::UnsupportedTask { pc: pc.resolved(&wir.table), task: *task }),
+                None => return Err(Error::UnknownTask { pc: pc.resolved(&wir.table), task: *task }),
             };
 
             // Return the elem
             Ok(Elem::Task(ElemTask {
-                id: format!("{}-{}-task", wf_id, pc.resolved(&wir.table)),
+                id: format!("{}-{}-task", wf_id, elem_id(wir, origins, pc)),
                 name: def.function.name.clone(),
                 package: def.package.clone(),
                 version: def.version,
@@ -295,11 +334,12 @@ fn reconstruct_graph(
                     .collect(),
                 output: result.as_ref().map(|name| Dataset { name: name.clone(), from: None }),
                 location: at.clone(),
+                image: None,
                 metadata: metadata
                     .iter()
                     .map(|md| Metadata { owner: md.owner.clone(), tag: md.tag.clone(), signature: md.signature.clone() })
                     .collect(),
-                next: Box::new(reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*next), plug, breakpoint)?),
+                next: Box::new(reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*next), plug, breakpoint)?),
             }))
         },
 
@@ -308,14 +348,23 @@ fn reconstruct_graph(
         ast::Edge::Branch { true_next, false_next, merge } => {
             // Construct the branches first
             let mut branches: Vec<Elem> =
-                vec![reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*true_next), Elem::Next, merge.map(|merge| pc.jump(merge)))?];
+                vec![reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*true_next), Elem::Next, merge.map(|merge| pc.jump(merge)))?];
             if let Some(false_next) = false_next {
-                branches.push(reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*false_next), Elem::Next, merge.map(|merge| pc.jump(merge)))?)
+                branches.push(reconstruct_graph(
+                    wir,
+                    wf_id,
+                    calls,
+                    origins,
+                    lkls,
+                    pc.jump(*false_next),
+                    Elem::Next,
+                    merge.map(|merge| pc.jump(merge)),
+                )?)
             }
 
             // Build the next, if there is any
             let next: Elem = merge
-                .map(|merge| reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(merge), plug, breakpoint))
+                .map(|merge| reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(merge), plug, breakpoint))
                 .transpose()?
                 .unwrap_or(Elem::Stop(HashSet::new()));
 
@@ -327,7 +376,7 @@ fn reconstruct_graph(
             // Construct the branches first
             let mut elem_branches: Vec<Elem> = Vec::with_capacity(branches.len());
             for branch in branches {
-                elem_branches.push(reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*branch), Elem::Next, Some(pc.jump(*merge)))?);
+                elem_branches.push(reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*branch), Elem::Next, Some(pc.jump(*merge)))?);
             }
 
             // Let us checkout that the merge point is a join
@@ -346,7 +395,7 @@ fn reconstruct_graph(
             };
 
             // Build the post-join point onwards
-            let next: Elem = reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(next), plug, breakpoint)?;
+            let next: Elem = reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(next), plug, breakpoint)?;
 
             // We have enough to build ourselves
             Ok(Elem::Parallel(ElemParallel { branches: elem_branches, merge: strategy, next: Box::new(next) }))
@@ -356,14 +405,14 @@ fn reconstruct_graph(
 
         ast::Edge::Loop { cond, body, next } => {
             // Build the body first
-            let body_elems: Elem = reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*body), Elem::Next, Some(pc.jump(*cond)))?;
+            let body_elems: Elem = reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*body), Elem::Next, Some(pc.jump(*cond)))?;
 
             // Build the condition, with immediately following the body for any open ends that we find
-            let cond: Elem = reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*cond), body_elems, Some(pc.jump(*body - 1)))?;
+            let cond: Elem = reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*cond), body_elems, Some(pc.jump(*body - 1)))?;
 
             // Build the next
             let next: Elem = next
-                .map(|next| reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(next), plug, breakpoint))
+                .map(|next| reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(next), plug, breakpoint))
                 .transpose()?
                 .unwrap_or(Elem::Stop(HashSet::new()));
 
@@ -376,9 +425,9 @@ fn reconstruct_graph(
             let func_def: &ast::FunctionDef = match calls.get(&pc) {
                 Some(id) => match wir.table.funcs.get(*id) {
                     Some(def) => def,
-                    None => panic!("Encountered unknown function '{id}' after preprocessing"),
+                    None => return Err(Error::UnknownFunction { pc: pc.resolved(&wir.table), func: *id }),
                 },
-                None => panic!("Encountered unresolved call after preprocessing"),
+                None => return Err(Error::UnresolvedCall { pc: pc.resolved(&wir.table) }),
             };
 
             // Only allow calls to builtins
@@ -414,11 +463,11 @@ fn reconstruct_graph(
                 };
 
                 // Construct next first
-                let next: Elem = reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*next), plug, breakpoint)?;
+                let next: Elem = reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*next), plug, breakpoint)?;
 
                 // Then we wrap the rest in a commit
                 Ok(Elem::Commit(ElemCommit {
-                    id: format!("{}-{}-commit", wf_id, pc.resolved(&wir.table)),
+                    id: format!("{}-{}-commit", wf_id, elem_id(wir, origins, pc)),
                     data_name,
                     location: locs.into_iter().next(),
                     input: new_input,
@@ -429,7 +478,7 @@ fn reconstruct_graph(
                 || func_def.name == BuiltinFunctions::Len.name()
             {
                 // Using them is OK, we just ignore them for the improved workflow
-                reconstruct_graph(wir, wf_id, calls, lkls, pc.jump(*next), plug, breakpoint)
+                reconstruct_graph(wir, wf_id, calls, origins, lkls, pc.jump(*next), plug, breakpoint)
             } else {
                 Err(Error::IllegalCall { pc: pc.resolved(&wir.table), name: func_def.name.clone() })
             }
@@ -444,7 +493,40 @@ impl TryFrom<ast::Workflow> for Workflow {
     type Error = Error;
 
     #[inline]
-    fn try_from(value: ast::Workflow) -> Result<Self, Self::Error> {
+    fn try_from(value: ast::Workflow) -> Result<Self, Self::Error> { Self::from_wir(value, &WorkflowLimits::default()) }
+}
+
+impl Workflow {
+    /// Compiles a [WIR](ast::Workflow) to a checker workflow, refusing it if it is too large or complex.
+    ///
+    /// # Arguments
+    /// - `value`: The [`ast::Workflow`] to compile.
+    /// - `limits`: The [`WorkflowLimits`] that the workflow must stay within while it is preprocessed.
+    ///
+    /// # Returns
+    /// A new checker [`Workflow`].
+    ///
+    /// # Errors
+    /// This function errors if the workflow is incoherent or not supported, or if it exceeds any of the `limits` (see
+    /// [`Error::exceeds_limits()`]).
+    #[inline]
+    pub fn from_wir(value: ast::Workflow, limits: &WorkflowLimits) -> Result<Self, Error> {
+        Self::from_wir_with_stats(value, limits).map(|(workflow, _)| workflow)
+    }
+
+    /// Compiles a [WIR](ast::Workflow) to a checker workflow like [`Workflow::from_wir()`], but also tells how much it grew
+    /// by preprocessing it.
+    ///
+    /// # Arguments
+    /// - `value`: The [`ast::Workflow`] to compile.
+    /// - `limits`: The [`WorkflowLimits`] that the workflow must stay within while it is preprocessed.
+    ///
+    /// # Returns
+    /// A tuple of the new checker [`Workflow`] and the [`InlineStats`] of preprocessing it.
+    ///
+    /// # Errors
+    /// This function errors for the same reasons as [`Workflow::from_wir()`].
+    pub fn from_wir_with_stats(value: ast::Workflow, limits: &WorkflowLimits) -> Result<(Self, InlineStats), Error> {
         let mut buf: Vec<u8> = Vec::new();
         brane_ast::traversals::print::ast::do_traversal(&value, &mut buf).unwrap();
         debug!("Compiling workflow:\n\n{}\n", String::from_utf8(buf).unwrap());
@@ -458,26 +540,29 @@ impl TryFrom<ast::Workflow> for Workflow {
 
         // First, analyse the calls in the workflow as much as possible (and simplify)
         let wf_id: String = value.id.clone();
-        let (wir, calls): (ast::Workflow, HashMap<ProgramCounter, usize>) = match preprocess::simplify(value) {
-            Ok(res) => res,
-            Err(err) => return Err(Error::Preprocess { err }),
-        };
+        let (mut wir, calls, origins, stats): (ast::Workflow, HashMap<ProgramCounter, usize>, Origins, InlineStats) =
+            match preprocess::simplify_with_limits(value, limits) {
+                Ok(res) => res,
+                Err(err) => return Err(Error::Preprocess { err }),
+            };
         if log::max_level() >= Level::Debug {
             // Write the processed graph
             let mut buf: Vec<u8> = vec![];
             brane_ast::traversals::print::ast::do_traversal(&wir, &mut buf).unwrap();
             debug!("Preprocessed workflow:\n\n{}\n", String::from_utf8_lossy(&buf));
         }
+        // Skip anything we know statically won't be executed
+        optimize::optimize_wir(&mut wir);
 
         // Collect the map of data to Last Known Locations (LKL).
         let mut lkls: HashMap<DataName, HashSet<String>> = HashMap::new();
         analyse_data_lkls(&mut lkls, &wir, ProgramCounter::start(), None);
 
         // Alright now attempt to re-build the graph in the new style
-        let graph: Elem = reconstruct_graph(&wir, &wf_id, &calls, &mut lkls, ProgramCounter::start(), Elem::Stop(HashSet::new()), None)?;
+        let graph: Elem = reconstruct_graph(&wir, &wf_id, &calls, &origins, &mut lkls, ProgramCounter::start(), Elem::Stop(HashSet::new()), None)?;
 
         // Build a new Workflow with that!
-        Ok(Self {
+        let workflow = Self {
             id:    wf_id,
             start: graph,
 
@@ -488,6 +573,7 @@ impl TryFrom<ast::Workflow> for Workflow {
                 .map(|md| Metadata { owner: md.owner.clone(), tag: md.tag.clone(), signature: md.signature.clone() })
                 .collect(),
             signature: "its_signed_i_swear_mom".into(),
-        })
+        };
+        Ok((workflow, stats))
     }
 }
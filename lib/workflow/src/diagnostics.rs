@@ -0,0 +1,101 @@
+//! Finds the places where a [`Workflow`] lacks information that reasoners need for a complete check.
+//!
+//! Workflows are compiled from the WIR as submitted, which is not necessarily fully planned yet. Such workflows still
+//! compile, but reasoners then have to leave parts of them out (e.g., eFLINT cannot say where an unplanned task runs),
+//! making the check weaker than the workflow author probably expects. These [`Diagnostic`]s describe what was left out.
+//!
+//! Conversely, workflows may also compile to far more than their author expects, as every call to a function is replaced
+//! by a copy of its body. The [`InlineStats`] of a workflow tell how much it grew, which explains slow checks instead.
+
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use serde::{Deserialize, Serialize};
+
+use crate::spec::{Dataset, ElemCommit, ElemTask, Location, Workflow};
+use crate::utils::{WorkflowVisitor, walk_workflow_preorder};
+
+/***** HELPERS *****/
+/// Collects the [`Diagnostic`]s of every element in a [`Workflow`].
+struct DiagnosticCollector(Vec<Diagnostic>);
+impl DiagnosticCollector {
+    /// Adds a [`Diagnostic::MissingTransferSource`] for every input that comes from nowhere.
+    fn inputs(&mut self, node: &str, input: &[Dataset], location: Option<&Location>) {
+        for i in input {
+            if i.from.is_none() && location.is_none() {
+                self.0.push(Diagnostic::MissingTransferSource { node: node.into(), dataset: i.name.clone() });
+            }
+        }
+    }
+}
+impl WorkflowVisitor for DiagnosticCollector {
+    fn visit_task(&mut self, task: &ElemTask) {
+        self.inputs(&task.id, &task.input, task.location.as_ref());
+        if task.location.is_none() {
+            self.0.push(Diagnostic::UnplannedTask { node: task.id.clone() });
+        }
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        // Unlike tasks, commits don't get their inputs from where they run
+        self.inputs(&commit.id, &commit.input, None);
+    }
+}
+
+/***** LIBRARY *****/
+/// Describes information missing from a [`Workflow`] that makes its check weaker.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Diagnostic {
+    /// A task is not planned on any location, so nothing can be said about where it runs.
+    UnplannedTask {
+        /// The identifier of the task.
+        node: String,
+    },
+    /// An input of a task or commit has no known source, so nothing can be said about transferring it.
+    MissingTransferSource {
+        /// The identifier of the task or commit.
+        node:    String,
+        /// The name of the dataset.
+        dataset: String,
+    },
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::UnplannedTask { node } => write!(f, "Task '{node}' is not planned on any location"),
+            Self::MissingTransferSource { node, dataset } => write!(f, "Input dataset '{dataset}' of '{node}' has no transfer source"),
+        }
+    }
+}
+
+/// Describes how much a workflow grew by inlining its functions when it was preprocessed.
+///
+/// The facts a reasoner derives from a workflow grow with the number of edges compiled, so these explain why some
+/// (otherwise small) workflows take long to check.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InlineStats {
+    /// The number of distinct functions whose body was inlined in the main body.
+    pub functions_inlined: usize,
+    /// The number of calls in the main body that were replaced by the body of the function called, including those in inlined bodies.
+    pub calls_inlined: usize,
+    /// The number of edges in the workflow before inlining, in its main body and all its functions together.
+    pub edges_before: usize,
+    /// The number of edges in the main body after inlining, i.e., the edges that are compiled.
+    pub edges_after: usize,
+    /// The number of calls left in the main body after inlining (e.g., to builtins or recursive functions).
+    pub calls_remaining: usize,
+    /// The number of loops in the main body after inlining.
+    pub loops: usize,
+}
+
+impl Workflow {
+    /// Finds the places where this Workflow lacks information that reasoners need for a complete check.
+    ///
+    /// # Returns
+    /// A list of [`Diagnostic`]s, in the order in which their elements occur in the workflow. Empty if the workflow is complete.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut collector = DiagnosticCollector(vec![]);
+        walk_workflow_preorder(&self.start, &mut collector);
+        collector.0
+    }
+}
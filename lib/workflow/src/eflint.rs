@@ -20,9 +20,16 @@ use enum_debug::EnumDebug as _;
 use log::{trace, warn};
 use rand::Rng as _;
 use rand::distributions::Alphanumeric;
+use rayon::prelude::*;
 
 use crate::spec::{Dataset, Elem, ElemBranch, ElemCommit, ElemLoop, ElemParallel, ElemTask, Metadata, User, Workflow};
 
+/***** CONSTANTS *****/
+/// The number of [`Elem`]s that (at least two) branches need before they are compiled concurrently.
+///
+/// Below this, the cost of spawning and concatenating separate phrase buffers outweighs the gain.
+const PARALLEL_THRESHOLD: usize = 256;
+
 /***** HELPER MACROS *****/
 /// Shorthand for creating an eFLINT JSON Specification true postulation.
 macro_rules! create {
@@ -54,12 +61,14 @@ macro_rules! str_lit {
 /// # Arguments
 /// - `elem`: The graph [`Elem`]ent to analyse.
 /// - `wf_id`: The identifier of the workflow to use for new loop IDs.
-/// - `loops`: A map of pointers to their IDs.
-fn name_loops(mut elem: &Elem, wf_id: &str, loops: &mut HashMap<*const ElemLoop, String>) {
+/// - `loops`: A map of loop addresses to their IDs. Addresses are stored as [`usize`] such that the map can be shared between threads.
+fn name_loops(mut elem: &Elem, wf_id: &str, loops: &mut HashMap<usize, String>) {
     // Note we're doing a combination of actual recursion and looping, to minimize stack usage
     loop {
         match elem {
-            Elem::Task(ElemTask { id: _, name: _, package: _, version: _, input: _, output: _, location: _, metadata: _, next }) => elem = next,
+            Elem::Task(ElemTask { id: _, name: _, package: _, version: _, input: _, output: _, location: _, image: _, metadata: _, next }) => {
+                elem = next
+            },
             Elem::Commit(ElemCommit { id: _, data_name: _, location: _, input: _, next }) => elem = next,
 
             Elem::Branch(ElemBranch { branches, next }) => {
@@ -79,7 +88,7 @@ fn name_loops(mut elem: &Elem, wf_id: &str, loops: &mut HashMap<*const ElemLoop,
 
                 // Generate a name for this loop
                 loops.insert(
-                    l as *const ElemLoop,
+                    l as *const ElemLoop as usize,
                     format!("{wf_id}-{}-loop", rand::thread_rng().sample_iter(Alphanumeric).take(4).map(char::from).collect::<String>()),
                 );
 
@@ -103,16 +112,11 @@ fn name_loops(mut elem: &Elem, wf_id: &str, loops: &mut HashMap<*const ElemLoop,
 /// - `last`: The last node(s) (node, commit or loop) in the subgraph.
 ///
 /// If no nodes are within this body, [`None`] is returned instead.
-fn analyse_loop_body(
-    mut elem: &Elem,
-    loop_names: &HashMap<*const ElemLoop, String>,
-    first: &mut Vec<(String, HashSet<Dataset>)>,
-    last: &mut HashSet<Dataset>,
-) {
+fn analyse_loop_body(mut elem: &Elem, loop_names: &HashMap<usize, String>, first: &mut Vec<(String, HashSet<Dataset>)>, last: &mut HashSet<Dataset>) {
     // Note we're doing a combination of actual recursion and looping, to minimize stack usage
     loop {
         match elem {
-            Elem::Task(ElemTask { id, name: _, package: _, version: _, input, output, location: _, metadata: _, next }) => {
+            Elem::Task(ElemTask { id, name: _, package: _, version: _, input, output, location: _, image: _, metadata: _, next }) => {
                 // Add it if it's the first one we encounter
                 if first.is_empty() {
                     *first = vec![(id.clone(), input.iter().cloned().collect())];
@@ -168,7 +172,7 @@ fn analyse_loop_body(
                 if first.is_empty() {
                     // Get the loop's name
                     let id: &String =
-                        loop_names.get(&(l as *const ElemLoop)).unwrap_or_else(|| panic!("Encountered loop without name after loop naming"));
+                        loop_names.get(&(l as *const ElemLoop as usize)).unwrap_or_else(|| panic!("Encountered loop without name after loop naming"));
 
                     // Set this loop as the first node, combining all the input dataset from the children
                     *first = vec![(id.clone(), body_first.into_iter().flat_map(|(_, data)| data).collect::<HashSet<Dataset>>())]
@@ -185,6 +189,70 @@ fn analyse_loop_body(
     }
 }
 
+/// Counts the [`Elem`]s in the given graph, up to some limit.
+///
+/// # Arguments
+/// - `elem`: The graph [`Elem`]ent to count from.
+/// - `limit`: The count at which to stop counting.
+///
+/// # Returns
+/// The number of elements, or `limit` if there are more.
+fn count_elems(mut elem: &Elem, limit: usize) -> usize {
+    let mut count: usize = 0;
+    while count < limit {
+        count += 1;
+        match elem {
+            Elem::Task(ElemTask { next, .. }) | Elem::Commit(ElemCommit { next, .. }) => elem = next,
+            Elem::Branch(ElemBranch { branches, next }) | Elem::Parallel(ElemParallel { branches, next, .. }) => {
+                for branch in branches {
+                    count += count_elems(branch, limit.saturating_sub(count));
+                }
+                elem = next;
+            },
+            Elem::Loop(ElemLoop { body, next }) => {
+                count += count_elems(body, limit.saturating_sub(count));
+                elem = next;
+            },
+            Elem::Next | Elem::Stop(_) => break,
+        }
+    }
+    count.min(limit)
+}
+
+/// Compiles the branches of a branching or parallel edge to eFLINT [`Phrase`]s.
+///
+/// If at least two branches are large, all of them are compiled concurrently into separate buffers. These are appended in the order
+/// of the branches afterwards, so the result is the same as compiling them in sequence.
+///
+/// # Arguments
+/// - `branches`: The branches to compile.
+/// - `wf_id`: The identifier/name of the workflow we're working with.
+/// - `wf_user`: The identifier/name of the user who will see the workflow result.
+/// - `loop_names`: A map of [`ElemLoop`]s to names we computed beforehand.
+/// - `phrases`: The list of eFLINT [`Phrase`]s we're compiling to.
+fn compile_eflint_branches(branches: &[Elem], wf_id: &str, wf_user: &User, loop_names: &HashMap<usize, String>, phrases: &mut Vec<Phrase>) {
+    if branches.iter().filter(|branch| count_elems(branch, PARALLEL_THRESHOLD) >= PARALLEL_THRESHOLD).take(2).count() < 2 {
+        for branch in branches {
+            compile_eflint(branch, wf_id, wf_user, loop_names, phrases);
+        }
+        return;
+    }
+
+    trace!("Compiling {} branches to eFLINT concurrently", branches.len());
+    let buffers: Vec<Vec<Phrase>> = branches
+        .par_iter()
+        .map(|branch| {
+            let mut buffer: Vec<Phrase> = Vec::new();
+            compile_eflint(branch, wf_id, wf_user, loop_names, &mut buffer);
+            buffer
+        })
+        .collect();
+    phrases.reserve(buffers.iter().map(Vec::len).sum());
+    for mut buffer in buffers {
+        phrases.append(&mut buffer);
+    }
+}
+
 /// Compiles a given piece of metadata.
 ///
 /// # Arguments
@@ -225,12 +293,12 @@ fn compile_metadata(metadata: &Metadata, phrases: &mut Vec<Phrase>) {
 /// - `wf_user`: The identifier/name of the user who will see the workflow result.
 /// - `loop_names`: A map of [`ElemLoop`]s to names we computed beforehand.
 /// - `phrases`: The list of eFLINT [`Phrase`]s we're compiling to.
-fn compile_eflint(mut elem: &Elem, wf_id: &str, wf_user: &User, loop_names: &HashMap<*const ElemLoop, String>, phrases: &mut Vec<Phrase>) {
+fn compile_eflint(mut elem: &Elem, wf_id: &str, wf_user: &User, loop_names: &HashMap<usize, String>, phrases: &mut Vec<Phrase>) {
     // Note we're doing a combination of actual recursion and looping, to minimize stack usage
     loop {
         trace!("Compiling {:?} to eFLINT", elem.variant());
         match elem {
-            Elem::Task(ElemTask { id, name, package, version, input, output, location, metadata, next }) => {
+            Elem::Task(ElemTask { id, name, package, version, input, output, location, image, metadata, next }) => {
                 // Define a new task call and make it part of the workflow
                 // ```eflint
                 // +node(workflow(#wf_id), #id).
@@ -300,6 +368,25 @@ fn compile_eflint(mut elem: &Elem, wf_id: &str, wf_user: &User, loop_names: &Has
                 } else {
                     warn!("Encountered unplanned task '{id}' part of workflow '{wf_id}'");
                 }
+                // Add the image of the task's code, if we know it
+                if let Some(image) = image {
+                    // ```eflint
+                    // +node-image(#node, #image.digest).
+                    // +node-image-registry(node-image(#node, #image.digest), #image.registry).
+                    // ```
+                    let node_image: Expression = constr_app!("node-image", node.clone(), str_lit!(image.digest.clone()));
+                    phrases.push(create!(node_image.clone()));
+                    if let Some(registry) = &image.registry {
+                        phrases.push(create!(constr_app!("node-image-registry", node_image.clone(), str_lit!(registry.clone()))));
+                    }
+                    // Note that only the signatures verified by the checker are given
+                    // ```eflint
+                    // +node-image-signed-by(node-image(#node, #image.digest), #key).
+                    // ```
+                    for key in &image.signed_by {
+                        phrases.push(create!(constr_app!("node-image-signed-by", node_image.clone(), str_lit!(key.clone()))));
+                    }
+                }
 
                 // Finally, add any task metadata
                 for m in metadata {
@@ -386,18 +473,14 @@ fn compile_eflint(mut elem: &Elem, wf_id: &str, wf_user: &User, loop_names: &Has
             },
 
             Elem::Branch(ElemBranch { branches, next }) => {
-                // Do the branches in sequence
-                for branch in branches {
-                    compile_eflint(branch, wf_id, wf_user, loop_names, phrases);
-                }
+                // Do the branches (concurrently if they're large enough)
+                compile_eflint_branches(branches, wf_id, wf_user, loop_names, phrases);
                 // Continue with the next one
                 elem = next;
             },
             Elem::Parallel(ElemParallel { branches, merge: _, next }) => {
-                // Do the branches in sequence
-                for branch in branches {
-                    compile_eflint(branch, wf_id, wf_user, loop_names, phrases);
-                }
+                // Do the branches (concurrently if they're large enough)
+                compile_eflint_branches(branches, wf_id, wf_user, loop_names, phrases);
                 // Continue with the next one
                 elem = next;
             },
@@ -507,7 +590,7 @@ impl Workflow {
         let mut phrases: Vec<Phrase> = vec![];
 
         // First, we shall name all loops
-        let mut loop_names: HashMap<*const ElemLoop, String> = HashMap::new();
+        let mut loop_names: HashMap<usize, String> = HashMap::new();
         name_loops(&self.start, &self.id, &mut loop_names);
 
         // Kick off the first phrase(s) by adding the notion of the workflow as a whole
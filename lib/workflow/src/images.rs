@@ -0,0 +1,85 @@
+//! Resolves the container images that implement the tasks in a [`Workflow`].
+//!
+//! The WIR only names the package and version of every task, not the image that will actually run. That is known to
+//! the checker's state instead (see `state_resolver::State::images`), which is what should be trusted anyway: a digest
+//! claimed by whoever submits the workflow says nothing about the code that will run. This module annotates every
+//! [`ElemTask`] with the [`Image`] of its package version, so policies may reason about it.
+
+use crate::spec::{Elem, ElemBranch, ElemParallel, ElemTask, Image, PackageImage, Workflow};
+
+/***** HELPER FUNCTIONS *****/
+/// Annotates every task in an element with the image of its package version.
+///
+/// # Arguments
+/// - `elem`: The [`Elem`] to annotate.
+/// - `images`: The known [`PackageImage`]s.
+///
+/// # Returns
+/// The number of tasks for which no image was known.
+fn resolve_elem(elem: &mut Elem, images: &[PackageImage]) -> usize {
+    match elem {
+        Elem::Task(ElemTask { package, version, image, next, .. }) => {
+            *image = images.iter().find(|known| known.package == *package && known.version == *version).map(|known| known.image.clone());
+            usize::from(image.is_none()) + resolve_elem(next, images)
+        },
+        Elem::Commit(commit) => resolve_elem(&mut commit.next, images),
+
+        Elem::Branch(ElemBranch { branches, next }) | Elem::Parallel(ElemParallel { branches, next, .. }) => {
+            branches.iter_mut().map(|branch| resolve_elem(branch, images)).sum::<usize>() + resolve_elem(next, images)
+        },
+        Elem::Loop(l) => resolve_elem(&mut l.body, images) + resolve_elem(&mut l.next, images),
+
+        Elem::Next | Elem::Stop(_) => 0,
+    }
+}
+
+/// Calls a closure on the image of every task in an element that has one.
+///
+/// # Arguments
+/// - `elem`: The [`Elem`] to walk.
+/// - `f`: The closure to call with the identifier of the task and its [`Image`].
+fn for_each_image_mut_elem(elem: &mut Elem, f: &mut impl FnMut(&str, &mut Image)) {
+    match elem {
+        Elem::Task(ElemTask { id, image, next, .. }) => {
+            if let Some(image) = image {
+                f(id, image);
+            }
+            for_each_image_mut_elem(next, f)
+        },
+        Elem::Commit(commit) => for_each_image_mut_elem(&mut commit.next, f),
+
+        Elem::Branch(ElemBranch { branches, next }) | Elem::Parallel(ElemParallel { branches, next, .. }) => {
+            for branch in branches {
+                for_each_image_mut_elem(branch, f);
+            }
+            for_each_image_mut_elem(next, f)
+        },
+        Elem::Loop(l) => {
+            for_each_image_mut_elem(&mut l.body, f);
+            for_each_image_mut_elem(&mut l.next, f)
+        },
+
+        Elem::Next | Elem::Stop(_) => {},
+    }
+}
+
+/***** LIBRARY *****/
+impl Workflow {
+    /// Annotates every task in this Workflow with the container [`Image`] of its package version.
+    ///
+    /// Any image already set is replaced, such that only the given images are trusted.
+    ///
+    /// # Arguments
+    /// - `images`: The [`PackageImage`]s known to the checker.
+    ///
+    /// # Returns
+    /// The number of tasks for which no image was known (and whose image is thus left unset).
+    pub fn resolve_images(&mut self, images: &[PackageImage]) -> usize { resolve_elem(&mut self.start, images) }
+
+    /// Calls a closure on the image of every task in this Workflow that has one (e.g., to verify its signatures).
+    ///
+    /// # Arguments
+    /// - `f`: The closure to call with the identifier of the task and its [`Image`].
+    #[inline]
+    pub fn for_each_image_mut(&mut self, mut f: impl FnMut(&str, &mut Image)) { for_each_image_mut_elem(&mut self.start, &mut f) }
+}
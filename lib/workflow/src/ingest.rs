@@ -0,0 +1,251 @@
+//! Reads WIR workflows written by other versions of Brane than the one we are compiled against.
+//!
+//! The WIR's schema changes every now and then, while the checker is typically deployed separately from the Brane
+//! instance(s) that send it workflows. Rather than failing deep inside the compilation with whatever `serde` has to
+//! say about it, this module first detects the [`WirVersion`] of a workflow in its raw JSON form and upgrades it to the
+//! schema we understand where that is feasible. Features that cannot be upgraded are reported as such.
+//!
+//! Currently, the following versions are recognised:
+//! - [`WirVersion::Legacy`]: WIRs from before workflows carried an identifier, a user and metadata. Their symbol tables
+//!   are scoped lists (`{ "d": [...], "o": <offset> }`) and their [`ast::Edge::Node`]s carry no metadata.
+//! - [`WirVersion::Current`]: WIRs matching the `brane-ast` we are compiled against.
+//! - [`WirVersion::Newer`]: WIRs with fields or edges we don't know. These are read as-is, and refused if that fails.
+
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+
+use brane_ast::ast;
+use log::{debug, warn};
+use rand::Rng as _;
+use rand::distributions::Alphanumeric;
+use serde_json::{Map, Value};
+
+/***** CONSTANTS *****/
+/// The top-level fields of a [`WirVersion::Current`] workflow.
+const CURRENT_FIELDS: [&str; 6] = ["id", "table", "metadata", "user", "graph", "funcs"];
+/// The top-level fields that [`WirVersion::Legacy`] workflows lack.
+const LEGACY_MISSING_FIELDS: [&str; 3] = ["id", "metadata", "user"];
+/// The symbol tables in a workflow's table.
+const TABLE_LISTS: [&str; 4] = ["funcs", "tasks", "classes", "vars"];
+/// The kinds of [`ast::Edge`]s we know.
+const EDGE_KINDS: [&str; 9] = ["lin", "nod", "stp", "brc", "par", "join", "loop", "cll", "ret"];
+
+/***** ERRORS *****/
+/// Defines errors that may occur when ingesting a WIR workflow.
+#[derive(Debug)]
+pub enum Error {
+    /// The given workflow is not a JSON object.
+    NotAnObject { got: &'static str },
+    /// A required field is missing from the given workflow.
+    MissingField { version: WirVersion, field: &'static str },
+    /// A symbol table of a legacy workflow is not the top-level scope.
+    UnsupportedTableOffset { table: &'static str, offset: Value },
+    /// An edge of a kind we don't know was found.
+    UnsupportedEdge { version: WirVersion, func: String, index: usize, kind: String },
+    /// The (upgraded) workflow could not be deserialized.
+    Deserialize { version: WirVersion, err: serde_json::Error },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            NotAnObject { got } => write!(f, "Given WIR workflow is not an object, but {got}"),
+            MissingField { version, field } => write!(f, "Given {version} WIR workflow is missing required field '{field}'"),
+            UnsupportedTableOffset { table, offset } => write!(
+                f,
+                "Cannot upgrade legacy WIR workflow with symbol table '{table}' at offset {offset} (only top-level tables at offset 0 are supported)"
+            ),
+            UnsupportedEdge { version, func, index, kind } => {
+                write!(f, "Given {version} WIR workflow has an unsupported edge of kind '{kind}' at {func}:{index}")
+            },
+            Deserialize { version, .. } => write!(f, "Failed to read given {version} WIR workflow"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            NotAnObject { .. } | MissingField { .. } | UnsupportedTableOffset { .. } | UnsupportedEdge { .. } => None,
+            Deserialize { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// The versions of the WIR schema we can tell apart.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WirVersion {
+    /// WIRs from before workflows carried an identifier, a user and metadata.
+    Legacy,
+    /// WIRs matching the `brane-ast` we are compiled against.
+    Current,
+    /// WIRs with fields we don't know, presumably from a newer Brane.
+    Newer,
+}
+impl Display for WirVersion {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Legacy => write!(f, "legacy"),
+            Self::Current => write!(f, "current"),
+            Self::Newer => write!(f, "newer"),
+        }
+    }
+}
+
+/***** HELPER FUNCTIONS *****/
+/// Returns a human-friendly name for the type of a [`Value`].
+fn value_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Calls a closure for every edge in the given workflow, in the main graph and in all function bodies.
+///
+/// # Arguments
+/// - `wir`: The workflow to iterate over.
+/// - `edge`: The closure to call with the function name (`<main>` for the main graph), the edge's index and the edge itself.
+///
+/// # Errors
+/// This function errors if the closure errors.
+fn for_each_edge(wir: &mut Map<String, Value>, mut edge: impl FnMut(&str, usize, &mut Map<String, Value>) -> Result<(), Error>) -> Result<(), Error> {
+    if let Some(Value::Array(graph)) = wir.get_mut("graph") {
+        for (i, e) in graph.iter_mut().enumerate() {
+            if let Value::Object(e) = e {
+                edge("<main>", i, e)?;
+            }
+        }
+    }
+    if let Some(Value::Object(funcs)) = wir.get_mut("funcs") {
+        for (func, body) in funcs.iter_mut() {
+            if let Value::Array(body) = body {
+                for (i, e) in body.iter_mut().enumerate() {
+                    if let Value::Object(e) = e {
+                        edge(func, i, e)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Upgrades a [`WirVersion::Legacy`] workflow to a [`WirVersion::Current`] one.
+///
+/// # Arguments
+/// - `wir`: The workflow to upgrade in-place.
+///
+/// # Errors
+/// This function errors if the workflow uses features that cannot be upgraded.
+fn upgrade_legacy(wir: &mut Map<String, Value>) -> Result<(), Error> {
+    // Legacy workflows don't have an identity yet, so make one up
+    if !wir.contains_key("id") {
+        let id: String = rand::thread_rng().sample_iter(Alphanumeric).take(8).map(char::from).collect();
+        debug!("Assigning identifier 'workflow-{id}' to legacy WIR workflow");
+        wir.insert("id".into(), Value::String(format!("workflow-{id}")));
+    }
+    wir.entry("user").or_insert(Value::Null);
+    wir.entry("metadata").or_insert_with(|| Value::Array(vec![]));
+
+    // Flatten the scoped symbol tables to the plain lists they are now
+    let Some(Value::Object(table)) = wir.get_mut("table") else {
+        return Err(Error::MissingField { version: WirVersion::Legacy, field: "table" });
+    };
+    for name in TABLE_LISTS {
+        let Some(list) = table.get_mut(name) else { continue };
+        if let Value::Object(scoped) = list {
+            let offset: Value = scoped.get("o").cloned().unwrap_or(Value::from(0));
+            if offset != Value::from(0) {
+                return Err(Error::UnsupportedTableOffset { table: name, offset });
+            }
+            *list = scoped.remove("d").unwrap_or_else(|| Value::Array(vec![]));
+        }
+    }
+
+    // Nodes did not carry metadata yet
+    for_each_edge(wir, |_, _, edge| {
+        if edge.get("kind").and_then(Value::as_str) == Some("nod") {
+            edge.entry("m").or_insert_with(|| Value::Array(vec![]));
+        }
+        Ok(())
+    })
+}
+
+/***** LIBRARY *****/
+/// Detects the [`WirVersion`] of a WIR workflow in its raw JSON form.
+///
+/// # Arguments
+/// - `wir`: The workflow to inspect.
+///
+/// # Returns
+/// The [`WirVersion`] it most likely is.
+///
+/// # Errors
+/// This function errors if the workflow is not a JSON object.
+pub fn detect(wir: &Value) -> Result<WirVersion, Error> {
+    let Value::Object(wir) = wir else {
+        return Err(Error::NotAnObject { got: value_kind(wir) });
+    };
+    if wir.keys().any(|key| !CURRENT_FIELDS.contains(&key.as_str())) {
+        Ok(WirVersion::Newer)
+    } else if LEGACY_MISSING_FIELDS.iter().any(|field| !wir.contains_key(*field)) {
+        Ok(WirVersion::Legacy)
+    } else {
+        Ok(WirVersion::Current)
+    }
+}
+
+/// Upgrades a WIR workflow in its raw JSON form to the schema of the `brane-ast` we are compiled against.
+///
+/// # Arguments
+/// - `wir`: The workflow to upgrade in-place.
+///
+/// # Returns
+/// The [`WirVersion`] the workflow was in before upgrading.
+///
+/// # Errors
+/// This function errors if the workflow is not an object, or if it uses features that we cannot upgrade or don't know.
+pub fn upgrade(wir: &mut Value) -> Result<WirVersion, Error> {
+    let version: WirVersion = detect(wir)?;
+    let Value::Object(map) = wir else { unreachable!() };
+    match version {
+        WirVersion::Legacy => upgrade_legacy(map)?,
+        WirVersion::Current => {},
+        WirVersion::Newer => {
+            let unknown: Vec<&String> = map.keys().filter(|key| !CURRENT_FIELDS.contains(&key.as_str())).collect();
+            warn!("Given WIR workflow has unknown fields {unknown:?}; attempting to read it anyway");
+        },
+    }
+
+    // Refuse edges we don't know explicitly, instead of whatever serde would make of them
+    for_each_edge(map, |func, index, edge| match edge.get("kind").and_then(Value::as_str) {
+        Some(kind) if !EDGE_KINDS.contains(&kind) => Err(Error::UnsupportedEdge { version, func: func.into(), index, kind: kind.into() }),
+        _ => Ok(()),
+    })?;
+    Ok(version)
+}
+
+/// Reads a WIR workflow of any supported [`WirVersion`].
+///
+/// # Arguments
+/// - `wir`: The workflow in its raw JSON form.
+///
+/// # Returns
+/// The parsed [`ast::Workflow`].
+///
+/// # Errors
+/// This function errors if the workflow could not be [upgraded](upgrade()), or if the result could not be read.
+pub fn ingest(mut wir: Value) -> Result<ast::Workflow, Error> {
+    let version: WirVersion = upgrade(&mut wir)?;
+    if version != WirVersion::Current {
+        debug!("Reading {version} WIR workflow");
+    }
+    serde_json::from_value(wir).map_err(|err| Error::Deserialize { version, err })
+}
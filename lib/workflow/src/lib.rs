@@ -13,13 +13,22 @@
 //
 
 // Declare the subsubmodules
+pub mod canonical;
+#[cfg(feature = "wir")]
 pub mod compile;
+pub mod diagnostics;
 #[cfg(feature = "eflint")]
 pub mod eflint;
+pub mod images;
+#[cfg(feature = "wir")]
+pub mod ingest;
+pub mod lint;
+#[cfg(feature = "wir")]
 pub mod optimize;
+#[cfg(feature = "wir")]
 pub mod preprocess;
 pub mod spec;
-#[cfg(test)]
+#[cfg(all(test, feature = "wir"))]
 pub mod tests;
 pub mod utils;
 pub mod visualize;
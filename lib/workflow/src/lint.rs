@@ -0,0 +1,318 @@
+//! Checks a [`Workflow`] for constructs that are probably mistakes, before it is deliberated upon.
+//!
+//! Where [`diagnostics`](crate::diagnostics) only reports what makes a check weaker, linting is about the workflow
+//! itself: tasks that are never planned, datasets that come from nowhere, parts of the graph that can never be reached
+//! and results that nobody receives. Every [`LintRule`] can be configured to be ignored, to warn or to deny the
+//! workflow with a [`LintConfig`], and [`Workflow::lint()`] returns a machine-readable [`Finding`] for everything it finds.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::spec::{Dataset, Elem, ElemBranch, ElemCommit, ElemParallel, ElemTask, Workflow};
+use crate::utils::{WorkflowVisitor, walk_workflow_preorder};
+
+/***** HELPER FUNCTIONS *****/
+/// Checks whether executing an element can reach the end of its (sub)graph, i.e., whether it does not always stop the workflow.
+///
+/// # Arguments
+/// - `elem`: The [`Elem`] to check.
+///
+/// # Returns
+/// True if the [`Elem::Next`] at the end of `elem` can be reached, false if every path through it ends in an [`Elem::Stop`].
+fn falls_through(elem: &Elem) -> bool {
+    match elem {
+        Elem::Task(ElemTask { next, .. }) | Elem::Commit(ElemCommit { next, .. }) => falls_through(next),
+        // One of the branches is taken, so at least one of them has to fall through
+        Elem::Branch(ElemBranch { branches, next }) => branches.iter().any(falls_through) && falls_through(next),
+        // All of the branches are taken, so all of them have to fall through
+        Elem::Parallel(ElemParallel { branches, next, .. }) => branches.iter().all(falls_through) && falls_through(next),
+        // The body may be taken zero times
+        Elem::Loop(l) => falls_through(&l.next),
+        Elem::Next => true,
+        Elem::Stop(_) => false,
+    }
+}
+
+/// Finds the identifier of the first task or commit in an element, if any.
+fn first_node(elem: &Elem) -> Option<&str> {
+    match elem {
+        Elem::Task(task) => Some(&task.id),
+        Elem::Commit(commit) => Some(&commit.id),
+        Elem::Branch(ElemBranch { branches, next }) | Elem::Parallel(ElemParallel { branches, next, .. }) => {
+            branches.iter().find_map(first_node).or_else(|| first_node(next))
+        },
+        Elem::Loop(l) => first_node(&l.body).or_else(|| first_node(&l.next)),
+        Elem::Next | Elem::Stop(_) => None,
+    }
+}
+
+/***** HELPERS *****/
+/// Collects the [`Finding`]s of every element in a [`Workflow`].
+struct Linter<'c> {
+    /// The configuration that decides which rules to run.
+    config:   &'c LintConfig,
+    /// The findings so far.
+    findings: Vec<Finding>,
+    /// The outputs of all tasks, with the task that produced them.
+    outputs:  Vec<(String, String)>,
+    /// The names of all datasets used by tasks or commits, or returned by the workflow.
+    used:     HashSet<String>,
+}
+impl<'c> Linter<'c> {
+    /// Adds a finding for the given rule, unless it is disabled.
+    fn report(&mut self, rule: LintRule, node: Option<&str>, message: String) {
+        let level: LintLevel = self.config.level(rule);
+        if level != LintLevel::Allow {
+            self.findings.push(Finding { rule, level, node: node.map(String::from), message });
+        }
+    }
+
+    /// Reports the elements after a branch or parallel edge as unreachable, unless there are none.
+    fn unreachable(&mut self, kind: &str, next: &Elem) {
+        if matches!(next, Elem::Next | Elem::Stop(_)) {
+            return;
+        }
+        let node: Option<&str> = first_node(next);
+        self.report(
+            LintRule::UnreachableBranch,
+            node,
+            format!("The elements after a {kind}{} can never be reached, because the workflow always stops before", match node {
+                Some(node) => format!(" (starting at '{node}')"),
+                None => String::new(),
+            }),
+        );
+    }
+}
+impl<'c> WorkflowVisitor for Linter<'c> {
+    fn visit_task(&mut self, task: &ElemTask) {
+        if task.location.is_none() {
+            self.report(LintRule::TaskWithoutLocation, Some(&task.id), format!("Task '{}' ({}) is not planned on any location", task.id, task.name));
+        }
+        for input in &task.input {
+            if input.from.is_none() && task.location.is_none() {
+                self.report(
+                    LintRule::DatasetWithoutSource,
+                    Some(&task.id),
+                    format!("Input dataset '{}' of task '{}' has no known source", input.name, task.id),
+                );
+            }
+            self.used.insert(input.name.clone());
+        }
+        if let Some(output) = &task.output {
+            self.outputs.push((task.id.clone(), output.name.clone()));
+        }
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        for input in &commit.input {
+            if input.from.is_none() {
+                self.report(
+                    LintRule::DatasetWithoutSource,
+                    Some(&commit.id),
+                    format!("Input dataset '{}' of commit '{}' has no known source", input.name, commit.id),
+                );
+            }
+            self.used.insert(input.name.clone());
+        }
+    }
+
+    fn visit_branch(&mut self, branch: &ElemBranch) {
+        if !branch.branches.iter().any(falls_through) {
+            self.unreachable("branch", &branch.next);
+        }
+    }
+
+    fn visit_parallel(&mut self, parallel: &ElemParallel) {
+        if !parallel.branches.iter().all(falls_through) {
+            self.unreachable("parallel", &parallel.next);
+        }
+    }
+
+    fn visit_stop(&mut self, stop: &HashSet<Dataset>) { self.used.extend(stop.iter().map(|data| data.name.clone())); }
+}
+
+/***** LIBRARY *****/
+/// Defines the checks that [`Workflow::lint()`] performs.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    /// A task is not planned on any location.
+    TaskWithoutLocation,
+    /// An input dataset of a task or commit has no known source.
+    DatasetWithoutSource,
+    /// Part of the workflow can never be reached, because every path before it stops the workflow.
+    UnreachableBranch,
+    /// A task produces a result that is never used, committed or returned, and is thus lost.
+    ResultWithoutRecipient,
+}
+impl LintRule {
+    /// All the rules, in the order in which they are documented.
+    pub const ALL: [Self; 4] = [Self::TaskWithoutLocation, Self::DatasetWithoutSource, Self::UnreachableBranch, Self::ResultWithoutRecipient];
+}
+impl Display for LintRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::TaskWithoutLocation => write!(f, "task-without-location"),
+            Self::DatasetWithoutSource => write!(f, "dataset-without-source"),
+            Self::UnreachableBranch => write!(f, "unreachable-branch"),
+            Self::ResultWithoutRecipient => write!(f, "result-without-recipient"),
+        }
+    }
+}
+impl FromStr for LintRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|rule| rule.to_string() == s).ok_or_else(|| {
+            format!(
+                "Unknown lint rule '{s}' (expected one of {})",
+                Self::ALL.iter().map(|rule| format!("'{rule}'")).collect::<Vec<String>>().join(", ")
+            )
+        })
+    }
+}
+
+/// Defines what happens when a [`LintRule`] finds something.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintLevel {
+    /// The rule is not run.
+    Allow,
+    /// The finding is reported, but the workflow is still accepted.
+    #[default]
+    Warn,
+    /// The finding is reported, and the workflow is refused.
+    Deny,
+}
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warn"),
+            Self::Deny => write!(f, "deny"),
+        }
+    }
+}
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            raw => Err(format!("Unknown lint level '{raw}' (expected 'allow', 'warn' or 'deny')")),
+        }
+    }
+}
+
+/// Sets the [`LintLevel`] of a single [`LintRule`], as given on the command line (`<RULE>=<LEVEL>`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LintSetting {
+    /// The rule to configure.
+    pub rule:  LintRule,
+    /// The level to run it at.
+    pub level: LintLevel,
+}
+impl FromStr for LintSetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((rule, level)) = s.split_once('=') else {
+            return Err(format!("Invalid lint setting '{s}' (expected '<RULE>=<LEVEL>')"));
+        };
+        Ok(Self { rule: rule.trim().parse()?, level: level.trim().parse()? })
+    }
+}
+
+/// Configures which [`LintRule`]s are run, and at what [`LintLevel`].
+///
+/// The default LintConfig runs all rules at [`LintLevel::Warn`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct LintConfig {
+    /// The levels of the rules that deviate from the default.
+    #[serde(default)]
+    pub levels: HashMap<LintRule, LintLevel>,
+}
+impl LintConfig {
+    /// Returns the level at which a rule is run.
+    #[inline]
+    pub fn level(&self, rule: LintRule) -> LintLevel { self.levels.get(&rule).copied().unwrap_or_default() }
+
+    /// Sets the level at which a rule is run.
+    ///
+    /// # Arguments
+    /// - `rule`: The [`LintRule`] to configure.
+    /// - `level`: The [`LintLevel`] to run it at.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with(mut self, rule: LintRule, level: LintLevel) -> Self {
+        self.levels.insert(rule, level);
+        self
+    }
+}
+impl FromIterator<LintSetting> for LintConfig {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = LintSetting>>(iter: T) -> Self {
+        Self { levels: iter.into_iter().map(|setting| (setting.rule, setting.level)).collect() }
+    }
+}
+
+/// Something found by a [`LintRule`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Finding {
+    /// The rule that found it.
+    pub rule:    LintRule,
+    /// The level at which the rule was run.
+    pub level:   LintLevel,
+    /// The identifier of the task or commit it was found at, if any.
+    pub node:    Option<String>,
+    /// A human-readable description of what was found.
+    pub message: String,
+}
+impl Display for Finding {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { write!(f, "{}[{}]: {}", self.level, self.rule, self.message) }
+}
+
+impl Workflow {
+    /// Lints this Workflow, i.e., looks for constructs in it that are probably mistakes.
+    ///
+    /// # Arguments
+    /// - `config`: The [`LintConfig`] that decides which rules to run.
+    ///
+    /// # Returns
+    /// A list of [`Finding`]s, in the order in which their elements occur in the workflow (except for those of
+    /// [`LintRule::ResultWithoutRecipient`], which come last). Empty if nothing was found.
+    pub fn lint(&self, config: &LintConfig) -> Vec<Finding> {
+        let mut linter = Linter { config, findings: vec![], outputs: vec![], used: HashSet::new() };
+        walk_workflow_preorder(&self.start, &mut linter);
+
+        // Only now do we know which results are used anywhere
+        for (node, output) in std::mem::take(&mut linter.outputs) {
+            if !linter.used.contains(&output) {
+                linter.report(
+                    LintRule::ResultWithoutRecipient,
+                    Some(&node),
+                    format!("Result '{output}' of task '{node}' is never used, committed or returned, and will be lost"),
+                );
+            }
+        }
+        linter.findings
+    }
+}
+
+/// Checks whether any of the given findings should cause the workflow to be refused.
+///
+/// # Arguments
+/// - `findings`: The [`Finding`]s as returned by [`Workflow::lint()`].
+///
+/// # Returns
+/// True if any of them was found by a rule at [`LintLevel::Deny`].
+#[inline]
+pub fn denies(findings: &[Finding]) -> bool { findings.iter().any(|finding| finding.level == LintLevel::Deny) }
@@ -11,16 +11,157 @@
 //  Description:
 //!   Optimizes a [`Workflow`] by aggregating elements that we can
 //!   aggregate.
+//!
+//!   Also optimizes the WIR it is compiled from by folding constant
+//!   conditions and skipping the branches they make dead.
 //
 
 use std::collections::HashSet;
+use std::sync::Arc;
 
+use brane_ast::ast::{self, Edge, EdgeInstr};
 use log::debug;
 use transform::Transform as _;
 
 use super::spec::{Elem, ElemBranch, Workflow};
 
 /***** HELPER FUNCTIONS *****/
+/// Collects the indices of edges in a WIR edge list that the compiler uses as breakpoints.
+///
+/// Jumps may never be redirected past these edges, or the compiler would walk beyond the end of a branch, parallel or loop body.
+///
+/// # Arguments
+/// - `edges`: The list of [`Edge`]s to analyse.
+///
+/// # Returns
+/// A [`HashSet`] with the indices of edges that must stay reachable as they are.
+fn wir_breakpoints(edges: &[Edge]) -> HashSet<usize> {
+    let mut breakpoints: HashSet<usize> = HashSet::new();
+    for edge in edges {
+        match edge {
+            Edge::Branch { merge: Some(merge), .. } | Edge::Parallel { merge, .. } => {
+                breakpoints.insert(*merge);
+            },
+            Edge::Loop { cond, body, .. } => {
+                breakpoints.insert(*cond);
+                breakpoints.insert(body.saturating_sub(1));
+            },
+            _ => {},
+        }
+    }
+    breakpoints
+}
+
+/// Checks whether a list of instructions jumps locally, i.e., whether offsets into it matter.
+#[inline]
+fn has_local_jumps(instrs: &[EdgeInstr]) -> bool {
+    instrs.iter().any(|instr| matches!(instr, EdgeInstr::Branch { .. } | EdgeInstr::BranchNot { .. }))
+}
+
+/// Folds constant boolean expressions in a list of instructions.
+///
+/// Lists with local jumps in them are left alone, since folding changes the offsets they jump to.
+///
+/// # Arguments
+/// - `instrs`: The list of [`EdgeInstr`]s to fold.
+///
+/// # Returns
+/// Whether or not anything was folded.
+fn fold_instrs(instrs: &mut Vec<EdgeInstr>) -> bool {
+    if has_local_jumps(instrs) {
+        return false;
+    }
+
+    let mut changed: bool = false;
+    let mut i: usize = 0;
+    while i < instrs.len() {
+        // Unary operators on a constant
+        if i >= 1 {
+            if let (EdgeInstr::Boolean { value }, EdgeInstr::Not {}) = (&instrs[i - 1], &instrs[i]) {
+                let value: bool = !*value;
+                instrs.splice(i - 1..=i, [EdgeInstr::Boolean { value }]);
+                changed = true;
+                i -= 1;
+                continue;
+            }
+        }
+
+        // Binary operators on two constants
+        if i >= 2 {
+            if let (EdgeInstr::Boolean { value: lhs }, EdgeInstr::Boolean { value: rhs }) = (&instrs[i - 2], &instrs[i - 1]) {
+                let value: Option<bool> = match &instrs[i] {
+                    EdgeInstr::And {} => Some(*lhs && *rhs),
+                    EdgeInstr::Or {} => Some(*lhs || *rhs),
+                    EdgeInstr::Eq {} => Some(*lhs == *rhs),
+                    EdgeInstr::Ne {} => Some(*lhs != *rhs),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    instrs.splice(i - 2..=i, [EdgeInstr::Boolean { value }]);
+                    changed = true;
+                    i -= 2;
+                    continue;
+                }
+            }
+        }
+
+        i += 1;
+    }
+    if changed {
+        debug!("Applied optimization: constant folding");
+    }
+    changed
+}
+
+/// Runs one round of the WIR optimizations over a single list of edges.
+///
+/// # Arguments
+/// - `edges`: The list of [`Edge`]s to optimize. Edges are never removed (their indices are referenced elsewhere), but some may become unreachable.
+///
+/// # Returns
+/// Whether or not an optimization occurred. This can be used to saturate them while possible.
+fn optimize_edges(edges: &mut [Edge]) -> bool {
+    let breakpoints: HashSet<usize> = wir_breakpoints(edges);
+
+    let mut changed: bool = false;
+    for i in 0..edges.len() {
+        let (mut instrs, mut next): (Vec<EdgeInstr>, usize) = match &edges[i] {
+            Edge::Linear { instrs, next } => (instrs.clone(), *next),
+            _ => continue,
+        };
+        let mut edge_changed: bool = fold_instrs(&mut instrs);
+
+        // Merge the linear edge following this one into it
+        if next != i && !breakpoints.contains(&next) {
+            if let Some(Edge::Linear { instrs: next_instrs, next: next_next }) = edges.get(next) {
+                debug!("Applied optimization: linear edge merging");
+                instrs.extend(next_instrs.iter().cloned());
+                next = *next_next;
+                edge_changed = true;
+            }
+        }
+
+        // Skip the branch following this one if we pushed its condition as a constant
+        if !breakpoints.contains(&next) && !has_local_jumps(&instrs) {
+            if let (Some(EdgeInstr::Boolean { value }), Some(Edge::Branch { true_next, false_next, merge })) = (instrs.last(), edges.get(next)) {
+                let target: Option<usize> = if *value { Some(*true_next) } else { false_next.or(*merge) };
+                if let Some(target) = target {
+                    debug!("Applied optimization: dead branch elimination");
+                    instrs.pop();
+                    next = target;
+                    edge_changed = true;
+                }
+            }
+        }
+
+        if edge_changed {
+            edges[i] = Edge::Linear { instrs, next };
+            changed = true;
+        }
+    }
+    changed
+}
+
 /// Attempts to optimize the given branch of [`Elem`]s.
 ///
 /// # Arguments
@@ -31,7 +172,11 @@ use super::spec::{Elem, ElemBranch, Workflow};
 fn optimize_elem(elem: Elem) -> (bool, Elem) {
     // Match on the element
     match elem {
-        Elem::Task(task) => (false, Elem::Task(task)),
+        Elem::Task(mut task) => {
+            let (changed, next) = optimize_elem(*task.next);
+            task.next = Box::new(next);
+            (changed, Elem::Task(task))
+        },
 
         Elem::Branch(mut branch) => {
             // Recurse into all branches with transpose to be able to remove or merge them
@@ -107,9 +252,39 @@ fn optimize_elem(elem: Elem) -> (bool, Elem) {
                 (changed | next_changed, Elem::Branch(branch))
             }
         },
-        Elem::Parallel(parallel) => (false, Elem::Parallel(parallel)),
-        Elem::Loop(l) => (false, Elem::Loop(l)),
-        Elem::Commit(commit) => (false, Elem::Commit(commit)),
+        Elem::Parallel(mut parallel) => {
+            let mut changed: bool = false;
+            parallel.branches = parallel
+                .branches
+                .drain(..)
+                .map(|b| {
+                    let (b_changed, b) = optimize_elem(b);
+                    changed |= b_changed;
+                    b
+                })
+                .collect();
+            let (next_changed, next) = optimize_elem(*parallel.next);
+            parallel.next = Box::new(next);
+            (changed | next_changed, Elem::Parallel(parallel))
+        },
+        Elem::Loop(mut l) => {
+            let (body_changed, body) = optimize_elem(*l.body);
+            let (next_changed, next) = optimize_elem(*l.next);
+            l.body = Box::new(body);
+            l.next = Box::new(next);
+            (body_changed | next_changed, Elem::Loop(l))
+        },
+        Elem::Commit(mut commit) => {
+            // Committing nothing, or a dataset to its own name, doesn't promote anything
+            if commit.input.iter().all(|data| data.name == commit.data_name) {
+                debug!("Applied optimization: no-op commit pruning");
+                let (_, next) = optimize_elem(*commit.next);
+                return (true, next);
+            }
+            let (changed, next) = optimize_elem(*commit.next);
+            commit.next = Box::new(next);
+            (changed, Elem::Commit(commit))
+        },
 
         Elem::Next => (false, Elem::Next),
         Elem::Stop(returns) => (false, Elem::Stop(returns)),
@@ -117,8 +292,23 @@ fn optimize_elem(elem: Elem) -> (bool, Elem) {
 }
 
 /***** LIBRARY *****/
+/// Optimizes a (preprocessed) WIR workflow before it is compiled to a [`Workflow`].
+///
+/// Specifically, this folds constant boolean expressions, merges consecutive linear edges and skips branches whose condition is known
+/// statically. Tasks in branches that are never taken thus never make it to the checker workflow.
+///
+/// # Arguments
+/// - `wir`: The [`ast::Workflow`] to optimize in-place.
+pub fn optimize_wir(wir: &mut ast::Workflow) {
+    while optimize_edges(Arc::make_mut(&mut wir.graph)) {}
+    for edges in Arc::make_mut(&mut wir.funcs).values_mut() {
+        while optimize_edges(edges) {}
+    }
+}
+
 impl Workflow {
-    /// Optimizes the workflow graph by pruning elements which do task-independent things (like branching without tasks) and aggregates aggregatable edges.
+    /// Optimizes the workflow graph by pruning elements which do task-independent things (like branching without tasks or committing
+    /// nothing) and aggregates aggregatable edges.
     pub fn optimize(&mut self) {
         let Self { start, .. } = self;
 
@@ -28,6 +28,7 @@ use enum_debug::EnumDebug as _;
 use log::{debug, trace};
 
 use super::utils;
+use crate::diagnostics::InlineStats;
 
 /***** TESTS *****/
 #[cfg(test)]
@@ -194,7 +195,7 @@ mod tests {
 
             // Alright preprocess it
             let wir: Workflow = match simplify(wir) {
-                Ok((wir, _)) => wir,
+                Ok((wir, ..)) => wir,
                 Err(err) => {
                     panic!("Failed to preprocess WIR: {err}");
                 },
@@ -217,6 +218,17 @@ pub enum Error {
     UnknownFunc { id: FunctionId },
     /// A [`Call`](ast::Edge::Call)-edge was encountered while we didn't know of a function ID on the stack.
     CallingWithoutId { pc: ResolvedProgramCounter },
+    /// The workflow has more edges than allowed.
+    TooManyEdges { edges: usize, max: usize },
+    /// Inlining the functions of the workflow would grow it to more edges than allowed.
+    InlinedTooLarge { edges: usize, max: usize },
+    /// The workflow has more loops than allowed after inlining.
+    TooManyLoops { loops: usize, max: usize },
+}
+impl Error {
+    /// Returns whether this error is about a workflow exceeding the [`WorkflowLimits`], rather than about it being incoherent.
+    #[inline]
+    pub fn exceeds_limits(&self) -> bool { matches!(self, Self::TooManyEdges { .. } | Self::InlinedTooLarge { .. } | Self::TooManyLoops { .. }) }
 }
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
@@ -225,6 +237,11 @@ impl Display for Error {
             UnknownTask { id } => write!(f, "Encountered unknown task ID {id} in Node"),
             UnknownFunc { id } => write!(f, "Encountered unknown function ID {id} in Call"),
             CallingWithoutId { pc } => write!(f, "Attempted to call function at {pc} without statically known task ID on the stack"),
+            TooManyEdges { edges, max } => write!(f, "Workflow has {edges} edges, but at most {max} are allowed"),
+            InlinedTooLarge { edges, max } => {
+                write!(f, "Inlining the functions of the workflow grows it to at least {edges} edges, but at most {max} are allowed")
+            },
+            TooManyLoops { loops, max } => write!(f, "Workflow has {loops} loops after inlining its functions, but at most {max} are allowed"),
         }
     }
 }
@@ -677,6 +694,16 @@ fn keep_unique_first(data: &mut Vec<usize>) {
     });
 }
 
+/// Finds where an edge originally came from.
+///
+/// # Arguments
+/// - `origins`: The [`Origins`] of the edges inlined so far.
+/// - `pc`: The [`ProgramCounter`] of the edge.
+///
+/// # Returns
+/// The origin of the edge, which is just itself if it was not inlined.
+fn origin_of(origins: &Origins, pc: ProgramCounter) -> Vec<ProgramCounter> { origins.get(&pc).cloned().unwrap_or_else(|| vec![pc]) }
+
 /// Traverses the given function body and replaces all [`Edge::Return`] with an [`Edge::Linear`] pointing to the given edge index.
 ///
 /// Also bumps definition pointers with the given values. This is necessary because we need to pull function scopes one layer up.
@@ -822,40 +849,47 @@ fn prep_func_body(
 /// # Arguments
 /// - `body`: A [WIR](Workflow) function body to inline functions _in_.
 /// - `calls`: The map of call indices to which function is actually called.
+/// - `origins`: The map of inlined edges to their [`Origins`], which we update with the edges inlined in `body`.
 /// - `funcs`: A map of call IDs to function bodies ready to be substituted in the `body`.
 /// - `inlinable`: A collection of functions that determines if functions are inlinable. If the set of `deps` is [`Some`], it's inlinable; else it's not.
 /// - `func_id`: The ID of the function we're inlining.
 /// - `pc`: Points to the current [`Edge`] to analyse.
+/// - `max_edges`: The maximum number of edges the `body` may grow to by inlining.
 /// - `breakpoint`: If given, then analysis should stop when this PC is hit.
+///
+/// # Errors
+/// This function errors if inlining would grow the `body` beyond `max_edges`.
 // It's a compiler function, too many arguments are kinda its thing :P No it's not worth it to come up with structs for this.
 #[allow(clippy::too_many_arguments)]
 fn inline_funcs_in_body(
     body: &mut Vec<Edge>,
     calls: &mut HashMap<ProgramCounter, usize>,
+    origins: &mut Origins,
     funcs: &HashMap<usize, Vec<Edge>>,
     inlinable: &HashMap<usize, Option<HashSet<usize>>>,
     func_id: FunctionId,
     pc: usize,
+    max_edges: usize,
     breakpoint: Option<usize>,
-) {
+) -> Result<(), Error> {
     // Stop on the breakpoint
     if let Some(breakpoint) = breakpoint {
         if pc == breakpoint {
-            return;
+            return Ok(());
         }
     }
     // Attempt to get the edge
     let body_len: usize = body.len();
     let edge: &mut Edge = match body.get_mut(pc) {
         Some(edge) => edge,
-        None => return,
+        None => return Ok(()),
     };
 
     // Match on its kind
     match edge {
         Edge::Node { next, .. } | Edge::Linear { next, .. } => {
             let next: usize = *next;
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, next, breakpoint)
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, next, max_edges, breakpoint)?
         },
 
         Edge::Stop {} => (),
@@ -864,14 +898,14 @@ fn inline_funcs_in_body(
             let (true_next, false_next, merge): (usize, Option<usize>, Option<usize>) = (*true_next, *false_next, *merge);
 
             // Analyse the left branch...
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, true_next, merge);
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, true_next, max_edges, merge)?;
             // ...the right branch...
             if let Some(false_next) = false_next {
-                inline_funcs_in_body(body, calls, funcs, inlinable, func_id, false_next, merge)
+                inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, false_next, max_edges, merge)?
             }
             // ...and the merge!
             if let Some(merge) = merge {
-                inline_funcs_in_body(body, calls, funcs, inlinable, func_id, merge, breakpoint)
+                inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, merge, max_edges, breakpoint)?
             }
         },
 
@@ -880,28 +914,28 @@ fn inline_funcs_in_body(
 
             // Collect all the branches
             for branch in branches {
-                inline_funcs_in_body(body, calls, funcs, inlinable, func_id, branch, Some(merge));
+                inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, branch, max_edges, Some(merge))?;
             }
 
             // Run merge and done is Cees
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, merge, breakpoint);
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, merge, max_edges, breakpoint)?;
         },
 
         Edge::Join { next, .. } => {
             let next: usize = *next;
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, next, breakpoint)
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, next, max_edges, breakpoint)?
         },
 
         Edge::Loop { cond, body: lbody, next } => {
             let (cond, lbody, next): (usize, usize, Option<usize>) = (*cond, *lbody, *next);
 
             // Traverse the condition...
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, cond, Some(lbody - 1));
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, cond, max_edges, Some(lbody - 1))?;
             // ...the body...
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, lbody, Some(cond));
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, lbody, max_edges, Some(cond))?;
             // ...and finally, the next step, if any
             if let Some(next) = next {
-                inline_funcs_in_body(body, calls, funcs, inlinable, func_id, next, breakpoint);
+                inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, next, max_edges, breakpoint)?;
             }
         },
 
@@ -920,11 +954,17 @@ fn inline_funcs_in_body(
             if inlinable.get(&call_id).map(|deps| deps.is_none()).unwrap_or(true) {
                 // Simply skip after doing the next
                 trace!("Not inlining function call to function {call_id} at {pc}");
-                inline_funcs_in_body(body, calls, funcs, inlinable, func_id, next, breakpoint);
-                return;
+                inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, next, max_edges, breakpoint)?;
+                return Ok(());
             }
             trace!("Inlining function call to function {call_id} at {pc}");
 
+            // Refuse to grow the body beyond the limit before we even copy anything
+            let edges: usize = body_len + funcs.get(&call_id).map(Vec::len).unwrap_or(0);
+            if edges > max_edges {
+                return Err(Error::InlinedTooLarge { edges, max: max_edges });
+            }
+
             // Otherwise, yank the call with a linear that refers to the inlined body instead (we'll put it after all the other edges to avoid them moving)
             // Note: we insert a pop to consume the function reference pushed on the stack to execute the call
             *edge = Edge::Linear { instrs: vec![EdgeInstr::Pop {}], next: body_len };
@@ -938,15 +978,24 @@ fn inline_funcs_in_body(
                 .clone();
             prep_func_body(&mut call_body, calls, call_id, body_len, next, 0, None);
 
+            // Remember where the inlined edges came from, such that their elements remain identifiable by it
+            let call_origin: Vec<ProgramCounter> = origin_of(origins, ProgramCounter::new(func_id, pc));
+            for idx in 0..call_body.len() {
+                let mut origin: Vec<ProgramCounter> = call_origin.clone();
+                origin.extend(origin_of(origins, ProgramCounter::new(call_id, idx)));
+                origins.insert(ProgramCounter::new(func_id, body_len + idx), origin);
+            }
+
             // Append it to the main body and the inlining is complete
             body.extend(call_body);
 
             // End with the next edges
-            inline_funcs_in_body(body, calls, funcs, inlinable, func_id, next, breakpoint);
+            inline_funcs_in_body(body, calls, origins, funcs, inlinable, func_id, next, max_edges, breakpoint)?;
         },
 
         Edge::Return { result: _ } => (),
     }
+    Ok(())
 }
 
 /***** SIMPLIFICATION FUNCTIONS *****/
@@ -960,13 +1009,14 @@ fn inline_funcs_in_body(
 /// # Arguments
 /// - `wir`: The input [WIR](Workflow) to simply.
 /// - `calls`: The map of call indices to which function is actually called.
+/// - `max_edges`: The maximum number of edges any function body (including main) may grow to by inlining.
 ///
 /// # Returns
-/// The same `wir` as given, but then optimized.
+/// A tuple of the same `wir` as given, but then optimized, and the [`Origins`] of the edges inlined in its main body.
 ///
 /// # Errors
-/// This function may error if the input workflow is incoherent.
-pub fn inline_functions(mut wir: Workflow, calls: &mut HashMap<ProgramCounter, usize>) -> Workflow {
+/// This function errors if inlining would grow any function body beyond `max_edges`.
+pub fn inline_functions(mut wir: Workflow, calls: &mut HashMap<ProgramCounter, usize>, max_edges: usize) -> Result<(Workflow, Origins), Error> {
     // Analyse which functions in the WIR are non-recursive
     let mut inlinable: HashMap<usize, Option<HashSet<usize>>> = HashMap::with_capacity(calls.len());
     find_inlinable_funcs(&wir, calls, &mut vec![], ProgramCounter::start(), None, &mut inlinable);
@@ -1003,6 +1053,7 @@ pub fn inline_functions(mut wir: Workflow, calls: &mut HashMap<ProgramCounter, u
             .join(", ")
     );
 
+    let mut origins: Origins = HashMap::new();
     {
         // Tear open the Workflow to satisfy the borrow checker
         let Workflow { id: _, graph: wir_graph, metadata: _, funcs: wir_funcs, table: wir_table, user: _ } = &mut wir;
@@ -1028,14 +1079,14 @@ pub fn inline_functions(mut wir: Workflow, calls: &mut HashMap<ProgramCounter, u
 
             // Inline the functions in this body
             debug!("Inlining functions in function {id}");
-            inline_funcs_in_body(&mut new_body, calls, &new_funcs, &inlinable, FunctionId::Func(id), 0, None);
+            inline_funcs_in_body(&mut new_body, calls, &mut origins, &new_funcs, &inlinable, FunctionId::Func(id), 0, max_edges, None)?;
             new_funcs.insert(id, new_body);
         }
         funcs = new_funcs;
 
         // Now inline the main with all function bodies inlined correctly
         debug!("Inlining functions in main");
-        inline_funcs_in_body(&mut graph, calls, &funcs, &inlinable, FunctionId::Main, 0, None);
+        inline_funcs_in_body(&mut graph, calls, &mut origins, &funcs, &inlinable, FunctionId::Main, 0, max_edges, None)?;
 
         // Write the functions and graphs back
         let mut table: Arc<SymTable> = Arc::new(table);
@@ -1046,11 +1097,65 @@ pub fn inline_functions(mut wir: Workflow, calls: &mut HashMap<ProgramCounter, u
         std::mem::swap(wir_graph, &mut graph);
     }
 
+    // Only the main body is left to compile, so only its origins are interesting
+    origins.retain(|pc, _| pc.is_main());
+
     // OK, we did all we could
-    wir
+    Ok((wir, origins))
 }
 
 /***** LIBRARY *****/
+/// Maps the edges that were inlined into a function body to where they originally came from.
+///
+/// The origin of an edge is the path of [`ProgramCounter`]s in the original WIR to reach it: first those of the calls
+/// through which it was inlined (outermost first), then its own position in the body of the function called last. Edges
+/// that were not inlined are not in the map; their origin is just their own position. Unlike the positions of inlined
+/// edges, which depend on the order in which functions are inlined, origins are stable, and they tell apart the copies
+/// of a function body that is called more than once.
+pub type Origins = HashMap<ProgramCounter, Vec<ProgramCounter>>;
+
+/// Formats the origin of an edge as a structural identifier.
+///
+/// This is what identifies the [`Elem`](crate::spec::Elem)s compiled from the edge in the checker workflow (e.g.,
+/// `main:4/func:2` for edge 2 of `func` as inlined in the call at `main:4`).
+///
+/// # Arguments
+/// - `table`: The [`SymTable`] of the WIR, to resolve function names with.
+/// - `origin`: The origin of the edge (see [`Origins`]).
+///
+/// # Returns
+/// The resolved [`ProgramCounter`]s of the origin, separated by slashes.
+pub fn structural_id<'p>(table: &SymTable, origin: impl IntoIterator<Item = &'p ProgramCounter>) -> String {
+    origin.into_iter().map(|pc| pc.resolved(table).to_string()).collect::<Vec<String>>().join("/")
+}
+
+/// Limits the size and complexity of the workflows that are simplified.
+///
+/// Inlining copies the body of a function for every call to it, so a workflow with nested functions that call each
+/// other several times grows exponentially. These limits refuse such workflows (whether adversarial or accidental)
+/// before they take up all memory, or make the reasoner take minutes to answer a question about them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WorkflowLimits {
+    /// The maximum number of edges in a workflow, in its main body and all its functions together.
+    pub max_edges: usize,
+    /// The maximum factor by which inlining may grow the number of edges in a workflow.
+    pub max_inline_expansion: usize,
+    /// The maximum number of loops in a workflow after inlining (i.e., a loop in a function counts for every call to it).
+    pub max_loops: usize,
+}
+impl Default for WorkflowLimits {
+    #[inline]
+    fn default() -> Self { Self { max_edges: 10_000, max_inline_expansion: 32, max_loops: 256 } }
+}
+
+/// Simplifies the given WIR-workflow as much as possible to increase the compatability with checker workflows.
+///
+/// Like [`simplify_with_limits()`], but with the [default](WorkflowLimits::default()) limits.
+#[inline]
+pub fn simplify(wir: Workflow) -> Result<(Workflow, HashMap<ProgramCounter, usize>, Origins, InlineStats), Error> {
+    simplify_with_limits(wir, &WorkflowLimits::default())
+}
+
 /// Simplifies the given WIR-workflow as much as possible to increase the compatability with checker workflows.
 ///
 /// Most importantly, it:
@@ -1058,20 +1163,51 @@ pub fn inline_functions(mut wir: Workflow, calls: &mut HashMap<ProgramCounter, u
 ///
 /// # Arguments
 /// - `wir`: The input [WIR](Workflow) to simply.
+/// - `limits`: The [`WorkflowLimits`] that the workflow must stay within.
 ///
 /// # Returns
-/// A tuple of the same `wir` as given, but then optimized, and a mapping of (remaining) [`Edge::Call`]s to whatever function they actually map.
+/// A tuple of the same `wir` as given, but then optimized, a mapping of (remaining) [`Edge::Call`]s to whatever function they actually map,
+/// the [`Origins`] of the inlined edges and the [`InlineStats`] that tell how much the workflow grew.
 ///
 /// # Errors
-/// This function may error if the input workflow is incoherent.
-pub fn simplify(mut wir: Workflow) -> Result<(Workflow, HashMap<ProgramCounter, usize>), Error> {
+/// This function may error if the input workflow is incoherent, or if it exceeds any of the `limits` (see [`Error::exceeds_limits()`]).
+pub fn simplify_with_limits(
+    mut wir: Workflow,
+    limits: &WorkflowLimits,
+) -> Result<(Workflow, HashMap<ProgramCounter, usize>, Origins, InlineStats), Error> {
+    // Refuse workflows that are too large to begin with, before analysing anything
+    let edges: usize = wir.graph.len() + wir.funcs.values().map(Vec::len).sum::<usize>();
+    if edges > limits.max_edges {
+        return Err(Error::TooManyEdges { edges, max: limits.max_edges });
+    }
+
     // Analyse call dependencies first
     let (mut calls, _): (HashMap<ProgramCounter, usize>, _) = resolve_calls(&wir, &wir.table, &mut vec![], ProgramCounter::start(), None, None)?;
     debug!("Resolved calls as: {:?}", calls.iter().map(|(pc, id)| (format!("{}", pc.resolved(&wir.table)), *id)).collect::<HashMap<String, usize>>());
 
     // Simplify functions as much as possible
-    wir = inline_functions(wir, &mut calls);
+    let origins: Origins;
+    (wir, origins) = inline_functions(wir, &mut calls, edges.max(1).saturating_mul(limits.max_inline_expansion))?;
+
+    // Loops are unrolled by some reasoners, so many of them make for slow questions
+    let loops: usize = wir.graph.iter().filter(|edge| matches!(edge, Edge::Loop { .. })).count();
+    if loops > limits.max_loops {
+        return Err(Error::TooManyLoops { loops, max: limits.max_loops });
+    }
+
+    // Tell how much inlining grew the workflow; every inlined copy of a function body leaves its call in the origins
+    let functions: HashSet<FunctionId> = origins.values().filter_map(|origin| origin.last()).map(|pc| pc.func_id).collect();
+    let copies: HashSet<&[ProgramCounter]> = origins.values().filter_map(|origin| origin.split_last()).map(|(_, calls)| calls).collect();
+    let stats = InlineStats {
+        functions_inlined: functions.len(),
+        calls_inlined: copies.len(),
+        edges_before: edges,
+        edges_after: wir.graph.len(),
+        calls_remaining: wir.graph.iter().filter(|edge| matches!(edge, Edge::Call { .. })).count(),
+        loops,
+    };
+    debug!("Inlined {} call(s) to {} function(s), growing {} edge(s) to {}", stats.calls_inlined, stats.functions_inlined, edges, stats.edges_after);
 
     // Done!
-    Ok((wir, calls))
+    Ok((wir, calls, origins, stats))
 }
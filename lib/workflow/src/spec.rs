@@ -15,13 +15,43 @@
 use std::collections::HashSet;
 use std::hash::Hash;
 
-use brane_ast::MergeStrategy;
-use brane_ast::locations::Location;
+#[cfg(feature = "wir")]
+pub use brane_ast::MergeStrategy;
+#[cfg(feature = "wir")]
+pub use brane_ast::locations::Location;
 use enum_debug::EnumDebug;
 use serde::{Deserialize, Serialize};
 use specifications::version::Version;
 
 /***** AUXILLARY DATA *****/
+/// Identifies a location (i.e., a domain) where tasks run and datasets live. The same as Brane's.
+#[cfg(not(feature = "wir"))]
+pub type Location = String;
+
+/// Defines how the results of the branches of an [`ElemParallel`] are joined. Mirrors Brane's, which is used instead if
+/// workflows are compiled from its WIR.
+#[cfg(not(feature = "wir"))]
+#[derive(Clone, Copy, Debug, Deserialize, EnumDebug, Eq, Hash, PartialEq, Serialize)]
+pub enum MergeStrategy {
+    /// Take the value that arrived first, without waiting for the rest.
+    First,
+    /// Take the value that arrived first, but wait for the rest.
+    FirstBlocking,
+    /// Take the value that arrived last.
+    Last,
+    /// Add all values together.
+    Sum,
+    /// Multiply all values together.
+    Product,
+    /// Take the largest value.
+    Max,
+    /// Take the smallest value.
+    Min,
+    /// Return all values as an array.
+    All,
+    /// Return nothing.
+    None,
+}
 /// Defines how a user looks like.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct User {
@@ -59,6 +89,34 @@ impl Hash for Dataset {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) { self.name.hash(state) }
 }
 
+/// Identifies the container image in which a task's code is run.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Image {
+    /// The digest of the image (e.g., `sha256:...`), which identifies its contents.
+    pub digest:     String,
+    /// The registry the image is pulled from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry:   Option<String>,
+    /// Detached, Base64-encoded signatures over the `digest`, as claimed by whoever published the image.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<String>,
+    /// The names of the keys of which a signature in `signatures` has been verified by the checker.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signed_by:  Vec<String>,
+}
+
+/// Relates a version of a package to the container [`Image`] that implements it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackageImage {
+    /// The name of the package.
+    pub package: String,
+    /// The version of the package.
+    pub version: Version,
+    /// The image of this version of the package.
+    #[serde(flatten)]
+    pub image:   Image,
+}
+
 /// Represents a "tag" and everything we need to know.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Metadata {
@@ -119,6 +177,11 @@ pub enum Elem {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ElemTask {
     /// Some identifier for this call specifically.
+    ///
+    /// When compiled from WIR, this is `<workflow id>-<structural id>-task`, where the structural id is where the task
+    /// is in the original WIR (see `preprocess::structural_id()`). For tasks in functions, it
+    /// includes the calls through which they are reached (e.g., `main:4/func:2`), such that every copy of an inlined
+    /// function body can be told apart.
     pub id: String,
 
     /// The name of the task to execute
@@ -137,6 +200,9 @@ pub struct ElemTask {
 
     /// The location where the task is planned to be executed, if any.
     pub location: Option<Location>,
+    /// The container image that implements the task's package, if known. Note: populated by the checker from its state (see [`Workflow::resolve_images()`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image:    Option<Image>,
     /// The list of metadata belonging to this task. Note: may need to be populated by the checker!
     pub metadata: Vec<Metadata>,
 
@@ -149,7 +215,7 @@ pub struct ElemTask {
 /// Checkers can assume that anything produced by a function will be deleted after the workflow stops (or at least, domains **should** do so) _unless_ committed.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ElemCommit {
-    /// Some identifier for this call specifically.
+    /// Some identifier for this call specifically. Like that of an [`ElemTask`], but ending in `-commit`.
     pub id: String,
 
     /// The name after committing.
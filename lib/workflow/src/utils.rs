@@ -14,7 +14,9 @@
 
 use std::collections::HashSet;
 
+#[cfg(feature = "wir")]
 use brane_ast::ast;
+#[cfg(feature = "wir")]
 use brane_exe::pc::ProgramCounter;
 
 use crate::{Dataset, Elem, ElemBranch, ElemCommit, ElemLoop, ElemParallel, ElemTask};
@@ -28,6 +30,7 @@ use crate::{Dataset, Elem, ElemBranch, ElemCommit, ElemLoop, ElemParallel, ElemT
 ///
 /// # Returns
 /// The edge the `pc` pointed to, or [`None`] if it was out-of-bounds.
+#[cfg(feature = "wir")]
 #[inline]
 pub fn get_edge(wir: &ast::Workflow, pc: ProgramCounter) -> Option<&ast::Edge> {
     if pc.func_id.is_main() { wir.graph.get(pc.edge_idx) } else { wir.funcs.get(&pc.func_id.id()).and_then(|edges| edges.get(pc.edge_idx)) }
@@ -51,7 +51,7 @@ macro_rules! write_iter {
 fn print_elem(f: &mut Formatter, elem: &Elem, prefix: &dyn Display) -> FResult {
     // Print the element
     match elem {
-        Elem::Task(ElemTask { id, name, package, version, input, output, location, metadata, next }) => {
+        Elem::Task(ElemTask { id, name, package, version, input, output, location, image, metadata, next }) => {
             writeln!(f, "{prefix}task")?;
             writeln!(f, "{prefix}  - id : {id}")?;
             writeln!(f, "{prefix}")?;
@@ -68,6 +68,7 @@ fn print_elem(f: &mut Formatter, elem: &Elem, prefix: &dyn Display) -> FResult {
             )?;
             writeln!(f, "{prefix}")?;
             writeln!(f, "{prefix}  - location  : {}", if let Some(location) = &location { location.as_str() } else { "<unplanned>" })?;
+            writeln!(f, "{prefix}  - image     : {}", if let Some(image) = &image { image.digest.as_str() } else { "<unknown>" })?;
             writeln!(
                 f,
                 "{}  - metadata  : {}",
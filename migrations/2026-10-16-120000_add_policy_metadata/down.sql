@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE policies
+  DROP COLUMN tags;
+
+ALTER TABLE policies
+  DROP COLUMN jurisdiction;
+
+ALTER TABLE policies
+  DROP COLUMN department;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE policies
+  ADD tags TEXT NOT NULL DEFAULT '[]';
+
+ALTER TABLE policies
+  ADD jurisdiction TEXT NULL;
+
+ALTER TABLE policies
+  ADD department TEXT NULL;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE usage_counters (
+  subject TEXT NOT NULL,
+  counter TEXT NOT NULL,
+  period TEXT NOT NULL,
+  value BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (subject, counter, period)
+);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE duties (
+  id TEXT NOT NULL PRIMARY KEY,
+  verdict_reference TEXT NOT NULL,
+  holder TEXT NOT NULL,
+  duty TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  fulfilled_at TIMESTAMP,
+  report TEXT
+);
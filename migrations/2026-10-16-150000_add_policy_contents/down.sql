@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE policies
+  DROP COLUMN content_hashes;
+
+DROP TABLE policy_contents;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE policy_contents (
+  hash TEXT NOT NULL PRIMARY KEY,
+  content TEXT NOT NULL
+);
+
+ALTER TABLE policies
+  ADD content_hashes TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE audit_log (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  identifier TEXT NOT NULL,
+  logged_at TIMESTAMP NOT NULL,
+  reference TEXT,
+  kind TEXT NOT NULL,
+  statement TEXT NOT NULL
+);
+CREATE INDEX audit_log_reference ON audit_log (reference);
+CREATE INDEX audit_log_kind ON audit_log (kind);
+CREATE INDEX audit_log_logged_at ON audit_log (logged_at);
+
+-- The audit log is append-only
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
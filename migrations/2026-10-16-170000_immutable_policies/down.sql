@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER policy_contents_no_rehash;
+DROP TRIGGER policies_no_delete;
+DROP TRIGGER policies_no_update;
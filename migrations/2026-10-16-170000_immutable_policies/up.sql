@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- Stored policy versions are immutable. The only update allowed is moving the contents of a version stored before
+-- contents were deduplicated to the `policy_contents` table, which leaves everything else as-is.
+CREATE TRIGGER policies_no_update BEFORE UPDATE ON policies
+WHEN NOT (
+  OLD.content_hashes = '[]' AND NEW.content = '[]'
+  AND NEW.version = OLD.version
+  AND NEW.description = OLD.description
+  AND NEW.version_description = OLD.version_description
+  AND NEW.creator = OLD.creator
+  AND NEW.created_at = OLD.created_at
+  AND NEW.reasoner_connector_context = OLD.reasoner_connector_context
+  AND NEW.tags = OLD.tags
+  AND NEW.jurisdiction IS OLD.jurisdiction
+  AND NEW.department IS OLD.department
+)
+BEGIN
+  SELECT RAISE(ABORT, 'policy versions are immutable');
+END;
+
+-- Nor can stored versions be removed, as verdicts in the audit log refer to them
+CREATE TRIGGER policies_no_delete BEFORE DELETE ON policies
+BEGIN
+  SELECT RAISE(ABORT, 'policy versions cannot be deleted');
+END;
+
+-- Contents are stored by their hash, which may thus never change (though they may be encrypted in place)
+CREATE TRIGGER policy_contents_no_rehash BEFORE UPDATE OF hash ON policy_contents
+WHEN NEW.hash IS NOT OLD.hash
+BEGIN
+  SELECT RAISE(ABORT, 'policy contents are immutable');
+END;
@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER policies_no_update;
+CREATE TRIGGER policies_no_update BEFORE UPDATE ON policies
+WHEN NOT (
+  OLD.content_hashes = '[]' AND NEW.content = '[]'
+  AND NEW.version = OLD.version
+  AND NEW.description = OLD.description
+  AND NEW.version_description = OLD.version_description
+  AND NEW.creator = OLD.creator
+  AND NEW.created_at = OLD.created_at
+  AND NEW.reasoner_connector_context = OLD.reasoner_connector_context
+  AND NEW.tags = OLD.tags
+  AND NEW.jurisdiction IS OLD.jurisdiction
+  AND NEW.department IS OLD.department
+)
+BEGIN
+  SELECT RAISE(ABORT, 'policy versions are immutable');
+END;
+
+ALTER TABLE policies DROP COLUMN external_id;
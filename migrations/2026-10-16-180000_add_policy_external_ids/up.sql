@@ -0,0 +1,27 @@
+-- Your SQL goes here
+-- Every policy version gets an identifier that is unique across reasoner instances, for correlating it elsewhere
+ALTER TABLE policies ADD external_id TEXT;
+
+-- Give the versions stored so far a (random, version 4) UUID, which the immutability trigger has to allow once
+DROP TRIGGER policies_no_update;
+UPDATE policies SET external_id =
+  lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' || substr(lower(hex(randomblob(2))), 2) || '-'
+  || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6)));
+
+CREATE TRIGGER policies_no_update BEFORE UPDATE ON policies
+WHEN NOT (
+  OLD.content_hashes = '[]' AND NEW.content = '[]'
+  AND NEW.version = OLD.version
+  AND NEW.description = OLD.description
+  AND NEW.version_description = OLD.version_description
+  AND NEW.creator = OLD.creator
+  AND NEW.created_at = OLD.created_at
+  AND NEW.reasoner_connector_context = OLD.reasoner_connector_context
+  AND NEW.tags = OLD.tags
+  AND NEW.jurisdiction IS OLD.jurisdiction
+  AND NEW.department IS OLD.department
+  AND NEW.external_id IS OLD.external_id
+)
+BEGIN
+  SELECT RAISE(ABORT, 'policy versions are immutable');
+END;
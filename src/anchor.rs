@@ -0,0 +1,244 @@
+//! Periodically anchors the audit log by computing Merkle roots over new entries.
+//!
+//! Every anchoring round, the entries written by the [`FileLogger`](crate::logger::FileLogger) since the previous
+//! round are collected in a segment and the root of a Merkle tree over them is recorded as an [`AuditAnchor`] in a
+//! separate anchors file. The anchor can additionally be published to an external [`Notary`], which is what gives the
+//! roots their weight: once a root is published, the segment can no longer be changed unnoticed.
+//!
+//! Given the log and the anchors file, [`InclusionProof::for_reference()`](audit_logger::merkle::InclusionProof::for_reference)
+//! proves that the entries of a particular request are part of an anchored segment.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use audit_logger::merkle::{AuditAnchor, MerkleTree, leaf_hash, live_offset};
+use error_trace::ErrorTrace as _;
+use log::{debug, error, info};
+use tokio::fs::{self as tfs, OpenOptions};
+use tokio::io::AsyncWriteExt as _;
+
+use crate::logger::LogLock;
+
+/***** ERRORS *****/
+/// Defines errors originating from anchoring the audit log.
+#[derive(Debug)]
+pub enum AnchorError {
+    /// Failed to lock the audit log.
+    LogLock { path: PathBuf, err: std::io::Error },
+    /// Failed to read the audit log.
+    LogRead { path: PathBuf, err: std::io::Error },
+    /// Failed to read the anchors file.
+    AnchorsRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse an anchor in the anchors file.
+    AnchorParse { path: PathBuf, line: usize, err: serde_json::Error },
+    /// Failed to serialize an anchor.
+    AnchorSerialize { err: serde_json::Error },
+    /// Failed to write an anchor to a file.
+    AnchorWrite { path: PathBuf, err: std::io::Error },
+    /// Failed to publish an anchor to an HTTP notary.
+    AnchorPublish { url: String, err: reqwest::Error },
+}
+impl Display for AnchorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use AnchorError::*;
+        match self {
+            LogLock { path, .. } => write!(f, "Failed to lock audit log '{}'", path.display()),
+            LogRead { path, .. } => write!(f, "Failed to read audit log '{}'", path.display()),
+            AnchorsRead { path, .. } => write!(f, "Failed to read anchors file '{}'", path.display()),
+            AnchorParse { path, line, .. } => write!(f, "Failed to parse anchor on line {} of anchors file '{}'", line + 1, path.display()),
+            AnchorSerialize { .. } => write!(f, "Failed to serialize audit log anchor"),
+            AnchorWrite { path, .. } => write!(f, "Failed to write audit log anchor to '{}'", path.display()),
+            AnchorPublish { url, .. } => write!(f, "Failed to publish audit log anchor to notary '{url}'"),
+        }
+    }
+}
+impl Error for AnchorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use AnchorError::*;
+        match self {
+            LogLock { err, .. } => Some(err),
+            LogRead { err, .. } => Some(err),
+            AnchorsRead { err, .. } => Some(err),
+            AnchorParse { err, .. } => Some(err),
+            AnchorSerialize { err } => Some(err),
+            AnchorWrite { err, .. } => Some(err),
+            AnchorPublish { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** HELPERS *****/
+/// Appends a single line to a file, creating it if it does not exist.
+async fn append_line(path: &PathBuf, line: &str) -> Result<(), std::io::Error> {
+    let mut handle = OpenOptions::new().create(true).append(true).open(path).await?;
+    handle.write_all(line.as_bytes()).await?;
+    handle.write_all(b"\n").await?;
+    handle.shutdown().await
+}
+
+/***** LIBRARY *****/
+/// Defines where anchors are published to, besides the local anchors file.
+#[derive(Clone, Debug)]
+pub enum Notary {
+    /// Appends every anchor as a JSON line to a file (e.g., on a write-once mount).
+    File(PathBuf),
+    /// POSTs every anchor as JSON to an HTTP endpoint (e.g., a transparency log).
+    Http(String),
+}
+impl Display for Notary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::Http(url) => write!(f, "{url}"),
+        }
+    }
+}
+impl FromStr for Notary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Http(s.into()))
+        } else if let Some(path) = s.strip_prefix("file:") {
+            Ok(Self::File(path.into()))
+        } else {
+            Err(format!("Unknown notary '{s}' (expected 'file:<PATH>' or an 'http(s)://' URL)"))
+        }
+    }
+}
+
+/// Computes and records Merkle roots over the audit log.
+#[derive(Clone, Debug)]
+pub struct Anchorer {
+    /// The path of the audit log to anchor.
+    log_path: PathBuf,
+    /// The path of the file to record anchors in.
+    anchors_path: PathBuf,
+    /// Where to publish anchors to, if anywhere.
+    notary: Option<Notary>,
+}
+
+impl Anchorer {
+    /// Constructor for the Anchorer.
+    ///
+    /// # Arguments
+    /// - `log_path`: The path of the audit log to anchor.
+    /// - `anchors_path`: The path of the file to record anchors in.
+    ///
+    /// # Returns
+    /// A new Anchorer that does not publish anchors anywhere else.
+    #[inline]
+    pub fn new(log_path: impl Into<PathBuf>, anchors_path: impl Into<PathBuf>) -> Self {
+        Self { log_path: log_path.into(), anchors_path: anchors_path.into(), notary: None }
+    }
+
+    /// Makes the Anchorer publish every anchor to the given notary.
+    ///
+    /// # Arguments
+    /// - `notary`: The [`Notary`] to publish to, if any.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_notary(mut self, notary: Option<Notary>) -> Self {
+        self.notary = notary;
+        self
+    }
+
+    /// Reads the anchors recorded so far.
+    ///
+    /// # Errors
+    /// This function errors if the anchors file exists but could not be read or parsed.
+    pub async fn anchors(&self) -> Result<Vec<AuditAnchor>, AnchorError> {
+        if !self.anchors_path.exists() {
+            return Ok(Vec::new());
+        }
+        let raw: String =
+            tfs::read_to_string(&self.anchors_path).await.map_err(|err| AnchorError::AnchorsRead { path: self.anchors_path.clone(), err })?;
+        raw.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| serde_json::from_str(line).map_err(|err| AnchorError::AnchorParse { path: self.anchors_path.clone(), line: i, err }))
+            .collect()
+    }
+
+    /// Anchors all complete entries written to the audit log since the previous anchor.
+    ///
+    /// # Returns
+    /// The new [`AuditAnchor`], or [`None`] if there were no new entries.
+    ///
+    /// # Errors
+    /// This function errors if we failed to read the log, or to record or publish the anchor.
+    pub async fn anchor(&self) -> Result<Option<AuditAnchor>, AnchorError> {
+        if !self.log_path.exists() {
+            return Ok(None);
+        }
+        // Read the log and its anchors together, such that they are not rewritten in between (see `retention`)
+        let lock: LogLock = LogLock::acquire(&self.log_path).await.map_err(|err| AnchorError::LogLock { path: self.log_path.clone(), err })?;
+        let raw: String = tfs::read_to_string(&self.log_path).await.map_err(|err| AnchorError::LogRead { path: self.log_path.clone(), err })?;
+        // Only consider complete lines; a logger that crashed may have left half of the last one
+        let entries: Vec<&str> = raw.split_inclusive('\n').filter_map(|line| line.strip_suffix('\n')).collect();
+
+        // Lines of archived segments are no longer in the log (see `retention`)
+        let anchors: Vec<AuditAnchor> = self.anchors().await?;
+        drop(lock);
+        let offset: usize = live_offset(&anchors);
+        let first: usize = anchors.last().map(AuditAnchor::end).unwrap_or(0);
+        if first - offset >= entries.len() {
+            debug!("No new audit log entries to anchor");
+            return Ok(None);
+        }
+        let tree = MerkleTree::new(entries[first - offset..].iter().map(|entry| leaf_hash(entry.as_bytes())).collect());
+        let anchor = AuditAnchor {
+            first,
+            size: tree.len(),
+            root: hex::encode(tree.root()),
+            anchored_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            migrated_from: None,
+            archived: None,
+        };
+
+        // Publish it first, such that a segment only counts as anchored once it's out of our hands
+        let line: String = serde_json::to_string(&anchor).map_err(|err| AnchorError::AnchorSerialize { err })?;
+        match &self.notary {
+            Some(Notary::File(path)) => append_line(path, &line).await.map_err(|err| AnchorError::AnchorWrite { path: path.clone(), err })?,
+            Some(Notary::Http(url)) => {
+                reqwest::Client::new()
+                    .post(url)
+                    .json(&anchor)
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(|err| AnchorError::AnchorPublish { url: url.clone(), err })?;
+            },
+            None => {},
+        }
+        let _lock: LogLock = LogLock::acquire(&self.log_path).await.map_err(|err| AnchorError::LogLock { path: self.log_path.clone(), err })?;
+        append_line(&self.anchors_path, &line).await.map_err(|err| AnchorError::AnchorWrite { path: self.anchors_path.clone(), err })?;
+        info!("Anchored audit log entries {}-{} with Merkle root {}", anchor.first, anchor.first + anchor.size - 1, anchor.root);
+        Ok(Some(anchor))
+    }
+
+    /// Spawns a background task that anchors the audit log at a fixed interval.
+    ///
+    /// Failures are logged but do not stop the task; the entries will simply be part of the next segment.
+    ///
+    /// # Arguments
+    /// - `interval`: The time between two anchoring rounds.
+    pub fn spawn(self, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately, which we don't need
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.anchor().await {
+                    error!("{}", err.trace());
+                }
+            }
+        });
+    }
+}
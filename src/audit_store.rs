@@ -0,0 +1,488 @@
+//! Implements an audit logger that writes statements to an append-only table in a SQLite database.
+//!
+//! Every statement is stored as its JSON serialization, next to its kind, the verdict reference it is about (if any) and
+//! the moment it was logged. These are indexed, such that the statements about a request (or of a kind, or in a period)
+//! can be queried with [`SqliteAuditLogger::query()`] instead of by parsing a log file front to back.
+//!
+//! The table is created by the `add_audit_log` migration, which also makes the database refuse to update or delete any
+//! of its rows.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::sync::Arc;
+
+use audit_logger::redact::Redactor;
+use audit_logger::{
+    ArchivedPayload, AuditLogger, ConnectorWithContext, Error as AuditLoggerError, LogStatement, ReasonerConnectorAuditLogger, RequestTimings,
+    SessionSummary, schema,
+};
+use auth_resolver::AuthContext;
+use chrono::{DateTime, Utc};
+use deliberation::ids::{IdGenerator, UuidV7Generator};
+use deliberation::spec::Verdict;
+use diesel::r2d2::{ConnectionManager, Pool, PoolError};
+use diesel::sqlite::SqliteConnection;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use enum_debug::EnumDebug as _;
+use log::debug;
+use policy::{Policy, PolicyArchive};
+use serde_json::Value;
+use state_resolver::State;
+use workflow::Workflow;
+use workflow::diagnostics::InlineStats;
+
+use crate::models::{NewSqliteAuditLogEntry, SqliteAuditLogEntry};
+
+/***** ERRORS *****/
+/// Defines errors originating from the [`SqliteAuditLogger`].
+#[derive(Debug)]
+pub enum SqliteAuditLoggerError {
+    /// Failed to get a connection to the database.
+    Connect { path: String, err: PoolError },
+    /// Failed to serialize a statement.
+    StatementSerialize { kind: String, err: serde_json::Error },
+    /// Failed to parse a statement read back from the database.
+    StatementParse { id: i32, err: schema::Error },
+    /// Failed to append a statement to the audit log.
+    Insert { path: String, err: diesel::result::Error },
+    /// Failed to query the audit log.
+    Query { path: String, err: diesel::result::Error },
+}
+impl Display for SqliteAuditLoggerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use SqliteAuditLoggerError::*;
+        match self {
+            Connect { path, .. } => write!(f, "Failed to connect to audit log database '{path}'"),
+            StatementSerialize { kind, .. } => write!(f, "Failed to serialize {kind}"),
+            StatementParse { id, .. } => write!(f, "Failed to parse audit log statement {id}"),
+            Insert { path, .. } => write!(f, "Failed to append statement to audit log database '{path}'"),
+            Query { path, .. } => write!(f, "Failed to query audit log database '{path}'"),
+        }
+    }
+}
+impl Error for SqliteAuditLoggerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use SqliteAuditLoggerError::*;
+        match self {
+            Connect { err, .. } => Some(err),
+            StatementSerialize { err, .. } => Some(err),
+            StatementParse { err, .. } => Some(err),
+            Insert { err, .. } => Some(err),
+            Query { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// Selects statements from the audit log. Every given criterium must match.
+#[derive(Clone, Debug, Default)]
+pub struct AuditLogQuery {
+    /// Only statements about the request with this verdict reference.
+    pub reference: Option<String>,
+    /// Only statements of this kind (e.g., `REASONER-VERDICT`).
+    pub kind:      Option<String>,
+    /// Only statements logged at or after this moment.
+    pub since:     Option<DateTime<Utc>>,
+    /// Only statements logged before this moment.
+    pub until:     Option<DateTime<Utc>>,
+}
+
+/// A statement read back from the audit log.
+#[derive(Clone, Debug)]
+pub struct AuditLogEntry {
+    /// The identifier of the reasoner that logged the statement. E.g. "policy-reasoner v1.2.3".
+    pub identifier: String,
+    /// When the statement was logged.
+    pub logged_at:  DateTime<Utc>,
+    /// The statement itself.
+    pub statement:  LogStatement<'static>,
+}
+
+/***** LIBRARY *****/
+/// An audit logger that appends statements to a table in a SQLite database.
+#[derive(Clone)]
+pub struct SqliteAuditLogger {
+    /// The identifier of source of the logger. E.g. "policy-reasoner v1.2.3".
+    identifier: String,
+    /// The path of the database, for error messages.
+    path: String,
+    /// The connections to the database.
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+
+    /// Redacts personal data from statements before they are written.
+    redactor: Redactor,
+    /// Generates the identifiers of statements.
+    ids:      Arc<dyn IdGenerator>,
+}
+impl SqliteAuditLogger {
+    /// Constructor for the SqliteAuditLogger.
+    ///
+    /// Note that the database must already have the `add_audit_log` migration applied (e.g., by the `init` subcommand).
+    ///
+    /// # Arguments
+    /// - `identifier`: The identifier of the source of the logger. E.g. "policy-reasoner v1.2.3".
+    /// - `path`: The path to the database to log to.
+    ///
+    /// # Returns
+    /// A new instance of self, ready for action.
+    pub fn new(identifier: String, path: impl Into<String>) -> Self {
+        let path: String = path.into();
+        let manager = ConnectionManager::<SqliteConnection>::new(&path);
+        let pool = Pool::builder().test_on_check_out(true).build_unchecked(manager);
+        Self { identifier, path, pool, redactor: Redactor::default(), ids: Arc::new(UuidV7Generator) }
+    }
+
+    /// Makes the SqliteAuditLogger redact every statement before writing it.
+    ///
+    /// # Arguments
+    /// - `redactor`: The [`Redactor`] that pseudonymizes and/or drops personal data.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Sets how the identifiers of statements are generated.
+    ///
+    /// # Arguments
+    /// - `ids`: The [`IdGenerator`] to use. Defaults to a [`UuidV7Generator`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_id_generator(mut self, ids: impl 'static + IdGenerator) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
+
+    /// Appends a log statement to the audit log.
+    ///
+    /// # Arguments
+    /// - `stmt`: The [`LogStatement`] that determines what we're gonna log.
+    ///
+    /// # Errors
+    /// This function errors if we failed to serialize the statement or to write it to the database.
+    pub async fn log(&self, stmt: LogStatement<'_>) -> Result<(), SqliteAuditLoggerError> {
+        use crate::schema::audit_log::dsl;
+
+        debug!("Writing {}-statement to audit log database...", stmt.variant());
+        let stmt: LogStatement = self.redactor.redact(stmt);
+        let value: Value = serde_json::to_value(stmt.versioned().with_id(self.ids.generate()))
+            .map_err(|err| SqliteAuditLoggerError::StatementSerialize { kind: format!("{:?}", stmt.variant()), err })?;
+
+        // The kind and reference are already in the serialized statement, so take them from there
+        let entry = NewSqliteAuditLogEntry {
+            identifier: self.identifier.clone(),
+            logged_at: Utc::now().naive_utc(),
+            reference: value.get("reference").and_then(Value::as_str).map(String::from),
+            kind: value.get("kind").and_then(Value::as_str).unwrap_or_default().into(),
+            statement: value.to_string(),
+        };
+        let mut conn = self.pool.get().map_err(|err| SqliteAuditLoggerError::Connect { path: self.path.clone(), err })?;
+        diesel::insert_into(dsl::audit_log)
+            .values(&entry)
+            .execute(&mut conn)
+            .map(|_| ())
+            .map_err(|err| SqliteAuditLoggerError::Insert { path: self.path.clone(), err })
+    }
+
+    /// Reads the statements matching a query from the audit log.
+    ///
+    /// # Arguments
+    /// - `query`: The [`AuditLogQuery`] that selects the statements to read.
+    ///
+    /// # Returns
+    /// The matching [`AuditLogEntry`]s, in the order in which they were logged.
+    ///
+    /// # Errors
+    /// This function errors if we failed to query the database, or if any of the statements in it is not valid.
+    pub fn query(&self, query: &AuditLogQuery) -> Result<Vec<AuditLogEntry>, SqliteAuditLoggerError> {
+        use crate::schema::audit_log::dsl;
+        let mut conn = self.pool.get().map_err(|err| SqliteAuditLoggerError::Connect { path: self.path.clone(), err })?;
+
+        let mut select = dsl::audit_log.order_by(dsl::id.asc()).into_boxed();
+        if let Some(reference) = &query.reference {
+            select = select.filter(dsl::reference.eq(reference));
+        }
+        if let Some(kind) = &query.kind {
+            select = select.filter(dsl::kind.eq(kind));
+        }
+        if let Some(since) = query.since {
+            select = select.filter(dsl::logged_at.ge(since.naive_utc()));
+        }
+        if let Some(until) = query.until {
+            select = select.filter(dsl::logged_at.lt(until.naive_utc()));
+        }
+        let rows: Vec<SqliteAuditLogEntry> = select
+            .select(SqliteAuditLogEntry::as_select())
+            .load(&mut conn)
+            .map_err(|err| SqliteAuditLoggerError::Query { path: self.path.clone(), err })?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(AuditLogEntry {
+                    identifier: row.identifier,
+                    logged_at:  row.logged_at.and_utc(),
+                    statement:  schema::parse(&row.statement).map_err(|err| SqliteAuditLoggerError::StatementParse { id: row.id, err })?,
+                })
+            })
+            .collect()
+    }
+}
+#[async_trait::async_trait]
+impl AuditLogger for SqliteAuditLogger {
+    async fn log_exec_task_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+        task: &str,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log execute_task request");
+
+        let stmt: LogStatement = LogStatement::execute_task(reference, auth, policy, policy_hashes, state, workflow, task);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_data_access_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+        data: &str,
+        task: &Option<String>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log data_access request");
+
+        let stmt = LogStatement::asset_access(reference, auth, policy, policy_hashes, state, workflow, data, task);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_validate_workflow_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log workflow_validate request");
+
+        let stmt = LogStatement::workflow_validate(reference, auth, policy, policy_hashes, state, workflow);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_query_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        state: &State,
+        query: &Value,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log query request");
+
+        let stmt = LogStatement::query(reference, auth, policy, state, query);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_verdict(&self, reference: &str, verdict: &Verdict) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log reasoner verdict");
+
+        let stmt = LogStatement::reasoner_verdict(reference, verdict);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_request_timings(&self, timings: &RequestTimings) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log request timings");
+
+        let stmt = LogStatement::request_timings(timings);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_workflow_preprocessed(&self, reference: &str, stats: &InlineStats) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log workflow preprocessing");
+
+        let stmt = LogStatement::workflow_preprocessed(reference, stats);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_reasoner_fallback(&self, reference: &str, behaviour: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log reasoner fallback");
+
+        let stmt = LogStatement::reasoner_fallback(reference, behaviour, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_circuit_open(&self, reference: &str, behaviour: &str, retry_after_ms: u64, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log open circuit breaker");
+
+        let stmt = LogStatement::circuit_open(reference, behaviour, retry_after_ms, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_verdict_transformed(&self, reference: &str, transformer: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log verdict transformation");
+
+        let stmt = LogStatement::verdict_transformed(reference, transformer, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_prefilter_matched(&self, reference: &str, rule: &str, effect: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log prefilter match");
+
+        let stmt = LogStatement::prefilter_matched(reference, rule, effect);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_retrospective_evaluation(&self, reference: &str, as_of: &str, state_snapshot: bool) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log retrospective evaluation");
+
+        let stmt = LogStatement::retrospective_evaluation(reference, as_of, state_snapshot);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_reasoner_context<C: ConnectorWithContext>(&self) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log reasoner connector context");
+
+        let stmt: LogStatement = LogStatement::reasoner_context::<C>();
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_effective_reasoner_context(&self, context: &Value, hash: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log effective reasoner connector context");
+
+        let stmt: LogStatement = LogStatement::effective_reasoner_context(context, hash);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_add_policy_request<C: ConnectorWithContext>(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy add");
+
+        let stmt: LogStatement = LogStatement::policy_add::<C>(auth, policy);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_set_active_version_policy(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy activate");
+
+        let stmt = LogStatement::policy_activate(auth, policy);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_deactivate_policy(&self, auth: &AuthContext) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy deactivation");
+
+        let stmt = LogStatement::policy_deactivate(auth);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_import_policies(&self, auth: &AuthContext, archive: &PolicyArchive) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy import");
+
+        let stmt = LogStatement::policy_import(auth, archive);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_lockdown(&self, auth: &AuthContext, engaged: bool, reason: Option<&str>) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log lockdown");
+
+        let stmt = LogStatement::lockdown(auth, engaged, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_auth_failure(&self, route: &str, reason: &str, detail: &str, initiator_hint: Option<&str>) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log authentication failure");
+
+        let stmt = LogStatement::auth_failure(route, reason, detail, initiator_hint);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_auth_success(&self, auth: &AuthContext, route: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log authentication success");
+
+        let stmt = LogStatement::auth_success(auth, route);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_owner_notification(
+        &self,
+        reference: &str,
+        dataset: &str,
+        channel: &str,
+        recipient: &str,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log owner notification");
+
+        let stmt = LogStatement::owner_notification(reference, dataset, channel, recipient, error);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_federated_verdict(
+        &self,
+        reference: &str,
+        domain: &str,
+        remote_reference: Option<&str>,
+        allowed: bool,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log federated verdict");
+
+        let stmt = LogStatement::federated_verdict(reference, domain, remote_reference, allowed, error);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_policy_disclosure(&self, auth: &AuthContext, fields: &[String], withheld: &[String]) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy disclosure");
+
+        let stmt = LogStatement::policy_disclosure(auth, fields, withheld);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn flush(&self, _reference: &str) -> Result<(), AuditLoggerError> {
+        // Every statement is committed to the database before logging returns, so there's nothing left to write
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReasonerConnectorAuditLogger for SqliteAuditLogger {
+    async fn log_reasoner_response(&self, reference: &str, response: &str, retries: u32) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log reasoner response");
+
+        let stmt = LogStatement::reasoner_response(reference, response, retries);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_session_summary(&self, summary: &SessionSummary) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log session summary");
+
+        let stmt = LogStatement::session_summary(summary);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_shadow_evaluation(&self, reference: &str, primary: &Value, shadow: &Value, agree: bool) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log shadow evaluation");
+
+        let stmt = LogStatement::shadow_evaluation(reference, primary, shadow, agree);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_payload_archived(&self, payload: &ArchivedPayload) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log archived payload");
+
+        let stmt = LogStatement::payload_archived(payload);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+}
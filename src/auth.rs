@@ -3,11 +3,12 @@ use std::fs;
 
 use auth_resolver::{AuthContext, AuthResolver, AuthResolverError};
 use base64ct::Encoding as _;
+use http::{HeaderMap, HeaderValue};
+use jsonwebtoken::errors::ErrorKind;
 use jsonwebtoken::jwk::{AlgorithmParameters, Jwk, JwkSet};
-use jsonwebtoken::{DecodingKey, Header, Validation};
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation};
 use log::{debug, info};
 use serde::Deserialize;
-use warp::http::{HeaderMap, HeaderValue};
 
 #[async_trait::async_trait]
 pub trait KeyResolver {
@@ -20,9 +21,17 @@ pub struct KidResolver {
 
 impl KidResolver {
     pub fn new(key_set_loc: &str) -> Result<Self, AuthResolverError> {
-        let r = fs::read_to_string(key_set_loc)
-            .map_err(|err| AuthResolverError::new(format!("Could not load jwk set from location: {}; {}", key_set_loc, err)))?;
-        let keyfile: JwkSet = serde_json::from_str(&r).map_err(|err| AuthResolverError::new(format!("Could not load parse jwk set: {}", err)))?;
+        let r = fs::read_to_string(key_set_loc).map_err(|err| AuthResolverError::KeySet {
+            what:   format!("Could not load jwk set from location: {key_set_loc}"),
+            source: Some(Box::new(err)),
+        })?;
+        Self::from_json(&r)
+    }
+
+    /// Constructor for the KidResolver that takes the JWK set itself, e.g., as fetched from a secrets manager.
+    pub fn from_json(raw: &str) -> Result<Self, AuthResolverError> {
+        let keyfile: JwkSet = serde_json::from_str(raw)
+            .map_err(|err| AuthResolverError::KeySet { what: "Could not parse jwk set".into(), source: Some(Box::new(err)) })?;
 
         Ok(Self { jwk_store: keyfile })
     }
@@ -31,12 +40,16 @@ impl KidResolver {
 #[async_trait::async_trait]
 impl KeyResolver for KidResolver {
     async fn resolve_key(&self, header: &Header) -> Result<DecodingKey, AuthResolverError> {
-        let kid = header.kid.as_ref().ok_or_else(|| AuthResolverError::new("No kid present in header".into()))?;
+        let kid = header.kid.as_ref().ok_or_else(|| AuthResolverError::Malformed {
+            what: "No kid present in header".into(),
+            initiator_hint: None,
+            source: None,
+        })?;
 
         // Get the key
         let key: &Jwk = match self.jwk_store.find(kid) {
             Some(key) => key,
-            None => return Err(AuthResolverError::new(format!("Could not find key for kid: {}", kid))),
+            None => return Err(AuthResolverError::UnknownKey { kid: kid.clone(), initiator_hint: None }),
         };
         // match self.jwk_store.find(&kid) {
         //     Some(key) => DecodingKey::from_jwk(key)
@@ -48,10 +61,15 @@ impl KeyResolver for KidResolver {
         let secret: Vec<u8> = if let AlgorithmParameters::OctetKey(oct) = &key.algorithm {
             match base64ct::Base64Url::decode_vec(&oct.value) {
                 Ok(val) => val,
-                Err(err) => return Err(AuthResolverError::new(format!("Could not decode secret key as URL-safe base64: {err}"))),
+                Err(err) => {
+                    return Err(AuthResolverError::KeySet {
+                        what:   format!("Could not decode secret key '{kid}' as URL-safe base64"),
+                        source: Some(Box::new(err)),
+                    });
+                },
             }
         } else {
-            return Err(AuthResolverError::new("Unsupported key type".into()));
+            return Err(AuthResolverError::KeySet { what: format!("Key '{kid}' has an unsupported key type"), source: None });
         };
 
         // Now return that as decoding key
@@ -67,6 +85,91 @@ pub struct JwtResolver<KR: KeyResolver> {
 #[derive(Deserialize)]
 pub struct JwtConfig {
     initiator_claim: String,
+    /// The claim naming the party that acts on behalf of the initiator, for delegated requests (e.g., `act`). Tokens without
+    /// it are taken to be sent by the initiator themselves.
+    #[serde(default)]
+    actor_claim: Option<String>,
+    /// The issuer that tokens must name in their `iss` claim. Tokens of any issuer are accepted if omitted.
+    #[serde(default)]
+    issuer: Option<String>,
+    /// The audiences of which tokens must name at least one in their `aud` claim. Tokens naming any audience are refused if empty.
+    #[serde(default)]
+    audience: Vec<String>,
+    /// The algorithms tokens may be signed with. Any algorithm fitting the key is accepted if empty.
+    #[serde(default)]
+    algorithms: Vec<Algorithm>,
+    /// The number of seconds a token may be expired (or not valid yet), to allow for clocks that are out of sync.
+    #[serde(default = "JwtConfig::default_clock_skew")]
+    clock_skew: u64,
+}
+impl JwtConfig {
+    /// The default for [`JwtConfig::clock_skew`].
+    #[inline]
+    fn default_clock_skew() -> u64 { 60 }
+
+    /// Builds the rules a token signed with the given algorithm must follow.
+    fn validation(&self, alg: Algorithm) -> Validation {
+        let mut validation = Validation::new(alg);
+        validation.leeway = self.clock_skew;
+        validation.validate_nbf = true;
+        let mut required: Vec<&str> = vec!["exp"];
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        if !self.audience.is_empty() {
+            validation.set_audience(&self.audience);
+            required.push("aud");
+        }
+        validation.set_required_spec_claims(&required);
+        validation
+    }
+}
+
+/// Reads the identity in a claim.
+///
+/// # Arguments
+/// - `claim`: The value of the claim. Either a string or number, or an object with a `sub` claim like the `act` claim of
+///   RFC 8693.
+///
+/// # Returns
+/// The identity, or [`None`] if the claim has another type.
+fn identity_of(claim: &serde_json::Value) -> Option<String> {
+    match claim {
+        serde_json::Value::Number(v) => Some(v.to_string()),
+        serde_json::Value::String(v) => Some(v.clone()),
+        serde_json::Value::Object(v) => v.get("sub").and_then(|sub| sub.as_str().map(String::from)),
+        _ => None,
+    }
+}
+
+/// Tells why a token failed validation.
+///
+/// # Arguments
+/// - `alg`: The algorithm in the header of the token.
+/// - `initiator_hint`: Who the token claims to be sent by, if that could be read.
+/// - `err`: The error of the JWT library.
+///
+/// # Returns
+/// The [`AuthResolverError`] for the cause of `err`, which is kept as its source.
+fn validation_error(alg: Algorithm, initiator_hint: Option<String>, err: jsonwebtoken::errors::Error) -> AuthResolverError {
+    match err.kind() {
+        ErrorKind::InvalidSignature => AuthResolverError::InvalidSignature { initiator_hint, source: Box::new(err) },
+        ErrorKind::InvalidAlgorithm | ErrorKind::MissingAlgorithm => {
+            AuthResolverError::DisallowedAlgorithm { alg: format!("{alg:?}"), initiator_hint, source: Some(Box::new(err)) }
+        },
+        ErrorKind::ExpiredSignature => AuthResolverError::Expired { initiator_hint, source: Box::new(err) },
+        ErrorKind::ImmatureSignature => AuthResolverError::NotYetValid { initiator_hint, source: Box::new(err) },
+        ErrorKind::InvalidIssuer => AuthResolverError::WrongIssuer { initiator_hint, source: Box::new(err) },
+        ErrorKind::InvalidAudience => AuthResolverError::WrongAudience { initiator_hint, source: Box::new(err) },
+        ErrorKind::MissingRequiredClaim(claim) => {
+            AuthResolverError::MissingClaim { claim: claim.clone(), initiator_hint, source: Some(Box::new(err)) }
+        },
+        ErrorKind::InvalidToken | ErrorKind::Base64(_) | ErrorKind::Json(_) | ErrorKind::Utf8(_) => {
+            AuthResolverError::Malformed { what: "Could not decode jwt".into(), initiator_hint, source: Some(Box::new(err)) }
+        },
+        _ => AuthResolverError::Other { what: "Could not validate jwt".into(), initiator_hint, source: Some(Box::new(err)) },
+    }
 }
 
 impl<KR> JwtResolver<KR>
@@ -76,21 +179,44 @@ where
     #[inline]
     pub fn new(config: JwtConfig, key_resolver: KR) -> Result<Self, Box<dyn std::error::Error>> { Ok(JwtResolver { config, key_resolver }) }
 
+    /// Reads who a token claims to be sent by without validating it, to hint at who sent a request that was refused.
+    ///
+    /// # Arguments
+    /// - `raw_jwt`: The token.
+    /// - `alg`: The algorithm in its header.
+    ///
+    /// # Returns
+    /// The identity in the initiator claim, or [`None`] if the token cannot be read or has no such claim.
+    fn initiator_hint(&self, raw_jwt: &str, alg: Algorithm) -> Option<String> {
+        let mut validation = Validation::new(alg);
+        validation.insecure_disable_signature_validation();
+        validation.validate_exp = false;
+        validation.validate_nbf = false;
+        validation.validate_aud = false;
+        validation.set_required_spec_claims::<&str>(&[]);
+        let data = jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(raw_jwt, &DecodingKey::from_secret(&[]), &validation).ok()?;
+        data.claims.get(&self.config.initiator_claim).and_then(identity_of)
+    }
+
     pub fn extract_jwt(&self, auth_header: Option<&HeaderValue>) -> Result<String, AuthResolverError> {
         let header_val: &str = match auth_header {
             Some(v) => match v.to_str() {
                 Ok(v) => v,
-                Err(_) => return Err(AuthResolverError::new("Invalid authorization header".into())),
-            },
-            None => {
-                return Err(AuthResolverError::new("Authorization header not present".into()));
+                Err(err) => {
+                    return Err(AuthResolverError::Malformed {
+                        what: "Invalid authorization header".into(),
+                        initiator_hint: None,
+                        source: Some(Box::new(err)),
+                    });
+                },
             },
+            None => return Err(AuthResolverError::MissingCredentials),
         };
 
         let parts = header_val.splitn(2, ' ').collect::<Vec<&str>>();
 
-        if parts[0] != "Bearer" {
-            return Err(AuthResolverError::new("Invalid authorization header".into()));
+        if parts[0] != "Bearer" || parts.len() < 2 {
+            return Err(AuthResolverError::Malformed { what: "Invalid authorization header".into(), initiator_hint: None, source: None });
         }
 
         Ok(parts[1].into())
@@ -108,28 +234,62 @@ where
         let raw_jwt = self.extract_jwt(headers.get("Authorization"))?;
         debug!("Received JWT: '{raw_jwt}'");
 
-        let header = jsonwebtoken::decode_header(&raw_jwt).map_err(|err| AuthResolverError::new(format!("Could not parse header: {}", err)))?;
+        let header = jsonwebtoken::decode_header(&raw_jwt).map_err(|err| AuthResolverError::Malformed {
+            what: "Could not parse header".into(),
+            initiator_hint: None,
+            source: Some(Box::new(err)),
+        })?;
         debug!("JWT header: '{header:?}'");
+        if !self.config.algorithms.is_empty() && !self.config.algorithms.contains(&header.alg) {
+            return Err(AuthResolverError::DisallowedAlgorithm {
+                alg: format!("{:?}", header.alg),
+                initiator_hint: self.initiator_hint(&raw_jwt, header.alg),
+                source: None,
+            });
+        }
 
         debug!("Resolving key in keystore...");
-        let decoding_key = self.key_resolver.resolve_key(&header).await?;
-        let validation = Validation::new(header.alg);
+        let decoding_key =
+            self.key_resolver.resolve_key(&header).await.map_err(|err| err.with_initiator_hint(self.initiator_hint(&raw_jwt, header.alg)))?;
+        let validation = self.config.validation(header.alg);
         debug!("Validating JWT with {:?}...", header.alg);
         let result = jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(&raw_jwt, &decoding_key, &validation)
-            .map_err(|err| AuthResolverError::new(format!("Could not validate jwt: {}", err)))?;
+            .map_err(|err| validation_error(header.alg, self.initiator_hint(&raw_jwt, header.alg), err))?;
         debug!("Validating OK");
 
-        match result.claims.get(&self.config.initiator_claim) {
-            Some(initiator) => match initiator {
-                serde_json::Value::Number(v) => Ok(AuthContext { initiator: v.to_string(), system: "TODO implement!".into() }),
-                serde_json::Value::String(v) => Ok(AuthContext { initiator: v.clone(), system: "TODO implement!".into() }),
-                _ => Err(AuthResolverError::new(format!(
-                    "Invalid type for initiator claim (only string or number allowed): {}",
-                    self.config.initiator_claim
-                ))),
+        let initiator: String = match result.claims.get(&self.config.initiator_claim) {
+            Some(serde_json::Value::Number(v)) => v.to_string(),
+            Some(serde_json::Value::String(v)) => v.clone(),
+            Some(_) => {
+                return Err(AuthResolverError::Malformed {
+                    what: format!("Invalid type for initiator claim (only string or number allowed): {}", self.config.initiator_claim),
+                    initiator_hint: None,
+                    source: None,
+                });
             },
-            None => Err(AuthResolverError::new(format!("Missing initiator claim: {}", self.config.initiator_claim))),
-        }
+            None => {
+                return Err(AuthResolverError::MissingClaim { claim: self.config.initiator_claim.clone(), initiator_hint: None, source: None });
+            },
+        };
+
+        // Delegated requests also name who sent them on behalf of the initiator
+        let actor: Option<String> = match self.config.actor_claim.as_ref().and_then(|claim| result.claims.get(claim).map(|value| (claim, value))) {
+            Some((claim, value)) => match identity_of(value) {
+                Some(actor) => {
+                    debug!("Request by '{actor}' on behalf of '{initiator}'");
+                    Some(actor)
+                },
+                None => {
+                    return Err(AuthResolverError::Malformed {
+                        what: format!("Invalid type for actor claim (only string, number or object with a 'sub' allowed): {claim}"),
+                        initiator_hint: Some(initiator.clone()),
+                        source: None,
+                    });
+                },
+            },
+            None => None,
+        };
+        Ok(AuthContext { initiator, system: "TODO implement!".into(), actor })
     }
 }
 
@@ -138,7 +298,7 @@ pub struct MockAuthResolver {
 }
 
 impl MockAuthResolver {
-    pub fn new(initiator: String, system: String) -> Self { Self { ctx: AuthContext { initiator, system } } }
+    pub fn new(initiator: String, system: String) -> Self { Self { ctx: AuthContext { initiator, system, actor: None } } }
 }
 
 #[async_trait::async_trait]
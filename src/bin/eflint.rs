@@ -16,7 +16,9 @@ pub mod implementation;
 
 use std::env;
 use std::fs::File;
+use std::time::Duration;
 
+use audit_logger::redact::Redactor;
 use clap::Parser;
 use error_trace::ErrorTrace as _;
 use humanlog::{DebugMode, HumanLogger};
@@ -25,22 +27,37 @@ use implementation::eflint::EFlintLeakNoErrors;
 #[cfg(feature = "leak-public-errors")]
 use implementation::eflint::EFlintLeakPrefixErrors;
 use implementation::eflint::EFlintReasonerConnector;
-use implementation::interface::Arguments;
+#[cfg(feature = "init")]
+use implementation::init;
+use implementation::interface::{Action, Arguments};
+use implementation::simulate;
 use log::{error, info};
+use policy_reasoner::anchor::Anchorer;
+use policy_reasoner::audit_store::SqliteAuditLogger;
 use policy_reasoner::auth::{JwtConfig, JwtResolver, KidResolver};
-use policy_reasoner::logger::FileLogger;
+use policy_reasoner::dispatch::Dispatcher;
+use policy_reasoner::forwarder::ForwardingLogger;
+use policy_reasoner::logger::{AuditLogBackend, FileLogger};
 use policy_reasoner::sqlite::SqlitePolicyDataStore;
+use reasonerconn::circuit_breaker::CircuitBreaker;
 use srv::Srv;
+use srv::access_tokens::AccessTokenIssuer;
+use srv::disclosure::Disclosure;
+use srv::federation::Federation;
+use srv::i18n::MessageCatalog;
+use srv::maintenance::MaintenanceSchedule;
+use srv::notifications::Notifier;
+use srv::postprocess::VerdictPipeline;
+use srv::prefilter::Prefilter;
+use srv::signatures::StaticKeySet;
 
 /***** HELPER FUNCTIONS *****/
-fn get_pauth_resolver() -> JwtResolver<KidResolver> {
-    let kid_resolver = KidResolver::new("./examples/config/jwk_set_expert.json").unwrap();
+fn get_pauth_resolver(kid_resolver: KidResolver) -> JwtResolver<KidResolver> {
     let r = File::open("./examples/config/jwt_resolver.yaml").unwrap();
     let jwt_cfg: JwtConfig = serde_yaml::from_reader(r).unwrap();
     JwtResolver::new(jwt_cfg, kid_resolver).unwrap()
 }
-fn get_dauth_resolver() -> JwtResolver<KidResolver> {
-    let kid_resolver = KidResolver::new("./examples/config/jwk_set_delib.json").unwrap();
+fn get_dauth_resolver(kid_resolver: KidResolver) -> JwtResolver<KidResolver> {
     let r = File::open("./examples/config/jwt_resolver.yaml").unwrap();
     let jwt_cfg: JwtConfig = serde_yaml::from_reader(r).unwrap();
     JwtResolver::new(jwt_cfg, kid_resolver).unwrap()
@@ -48,7 +65,7 @@ fn get_dauth_resolver() -> JwtResolver<KidResolver> {
 
 /***** PLUGINS *****/
 /// The plugin used to do the audit logging.
-type AuditLogPlugin = FileLogger;
+type AuditLogPlugin = AuditLogBackend;
 
 /// The plugin used to do authentication for the policy expert API.
 type PolicyAuthResolverPlugin = JwtResolver<KidResolver>;
@@ -74,7 +91,7 @@ type StateResolverPlugin = policy_reasoner::state::FileStateResolver;
 #[tokio::main]
 async fn main() {
     // Parse arguments
-    let args = Arguments::parse();
+    let mut args = Arguments::parse();
 
     // Setup a logger
     if let Err(err) = HumanLogger::terminal(if args.trace { DebugMode::Full } else { DebugMode::Debug }).init() {
@@ -82,6 +99,31 @@ async fn main() {
     }
     info!("{} - v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
+    // Handle subcommands
+    #[cfg(feature = "init")]
+    if let Some(Action::Init(init)) = &args.action {
+        if let Err(err) = init::scaffold(init, true) {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+    if let Some(Action::Simulate(simulate)) = &args.action {
+        let rconn: ReasonerConnectorPlugin = match ReasonerConnectorPlugin::new(args.reasoner_connector.clone().unwrap_or_else(String::new)) {
+            Ok(rconn) => rconn,
+            Err(err) => {
+                error!("{}", err.trace());
+                std::process::exit(1);
+            },
+        };
+        if let Err(err) = simulate::replay(simulate, &rconn).await {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+
     // Handle help
     let mut exit: bool = false;
     if args.help_reasoner_connector {
@@ -96,21 +138,67 @@ async fn main() {
         std::process::exit(0);
     }
 
+    // Fetch the secrets before anything needs them
+    if let Err(err) = args.fetch_secrets().await {
+        error!("{}", err.trace());
+        std::process::exit(1);
+    }
+
     // Initialize the plugins
     let log_identifier = format!("{binary} v{version}", binary = env!("CARGO_BIN_NAME"), version = env!("CARGO_PKG_VERSION"));
-    let logger: AuditLogPlugin = FileLogger::new(log_identifier, "./audit-log.log");
-    let pauthresolver: PolicyAuthResolverPlugin = get_pauth_resolver();
-    let dauthresolver: DeliberationAuthResolverPlugin = get_dauth_resolver();
-    let pstore: PolicyStorePlugin = SqlitePolicyDataStore::new("./data/policy.db");
-    let rconn: ReasonerConnectorPlugin = match ReasonerConnectorPlugin::new(args.reasoner_connector.unwrap_or_else(String::new)) {
-        Ok(rconn) => rconn,
+    let redactor: Redactor = match args.audit_redactor() {
+        Ok(redactor) => redactor,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let logger: AuditLogPlugin = match args.audit_forwarder() {
+        Ok(Some(config)) => AuditLogBackend::Forwarding(ForwardingLogger::new(log_identifier, config).with_redactor(redactor)),
+        Ok(None) => match &args.audit_db {
+            Some(path) => AuditLogBackend::Sqlite(SqliteAuditLogger::new(log_identifier, path.display().to_string()).with_redactor(redactor)),
+            None => AuditLogBackend::File(FileLogger::new(log_identifier, "./audit-log.log").with_redactor(redactor)),
+        },
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let pauthresolver: PolicyAuthResolverPlugin = match args.jwk_set_expert() {
+        Ok(kid_resolver) => get_pauth_resolver(kid_resolver),
         Err(err) => {
             error!("{}", err.trace());
             std::process::exit(1);
         },
     };
+    let dauthresolver: DeliberationAuthResolverPlugin = match args.jwk_set_delib() {
+        Ok(kid_resolver) => get_dauth_resolver(kid_resolver),
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let pstore: PolicyStorePlugin = match args.policy_cipher() {
+        Ok(Some(cipher)) => SqlitePolicyDataStore::new("./data/policy.db").with_encryption(cipher),
+        Ok(None) => SqlitePolicyDataStore::new("./data/policy.db"),
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    // One reasoner connector per shard, each behind its own circuit breaker
+    let mut shards: Vec<CircuitBreaker<ReasonerConnectorPlugin>> = Vec::with_capacity(1 + args.reasoner_shard.len());
+    for shard_args in std::iter::once(args.reasoner_connector.clone().unwrap_or_else(String::new)).chain(args.reasoner_shard.iter().cloned()) {
+        match ReasonerConnectorPlugin::new(shard_args) {
+            Ok(rconn) => shards.push(CircuitBreaker::new(rconn.with_retry(args.reasoner_retry()), args.circuit_breaker())),
+            Err(err) => {
+                error!("{}", err.trace());
+                std::process::exit(1);
+            },
+        }
+    }
 
-    let sresolve: StateResolverPlugin = match StateResolverPlugin::new(args.state_resolver.unwrap_or_else(String::new)) {
+    let sresolve: StateResolverPlugin = match StateResolverPlugin::new(args.state_resolver.clone().unwrap_or_else(String::new)) {
         Ok(sresolve) => sresolve,
         Err(err) => {
             error!("{}", err.trace());
@@ -119,7 +207,113 @@ async fn main() {
     };
 
     // Run them!
-    let server = Srv::new(args.address, logger, rconn, pstore, sresolve, pauthresolver, dauthresolver);
+    let rconn: Dispatcher<CircuitBreaker<ReasonerConnectorPlugin>> = match Dispatcher::new(shards, args.reasoner_route.clone()) {
+        Ok(rconn) => rconn,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let verdict_pipeline: VerdictPipeline = match args.verdict_pipeline() {
+        Ok(pipeline) => pipeline,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let prefilter: Prefilter = match args.prefilter() {
+        Ok(prefilter) => prefilter,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let maintenance: MaintenanceSchedule = match args.maintenance() {
+        Ok(maintenance) => maintenance,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let federation: Federation = match args.federation() {
+        Ok(federation) => federation,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let disclosure: Disclosure = match args.disclosure() {
+        Ok(disclosure) => disclosure,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let image_keys: StaticKeySet = match args.image_keys() {
+        Ok(keys) => keys,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let message_catalog: MessageCatalog = match args.message_catalog() {
+        Ok(catalog) => catalog,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let archive_key: Option<Vec<u8>> = match args.archive_key() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let access_tokens: Option<AccessTokenIssuer> = match args.access_token_issuer() {
+        Ok(issuer) => issuer,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let notifier: Option<Notifier> = match args.notifier() {
+        Ok(notifier) => notifier,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    if let Some(interval) = args.audit_anchor_interval {
+        Anchorer::new("./audit-log.log", "./audit-log.anchors").with_notary(args.audit_anchor_notary.clone()).spawn(Duration::from_secs(interval));
+    }
+    let usage_counters: Option<PolicyStorePlugin> = args.usage_counters.then(|| pstore.clone());
+    let duties: Option<PolicyStorePlugin> = args.duties.then(|| pstore.clone());
+    let mut server = Srv::new(args.address, logger, rconn, pstore, sresolve, pauthresolver, dauthresolver)
+        .with_fallback(args.reasoner_fallback)
+        .with_question_queue(args.question_queue())
+        .with_verdict_pipeline(verdict_pipeline)
+        .with_prefilter(prefilter)
+        .with_maintenance(maintenance)
+        .with_federation(federation)
+        .with_disclosure(disclosure)
+        .with_lint_config(args.lint_config())
+        .with_state_check(args.state_check)
+        .with_workflow_limits(args.workflow_limits())
+        .with_max_policy_size(args.max_policy_size)
+        .with_image_verifier(image_keys)
+        .with_message_catalog(message_catalog)
+        .with_archive_key(archive_key)
+        .with_access_token_issuer(access_tokens)
+        .with_notifier(notifier)
+        .with_payload_store(args.payload_store())
+        .with_callback_origins(args.callback_origin.clone());
+    if let Some(counters) = usage_counters {
+        server = server.with_usage_counters(counters);
+    }
+    if let Some(duties) = duties {
+        server = server.with_duty_store(duties);
+    }
 
     server.run().await;
 }
@@ -1,21 +1,41 @@
 use std::collections::HashMap;
 use std::error;
 use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use audit_logger::{ConnectorContext, ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use audit_logger::{ConnectorContext, ConnectorWithContext, Error as AuditLoggerError, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::{Explanation, Granularity};
 use eflint_json::spec::auxillary::Version;
 use eflint_json::spec::{
     ConstructorInput, Expression, ExpressionConstructorApp, ExpressionPrimitive, Phrase, PhraseCreate, PhraseResult, Request, RequestCommon,
-    RequestPhrases,
+    RequestPhrases, ResponsePhrases,
 };
-use log::{debug, error, info};
+use eflint_to_json::annotate::{self, Annotation, Annotations, Severity};
+use eflint_to_json::sourcemap::{self, SourceMap};
+use error_trace::ErrorTrace as _;
+use log::{debug, error, info, warn};
 use nested_cli_parser::map_parser::MapParser;
 use nested_cli_parser::{NestedCliParser as _, NestedCliParserHelpFormatter};
 use policy::{Policy, PolicyContent};
-use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
+use reasonerconn::interpret::{BackendAnswer, categorize, eflint_instances, interpret, interpret_query};
+use reasonerconn::policy_cache::PolicyCache;
+use reasonerconn::retry::RetryConfig;
+use reasonerconn::{
+    DenyCategory, PolicyCompositionError, QuestionKind, ReasonerCapabilities, ReasonerConnError, ReasonerConnector, ReasonerMetric,
+    ReasonerQueryResponse, ReasonerResponse, explain,
+};
 use state_resolver::State;
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
 use workflow::spec::Workflow;
 
+use crate::implementation::eflint_supervisor::EFlintServerSupervisor;
+use crate::implementation::static_list::{STATIC_LIST_ID, StaticList};
+
 /***** HELPER MACROS *****/
 /// Shortcut for creating an eFLINT JSON Specification [`Phrase::Create`].
 ///
@@ -64,6 +84,10 @@ macro_rules! str_lit {
 /***** CONSTANTS *****/
 /// The identifier used for this connector backend.
 pub const EFLINT_JSON_ID: &'static str = "eflint-json";
+/// How long a single exchange with the reasoner over TCP may take before it is abandoned (and its connection closed).
+const TCP_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many idle TCP connections to the reasoner are kept around for reuse.
+const TCP_POOL_SIZE: usize = 4;
 
 // Externalized "constants"
 /// The entire base specification, already serialized as eFLINT JSON. See `build.rs` to find how the `BASE_DEFS_EFLINT_JSON` environment variable is populated.
@@ -77,15 +101,32 @@ const JSON_BASE_SPEC_HASH: &'static str = env!("BASE_DEFS_EFLINT_JSON_HASH");
 pub enum Error<E> {
     /// Failed to parse the CLI arguments to the eFLINT reasoner connector.
     CliArgumentsParse { raw: String, err: nested_cli_parser::map_parser::Error },
+    /// Failed to read the configuration file with further nested arguments.
+    ConfigRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the configuration file with further nested arguments.
+    ConfigParse { path: PathBuf, err: nested_cli_parser::map_parser::Error },
     /// Failed to construct the nested ErrorHandler plugin.
     ErrorHandler { name: &'static str, err: E },
+    /// Failed to start the supervised eflint-server.
+    Supervisor { err: crate::implementation::eflint_supervisor::Error },
+    /// The given transport was not recognized.
+    UnknownTransport { raw: String },
+    /// The given explanation budget was not a number.
+    IllegalExplainBudget { raw: String, err: std::num::ParseIntError },
 }
 impl<E> Display for Error<E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
         use Error::*;
         match self {
             CliArgumentsParse { raw, .. } => write!(f, "Failed to parse '{raw}' as CLI argument string for an EFlintReasonerConnector"),
+            ConfigRead { path, .. } => write!(f, "Failed to read configuration file '{}'", path.display()),
+            ConfigParse { path, .. } => {
+                write!(f, "Failed to parse configuration file '{}' as CLI argument string for an EFlintReasonerConnector", path.display())
+            },
             ErrorHandler { name, .. } => write!(f, "Failed to initialize error handler plugin '{name}'"),
+            Supervisor { .. } => write!(f, "Failed to start supervised eflint-server"),
+            UnknownTransport { raw } => write!(f, "Unknown transport '{raw}' (expected 'http' or 'tcp')"),
+            IllegalExplainBudget { raw, .. } => write!(f, "Illegal explanation budget '{raw}' (expected a number of questions)"),
         }
     }
 }
@@ -94,7 +135,12 @@ impl<E: 'static + error::Error> error::Error for Error<E> {
         use Error::*;
         match self {
             CliArgumentsParse { err, .. } => Some(err),
+            ConfigRead { err, .. } => Some(err),
+            ConfigParse { err, .. } => Some(err),
             ErrorHandler { err, .. } => Some(err),
+            Supervisor { err } => Some(err),
+            UnknownTransport { .. } => None,
+            IllegalExplainBudget { err, .. } => Some(err),
         }
     }
 }
@@ -130,11 +176,24 @@ pub trait EFlintErrorHandler {
     where
         Self: Sized;
 
+    /// Extracts the reasons to share with clients from the result of the question.
+    ///
+    /// The `annotations` are those of the rules in the policy (see [`eflint_to_json::annotate`]).
     #[inline]
-    fn extract_errors(&self, _: Option<&PhraseResult>) -> Vec<String> { vec![] }
+    fn extract_errors(&self, _: Option<&PhraseResult>, _annotations: &Annotations) -> Vec<String> { vec![] }
+
+    /// Decides which of the given violated rules to share with clients, e.g., after asking for them one by one.
+    ///
+    /// The `annotations` are those of the rules in the policy (see [`eflint_to_json::annotate`]).
+    #[inline]
+    fn share_violations(&self, _violated: &[String], _annotations: &Annotations) -> Vec<String> { vec![] }
 
     #[inline]
     fn nested_args() -> Vec<(char, &'static str, &'static str)> { vec![] }
+
+    /// Describes how the handler is configured, for the effective context of the connector.
+    #[inline]
+    fn configuration(&self) -> serde_json::Value { serde_json::Value::Null }
 }
 
 pub struct EFlintLeakNoErrors;
@@ -154,6 +213,11 @@ impl EFlintErrorHandler for EFlintLeakNoErrors {
 /// EFlintLeakPrefixErrors is an e-flint error handler
 /// that returns errors if the violation identifier start with a certain
 /// prefix. Which prefix is matched against can be configured.
+///
+/// Violated rules annotated as `public` or `private` in the policy are
+/// shared or not regardless of their prefix. Shared rules with a `message`
+/// annotation are reported by that message key instead of their name, and
+/// the most severe violations are reported first.
 pub struct EFlintLeakPrefixErrors {
     prefix: String,
 }
@@ -174,55 +238,394 @@ impl EFlintErrorHandler for EFlintLeakPrefixErrors {
         Ok(Self { prefix })
     }
 
-    fn extract_errors(&self, result: Option<&PhraseResult>) -> Vec<String> {
-        result
-            .map(|r| match r {
-                eflint_json::spec::PhraseResult::StateChange(sc) => match &sc.violations {
-                    Some(v) => v.iter().filter(|v| v.identifier.starts_with(&self.prefix)).map(|v| v.identifier.clone()).collect(),
-                    None => vec![],
-                },
-                _ => vec![],
+    fn extract_errors(&self, result: Option<&PhraseResult>, annotations: &Annotations) -> Vec<String> {
+        let violations = match result {
+            Some(eflint_json::spec::PhraseResult::StateChange(sc)) => match &sc.violations {
+                Some(v) => v,
+                None => return vec![],
+            },
+            _ => return vec![],
+        };
+        let violated: Vec<String> = violations.iter().map(|v| v.identifier.clone()).collect();
+        self.share_violations(&violated, annotations)
+    }
+
+    fn share_violations(&self, violated: &[String], annotations: &Annotations) -> Vec<String> {
+        // Keep those that are public, by annotation or else by prefix
+        let mut reasons: Vec<(Option<Severity>, String)> = violated
+            .iter()
+            .filter_map(|identifier| {
+                let annotation: Option<&Annotation> = annotations.get(identifier);
+                if !annotation.and_then(|a| a.public).unwrap_or_else(|| identifier.starts_with(&self.prefix)) {
+                    return None;
+                }
+                Some((annotation.and_then(|a| a.severity), annotation.and_then(|a| a.message.clone()).unwrap_or_else(|| identifier.clone())))
             })
-            .unwrap_or_default()
+            .collect();
+        // Most severe first; the sort is stable, so equally severe ones keep the reasoner's order
+        reasons.sort_by(|(lhs, _), (rhs, _)| rhs.cmp(lhs));
+        reasons.into_iter().map(|(_, reason)| reason).collect()
     }
 
     #[inline]
     fn nested_args() -> Vec<(char, &'static str, &'static str)> {
-        vec![('p', "prefix", "Any eFLINT facts that have this prefix will be shared with clients. Default: 'pub-'")]
+        vec![(
+            'p',
+            "prefix",
+            "Any eFLINT facts that have this prefix will be shared with clients, unless annotated as private in the policy. Default: 'pub-'",
+        )]
+    }
+
+    #[inline]
+    fn configuration(&self) -> serde_json::Value { serde_json::json!({ "prefix": self.prefix }) }
+}
+
+/***** TRANSPORTS *****/
+/// Defines the ways in which we can talk to an eFLINT reasoner.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransportKind {
+    /// Sends every request as a separate HTTP POST request.
+    Http,
+    /// Sends requests as newline-delimited JSON over a small pool of persistent TCP connections.
+    Tcp,
+}
+impl Display for TransportKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Http => write!(f, "http"),
+            Self::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+impl FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(Self::Http),
+            "tcp" => Ok(Self::Tcp),
+            raw => Err(raw.into()),
+        }
+    }
+}
+
+/// The transport used to send requests to the eFLINT reasoner.
+enum Transport {
+    /// Talks HTTP to the reasoner, reusing the same client for every request.
+    Http { client: reqwest::Client },
+    /// Talks raw JSON over TCP to the reasoner. Connections are opened lazily and kept, together with the address they were
+    /// opened to, in a pool of idle ones. A question takes a connection out of the pool for the duration of its exchange and
+    /// only puts it back once that completed; connections of exchanges that failed, timed out or were cancelled are dropped.
+    Tcp { pool: Mutex<Vec<(String, BufReader<TcpStream>)>> },
+}
+impl Transport {
+    /// Sends a single request over the given TCP connection and reads the response.
+    ///
+    /// # Arguments
+    /// - `stream`: The connection to send the request over.
+    /// - `body`: The serialized request to send, without a trailing newline.
+    ///
+    /// # Returns
+    /// The raw response of the reasoner.
+    ///
+    /// # Errors
+    /// This function errors if we failed to write or read.
+    async fn tcp_exchange(stream: &mut BufReader<TcpStream>, body: &str) -> Result<String, std::io::Error> {
+        stream.get_mut().write_all(body.as_bytes()).await?;
+        stream.get_mut().write_all(b"\n").await?;
+        stream.get_mut().flush().await?;
+        let mut raw_body: String = String::new();
+        if stream.read_line(&mut raw_body).await? == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "eFLINT reasoner closed the connection"));
+        }
+        Ok(raw_body)
+    }
+
+    /// Sends a single request over a pooled (or new) TCP connection, and returns the connection to the pool if that succeeded.
+    ///
+    /// # Arguments
+    /// - `pool`: The pool of idle connections. Connections in it to another address than `addr` are closed.
+    /// - `addr`: The address of the reasoner.
+    /// - `body`: The serialized request to send, without a trailing newline.
+    /// - `fresh`: Whether to always open a new connection instead of taking one from the pool.
+    ///
+    /// # Returns
+    /// The raw response of the reasoner.
+    ///
+    /// # Errors
+    /// This function errors if we failed to connect, write or read, or if the exchange took longer than [`TCP_TIMEOUT`].
+    async fn tcp_send(pool: &Mutex<Vec<(String, BufReader<TcpStream>)>>, addr: &str, body: &str, fresh: bool) -> Result<String, std::io::Error> {
+        // Take a connection out of the pool, such that nobody else uses it while we're not done with it
+        let conn: Option<BufReader<TcpStream>> = if fresh {
+            None
+        } else {
+            let mut pool = pool.lock().await;
+            pool.retain(|(conn_addr, _)| conn_addr == addr);
+            pool.pop().map(|(_, stream)| stream)
+        };
+
+        // Do the exchange on it. If this fails or is cancelled, the connection is dropped together with whatever is left of the exchange.
+        let (stream, raw_body): (BufReader<TcpStream>, String) = tokio::time::timeout(TCP_TIMEOUT, async move {
+            let mut stream: BufReader<TcpStream> = match conn {
+                Some(stream) => stream,
+                None => {
+                    debug!("Opening TCP connection to eFLINT reasoner at '{addr}'");
+                    BufReader::new(TcpStream::connect(addr).await?)
+                },
+            };
+            let raw_body: String = Self::tcp_exchange(&mut stream, body).await?;
+            Ok::<_, std::io::Error>((stream, raw_body))
+        })
+        .await
+        .map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::TimedOut, format!("eFLINT reasoner did not answer within {}s", TCP_TIMEOUT.as_secs()))
+        })??;
+
+        // Only now the connection is known to be clean, so it can be reused
+        let mut pool = pool.lock().await;
+        if pool.len() < TCP_POOL_SIZE {
+            pool.push((addr.into(), stream));
+        }
+        Ok(raw_body)
+    }
+
+    /// Sends a request to the reasoner and returns its raw response.
+    ///
+    /// # Arguments
+    /// - `addr`: The address of the reasoner.
+    /// - `request`: The eFLINT JSON [`Request`] to send.
+    ///
+    /// # Returns
+    /// The raw response body as returned by the reasoner.
+    ///
+    /// # Errors
+    /// This function errors if we failed to reach the reasoner. For TCP, the request is retried once on a new connection before giving up.
+    /// Failures that may go away when asked again (e.g., connection errors, timeouts or a `502 Bad Gateway`) are
+    /// [transient](ReasonerConnError::transient()).
+    async fn send(&self, addr: &str, request: &Request) -> Result<String, ReasonerConnError> {
+        match self {
+            Self::Http { client } => {
+                let res = client.post(addr).json(request).send().await.map_err(|err| {
+                    if err.is_connect() || err.is_timeout() || err.is_request() {
+                        ReasonerConnError::transient(err.to_string())
+                    } else {
+                        ReasonerConnError::new(err.to_string())
+                    }
+                })?;
+                let status: reqwest::StatusCode = res.status();
+                if matches!(
+                    status,
+                    reqwest::StatusCode::BAD_GATEWAY | reqwest::StatusCode::SERVICE_UNAVAILABLE | reqwest::StatusCode::GATEWAY_TIMEOUT
+                ) {
+                    return Err(ReasonerConnError::transient(format!("eFLINT reasoner at '{addr}' returned {status}")));
+                }
+                debug!("Awaiting response...");
+                res.text().await.map_err(|err| ReasonerConnError::transient(err.to_string()))
+            },
+            Self::Tcp { pool } => {
+                let body: String = serde_json::to_string(request).map_err(|err| ReasonerConnError::new(err.to_string()))?;
+                match Self::tcp_send(pool, addr, &body, false).await {
+                    Ok(raw_body) => Ok(raw_body),
+                    Err(err) => {
+                        // The connection may have gone stale; reconnect and try again once
+                        warn!("Failed to communicate with eFLINT reasoner at '{addr}' ({err}); reconnecting");
+                        Self::tcp_send(pool, addr, &body, true).await.map_err(|err| {
+                            ReasonerConnError::transient(format!("Failed to communicate with eFLINT reasoner at '{addr}' over TCP")).with_source(err)
+                        })
+                    },
+                }
+            },
+        }
     }
 }
 
 /***** LIBRARY *****/
-pub struct EFlintReasonerConnector<T: EFlintErrorHandler> {
-    pub addr:    String,
+/// An eFLINT policy in the form the [`EFlintReasonerConnector`] works with, as kept in its [`PolicyCache`].
+struct EFlintPolicy {
+    /// The phrases of the policy.
+    phrases:     Vec<Phrase>,
+    /// The annotations on the rules in the policy (see [`eflint_to_json::annotate`]).
+    annotations: Annotations,
+    /// Traces the rules in the policy back to the lines of the `.eflint` files they were compiled from (see [`eflint_to_json::sourcemap`]).
+    source_map:  SourceMap,
+    /// The names of the invariants declared in the policy, which are asked one by one for exhaustive answers.
+    invariants:  Vec<String>,
+    /// The static allow/deny lists of the policy, if it has any and the pre-filter is enabled.
+    static_list: Option<StaticList>,
+}
+
+/// The parts of the [`EFlintReasonerConnector`]'s configuration that can be changed by reloading it.
+struct EFlintConfig<T> {
+    /// The address of the reasoner.
+    addr: String,
+    /// Decides which errors of the reasoner are shared with clients.
     err_handler: T,
-    base_defs:   Vec<Phrase>,
+}
+
+pub struct EFlintReasonerConnector<T: EFlintErrorHandler> {
+    /// The nested arguments given to us, which are parsed again on a reload.
+    cli_args: String,
+    /// How we talk to the reasoner. Cannot be changed by a reload.
+    kind: TransportKind,
+    transport: Transport,
+    /// The reconfigurable parts. Questions take a snapshot when they start, such that a reload doesn't affect them.
+    config: RwLock<Arc<EFlintConfig<T>>>,
+    base_defs: Vec<Phrase>,
+    /// The policies extracted so far, such that they need not be deserialized again for every question.
+    policy_cache: PolicyCache<EFlintPolicy>,
+    /// The directory in which to keep what was exchanged with the reasoner for every question, if any.
+    debug_dir: Option<PathBuf>,
+    /// How to retry questions that the reasoner failed to answer transiently.
+    retry: RetryConfig,
+    /// How many times the reasoner may be asked to explain a single denial, if denials are explained at all.
+    explain_budget: Option<usize>,
+    /// Whether questions are checked against the static allow/deny lists of the policy before asking the reasoner.
+    prefilter: bool,
+    /// The eflint-server we manage ourselves, if any. Kept here such that it lives as long as the connector.
+    _supervisor: Option<EFlintServerSupervisor>,
 }
 
 impl<T: EFlintErrorHandler> EFlintReasonerConnector<T> {
     pub fn new(cli_args: String) -> Result<Self, Error<T::Error>> {
         info!("Creating new EFlintReasonerConnector with {} plugin", std::any::type_name::<T>());
+        let args: HashMap<String, Option<String>> = Self::parse_args(&cli_args)?;
+
+        // See what to do with it
+        let kind: TransportKind = match args.get("transport") {
+            Some(Some(raw)) => match TransportKind::from_str(raw) {
+                Ok(kind) => kind,
+                Err(raw) => return Err(Error::UnknownTransport { raw }),
+            },
+            _ => TransportKind::Http,
+        };
+        let supervisor: Option<EFlintServerSupervisor> = match EFlintServerSupervisor::from_args(&args) {
+            Ok(supervisor) => supervisor,
+            Err(err) => return Err(Error::Supervisor { err }),
+        };
+        let transport: Transport = match kind {
+            TransportKind::Http => Transport::Http { client: reqwest::Client::new() },
+            TransportKind::Tcp => Transport::Tcp { pool: Mutex::new(Vec::new()) },
+        };
+        let debug_dir: Option<PathBuf> = match args.get("debug-dir") {
+            Some(Some(dir)) => Some(dir.into()),
+            _ => None,
+        };
+        let explain_budget: Option<usize> = match args.get("explain-budget") {
+            Some(Some(raw)) => match raw.parse::<usize>() {
+                Ok(budget) => Some(budget),
+                Err(err) => return Err(Error::IllegalExplainBudget { raw: raw.clone(), err }),
+            },
+            Some(None) => Some(explain::DEFAULT_EXPLAIN_BUDGET),
+            None => None,
+        };
+        let prefilter: bool = args.get("static-list").is_some();
+        let config: EFlintConfig<T> = Self::build_config(&args, kind, supervisor.as_ref())?;
 
+        debug!("Creating new EFlintReasonerConnector to '{}' (over {kind})", config.addr);
+        let base_defs: RequestPhrases = serde_json::from_str(JSON_BASE_SPEC).unwrap();
+        Ok(EFlintReasonerConnector {
+            cli_args,
+            kind,
+            transport,
+            config: RwLock::new(Arc::new(config)),
+            base_defs: base_defs.phrases,
+            policy_cache: PolicyCache::default(),
+            debug_dir,
+            retry: RetryConfig::default(),
+            explain_budget,
+            prefilter,
+            _supervisor: supervisor,
+        })
+    }
+
+    /// Parses the nested arguments, including those in the configuration file they point to (if any).
+    ///
+    /// # Arguments
+    /// - `cli_args`: The raw nested arguments given to us.
+    ///
+    /// # Returns
+    /// The parsed arguments. Those given directly take precedence over those in the configuration file.
+    ///
+    /// # Errors
+    /// This function errors if we failed to parse the arguments, or to read or parse the configuration file.
+    fn parse_args(cli_args: &str) -> Result<HashMap<String, Option<String>>, Error<T::Error>> {
         debug!("Parsing nested arguments for EFlintReasonerConnector<{}>", std::any::type_name::<T>());
         let parser = MapParser::new(Self::cli_args());
-        let args: HashMap<String, Option<String>> = match parser.parse(&cli_args) {
+        let mut args: HashMap<String, Option<String>> = match parser.parse(cli_args) {
             Ok(args) => args,
-            Err(err) => return Err(Error::CliArgumentsParse { raw: cli_args, err }),
+            Err(err) => return Err(Error::CliArgumentsParse { raw: cli_args.into(), err }),
         };
 
-        // See what to do with it
-        let addr: String = match args.get("reasoner-address") {
-            Some(Some(path)) => path.into(),
-            _ => "http://localhost:8080".into(),
+        // Add those in the configuration file, if any, unless given directly
+        if let Some(Some(config)) = args.get("config").cloned() {
+            let config: PathBuf = config.into();
+            debug!("Reading nested arguments for EFlintReasonerConnector<{}> from '{}'...", std::any::type_name::<T>(), config.display());
+            let raw: String = match std::fs::read_to_string(&config) {
+                Ok(raw) => raw,
+                Err(err) => return Err(Error::ConfigRead { path: config, err }),
+            };
+            match parser.parse(raw.trim()) {
+                Ok(file_args) => {
+                    for (key, value) in file_args {
+                        args.entry(key).or_insert(value);
+                    }
+                },
+                Err(err) => return Err(Error::ConfigParse { path: config, err }),
+            }
+        }
+        Ok(args)
+    }
+
+    /// Builds the reconfigurable parts of the connector from its arguments.
+    ///
+    /// # Arguments
+    /// - `args`: The parsed nested arguments.
+    /// - `kind`: The [`TransportKind`] used to talk to the reasoner, which determines the default address.
+    /// - `supervisor`: The eflint-server we manage ourselves, if any, in which case its address is always used.
+    ///
+    /// # Returns
+    /// A new [`EFlintConfig`].
+    ///
+    /// # Errors
+    /// This function errors if the error handler plugin failed to parse its arguments.
+    fn build_config(
+        args: &HashMap<String, Option<String>>,
+        kind: TransportKind,
+        supervisor: Option<&EFlintServerSupervisor>,
+    ) -> Result<EFlintConfig<T>, Error<T::Error>> {
+        let addr: String = match (supervisor, args.get("reasoner-address")) {
+            (Some(supervisor), _) => match kind {
+                TransportKind::Http => format!("http://127.0.0.1:{}", supervisor.port()),
+                TransportKind::Tcp => format!("127.0.0.1:{}", supervisor.port()),
+            },
+            (None, Some(Some(path))) => path.into(),
+            (None, _) => match kind {
+                TransportKind::Http => "http://localhost:8080".into(),
+                TransportKind::Tcp => "localhost:8080".into(),
+            },
         };
-        let err_handler: T = match T::new(&args) {
+        let err_handler: T = match T::new(args) {
             Ok(handler) => handler,
             Err(err) => return Err(Error::ErrorHandler { name: std::any::type_name::<T>(), err }),
         };
+        Ok(EFlintConfig { addr, err_handler })
+    }
 
-        debug!("Creating new EFlintReasonerConnector to '{addr}'");
-        let base_defs: RequestPhrases = serde_json::from_str(JSON_BASE_SPEC).unwrap();
-        Ok(EFlintReasonerConnector { addr, base_defs: base_defs.phrases, err_handler })
+    /// Returns a snapshot of the current reconfigurable parts of the connector.
+    #[inline]
+    fn config(&self) -> Arc<EFlintConfig<T>> { self.config.read().unwrap_or_else(|err| err.into_inner()).clone() }
+
+    /// Sets how to retry questions that the reasoner failed to answer transiently.
+    ///
+    /// # Arguments
+    /// - `retry`: The [`RetryConfig`] to use.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
     }
 
     /// Returns the arguments necessary to build the parser for the EFlintReasonerConnector.
@@ -231,11 +634,48 @@ impl<T: EFlintErrorHandler> EFlintReasonerConnector<T> {
     /// A vector of arguments appropriate to use to build a [`MapParser`].
     #[inline]
     fn cli_args() -> Vec<(char, &'static str, &'static str)> {
-        let mut args: Vec<(char, &'static str, &'static str)> = vec![(
-            'r',
-            "reasoner-address",
-            "The address (as `<scheme>://<hostname>:<port>`) of the actual reasoner to connect with. Default: 'http://localhost:8080'",
-        )];
+        let mut args: Vec<(char, &'static str, &'static str)> = vec![
+            (
+                'r',
+                "reasoner-address",
+                "The address (as `<scheme>://<hostname>:<port>` for HTTP, or `<hostname>:<port>` for TCP) of the actual reasoner to connect \
+                 with. Default: 'http://localhost:8080' or 'localhost:8080', respectively",
+            ),
+            (
+                't',
+                "transport",
+                "How to talk to the reasoner; either 'http' (a POST request per question) or 'tcp' (raw JSON over persistent connections). \
+                 Default: 'http'",
+            ),
+            (
+                'd',
+                "debug-dir",
+                "A directory in which to keep the exact eFLINT JSON request and response of every question as '<REFERENCE>.json', such that \
+                 it can be replayed against a local eflint-server. Default: none (nothing is kept)",
+            ),
+            (
+                'c',
+                "config",
+                "A file with further arguments (in the same syntax), which is read again when the reasoner is reloaded. Only the reasoner \
+                 address and the arguments of the error handler can be changed that way. Arguments given directly take precedence. Default: \
+                 none",
+            ),
+            (
+                'x',
+                "explain-budget",
+                "If given, denials are explained on request by asking the reasoner again with facts and policy phrases left out, at most this \
+                 many times per denial (64 if no number is given). Note that explanations show clients the policy phrases that deny them, \
+                 whatever the error handler shares. Default: none (denials are not explained)",
+            ),
+            (
+                's',
+                "static-list",
+                "If given, questions are first checked against the static allow/deny lists in the 'static-list' content of the policy (if \
+                 any), and denied without asking the reasoner if they match a deny rule but no allow rule. Default: none (such content is \
+                 ignored)",
+            ),
+        ];
+        args.extend(EFlintServerSupervisor::cli_args());
         args.extend(T::nested_args());
         args
     }
@@ -309,28 +749,155 @@ impl<T: EFlintErrorHandler> EFlintReasonerConnector<T> {
         let function_len: usize = result.len();
         debug!("Generated {} function phrases", function_len - dataset_len);
 
+        for duty in state.duties.iter() {
+            // ```eflint
+            // +open-duty(user(#duty.holder), #duty.duty).
+            // ```
+            result.push(create!(constr_app!("open-duty", constr_app!("user", str_lit!(duty.holder.clone())), str_lit!(duty.duty.clone()))));
+        }
+        let duty_len: usize = result.len();
+        debug!("Generated {} duty phrases", duty_len - function_len);
+
+        if let Some(delegation) = &state.delegation {
+            // ```eflint
+            // +acts-on-behalf-of(user(#delegation.actor), user(#delegation.subject)).
+            // ```
+            result.push(create!(constr_app!(
+                "acts-on-behalf-of",
+                constr_app!("user", str_lit!(delegation.actor.clone())),
+                constr_app!("user", str_lit!(delegation.subject.clone()))
+            )));
+            debug!("Generated delegation phrase");
+        }
+
         result
     }
 
-    fn extract_eflint_policy(&self, policy: &Policy) -> Vec<Phrase> {
+    /// Reads the eFLINT phrases of the policy, or returns why the policy is not a valid eFLINT policy.
+    fn try_extract_eflint_policy(&self, policy: &Policy) -> Result<Vec<Phrase>, String> {
         info!("Extracting eFLINT policy...");
-        let eflint_content: Vec<&PolicyContent> = policy.content.iter().filter(|x| x.reasoner == EFLINT_JSON_ID).collect();
-        let eflint_content = eflint_content.first().unwrap();
+        let Some(eflint_content) = policy.content.iter().find(|x| x.reasoner == EFLINT_JSON_ID) else {
+            return Err(format!("Policy has no '{EFLINT_JSON_ID}' content"));
+        };
         debug!("Deserializing input to eFLINT JSON...");
         let content: &str = eflint_content.content.get();
         let result: RequestPhrases = match serde_json::from_str(content) {
             Ok(result) => match result {
                 Request::Phrases(phrases) => phrases,
-                Request::Handshake(_) | Request::Inspect(_) | Request::Ping(_) => panic!("Cannot accept non-Phrases Request input from request"),
+                Request::Handshake(_) | Request::Inspect(_) | Request::Ping(_) => {
+                    return Err("Cannot accept non-Phrases Request input from request".into());
+                },
+            },
+            Err(err) => {
+                return Err(format!(
+                    "Input is not valid eFLINT JSON: {err}\n\nInput:\n{}\n{}\n{}\n",
+                    (0..80).map(|_| '-').collect::<String>(),
+                    content,
+                    (0..80).map(|_| '-').collect::<String>()
+                ));
             },
-            Err(err) => panic!(
-                "Input is not valid eFLINT JSON: {err}\n\nInput:\n{}\n{}\n{}\n",
-                (0..80).map(|_| '-').collect::<String>(),
-                content,
-                (0..80).map(|_| '-').collect::<String>()
-            ),
         };
-        result.phrases
+        Ok(result.phrases)
+    }
+
+    /// Reads the annotations on the rules in the eFLINT policy, if any (see [`eflint_to_json::annotate`]).
+    fn extract_eflint_annotations(&self, policy: &Policy) -> Annotations {
+        match policy.content.iter().find(|x| x.reasoner == EFLINT_JSON_ID) {
+            Some(content) => annotate::annotations_of(content.content.get()),
+            None => Annotations::new(),
+        }
+    }
+
+    /// Reads the source map of the eFLINT policy, if any (see [`eflint_to_json::sourcemap`]).
+    fn extract_eflint_source_map(&self, policy: &Policy) -> SourceMap {
+        match policy.content.iter().find(|x| x.reasoner == EFLINT_JSON_ID) {
+            Some(content) => sourcemap::source_map_of(content.content.get()),
+            None => SourceMap::new(),
+        }
+    }
+
+    /// Lists the names of the invariants declared in the given eFLINT phrases.
+    fn extract_eflint_invariants(phrases: &[Phrase]) -> Vec<String> {
+        let mut invariants: Vec<String> = Vec::new();
+        for phrase in phrases.iter().filter_map(|phrase| serde_json::to_value(phrase).ok()) {
+            if phrase.get("kind").and_then(serde_json::Value::as_str) != Some("predicate")
+                || !phrase.get("is-invariant").and_then(serde_json::Value::as_bool).unwrap_or(false)
+            {
+                continue;
+            }
+            if let Some(name) = phrase.get("name").and_then(serde_json::Value::as_str) {
+                if !invariants.iter().any(|invariant| invariant == name) {
+                    invariants.push(name.into());
+                }
+            }
+        }
+        invariants
+    }
+
+    /// Reads the static allow/deny lists of the policy, if any and if the pre-filter is enabled.
+    fn extract_static_list(&self, policy: &Policy) -> Result<Option<StaticList>, String> {
+        if !self.prefilter {
+            return Ok(None);
+        }
+        StaticList::from_policy(policy)
+    }
+
+    /// Returns the phrases, annotations and static lists of the policy, extracting them only if the policy is not cached yet.
+    ///
+    /// # Errors
+    /// This function errors if the policy is not a valid eFLINT policy, or its static lists are malformed.
+    fn compiled_policy(&self, policy: &Policy) -> Result<Arc<EFlintPolicy>, ReasonerConnError> {
+        self.policy_cache
+            .get_or_try_compile(policy, |policy| {
+                let phrases: Vec<Phrase> = self.try_extract_eflint_policy(policy)?;
+                Ok(EFlintPolicy {
+                    invariants: Self::extract_eflint_invariants(&phrases),
+                    phrases,
+                    annotations: self.extract_eflint_annotations(policy),
+                    source_map: self.extract_eflint_source_map(policy),
+                    static_list: self.extract_static_list(policy)?,
+                })
+            })
+            .map_err(|err: String| ReasonerConnError::invalid(format!("Failed to prepare for policy: {err}")))
+    }
+
+    /// Checks a question against the static allow/deny lists of the policy, before asking the reasoner.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the fast-path answer with.
+    /// - `policy`: The [`Policy`] whose lists to check.
+    /// - `workflow`: The [`Workflow`] in question.
+    /// - `question`: What is asked.
+    ///
+    /// # Returns
+    /// The denial if the lists deny the question, or [`None`] if the reasoner should be asked.
+    ///
+    /// # Errors
+    /// This function errors if the denial could not be logged.
+    async fn prefilter<L: ReasonerConnectorAuditLogger + Send + Sync>(
+        &self,
+        logger: &SessionedConnectorAuditLogger<L>,
+        policy: &Policy,
+        workflow: &Workflow,
+        question: &QuestionKind,
+    ) -> Result<Option<ReasonerResponse>, ReasonerConnError> {
+        let compiled: Arc<EFlintPolicy> = self.compiled_policy(policy)?;
+        let Some(errors) = compiled.static_list.as_ref().and_then(|list| list.denial(workflow, question)) else { return Ok(None) };
+        debug!("Question denied by the static deny list; not asking the reasoner");
+
+        // Still record that (and why) we answered, in place of the reasoner's response
+        let raw: String = serde_json::json!({ STATIC_LIST_ID: { "success": false, "errors": errors } }).to_string();
+        logger.log_reasoner_response(&raw).await.map_err(|err| ReasonerConnError::new(err.to_string()))?;
+        let mut categories: Vec<DenyCategory> = Vec::new();
+        for category in errors.iter().map(|reason| categorize(reason)) {
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        if categories.is_empty() {
+            categories.push(DenyCategory::PermissionDenied);
+        }
+        Ok(Some(ReasonerResponse::new(false, errors).with_categories(categories)))
     }
 
     fn conv_workflow(&self, workflow: Workflow) -> Vec<Phrase> {
@@ -355,7 +922,62 @@ impl<T: EFlintErrorHandler> EFlintReasonerConnector<T> {
         Ok(Version(maj, min, patch))
     }
 
-    fn build_phrases(&self, policy: &Policy, state: State, workflow: Workflow, question: Phrase) -> Vec<Phrase> {
+    /// Builds the phrase that asks a question about a workflow.
+    ///
+    /// # Arguments
+    /// - `workflow`: The [`Workflow`] in question.
+    /// - `question`: What is asked.
+    ///
+    /// # Returns
+    /// A [`Phrase`] that creates the fact that the policy's invariants are checked against.
+    fn question_phrase(workflow: &Workflow, question: QuestionKind) -> Phrase {
+        match question {
+            QuestionKind::ExecuteTask { task } => {
+                // ```eflint
+                // +task-to-execute(task(node(workflow(#workflow.id), #task))).
+                // ```
+                create!(constr_app!(
+                    "task-to-execute",
+                    constr_app!("task", constr_app!("node", constr_app!("workflow", str_lit!(workflow.id.clone())), str_lit!(task)))
+                ))
+            },
+            // Determine if we're asking for a node-to-node data transfer (there's a task as context) or a node-to-user (there's no task).
+            QuestionKind::AccessData { data, task: Some(task_id) } => {
+                // ```eflint
+                // +dataset-to-transfer(node-input(node(workflow(#workflow.id), #task), asset(#data))).
+                // ```
+                create!(constr_app!(
+                    "dataset-to-transfer",
+                    constr_app!(
+                        "node-input",
+                        constr_app!("node", constr_app!("workflow", str_lit!(workflow.id.clone())), str_lit!(task_id)),
+                        constr_app!("asset", str_lit!(data)),
+                    )
+                ))
+            },
+            QuestionKind::AccessData { data, task: None } => {
+                // ```eflint
+                // +result-to-transfer(workflow-result-recipient(workflow-result(workflow(#workflow.id), asset(#data)), user(#workflow.user))).
+                // ```
+                create!(constr_app!(
+                    "result-to-transfer",
+                    constr_app!(
+                        "workflow-result-recipient",
+                        constr_app!("workflow-result", constr_app!("workflow", str_lit!(workflow.id.clone())), constr_app!("asset", str_lit!(data))),
+                        constr_app!("user", str_lit!(workflow.user.name.clone()))
+                    )
+                ))
+            },
+            QuestionKind::ValidateWorkflow => {
+                // ```eflint
+                // +workflow-to-execute(workflow(#workflow.id)).
+                // ```
+                create!(constr_app!("workflow-to-execute", constr_app!("workflow", str_lit!(workflow.id.clone()))))
+            },
+        }
+    }
+
+    fn build_phrases(&self, policy: &Policy, state: State, workflow: Workflow, question: Phrase) -> Result<Vec<Phrase>, ReasonerConnError> {
         let mut phrases = Vec::<Phrase>::new();
 
         // Build request
@@ -378,41 +1000,89 @@ impl<T: EFlintErrorHandler> EFlintReasonerConnector<T> {
         phrases.extend(workflow_phrases);
 
         // 5. Add Policy
-        let policy_phrases: Vec<Phrase> = self.extract_eflint_policy(policy);
-        debug!("Loading policy ({} phrase(s))", policy_phrases.len());
-        phrases.extend(policy_phrases);
+        let compiled: Arc<EFlintPolicy> = self.compiled_policy(policy)?;
+        debug!("Loading policy ({} phrase(s))", compiled.phrases.len());
+        phrases.extend(compiled.phrases.iter().cloned());
 
-        phrases
+        Ok(phrases)
     }
 
-    async fn process_phrases<L: ReasonerConnectorAuditLogger + Send + Sync>(
+    /// Keeps what was exchanged with the reasoner for a question, such that it can be replayed offline.
+    ///
+    /// Failing to do so does not affect the question; it is only logged.
+    ///
+    /// # Arguments
+    /// - `dir`: The directory to write the bundle to.
+    /// - `addr`: The address of the reasoner that was asked.
+    /// - `reference`: The verdict reference of the question, used as the bundle's name.
+    /// - `request`: The eFLINT JSON [`Request`] sent to the reasoner.
+    /// - `raw_body`: The raw response of the reasoner.
+    async fn write_debug_bundle(&self, dir: &Path, addr: &str, reference: &str, request: &Request, raw_body: &str) {
+        let bundle: serde_json::Value = serde_json::json!({
+            "reference": reference,
+            "reasoner_address": addr,
+            "request": request,
+            "raw_response": raw_body,
+            "response": serde_json::from_str::<serde_json::Value>(raw_body).ok(),
+        });
+        let path: PathBuf = dir.join(format!("{reference}.json"));
+        let res: Result<(), std::io::Error> = async {
+            tokio::fs::create_dir_all(dir).await?;
+            tokio::fs::write(&path, serde_json::to_vec_pretty(&bundle)?).await
+        }
+        .await;
+        if let Err(err) = res {
+            warn!("Failed to write eFLINT debug bundle '{}': {err}", path.display());
+        }
+    }
+
+    /// Sends phrases to the reasoner and logs its raw response.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the response with.
+    /// - `policy`: The [`Policy`] that determines the eFLINT version to use.
+    /// - `phrases`: The phrases to send, ending with the question.
+    ///
+    /// # Returns
+    /// The raw response, the parsed response and the configuration that the question was asked with.
+    ///
+    /// # Errors
+    /// This function errors if the reasoner could not be reached, or its response could not be logged or parsed.
+    async fn send_phrases<L: ReasonerConnectorAuditLogger + Send + Sync>(
         &self,
-        logger: SessionedConnectorAuditLogger<L>,
+        logger: &SessionedConnectorAuditLogger<L>,
         policy: &Policy,
         phrases: Vec<Phrase>,
-    ) -> Result<ReasonerResponse, ReasonerConnError> {
+    ) -> Result<(String, ResponsePhrases, Arc<EFlintConfig<T>>), ReasonerConnError> {
         let version = self.extract_eflint_version(policy).map_err(ReasonerConnError::new)?;
         debug!("Full request length: {} phrase(s)", phrases.len());
         let request = Request::Phrases(RequestPhrases { common: RequestCommon { version, extensions: HashMap::new() }, phrases, updates: true });
         debug!("Full request:\n\n{}\n\n", serde_json::to_string_pretty(&request).unwrap_or_else(|_| "<serialization failure>".into()));
 
-        // Make request
-        debug!("Sending eFLINT exec-task request to '{}'", self.addr);
-        let client = reqwest::Client::new();
-        let res = client.post(&self.addr).json(&request).send().await.map_err(|err| ReasonerConnError::new(err.to_string()))?;
-
-        debug!("Awaiting response...");
-        let raw_body = res.text().await.map_err(|err| ReasonerConnError::new(err.to_string()))?;
+        // Make request, sticking to the same configuration for the whole question
+        let config: Arc<EFlintConfig<T>> = self.config();
+        debug!("Sending eFLINT request to '{}'", config.addr);
+        let (raw_body, retries) = logger.time_reasoner(self.retry.run(|| self.transport.send(&config.addr, &request))).await;
+        let raw_body: String = raw_body?;
 
         debug!("Log raw response...");
 
-        logger.log_reasoner_response(&raw_body).await.map_err(|err| {
+        logger.log_reasoner_response_after_retries(&raw_body, retries).await.map_err(|err| {
             debug!("Error trying to log{:?}", err);
             ReasonerConnError::new(err.to_string())
         })?;
+        let raw_request: String = serde_json::to_string(&request).map_err(|err| ReasonerConnError::new(err.to_string()))?;
+        match logger.archive_exchange(&raw_request, &raw_body).await {
+            Ok(()) => {},
+            Err(AuditLoggerError::CouldNotArchive(err)) => warn!("Failed to archive eFLINT exchange: {err}"),
+            Err(err) => return Err(ReasonerConnError::new(err.to_string())),
+        }
+        if let Some(dir) = &self.debug_dir {
+            self.write_debug_bundle(dir, &config.addr, &logger.reference, &request, &raw_body).await;
+        }
 
         debug!("Parsing response...");
-        let response = serde_json::from_str::<eflint_json::spec::ResponsePhrases>(&raw_body).map_err(|err| {
+        let response = serde_json::from_str::<ResponsePhrases>(&raw_body).map_err(|err| {
             error!(
                 "{}\n\nRaw response:\n{}\n{}\n{}\n",
                 err,
@@ -422,33 +1092,144 @@ impl<T: EFlintErrorHandler> EFlintReasonerConnector<T> {
             );
             ReasonerConnError::new(err.to_string())
         })?;
+        Ok((raw_body, response, config))
+    }
+
+    /// Asks the reasoner the question in the given phrases, to see why it is denied.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the response with.
+    /// - `policy`: The [`Policy`] that determines the eFLINT version to use.
+    /// - `phrases`: The phrases to send, including the question.
+    ///
+    /// # Returns
+    /// The reasons for denial as shared by the error handler, or [`None`] if the question is allowed or the reasoner failed
+    /// to process the phrases (e.g., because a type declaration was left out).
+    ///
+    /// # Errors
+    /// This function errors if the reasoner could not be reached, or its response could not be logged or parsed.
+    async fn denial_of<L: ReasonerConnectorAuditLogger + Send + Sync>(
+        &self,
+        logger: &SessionedConnectorAuditLogger<L>,
+        policy: &Policy,
+        phrases: Vec<Phrase>,
+    ) -> Result<Option<Vec<String>>, ReasonerConnError> {
+        let (_, response, config) = self.send_phrases(logger, policy, phrases).await?;
+        let errors: Vec<String> = config.err_handler.extract_errors(response.results.last(), &self.compiled_policy(policy)?.annotations);
+        let answer: BackendAnswer = BackendAnswer::from_eflint(&response, errors);
+        // A failing reasoner denies too, but that's not the denial being explained
+        if !answer.success {
+            return Ok(None);
+        }
+        match interpret(&answer) {
+            Ok(res) if !res.success => Ok(Some(res.errors)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Asks the reasoner which of the given invariants are violated after the given phrases, one boolean query each.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the response with.
+    /// - `policy`: The [`Policy`] that determines the eFLINT version to use.
+    /// - `phrases`: The phrases of the question, after which to ask the queries.
+    /// - `invariants`: The names of the invariants to ask about.
+    ///
+    /// # Returns
+    /// The names of the invariants that do not hold, or none if the reasoner failed to process the phrases.
+    ///
+    /// # Errors
+    /// This function errors if the reasoner could not be reached, or its response could not be logged or parsed.
+    async fn violated_invariants<L: ReasonerConnectorAuditLogger + Send + Sync>(
+        &self,
+        logger: &SessionedConnectorAuditLogger<L>,
+        policy: &Policy,
+        mut phrases: Vec<Phrase>,
+        invariants: &[&String],
+    ) -> Result<Vec<String>, ReasonerConnError> {
+        // Ask every invariant as a boolean query
+        // ```eflint
+        // ?#invariant.
+        // ```
+        for invariant in invariants {
+            let query: Phrase =
+                serde_json::from_value(serde_json::json!({ "kind": "bquery", "expression": { "identifier": invariant, "operands": [] } }))
+                    .map_err(|err| ReasonerConnError::new(format!("Failed to build query for invariant '{invariant}'")).with_source(err))?;
+            phrases.push(query);
+        }
+        let (_, response, _) = self.send_phrases(logger, policy, phrases).await?;
+        if !response.common.success {
+            warn!("Reasoner failed to answer queries for {} invariant(s); only reporting the violations found at first", invariants.len());
+            return Ok(vec![]);
+        }
+
+        // The answers to the queries are the last ones, in order
+        let answers: &[PhraseResult] = &response.results[response.results.len().saturating_sub(invariants.len())..];
+        Ok(invariants
+            .iter()
+            .zip(answers)
+            .filter(|(_, answer)| matches!(answer, PhraseResult::BooleanQuery(answer) if !answer.result))
+            .map(|(invariant, _)| (*invariant).clone())
+            .collect())
+    }
+
+    /// Asks the reasoner the question in the given phrases.
+    ///
+    /// # Arguments
+    /// - `logger`: The [`SessionedConnectorAuditLogger`] to log the response with.
+    /// - `policy`: The [`Policy`] that determines the eFLINT version to use.
+    /// - `phrases`: The phrases to send, including the question.
+    /// - `exhaustive`: Whether to report all violated invariants (asking those not reported by the question one by one), or
+    ///   only the first, or [`None`] to report what the reasoner reports without saying which [`Granularity`] that is.
+    ///
+    /// # Errors
+    /// This function errors if the reasoner could not be reached, or its response could not be logged or parsed.
+    async fn process_phrases<L: ReasonerConnectorAuditLogger + Send + Sync>(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: &Policy,
+        phrases: Vec<Phrase>,
+        exhaustive: Option<bool>,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        let question: Option<Vec<Phrase>> = (exhaustive == Some(true)).then(|| phrases.clone());
+        let (_, response, config) = self.send_phrases(&logger, policy, phrases).await?;
 
         debug!("Analysing response...");
-        let errors: Vec<String> = self.err_handler.extract_errors(response.results.last());
-
-        // TODO proper handle invalid query and unexpected result
-        let success: Result<bool, String> = response
-            .results
-            .last()
-            .map(|r| match r {
-                eflint_json::spec::PhraseResult::BooleanQuery(r) => Ok(r.result),
-                eflint_json::spec::PhraseResult::InstanceQuery(_) => Err("Invalid query".into()),
-                eflint_json::spec::PhraseResult::StateChange(r) => Ok(!r.violated),
-            })
-            .unwrap_or_else(|| Err("Unexpected result".into()));
-
-        match success {
-            Ok(success) => {
-                debug!(
-                    "Response judged as: {} ({} && {})",
-                    if success && response.common.success { "success" } else { "violated" },
-                    success,
-                    response.common.success
-                );
-                Ok(ReasonerResponse::new(success && response.common.success, errors))
+        let compiled: Arc<EFlintPolicy> = self.compiled_policy(policy)?;
+        if let Some(PhraseResult::StateChange(sc)) = response.results.last() {
+            for violation in sc.violations.iter().flatten() {
+                if let Some(position) = compiled.source_map.declaration(&violation.identifier) {
+                    debug!("Violated '{}' is declared at {position}", violation.identifier);
+                }
+            }
+        }
+        let errors: Vec<String> = config.err_handler.extract_errors(response.results.last(), &compiled.annotations);
+        let mut res: ReasonerResponse = interpret(&BackendAnswer::from_eflint(&response, errors))?;
+        match exhaustive {
+            None => Ok(res),
+            Some(false) => {
+                // The reasons are sorted most severe first, so keep the one that matters most
+                res.errors.truncate(1);
+                Ok(res.with_granularity(Granularity::FailFast))
+            },
+            Some(true) => {
+                let Some(question) = question.filter(|_| !res.success && response.common.success) else {
+                    return Ok(res.with_granularity(Granularity::Exhaustive));
+                };
+
+                // Find out which invariants are violated that the reasoner didn't report
+                let mut violated: Vec<String> = match response.results.last() {
+                    Some(PhraseResult::StateChange(sc)) => sc.violations.iter().flatten().map(|v| v.identifier.clone()).collect(),
+                    _ => vec![],
+                };
+                let unreported: Vec<&String> = compiled.invariants.iter().filter(|invariant| !violated.contains(invariant)).collect();
+                if !unreported.is_empty() {
+                    debug!("Asking {} unreported invariant(s) one by one...", unreported.len());
+                    violated.extend(self.violated_invariants(&logger, policy, question, &unreported).await?);
+                }
+                let errors: Vec<String> = config.err_handler.share_violations(&violated, &compiled.annotations);
+                Ok(interpret(&BackendAnswer::from_eflint(&response, errors))?.with_granularity(Granularity::Exhaustive))
             },
-            // TODO better error handling
-            Err(err) => Err(ReasonerConnError::new(err)),
         }
     }
 }
@@ -504,19 +1285,16 @@ impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static, T: EFlintErrorHand
         task: String,
     ) -> Result<ReasonerResponse, ReasonerConnError> {
         info!("Considering task '{}' in workflow '{}' for execution", task, workflow.id);
-
-        // Add the question for this task
-        // ```eflint
-        // +task-to-execute(task(node(workflow(#workflow.id), #task))).
-        // ```
-        let question: Phrase = create!(constr_app!(
-            "task-to-execute",
-            constr_app!("task", constr_app!("node", constr_app!("workflow", str_lit!(workflow.id.clone())), str_lit!(task)))
-        ));
+        let question: QuestionKind = QuestionKind::ExecuteTask { task };
+        if let Some(response) = self.prefilter(&logger, &policy, &workflow, &question).await? {
+            return Ok(response);
+        }
+        let question: Phrase = Self::question_phrase(&workflow, question);
 
         // Build & submit the phrases with the given policy, state, workflow _and_ question
-        let phrases = self.build_phrases(&policy, state, workflow, question);
-        self.process_phrases(logger, &policy, phrases).await
+        let exhaustive: Option<bool> = state.exhaustive;
+        let phrases = self.build_phrases(&policy, state, workflow, question)?;
+        self.process_phrases(logger, &policy, phrases, exhaustive).await
     }
 
     async fn access_data_request(
@@ -528,42 +1306,19 @@ impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static, T: EFlintErrorHand
         data: String,
         task: Option<String>,
     ) -> Result<ReasonerResponse, ReasonerConnError> {
-        // Determine if we're asking for a node-to-node data transfer (there's a task as context) or a node-to-user (there's no task).
-        let question: Phrase = match task {
-            Some(task_id) => {
-                info!("Considering data access '{}' for task '{}' in workflow '{}'", data, task_id, workflow.id);
-
-                // ```eflint
-                // +dataset-to-transfer(node-input(node(workflow(#workflow.id), #task), asset(#data))).
-                // ```
-                create!(constr_app!(
-                    "dataset-to-transfer",
-                    constr_app!(
-                        "node-input",
-                        constr_app!("node", constr_app!("workflow", str_lit!(workflow.id.clone())), str_lit!(task_id)),
-                        constr_app!("asset", str_lit!(data)),
-                    )
-                ))
-            },
-            None => {
-                info!("Considering data access '{}' for result of workflow '{}'", data, workflow.id);
-
-                // ```eflint
-                // +result-to-transfer(workflow-result-recipient(workflow-result(workflow(#workflow.id), asset(#data)), user(#workflow.user))).
-                // ```
-                create!(constr_app!(
-                    "result-to-transfer",
-                    constr_app!(
-                        "workflow-result-recipient",
-                        constr_app!("workflow-result", constr_app!("workflow", str_lit!(workflow.id.clone())), constr_app!("asset", str_lit!(data))),
-                        constr_app!("user", str_lit!(workflow.user.name.clone()))
-                    )
-                ))
-            },
-        };
+        match &task {
+            Some(task_id) => info!("Considering data access '{}' for task '{}' in workflow '{}'", data, task_id, workflow.id),
+            None => info!("Considering data access '{}' for result of workflow '{}'", data, workflow.id),
+        }
+        let question: QuestionKind = QuestionKind::AccessData { data, task };
+        if let Some(response) = self.prefilter(&logger, &policy, &workflow, &question).await? {
+            return Ok(response);
+        }
+        let question: Phrase = Self::question_phrase(&workflow, question);
 
-        let phrases = self.build_phrases(&policy, state, workflow, question);
-        self.process_phrases(logger, &policy, phrases).await
+        let exhaustive: Option<bool> = state.exhaustive;
+        let phrases = self.build_phrases(&policy, state, workflow, question)?;
+        self.process_phrases(logger, &policy, phrases, exhaustive).await
     }
 
     async fn workflow_validation_request(
@@ -574,15 +1329,282 @@ impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static, T: EFlintErrorHand
         workflow: Workflow,
     ) -> Result<ReasonerResponse, ReasonerConnError> {
         info!("Considering workflow '{}'", workflow.id);
+        if let Some(response) = self.prefilter(&logger, &policy, &workflow, &QuestionKind::ValidateWorkflow).await? {
+            return Ok(response);
+        }
+        let question: Phrase = Self::question_phrase(&workflow, QuestionKind::ValidateWorkflow);
 
-        // Add the question for this task
+        // Build & submit the phrases with the given policy, state, workflow _and_ question
+        let exhaustive: Option<bool> = state.exhaustive;
+        let phrases = self.build_phrases(&policy, state, workflow, question)?;
+        self.process_phrases(logger, &policy, phrases, exhaustive).await
+    }
+
+    async fn query(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        query: serde_json::Value,
+    ) -> Result<ReasonerQueryResponse, ReasonerConnError> {
+        info!("Considering query in use-case '{}'", state.use_case);
+
+        // Ask the query as an instance query
         // ```eflint
-        // +workflow-to-execute(workflow(#workflow.id)).
+        // ?-#query.
         // ```
-        let question = create!(constr_app!("workflow-to-execute", constr_app!("workflow", str_lit!(workflow.id.clone()))));
+        let question: Phrase = serde_json::from_value(serde_json::json!({ "kind": "iquery", "expression": query }))
+            .map_err(|err| ReasonerConnError::invalid("Query is not a valid eFLINT JSON expression").with_source(err))?;
 
-        // Build & submit the phrases with the given policy, state, workflow _and_ question
-        let phrases = self.build_phrases(&policy, state, workflow, question);
-        self.process_phrases(logger, &policy, phrases).await
+        // Build & submit the phrases with the given policy and state; the query must come last, to be answered with all of them
+        let mut phrases: Vec<Phrase> = self.base_defs.clone();
+        phrases.extend(self.conv_state_to_eflint(state));
+        phrases.extend(self.compiled_policy(&policy)?.phrases.iter().cloned());
+        phrases.push(question);
+        let (raw_body, response, _) = self.send_phrases(&logger, &policy, phrases).await?;
+
+        debug!("Analysing response...");
+        let raw: serde_json::Value = serde_json::from_str(&raw_body).unwrap_or(serde_json::Value::Null);
+        interpret_query(&BackendAnswer { instances: eflint_instances(&raw), ..BackendAnswer::from_eflint(&response, vec![]) })
+    }
+
+    async fn explain(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        question: QuestionKind,
+    ) -> Result<Explanation, ReasonerConnError> {
+        let Some(budget) = self.explain_budget else {
+            return Err(ReasonerConnError::invalid("This reasoner connector is not configured to explain denials"));
+        };
+        info!("Explaining denial in workflow '{}' (at most {budget} question(s))", workflow.id);
+        let question: Phrase = Self::question_phrase(&workflow, question);
+
+        // Anything may be left out except for the interface and the question itself. The state comes before the question,
+        // the rest after it, as when the question was asked.
+        let mut candidates: Vec<Phrase> = self.conv_state_to_eflint(state);
+        let state_len: usize = candidates.len();
+        candidates.extend(self.conv_workflow(workflow));
+        candidates.extend(self.compiled_policy(&policy)?.phrases.iter().cloned());
+        let assemble = |subset: &[usize]| -> Vec<Phrase> {
+            let mut phrases: Vec<Phrase> = self.base_defs.clone();
+            phrases.extend(subset.iter().filter(|i| **i < state_len).map(|i| candidates[*i].clone()));
+            phrases.push(question.clone());
+            phrases.extend(subset.iter().filter(|i| **i >= state_len).map(|i| candidates[*i].clone()));
+            phrases
+        };
+
+        // Find out what's to be explained first
+        let all: Vec<usize> = (0..candidates.len()).collect();
+        let violations: Vec<String> = match self.denial_of(&logger, &policy, assemble(&all)).await? {
+            Some(violations) => violations,
+            None => return Err(ReasonerConnError::invalid("The question is not denied, so there is nothing to explain")),
+        };
+
+        // Then leave out whatever isn't needed to keep (at least) the same violations
+        let (logger, policy, violations_ref) = (&logger, &policy, &violations);
+        let (conflicting, minimal) = explain::minimize(all, budget.saturating_sub(1), |subset: Vec<usize>| {
+            let phrases: Vec<Phrase> = assemble(&subset);
+            async move {
+                match self.denial_of(logger, policy, phrases).await {
+                    Ok(Some(found)) => violations_ref.iter().all(|violation| found.contains(violation)),
+                    Ok(None) => false,
+                    Err(err) => {
+                        warn!("Failed to ask reasoner while explaining denial: {err}");
+                        false
+                    },
+                }
+            }
+        })
+        .await;
+        debug!("Explained denial with {} of {} phrase(s) (minimal: {minimal})", conflicting.len(), candidates.len());
+
+        let conflicting: Vec<serde_json::Value> = conflicting
+            .into_iter()
+            .map(|i| serde_json::to_value(&candidates[i]))
+            .collect::<Result<_, _>>()
+            .map_err(|err| ReasonerConnError::new("Failed to serialize eFLINT phrase").with_source(err))?;
+        Ok(Explanation { violations, conflicting, minimal })
+    }
+
+    fn compose(&self, fragments: &[Policy]) -> Result<Vec<PolicyContent>, PolicyCompositionError> {
+        info!("Composing {} eFLINT policy fragment(s)...", fragments.len());
+
+        // Concatenate the phrases of all fragments, keeping track of which declared what
+        let mut conflicts: Vec<String> = Vec::new();
+        let mut composed: Option<(String, RequestPhrases)> = None;
+        let mut declared: HashMap<(String, String), (i64, serde_json::Value)> = HashMap::new();
+        let mut annotations: HashMap<String, (i64, Annotation)> = HashMap::new();
+        let mut source_map: SourceMap = SourceMap::new();
+        let mut static_list: Option<(String, StaticList)> = None;
+        for fragment in fragments {
+            let id: i64 = fragment.version.version.unwrap_or(-1);
+
+            // Static lists are simply concatenated, as rules never conflict
+            if let Some(content) = fragment.content.iter().find(|c| c.reasoner == STATIC_LIST_ID) {
+                match StaticList::from_content(content) {
+                    Ok(list) => {
+                        let (_, target): &mut (String, StaticList) =
+                            static_list.get_or_insert_with(|| (content.reasoner_version.clone(), StaticList::default()));
+                        target.deny.extend(list.deny);
+                        target.allow.extend(list.allow);
+                    },
+                    Err(err) => conflicts.push(format!("Fragment {id}: {err}")),
+                }
+            }
+
+            let content: &PolicyContent = match fragment.content.iter().find(|c| c.reasoner == EFLINT_JSON_ID) {
+                Some(content) => content,
+                None => {
+                    conflicts.push(format!("Fragment {id} has no '{EFLINT_JSON_ID}' content"));
+                    continue;
+                },
+            };
+            let phrases: RequestPhrases = match serde_json::from_str(content.content.get()) {
+                Ok(Request::Phrases(phrases)) => phrases,
+                Ok(_) => {
+                    conflicts.push(format!("Fragment {id} is not an eFLINT JSON phrases request"));
+                    continue;
+                },
+                Err(err) => {
+                    conflicts.push(format!("Fragment {id} is not valid eFLINT JSON: {err}"));
+                    continue;
+                },
+            };
+
+            // Merge it with what we have so far
+            if composed.is_none() {
+                composed = Some((content.reasoner_version.clone(), RequestPhrases { phrases: Vec::new(), ..phrases.clone() }));
+            }
+            let (version, target): &mut (String, RequestPhrases) = composed.as_mut().unwrap();
+            if *version != content.reasoner_version {
+                conflicts.push(format!("Fragment {id} targets eFLINT {} instead of {}", content.reasoner_version, version));
+                continue;
+            }
+            for phrase in phrases.phrases {
+                // Declarations are identified by their kind and name; the same declaration in two fragments is only added once
+                let value: serde_json::Value = serde_json::to_value(&phrase).unwrap_or(serde_json::Value::Null);
+                let key: Option<(String, String)> = match (value.get("kind").and_then(|k| k.as_str()), value.get("name").and_then(|n| n.as_str())) {
+                    (Some(kind), Some(name)) => Some((kind.into(), name.into())),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    match declared.get(&key) {
+                        Some((_, existing)) if *existing == value => continue,
+                        Some((other, _)) => {
+                            conflicts.push(format!("Fragments {other} and {id} declare {} '{}' differently", key.0, key.1));
+                            continue;
+                        },
+                        None => {
+                            declared.insert(key, (id, value));
+                        },
+                    }
+                }
+                target.phrases.push(phrase);
+            }
+
+            // Annotations are merged the same way
+            for (name, annotation) in annotate::annotations_of(content.content.get()) {
+                match annotations.get(&name) {
+                    Some((_, existing)) if *existing == annotation => {},
+                    Some((other, _)) => conflicts.push(format!("Fragments {other} and {id} annotate '{name}' differently")),
+                    None => {
+                        annotations.insert(name, (id, annotation));
+                    },
+                }
+            }
+
+            // Only where things were declared is kept of the source maps, since the lines of the fragments are not those of the composed policy
+            for (name, position) in sourcemap::source_map_of(content.content.get()).declarations {
+                source_map.declarations.entry(name).or_insert(position);
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(PolicyCompositionError { conflicts });
+        }
+
+        // Serialize the result as a single content
+        let (version, composed): (String, RequestPhrases) = match composed {
+            Some(composed) => composed,
+            None => return Err(PolicyCompositionError { conflicts: vec!["No eFLINT policy fragments given".into()] }),
+        };
+        let annotations: Annotations = annotations.into_iter().map(|(name, (_, annotation))| (name, annotation)).collect();
+        let composed: Result<serde_json::Value, serde_json::Error> = serde_json::to_value(Request::Phrases(composed)).map(|mut composed| {
+            annotate::attach(&mut composed, &annotations);
+            sourcemap::attach(&mut composed, &source_map);
+            composed
+        });
+        let mut contents: Vec<PolicyContent> = match composed.and_then(|composed| serde_json::value::to_raw_value(&composed)) {
+            Ok(content) => vec![PolicyContent { reasoner: EFLINT_JSON_ID.into(), reasoner_version: version, content: content.into() }],
+            Err(err) => return Err(PolicyCompositionError { conflicts: vec![format!("Failed to serialize composed eFLINT policy: {err}")] }),
+        };
+        if let Some((version, list)) = static_list {
+            match serde_json::value::to_raw_value(&list) {
+                Ok(content) => contents.push(PolicyContent { reasoner: STATIC_LIST_ID.into(), reasoner_version: version, content: content.into() }),
+                Err(err) => return Err(PolicyCompositionError { conflicts: vec![format!("Failed to serialize composed static lists: {err}")] }),
+            }
+        }
+        Ok(contents)
+    }
+
+    #[inline]
+    fn capabilities(&self) -> ReasonerCapabilities {
+        let capabilities: ReasonerCapabilities = ReasonerCapabilities::new(&Self::context()).with_queries();
+        let capabilities: ReasonerCapabilities = if self.explain_budget.is_some() { capabilities.with_explanations() } else { capabilities };
+        capabilities.with_options(Self::cli_args())
+    }
+
+    async fn debug_bundle(&self, reference: &str) -> Result<Option<serde_json::Value>, ReasonerConnError> {
+        let Some(dir) = &self.debug_dir else { return Ok(None) };
+        // References are UUIDs (optionally prefixed); refuse anything that could escape the directory
+        if reference.is_empty() || !reference.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Ok(None);
+        }
+        match tokio::fs::read(dir.join(format!("{reference}.json"))).await {
+            Ok(raw) => serde_json::from_slice(&raw).map(Some).map_err(ReasonerConnError::from),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(ReasonerConnError::from(err)),
+        }
+    }
+
+    async fn reload(&self) -> Result<(), ReasonerConnError> {
+        info!("Reloading EFlintReasonerConnector with {} plugin", std::any::type_name::<T>());
+        let config: EFlintConfig<T> = Self::parse_args(&self.cli_args)
+            .and_then(|args| Self::build_config(&args, self.kind, self._supervisor.as_ref()))
+            .map_err(|err| ReasonerConnError::new(err.trace().to_string()))?;
+
+        // Questions that already started keep asking the old reasoner (see `EFlintReasonerConnector::config()`)
+        let old_addr: String = self.config().addr.clone();
+        if config.addr != old_addr {
+            info!("Switching eFLINT reasoner from '{old_addr}' to '{}'", config.addr);
+        }
+        *self.config.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(config);
+        Ok(())
+    }
+
+    async fn on_policy_activated(&self, policy: &Policy) -> Result<(), ReasonerConnError> {
+        // Drop the old policy, and deserialize the new one before the first question needs it
+        self.policy_cache.invalidate();
+        info!("Preparing for eFLINT policy version {}", policy.version.version.unwrap_or(-1));
+        self.compiled_policy(policy).map(|_| ())
+    }
+
+    #[inline]
+    async fn on_policy_deactivated(&self) -> Result<(), ReasonerConnError> {
+        self.policy_cache.invalidate();
+        Ok(())
+    }
+
+    #[inline]
+    fn metrics(&self) -> Vec<ReasonerMetric> { self.policy_cache.metrics() }
+
+    fn configuration(&self) -> serde_json::Value {
+        let config: Arc<EFlintConfig<T>> = self.config();
+        serde_json::json!({
+            "reasoner_address": config.addr,
+            "error_handler": config.err_handler.configuration(),
+        })
     }
 }
@@ -0,0 +1,274 @@
+//! Supervises a local `eflint-server` process on behalf of the [`EFlintReasonerConnector`](super::eflint::EFlintReasonerConnector).
+//!
+//! In single-node deployments, it is often more convenient to have the policy reasoner spawn the eFLINT reasoner
+//! itself instead of orchestrating a separate container for it. The [`EFlintServerSupervisor`] does exactly that: it
+//! spawns the given binary on a (possibly automatically allocated) port, periodically checks that it is still alive and
+//! accepting connections, and restarts it if it isn't.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/***** CONSTANTS *****/
+/// The placeholder in the server arguments that is replaced with the allocated port.
+const PORT_PLACEHOLDER: &'static str = "{port}";
+
+/// The default arguments given to the spawned server.
+const DEFAULT_SERVER_ARGS: &'static str = "{port}";
+/// The default time (in milliseconds) between health checks.
+const DEFAULT_HEALTH_INTERVAL_MS: u64 = 5000;
+/// The default number of consecutive restarts after which we give up.
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/***** ERRORS *****/
+/// Defines errors that originate from the [`EFlintServerSupervisor`].
+#[derive(Debug)]
+pub enum Error {
+    /// A numeric argument was not a valid number.
+    IllegalNumber { what: &'static str, raw: String, err: ParseIntError },
+    /// Failed to find a free port to run the server on.
+    PortAllocate { err: std::io::Error },
+    /// Failed to spawn the eflint-server process.
+    Spawn { cmd: String, err: std::io::Error },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            IllegalNumber { what, raw, .. } => write!(f, "Failed to parse '{raw}' as a {what}"),
+            PortAllocate { .. } => write!(f, "Failed to allocate a free port for the eflint-server"),
+            Spawn { cmd, .. } => write!(f, "Failed to spawn eflint-server {cmd:?}"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            IllegalNumber { err, .. } => Some(err),
+            PortAllocate { err } => Some(err),
+            Spawn { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** HELPER FUNCTIONS *****/
+/// Parses an optional numeric argument, falling back to a default if it wasn't given.
+///
+/// # Arguments
+/// - `args`: The parsed nested CLI arguments.
+/// - `key`: The key of the argument to parse.
+/// - `what`: A description of the argument, used in errors.
+/// - `default`: The value to return if the argument is missing.
+///
+/// # Returns
+/// The parsed number.
+///
+/// # Errors
+/// This function errors if the argument was given but not a valid number.
+fn parse_number<T: std::str::FromStr<Err = ParseIntError>>(
+    args: &HashMap<String, Option<String>>,
+    key: &str,
+    what: &'static str,
+    default: T,
+) -> Result<T, Error> {
+    match args.get(key) {
+        Some(Some(raw)) => raw.parse::<T>().map_err(|err| Error::IllegalNumber { what, raw: raw.clone(), err }),
+        _ => Ok(default),
+    }
+}
+
+/// Spawns the eflint-server process.
+///
+/// # Arguments
+/// - `binary`: The path to the eflint-server binary.
+/// - `args`: The arguments to give it.
+///
+/// # Returns
+/// A handle to the new [`Child`].
+///
+/// # Errors
+/// This function errors if we failed to spawn the process.
+fn spawn_server(binary: &Path, args: &[String]) -> Result<Child, Error> {
+    let mut cmd: Command = Command::new(binary);
+    cmd.args(args);
+    cmd.stdin(Stdio::null());
+    cmd.kill_on_drop(true);
+    debug!("Spawning eflint-server {cmd:?}");
+    cmd.spawn().map_err(|err| Error::Spawn { cmd: format!("{cmd:?}"), err })
+}
+
+/// Checks whether the server accepts connections on the given address.
+///
+/// # Arguments
+/// - `addr`: The address to connect to.
+/// - `timeout`: The maximum time to wait for the connection.
+///
+/// # Returns
+/// True if the server could be reached, or false otherwise.
+async fn is_healthy(addr: SocketAddr, timeout: Duration) -> bool {
+    matches!(tokio::time::timeout(timeout, TcpStream::connect(addr)).await, Ok(Ok(_)))
+}
+
+/***** LIBRARY *****/
+/// Spawns and supervises a local eflint-server process.
+///
+/// The process is killed when the supervisor is dropped.
+pub struct EFlintServerSupervisor {
+    /// The port on which the supervised server listens.
+    port:    u16,
+    /// The supervised process itself.
+    child:   Arc<Mutex<Child>>,
+    /// The background task that performs health checks and restarts.
+    monitor: JoinHandle<()>,
+}
+
+impl EFlintServerSupervisor {
+    /// Spawns a supervised eflint-server if the nested CLI arguments ask for it.
+    ///
+    /// # Arguments
+    /// - `args`: The parsed nested CLI arguments of the eFLINT reasoner connector.
+    ///
+    /// # Returns
+    /// A new [`EFlintServerSupervisor`] if `server-binary` was given, or [`None`] otherwise.
+    ///
+    /// # Errors
+    /// This function errors if the arguments were invalid, or if we failed to spawn the server.
+    pub fn from_args(args: &HashMap<String, Option<String>>) -> Result<Option<Self>, Error> {
+        let binary: PathBuf = match args.get("server-binary") {
+            Some(Some(path)) => path.into(),
+            _ => return Ok(None),
+        };
+        let port: u16 = parse_number(args, "server-port", "port number", 0)?;
+        let health_interval: u64 = parse_number(args, "server-health-interval", "health check interval", DEFAULT_HEALTH_INTERVAL_MS)?;
+        let max_restarts: u32 = parse_number(args, "server-max-restarts", "maximum number of restarts", DEFAULT_MAX_RESTARTS)?;
+        let raw_args: &str = match args.get("server-args") {
+            Some(Some(raw)) => raw,
+            _ => DEFAULT_SERVER_ARGS,
+        };
+
+        // Allocate a port if we're asked to pick one ourselves
+        let port: u16 = if port == 0 {
+            let listener: TcpListener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|err| Error::PortAllocate { err })?;
+            listener.local_addr().map_err(|err| Error::PortAllocate { err })?.port()
+        } else {
+            port
+        };
+        let server_args: Vec<String> = raw_args.split_whitespace().map(|arg| arg.replace(PORT_PLACEHOLDER, &port.to_string())).collect();
+
+        // Spawn the server and the task monitoring it
+        info!("Spawning supervised eflint-server '{}' on port {port}", binary.display());
+        let child: Arc<Mutex<Child>> = Arc::new(Mutex::new(spawn_server(&binary, &server_args)?));
+        let monitor: JoinHandle<()> =
+            tokio::spawn(Self::monitor(binary, server_args, port, Duration::from_millis(health_interval), max_restarts, child.clone()));
+        Ok(Some(Self { port, child, monitor }))
+    }
+
+    /// Returns the arguments necessary to build the parser for the supervisor.
+    ///
+    /// # Returns
+    /// A vector of arguments appropriate to use to build a [`MapParser`](nested_cli_parser::map_parser::MapParser).
+    #[inline]
+    pub fn cli_args() -> Vec<(char, &'static str, &'static str)> {
+        vec![
+            (
+                'b',
+                "server-binary",
+                "If given, spawns and supervises the eflint-server at this path instead of connecting to an already running one. The \
+                 'reasoner-address' is ignored in that case.",
+            ),
+            ('P', "server-port", "The port on which the supervised eflint-server listens. Use '0' to pick a free one. Default: '0'"),
+            (
+                'a',
+                "server-args",
+                "The arguments given to the supervised eflint-server, separated by whitespace. Any '{port}' is replaced with the port. Default: \
+                 '{port}'",
+            ),
+            ('i', "server-health-interval", "The time (in milliseconds) between health checks of the supervised eflint-server. Default: '5000'"),
+            ('m', "server-max-restarts", "The number of consecutive restarts of the supervised eflint-server after which we give up. Default: '5'"),
+        ]
+    }
+
+    /// Returns the port on which the supervised server listens.
+    #[inline]
+    pub fn port(&self) -> u16 { self.port }
+
+    /// Periodically checks the supervised server and restarts it if it crashed or stopped responding.
+    ///
+    /// # Arguments
+    /// - `binary`: The path to the eflint-server binary.
+    /// - `args`: The arguments to give it.
+    /// - `port`: The port on which it listens.
+    /// - `interval`: The time between health checks.
+    /// - `max_restarts`: The number of consecutive restarts after which we give up.
+    /// - `child`: The currently running process.
+    async fn monitor(binary: PathBuf, args: Vec<String>, port: u16, interval: Duration, max_restarts: u32, child: Arc<Mutex<Child>>) {
+        let addr: SocketAddr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let mut restarts: u32 = 0;
+        // Give the server one interval to come up before we start judging it
+        let mut healthy_once: bool = false;
+        loop {
+            tokio::time::sleep(interval).await;
+
+            // See if the process is still alive and accepting connections
+            let mut child = child.lock().await;
+            let reason: String = match child.try_wait() {
+                Ok(Some(status)) => format!("exited with {status}"),
+                Ok(None) => {
+                    if is_healthy(addr, interval).await {
+                        healthy_once = true;
+                        restarts = 0;
+                        continue;
+                    } else if !healthy_once {
+                        debug!("Supervised eflint-server is not yet accepting connections on {addr}");
+                        healthy_once = true;
+                        continue;
+                    }
+                    "does not accept connections".into()
+                },
+                Err(err) => format!("could not be polled ({err})"),
+            };
+
+            // Restart it (if we're still allowed to)
+            if restarts >= max_restarts {
+                error!("Supervised eflint-server {reason}; giving up after {restarts} consecutive restart(s)");
+                return;
+            }
+            warn!("Supervised eflint-server {reason}; restarting it");
+            if let Err(err) = child.kill().await {
+                debug!("Failed to kill eflint-server: {err}");
+            }
+            match spawn_server(&binary, &args) {
+                Ok(new) => *child = new,
+                Err(err) => {
+                    error!("{err}");
+                },
+            }
+            restarts += 1;
+            healthy_once = false;
+        }
+    }
+}
+
+impl Drop for EFlintServerSupervisor {
+    fn drop(&mut self) {
+        self.monitor.abort();
+        if let Ok(mut child) = self.child.try_lock() {
+            if let Err(err) = child.start_kill() {
+                debug!("Failed to kill eflint-server: {err}");
+            }
+        }
+    }
+}
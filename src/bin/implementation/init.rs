@@ -0,0 +1,259 @@
+//! Implements the `init`-subcommand, which scaffolds a working local deployment of a policy reasoner.
+//!
+//! Setting up a policy reasoner for the first time involves a lot of moving parts: keys and tokens for both APIs, the
+//! JWT resolver config, a database with all migrations applied and (for the eFLINT reasoner) a running eFLINT server.
+//! This module generates all of those in one go, such that one can simply run the reasoner (or `docker compose up`) in
+//! the generated directory afterwards.
+
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{error, fs};
+
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore as _;
+use clap::Parser;
+use diesel::{Connection as _, SqliteConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness as _, embed_migrations};
+use jsonwebtoken::jwk::KeyAlgorithm;
+use log::{debug, info};
+
+/***** CONSTANTS *****/
+/// The migrations of the policy database, embedded in the binary.
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+/// The contents of the JWT resolver config that we generate.
+const JWT_RESOLVER_CONFIG: &'static str = "initiator_claim: username\n";
+
+/***** ERRORS *****/
+/// Defines errors that originate from scaffolding a deployment.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to apply the migrations to the new database.
+    DatabaseMigrate { path: PathBuf, err: Box<dyn 'static + Send + Sync + error::Error> },
+    /// Failed to connect to the new database.
+    DatabaseConnect { path: PathBuf, err: diesel::ConnectionError },
+    /// Failed to create a directory.
+    DirCreate { path: PathBuf, err: std::io::Error },
+    /// Refusing to overwrite an existing file.
+    FileExists { path: PathBuf },
+    /// Failed to write a file.
+    FileWrite { path: PathBuf, err: std::io::Error },
+    /// Failed to generate a key.
+    GenerateKey { path: PathBuf, err: brane_ctl::errors::GenerateError },
+    /// Failed to generate a token.
+    GenerateToken { path: PathBuf, err: brane_ctl::errors::GenerateError },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            DatabaseMigrate { path, .. } => write!(f, "Failed to apply migrations to database '{}'", path.display()),
+            DatabaseConnect { path, .. } => write!(f, "Failed to connect to database '{}'", path.display()),
+            DirCreate { path, .. } => write!(f, "Failed to create directory '{}'", path.display()),
+            FileExists { path } => write!(f, "File '{}' already exists (give '--force' to overwrite it)", path.display()),
+            FileWrite { path, .. } => write!(f, "Failed to write file '{}'", path.display()),
+            GenerateKey { path, .. } => write!(f, "Failed to generate key '{}'", path.display()),
+            GenerateToken { path, .. } => write!(f, "Failed to generate token '{}'", path.display()),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            DatabaseMigrate { err, .. } => Some(&**err),
+            DatabaseConnect { err, .. } => Some(err),
+            DirCreate { err, .. } => Some(err),
+            FileExists { .. } => None,
+            FileWrite { err, .. } => Some(err),
+            GenerateKey { err, .. } => Some(err),
+            GenerateToken { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** ARGUMENTS *****/
+/// Defines the arguments for the `init`-subcommand.
+#[derive(Debug, Parser)]
+pub struct InitArguments {
+    /// The directory to scaffold the deployment in.
+    #[clap(name = "PATH", default_value = ".", help = "The directory to scaffold the deployment in. Will be created if it does not exist.")]
+    pub path: PathBuf,
+
+    /// The name of the user for which to generate tokens.
+    #[clap(short, long, default_value = "admin", help = "The name of the user for which to generate the deliberation and management tokens.")]
+    pub user:   String,
+    /// The name of the system for which to generate tokens.
+    #[clap(
+        short,
+        long,
+        help = "The name of the system through which the user accesses the reasoner. Omit to default to the same value as '--user'."
+    )]
+    pub system: Option<String>,
+    /// The number of days the generated tokens are valid.
+    #[clap(short, long, default_value = "31", help = "The number of days for which the generated tokens are valid.")]
+    pub days:   u64,
+    /// The identifier of the generated keys.
+    #[clap(short, long, help = "The identifier ('kid') of the generated keys. Omit to give each key a random identifier.")]
+    pub kid:    Option<String>,
+
+    /// The image to use for the policy reasoner in the docker-compose file.
+    #[clap(long, default_value = "policy-reasoner:latest", help = "The image to use for the policy reasoner in the generated docker-compose file.")]
+    pub reasoner_image: String,
+    /// The image to use for the eFLINT server in the docker-compose file.
+    #[clap(long, default_value = "eflint-server:latest", help = "The image to use for the eFLINT server in the generated docker-compose file.")]
+    pub eflint_image:   String,
+
+    /// Whether to overwrite existing files.
+    #[clap(short, long, help = "If given, overwrites any existing files instead of refusing to scaffold.")]
+    pub force: bool,
+}
+
+/***** HELPER FUNCTIONS *****/
+/// Writes a file, refusing to overwrite existing ones unless told to.
+///
+/// # Arguments
+/// - `path`: The path of the file to write.
+/// - `contents`: The contents to write.
+/// - `force`: Whether to overwrite the file if it already exists.
+///
+/// # Errors
+/// This function errors if the file already exists and `force` is false, or if we failed to write it.
+fn write_file(path: &Path, contents: &str, force: bool) -> Result<(), Error> {
+    if !force && path.exists() {
+        return Err(Error::FileExists { path: path.into() });
+    }
+    debug!("Writing '{}'", path.display());
+    fs::write(path, contents).map_err(|err| Error::FileWrite { path: path.into(), err })
+}
+
+/// Checks whether a file may be generated.
+///
+/// # Arguments
+/// - `path`: The path of the file to generate.
+/// - `force`: Whether to overwrite the file if it already exists.
+///
+/// # Errors
+/// This function errors if the file already exists and `force` is false.
+fn check_overwrite(path: &Path, force: bool) -> Result<(), Error> {
+    if !force && path.exists() {
+        return Err(Error::FileExists { path: path.into() });
+    }
+    Ok(())
+}
+
+/// Generates a random key identifier.
+///
+/// # Returns
+/// 16 random bytes, hex-encoded.
+fn random_kid() -> String {
+    let mut kid: [u8; 16] = [0; 16];
+    OsRng.fill_bytes(&mut kid);
+    hex::encode(kid)
+}
+
+/// Generates the contents of the docker-compose file.
+///
+/// # Arguments
+/// - `args`: The [`InitArguments`] that determine the images to use.
+/// - `with_eflint`: Whether to include an eFLINT server in the deployment.
+///
+/// # Returns
+/// The contents of the docker-compose file, as YAML.
+fn docker_compose(args: &InitArguments, with_eflint: bool) -> String {
+    let mut compose: String = String::from("services:\n");
+    if with_eflint {
+        compose.push_str(&format!("  eflint-server:\n    image: {}\n    restart: unless-stopped\n", args.eflint_image));
+    }
+    compose.push_str(&format!(
+        "  policy-reasoner:\n    image: {}\n    restart: unless-stopped\n    ports:\n      - \"3030:3030\"\n    working_dir: /policy-reasoner\n    \
+         volumes:\n      - ./examples/config:/policy-reasoner/examples/config:ro\n      - ./data:/policy-reasoner/data\n    environment:\n      \
+         ADDRESS: \"0.0.0.0:3030\"\n",
+        args.reasoner_image
+    ));
+    if with_eflint {
+        compose.push_str("      REASONER_CONNECTOR: \"reasoner-address=http://eflint-server:8080\"\n    depends_on:\n      - eflint-server\n");
+    }
+    compose
+}
+
+/***** LIBRARY *****/
+/// Scaffolds a working local deployment of the policy reasoner.
+///
+/// The generated directory mirrors the paths the reasoner binaries expect, i.e., keys and configs in
+/// `examples/config` and the database in `data/policy.db`. As such, the reasoner can be run directly from within it.
+///
+/// # Arguments
+/// - `args`: The [`InitArguments`] that configure what to generate.
+/// - `with_eflint`: Whether the deployment uses an eFLINT server (and should thus have one in the docker-compose file).
+///
+/// # Errors
+/// This function errors if we failed to generate any of the files, or if any of them already exist and `--force` is not given.
+/// In the latter case, nothing is generated at all.
+pub fn scaffold(args: &InitArguments, with_eflint: bool) -> Result<(), Error> {
+    info!("Scaffolding policy reasoner deployment in '{}'", args.path.display());
+    let config_dir: PathBuf = args.path.join("examples").join("config");
+    let data_dir: PathBuf = args.path.join("data");
+    let resolver_path: PathBuf = config_dir.join("jwt_resolver.yaml");
+    let db_path: PathBuf = data_dir.join("policy.db");
+    let compose_path: PathBuf = args.path.join("docker-compose.yml");
+
+    // Refuse before generating anything if any of the files exists already
+    for api in ["delib", "expert"] {
+        check_overwrite(&config_dir.join(format!("jwk_set_{api}.json")), args.force)?;
+        check_overwrite(&args.path.join(format!("jwt_{api}.json")), args.force)?;
+    }
+    for path in [&resolver_path, &db_path, &compose_path] {
+        check_overwrite(path, args.force)?;
+    }
+
+    // Create the directories
+    for dir in [&config_dir, &data_dir] {
+        fs::create_dir_all(dir).map_err(|err| Error::DirCreate { path: dir.clone(), err })?;
+    }
+
+    // Generate the keys and tokens for both APIs
+    let system: String = args.system.clone().unwrap_or_else(|| args.user.clone());
+    for api in ["delib", "expert"] {
+        let key_path: PathBuf = config_dir.join(format!("jwk_set_{api}.json"));
+        let token_path: PathBuf = args.path.join(format!("jwt_{api}.json"));
+
+        debug!("Generating key '{}'", key_path.display());
+        let kid: String = args.kid.clone().unwrap_or_else(random_kid);
+        if let Err(err) = brane_ctl::generate::policy_secret(false, key_path.clone(), kid, KeyAlgorithm::HS256) {
+            return Err(Error::GenerateKey { path: key_path, err });
+        }
+        debug!("Generating token '{}'", token_path.display());
+        if let Err(err) = brane_ctl::generate::policy_token(
+            false,
+            token_path.clone(),
+            key_path,
+            args.user.clone(),
+            system.clone(),
+            Duration::from_secs(args.days * 24 * 60 * 60),
+        ) {
+            return Err(Error::GenerateToken { path: token_path, err });
+        }
+    }
+
+    // Write the JWT resolver config
+    write_file(&resolver_path, JWT_RESOLVER_CONFIG, args.force)?;
+
+    // Create the database and apply all migrations
+    if db_path.exists() {
+        fs::remove_file(&db_path).map_err(|err| Error::FileWrite { path: db_path.clone(), err })?;
+    }
+    debug!("Creating database '{}'", db_path.display());
+    let mut conn: SqliteConnection =
+        SqliteConnection::establish(&db_path.display().to_string()).map_err(|err| Error::DatabaseConnect { path: db_path.clone(), err })?;
+    if let Err(err) = conn.run_pending_migrations(MIGRATIONS) {
+        return Err(Error::DatabaseMigrate { path: db_path, err });
+    }
+
+    // Finally, the docker-compose file
+    write_file(&compose_path, &docker_compose(args, with_eflint), args.force)?;
+
+    info!("Scaffolded policy reasoner deployment in '{}'", args.path.display());
+    Ok(())
+}
@@ -1,6 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter, Result as FResult};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{error, fs};
 
-use clap::Parser;
+use audit_logger::PayloadStore;
+use audit_logger::redact::Redactor;
+use clap::{Parser, Subcommand};
+use log::warn;
+use policy_reasoner::anchor::Notary;
+use policy_reasoner::auth::KidResolver;
+use policy_reasoner::dispatch::Route;
+use policy_reasoner::encryption::{EncryptionError, KeySource, PolicyCipher};
+use policy_reasoner::forwarder::{ForwardKind, ForwarderConfig, ObjectLockMode, S3Config, ServerSideEncryption};
+use policy_reasoner::secrets::{DirSecrets, EnvSecrets, Secrets, SecretsError, VaultSecrets};
+use reasonerconn::circuit_breaker::CircuitBreakerConfig;
+use reasonerconn::retry::RetryConfig;
+use srv::access_tokens::{AccessTokenError, AccessTokenIssuer};
+use srv::disclosure::{Disclosure, DisclosureConfig};
+use srv::fallback::FallbackBehaviour;
+use srv::federation::{Federation, FederationConfig};
+use srv::i18n::MessageCatalog;
+use srv::maintenance::{MaintenanceSchedule, RecurringMaintenance};
+use srv::notifications::{DEFAULT_SENDMAIL, NotificationRule, Notifier};
+use srv::postprocess::{VerdictPipeline, VerdictTransformerConfig};
+use srv::prefilter::{Prefilter, PrefilterRule};
+use srv::queue::QuestionQueueConfig;
+use srv::signatures::{SignatureError, StaticKeySet};
+use workflow::lint::{LintConfig, LintLevel, LintSetting};
+use workflow::preprocess::WorkflowLimits;
+
+#[cfg(feature = "init")]
+use crate::implementation::init::InitArguments;
+use crate::implementation::simulate::SimulateArguments;
+
+/***** CONSTANTS *****/
+/// The prefix of the environment variables that secrets are read from (see [`EnvSecrets`]).
+pub const SECRETS_ENV_PREFIX: &str = "POLICY_REASONER_SECRET_";
+
+/// The secret with the JWK set used to authenticate the policy expert API.
+pub const JWK_SET_EXPERT_SECRET: &str = "jwk-set-expert";
+/// The secret with the JWK set used to authenticate the deliberation API.
+pub const JWK_SET_DELIB_SECRET: &str = "jwk-set-delib";
+/// The secret with the key used to sign policy archives.
+pub const ARCHIVE_KEY_SECRET: &str = "archive-key";
+/// The secret with the key used to pseudonymize user names in the audit log.
+pub const PSEUDONYM_KEY_SECRET: &str = "audit-pseudonym-key";
+/// The secret with the access key ID of the S3 audit log store.
+pub const S3_ACCESS_KEY_SECRET: &str = "audit-s3-access-key";
+/// The secret with the secret access key of the S3 audit log store.
+pub const S3_SECRET_KEY_SECRET: &str = "audit-s3-secret-key";
+/// The secret with the key used to sign webhook notifications.
+pub const WEBHOOK_SIGNING_KEY_SECRET: &str = "webhook-signing-key";
+
+/// Every secret fetched by [`Arguments::fetch_secrets()`].
+const SECRETS: [&str; 7] = [
+    JWK_SET_EXPERT_SECRET,
+    JWK_SET_DELIB_SECRET,
+    ARCHIVE_KEY_SECRET,
+    PSEUDONYM_KEY_SECRET,
+    S3_ACCESS_KEY_SECRET,
+    S3_SECRET_KEY_SECRET,
+    WEBHOOK_SIGNING_KEY_SECRET,
+];
+
+/// The JWK set used to authenticate the policy expert API if no other is given. Only meant for trying things out.
+const EXAMPLE_JWK_SET_EXPERT: &str = "./examples/config/jwk_set_expert.json";
+/// The JWK set used to authenticate the deliberation API if no other is given. Only meant for trying things out.
+const EXAMPLE_JWK_SET_DELIB: &str = "./examples/config/jwk_set_delib.json";
+
+/***** ERRORS *****/
+/// Defines errors that originate from interpreting the arguments.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read the access token key file.
+    AccessTokenKeyRead { path: PathBuf, err: std::io::Error },
+    /// The access token key could not be used.
+    AccessTokenKey { path: PathBuf, err: AccessTokenError },
+    /// Asked to fetch secrets from Vault without a token.
+    VaultTokenMissing,
+    /// Failed to fetch the secrets.
+    Secrets { err: SecretsError },
+    /// A JWK set could not be loaded.
+    JwkSet { name: &'static str, err: auth_resolver::AuthResolverError },
+    /// Failed to read the webhook signing key file.
+    WebhookSigningKeyRead { path: PathBuf, err: std::io::Error },
+    /// Failed to read the archive key file.
+    ArchiveKeyRead { path: PathBuf, err: std::io::Error },
+    /// Failed to read the audit log pseudonym key file.
+    PseudonymKeyRead { path: PathBuf, err: std::io::Error },
+    /// Asked to forward the audit log without saying where to.
+    ForwardUrlMissing { kind: ForwardKind },
+    /// Asked to ship the audit log to an object store without credentials.
+    S3CredentialsMissing,
+    /// Failed to read the S3 secret access key file.
+    S3SecretKeyRead { path: PathBuf, err: std::io::Error },
+    /// The policy encryption key could not be used.
+    PolicyEncryptionKey { err: EncryptionError },
+    /// Failed to read the verdict transformers file.
+    VerdictTransformersRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the verdict transformers file.
+    VerdictTransformersParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to read the prefilter rules file.
+    PrefilterRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the prefilter rules file.
+    PrefilterParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to read the maintenance windows file.
+    MaintenanceRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the maintenance windows file.
+    MaintenanceParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to read the federation file.
+    FederationRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the federation file.
+    FederationParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to read the disclosure file.
+    DisclosureRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the disclosure file.
+    DisclosureParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to read the image keys file.
+    ImageKeysRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the image keys file.
+    ImageKeysParse { path: PathBuf, err: serde_yaml::Error },
+    /// A key in the image keys file was invalid.
+    ImageKey { path: PathBuf, err: SignatureError },
+    /// Failed to read the message catalog file.
+    MessagesRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the message catalog file.
+    MessagesParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to read the notification rules file.
+    NotificationRulesRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the notification rules file.
+    NotificationRulesParse { path: PathBuf, err: serde_yaml::Error },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            AccessTokenKeyRead { path, .. } => write!(f, "Failed to read access token key file '{}'", path.display()),
+            AccessTokenKey { path, .. } => write!(f, "Invalid access token key in '{}'", path.display()),
+            VaultTokenMissing => write!(f, "Asked to fetch secrets from Vault, but no '--vault-token' was given"),
+            Secrets { .. } => write!(f, "Failed to fetch secrets"),
+            JwkSet { name, .. } => write!(f, "Failed to load JWK set '{name}'"),
+            WebhookSigningKeyRead { path, .. } => write!(f, "Failed to read webhook signing key file '{}'", path.display()),
+            ArchiveKeyRead { path, .. } => write!(f, "Failed to read archive key file '{}'", path.display()),
+            PseudonymKeyRead { path, .. } => write!(f, "Failed to read audit log pseudonym key file '{}'", path.display()),
+            ForwardUrlMissing { kind } => write!(f, "Asked to forward the audit log to {kind}, but no '--audit-forward-url' was given"),
+            S3CredentialsMissing => {
+                write!(f, "Asked to forward the audit log to s3, but no '--audit-s3-access-key' and '--audit-s3-secret-key' (or secrets) were given")
+            },
+            S3SecretKeyRead { path, .. } => write!(f, "Failed to read S3 secret access key file '{}'", path.display()),
+            PolicyEncryptionKey { .. } => write!(f, "Failed to load policy encryption key"),
+            VerdictTransformersRead { path, .. } => write!(f, "Failed to read verdict transformers file '{}'", path.display()),
+            VerdictTransformersParse { path, .. } => write!(f, "Failed to parse verdict transformers file '{}'", path.display()),
+            PrefilterRead { path, .. } => write!(f, "Failed to read prefilter rules file '{}'", path.display()),
+            PrefilterParse { path, .. } => write!(f, "Failed to parse prefilter rules file '{}'", path.display()),
+            MaintenanceRead { path, .. } => write!(f, "Failed to read maintenance windows file '{}'", path.display()),
+            MaintenanceParse { path, .. } => write!(f, "Failed to parse maintenance windows file '{}'", path.display()),
+            FederationRead { path, .. } => write!(f, "Failed to read federation file '{}'", path.display()),
+            FederationParse { path, .. } => write!(f, "Failed to parse federation file '{}'", path.display()),
+            DisclosureRead { path, .. } => write!(f, "Failed to read disclosure file '{}'", path.display()),
+            DisclosureParse { path, .. } => write!(f, "Failed to parse disclosure file '{}'", path.display()),
+            ImageKeysRead { path, .. } => write!(f, "Failed to read image keys file '{}'", path.display()),
+            ImageKeysParse { path, .. } => write!(f, "Failed to parse image keys file '{}'", path.display()),
+            ImageKey { path, .. } => write!(f, "Invalid key in image keys file '{}'", path.display()),
+            MessagesRead { path, .. } => write!(f, "Failed to read message catalog file '{}'", path.display()),
+            MessagesParse { path, .. } => write!(f, "Failed to parse message catalog file '{}'", path.display()),
+            NotificationRulesRead { path, .. } => write!(f, "Failed to read notification rules file '{}'", path.display()),
+            NotificationRulesParse { path, .. } => write!(f, "Failed to parse notification rules file '{}'", path.display()),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            AccessTokenKeyRead { err, .. } => Some(err),
+            AccessTokenKey { err, .. } => Some(err),
+            VaultTokenMissing => None,
+            Secrets { err } => Some(err),
+            JwkSet { err, .. } => Some(err),
+            WebhookSigningKeyRead { err, .. } => Some(err),
+            ArchiveKeyRead { err, .. } => Some(err),
+            PseudonymKeyRead { err, .. } => Some(err),
+            ForwardUrlMissing { .. } => None,
+            S3CredentialsMissing => None,
+            S3SecretKeyRead { err, .. } => Some(err),
+            PolicyEncryptionKey { err } => Some(err),
+            VerdictTransformersRead { err, .. } => Some(err),
+            VerdictTransformersParse { err, .. } => Some(err),
+            PrefilterRead { err, .. } => Some(err),
+            PrefilterParse { err, .. } => Some(err),
+            MaintenanceRead { err, .. } => Some(err),
+            MaintenanceParse { err, .. } => Some(err),
+            FederationRead { err, .. } => Some(err),
+            FederationParse { err, .. } => Some(err),
+            DisclosureRead { err, .. } => Some(err),
+            DisclosureParse { err, .. } => Some(err),
+            ImageKeysRead { err, .. } => Some(err),
+            ImageKeysParse { err, .. } => Some(err),
+            ImageKey { err, .. } => Some(err),
+            MessagesRead { err, .. } => Some(err),
+            MessagesParse { err, .. } => Some(err),
+            NotificationRulesRead { err, .. } => Some(err),
+            NotificationRulesParse { err, .. } => Some(err),
+        }
+    }
+}
 
 /***** ARGUMENTS *****/
 /// Defines the arguments for the `policy-reasoner` server.
@@ -39,4 +245,798 @@ pub struct Arguments {
         help = "Arguments to pass to the current reasoner connector plugin. To find which are possible, see '--help-reasoner-connector'."
     )]
     pub reasoner_connector: Option<String>,
+    /// The arguments of additional reasoner connectors, each talking to its own backend instance.
+    #[clap(
+        long,
+        env,
+        value_delimiter = ';',
+        help = "Adds another reasoner backend instance (shard) to spread questions over, given as the arguments to the reasoner connector plugin \
+                (see '--reasoner-connector', which configures shard 0). Can be given multiple times (or as a ';'-separated list)."
+    )]
+    pub reasoner_shard: Vec<String>,
+    /// Use-cases pinned to a particular reasoner shard.
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Pins all questions of a use-case to a particular reasoner shard, given as '<USE_CASE>=<SHARD>'. Questions of other use-cases are \
+                spread over the shards by a hash of their workflow's identifier. Can be given multiple times (or as a ','-separated list)."
+    )]
+    pub reasoner_route: Vec<Route>,
+    /// What to do when the reasoner connector fails to give an answer.
+    #[clap(
+        long,
+        env,
+        default_value = "deny",
+        help = "What to do when the reasoner backend cannot be consulted. Can be 'deny' (reject with a 503 problem), 'cache' (serve the last \
+                verdict for the same question, or deny if there is none) or 'allow-with-warning'. Every fallback is recorded in the audit log."
+    )]
+    pub reasoner_fallback: FallbackBehaviour,
+    /// The arguments of an eFLINT reasoner connector whose answers are only logged.
+    #[clap(
+        long,
+        env,
+        help = "If given, every question is also put to an eFLINT reasoner connector with these arguments (see '--reasoner-connector'). Its answers \
+                are only written to the audit log, next to those of the actual reasoner and whether they agree; they never affect the verdict. \
+                Policies need eFLINT content for it to answer sensibly. Only used by the no-op and POSIX reasoners."
+    )]
+    pub shadow_eflint: Option<String>,
+    /// After how many consecutive failures the circuit breaker opens.
+    #[clap(
+        long,
+        env,
+        default_value = "5",
+        help = "The number of consecutive reasoner failures (or latency SLO breaches) after which the circuit breaker opens and stops consulting \
+                the reasoner backend for a while. Give '0' to disable the circuit breaker."
+    )]
+    pub circuit_breaker_threshold: u32,
+    /// The latency SLO of the reasoner backend, in milliseconds.
+    #[clap(long, env, help = "If given, answers of the reasoner backend that take longer than this many milliseconds count as failures too.")]
+    pub circuit_breaker_latency_slo: Option<u64>,
+    /// How long the circuit breaker stays open, in milliseconds.
+    #[clap(
+        long,
+        env,
+        default_value = "30000",
+        help = "The number of milliseconds the circuit breaker stays open before letting a probe request through to the reasoner backend."
+    )]
+    pub circuit_breaker_open_duration: u64,
+    /// How often a question is put to the reasoner backend in total when it fails transiently.
+    #[clap(
+        long,
+        env,
+        default_value = "3",
+        help = "The maximum number of times a question is put to the reasoner backend when it fails transiently (e.g., on a dropped connection or a \
+                '502 Bad Gateway'). Give '1' to never retry. The number of retries is recorded in the audit log. Only used by the eFLINT reasoner \
+                connector."
+    )]
+    pub reasoner_retry_attempts: u32,
+    /// The backoff before the first retry, in milliseconds.
+    #[clap(
+        long,
+        env,
+        default_value = "100",
+        help = "The number of milliseconds to back off before retrying a transiently failed question for the first time. Doubled for every next \
+                retry, and randomized (jittered) such that failed questions don't all come back at once."
+    )]
+    pub reasoner_retry_backoff: u64,
+    /// The maximum backoff before a retry, in milliseconds.
+    #[clap(
+        long,
+        env,
+        default_value = "2000",
+        help = "The maximum number of milliseconds to back off before retrying a transiently failed question."
+    )]
+    pub reasoner_retry_max_backoff: u64,
+    /// The file with the transformers to pass the reasoner's answers through.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file with a list of transformers that may alter the reasoner's answers before they are returned, applied in \
+                order. Each has a 'kind': 'maintenance-window' (with 'from', 'until' and optionally 'reason'), 'rate-limit' (with 'max_requests' \
+                and 'window_secs') or 'obligation' (with 'obligation' and optionally 'questions'). Every change is recorded in the audit log."
+    )]
+    pub verdict_transformers: Option<PathBuf>,
+    /// The file with the allow/deny rules that may answer questions before the reasoner is asked.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file with a list of allow/deny rules that are checked before the reasoner is asked. Each has an 'id', an \
+                'effect' ('allow' or 'deny') and optionally lists of 'users', 'datasets', 'locations' and 'questions' it applies to, and a 'reason' \
+                for denial. The first rule that matches a question answers it without asking the reasoner, which is recorded in the audit log."
+    )]
+    pub prefilter: Option<PathBuf>,
+    /// The file with the recurring maintenance windows of the reasoner backend.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file with a list of recurring maintenance windows of the reasoner backend, during which it is not asked. Each \
+                has a 'name', a 'start' time (in UTC, e.g., '22:00:00'), a 'duration_mins', optionally the 'days' of the week it opens (e.g., \
+                'Sun'; default every day), a 'behaviour' ('allow', 'deny' or 'retry-later' (default)) and a 'reason' given to clients."
+    )]
+    pub maintenance: Option<PathBuf>,
+    /// The file with the partner checkers that questions about their datasets are forwarded to.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file with our own 'domain' and a list of 'partners': checkers of other organizations that questions about their \
+                datasets are forwarded to. Each has a 'domain', the 'address' of its checker, optionally a 'token' to authenticate with, and the \
+                'locations' and 'datasets' it governs. Every remote verdict is recorded in the audit log."
+    )]
+    pub federation: Option<PathBuf>,
+    /// The file with the redacted summary of the policy that is disclosed to other domains.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file with our own 'domain', the 'partners' (initiators) that are other domains, and the 'fields' of a redacted \
+                summary of the policy (e.g., dataset classes that are never released). Each field has a 'value' and a 'visibility' of 'public', \
+                'partners' or 'private' (the default). Every disclosure is recorded in the audit log."
+    )]
+    pub disclosure: Option<PathBuf>,
+    /// The levels of the lint rules run on workflows before deliberating on them.
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Sets the level of a lint rule run on workflows before deliberating on them, given as '<RULE>=<LEVEL>'. Rules are \
+                'task-without-location', 'dataset-without-source', 'unreachable-branch' and 'result-without-recipient'; levels are 'allow' (don't \
+                run), 'warn' (log only; the default) or 'deny' (refuse the workflow). Can be given multiple times (or as a ','-separated list)."
+    )]
+    pub lint: Vec<LintSetting>,
+    /// What happens to workflows that refer to datasets, locations or users the resolved state doesn't know about.
+    #[clap(
+        long,
+        env,
+        default_value = "allow",
+        help = "Checks whether the datasets, locations and users that workflows refer to are in the resolved state before deliberating on them. Can \
+                be 'allow' (don't check; the default), 'warn' (log unknown ones only) or 'deny' (refuse the workflow with a '400 Bad Request' \
+                problem of type '/problems/unknown-references' listing them)."
+    )]
+    pub state_check: LintLevel,
+    /// The maximum number of edges in a workflow.
+    #[clap(
+        long,
+        env,
+        default_value = "10000",
+        help = "The maximum number of edges in a workflow (in its main body and all its functions together). Larger workflows are refused with a \
+                '400 Bad Request' problem of type '/problems/workflow-limits'."
+    )]
+    pub max_workflow_edges: usize,
+    /// The maximum factor by which inlining functions may grow a workflow.
+    #[clap(
+        long,
+        env,
+        default_value = "32",
+        help = "The maximum factor by which inlining the functions of a workflow may grow its number of edges. Workflows that grow larger (e.g., \
+                because of nested functions calling each other several times) are refused like those with too many edges."
+    )]
+    pub max_inline_expansion: usize,
+    /// The maximum number of loops in a workflow.
+    #[clap(
+        long,
+        env,
+        default_value = "256",
+        help = "The maximum number of loops in a workflow after inlining its functions (i.e., a loop in a function counts for every call to it). \
+                Workflows with more loops are refused like those with too many edges."
+    )]
+    pub max_workflow_loops: usize,
+    /// The largest policy that may be pushed.
+    #[clap(
+        long,
+        env,
+        default_value = "536870912",
+        help = "The largest policy that may be pushed, in bytes (of the request pushing it). Larger policies are refused with a '413 Payload Too \
+                Large' as soon as that is clear, without receiving the rest."
+    )]
+    pub max_policy_size: u64,
+    /// The file with the public keys to verify image signatures with.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file mapping key names to PEM-encoded Ed25519 or ECDSA P-256 public keys (e.g., a 'cosign.pub'). The signatures \
+                that the state gives for the image of a task are verified against them, and policies learn the names of the keys that verified. If \
+                omitted, no image is considered signed."
+    )]
+    pub image_keys: Option<PathBuf>,
+    /// The file with the translations of the reasons given to clients.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file mapping language tags (e.g., 'nl') to maps of reasons to their translations. Clients asking with an \
+                'Accept-Language' header get the reasons for denial and lint findings in their language where a translation is known; the audit log \
+                always keeps the original ones. Reasons like '<KEY>: <DETAIL>' may be translated by a template for '<KEY>' with '{detail}', and \
+                lint findings by a template for 'lint.<RULE>' with '{node}' and '{message}'."
+    )]
+    pub messages: Option<PathBuf>,
+    /// Whether to count usage in the policy database.
+    #[clap(
+        long,
+        env,
+        help = "If given, counts the tasks and data accesses that the reasoner permits per user and per dataset in the policy database, and gives \
+                the counts of the current month to the reasoner as part of the state. This allows policies that limit usage (e.g., 'at most 5 \
+                analyses of dataset D per month')."
+    )]
+    pub usage_counters: bool,
+    /// Whether to track duties in the policy database.
+    #[clap(
+        long,
+        env,
+        help = "If given, records the obligations of allowed verdicts as duties of the user asking in the policy database, and gives the open \
+                duties of the user asking to the reasoner as part of the state, such that policies can deny requests while duties are outstanding. \
+                Open duties are listed at '/v1/duties' and reported fulfilled at '/v1/duties/<ID>/fulfil'."
+    )]
+    pub duties: bool,
+    /// How many questions are put to the reasoner at the same time.
+    #[clap(
+        long,
+        env,
+        default_value = "16",
+        help = "The maximum number of questions put to the reasoner backend at the same time. Other questions wait for their turn, interactive ones \
+                before batch ones. Give '0' to put all questions to the backend immediately."
+    )]
+    pub queue_concurrency: usize,
+    /// How many questions may wait for their turn.
+    #[clap(
+        long,
+        env,
+        default_value = "1024",
+        help = "The maximum number of questions waiting for their turn at the reasoner backend. Questions arriving when the queue is full are \
+                treated as if the backend could not be consulted (see '--reasoner-fallback')."
+    )]
+    pub queue_capacity: usize,
+    /// The file with the secret key used to sign policy archives.
+    #[clap(
+        long,
+        env,
+        help = "The path to a file with the secret key used to sign exported policy archives and to verify imported ones. If omitted, the export \
+                and import endpoints are disabled."
+    )]
+    pub archive_key: Option<PathBuf>,
+    /// Where to read the key that encrypts policies at rest from.
+    #[clap(
+        long,
+        env,
+        help = "If given, encrypts the contents of policies in './data/policy.db' with AES-256-GCM, using a key of 32 hex-encoded bytes read from a \
+                file ('file:<PATH>') or an environment variable ('env:<VAR>'). Policies already stored in plaintext are encrypted on startup."
+    )]
+    pub policy_encryption_key: Option<KeySource>,
+    /// The file with the private key used to sign access tokens.
+    #[clap(
+        long,
+        env,
+        help = "The path to a PEM-encoded (PKCS#8) Ed25519 or ECDSA P-256 private key. If given, allowed data-access verdicts come with a \
+                short-lived 'access_token' signed with it, naming the dataset, user and workflow, such that the service providing the dataset can \
+                verify the verdict offline with the matching public key. If omitted, no access tokens are given."
+    )]
+    pub access_token_key: Option<PathBuf>,
+    /// The identifier of the access token key.
+    #[clap(
+        long,
+        env,
+        requires = "access_token_key",
+        help = "The identifier to give as 'kid' in access tokens, for services that know multiple keys."
+    )]
+    pub access_token_key_id: Option<String>,
+    /// How long access tokens are valid, in seconds.
+    #[clap(long, env, default_value = "300", help = "How long access tokens are valid after they are issued, in seconds.")]
+    pub access_token_ttl: u64,
+    /// The file with the rules that decide which verdicts the owners of datasets are notified of.
+    #[clap(
+        long,
+        env,
+        help = "The path to a YAML file with a list of rules that decide which verdicts the owners of datasets (as given in the state) are notified \
+                of, and how. Every rule may filter on 'verdicts' ('allow', 'deny'), 'questions' (e.g., 'access-data') and 'datasets', and names the \
+                'channels' ('webhook', 'email') to notify through; filters left out match everything. If omitted, owners are not notified."
+    )]
+    pub notification_rules: Option<PathBuf>,
+    /// The sendmail binary used to e-mail owners.
+    #[clap(long, env, default_value = DEFAULT_SENDMAIL, help = "The sendmail binary used to notify the owners of datasets by e-mail.")]
+    pub notification_sendmail: PathBuf,
+    /// The file with the secret key used to sign webhook notifications.
+    #[clap(
+        long,
+        env,
+        help = "The path to a file with the secret key used to sign webhook notifications. If omitted, the 'webhook-signing-key' secret is used; if \
+                there is none either, notifications are not signed."
+    )]
+    pub webhook_signing_key: Option<PathBuf>,
+    /// The JWK set used to authenticate the policy expert API.
+    #[clap(
+        long,
+        env,
+        help = "The path to the JWK set used to authenticate the policy expert API. If omitted, the 'jwk-set-expert' secret is used; if there is \
+                none either, the example set in './examples/config' is used."
+    )]
+    pub jwk_set_expert: Option<PathBuf>,
+    /// The JWK set used to authenticate the deliberation API.
+    #[clap(
+        long,
+        env,
+        help = "The path to the JWK set used to authenticate the deliberation API. If omitted, the 'jwk-set-delib' secret is used; if there is none \
+                either, the example set in './examples/config' is used."
+    )]
+    pub jwk_set_delib: Option<PathBuf>,
+    /// The address of HashiCorp Vault.
+    #[clap(
+        long,
+        env,
+        help = "If given, fetches secrets (keys and credentials not given with their own option) from the KV (version 2) secrets engine of the \
+                HashiCorp Vault at this address (e.g., 'https://vault.example.com:8200'). Every secret is a field of a single Vault secret."
+    )]
+    pub vault_addr: Option<String>,
+    /// The token to authenticate with Vault.
+    #[clap(long, env, hide_env_values = true, help = "The token to authenticate with Vault.")]
+    pub vault_token: Option<String>,
+    /// Where the KV secrets engine is mounted in Vault.
+    #[clap(long, env, default_value = "secret", help = "Where the KV secrets engine is mounted in Vault.")]
+    pub vault_mount: String,
+    /// The Vault secret whose fields are the reasoner's secrets.
+    #[clap(long, env, default_value = "policy-reasoner", help = "The path of the Vault secret whose fields are the reasoner's secrets.")]
+    pub vault_path: String,
+    /// The directory to read secrets from.
+    #[clap(
+        long,
+        env,
+        help = "If given, reads secrets not found in Vault or in 'POLICY_REASONER_SECRET_*' environment variables from files in this directory, \
+                named after the secret (e.g., '/run/secrets/jwk-set-expert')."
+    )]
+    pub secrets_dir: Option<PathBuf>,
+    /// The secrets fetched by [`Arguments::fetch_secrets()`].
+    #[clap(skip)]
+    pub secrets: HashMap<String, Vec<u8>>,
+    /// The file with the secret key used to pseudonymize user names in the audit log.
+    #[clap(
+        long,
+        env,
+        help = "The path to a file with the secret key used to replace user names in the audit log with pseudonyms. Keep this file separate from \
+                the audit log; only those holding it can link pseudonyms back to users. If omitted, user names are logged as-is."
+    )]
+    pub audit_pseudonym_key: Option<PathBuf>,
+    /// Whether to drop raw payloads from the audit log.
+    #[clap(
+        long,
+        env,
+        help = "If given, does not write raw workflows and reasoner responses to the audit log. Only the workflow identifier and (pseudonymized) \
+                user are kept."
+    )]
+    pub audit_drop_payloads: bool,
+    /// The directory to archive the full payloads of questions in.
+    #[clap(
+        long,
+        env,
+        help = "If given, writes the full body of every deliberation request and the raw requests and responses exchanged with the reasoner backend \
+                to this directory, one subdirectory per verdict reference. Every payload is linked from the audit log with a 'PAYLOAD-ARCHIVED' \
+                statement, such that contested verdicts can be reproduced exactly."
+    )]
+    pub payload_dir: Option<PathBuf>,
+    /// The maximum size of an archived payload, in bytes.
+    #[clap(
+        long,
+        env,
+        default_value = "1048576",
+        requires = "payload_dir",
+        help = "The maximum number of bytes archived of a single payload. Larger payloads are truncated, but the digest in the audit log is always \
+                that of the full payload."
+    )]
+    pub payload_max_size: usize,
+    /// How long archived payloads are kept, in seconds.
+    #[clap(
+        long,
+        env,
+        requires = "payload_dir",
+        help = "If given, removes the archived payloads of a verdict reference once they are older than this many seconds. If omitted, they are \
+                kept forever."
+    )]
+    pub payload_retention: Option<u64>,
+    /// The origins that the results of asynchronous questions may be POSTed to.
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Allows clients asking questions with '?async=true' to have the result POSTed to a '&callback=<URL>' on this origin (e.g., \
+                'https://example.com'). Can be given multiple times (or as a ','-separated list). Callbacks to any other origin are refused; if \
+                omitted, clients can only poll for results."
+    )]
+    pub callback_origin: Vec<String>,
+    /// How often to anchor the audit log, in seconds.
+    #[clap(
+        long,
+        env,
+        help = "If given, computes a Merkle root over the new audit log entries every this many seconds and records it in './audit-log.anchors'. \
+                These roots are needed to produce inclusion proofs for entries."
+    )]
+    pub audit_anchor_interval: Option<u64>,
+    /// Where to publish audit log anchors to.
+    #[clap(
+        long,
+        env,
+        help = "If given, also publishes every audit log anchor to this notary. Can be 'file:<PATH>' to append it to a file, or an 'http(s)://' URL \
+                (e.g., of a transparency log) to POST it to as JSON."
+    )]
+    pub audit_anchor_notary: Option<Notary>,
+    /// The SQLite database to write the audit log to.
+    #[clap(
+        long,
+        env,
+        conflicts_with = "audit_forward",
+        help = "If given, appends the audit log to a table in this SQLite database (e.g., './data/policy.db') instead of writing it to \
+                './audit-log.log', such that it can be queried by reference, kind or time. The database must have all migrations applied."
+    )]
+    pub audit_db: Option<PathBuf>,
+    /// The central log store to ship the audit log to.
+    #[clap(
+        long,
+        env,
+        help = "If given, ships the audit log to a central log store instead of writing it to './audit-log.log'. Can be 'elasticsearch', 'loki' or \
+                's3' (for S3-compatible object stores, such as MinIO). Requests are rejected if their statements cannot be delivered."
+    )]
+    pub audit_forward: Option<ForwardKind>,
+    /// The URL of the central log store.
+    #[clap(
+        long,
+        env,
+        help = "The base URL of the log store given with '--audit-forward' (e.g., 'http://localhost:9200'). For 's3', this includes the bucket \
+                (e.g., 'https://s3.eu-west-1.amazonaws.com/audit')."
+    )]
+    pub audit_forward_url: Option<String>,
+    /// The index (or label) to ship the audit log under.
+    #[clap(
+        long,
+        env,
+        default_value = "policy-reasoner-audit",
+        help = "The Elasticsearch index to write the audit log to, the value of its 'index' label in Loki, or the prefix of the objects' keys in S3."
+    )]
+    pub audit_forward_index: String,
+    /// The maximum number of statements waiting to be shipped.
+    #[clap(
+        long,
+        env,
+        default_value = "1024",
+        help = "The maximum number of audit log statements waiting to be shipped. Requests are rejected while the queue is full."
+    )]
+    pub audit_forward_queue_size: usize,
+    /// The maximum number of statements shipped at once.
+    #[clap(long, env, default_value = "100", help = "The maximum number of audit log statements shipped in a single request to the log store.")]
+    pub audit_forward_batch_size: usize,
+    /// How long to wait for the log store, in milliseconds.
+    #[clap(
+        long,
+        env,
+        default_value = "5000",
+        help = "The number of milliseconds to wait for the log store to confirm a batch before considering it undelivered."
+    )]
+    pub audit_forward_timeout: u64,
+    /// The region of the S3 bucket.
+    #[clap(long, env, default_value = "us-east-1", help = "The region of the bucket when forwarding the audit log to 's3'.")]
+    pub audit_s3_region: String,
+    /// The S3 access key ID.
+    #[clap(long, env, help = "The access key ID to sign requests with when forwarding the audit log to 's3'.")]
+    pub audit_s3_access_key: Option<String>,
+    /// The file with the S3 secret access key.
+    #[clap(long, env, help = "The path to a file with the secret access key to sign requests with when forwarding the audit log to 's3'.")]
+    pub audit_s3_secret_key: Option<PathBuf>,
+    /// How the S3 store encrypts the audit log.
+    #[clap(
+        long,
+        env,
+        help = "If given, asks the object store to encrypt the audit log objects. Can be 'aes256' (keys managed by the store), 'aws:kms' (the \
+                bucket's default KMS key) or 'aws:kms:<KEY_ID>'."
+    )]
+    pub audit_s3_encryption: Option<ServerSideEncryption>,
+    /// How strictly the audit log objects are locked.
+    #[clap(
+        long,
+        env,
+        requires = "audit_s3_retain_days",
+        help = "If given, locks every audit log object in a bucket with Object Lock enabled. Can be 'governance' or 'compliance'. Objects are never \
+                overwritten or removed by the reasoner either way."
+    )]
+    pub audit_s3_object_lock: Option<ObjectLockMode>,
+    /// How long the audit log objects are locked.
+    #[clap(
+        long,
+        env,
+        requires = "audit_s3_object_lock",
+        help = "The number of days every audit log object is locked for with '--audit-s3-object-lock'."
+    )]
+    pub audit_s3_retain_days: Option<u32>,
+
+    /// An optional subcommand to execute instead of running the server.
+    #[clap(subcommand)]
+    pub action: Option<Action>,
+}
+
+impl Arguments {
+    /// Fetches the secrets of the reasoner from Vault, the environment and/or a secrets directory, in that order.
+    ///
+    /// The secrets are kept in [`Arguments::secrets`], where the methods that need them find them.
+    ///
+    /// # Errors
+    /// This function errors if Vault is given without a token, or if any of the sources could not be read.
+    pub async fn fetch_secrets(&mut self) -> Result<(), Error> {
+        let mut secrets: Secrets = Secrets::new();
+        if let Some(addr) = &self.vault_addr {
+            let Some(token) = &self.vault_token else { return Err(Error::VaultTokenMissing) };
+            secrets = secrets.with_provider(VaultSecrets::new(addr, token.clone(), &self.vault_mount, &self.vault_path));
+        }
+        secrets = secrets.with_provider(EnvSecrets::new(SECRETS_ENV_PREFIX));
+        if let Some(dir) = &self.secrets_dir {
+            secrets = secrets.with_provider(DirSecrets::new(dir.clone()));
+        }
+        self.secrets = secrets.fetch(&SECRETS).await.map_err(|err| Error::Secrets { err })?;
+        Ok(())
+    }
+
+    /// Loads a JWK set used to authenticate an API.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the secret with the set.
+    /// - `path`: The file with the set given on the command line, if any, which takes precedence over the secret.
+    /// - `example`: The example set to fall back to if there is neither.
+    ///
+    /// # Errors
+    /// This function errors if the set could not be read or parsed.
+    fn jwk_set(&self, name: &'static str, path: Option<&PathBuf>, example: &str) -> Result<KidResolver, Error> {
+        let res = match (path, self.secrets.get(name)) {
+            (Some(path), _) => KidResolver::new(&path.display().to_string()),
+            (None, Some(raw)) => KidResolver::from_json(&String::from_utf8_lossy(raw)),
+            (None, None) => {
+                warn!("No JWK set '{name}' given; falling back to the example set '{example}', which is NOT fit for production use");
+                KidResolver::new(example)
+            },
+        };
+        res.map_err(|err| Error::JwkSet { name, err })
+    }
+
+    /// Loads the JWK set used to authenticate the policy expert API.
+    ///
+    /// # Errors
+    /// This function errors if the set could not be read or parsed.
+    #[inline]
+    pub fn jwk_set_expert(&self) -> Result<KidResolver, Error> {
+        self.jwk_set(JWK_SET_EXPERT_SECRET, self.jwk_set_expert.as_ref(), EXAMPLE_JWK_SET_EXPERT)
+    }
+
+    /// Loads the JWK set used to authenticate the deliberation API.
+    ///
+    /// # Errors
+    /// This function errors if the set could not be read or parsed.
+    #[inline]
+    pub fn jwk_set_delib(&self) -> Result<KidResolver, Error> {
+        self.jwk_set(JWK_SET_DELIB_SECRET, self.jwk_set_delib.as_ref(), EXAMPLE_JWK_SET_DELIB)
+    }
+
+    /// Returns the configuration of the circuit breaker in front of the reasoner connector.
+    #[inline]
+    pub fn circuit_breaker(&self) -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: self.circuit_breaker_threshold,
+            latency_slo: self.circuit_breaker_latency_slo.map(Duration::from_millis),
+            open_duration: Duration::from_millis(self.circuit_breaker_open_duration),
+        }
+    }
+
+    /// Returns how to retry questions that the reasoner backend failed to answer transiently.
+    #[inline]
+    pub fn reasoner_retry(&self) -> RetryConfig {
+        RetryConfig {
+            max_attempts: self.reasoner_retry_attempts.max(1),
+            base_backoff: Duration::from_millis(self.reasoner_retry_backoff),
+            max_backoff:  Duration::from_millis(self.reasoner_retry_max_backoff),
+        }
+    }
+
+    /// Returns the configuration of the queue in front of the reasoner connector.
+    #[inline]
+    pub fn question_queue(&self) -> QuestionQueueConfig { QuestionQueueConfig { concurrency: self.queue_concurrency, capacity: self.queue_capacity } }
+
+    /// Returns the configuration of the lint rules run on workflows before deliberating on them.
+    #[inline]
+    pub fn lint_config(&self) -> LintConfig { self.lint.iter().copied().collect() }
+
+    /// Returns the limits on the size and complexity of workflows.
+    #[inline]
+    pub fn workflow_limits(&self) -> WorkflowLimits {
+        WorkflowLimits { max_edges: self.max_workflow_edges, max_inline_expansion: self.max_inline_expansion, max_loops: self.max_workflow_loops }
+    }
+
+    /// Reads the transformers to pass the reasoner's answers through, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the transformers file could not be read or parsed.
+    pub fn verdict_pipeline(&self) -> Result<VerdictPipeline, Error> {
+        let Some(path) = &self.verdict_transformers else { return Ok(VerdictPipeline::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::VerdictTransformersRead { path: path.clone(), err })?;
+        let configs: Vec<VerdictTransformerConfig> =
+            serde_yaml::from_str(&raw).map_err(|err| Error::VerdictTransformersParse { path: path.clone(), err })?;
+        Ok(VerdictPipeline::from_config(configs))
+    }
+
+    /// Reads the rules to answer questions with before the reasoner is asked, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the prefilter rules file could not be read or parsed.
+    pub fn prefilter(&self) -> Result<Prefilter, Error> {
+        let Some(path) = &self.prefilter else { return Ok(Prefilter::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::PrefilterRead { path: path.clone(), err })?;
+        let rules: Vec<PrefilterRule> = serde_yaml::from_str(&raw).map_err(|err| Error::PrefilterParse { path: path.clone(), err })?;
+        Ok(Prefilter::new(rules))
+    }
+
+    /// Reads the recurring maintenance windows of the reasoner backend, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the maintenance windows file could not be read or parsed.
+    pub fn maintenance(&self) -> Result<MaintenanceSchedule, Error> {
+        let Some(path) = &self.maintenance else { return Ok(MaintenanceSchedule::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::MaintenanceRead { path: path.clone(), err })?;
+        let windows: Vec<RecurringMaintenance> = serde_yaml::from_str(&raw).map_err(|err| Error::MaintenanceParse { path: path.clone(), err })?;
+        Ok(MaintenanceSchedule::new(windows))
+    }
+
+    /// Reads the partner checkers that questions about their datasets are forwarded to, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the federation file could not be read or parsed.
+    pub fn federation(&self) -> Result<Federation, Error> {
+        let Some(path) = &self.federation else { return Ok(Federation::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::FederationRead { path: path.clone(), err })?;
+        let config: FederationConfig = serde_yaml::from_str(&raw).map_err(|err| Error::FederationParse { path: path.clone(), err })?;
+        Ok(Federation::new(config))
+    }
+
+    /// Reads the redacted summary of the policy that is disclosed to other domains, if one is given.
+    ///
+    /// # Errors
+    /// This function errors if the disclosure file could not be read or parsed.
+    pub fn disclosure(&self) -> Result<Disclosure, Error> {
+        let Some(path) = &self.disclosure else { return Ok(Disclosure::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::DisclosureRead { path: path.clone(), err })?;
+        let config: DisclosureConfig = serde_yaml::from_str(&raw).map_err(|err| Error::DisclosureParse { path: path.clone(), err })?;
+        Ok(Disclosure::new(config))
+    }
+
+    /// Reads the public keys to verify image signatures with, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the keys file could not be read or parsed, or if any key in it is invalid.
+    pub fn image_keys(&self) -> Result<StaticKeySet, Error> {
+        let Some(path) = &self.image_keys else { return Ok(StaticKeySet::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::ImageKeysRead { path: path.clone(), err })?;
+        let keys: BTreeMap<String, String> = serde_yaml::from_str(&raw).map_err(|err| Error::ImageKeysParse { path: path.clone(), err })?;
+        StaticKeySet::from_pem(keys).map_err(|err| Error::ImageKey { path: path.clone(), err })
+    }
+
+    /// Reads the translations of the reasons given to clients, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the message catalog file could not be read or parsed.
+    pub fn message_catalog(&self) -> Result<MessageCatalog, Error> {
+        let Some(path) = &self.messages else { return Ok(MessageCatalog::default()) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::MessagesRead { path: path.clone(), err })?;
+        serde_yaml::from_str(&raw).map_err(|err| Error::MessagesParse { path: path.clone(), err })
+    }
+
+    /// Reads the secret key used to sign policy archives, if any is given (either as a file or as a secret).
+    ///
+    /// Trailing whitespace (e.g., a newline) in the key file is ignored.
+    ///
+    /// # Errors
+    /// This function errors if the key file could not be read.
+    pub fn archive_key(&self) -> Result<Option<Vec<u8>>, Error> {
+        let Some(path) = &self.archive_key else { return Ok(self.secrets.get(ARCHIVE_KEY_SECRET).cloned()) };
+        read_key(path).map(Some).map_err(|err| Error::ArchiveKeyRead { path: path.clone(), err })
+    }
+
+    /// Reads the key that encrypts policies at rest, if any is given.
+    ///
+    /// # Errors
+    /// This function errors if the key could not be read or is not 32 hex-encoded bytes.
+    pub fn policy_cipher(&self) -> Result<Option<PolicyCipher>, Error> {
+        let Some(source) = &self.policy_encryption_key else { return Ok(None) };
+        PolicyCipher::new(source).map(Some).map_err(|err| Error::PolicyEncryptionKey { err })
+    }
+
+    /// Reads the key used to sign access tokens, if any is given.
+    ///
+    /// # Errors
+    /// This function errors if the key file could not be read or does not contain a supported private key.
+    pub fn access_token_issuer(&self) -> Result<Option<AccessTokenIssuer>, Error> {
+        let Some(path) = &self.access_token_key else { return Ok(None) };
+        let pem: Vec<u8> = fs::read(path).map_err(|err| Error::AccessTokenKeyRead { path: path.clone(), err })?;
+        AccessTokenIssuer::from_pem(&pem, self.access_token_key_id.clone(), Duration::from_secs(self.access_token_ttl))
+            .map(Some)
+            .map_err(|err| Error::AccessTokenKey { path: path.clone(), err })
+    }
+
+    /// Reads the rules that decide which verdicts the owners of datasets are notified of, if any are given.
+    ///
+    /// # Errors
+    /// This function errors if the notification rules file could not be read or parsed.
+    pub fn notifier(&self) -> Result<Option<Notifier>, Error> {
+        let Some(path) = &self.notification_rules else { return Ok(None) };
+        let raw: String = fs::read_to_string(path).map_err(|err| Error::NotificationRulesRead { path: path.clone(), err })?;
+        let rules: Vec<NotificationRule> = serde_yaml::from_str(&raw).map_err(|err| Error::NotificationRulesParse { path: path.clone(), err })?;
+        let signing_key: Option<Vec<u8>> = match &self.webhook_signing_key {
+            Some(path) => Some(read_key(path).map_err(|err| Error::WebhookSigningKeyRead { path: path.clone(), err })?),
+            None => self.secrets.get(WEBHOOK_SIGNING_KEY_SECRET).cloned(),
+        };
+        Ok(Some(Notifier::new(rules, &self.notification_sendmail).with_signing_key(signing_key)))
+    }
+
+    /// Returns the [`Redactor`] to apply to every audit log statement.
+    ///
+    /// # Errors
+    /// This function errors if the pseudonym key file could not be read.
+    pub fn audit_redactor(&self) -> Result<Redactor, Error> {
+        let key: Option<Vec<u8>> = match &self.audit_pseudonym_key {
+            Some(path) => Some(read_key(path).map_err(|err| Error::PseudonymKeyRead { path: path.clone(), err })?),
+            None => self.secrets.get(PSEUDONYM_KEY_SECRET).cloned(),
+        };
+        Ok(Redactor::new(key, self.audit_drop_payloads))
+    }
+
+    /// Returns where to archive the full payloads of questions, if anywhere.
+    pub fn payload_store(&self) -> Option<PayloadStore> {
+        let dir: &PathBuf = self.payload_dir.as_ref()?;
+        Some(PayloadStore::new(dir.clone(), self.payload_max_size, self.payload_retention.map(Duration::from_secs)))
+    }
+
+    /// Returns where to ship the audit log to, if anywhere.
+    ///
+    /// # Errors
+    /// This function errors if a log store is given without a URL, or an object store without (readable) credentials.
+    pub fn audit_forwarder(&self) -> Result<Option<ForwarderConfig>, Error> {
+        let Some(kind) = self.audit_forward else { return Ok(None) };
+        let Some(url) = &self.audit_forward_url else { return Err(Error::ForwardUrlMissing { kind }) };
+        let s3: Option<S3Config> = if kind == ForwardKind::S3 {
+            let access_key: Option<String> = self
+                .audit_s3_access_key
+                .clone()
+                .or_else(|| self.secrets.get(S3_ACCESS_KEY_SECRET).map(|key| String::from_utf8_lossy(key).into_owned()));
+            let secret_key: Option<Vec<u8>> = match &self.audit_s3_secret_key {
+                Some(path) => Some(read_key(path).map_err(|err| Error::S3SecretKeyRead { path: path.clone(), err })?),
+                None => self.secrets.get(S3_SECRET_KEY_SECRET).cloned(),
+            };
+            let (Some(access_key), Some(secret_key)) = (access_key, secret_key) else { return Err(Error::S3CredentialsMissing) };
+            Some(S3Config {
+                region: self.audit_s3_region.clone(),
+                access_key,
+                secret_key: String::from_utf8_lossy(&secret_key).into_owned(),
+                encryption: self.audit_s3_encryption.clone(),
+                object_lock: self.audit_s3_object_lock.zip(self.audit_s3_retain_days),
+            })
+        } else {
+            None
+        };
+        Ok(Some(ForwarderConfig {
+            kind,
+            url: url.clone(),
+            index: self.audit_forward_index.clone(),
+            queue_size: self.audit_forward_queue_size,
+            batch_size: self.audit_forward_batch_size,
+            timeout: Duration::from_millis(self.audit_forward_timeout),
+            s3,
+        }))
+    }
+}
+
+/***** HELPERS *****/
+/// Reads a secret key from a file, ignoring trailing whitespace (e.g., a newline).
+fn read_key(path: &PathBuf) -> Result<Vec<u8>, std::io::Error> {
+    let mut key: Vec<u8> = fs::read(path)?;
+    while key.last().map(|b| b.is_ascii_whitespace()).unwrap_or(false) {
+        key.pop();
+    }
+    Ok(key)
+}
+
+/// Defines the subcommands that can be given instead of running the server.
+#[derive(Debug, Subcommand)]
+pub enum Action {
+    #[cfg(feature = "init")]
+    #[clap(name = "init", about = "Scaffolds a working local deployment (keys, tokens, config, database and a docker-compose file).")]
+    Init(InitArguments),
+    #[clap(name = "simulate", about = "Replays the questions in an audit log against a policy file, without running the server.")]
+    Simulate(SimulateArguments),
 }
@@ -1,4 +1,11 @@
 pub mod eflint;
+pub mod eflint_supervisor;
+#[cfg(feature = "init")]
+pub mod init;
 pub mod interface;
 pub mod no_op;
+#[cfg(feature = "brane")]
 pub mod posix;
+pub mod simulate;
+pub mod static_list;
+pub mod subprocess;
@@ -31,8 +31,9 @@
 //! - [Access data](fn@PosixReasonerConnector::access_data_request)
 //! - [Workflow validation](fn@PosixReasonerConnector::workflow_validation_request)
 //!
-//! As of now, the assumption is taken that it does not matter for this reasoner which type of request comes in, as we
-//! only look at the data usage in the [Workflow].
+//! Each of these only looks at the data usage in the part of the [Workflow] that it is about (see [QuestionScope]):
+//! executing a task checks that its inputs may be read, accessing data checks that the dataset may be read by the task
+//! (or downloaded as a result, without a task) and only validating a workflow checks all data usage in it.
 //!
 //! As one of these requests comes in, the provided [Workflow] is parsed using a [DatasetCollectorVisitor] (an
 //! implementation of the new util trait [WorkflowVisitor]) and all data accesses in the workflow are gathered and
@@ -53,6 +54,20 @@
 //! neither of these is true, the other permissions are checked. If the user has the required permissions, the request is
 //! approved. If not, the request is denied : [satisfies_posix_permissions].
 //!
+//! Next to the mapping, a location may define [path rules](PosixPathRule) that grant permissions on groups of datasets
+//! by their path, such as everything under `/shares/public/**` being readable by everyone. A rule is checked before the
+//! file itself: if any rule matching the path grants the required permissions to the user, the file's permissions do
+//! not matter (and the user need not be mapped). If access is denied anyway, the reasons tell which rules matched the
+//! path and what they grant, such that it is clear why none of them applied.
+//!
+//! As workflows may touch hundreds of files, and the same files are checked for many questions, the metadata of files
+//! is cached for a short while (the `STAT_CACHE_TTL_MS` environment variable, 5 seconds by default) in a [StatCache].
+//! How often the cache is hit is exposed on the metrics endpoint.
+//!
+//! To debug why a mount denies access, the reasoner can be put in a dry run (`POSIX_DRY_RUN=1`). It then denies every
+//! question, with the verdict it would have given and the effective permissions matrix as reasons: for every file and
+//! required permission, through which classes the mapped identity has it ([PosixReasonerConnector::with_dry_run]).
+//!
 //!
 //! # State of the implementation
 //!
@@ -83,10 +98,10 @@
 //! but also the policy reasoner needs to reach at least the directory in which the file resides in order for the
 //! reasoner to be able to `stat(1)` the file.
 //!
-//! Right now, we are seemingly limited by the fact that the policy reasoner GUI does not send at which site a [Dataset]
-//! is accessed, making it impossible to fully know which of the mappings in the policy to use. There is a location
-//! field that could be used, but since it is always set to `None`, we opted to fall back to the assumed location as
-//! hardcoded in the static: [`ASSUMED_LOCATION`]
+//! Which of the mappings in the policy to use depends on the site at which a [Dataset] is accessed. This is taken from
+//! the workflow's planning information: the location of the task or commit accessing it, or the location a dataset is
+//! pulled from. If a location cannot be found, the request is denied with a [`ValidationError::MissingLocation`] rather
+//! than guessing one.
 //!
 //! # Future work
 //!
@@ -128,27 +143,28 @@
 //! [no_op](crate::no_op)) reasoner can help guide future contributors in either extension of the current reasoners or
 //! the addition of new reasoner types.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::iter::repeat;
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use audit_logger::{ConnectorContext, ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::{DenyCategory, Granularity};
 use itertools::{Either, Itertools};
 use log::{debug, error, info};
 use policy::{Policy, PolicyContent};
-use reasonerconn::{ReasonerConnError, ReasonerConnector, ReasonerResponse};
-use serde::Deserialize;
+use reasonerconn::policy_cache::PolicyCache;
+use reasonerconn::{PolicyCompositionError, ReasonerConnError, ReasonerConnector, ReasonerMetric, ReasonerResponse};
+use serde::{Deserialize, Serialize};
 use specifications::data::{DataIndex, Location};
 use state_resolver::State;
 use workflow::Dataset;
 use workflow::spec::Workflow;
 use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
 
-/// This location is an assumption right now, and is needed as long as the location is not passed to the workflow
-/// validator.
-static ASSUMED_LOCATION: &str = "surf";
-
 /***** LIBRARY *****/
 /// E.g., `st_antonius_etc`.
 type LocationIdentifier = String;
@@ -164,10 +180,18 @@ pub struct PosixPolicy {
 impl PosixPolicy {
     /// Extracts and parses a [`PosixPolicy`] from a generic [`Policy`] object. Expects the policy to be specified and
     /// expects it to adhere to the [`PosixPolicy`] YAML structure. See [`PosixPolicy`].
-    fn from_policy(policy: Policy) -> Self {
-        let policy_content: PolicyContent = policy.content.first().expect("Failed to parse PolicyContent").clone();
+    ///
+    /// # Errors
+    /// This function errors with why the policy is not a valid [`PosixPolicy`].
+    fn try_from_policy(policy: &Policy) -> Result<Self, String> {
+        let policy_content: &PolicyContent = policy.content.first().ok_or("Failed to parse PolicyContent")?;
         let content_str = policy_content.content.get().trim();
-        PosixPolicy { datasets: serde_json::from_str(content_str).expect("Failed to parse PosixPolicy") }
+        serde_json::from_str(content_str).map(|datasets| PosixPolicy { datasets }).map_err(|err| format!("Failed to parse PosixPolicy: {err}"))
+    }
+
+    /// Returns the part of the policy for the given location (e.g., `st_antonius_ect`).
+    fn get_location(&self, location: &str) -> Result<&PosixPolicyLocation, PolicyError> {
+        self.datasets.get(location).ok_or_else(|| PolicyError::MissingLocation(location.to_owned()))
     }
 
     /// Given a location (e.g., `st_antonius_ect`) and the workflow user's name (e.g., `test`), returns the
@@ -176,9 +200,7 @@ impl PosixPolicy {
     /// The returned identity is used for file permission checks. For more about this permissions check see
     /// [`validate_dataset_permissions`].
     fn get_local_identity(&self, location: &str, workflow_user: &str) -> Result<&PosixLocalIdentity, PolicyError> {
-        self.datasets
-            .get(location)
-            .ok_or_else(|| PolicyError::MissingLocation(location.to_owned()))?
+        self.get_location(location)?
             .user_map
             .get(workflow_user)
             .ok_or_else(|| PolicyError::MissingUser(workflow_user.to_owned(), location.to_owned()))
@@ -196,10 +218,128 @@ enum PolicyError {
 }
 
 /// Part of the [`PosixPolicy`]. Represents a location (e.g., `st_antonius_etc`) and contains the global workflow
-/// username to local identity mappings for this location.
+/// username to local identity mappings for this location, and the path rules that apply at it.
 #[derive(Deserialize, Debug)]
 pub struct PosixPolicyLocation {
+    #[serde(default)]
     user_map: HashMap<GlobalUsername, PosixLocalIdentity>,
+    #[serde(default)]
+    rules:    Vec<PosixPathRule>,
+}
+
+impl PosixPolicyLocation {
+    /// Finds a path rule that grants the given user all of the requested permissions on the file at `path`.
+    ///
+    /// Returns the first rule that does, or otherwise all rules that match the path (but do not grant enough), such
+    /// that a denial can tell why they did not apply.
+    fn match_rules(
+        &self,
+        workflow_user: &str,
+        path: &Path,
+        requested_permissions: &[PosixFilePermission],
+    ) -> Result<&PosixPathRule, Vec<&PosixPathRule>> {
+        let mut matched = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.matches(path)) {
+            if rule.grants(workflow_user, requested_permissions) {
+                return Ok(rule);
+            }
+            matched.push(rule);
+        }
+        Err(matched)
+    }
+}
+
+/// Part of a [`PosixPolicyLocation`]. Grants permissions on all datasets whose path matches a pattern, either to
+/// everyone or to the listed global usernames only, regardless of the permissions on the files themselves.
+///
+/// A pattern without wildcards matches the path itself and everything under it. Otherwise, it must match the whole
+/// path, where `*` matches any part of a single path component, `?` any single character in it and a `**` component
+/// any number of components.
+///
+/// Example, the policy below makes everything under `/shares/public` readable by everyone at `st_antonius_ect`, and
+/// lets `test` write any CSV file directly in `/shares/results`.
+/// ``` yaml
+///  # file: posix-policy.yml
+///  content:
+///    st_antonius_ect:
+///      rules:
+///        - pattern: /shares/public/**
+///          permissions: [ read ]
+///        - pattern: /shares/results/*.csv
+///          permissions: [ read, write ]
+///          users: [ test ]
+/// ```
+#[derive(Clone, Deserialize, Serialize, Debug, Eq, PartialEq)]
+struct PosixPathRule {
+    /// The path prefix or glob that paths must match.
+    pattern: String,
+    /// The permissions granted on the matching paths.
+    permissions: Vec<PosixFilePermission>,
+    /// The global usernames the permissions are granted to, or everyone if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    users: Option<Vec<GlobalUsername>>,
+}
+
+impl PosixPathRule {
+    /// Returns whether the file at `path` is covered by this rule.
+    ///
+    /// Paths with `..` components never match, as they may point outside of what the pattern covers (e.g.,
+    /// `/shares/public/../private`). Such paths are left to the file permissions instead.
+    fn matches(&self, path: &Path) -> bool {
+        let pattern: Vec<&str> = self.pattern.split('/').filter(|component| !component.is_empty()).collect();
+        let mut components: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(component) => components.push(component.to_string_lossy().into_owned()),
+                Component::ParentDir => return false,
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {},
+            }
+        }
+        let path: Vec<&str> = components.iter().map(String::as_str).collect();
+        if !self.pattern.contains(['*', '?']) {
+            return path.starts_with(&pattern);
+        }
+        glob_matches(&pattern, &path)
+    }
+
+    /// Returns whether this rule grants the given user all of the requested permissions.
+    fn grants(&self, workflow_user: &str, requested_permissions: &[PosixFilePermission]) -> bool {
+        self.users.as_ref().is_none_or(|users| users.iter().any(|user| user == workflow_user))
+            && requested_permissions.iter().all(|permission| self.permissions.contains(permission))
+    }
+}
+
+impl std::fmt::Display for PosixPathRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let permissions = self.permissions.iter().map(|permission| permission.name()).join(", ");
+        match &self.users {
+            Some(users) => write!(f, "'{}' (grants {} to {})", self.pattern, permissions, users.join(", ")),
+            None => write!(f, "'{}' (grants {} to everyone)", self.pattern, permissions),
+        }
+    }
+}
+
+/// Matches the components of a path against those of a glob pattern. See [`PosixPathRule`].
+fn glob_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| glob_matches(rest, &path[skip..])),
+        Some((component, rest)) => path.split_first().is_some_and(|(first, path)| component_matches(component, first) && glob_matches(rest, path)),
+    }
+}
+
+/// Matches a single path component against a component of a glob pattern, which may contain `*` and `?`.
+fn component_matches(pattern: &str, component: &str) -> bool {
+    let mut pattern = pattern.chars();
+    match pattern.next() {
+        None => component.is_empty(),
+        Some('*') => component.char_indices().map(|(i, _)| i).chain([component.len()]).any(|i| component_matches(pattern.as_str(), &component[i..])),
+        Some('?') => {
+            let mut component = component.chars();
+            component.next().is_some() && component_matches(pattern.as_str(), component.as_str())
+        },
+        Some(c) => component.strip_prefix(c).is_some_and(|component| component_matches(pattern.as_str(), component)),
+    }
 }
 
 /// The local identity defines a user id and a list of group ids. The local identity is used on the machine on which a
@@ -223,7 +363,7 @@ pub struct PosixPolicyLocation {
 ///            - 1002
 ///            - 1003
 /// ```
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug, Eq, PartialEq)]
 struct PosixLocalIdentity {
     /// The user identifier of a Linux user.
     uid:  u32,
@@ -232,7 +372,8 @@ struct PosixLocalIdentity {
 }
 
 /// Represents a POSIX file permission. See: <https://en.wikipedia.org/wiki/File-system_permissions#Permissions>.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
 enum PosixFilePermission {
     Read,
     Write,
@@ -256,6 +397,15 @@ impl PosixFilePermission {
             PosixFilePermission::Execute => 1,
         }
     }
+
+    /// Returns the name of this permission, as used in the permissions matrix.
+    fn name(self) -> &'static str {
+        match self {
+            PosixFilePermission::Read => "read",
+            PosixFilePermission::Write => "write",
+            PosixFilePermission::Execute => "execute",
+        }
+    }
 }
 
 /// Represents a POSIX file class, also known as a scope. See:
@@ -281,64 +431,198 @@ impl PosixFileClass {
         };
         required_permissions.iter().fold(0, |acc, f| acc | (alignment_multiplier * f.to_mode_bit()))
     }
+
+    /// Returns the name of this class, as used in the permissions matrix.
+    fn name(self) -> &'static str {
+        match self {
+            PosixFileClass::Owner => "owner",
+            PosixFileClass::Group => "group",
+            PosixFileClass::Others => "others",
+        }
+    }
+}
+
+/// The parts of a file's metadata that permissions are checked against, as kept by the [`StatCache`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct FileStat {
+    /// The mode bits of the file.
+    mode:    u32,
+    /// The user id of the file owner.
+    uid:     u32,
+    /// The group id of the file owner.
+    gid:     u32,
+    /// When the file was last modified and when its metadata last changed (e.g., by `chmod(1)`), as `(s, ns)` pairs.
+    changed: [(i64, i64); 2],
+}
+
+/// A cache of file metadata, such that checking the same file for many questions does not `stat(1)` it every time.
+///
+/// Entries are trusted for a short time to live (TTL) only, as permissions may change at any time. After that, the
+/// file is stat'ed again; if its modification or change time differs from before, this counts as an invalidation.
+struct StatCache {
+    /// How long entries are trusted. A zero TTL disables the cache.
+    ttl: Duration,
+    /// The cached metadata by path, with when it was read.
+    entries: Mutex<HashMap<PathBuf, (Instant, FileStat)>>,
+    /// The number of lookups answered from the cache.
+    hits: AtomicU64,
+    /// The number of lookups that had to stat the file.
+    misses: AtomicU64,
+    /// The number of files found to have changed since they were cached.
+    invalidations: AtomicU64,
+}
+
+impl StatCache {
+    fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: Mutex::new(HashMap::new()), hits: AtomicU64::new(0), misses: AtomicU64::new(0), invalidations: AtomicU64::new(0) }
+    }
+
+    /// Returns the metadata of the file at `path`, from the cache if it is still fresh.
+    fn stat(&self, path: &Path) -> std::io::Result<FileStat> {
+        let mut entries = self.entries.lock().unwrap();
+        let previous: Option<FileStat> = match entries.get(path) {
+            Some((read, stat)) if read.elapsed() < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(*stat);
+            },
+            Some((_, stat)) => Some(*stat),
+            None => None,
+        };
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                entries.remove(path);
+                return Err(err);
+            },
+        };
+        let stat = FileStat {
+            mode:    metadata.permissions().mode(),
+            uid:     metadata.uid(),
+            gid:     metadata.gid(),
+            changed: [(metadata.mtime(), metadata.mtime_nsec()), (metadata.ctime(), metadata.ctime_nsec())],
+        };
+        if previous.is_some_and(|previous| previous.changed != stat.changed) {
+            debug!("Cached metadata of '{}' is outdated", path.display());
+            self.invalidations.fetch_add(1, Ordering::Relaxed);
+        }
+        if !self.ttl.is_zero() {
+            entries.insert(path.into(), (Instant::now(), stat));
+        }
+        Ok(stat)
+    }
+
+    /// Describes how well the cache does, for the metrics endpoint.
+    fn metrics(&self) -> Vec<ReasonerMetric> {
+        vec![
+            ReasonerMetric::new(
+                "policy_reasoner_posix_stat_cache_hits_total",
+                "The number of file permission checks answered from the metadata cache.",
+                self.hits.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_posix_stat_cache_misses_total",
+                "The number of file permission checks that had to stat the file.",
+                self.misses.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_posix_stat_cache_invalidations_total",
+                "The number of cached file metadata entries found to be outdated.",
+                self.invalidations.load(Ordering::Relaxed) as f64,
+            ),
+            ReasonerMetric::new(
+                "policy_reasoner_posix_stat_cache_entries",
+                "The number of files whose metadata is cached.",
+                self.entries.lock().unwrap().len() as f64,
+            ),
+        ]
+    }
 }
 
 /// Verifies whether the passed [`PosixLocalIdentity`] has all of the requested permissions (e.g., `Read` and `Write`)
 /// on a particular file (defined by the `path`). The identity's user id and group ids are checked against the file
 /// owner's user id and group id respectively. Additionally, the `Others` class permissions are also checked.
-fn satisfies_posix_permissions(path: impl AsRef<Path>, local_identity: &PosixLocalIdentity, requested_permissions: &[PosixFilePermission]) -> bool {
-    let metadata = std::fs::metadata(&path).expect("Could not get file metadata");
+fn satisfies_posix_permissions(
+    stat_cache: &StatCache,
+    path: impl AsRef<Path>,
+    local_identity: &PosixLocalIdentity,
+    requested_permissions: &[PosixFilePermission],
+) -> bool {
+    let metadata = stat_cache.stat(path.as_ref()).expect("Could not get file metadata");
+    !satisfying_classes(&metadata, local_identity, requested_permissions).is_empty()
+}
 
-    let mode_bits = metadata.permissions().mode();
-    let file_owner_uid = metadata.uid();
-    let file_owner_gid = metadata.gid();
+/// Returns through which of the [`PosixFileClass`]es the passed [`PosixLocalIdentity`] has all of the requested
+/// permissions on a file with the given metadata. See [`satisfies_posix_permissions`].
+fn satisfying_classes(
+    metadata: &FileStat,
+    local_identity: &PosixLocalIdentity,
+    requested_permissions: &[PosixFilePermission],
+) -> Vec<PosixFileClass> {
+    let mode_bits = metadata.mode;
+    let file_owner_uid = metadata.uid;
+    let file_owner_gid = metadata.gid;
 
+    let mut classes = Vec::with_capacity(3);
     if file_owner_uid == local_identity.uid {
         let mask = PosixFileClass::Owner.get_mode_bitmask(requested_permissions);
         if mode_bits & mask == mask {
-            return true;
+            classes.push(PosixFileClass::Owner);
         }
     }
 
     if local_identity.gids.contains(&file_owner_gid) {
         let mask = PosixFileClass::Group.get_mode_bitmask(requested_permissions);
         if mode_bits & mask == mask {
-            return true;
+            classes.push(PosixFileClass::Group);
         }
     }
 
     let mask = PosixFileClass::Others.get_mode_bitmask(requested_permissions);
-    mode_bits & mask == mask
+    if mode_bits & mask == mask {
+        classes.push(PosixFileClass::Others);
+    }
+    classes
 }
 
 enum ValidationOutput {
     Ok,
     // Below we might want to encapsulate the Dataset itself.
-    /// The string here represents a `Dataset.name`.
-    Fail(Vec<String>),
+    /// The strings here represent a `Dataset.name` and why access to it is denied.
+    Fail(Vec<(String, String)>),
 }
 
 /// Represents a validation error that occurred during the validation of a workflow. These errors contain more
 /// information about the problems that occurred during validation.
 #[derive(thiserror::Error, Debug)]
 enum ValidationError {
+    #[error("Missing location: {0} is not planned at any location")]
+    MissingLocation(String),
+    #[error("Not in workflow: {0}")]
+    NotInWorkflow(String),
     #[error("Policy Error: {0}")]
     PolicyError(PolicyError),
     #[error("Unknown dataset: {0}")]
     UnknownDataset(String),
 }
 
-/// Check if all the data accesses performed in the `workflow` are done on behalf of users that have the required
-/// permissions. If not all permissions are met, then [`ValidationError`]s are returned. These errors contain more
-/// information about the problems that occurred during validation.
-fn validate_dataset_permissions(workflow: &Workflow, data_index: &DataIndex, policy: &PosixPolicy) -> Result<ValidationOutput, Vec<ValidationError>> {
+/// Check if all the data accesses performed in the part of the `workflow` that a question is about (its `scope`) are
+/// done on behalf of users that have the required permissions. If not all permissions are met, then
+/// [`ValidationError`]s are returned. These errors contain more information about the problems that occurred during
+/// validation.
+fn validate_dataset_permissions(
+    workflow: &Workflow,
+    scope: QuestionScope,
+    data_index: &DataIndex,
+    stat_cache: &StatCache,
+    policy: &PosixPolicy,
+) -> Result<ValidationOutput, Vec<ValidationError>> {
     // The datasets used in the workflow. E.g., `st_antonius_ect`.
-    let datasets = find_datasets_in_workflow(workflow);
+    let datasets = find_datasets_in_workflow(workflow, scope)?;
 
-    let (forbidden, errors): (Vec<_>, Vec<_>) = std::iter::empty()
-        .chain(datasets.read_sets.iter().zip(repeat(vec![PosixFilePermission::Read])))
-        .chain(datasets.write_sets.iter().zip(repeat(vec![PosixFilePermission::Write])))
-        .chain(datasets.execute_sets.iter().zip(repeat(vec![PosixFilePermission::Read, PosixFilePermission::Execute])))
+    let (forbidden, errors): (Vec<_>, Vec<_>) = datasets
+        .accesses()
         .flat_map(|((location, dataset), permission)| {
             let Some(dataset) = data_index.get(&dataset.name) else {
                 return Either::Left(std::iter::once(Err(ValidationError::UnknownDataset(dataset.name.clone()))));
@@ -346,9 +630,26 @@ fn validate_dataset_permissions(workflow: &Workflow, data_index: &DataIndex, pol
             Either::Right(dataset.access.values().map(move |kind| match kind {
                 specifications::data::AccessKind::File { path } => {
                     info!("Contents of the DataInfo object:\n{:#?}", dataset);
+                    let policy_location = policy.get_location(location).map_err(ValidationError::PolicyError)?;
+                    let matched = match policy_location.match_rules(&workflow.user.name, path, &permission) {
+                        Ok(rule) => {
+                            debug!("Access to '{}' is granted by path rule {rule}", path.display());
+                            return Ok((dataset.name.clone(), None));
+                        },
+                        Err(matched) => matched,
+                    };
                     let local_identity = policy.get_local_identity(location, &workflow.user.name).map_err(ValidationError::PolicyError)?;
-                    let result = satisfies_posix_permissions(path, local_identity, &permission);
-                    Ok((dataset.name.clone(), path, result))
+                    if satisfies_posix_permissions(stat_cache, path, local_identity, &permission) {
+                        return Ok((dataset.name.clone(), None));
+                    }
+                    let permissions = permission.iter().map(|permission| permission.name()).join(", ");
+                    let rules = if matched.is_empty() {
+                        "no path rule matches it".into()
+                    } else {
+                        format!("matching path rules do not grant it: {}", matched.iter().join(", "))
+                    };
+                    let reason = format!("{permissions} on '{}' is not given by the file permissions, and {rules}", path.display());
+                    Ok((dataset.name.clone(), Some(reason)))
                 },
             }))
         })
@@ -357,11 +658,11 @@ fn validate_dataset_permissions(workflow: &Workflow, data_index: &DataIndex, pol
         // validation failures.
         .filter(|res| match res {
             // Filter out what was okay in either sense.
-            Ok((_, _, true)) => false,
+            Ok((_, None)) => false,
             _ => true,
         })
         .partition_map(|elem| match elem {
-            Ok((dataset_identifier, _, _)) => Either::Left(dataset_identifier),
+            Ok((dataset_identifier, reason)) => Either::Left((dataset_identifier, reason.unwrap_or_default())),
             Err(x) => Either::Right(x),
         });
 
@@ -374,17 +675,134 @@ fn validate_dataset_permissions(workflow: &Workflow, data_index: &DataIndex, pol
     }
 }
 
+/// One entry in the effective permissions matrix: through which classes the mapped identity has a permission on a
+/// file of a dataset. See [`permission_matrix`].
+#[derive(Debug, Serialize)]
+struct PermissionMatrixEntry {
+    dataset: String,
+    location: Location,
+    path: PathBuf,
+    uid: u32,
+    gids: Vec<u32>,
+    permission: &'static str,
+    /// The classes through which the permission is given; empty if it is not.
+    satisfied_by: Vec<&'static str>,
+    /// The pattern of the path rule that gives the permission regardless of the classes, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule: Option<String>,
+}
+
+/// Builds the effective permissions matrix of the data accesses in the part of the `workflow` that a question is about,
+/// with one entry per file and required permission. Accesses that cannot be checked (e.g., because the dataset or user
+/// is unknown) are left out, as [`validate_dataset_permissions`] reports them.
+fn permission_matrix(
+    workflow: &Workflow,
+    scope: QuestionScope,
+    data_index: &DataIndex,
+    stat_cache: &StatCache,
+    policy: &PosixPolicy,
+) -> Vec<PermissionMatrixEntry> {
+    let Ok(datasets) = find_datasets_in_workflow(workflow, scope) else { return vec![] };
+
+    let mut matrix = Vec::new();
+    for ((location, dataset), permissions) in datasets.accesses() {
+        let (Some(info), Ok(local_identity)) = (data_index.get(&dataset.name), policy.get_local_identity(location, &workflow.user.name)) else {
+            continue;
+        };
+        let Ok(policy_location) = policy.get_location(location) else { continue };
+        for kind in info.access.values() {
+            match kind {
+                specifications::data::AccessKind::File { path } => {
+                    let Ok(metadata) = stat_cache.stat(path) else { continue };
+                    for permission in &permissions {
+                        matrix.push(PermissionMatrixEntry {
+                            dataset: dataset.name.clone(),
+                            location: location.clone(),
+                            path: path.clone(),
+                            uid: local_identity.uid,
+                            gids: local_identity.gids.clone(),
+                            permission: permission.name(),
+                            satisfied_by: satisfying_classes(&metadata, local_identity, &[*permission])
+                                .into_iter()
+                                .map(PosixFileClass::name)
+                                .collect(),
+                            rule: policy_location.match_rules(&workflow.user.name, path, &[*permission]).ok().map(|rule| rule.pattern.clone()),
+                        });
+                    }
+                },
+            }
+        }
+    }
+    matrix
+}
+
 /// The POSIX reasoner connector. This connector is used to validate workflows based on POSIX file permissions.
 pub struct PosixReasonerConnector {
-    data_index: DataIndex,
+    data_index:   DataIndex,
+    stat_cache:   StatCache,
+    /// The policies parsed so far, such that they need not be parsed again for every question.
+    policy_cache: PolicyCache<PosixPolicy>,
+    /// Whether to deny every question with the effective permissions matrix instead of answering it.
+    dry_run:      bool,
 }
 
 impl PosixReasonerConnector {
-    pub fn new(data_index: DataIndex) -> Self {
+    /// Creates a new connector that checks against the given data index, caching file metadata for `stat_cache_ttl`.
+    pub fn new(data_index: DataIndex, stat_cache_ttl: Duration) -> Self {
         info!("Creating new PosixReasonerConnector with {} plugin", std::any::type_name::<Self>());
         debug!("Parsing nested arguments for PosixReasonerConnector<{}>", std::any::type_name::<Self>());
 
-        PosixReasonerConnector { data_index }
+        PosixReasonerConnector { data_index, stat_cache: StatCache::new(stat_cache_ttl), policy_cache: PolicyCache::default(), dry_run: false }
+    }
+
+    /// Puts the connector in dry-run mode, in which it denies every question. The reasons then tell which verdict it
+    /// would have given (`posix-dry-run: allow` or `posix-dry-run: deny`), followed by the reasons for that verdict and
+    /// the effective permissions matrix as `posix-permission: <JSON>` entries (see [`PermissionMatrixEntry`]).
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Answers a question about the given part of a workflow.
+    ///
+    /// Every dataset is checked regardless of whether an earlier one failed, so answers are always
+    /// [`Granularity::Exhaustive`].
+    ///
+    /// # Errors
+    /// This function errors if the policy is not a valid [`PosixPolicy`].
+    fn answer(&self, policy: Policy, workflow: &Workflow, scope: QuestionScope) -> Result<ReasonerResponse, ReasonerConnError> {
+        let posix_policy = self
+            .policy_cache
+            .get_or_try_compile(&policy, PosixPolicy::try_from_policy)
+            .map_err(|err| ReasonerConnError::invalid(format!("Failed to prepare for policy: {err}")))?;
+        let response = match validate_dataset_permissions(workflow, scope, &self.data_index, &self.stat_cache, &posix_policy) {
+            Ok(ValidationOutput::Ok) => ReasonerResponse::new(true, vec![]),
+            Ok(ValidationOutput::Fail(datasets)) => ReasonerResponse::new(
+                false,
+                datasets
+                    .into_iter()
+                    .map(|(dataset, reason)| format!("We do not have sufficient permissions for dataset: {dataset} ({reason})"))
+                    .collect(),
+            )
+            .with_categories(vec![DenyCategory::PermissionDenied]),
+            Err(errors) => ReasonerResponse::new(false, errors.into_iter().map(|error| error.to_string()).collect())
+                .with_categories(vec![DenyCategory::PermissionDenied]),
+        }
+        .with_granularity(Granularity::Exhaustive);
+        if !self.dry_run {
+            return Ok(response);
+        }
+
+        // Never allow anything in a dry run, but tell what would have happened and why
+        let mut reasons: Vec<String> = vec![format!("posix-dry-run: {}", if response.success { "allow" } else { "deny" })];
+        reasons.extend(response.errors);
+        for entry in permission_matrix(workflow, scope, &self.data_index, &self.stat_cache, &posix_policy) {
+            match serde_json::to_string(&entry) {
+                Ok(entry) => reasons.push(format!("posix-permission: {entry}")),
+                Err(err) => error!("Failed to serialize permissions matrix entry {entry:?}: {err}"),
+            }
+        }
+        Ok(ReasonerResponse::new(false, reasons).with_categories(response.categories).with_granularity(Granularity::Exhaustive))
     }
 }
 
@@ -397,17 +815,9 @@ impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static> ReasonerConnector<
         policy: Policy,
         _state: State,
         workflow: Workflow,
-        _task: String,
+        task: String,
     ) -> Result<ReasonerResponse, ReasonerConnError> {
-        let posix_policy = PosixPolicy::from_policy(policy);
-        match validate_dataset_permissions(&workflow, &self.data_index, &posix_policy) {
-            Ok(ValidationOutput::Ok) => Ok(ReasonerResponse::new(true, vec![])),
-            Ok(ValidationOutput::Fail(datasets)) => Ok(ReasonerResponse::new(
-                false,
-                datasets.into_iter().map(|dataset| format!("We do not have sufficient permissions for dataset: {dataset}")).collect(),
-            )),
-            Err(errors) => Ok(ReasonerResponse::new(false, errors.into_iter().map(|error| error.to_string()).collect())),
-        }
+        self.answer(policy, &workflow, QuestionScope::Task(&task))
     }
 
     async fn access_data_request(
@@ -416,18 +826,12 @@ impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static> ReasonerConnector<
         policy: Policy,
         _state: State,
         workflow: Workflow,
-        _data: String,
-        _task: Option<String>,
+        data: String,
+        task: Option<String>,
     ) -> Result<ReasonerResponse, ReasonerConnError> {
-        let posix_policy = PosixPolicy::from_policy(policy);
-        match validate_dataset_permissions(&workflow, &self.data_index, &posix_policy) {
-            Ok(ValidationOutput::Ok) => Ok(ReasonerResponse::new(true, vec![])),
-            Ok(ValidationOutput::Fail(datasets)) => Ok(ReasonerResponse::new(
-                false,
-                datasets.into_iter().map(|dataset| format!("We do not have sufficient permissions for dataset: {dataset}")).collect(),
-            )),
-            Err(errors) => Ok(ReasonerResponse::new(false, errors.into_iter().map(|error| error.to_string()).collect())),
-        }
+        // Without a task, it is a result of the workflow that is being downloaded
+        let scope = QuestionScope::Data { name: &data, task: task.as_deref() };
+        self.answer(policy, &workflow, scope)
     }
 
     async fn workflow_validation_request(
@@ -437,15 +841,109 @@ impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static> ReasonerConnector<
         _state: State,
         workflow: Workflow,
     ) -> Result<ReasonerResponse, ReasonerConnError> {
-        let posix_policy = PosixPolicy::from_policy(policy);
-        match validate_dataset_permissions(&workflow, &self.data_index, &posix_policy) {
-            Ok(ValidationOutput::Ok) => Ok(ReasonerResponse::new(true, vec![])),
-            Ok(ValidationOutput::Fail(datasets)) => Ok(ReasonerResponse::new(
-                false,
-                datasets.into_iter().map(|dataset| format!("We do not have sufficient permissions for dataset: {dataset}")).collect(),
-            )),
-            Err(errors) => Ok(ReasonerResponse::new(false, errors.into_iter().map(|error| error.to_string()).collect())),
+        self.answer(policy, &workflow, QuestionScope::Workflow)
+    }
+
+    fn compose(&self, fragments: &[Policy]) -> Result<Vec<PolicyContent>, PolicyCompositionError> {
+        info!("Composing {} POSIX policy fragment(s)...", fragments.len());
+
+        // Merge the user maps of all fragments. We use ordered maps to make the composition deterministic. Path rules only
+        // ever grant permissions, so those of all fragments simply apply together.
+        let mut conflicts: Vec<String> = Vec::new();
+        let mut header: Option<(String, String)> = None;
+        let mut merged: BTreeMap<LocationIdentifier, BTreeMap<GlobalUsername, (i64, PosixLocalIdentity)>> = BTreeMap::new();
+        let mut rules: BTreeMap<LocationIdentifier, Vec<PosixPathRule>> = BTreeMap::new();
+        for fragment in fragments {
+            let id: i64 = fragment.version.version.unwrap_or(-1);
+            let content: &PolicyContent = match fragment.content.first() {
+                Some(content) => content,
+                None => {
+                    conflicts.push(format!("Fragment {id} has no content"));
+                    continue;
+                },
+            };
+            let datasets: HashMap<LocationIdentifier, PosixPolicyLocation> = match serde_json::from_str(content.content.get().trim()) {
+                Ok(datasets) => datasets,
+                Err(err) => {
+                    conflicts.push(format!("Fragment {id} is not a valid POSIX policy: {err}"));
+                    continue;
+                },
+            };
+            if header.is_none() {
+                header = Some((content.reasoner.clone(), content.reasoner_version.clone()));
+            }
+
+            for (location, policy_location) in datasets {
+                let location_rules: &mut Vec<PosixPathRule> = rules.entry(location.clone()).or_default();
+                for rule in policy_location.rules {
+                    if !location_rules.contains(&rule) {
+                        location_rules.push(rule);
+                    }
+                }
+                let users: &mut BTreeMap<GlobalUsername, (i64, PosixLocalIdentity)> = merged.entry(location.clone()).or_default();
+                for (user, identity) in policy_location.user_map {
+                    match users.get(&user) {
+                        Some((_, existing)) if *existing == identity => {},
+                        Some((other, _)) => {
+                            conflicts.push(format!("Fragments {other} and {id} map user '{user}' to different identities at location '{location}'"))
+                        },
+                        None => {
+                            users.insert(user, (id, identity));
+                        },
+                    }
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(PolicyCompositionError { conflicts });
         }
+
+        // Serialize the result in the same shape as a normal POSIX policy
+        let (reasoner, reasoner_version): (String, String) = match header {
+            Some(header) => header,
+            None => return Err(PolicyCompositionError { conflicts: vec!["No POSIX policy fragments given".into()] }),
+        };
+        let datasets: BTreeMap<LocationIdentifier, BTreeMap<GlobalUsername, PosixLocalIdentity>> = merged
+            .into_iter()
+            .map(|(location, users)| (location, users.into_iter().map(|(user, (_, identity))| (user, identity)).collect()))
+            .collect();
+        let datasets: BTreeMap<LocationIdentifier, serde_json::Value> = datasets
+            .into_iter()
+            .map(|(location, user_map)| {
+                let rules: Vec<PosixPathRule> = rules.remove(&location).unwrap_or_default();
+                if rules.is_empty() {
+                    (location, serde_json::json!({ "user_map": user_map }))
+                } else {
+                    (location, serde_json::json!({ "user_map": user_map, "rules": rules }))
+                }
+            })
+            .collect();
+        match serde_json::value::to_raw_value(&datasets) {
+            Ok(content) => Ok(vec![PolicyContent { reasoner, reasoner_version, content: content.into() }]),
+            Err(err) => Err(PolicyCompositionError { conflicts: vec![format!("Failed to serialize composed POSIX policy: {err}")] }),
+        }
+    }
+
+    async fn on_policy_activated(&self, policy: &Policy) -> Result<(), ReasonerConnError> {
+        // Drop the old policy, and parse the new one (with its user maps) before the first question needs it
+        self.policy_cache.invalidate();
+        info!("Preparing for POSIX policy version {}", policy.version.version.unwrap_or(-1));
+        self.policy_cache
+            .get_or_try_compile(policy, PosixPolicy::try_from_policy)
+            .map(|_| ())
+            .map_err(|err| ReasonerConnError::invalid(format!("Failed to prepare for policy: {err}")))
+    }
+
+    #[inline]
+    async fn on_policy_deactivated(&self) -> Result<(), ReasonerConnError> {
+        self.policy_cache.invalidate();
+        Ok(())
+    }
+
+    fn metrics(&self) -> Vec<ReasonerMetric> {
+        let mut metrics: Vec<ReasonerMetric> = self.stat_cache.metrics();
+        metrics.extend(self.policy_cache.metrics());
+        metrics
     }
 }
 
@@ -486,50 +984,198 @@ struct WorkflowDatasets {
     execute_sets: Vec<(Location, Dataset)>,
 }
 
-fn find_datasets_in_workflow(workflow: &Workflow) -> WorkflowDatasets {
-    debug!("Walking the workflow in order to find datasets. Starting with {:?}", &workflow.start);
-    let mut visitor =
-        DatasetCollectorVisitor { read_sets: Default::default(), write_sets: Default::default(), execute_sets: Default::default() };
+impl WorkflowDatasets {
+    /// Returns every dataset access together with the permissions it requires.
+    fn accesses(&self) -> impl Iterator<Item = (&(Location, Dataset), Vec<PosixFilePermission>)> {
+        std::iter::empty()
+            .chain(self.read_sets.iter().zip(repeat(vec![PosixFilePermission::Read])))
+            .chain(self.write_sets.iter().zip(repeat(vec![PosixFilePermission::Write])))
+            .chain(self.execute_sets.iter().zip(repeat(vec![PosixFilePermission::Read, PosixFilePermission::Execute])))
+    }
+}
+
+/// Collects the datasets accessed in the part of a workflow that a question is about, at the location where they are
+/// accessed. A [`ValidationError::MissingLocation`] is returned for every element that is not planned at a location,
+/// and a [`ValidationError::NotInWorkflow`] if the task or result asked about does not occur in the workflow.
+fn find_datasets_in_workflow(workflow: &Workflow, scope: QuestionScope) -> Result<WorkflowDatasets, Vec<ValidationError>> {
+    debug!("Walking the workflow in order to find datasets for {:?}. Starting with {:?}", scope, &workflow.start);
+    let mut visitor = DatasetCollectorVisitor {
+        scope,
+        found: matches!(scope, QuestionScope::Workflow),
+        read_sets: Default::default(),
+        write_sets: Default::default(),
+        execute_sets: Default::default(),
+        missing: Default::default(),
+    };
 
     walk_workflow_preorder(&workflow.start, &mut visitor);
 
-    WorkflowDatasets { read_sets: visitor.read_sets, write_sets: visitor.write_sets, execute_sets: visitor.execute_sets }
+    if !visitor.found {
+        visitor.missing.push(ValidationError::NotInWorkflow(match visitor.scope {
+            QuestionScope::Workflow => unreachable!(),
+            QuestionScope::Task(task) | QuestionScope::Data { task: Some(task), .. } => format!("task '{task}'"),
+            QuestionScope::Data { name, task: None } => format!("result '{name}'"),
+        }));
+    }
+    if !visitor.missing.is_empty() {
+        return Err(visitor.missing);
+    }
+    Ok(WorkflowDatasets { read_sets: visitor.read_sets, write_sets: visitor.write_sets, execute_sets: visitor.execute_sets })
+}
+
+/// The part of a workflow that a question is about. See: [`find_datasets_in_workflow`].
+#[derive(Clone, Copy, Debug)]
+enum QuestionScope<'q> {
+    /// The workflow as a whole.
+    Workflow,
+    /// Executing the task with the given identifier, which reads its inputs.
+    Task(&'q str),
+    /// Reading the dataset with the given name, either as input to the task with the given identifier or as a result
+    /// of the workflow.
+    Data { name: &'q str, task: Option<&'q str> },
 }
 
 /// Implements a visitor that traverses a [`Workflow`] and collect the datasets that are accessed and/or modified in
 /// the workflow. See: [`WorkflowDatasets`] and [`WorkflowVisitor`].
-struct DatasetCollectorVisitor {
-    pub read_sets:    Vec<(Location, Dataset)>,
-    pub write_sets:   Vec<(Location, Dataset)>,
+struct DatasetCollectorVisitor<'q> {
+    /// The part of the workflow to collect the datasets of.
+    pub scope: QuestionScope<'q>,
+    /// Whether the element(s) that the scope is about were found. Always true for the workflow as a whole.
+    pub found: bool,
+    pub read_sets: Vec<(Location, Dataset)>,
+    pub write_sets: Vec<(Location, Dataset)>,
     pub execute_sets: Vec<(Location, Dataset)>,
+    /// The elements for which no location could be found.
+    pub missing: Vec<ValidationError>,
 }
 
-impl WorkflowVisitor for DatasetCollectorVisitor {
-    fn visit_task(&mut self, task: &workflow::ElemTask) {
-        // FIXME: Location is not currently sent as part of the workflow validation request,
-        // this makes this not really possible to do now. To ensure the code is working
-        // however, we will for the mean time assume the location
+impl DatasetCollectorVisitor<'_> {
+    /// Resolves the location of an element, recording it as missing if there is none.
+    fn resolve(&mut self, location: Option<&Location>, what: impl FnOnce() -> String) -> Option<Location> {
+        if location.is_none() {
+            self.missing.push(ValidationError::MissingLocation(what()));
+        }
+        location.cloned()
+    }
 
-        let location = task.location.clone().unwrap_or_else(|| String::from(ASSUMED_LOCATION));
-        if let Some(output) = &task.output {
-            self.read_sets.push((location.clone(), output.clone()));
+    /// Records that a task reads a dataset, which happens where the dataset is pulled from or else where the task is.
+    fn read_input(&mut self, task: &workflow::ElemTask, input: &Dataset) {
+        let location = input.from.as_ref().or(task.location.as_ref());
+        if let Some(location) = self.resolve(location, || format!("input '{}' of task '{}'", input.name, task.id)) {
+            self.read_sets.push((location, input.clone()));
+        }
+    }
+}
+
+impl WorkflowVisitor for DatasetCollectorVisitor<'_> {
+    fn visit_task(&mut self, task: &workflow::ElemTask) {
+        match self.scope {
+            QuestionScope::Workflow => {
+                let Some(output) = &task.output else { return };
+                if let Some(location) = self.resolve(task.location.as_ref(), || format!("task '{}'", task.id)) {
+                    self.read_sets.push((location, output.clone()));
+                }
+            },
+            QuestionScope::Task(id) if task.id == id => {
+                self.found = true;
+                for input in &task.input {
+                    self.read_input(task, input);
+                }
+            },
+            QuestionScope::Data { name, task: Some(id) } if task.id == id => {
+                self.found = true;
+                // Datasets not planned as input live wherever the task is
+                let input = task.input.iter().find(|input| input.name == name).cloned().unwrap_or_else(|| Dataset { name: name.into(), from: None });
+                self.read_input(task, &input);
+            },
+            _ => {},
         }
     }
 
     fn visit_commit(&mut self, commit: &workflow::ElemCommit) {
-        let location = commit.location.clone().unwrap_or_else(|| String::from(ASSUMED_LOCATION));
+        if !matches!(self.scope, QuestionScope::Workflow) {
+            return;
+        }
+        let Some(location) = self.resolve(commit.location.as_ref(), || format!("commit '{}'", commit.id)) else { return };
         self.read_sets.extend(repeat(location.clone()).zip(commit.input.iter().cloned()));
 
         // TODO: Maybe create a dedicated enum type for this e.g. NewDataset for datasets that will be
         // created, might fail if one already exists.
-        let location = commit.location.clone().unwrap_or_else(|| String::from(ASSUMED_LOCATION));
-        self.write_sets.push((location.clone(), Dataset { name: commit.data_name.clone(), from: None }));
+        self.write_sets.push((location, Dataset { name: commit.data_name.clone(), from: None }));
     }
 
-    // TODO: We do not really have a location for this one right now, we should figure out how to
-    // interpret this
     fn visit_stop(&mut self, stop_sets: &HashSet<Dataset>) {
-        let location = String::from(ASSUMED_LOCATION);
-        self.write_sets.extend(repeat(location).zip(stop_sets.iter().cloned()));
+        // The results live where they are planned to be pulled from
+        for dataset in stop_sets {
+            match self.scope {
+                QuestionScope::Workflow => {
+                    if let Some(location) = self.resolve(dataset.from.as_ref(), || format!("result '{}'", dataset.name)) {
+                        self.write_sets.push((location, dataset.clone()));
+                    }
+                },
+                QuestionScope::Data { name, task: None } if dataset.name == name => {
+                    self.found = true;
+                    if let Some(location) = self.resolve(dataset.from.as_ref(), || format!("result '{}'", dataset.name)) {
+                        // The same result may be reached through multiple branches
+                        let access: (Location, Dataset) = (location, dataset.clone());
+                        if !self.read_sets.contains(&access) {
+                            self.read_sets.push(access);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shorthand for a rule granting read permission to everyone on the given pattern.
+    fn rule(pattern: &str) -> PosixPathRule { PosixPathRule { pattern: pattern.into(), permissions: vec![PosixFilePermission::Read], users: None } }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches(&["shares", "*.csv"], &["shares", "results.csv"]));
+        assert!(!glob_matches(&["shares", "*.csv"], &["shares", "results.txt"]));
+        assert!(!glob_matches(&["shares", "*.csv"], &["shares", "nested", "results.csv"]));
+        assert!(glob_matches(&["shares", "data-?"], &["shares", "data-1"]));
+        assert!(!glob_matches(&["shares", "data-?"], &["shares", "data-10"]));
+        assert!(glob_matches(&["shares", "**"], &["shares"]));
+        assert!(glob_matches(&["shares", "**", "*.csv"], &["shares", "a", "b", "c.csv"]));
+        assert!(!glob_matches(&["shares", "**", "*.csv"], &["other", "c.csv"]));
+        assert!(glob_matches(&[], &[]));
+        assert!(!glob_matches(&[], &["shares"]));
+    }
+
+    #[test]
+    fn test_matches_prefix() {
+        let rule: PosixPathRule = rule("/shares/public");
+        assert!(rule.matches(Path::new("/shares/public")));
+        assert!(rule.matches(Path::new("/shares/public/data.csv")));
+        assert!(rule.matches(Path::new("/shares/./public/data.csv")));
+        assert!(!rule.matches(Path::new("/shares/publicity/data.csv")));
+        assert!(!rule.matches(Path::new("/shares")));
+    }
+
+    #[test]
+    fn test_matches_glob() {
+        let rule: PosixPathRule = rule("/shares/results/*.csv");
+        assert!(rule.matches(Path::new("/shares/results/out.csv")));
+        assert!(!rule.matches(Path::new("/shares/results/out.txt")));
+        assert!(!rule.matches(Path::new("/shares/results/nested/out.csv")));
+    }
+
+    #[test]
+    fn test_matches_traversal() {
+        // Would otherwise be read as `/shares/public/private/secret.csv`
+        assert!(!rule("/shares/public").matches(Path::new("/shares/public/../private/secret.csv")));
+        assert!(!rule("/shares/public/**").matches(Path::new("/shares/public/../../etc/passwd")));
+        assert!(!rule("/shares/*/*.csv").matches(Path::new("/shares/public/../secret.csv")));
+        assert!(!rule("/shares/public").matches(Path::new("/shares/public/data/..")));
+        assert!(!rule("/**").matches(Path::new("/..")));
     }
 }
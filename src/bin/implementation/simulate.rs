@@ -0,0 +1,159 @@
+//! Implements the `simulate`-subcommand, which replays the questions in an audit log against a policy file.
+//!
+//! Unlike `POST v1/management/policies/:id/simulate`, this needs neither a running reasoner server nor a policy that
+//! was pushed to it: the policy is read from a file (in the same format as the body of `POST v1/management/policies`)
+//! and the questions are put directly to the reasoner connector of the binary, configured as when running the server.
+//! The raw exchanges with the reasoner are logged to a separate audit log, such that the real one is left untouched.
+
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::{error, fs};
+
+use audit_logger::{LogStatement, schema};
+use clap::Parser;
+use log::{debug, info, warn};
+use policy::Policy;
+use policy_reasoner::logger::FileLogger;
+use reasonerconn::ReasonerConnector;
+use srv::models::AddPolicyPostModel;
+use srv::simulation::{self, RecordedQuestion, SimulationReport};
+
+/***** ERRORS *****/
+/// Defines errors that originate from simulating a policy.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read the audit log.
+    LogRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse a statement in the audit log.
+    LogParse { path: PathBuf, line: usize, err: schema::Error },
+    /// Failed to read the policy file.
+    PolicyRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the policy file.
+    PolicyParse { path: PathBuf, err: serde_json::Error },
+    /// Failed to serialize the report.
+    ReportSerialize { err: serde_json::Error },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            LogRead { path, .. } => write!(f, "Failed to read audit log '{}'", path.display()),
+            LogParse { path, line, .. } => write!(f, "Failed to parse line {line} of audit log '{}' as a log statement", path.display()),
+            PolicyRead { path, .. } => write!(f, "Failed to read policy file '{}'", path.display()),
+            PolicyParse { path, .. } => write!(f, "Failed to parse policy file '{}'", path.display()),
+            ReportSerialize { .. } => write!(f, "Failed to serialize simulation report"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            LogRead { err, .. } => Some(err),
+            LogParse { err, .. } => Some(err),
+            PolicyRead { err, .. } => Some(err),
+            PolicyParse { err, .. } => Some(err),
+            ReportSerialize { err } => Some(err),
+        }
+    }
+}
+
+/***** ARGUMENTS *****/
+/// Defines the arguments for the `simulate`-subcommand.
+#[derive(Debug, Parser)]
+pub struct SimulateArguments {
+    /// The policy to simulate.
+    #[clap(
+        name = "POLICY",
+        help = "The policy to replay the questions against, as a JSON file in the same format as the body of 'POST v1/management/policies' (i.e., \
+                with a 'version_description' and a list of 'content')."
+    )]
+    pub policy: PathBuf,
+
+    /// The audit log to read the questions from.
+    #[clap(short, long, default_value = "./audit-log.log", help = "The audit log to read the recorded questions (and their verdicts) from.")]
+    pub log:    PathBuf,
+    /// The reference of the first question to replay.
+    #[clap(short, long, help = "If given, skips all questions before the one with this verdict reference.")]
+    pub from:   Option<String>,
+    /// The reference of the last question to replay.
+    #[clap(short, long, help = "If given, skips all questions after the one with this verdict reference.")]
+    pub to:     Option<String>,
+    /// Where to log the exchanges with the reasoner.
+    #[clap(
+        short,
+        long,
+        default_value = "./simulation-audit-log.log",
+        help = "The audit log to log the raw exchanges with the reasoner to while replaying. Kept separate from the real audit log, which is never \
+                written to."
+    )]
+    pub output: PathBuf,
+}
+
+/***** HELPER FUNCTIONS *****/
+/// Reads all statements from an audit log written by a `FileLogger`.
+///
+/// # Arguments
+/// - `path`: The path to the audit log to read.
+///
+/// # Returns
+/// The [`LogStatement`]s in the log, in order. Lines that do not start a statement are skipped.
+///
+/// # Errors
+/// This function errors if the log could not be read or a statement in it could not be parsed.
+fn read_audit_log(path: &Path) -> Result<Vec<LogStatement<'static>>, Error> {
+    let raw: String = fs::read_to_string(path).map_err(|err| Error::LogRead { path: path.into(), err })?;
+    let mut statements: Vec<LogStatement> = Vec::new();
+    for (i, line) in raw.lines().enumerate() {
+        // Every entry is `[<identifier>][<timestamp>] <statement>`
+        let Some(start) = line.find("] {") else {
+            debug!("Skipping line {} of audit log '{}' because it does not start a statement", i + 1, path.display());
+            continue;
+        };
+        statements.push(schema::parse(&line[start + 2..]).map_err(|err| Error::LogParse { path: path.into(), line: i + 1, err })?);
+    }
+    Ok(statements)
+}
+
+/***** LIBRARY *****/
+/// Replays the questions recorded in an audit log against a policy file, and prints a report of how the verdicts differ.
+///
+/// The report is printed to stdout as JSON (see [`SimulationReport`]).
+///
+/// # Arguments
+/// - `args`: The [`SimulateArguments`] that configure what to replay against what.
+/// - `connector`: The reasoner connector to put the questions to.
+///
+/// # Errors
+/// This function errors if the audit log or policy could not be read, or the report could not be serialized. Questions
+/// that the reasoner fails to answer are reported as failed instead.
+pub async fn replay<C: ReasonerConnector<FileLogger>>(args: &SimulateArguments, connector: &C) -> Result<(), Error> {
+    // Read the policy
+    let raw: String = fs::read_to_string(&args.policy).map_err(|err| Error::PolicyRead { path: args.policy.clone(), err })?;
+    let policy: Policy =
+        serde_json::from_str::<AddPolicyPostModel>(&raw).map_err(|err| Error::PolicyParse { path: args.policy.clone(), err })?.into_domain();
+
+    // Read the questions
+    let statements: Vec<LogStatement> = read_audit_log(&args.log)?;
+    let questions: Vec<RecordedQuestion> = simulation::collect_questions(&statements, args.from.as_deref(), args.to.as_deref());
+    if questions.is_empty() {
+        warn!("No questions found in audit log '{}' (in the given range)", args.log.display());
+    }
+
+    // Replay them
+    info!("Replaying {} question(s) from '{}' against policy '{}'...", questions.len(), args.log.display(), args.policy.display());
+    let identifier: String = format!("{binary} v{version}", binary = env!("CARGO_BIN_NAME"), version = env!("CARGO_PKG_VERSION"));
+    let logger: FileLogger = FileLogger::new(identifier, args.output.clone());
+    let report: SimulationReport = simulation::simulate(connector, &logger, &policy, questions).await;
+    info!(
+        "Replayed {} question(s): {} unchanged, {} newly allowed, {} newly denied, {} unknown, {} failed",
+        report.total,
+        report.unchanged,
+        report.newly_allowed.len(),
+        report.newly_denied.len(),
+        report.unknown.len(),
+        report.failed.len()
+    );
+    println!("{}", serde_json::to_string_pretty(&report).map_err(|err| Error::ReportSerialize { err })?);
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Implements static allow/deny lists that the eFLINT reasoner connector checks before asking the reasoner.
+//!
+//! Some requests are obviously denied (e.g., a user that is banned outright, or a dataset that may never be read at a
+//! particular site), yet answering them costs a full round-trip to the reasoner. A [`Policy`] may therefore carry a
+//! [`STATIC_LIST_ID`] content next to its eFLINT JSON: a JSON object with a `deny` and an `allow` list of
+//! [`StaticRule`]s. If the connector's pre-filter is enabled, a question is denied without invoking the reasoner when
+//! any of the accesses it is about matches a `deny` rule but no `allow` rule; otherwise, the reasoner decides as usual.
+//! The lists can thus only ever deny faster, never allow something the eFLINT policy denies.
+
+use policy::{Policy, PolicyContent};
+use reasonerconn::QuestionKind;
+use serde::{Deserialize, Serialize};
+use workflow::spec::{Dataset, ElemCommit, ElemTask, Workflow};
+use workflow::utils::{WorkflowVisitor, walk_workflow_preorder};
+
+/***** CONSTANTS *****/
+/// The identifier of the static allow/deny lists in a [`Policy`]'s content.
+pub const STATIC_LIST_ID: &'static str = "static-list";
+
+/***** HELPERS *****/
+/// A single access that a question is about.
+#[derive(Clone, Debug)]
+struct Access {
+    /// The location where the access happens, if known (results are sent to the user, who is not at a location).
+    location: Option<String>,
+    /// The dataset accessed, if any (a task without inputs accesses none).
+    dataset:  Option<String>,
+}
+
+/// Collects the accesses in the part of a workflow that a question is about.
+struct AccessCollector<'q> {
+    /// The task to collect the accesses of, or [`None`] to collect those of the whole workflow.
+    task:     Option<&'q str>,
+    /// The dataset to collect the accesses of, or [`None`] to collect all inputs.
+    dataset:  Option<&'q str>,
+    /// The accesses found so far.
+    accesses: Vec<Access>,
+}
+impl AccessCollector<'_> {
+    /// Records that an element reads its inputs, which happens where they are pulled from or else where the element is.
+    fn read(&mut self, location: Option<&String>, inputs: &[Dataset]) {
+        let before: usize = self.accesses.len();
+        for input in inputs.iter().filter(|input| self.dataset.is_none_or(|dataset| input.name == dataset)) {
+            self.accesses.push(Access { location: input.from.as_ref().or(location).cloned(), dataset: Some(input.name.clone()) });
+        }
+        // Datasets not planned as input live wherever the element is
+        if self.accesses.len() == before {
+            self.accesses.push(Access { location: location.cloned(), dataset: self.dataset.map(String::from) });
+        }
+    }
+}
+impl WorkflowVisitor for AccessCollector<'_> {
+    fn visit_task(&mut self, task: &ElemTask) {
+        if self.task.is_none_or(|id| task.id == id) {
+            self.read(task.location.as_ref(), &task.input);
+        }
+    }
+
+    fn visit_commit(&mut self, commit: &ElemCommit) {
+        if self.task.is_none() {
+            self.read(commit.location.as_ref(), &commit.input);
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// A rule in a [`StaticList`]. Every condition that is given must hold for the rule to match; omitted ones match anything.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaticRule {
+    /// The user instigating the workflow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user:     Option<String>,
+    /// The dataset accessed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset:  Option<String>,
+    /// The location where the access happens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// The reason for denial that is shared with clients, if any. Meaningless for `allow` rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason:   Option<String>,
+}
+impl StaticRule {
+    /// Checks whether the rule matches an access by a user.
+    fn matches(&self, user: &str, access: &Access) -> bool {
+        self.user.as_ref().is_none_or(|u| u == user)
+            && self.dataset.as_ref().is_none_or(|d| access.dataset.as_ref() == Some(d))
+            && self.location.as_ref().is_none_or(|l| access.location.as_ref() == Some(l))
+    }
+}
+
+/// The static allow/deny lists of a [`Policy`]. `allow` rules are exceptions to the `deny` rules.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaticList {
+    /// The rules that deny the accesses they match.
+    #[serde(default)]
+    pub deny:  Vec<StaticRule>,
+    /// The rules that exempt the accesses they match from the `deny` rules.
+    #[serde(default)]
+    pub allow: Vec<StaticRule>,
+}
+impl StaticList {
+    /// Reads the static lists of a policy.
+    ///
+    /// # Arguments
+    /// - `policy`: The [`Policy`] to read the lists of.
+    ///
+    /// # Returns
+    /// The [`StaticList`] of the policy, or [`None`] if it has no [`STATIC_LIST_ID`] content.
+    ///
+    /// # Errors
+    /// This function errors if the content is not a valid static list.
+    pub fn from_policy(policy: &Policy) -> Result<Option<Self>, String> {
+        let Some(content) = policy.content.iter().find(|x| x.reasoner == STATIC_LIST_ID) else { return Ok(None) };
+        Self::from_content(content).map(Some)
+    }
+
+    /// Reads static lists from a single policy content.
+    ///
+    /// # Errors
+    /// This function errors if the content is not a valid static list.
+    pub fn from_content(content: &PolicyContent) -> Result<Self, String> {
+        serde_json::from_str(content.content.get()).map_err(|err| format!("'{STATIC_LIST_ID}' content is not a valid static list: {err}"))
+    }
+
+    /// Decides whether a question is denied by the lists.
+    ///
+    /// # Arguments
+    /// - `workflow`: The [`Workflow`] in question.
+    /// - `question`: What is asked.
+    ///
+    /// # Returns
+    /// The reasons for denial of the `deny` rules that matched (which may be none), or [`None`] if the question is not
+    /// denied by the lists and should be put to the reasoner.
+    pub fn denial(&self, workflow: &Workflow, question: &QuestionKind) -> Option<Vec<String>> {
+        if self.deny.is_empty() {
+            return None;
+        }
+        let mut collector = AccessCollector { task: None, dataset: None, accesses: Vec::new() };
+        match question {
+            QuestionKind::ExecuteTask { task } => collector.task = Some(task),
+            QuestionKind::AccessData { data, task: Some(task) } => {
+                collector.task = Some(task);
+                collector.dataset = Some(data);
+            },
+            QuestionKind::AccessData { data, task: None } => {
+                collector.accesses.push(Access { location: None, dataset: Some(data.clone()) });
+            },
+            QuestionKind::ValidateWorkflow => {},
+        }
+        if !matches!(question, QuestionKind::AccessData { task: None, .. }) {
+            walk_workflow_preorder(&workflow.start, &mut collector);
+        }
+
+        let user: &str = &workflow.user.name;
+        let mut denied: bool = false;
+        let mut reasons: Vec<String> = Vec::new();
+        for access in &collector.accesses {
+            if self.allow.iter().any(|rule| rule.matches(user, access)) {
+                continue;
+            }
+            for rule in self.deny.iter().filter(|rule| rule.matches(user, access)) {
+                denied = true;
+                if let Some(reason) = &rule.reason {
+                    if !reasons.contains(reason) {
+                        reasons.push(reason.clone());
+                    }
+                }
+            }
+        }
+        if denied { Some(reasons) } else { None }
+    }
+}
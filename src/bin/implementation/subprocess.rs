@@ -0,0 +1,426 @@
+//! A policy reasoner implementation that delegates deliberation to an external process.
+//!
+//! The [`SubprocessReasonerConnector`] spawns a configured executable and exchanges questions and verdicts with it as
+//! newline-delimited JSON over the process' stdin and stdout. This makes it trivial to prototype reasoners in Python
+//! (or any other language) against the framework, without having to write a single line of Rust.
+//!
+//! # Protocol
+//!
+//! For every question, the connector writes a single line to the process' stdin containing a JSON object with a
+//! `kind` field (one of `EXECUTE-TASK`, `ACCESS-DATA` or `VALIDATE-WORKFLOW`) and the policy, state and workflow to
+//! reason about. For the first two, a `task` and/or `data` field is present as well.
+//!
+//! The process is expected to answer with a single line on its stdout that contains a serialized
+//! [`ReasonerResponse`]; e.g.,
+//! ```json
+//! { "success": false, "errors": [ "Task is not allowed to run at this location" ], "categories": [ "location-forbidden" ] }
+//! ```
+//! The `categories` are optional; if omitted, they are guessed from the `errors` (see
+//! [`categorize()`](reasonerconn::interpret::categorize)).
+//! Anything the process writes to stderr is inherited by the reasoner's own stderr.
+//!
+//! # Supervision
+//!
+//! The process is started lazily on the first question. If it crashes, times out or produces garbage, it is killed
+//! and the question is answered with an error; the next question will then restart the process. If the process fails
+//! too many times in a row (see `max-restarts`), the connector stops restarting it and refuses to answer.
+//!
+//! # Configuration
+//!
+//! In containerized deployments, the options may also be given as environment variables (e.g.,
+//! `SUBPROCESS_REASONER_COMMAND`) or in a config file (`config`, or `SUBPROCESS_REASONER_CONFIG`) with one
+//! `<OPTION>=<VALUE>` per line. Options in the nested CLI string take precedence over environment variables, which take
+//! precedence over the config file.
+
+use std::error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::process::Stdio;
+use std::time::Duration;
+
+use audit_logger::{ConnectorContext, ConnectorWithContext, Error as AuditLoggerError, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use log::{debug, error, info, warn};
+use nested_cli_parser::typed_parser::{ArgSpec, ArgType, TypedArgs, TypedParser, at_least, non_empty};
+use nested_cli_parser::{NestedCliParser as _, NestedCliParserHelpFormatter};
+use policy::Policy;
+use reasonerconn::interpret::{Answer, BackendAnswer, interpret};
+use reasonerconn::{ReasonerCapabilities, ReasonerConnError, ReasonerConnector, ReasonerResponse};
+use serde::Serialize;
+use state_resolver::State;
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use workflow::spec::Workflow;
+
+/***** CONSTANTS *****/
+/// The identifier used for this connector backend.
+pub const SUBPROCESS_ID: &'static str = "subprocess";
+
+/// The default timeout (in milliseconds) for a single question.
+const DEFAULT_TIMEOUT_MS: &'static str = "30000";
+/// The default number of consecutive failures after which we stop restarting the process.
+const DEFAULT_MAX_RESTARTS: &'static str = "5";
+
+/***** ERRORS *****/
+/// Main error that originates from the [`SubprocessReasonerConnector`].
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to parse the CLI arguments to the subprocess reasoner connector.
+    CliArgumentsParse { raw: String, err: nested_cli_parser::typed_parser::Error },
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use Error::*;
+        match self {
+            CliArgumentsParse { raw, .. } => write!(f, "Failed to parse '{raw}' as CLI argument string for a SubprocessReasonerConnector"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            CliArgumentsParse { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** HELPERS *****/
+/// Defines the question as it is sent to the external process.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "SCREAMING-KEBAB-CASE")]
+enum SubprocessQuestion<'a> {
+    /// Asks if a task may be executed.
+    ExecuteTask { policy: &'a Policy, state: &'a State, workflow: &'a Workflow, task: &'a str },
+    /// Asks if a dataset may be accessed (by a task, or as a workflow result if `task` is [`None`]).
+    AccessData { policy: &'a Policy, state: &'a State, workflow: &'a Workflow, data: &'a str, task: Option<&'a str> },
+    /// Asks if a workflow as a whole is OK.
+    ValidateWorkflow { policy: &'a Policy, state: &'a State, workflow: &'a Workflow },
+}
+
+/// Represents a running instance of the external process.
+struct RunningProcess {
+    /// The handle to the process itself.
+    child:  Child,
+    /// The process' stdin, where we write questions to.
+    stdin:  ChildStdin,
+    /// The process' stdout, where we read verdicts from.
+    stdout: BufReader<ChildStdout>,
+}
+
+/// Keeps track of the external process' lifecycle.
+struct Supervisor {
+    /// The process, if it is currently running.
+    process:  Option<RunningProcess>,
+    /// The number of times the process has failed in a row.
+    failures: u32,
+}
+
+/***** LIBRARY *****/
+/// A reasoner connector that spawns an external executable and lets it answer questions over stdin/stdout.
+pub struct SubprocessReasonerConnector {
+    /// The command (and its arguments) to execute.
+    command:      Vec<String>,
+    /// The maximum time we wait for an answer to a single question.
+    timeout:      Duration,
+    /// The number of consecutive failures after which we stop restarting the process.
+    max_restarts: u32,
+    /// The state of the external process. Only one question can be asked at a time.
+    supervisor:   Mutex<Supervisor>,
+}
+
+impl SubprocessReasonerConnector {
+    pub fn new(cli_args: String) -> Result<Self, Error> {
+        info!("Creating new SubprocessReasonerConnector");
+
+        debug!("Parsing nested arguments for SubprocessReasonerConnector");
+        let parser = TypedParser::new(Self::cli_args());
+        let args: TypedArgs = match parser.parse(&cli_args) {
+            Ok(args) => args,
+            Err(err) => return Err(Error::CliArgumentsParse { raw: cli_args, err }),
+        };
+
+        // See what to do with it (the parser checked the values and filled in the defaults)
+        let command: Vec<String> = args.get_str("command").unwrap_or_default().split_whitespace().map(String::from).collect();
+        let timeout: Duration = Duration::from_millis(args.get_int("timeout").unwrap_or_default() as u64);
+        let max_restarts: u32 = args.get_int("max-restarts").unwrap_or_default() as u32;
+
+        debug!("Creating new SubprocessReasonerConnector for command {command:?} (timeout: {}ms)", timeout.as_millis());
+        Ok(Self { command, timeout, max_restarts, supervisor: Mutex::new(Supervisor { process: None, failures: 0 }) })
+    }
+
+    /// Returns the arguments necessary to build the parser for the SubprocessReasonerConnector.
+    ///
+    /// # Returns
+    /// A vector of [`ArgSpec`]s appropriate to use to build a [`TypedParser`].
+    #[inline]
+    fn cli_args() -> Vec<ArgSpec> {
+        vec![
+            ArgSpec::new(
+                'c',
+                "command",
+                "The command to execute as the reasoner, including its arguments (separated by whitespace).",
+                ArgType::String,
+            )
+            .required()
+            .with_validator(non_empty())
+            .with_env("SUBPROCESS_REASONER_COMMAND"),
+            ArgSpec::new('t', "timeout", "The maximum time (in milliseconds) the process may take to answer a single question.", ArgType::Int)
+                .with_default(DEFAULT_TIMEOUT_MS)
+                .with_validator(at_least(1))
+                .with_env("SUBPROCESS_REASONER_TIMEOUT"),
+            ArgSpec::new('m', "max-restarts", "The number of consecutive failures after which the process is no longer restarted.", ArgType::Int)
+                .with_default(DEFAULT_MAX_RESTARTS)
+                .with_validator(at_least(0))
+                .with_env("SUBPROCESS_REASONER_MAX_RESTARTS"),
+            ArgSpec::new('f', "config", "A config file that gives any of the other options.", ArgType::Path)
+                .with_env("SUBPROCESS_REASONER_CONFIG")
+                .as_config_file(),
+        ]
+    }
+
+    /// Returns a formatter that can be printed to understand the arguments to this connector.
+    ///
+    /// # Arguments
+    /// - `short`: A shortname for the argument that contains the nested arguments we parse.
+    /// - `long`: A longname for the argument that contains the nested arguments we parse.
+    ///
+    /// # Returns
+    /// A [`NestedCliParserHelpFormatter`] that implements [`Display`].
+    #[allow(clippy::needless_lifetimes)]
+    pub fn help<'l>(short: char, long: &'l str) -> NestedCliParserHelpFormatter<'static, 'l, TypedParser> {
+        TypedParser::new(Self::cli_args()).into_help("SubprocessReasonerConnector plugin", short, long)
+    }
+
+    /// Spawns a new instance of the external process.
+    ///
+    /// # Returns
+    /// A new [`RunningProcess`] with its stdin and stdout captured.
+    ///
+    /// # Errors
+    /// This function errors if we failed to spawn the process.
+    fn spawn(&self) -> Result<RunningProcess, ReasonerConnError> {
+        debug!("Spawning reasoner process {:?}", self.command);
+        let mut cmd: Command = Command::new(&self.command[0]);
+        cmd.args(&self.command[1..]);
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::inherit());
+        cmd.kill_on_drop(true);
+        let mut child: Child =
+            cmd.spawn().map_err(|err| ReasonerConnError::new(format!("Failed to spawn reasoner process {:?}", self.command)).with_source(err))?;
+
+        // Take the handles
+        let stdin: ChildStdin = child.stdin.take().unwrap();
+        let stdout: BufReader<ChildStdout> = BufReader::new(child.stdout.take().unwrap());
+        Ok(RunningProcess { child, stdin, stdout })
+    }
+
+    /// Sends a single (serialized) question to the external process and waits for the answer.
+    ///
+    /// # Arguments
+    /// - `process`: The [`RunningProcess`] to talk to.
+    /// - `question`: The serialized question, without a trailing newline.
+    ///
+    /// # Returns
+    /// The raw answer of the process.
+    ///
+    /// # Errors
+    /// This function errors if we failed to communicate with the process or if it did not answer in time.
+    async fn exchange(&self, process: &mut RunningProcess, question: &str) -> Result<String, ReasonerConnError> {
+        let exchange = async {
+            process.stdin.write_all(question.as_bytes()).await?;
+            process.stdin.write_all(b"\n").await?;
+            process.stdin.flush().await?;
+
+            let mut answer: String = String::new();
+            if process.stdout.read_line(&mut answer).await? == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Reasoner process closed its stdout"));
+            }
+            Ok::<String, std::io::Error>(answer)
+        };
+        match tokio::time::timeout(self.timeout, exchange).await {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(err)) => Err(ReasonerConnError::new("Failed to communicate with reasoner process").with_source(err)),
+            Err(_) => Err(ReasonerConnError::new(format!("Reasoner process did not answer within {}ms", self.timeout.as_millis()))),
+        }
+    }
+
+    /// Asks the external process a question, (re)starting it if necessary.
+    ///
+    /// # Arguments
+    /// - `logger`: The logger used to log the raw response of the process.
+    /// - `question`: The [`SubprocessQuestion`] to ask.
+    ///
+    /// # Returns
+    /// The [`ReasonerResponse`] given by the process.
+    ///
+    /// # Errors
+    /// This function errors if the process could not be (re)started, failed to answer in time, or answered with garbage.
+    async fn ask<L: ReasonerConnectorAuditLogger + Send + Sync>(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        question: SubprocessQuestion<'_>,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        let question: String =
+            serde_json::to_string(&question).map_err(|err| ReasonerConnError::new("Failed to serialize question").with_source(err))?;
+
+        // Get the process, (re)starting it if it isn't running (anymore)
+        let mut guard = self.supervisor.lock().await;
+        let supervisor: &mut Supervisor = &mut guard;
+        if let Some(process) = &mut supervisor.process {
+            if let Ok(Some(status)) = process.child.try_wait() {
+                warn!("Reasoner process exited unexpectedly ({status}); restarting");
+                supervisor.process = None;
+                supervisor.failures += 1;
+            }
+        }
+        if supervisor.failures > self.max_restarts {
+            return Err(ReasonerConnError::new(format!(
+                "Reasoner process failed {} times in a row; refusing to restart it (max-restarts is {})",
+                supervisor.failures, self.max_restarts
+            )));
+        }
+        if supervisor.process.is_none() {
+            match self.spawn() {
+                Ok(process) => supervisor.process = Some(process),
+                Err(err) => {
+                    supervisor.failures += 1;
+                    return Err(err);
+                },
+            }
+        }
+
+        // Exchange the question for an answer
+        debug!("Sending question to reasoner process...");
+        let res: Result<String, ReasonerConnError> = logger.time_reasoner(self.exchange(supervisor.process.as_mut().unwrap(), &question)).await;
+        let raw_body: String = match res {
+            Ok(answer) => answer,
+            Err(err) => {
+                error!("{err}; killing reasoner process");
+                if let Some(mut process) = supervisor.process.take() {
+                    if let Err(err) = process.child.kill().await {
+                        warn!("Failed to kill reasoner process: {err}");
+                    }
+                }
+                supervisor.failures += 1;
+                return Err(err);
+            },
+        };
+
+        debug!("Log raw response...");
+        logger.log_reasoner_response(raw_body.trim_end()).await.map_err(|err| {
+            debug!("Error trying to log{:?}", err);
+            ReasonerConnError::new(err.to_string())
+        })?;
+        match logger.archive_exchange(&question, raw_body.trim_end()).await {
+            Ok(()) => {},
+            Err(AuditLoggerError::CouldNotArchive(err)) => warn!("Failed to archive exchange with reasoner process: {err}"),
+            Err(err) => return Err(ReasonerConnError::new(err.to_string())),
+        }
+
+        debug!("Parsing response...");
+        match serde_json::from_str::<ReasonerResponse>(&raw_body) {
+            Ok(response) => {
+                supervisor.failures = 0;
+                interpret(&BackendAnswer {
+                    success:    true,
+                    answers:    vec![Answer::Boolean(response.success)],
+                    violations: response.errors,
+                    warnings:   vec![],
+                    categories: response.categories,
+                })
+            },
+            Err(err) => {
+                error!(
+                    "{}\n\nRaw response:\n{}\n{}\n{}\n",
+                    err,
+                    (0..80).map(|_| '-').collect::<String>(),
+                    raw_body,
+                    (0..80).map(|_| '-').collect::<String>()
+                );
+                // The process is out-of-sync with us, so better restart it
+                supervisor.process = None;
+                supervisor.failures += 1;
+                Err(ReasonerConnError::new("Reasoner process returned an invalid response").with_source(err))
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<L: ReasonerConnectorAuditLogger + Send + Sync + 'static> ReasonerConnector<L> for SubprocessReasonerConnector {
+    async fn execute_task(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        task: String,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        info!("Considering task '{}' in workflow '{}' for execution", task, workflow.id);
+        self.ask(logger, SubprocessQuestion::ExecuteTask { policy: &policy, state: &state, workflow: &workflow, task: &task }).await
+    }
+
+    async fn access_data_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        data: String,
+        task: Option<String>,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        info!("Considering data access '{}' in workflow '{}'", data, workflow.id);
+        self.ask(logger, SubprocessQuestion::AccessData {
+            policy:   &policy,
+            state:    &state,
+            workflow: &workflow,
+            data:     &data,
+            task:     task.as_deref(),
+        })
+        .await
+    }
+
+    async fn workflow_validation_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        info!("Considering workflow '{}'", workflow.id);
+        self.ask(logger, SubprocessQuestion::ValidateWorkflow { policy: &policy, state: &state, workflow: &workflow }).await
+    }
+
+    #[inline]
+    fn capabilities(&self) -> ReasonerCapabilities { ReasonerCapabilities::new(&Self::context()).with_options(Self::cli_args()) }
+}
+
+/// The context for the subprocess reasoner connector.
+/// See the [`ConnectorContext`] and [`ConnectorWithContext`] traits for more information.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SubprocessReasonerConnectorContext {
+    #[serde(rename = "type")]
+    pub t: String,
+    pub version: String,
+}
+
+impl std::hash::Hash for SubprocessReasonerConnectorContext {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.t.hash(state);
+        self.version.hash(state);
+    }
+}
+
+impl ConnectorContext for SubprocessReasonerConnectorContext {
+    fn r#type(&self) -> String { self.t.clone() }
+
+    fn version(&self) -> String { self.version.clone() }
+}
+
+impl ConnectorWithContext for SubprocessReasonerConnector {
+    type Context = SubprocessReasonerConnectorContext;
+
+    #[inline]
+    fn context() -> Self::Context { SubprocessReasonerConnectorContext { t: SUBPROCESS_ID.into(), version: "0.1.0".into() } }
+}
@@ -4,31 +4,54 @@
 use std::env;
 use std::fs::File;
 use std::future::Future;
+use std::time::Duration;
 
 pub mod implementation;
 
 use async_trait::async_trait;
+use audit_logger::redact::Redactor;
 use clap::Parser;
+use error_trace::ErrorTrace as _;
 use humanlog::{DebugMode, HumanLogger};
-use implementation::interface::Arguments;
+#[cfg(not(feature = "leak-public-errors"))]
+use implementation::eflint::EFlintLeakNoErrors;
+#[cfg(feature = "leak-public-errors")]
+use implementation::eflint::EFlintLeakPrefixErrors;
+use implementation::eflint::EFlintReasonerConnector;
+#[cfg(feature = "init")]
+use implementation::init;
+use implementation::interface::{Action, Arguments};
 use implementation::no_op::NoOpReasonerConnector;
-use log::info;
-use policy::{Context, Policy, PolicyDataAccess, PolicyDataError, PolicyVersion};
+use implementation::simulate;
+use log::{error, info};
+use policy::{Context, Policy, PolicyArchive, PolicyDataAccess, PolicyDataError, PolicyVersion, PolicyVersionFilter};
+use policy_reasoner::anchor::Anchorer;
+use policy_reasoner::audit_store::SqliteAuditLogger;
 use policy_reasoner::auth::{JwtConfig, JwtResolver, KidResolver};
-use policy_reasoner::logger::FileLogger;
+use policy_reasoner::forwarder::ForwardingLogger;
+use policy_reasoner::logger::{AuditLogBackend, FileLogger};
 use reasonerconn::ReasonerConnector;
+use reasonerconn::circuit_breaker::CircuitBreaker;
+use reasonerconn::shadow::Shadow;
 use srv::Srv;
+use srv::access_tokens::AccessTokenIssuer;
+use srv::disclosure::Disclosure;
+use srv::federation::Federation;
+use srv::i18n::MessageCatalog;
+use srv::maintenance::MaintenanceSchedule;
+use srv::notifications::Notifier;
+use srv::postprocess::VerdictPipeline;
+use srv::prefilter::Prefilter;
+use srv::signatures::StaticKeySet;
 use state_resolver::{State, StateResolver};
 
 /***** HELPER FUNCTIONS *****/
-fn get_pauth_resolver() -> policy_reasoner::auth::JwtResolver<KidResolver> {
-    let kid_resolver = KidResolver::new("./examples/config/jwk_set_expert.json").unwrap();
+fn get_pauth_resolver(kid_resolver: KidResolver) -> policy_reasoner::auth::JwtResolver<KidResolver> {
     let r = File::open("./examples/config/jwt_resolver.yaml").unwrap();
     let jwt_cfg: JwtConfig = serde_yaml::from_reader(r).unwrap();
     JwtResolver::new(jwt_cfg, kid_resolver).unwrap()
 }
-fn get_dauth_resolver() -> policy_reasoner::auth::JwtResolver<KidResolver> {
-    let kid_resolver = KidResolver::new("./examples/config/jwk_set_delib.json").unwrap();
+fn get_dauth_resolver(kid_resolver: KidResolver) -> policy_reasoner::auth::JwtResolver<KidResolver> {
     let r = File::open("./examples/config/jwt_resolver.yaml").unwrap();
     let jwt_cfg: JwtConfig = serde_yaml::from_reader(r).unwrap();
     JwtResolver::new(jwt_cfg, kid_resolver).unwrap()
@@ -36,13 +59,19 @@ fn get_dauth_resolver() -> policy_reasoner::auth::JwtResolver<KidResolver> {
 
 /***** PLUGINS *****/
 /// The plugin used to do the audit logging.
-type AuditLogPlugin = FileLogger;
+type AuditLogPlugin = AuditLogBackend;
 
 /// The plugin used to do authentication for the policy expert API.
 type PolicyAuthResolverPlugin = JwtResolver<KidResolver>;
 /// The plugin used to do authentication for the deliberation API.
 type DeliberationAuthResolverPlugin = JwtResolver<KidResolver>;
 
+/// The plugin used to evaluate questions in the shadow of the actual reasoner (see `--shadow-eflint`).
+#[cfg(feature = "leak-public-errors")]
+type ShadowReasonerConnectorPlugin = EFlintReasonerConnector<EFlintLeakPrefixErrors>;
+#[cfg(not(feature = "leak-public-errors"))]
+type ShadowReasonerConnectorPlugin = EFlintReasonerConnector<EFlintLeakNoErrors>;
+
 /// The plugin used to interact with the policy store.
 type PolicyStorePlugin = DummyPolicyStore;
 
@@ -53,8 +82,20 @@ struct DummyStateResolver;
 impl StateResolver for DummyStateResolver {
     type Error = std::convert::Infallible;
 
-    async fn get_state(&self, _use_case: String) -> Result<State, Self::Error> {
-        Ok(State { users: Default::default(), locations: Default::default(), datasets: Default::default(), functions: Default::default() })
+    async fn get_state(&self, use_case: String) -> Result<State, Self::Error> {
+        Ok(State {
+            users: Default::default(),
+            locations: Default::default(),
+            datasets: Default::default(),
+            functions: Default::default(),
+            images: Default::default(),
+            counters: Default::default(),
+            duties: Default::default(),
+            owners: Default::default(),
+            delegation: None,
+            exhaustive: None,
+            use_case,
+        })
     }
 }
 
@@ -82,6 +123,9 @@ impl PolicyDataAccess for DummyPolicyStore {
                 version_description: String::from("This is a dummy version of a dummy policy"),
                 // TODO: Compute hash by hand
                 reasoner_connector_context: String::from("No context exists"),
+                metadata: Default::default(),
+                content_hashes: Vec::new(),
+                external_id: None,
             },
             content:     Vec::new(),
         })
@@ -98,6 +142,9 @@ impl PolicyDataAccess for DummyPolicyStore {
                 version_description: String::from("This is a dummy version of a dummy policy"),
                 // TODO: Compute hash by hand
                 reasoner_connector_context: String::from("No context exists"),
+                metadata: Default::default(),
+                content_hashes: Vec::new(),
+                external_id: None,
             },
             content:     Vec::new(),
         })
@@ -114,12 +161,15 @@ impl PolicyDataAccess for DummyPolicyStore {
                 version_description: String::from("This is a dummy version of a dummy policy"),
                 // TODO: Compute hash by hand
                 reasoner_connector_context: String::from("No context exists"),
+                metadata: Default::default(),
+                content_hashes: Vec::new(),
+                external_id: None,
             },
             content:     Vec::new(),
         })
     }
 
-    async fn get_versions(&self) -> Result<Vec<PolicyVersion>, PolicyDataError> {
+    async fn get_versions(&self, _filter: &PolicyVersionFilter) -> Result<Vec<PolicyVersion>, PolicyDataError> {
         #[allow(unreachable_code)]
         Ok(vec![PolicyVersion {
             creator: None,
@@ -128,6 +178,9 @@ impl PolicyDataAccess for DummyPolicyStore {
             version_description: String::from("This is a dummy version of a dummy policy"),
             // TODO: Compute hash by hand
             reasoner_connector_context: String::from("No context exists"),
+            metadata: Default::default(),
+            content_hashes: Vec::new(),
+            external_id: None,
         }])
     }
 
@@ -142,11 +195,16 @@ impl PolicyDataAccess for DummyPolicyStore {
                 version_description: String::from("This is a dummy version of a dummy policy"),
                 // TODO: Compute hash by hand
                 reasoner_connector_context: String::from("No context exists"),
+                metadata: Default::default(),
+                content_hashes: Vec::new(),
+                external_id: None,
             },
             content:     Vec::new(),
         })
     }
 
+    async fn get_active_at(&self, _at: chrono::DateTime<chrono::Local>) -> Result<Policy, PolicyDataError> { self.get_active().await }
+
     async fn set_active<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
         &self,
         _version: i64,
@@ -163,8 +221,12 @@ impl PolicyDataAccess for DummyPolicyStore {
                 version_description: String::from("This is a dummy version of a dummy policy"),
                 // TODO: Compute hash by hand
                 reasoner_connector_context: String::from("No context exists"),
+                metadata: Default::default(),
+                content_hashes: Vec::new(),
+                external_id: None,
             },
-            content:     Vec::new(), })
+            content:     Vec::new(),
+        })
     }
 
     async fn deactivate_policy<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
@@ -175,6 +237,21 @@ impl PolicyDataAccess for DummyPolicyStore {
         // Nothing to do
         Ok(())
     }
+
+    async fn export_store(&self) -> Result<PolicyArchive, PolicyDataError> {
+        // Nothing to export
+        Ok(PolicyArchive::new(Vec::new(), Vec::new()))
+    }
+
+    async fn import_store<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
+        &self,
+        _archive: PolicyArchive,
+        _context: Context,
+        _transaction: impl 'static + Send + FnOnce(PolicyArchive) -> F,
+    ) -> Result<(), PolicyDataError> {
+        // Nothing to do
+        Ok(())
+    }
 }
 
 /***** ENTRYPOINT *****/
@@ -191,24 +268,181 @@ async fn main() {
 
     info!("{} - v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
-    run_app(args, rconn).await;
+    // Handle subcommands
+    #[cfg(feature = "init")]
+    if let Some(Action::Init(init)) = &args.action {
+        if let Err(err) = init::scaffold(init, false) {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+    if let Some(Action::Simulate(simulate)) = &args.action {
+        if let Err(err) = simulate::replay(simulate, &rconn).await {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    // Optionally put every question to an eFLINT reasoner too, but only to log its answers
+    match args.shadow_eflint.clone() {
+        Some(shadow_args) => match ShadowReasonerConnectorPlugin::new(shadow_args) {
+            Ok(shadow) => {
+                let shadow = shadow.with_retry(args.reasoner_retry());
+                run_app(args, Shadow::new(rconn, shadow)).await
+            },
+            Err(err) => {
+                error!("{}", err.trace());
+                std::process::exit(1);
+            },
+        },
+        None => run_app(args, rconn).await,
+    }
 }
 
-async fn run_app<R>(args: Arguments, rconn: R)
+async fn run_app<R>(mut args: Arguments, rconn: R)
 where
     R: ReasonerConnector<AuditLogPlugin> + Send + Sync + 'static,
 {
+    // Fetch the secrets before anything needs them
+    if let Err(err) = args.fetch_secrets().await {
+        error!("{}", err.trace());
+        std::process::exit(1);
+    }
+
     // Initialize the plugins
     let log_identifier = format!("{binary} v{version}", binary = env!("CARGO_BIN_NAME"), version = env!("CARGO_PKG_VERSION"));
-    let logger: AuditLogPlugin = FileLogger::new(log_identifier, "./audit-log.log");
-    let pauthresolver: PolicyAuthResolverPlugin = get_pauth_resolver();
-    let dauthresolver: DeliberationAuthResolverPlugin = get_dauth_resolver();
+    let redactor: Redactor = match args.audit_redactor() {
+        Ok(redactor) => redactor,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let logger: AuditLogPlugin = match args.audit_forwarder() {
+        Ok(Some(config)) => AuditLogBackend::Forwarding(ForwardingLogger::new(log_identifier, config).with_redactor(redactor)),
+        Ok(None) => match &args.audit_db {
+            Some(path) => AuditLogBackend::Sqlite(SqliteAuditLogger::new(log_identifier, path.display().to_string()).with_redactor(redactor)),
+            None => AuditLogBackend::File(FileLogger::new(log_identifier, "./audit-log.log").with_redactor(redactor)),
+        },
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let pauthresolver: PolicyAuthResolverPlugin = match args.jwk_set_expert() {
+        Ok(kid_resolver) => get_pauth_resolver(kid_resolver),
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let dauthresolver: DeliberationAuthResolverPlugin = match args.jwk_set_delib() {
+        Ok(kid_resolver) => get_dauth_resolver(kid_resolver),
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
     let pstore: PolicyStorePlugin = DummyPolicyStore {};
 
     let sresolve: StateResolverPlugin = DummyStateResolver {};
 
     // Run them!
-    let server = Srv::new(args.address, logger, rconn, pstore, sresolve, pauthresolver, dauthresolver);
+    let rconn = CircuitBreaker::new(rconn, args.circuit_breaker());
+    let verdict_pipeline: VerdictPipeline = match args.verdict_pipeline() {
+        Ok(pipeline) => pipeline,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let prefilter: Prefilter = match args.prefilter() {
+        Ok(prefilter) => prefilter,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let maintenance: MaintenanceSchedule = match args.maintenance() {
+        Ok(maintenance) => maintenance,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let federation: Federation = match args.federation() {
+        Ok(federation) => federation,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let disclosure: Disclosure = match args.disclosure() {
+        Ok(disclosure) => disclosure,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let image_keys: StaticKeySet = match args.image_keys() {
+        Ok(keys) => keys,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let message_catalog: MessageCatalog = match args.message_catalog() {
+        Ok(catalog) => catalog,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let archive_key: Option<Vec<u8>> = match args.archive_key() {
+        Ok(key) => key,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let access_tokens: Option<AccessTokenIssuer> = match args.access_token_issuer() {
+        Ok(issuer) => issuer,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    let notifier: Option<Notifier> = match args.notifier() {
+        Ok(notifier) => notifier,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    };
+    if let Some(interval) = args.audit_anchor_interval {
+        Anchorer::new("./audit-log.log", "./audit-log.anchors").with_notary(args.audit_anchor_notary.clone()).spawn(Duration::from_secs(interval));
+    }
+    let server = Srv::new(args.address, logger, rconn, pstore, sresolve, pauthresolver, dauthresolver)
+        .with_fallback(args.reasoner_fallback)
+        .with_question_queue(args.question_queue())
+        .with_verdict_pipeline(verdict_pipeline)
+        .with_prefilter(prefilter)
+        .with_maintenance(maintenance)
+        .with_federation(federation)
+        .with_disclosure(disclosure)
+        .with_lint_config(args.lint_config())
+        .with_state_check(args.state_check)
+        .with_workflow_limits(args.workflow_limits())
+        .with_max_policy_size(args.max_policy_size)
+        .with_image_verifier(image_keys)
+        .with_message_catalog(message_catalog)
+        .with_archive_key(archive_key)
+        .with_access_token_issuer(access_tokens)
+        .with_notifier(notifier)
+        .with_payload_store(args.payload_store())
+        .with_callback_origins(args.callback_origin.clone());
 
     server.run().await;
 }
@@ -0,0 +1,322 @@
+//! Spreads questions over several instances (shards) of the same reasoner backend.
+//!
+//! A single backend (e.g., one eFLINT server) can only answer so many questions per second. The [`Dispatcher`] is a
+//! [`ReasonerConnector`] that owns several connectors of the same kind, each talking to its own backend instance, and
+//! routes every question to one of them. Use-cases can be pinned to a particular shard with a [`Route`]; all other
+//! questions are spread by a hash of their workflow's identifier, such that questions about the same workflow always end
+//! up at the same backend.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use audit_logger::{ConnectorWithContext, ReasonerConnectorAuditLogger, SessionedConnectorAuditLogger};
+use deliberation::spec::{Explanation, NodeResult};
+use log::debug;
+use policy::{Policy, PolicyContent};
+use reasonerconn::{
+    PolicyCompositionError, QuestionKind, ReasonerCapabilities, ReasonerConnError, ReasonerConnector, ReasonerMetric, ReasonerQueryResponse,
+    ReasonerResponse,
+};
+use sha2::{Digest as _, Sha256};
+use state_resolver::State;
+use workflow::spec::Workflow;
+
+/***** ERRORS *****/
+/// Defines errors originating from configuring a [`Dispatcher`].
+#[derive(Debug)]
+pub enum DispatchError {
+    /// The dispatcher was given no shards to dispatch to.
+    NoShards,
+    /// A route pins a use-case to a shard that does not exist.
+    UnknownShard { use_case: String, shard: usize, shards: usize },
+}
+impl Display for DispatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use DispatchError::*;
+        match self {
+            NoShards => write!(f, "Cannot dispatch questions without any reasoner shards"),
+            UnknownShard { use_case, shard, shards } => {
+                write!(f, "Use-case '{use_case}' is routed to reasoner shard {shard}, but there are only {shards} shard(s)")
+            },
+        }
+    }
+}
+impl Error for DispatchError {}
+
+/***** AUXILLARY *****/
+/// Pins all questions of a use-case to a particular shard.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Route {
+    /// The use-case to route.
+    pub use_case: String,
+    /// The (zero-indexed) shard to route it to.
+    pub shard:    usize,
+}
+impl FromStr for Route {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((use_case, shard)) = s.rsplit_once('=') else {
+            return Err(format!("Invalid route '{s}' (expected '<USE_CASE>=<SHARD>')"));
+        };
+        let shard: usize = shard.trim().parse().map_err(|err| format!("Invalid shard '{shard}' in route '{s}': {err}"))?;
+        Ok(Self { use_case: use_case.trim().into(), shard })
+    }
+}
+
+/***** LIBRARY *****/
+/// Wraps several [`ReasonerConnector`]s of the same kind and routes every question to one of them.
+///
+/// Note that all shards are of the same type, so they share the same context; dispatching does not invalidate any
+/// active policy.
+#[derive(Debug)]
+pub struct Dispatcher<C> {
+    /// The connectors we dispatch to.
+    shards:     Vec<C>,
+    /// The use-cases pinned to a particular shard.
+    routes:     HashMap<String, usize>,
+    /// The number of questions dispatched to every shard so far.
+    dispatched: Vec<AtomicU64>,
+}
+
+impl<C> Dispatcher<C> {
+    /// Constructor for the Dispatcher.
+    ///
+    /// # Arguments
+    /// - `shards`: The [`ReasonerConnector`]s to dispatch to.
+    /// - `routes`: The [`Route`]s that pin use-cases to particular shards.
+    ///
+    /// # Returns
+    /// A new Dispatcher.
+    ///
+    /// # Errors
+    /// This function errors if there are no shards, or if a route refers to a shard that does not exist.
+    pub fn new(shards: Vec<C>, routes: impl IntoIterator<Item = Route>) -> Result<Self, DispatchError> {
+        if shards.is_empty() {
+            return Err(DispatchError::NoShards);
+        }
+        let routes: HashMap<String, usize> = routes
+            .into_iter()
+            .map(|route| {
+                if route.shard < shards.len() {
+                    Ok((route.use_case, route.shard))
+                } else {
+                    Err(DispatchError::UnknownShard { use_case: route.use_case, shard: route.shard, shards: shards.len() })
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let dispatched: Vec<AtomicU64> = shards.iter().map(|_| AtomicU64::new(0)).collect();
+        Ok(Self { shards, routes, dispatched })
+    }
+
+    /// Returns the shards we dispatch to.
+    #[inline]
+    pub fn shards(&self) -> &[C] { &self.shards }
+
+    /// Decides which shard answers a question.
+    ///
+    /// # Arguments
+    /// - `state`: The [`State`] of the question, which tells us its use-case.
+    /// - `workflow`: The [`Workflow`] the question is about.
+    ///
+    /// # Returns
+    /// The shard to consult.
+    fn route(&self, state: &State, workflow: &Workflow) -> &C {
+        let shard: usize = self.shard_of(&state.use_case, &workflow.id);
+        debug!("Dispatching question about workflow '{}' (use-case '{}') to reasoner shard {shard}", workflow.id, state.use_case);
+        self.dispatched[shard].fetch_add(1, Ordering::Relaxed);
+        &self.shards[shard]
+    }
+
+    /// Decides which shard answers a query, which is not about any workflow.
+    ///
+    /// # Arguments
+    /// - `state`: The [`State`] of the query, which tells us its use-case.
+    ///
+    /// # Returns
+    /// The shard to consult.
+    fn route_query(&self, state: &State) -> &C {
+        let shard: usize = self.shard_of(&state.use_case, &state.use_case);
+        debug!("Dispatching query (use-case '{}') to reasoner shard {shard}", state.use_case);
+        self.dispatched[shard].fetch_add(1, Ordering::Relaxed);
+        &self.shards[shard]
+    }
+
+    /// Finds the shard of a question.
+    ///
+    /// # Arguments
+    /// - `use_case`: The use-case of the question, which may be pinned to a shard.
+    /// - `key`: What to spread the question by if it isn't.
+    ///
+    /// # Returns
+    /// The index of the shard.
+    fn shard_of(&self, use_case: &str, key: &str) -> usize {
+        match self.routes.get(use_case) {
+            Some(shard) => *shard,
+            None => {
+                // Use a stable hash, such that the same key lands on the same backend across restarts too
+                let digest = Sha256::digest(key.as_bytes());
+                (u64::from_be_bytes(digest[..8].try_into().unwrap()) % self.shards.len() as u64) as usize
+            },
+        }
+    }
+}
+
+impl<C: ConnectorWithContext> ConnectorWithContext for Dispatcher<C> {
+    type Context = C::Context;
+
+    #[inline]
+    fn hash() -> String { C::hash() }
+
+    #[inline]
+    fn context() -> Self::Context { C::context() }
+}
+
+#[async_trait::async_trait]
+impl<L, C> ReasonerConnector<L> for Dispatcher<C>
+where
+    L: ReasonerConnectorAuditLogger + Send + Sync + 'static,
+    C: ReasonerConnector<L> + Send + Sync,
+{
+    async fn execute_task(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        task: String,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        self.route(&state, &workflow).execute_task(logger, policy, state, workflow, task).await
+    }
+
+    async fn access_data_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        data: String,
+        task: Option<String>,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        self.route(&state, &workflow).access_data_request(logger, policy, state, workflow, data, task).await
+    }
+
+    async fn workflow_validation_request(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<ReasonerResponse, ReasonerConnError> {
+        self.route(&state, &workflow).workflow_validation_request(logger, policy, state, workflow).await
+    }
+
+    async fn query(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        query: serde_json::Value,
+    ) -> Result<ReasonerQueryResponse, ReasonerConnError> {
+        self.route_query(&state).query(logger, policy, state, query).await
+    }
+
+    async fn explain(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+        question: QuestionKind,
+    ) -> Result<Explanation, ReasonerConnError> {
+        self.route(&state, &workflow).explain(logger, policy, state, workflow, question).await
+    }
+
+    async fn node_results(
+        &self,
+        logger: SessionedConnectorAuditLogger<L>,
+        policy: Policy,
+        state: State,
+        workflow: Workflow,
+    ) -> Result<Vec<NodeResult>, ReasonerConnError> {
+        self.route(&state, &workflow).node_results(logger, policy, state, workflow).await
+    }
+
+    /// Composes policy fragments using the first shard, as all shards are of the same kind.
+    #[inline]
+    fn compose(&self, fragments: &[Policy]) -> Result<Vec<PolicyContent>, PolicyCompositionError> { self.shards[0].compose(fragments) }
+
+    /// Describes the first shard, as all shards are of the same kind.
+    #[inline]
+    fn capabilities(&self) -> ReasonerCapabilities { self.shards[0].capabilities() }
+
+    fn metrics(&self) -> Vec<ReasonerMetric> {
+        // Label the measurements of every shard with the shard they came from
+        let mut metrics: Vec<ReasonerMetric> = self
+            .dispatched
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                ReasonerMetric::new(
+                    "policy_reasoner_dispatched_total",
+                    "The number of questions dispatched to the given reasoner shard.",
+                    count.load(Ordering::Relaxed) as f64,
+                )
+                .with_label("shard", i.to_string())
+            })
+            .collect();
+        for (i, shard) in self.shards.iter().enumerate() {
+            metrics.extend(shard.metrics().into_iter().map(|metric| metric.with_label("shard", i.to_string())));
+        }
+        // Metrics with the same name must be adjacent; the sort is stable, so the order within a shard is kept
+        metrics.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        metrics
+    }
+
+    /// Asks every shard for the bundle, as the question may have been routed to any of them.
+    async fn debug_bundle(&self, reference: &str) -> Result<Option<serde_json::Value>, ReasonerConnError> {
+        for shard in &self.shards {
+            if let Some(bundle) = shard.debug_bundle(reference).await? {
+                return Ok(Some(bundle));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn reload(&self) -> Result<(), ReasonerConnError> {
+        // Shards that reloaded fine keep their new configuration, even if a later one fails
+        for (i, shard) in self.shards.iter().enumerate() {
+            shard.reload().await.map_err(|err| ReasonerConnError::new(format!("Failed to reload reasoner shard {i}")).with_source(err))?;
+        }
+        Ok(())
+    }
+
+    async fn on_policy_activated(&self, policy: &Policy) -> Result<(), ReasonerConnError> {
+        for (i, shard) in self.shards.iter().enumerate() {
+            shard
+                .on_policy_activated(policy)
+                .await
+                .map_err(|err| ReasonerConnError::new(format!("Reasoner shard {i} failed to prepare for policy")).with_source(err))?;
+        }
+        Ok(())
+    }
+
+    async fn on_policy_deactivated(&self) -> Result<(), ReasonerConnError> {
+        for (i, shard) in self.shards.iter().enumerate() {
+            shard
+                .on_policy_deactivated()
+                .await
+                .map_err(|err| ReasonerConnError::new(format!("Reasoner shard {i} failed to handle policy deactivation")).with_source(err))?;
+        }
+        Ok(())
+    }
+
+    fn configuration(&self) -> serde_json::Value {
+        // One configuration per shard, unless there is nothing to tell about any of them
+        let configs: Vec<serde_json::Value> = self.shards.iter().map(|shard| shard.configuration()).collect();
+        if configs.iter().all(serde_json::Value::is_null) { serde_json::Value::Null } else { serde_json::Value::Array(configs) }
+    }
+}
@@ -0,0 +1,289 @@
+//! Implements encrypting policy contents at rest.
+//!
+//! Policies may reveal sensitive business or medical rules, and the SQLite policy store is a plain file that may well
+//! live on a shared disk. If configured with a [`PolicyCipher`], the store encrypts every content blob with AES-256-GCM
+//! before writing it, and decrypts it again when reading, such that the API never notices.
+//!
+//! Encrypted blobs are stored as [`ENCRYPTED_PREFIX`] followed by the Base64-encoded nonce and ciphertext. The hash of
+//! the content (which is how versions refer to it) is used as associated data, so blobs cannot be swapped between rows.
+//! Blobs without the prefix are plaintext, which is how stores written before encryption was enabled can still be read.
+
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FResult};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::{env, fs};
+
+use aes_gcm::aead::{Aead as _, AeadCore as _, KeyInit as _, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64ct::{Base64, Encoding as _};
+
+/***** CONSTANTS *****/
+/// The prefix of encrypted content blobs, which also identifies the format.
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// The length of the nonces prepended to the ciphertexts, in bytes.
+const NONCE_LEN: usize = 12;
+
+/***** ERRORS *****/
+/// Defines errors originating from encrypting or decrypting policy contents.
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// Failed to read the key file.
+    KeyRead { path: PathBuf, err: std::io::Error },
+    /// The environment variable with the key is not set.
+    KeyEnvMissing { var: String },
+    /// The key is not hex-encoded.
+    KeyDecode { source: KeySource, err: hex::FromHexError },
+    /// The key is not 256 bits long.
+    KeyLength { source: KeySource, len: usize },
+    /// A blob could not be encrypted.
+    Encrypt { hash: String },
+    /// An encrypted blob is not valid Base64 or too short.
+    Malformed { hash: String },
+    /// An encrypted blob could not be decrypted (i.e., the key is wrong or the blob was tampered with).
+    Decrypt { hash: String },
+    /// An encrypted blob was found, but no key is configured.
+    NoKey { hash: String },
+}
+impl Display for EncryptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use EncryptionError::*;
+        match self {
+            KeyRead { path, .. } => write!(f, "Failed to read policy encryption key file '{}'", path.display()),
+            KeyEnvMissing { var } => write!(f, "Environment variable '{var}' with the policy encryption key is not set"),
+            KeyDecode { source, .. } => write!(f, "Policy encryption key from {source} is not hex-encoded"),
+            KeyLength { source, len } => write!(f, "Policy encryption key from {source} is {len} bytes long instead of 32"),
+            Encrypt { hash } => write!(f, "Failed to encrypt policy content '{hash}'"),
+            Malformed { hash } => write!(f, "Encrypted policy content '{hash}' is malformed"),
+            Decrypt { hash } => write!(f, "Failed to decrypt policy content '{hash}' (wrong key, or tampered with)"),
+            NoKey { hash } => write!(f, "Policy content '{hash}' is encrypted, but no policy encryption key is configured"),
+        }
+    }
+}
+impl Error for EncryptionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use EncryptionError::*;
+        match self {
+            KeyRead { err, .. } => Some(err),
+            KeyEnvMissing { .. } => None,
+            KeyDecode { err, .. } => Some(err),
+            KeyLength { .. } => None,
+            Encrypt { .. } => None,
+            Malformed { .. } => None,
+            Decrypt { .. } => None,
+            NoKey { .. } => None,
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// Defines where the key to encrypt policy contents with comes from.
+///
+/// Either way, the key is 32 hex-encoded bytes (e.g., as generated by `openssl rand -hex 32`). Keys held in a KMS can be
+/// provided through either, e.g., by a secrets agent writing them to a file or the environment.
+#[derive(Clone, Debug)]
+pub enum KeySource {
+    /// A file with the key.
+    File(PathBuf),
+    /// An environment variable with the key.
+    Env(String),
+}
+impl KeySource {
+    /// Reads the key.
+    ///
+    /// # Returns
+    /// The raw, 32-byte key.
+    ///
+    /// # Errors
+    /// This function errors if the key could not be read, or is not 32 hex-encoded bytes.
+    pub fn read(&self) -> Result<Vec<u8>, EncryptionError> {
+        let raw: String = match self {
+            Self::File(path) => fs::read_to_string(path).map_err(|err| EncryptionError::KeyRead { path: path.clone(), err })?,
+            Self::Env(var) => env::var(var).map_err(|_| EncryptionError::KeyEnvMissing { var: var.clone() })?,
+        };
+        let key: Vec<u8> = hex::decode(raw.trim()).map_err(|err| EncryptionError::KeyDecode { source: self.clone(), err })?;
+        if key.len() != 32 {
+            return Err(EncryptionError::KeyLength { source: self.clone(), len: key.len() });
+        }
+        Ok(key)
+    }
+}
+impl Display for KeySource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::Env(var) => write!(f, "env:{var}"),
+        }
+    }
+}
+impl FromStr for KeySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            Ok(Self::File(path.into()))
+        } else if let Some(var) = s.strip_prefix("env:") {
+            Ok(Self::Env(var.into()))
+        } else {
+            Err(format!("Unknown policy encryption key source '{s}' (expected 'file:<PATH>' or 'env:<VAR>')"))
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// Encrypts and decrypts policy content blobs with AES-256-GCM.
+#[derive(Clone)]
+pub struct PolicyCipher {
+    /// The cipher, initialized with the key.
+    cipher: Aes256Gcm,
+}
+impl Debug for PolicyCipher {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { f.debug_struct("PolicyCipher").finish_non_exhaustive() }
+}
+impl PolicyCipher {
+    /// Constructor for the PolicyCipher.
+    ///
+    /// # Arguments
+    /// - `source`: The [`KeySource`] to read the key from.
+    ///
+    /// # Returns
+    /// A new PolicyCipher.
+    ///
+    /// # Errors
+    /// This function errors if the key could not be read, or is not 32 hex-encoded bytes.
+    pub fn new(source: &KeySource) -> Result<Self, EncryptionError> {
+        let key: Vec<u8> = source.read()?;
+        let cipher: Aes256Gcm =
+            Aes256Gcm::new_from_slice(&key).map_err(|_| EncryptionError::KeyLength { source: source.clone(), len: key.len() })?;
+        Ok(Self { cipher })
+    }
+
+    /// Returns whether a stored blob is encrypted.
+    #[inline]
+    pub fn is_encrypted(blob: &str) -> bool { blob.starts_with(ENCRYPTED_PREFIX) }
+
+    /// Encrypts a content blob.
+    ///
+    /// # Arguments
+    /// - `hash`: The hash of the content, which the encrypted blob is bound to.
+    /// - `blob`: The (serialized) content.
+    ///
+    /// # Returns
+    /// The encrypted blob, ready for storing.
+    ///
+    /// # Errors
+    /// This function errors if the blob could not be encrypted.
+    pub fn encrypt(&self, hash: &str, blob: &str) -> Result<String, EncryptionError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext: Vec<u8> = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: blob.as_bytes(), aad: hash.as_bytes() })
+            .map_err(|_| EncryptionError::Encrypt { hash: hash.into() })?;
+        let mut raw: Vec<u8> = nonce.to_vec();
+        raw.extend(ciphertext);
+        Ok(format!("{ENCRYPTED_PREFIX}{}", Base64::encode_string(&raw)))
+    }
+
+    /// Decrypts a content blob, if it is encrypted.
+    ///
+    /// # Arguments
+    /// - `cipher`: The PolicyCipher to decrypt with, if any.
+    /// - `hash`: The hash of the content, which the encrypted blob is bound to.
+    /// - `blob`: The stored blob.
+    ///
+    /// # Returns
+    /// The (serialized) content, which is `blob` itself if it was plaintext.
+    ///
+    /// # Errors
+    /// This function errors if the blob is encrypted but there is no cipher, if it is malformed, or if it could not be
+    /// decrypted with this key.
+    pub fn decrypt(cipher: Option<&Self>, hash: &str, blob: String) -> Result<String, EncryptionError> {
+        let Some(encoded) = blob.strip_prefix(ENCRYPTED_PREFIX) else { return Ok(blob) };
+        let Some(cipher) = cipher else { return Err(EncryptionError::NoKey { hash: hash.into() }) };
+        let raw: Vec<u8> = Base64::decode_vec(encoded).map_err(|_| EncryptionError::Malformed { hash: hash.into() })?;
+        if raw.len() < NONCE_LEN {
+            return Err(EncryptionError::Malformed { hash: hash.into() });
+        }
+        let (nonce, ciphertext): (&[u8], &[u8]) = raw.split_at(NONCE_LEN);
+        let plaintext: Vec<u8> = cipher
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: hash.as_bytes() })
+            .map_err(|_| EncryptionError::Decrypt { hash: hash.into() })?;
+        String::from_utf8(plaintext).map_err(|_| EncryptionError::Malformed { hash: hash.into() })
+    }
+}
+
+/***** TESTS *****/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The hash that the blobs in these tests are bound to.
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    /// The content encrypted in these tests.
+    const CONTENT: &str = r#"[{"kind":"fact","name":"user"}]"#;
+
+    /// Creates a cipher with a key of 32 times the given byte.
+    fn cipher_with(byte: u8) -> PolicyCipher { PolicyCipher { cipher: Aes256Gcm::new_from_slice(&[byte; 32]).unwrap() } }
+
+    /// Flips a bit of the given byte of the nonce and ciphertext of an encrypted blob.
+    fn tamper(blob: &str, index: usize) -> String {
+        let mut raw: Vec<u8> = Base64::decode_vec(blob.strip_prefix(ENCRYPTED_PREFIX).unwrap()).unwrap();
+        raw[index] ^= 0x01;
+        format!("{ENCRYPTED_PREFIX}{}", Base64::encode_string(&raw))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cipher: PolicyCipher = cipher_with(42);
+        let blob: String = cipher.encrypt(HASH, CONTENT).unwrap();
+        assert!(PolicyCipher::is_encrypted(&blob));
+        assert!(!blob.contains(CONTENT));
+        assert_eq!(PolicyCipher::decrypt(Some(&cipher), HASH, blob.clone()).unwrap(), CONTENT);
+
+        // Every encryption uses a fresh nonce
+        assert_ne!(cipher.encrypt(HASH, CONTENT).unwrap(), blob);
+
+        // Plaintext blobs are passed through, with or without a key
+        assert!(!PolicyCipher::is_encrypted(CONTENT));
+        assert_eq!(PolicyCipher::decrypt(Some(&cipher), HASH, CONTENT.into()).unwrap(), CONTENT);
+        assert_eq!(PolicyCipher::decrypt(None, HASH, CONTENT.into()).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_tampered_ciphertext() {
+        let cipher: PolicyCipher = cipher_with(42);
+        let blob: String = cipher.encrypt(HASH, CONTENT).unwrap();
+
+        // Changing the nonce, the ciphertext or the tag fails to decrypt
+        let len: usize = Base64::decode_vec(blob.strip_prefix(ENCRYPTED_PREFIX).unwrap()).unwrap().len();
+        for index in [0, NONCE_LEN, len - 1] {
+            assert!(matches!(PolicyCipher::decrypt(Some(&cipher), HASH, tamper(&blob, index)), Err(EncryptionError::Decrypt { .. })));
+        }
+
+        // Blobs that are not Base64 or too short to hold a nonce are malformed
+        let malformed: [String; 2] = [format!("{ENCRYPTED_PREFIX}not base64!"), format!("{ENCRYPTED_PREFIX}{}", Base64::encode_string(&[0; 4]))];
+        for blob in malformed {
+            assert!(matches!(PolicyCipher::decrypt(Some(&cipher), HASH, blob), Err(EncryptionError::Malformed { .. })));
+        }
+    }
+
+    #[test]
+    fn test_wrong_aad() {
+        let cipher: PolicyCipher = cipher_with(42);
+        let blob: String = cipher.encrypt(HASH, CONTENT).unwrap();
+
+        // A blob cannot be moved to the row of another content hash
+        let other: &str = "60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752";
+        assert!(matches!(PolicyCipher::decrypt(Some(&cipher), other, blob), Err(EncryptionError::Decrypt { hash }) if hash == other));
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let blob: String = cipher_with(42).encrypt(HASH, CONTENT).unwrap();
+        assert!(matches!(PolicyCipher::decrypt(Some(&cipher_with(43)), HASH, blob.clone()), Err(EncryptionError::Decrypt { .. })));
+        assert!(matches!(PolicyCipher::decrypt(None, HASH, blob), Err(EncryptionError::NoKey { .. })));
+    }
+}
@@ -0,0 +1,743 @@
+//! Implements an audit logger that ships statements to a central log store (Elasticsearch, Grafana Loki or S3-compatible
+//! object storage).
+//!
+//! Statements are put on a bounded queue and shipped in batches by a background task: whatever is waiting when the
+//! task becomes free is sent in a single request (up to a maximum batch size), such that no latency is added when the
+//! reasoner is idle while requests are still bundled under load.
+//!
+//! Object stores (such as AWS S3 or MinIO) get every batch as a new newline-delimited JSON object. Objects are never
+//! overwritten or deleted, so buckets with Object Lock (i.e., write-once-read-many storage) can be used; see
+//! [`S3Config`] for encrypting objects and setting their retention.
+//!
+//! Since a verdict may never be given without it being recorded, logging a statement only returns once the store has
+//! confirmed the batch it is in. If the queue is full or the store cannot be reached, logging fails, which in turn fails
+//! the request that caused it.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use audit_logger::redact::Redactor;
+use audit_logger::{
+    ArchivedPayload, AuditLogger, ConnectorWithContext, Error as AuditLoggerError, LogStatement, ReasonerConnectorAuditLogger, RequestTimings,
+    SessionSummary,
+};
+use auth_resolver::AuthContext;
+use base64ct::{Base64, Encoding as _};
+use deliberation::ids::{IdGenerator, UuidV7Generator};
+use deliberation::spec::Verdict;
+use enum_debug::EnumDebug as _;
+use hmac::{Hmac, Mac as _};
+use log::{debug, warn};
+use policy::{Policy, PolicyArchive};
+use serde_json::{Value, json};
+use sha2::{Digest as _, Sha256};
+use state_resolver::State;
+use tokio::sync::{mpsc, oneshot};
+use workflow::Workflow;
+use workflow::diagnostics::InlineStats;
+
+/***** ERRORS *****/
+/// Defines errors originating from the [`ForwardingLogger`].
+#[derive(Debug)]
+pub enum ForwarderError {
+    /// Failed to serialize a statement.
+    StatementSerialize { kind: String, err: serde_json::Error },
+    /// The queue of statements waiting to be shipped is full.
+    QueueFull { size: usize },
+    /// The background task shipping statements is no longer running.
+    Stopped,
+    /// The log store did not confirm delivery.
+    Delivery { target: String, reason: String },
+}
+impl Display for ForwarderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use ForwarderError::*;
+        match self {
+            StatementSerialize { kind, .. } => write!(f, "Failed to serialize {kind}"),
+            QueueFull { size } => write!(f, "Audit log forwarding queue is full ({size} statements waiting)"),
+            Stopped => write!(f, "Audit log forwarder is no longer running"),
+            Delivery { target, reason } => write!(f, "Failed to deliver audit log statements to {target}: {reason}"),
+        }
+    }
+}
+impl Error for ForwarderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use ForwarderError::*;
+        match self {
+            StatementSerialize { err, .. } => Some(err),
+            QueueFull { .. } => None,
+            Stopped => None,
+            Delivery { .. } => None,
+        }
+    }
+}
+
+/***** AUXILLARY *****/
+/// Defines the kinds of log stores we can forward to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ForwardKind {
+    /// Elasticsearch (or OpenSearch), using the bulk API.
+    Elasticsearch,
+    /// Grafana Loki, using the push API.
+    Loki,
+    /// An S3-compatible object store (e.g., AWS S3 or MinIO), writing every batch as a new object.
+    S3,
+}
+impl Display for ForwardKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Elasticsearch => write!(f, "elasticsearch"),
+            Self::Loki => write!(f, "loki"),
+            Self::S3 => write!(f, "s3"),
+        }
+    }
+}
+impl FromStr for ForwardKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elasticsearch" | "es" => Ok(Self::Elasticsearch),
+            "loki" => Ok(Self::Loki),
+            "s3" | "minio" => Ok(Self::S3),
+            other => Err(format!("Unknown log store '{other}' (expected 'elasticsearch', 'loki' or 's3')")),
+        }
+    }
+}
+
+/// Defines how objects written to an S3-compatible store are encrypted by the store.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ServerSideEncryption {
+    /// With keys managed by the store (`AES256`).
+    S3,
+    /// With keys managed by AWS KMS (`aws:kms`), optionally with a particular key instead of the bucket's default.
+    Kms { key_id: Option<String> },
+}
+impl Display for ServerSideEncryption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::S3 => write!(f, "aes256"),
+            Self::Kms { key_id: None } => write!(f, "aws:kms"),
+            Self::Kms { key_id: Some(key_id) } => write!(f, "aws:kms:{key_id}"),
+        }
+    }
+}
+impl FromStr for ServerSideEncryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aes256" | "AES256" => Ok(Self::S3),
+            "aws:kms" => Ok(Self::Kms { key_id: None }),
+            other => match other.strip_prefix("aws:kms:") {
+                Some(key_id) if !key_id.is_empty() => Ok(Self::Kms { key_id: Some(key_id.into()) }),
+                _ => Err(format!("Unknown server-side encryption '{other}' (expected 'aes256', 'aws:kms' or 'aws:kms:<KEY_ID>')")),
+            },
+        }
+    }
+}
+
+/// Defines how strictly objects written to a bucket with Object Lock are protected during their retention.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ObjectLockMode {
+    /// Users with special permissions may still remove the object.
+    Governance,
+    /// No one may remove the object, not even the root user.
+    Compliance,
+}
+impl Display for ObjectLockMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Governance => write!(f, "governance"),
+            Self::Compliance => write!(f, "compliance"),
+        }
+    }
+}
+impl FromStr for ObjectLockMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "governance" => Ok(Self::Governance),
+            "compliance" => Ok(Self::Compliance),
+            other => Err(format!("Unknown object lock mode '{other}' (expected 'governance' or 'compliance')")),
+        }
+    }
+}
+
+/// Configures how a [`ForwardingLogger`] writes to an S3-compatible object store.
+///
+/// The bucket is part of the URL of the [`ForwarderConfig`] (i.e., path-style, such as `http://localhost:9000/audit`),
+/// and its index is used as the prefix of the objects' keys.
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    /// The region of the bucket (e.g., `eu-west-1`). S3-compatible stores usually accept `us-east-1`.
+    pub region:      String,
+    /// The access key ID to sign requests with.
+    pub access_key:  String,
+    /// The secret access key to sign requests with.
+    pub secret_key:  String,
+    /// How the store encrypts the objects, or [`None`] to leave it to the bucket's default.
+    pub encryption:  Option<ServerSideEncryption>,
+    /// How long (in days) and how strictly every object is locked, or [`None`] to leave it to the bucket's default.
+    pub object_lock: Option<(ObjectLockMode, u32)>,
+}
+
+/// Configures where and how a [`ForwardingLogger`] ships statements.
+#[derive(Clone, Debug)]
+pub struct ForwarderConfig {
+    /// The kind of log store to ship to.
+    pub kind: ForwardKind,
+    /// The base URL of the log store (e.g., `http://localhost:9200` or `http://localhost:3100`).
+    pub url: String,
+    /// The Elasticsearch index to write to, or the value of the `index` label in Loki.
+    pub index: String,
+    /// The maximum number of statements waiting to be shipped.
+    pub queue_size: usize,
+    /// The maximum number of statements shipped in one request.
+    pub batch_size: usize,
+    /// The time to wait for the log store to confirm a batch.
+    pub timeout: Duration,
+    /// How to write to the log store if it is an object store.
+    pub s3: Option<S3Config>,
+}
+
+/// A statement waiting to be shipped.
+struct Pending {
+    /// When the statement was logged, in nanoseconds since the Unix epoch.
+    timestamp: u128,
+    /// The document to ship.
+    document: Value,
+    /// Where to report whether the statement was delivered.
+    ack: oneshot::Sender<Result<(), String>>,
+}
+
+/***** HELPERS *****/
+/// Computes the HMAC-SHA256 of a message.
+fn hmac_sha256(key: &[u8], msg: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(msg.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes everything in an object key but unreserved characters and slashes, as S3 expects.
+fn uri_encode(key: &str) -> String {
+    key.bytes().map(|b| if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) { (b as char).to_string() } else { format!("%{b:02X}") }).collect()
+}
+
+/// Writes a new object to an S3-compatible store, signed with AWS Signature Version 4.
+///
+/// The object is written with `If-None-Match: *`, so it is never overwritten, and with a SHA-256 checksum, which buckets
+/// with Object Lock require.
+///
+/// # Errors
+/// This function errors with a human-readable reason if the store did not confirm the object.
+async fn put_object(client: &reqwest::Client, s3: &S3Config, bucket: &str, key: &str, body: String) -> Result<(), String> {
+    let url: reqwest::Url = reqwest::Url::parse(&format!("{bucket}/{}", uri_encode(key))).map_err(|err| err.to_string())?;
+    let host: String = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.into(),
+        (None, _) => return Err(format!("URL '{url}' has no host")),
+    };
+    let now: chrono::DateTime<chrono::Utc> = chrono::Utc::now();
+    let date: String = now.format("%Y%m%d").to_string();
+    let timestamp: String = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let digest = Sha256::digest(body.as_bytes());
+
+    // Every header is signed, in order of their (lowercase) names
+    let mut headers: BTreeMap<&str, String> = BTreeMap::from([
+        ("content-type", "application/x-ndjson".into()),
+        ("host", host),
+        ("if-none-match", "*".into()),
+        ("x-amz-checksum-sha256", Base64::encode_string(&digest)),
+        ("x-amz-content-sha256", hex::encode(digest)),
+        ("x-amz-date", timestamp.clone()),
+        ("x-amz-sdk-checksum-algorithm", "SHA256".into()),
+    ]);
+    match &s3.encryption {
+        Some(ServerSideEncryption::S3) => {
+            headers.insert("x-amz-server-side-encryption", "AES256".into());
+        },
+        Some(ServerSideEncryption::Kms { key_id }) => {
+            headers.insert("x-amz-server-side-encryption", "aws:kms".into());
+            if let Some(key_id) = key_id {
+                headers.insert("x-amz-server-side-encryption-aws-kms-key-id", key_id.clone());
+            }
+        },
+        None => {},
+    }
+    if let Some((mode, days)) = s3.object_lock {
+        let until: chrono::DateTime<chrono::Utc> = now + chrono::Duration::days(days.into());
+        headers.insert("x-amz-object-lock-mode", mode.to_string().to_uppercase());
+        headers.insert("x-amz-object-lock-retain-until-date", until.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    }
+
+    let signed: String = headers.keys().copied().collect::<Vec<&str>>().join(";");
+    let canonical: String = format!(
+        "PUT\n{}\n\n{}\n{signed}\n{}",
+        url.path(),
+        headers.iter().map(|(name, value)| format!("{name}:{}\n", value.trim())).collect::<String>(),
+        headers["x-amz-content-sha256"]
+    );
+    let scope: String = format!("{date}/{}/s3/aws4_request", s3.region);
+    let to_sign: String = format!("AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}", hex::encode(Sha256::digest(canonical.as_bytes())));
+    let signing_key: Vec<u8> = [date.as_str(), s3.region.as_str(), "s3", "aws4_request"]
+        .into_iter()
+        .fold(format!("AWS4{}", s3.secret_key).into_bytes(), |key, part| hmac_sha256(&key, part));
+    let signature: String = hex::encode(hmac_sha256(&signing_key, &to_sign));
+
+    let mut req = client
+        .put(url.clone())
+        .header("Authorization", format!("AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed}, Signature={signature}", s3.access_key));
+    for (name, value) in headers.iter().filter(|(name, _)| **name != "host") {
+        req = req.header(*name, value);
+    }
+    req.body(body).send().await.and_then(|res| res.error_for_status()).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+/// Ships a batch of statements to the log store.
+///
+/// # Errors
+/// This function errors with a human-readable reason if the log store did not confirm the complete batch.
+async fn ship(client: &reqwest::Client, config: &ForwarderConfig, identifier: &str, batch: &[Pending]) -> Result<(), String> {
+    let url: &str = config.url.trim_end_matches('/');
+    match config.kind {
+        ForwardKind::Elasticsearch => {
+            // The bulk API takes alternating action and document lines
+            let mut body: String = String::new();
+            for pending in batch {
+                body.push_str(&json!({ "index": { "_index": config.index } }).to_string());
+                body.push('\n');
+                body.push_str(&pending.document.to_string());
+                body.push('\n');
+            }
+            let res = client
+                .post(format!("{url}/_bulk"))
+                .header("Content-Type", "application/x-ndjson")
+                .body(body)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status())
+                .map_err(|err| err.to_string())?;
+            // Elasticsearch reports failures of individual documents in the body only
+            let res: Value = res.json().await.map_err(|err| err.to_string())?;
+            if res.get("errors").and_then(Value::as_bool).unwrap_or(true) {
+                return Err("Elasticsearch rejected (some of) the documents in the batch".into());
+            }
+            Ok(())
+        },
+
+        ForwardKind::Loki => {
+            let values: Vec<[String; 2]> = batch.iter().map(|pending| [pending.timestamp.to_string(), pending.document.to_string()]).collect();
+            let labels: BTreeMap<&str, &str> = [("job", "policy-reasoner"), ("index", config.index.as_str()), ("identifier", identifier)].into();
+            client
+                .post(format!("{url}/loki/api/v1/push"))
+                .json(&json!({ "streams": [{ "stream": labels, "values": values }] }))
+                .send()
+                .await
+                .and_then(|res| res.error_for_status())
+                .map_err(|err| err.to_string())?;
+            Ok(())
+        },
+
+        ForwardKind::S3 => {
+            let Some(s3) = &config.s3 else { return Err("No S3 credentials configured".into()) };
+            // Keys sort by time, and are unique per reasoner since batches are shipped one at a time
+            let source: String =
+                identifier.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' }).collect();
+            let key: String =
+                format!("{}/{}-{source}-{}.ndjson", config.index.trim_matches('/'), chrono::Utc::now().format("%Y/%m/%d/%H%M%S%.9f"), batch.len());
+            let body: String = batch.iter().map(|pending| format!("{}\n", pending.document)).collect();
+            put_object(client, s3, url, &key, body).await
+        },
+    }
+}
+
+/// Ships statements from the queue until all senders are gone.
+async fn run(config: ForwarderConfig, identifier: String, mut queue: mpsc::Receiver<Pending>) {
+    let client: reqwest::Client = match reqwest::Client::builder().timeout(config.timeout).build() {
+        Ok(client) => client,
+        Err(err) => {
+            warn!("Failed to build HTTP client for audit log forwarding: {err}");
+            return;
+        },
+    };
+
+    while let Some(first) = queue.recv().await {
+        // Take whatever else is already waiting
+        let mut batch: Vec<Pending> = vec![first];
+        while batch.len() < config.batch_size {
+            match queue.try_recv() {
+                Ok(pending) => batch.push(pending),
+                Err(_) => break,
+            }
+        }
+
+        debug!("Shipping {} audit log statement(s) to {} at '{}'...", batch.len(), config.kind, config.url);
+        let res: Result<(), String> = ship(&client, &config, &identifier, &batch).await;
+        if let Err(reason) = &res {
+            warn!("Failed to ship {} audit log statement(s) to {}: {reason}", batch.len(), config.kind);
+        }
+        for pending in batch {
+            // The request may have been dropped in the meantime; it doesn't care anymore then
+            let _ = pending.ack.send(res.clone());
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// An audit logger that ships statements to a central log store.
+#[derive(Clone)]
+pub struct ForwardingLogger {
+    /// The identifier of source of the logger. E.g. "policy-reasoner v1.2.3".
+    identifier: String,
+    /// Where statements are shipped to, for error messages.
+    target:     String,
+    /// The size of the queue, for error messages.
+    queue_size: usize,
+    /// The queue of statements to ship.
+    queue:      mpsc::Sender<Pending>,
+
+    /// Redacts personal data from statements before they are shipped.
+    redactor: Redactor,
+    /// Generates the identifiers of statements.
+    ids:      Arc<dyn IdGenerator>,
+}
+
+impl ForwardingLogger {
+    /// Constructor for the ForwardingLogger.
+    ///
+    /// Note that this spawns the background task that ships the statements, so it must be called within a Tokio runtime.
+    ///
+    /// # Arguments
+    /// - `identifier`: The identifier of the source of the logger. E.g. "policy-reasoner v1.2.3".
+    /// - `config`: The [`ForwarderConfig`] that determines where and how to ship statements.
+    ///
+    /// # Returns
+    /// A new instance of self, ready for action.
+    pub fn new(identifier: String, config: ForwarderConfig) -> Self {
+        let (queue, receiver) = mpsc::channel(config.queue_size.max(1));
+        let target: String = format!("{} at '{}'", config.kind, config.url);
+        let queue_size: usize = config.queue_size;
+        tokio::spawn(run(config, identifier.clone(), receiver));
+        Self { identifier, target, queue_size, queue, redactor: Redactor::default(), ids: Arc::new(UuidV7Generator) }
+    }
+
+    /// Makes the ForwardingLogger redact every statement before shipping it.
+    ///
+    /// # Arguments
+    /// - `redactor`: The [`Redactor`] that pseudonymizes and/or drops personal data.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Sets how the identifiers of statements are generated.
+    ///
+    /// # Arguments
+    /// - `ids`: The [`IdGenerator`] to use. Defaults to a [`UuidV7Generator`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_id_generator(mut self, ids: impl 'static + IdGenerator) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
+
+    /// Ships a log statement to the log store.
+    ///
+    /// # Arguments
+    /// - `stmt`: The [`LogStatement`] that determines what we're gonna log.
+    ///
+    /// # Errors
+    /// This function errors if the queue is full, or if the log store did not confirm delivery.
+    pub async fn log(&self, stmt: LogStatement<'_>) -> Result<(), ForwarderError> {
+        debug!("Queueing {}-statement for forwarding...", stmt.variant());
+        let stmt: LogStatement = self.redactor.redact(stmt);
+        let statement: Value = serde_json::to_value(stmt.versioned().with_id(self.ids.generate()))
+            .map_err(|err| ForwarderError::StatementSerialize { kind: format!("{:?}", stmt.variant()), err })?;
+
+        let now: Duration = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let document: Value = json!({
+            "@timestamp": chrono::Local::now().to_rfc3339(),
+            "identifier": self.identifier,
+            "statement": statement,
+        });
+        let (ack, confirmed) = oneshot::channel();
+        self.queue.try_send(Pending { timestamp: now.as_nanos(), document, ack }).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => ForwarderError::QueueFull { size: self.queue_size },
+            mpsc::error::TrySendError::Closed(_) => ForwarderError::Stopped,
+        })?;
+
+        // Only return once the statement is safely stored
+        match confirmed.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(reason)) => Err(ForwarderError::Delivery { target: self.target.clone(), reason }),
+            Err(_) => Err(ForwarderError::Stopped),
+        }
+    }
+}
+#[async_trait::async_trait]
+impl AuditLogger for ForwardingLogger {
+    async fn log_exec_task_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+        task: &str,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward execute_task request");
+
+        let stmt: LogStatement = LogStatement::execute_task(reference, auth, policy, policy_hashes, state, workflow, task);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_data_access_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+        data: &str,
+        task: &Option<String>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward data_access request");
+
+        let stmt = LogStatement::asset_access(reference, auth, policy, policy_hashes, state, workflow, data, task);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_validate_workflow_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward workflow_validate request");
+
+        let stmt = LogStatement::workflow_validate(reference, auth, policy, policy_hashes, state, workflow);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_query_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        state: &State,
+        query: &Value,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward query request");
+
+        let stmt = LogStatement::query(reference, auth, policy, state, query);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_verdict(&self, reference: &str, verdict: &Verdict) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward reasoner verdict");
+
+        let stmt = LogStatement::reasoner_verdict(reference, verdict);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_request_timings(&self, timings: &RequestTimings) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward request timings");
+
+        let stmt = LogStatement::request_timings(timings);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_workflow_preprocessed(&self, reference: &str, stats: &InlineStats) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward workflow preprocessing");
+
+        let stmt = LogStatement::workflow_preprocessed(reference, stats);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_reasoner_fallback(&self, reference: &str, behaviour: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward reasoner fallback");
+
+        let stmt = LogStatement::reasoner_fallback(reference, behaviour, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_circuit_open(&self, reference: &str, behaviour: &str, retry_after_ms: u64, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward open circuit breaker");
+
+        let stmt = LogStatement::circuit_open(reference, behaviour, retry_after_ms, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_verdict_transformed(&self, reference: &str, transformer: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward verdict transformation");
+
+        let stmt = LogStatement::verdict_transformed(reference, transformer, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_prefilter_matched(&self, reference: &str, rule: &str, effect: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward prefilter match");
+
+        let stmt = LogStatement::prefilter_matched(reference, rule, effect);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_retrospective_evaluation(&self, reference: &str, as_of: &str, state_snapshot: bool) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward retrospective evaluation");
+
+        let stmt = LogStatement::retrospective_evaluation(reference, as_of, state_snapshot);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_reasoner_context<C: ConnectorWithContext>(&self) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward reasoner connector context");
+
+        let stmt: LogStatement = LogStatement::reasoner_context::<C>();
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_effective_reasoner_context(&self, context: &Value, hash: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward effective reasoner connector context");
+
+        let stmt: LogStatement = LogStatement::effective_reasoner_context(context, hash);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_add_policy_request<C: ConnectorWithContext>(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward policy add");
+
+        let stmt: LogStatement = LogStatement::policy_add::<C>(auth, policy);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_set_active_version_policy(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward policy activate");
+
+        let stmt = LogStatement::policy_activate(auth, policy);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_deactivate_policy(&self, auth: &AuthContext) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward policy deactivation");
+
+        let stmt = LogStatement::policy_deactivate(auth);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_import_policies(&self, auth: &AuthContext, archive: &PolicyArchive) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward policy import");
+
+        let stmt = LogStatement::policy_import(auth, archive);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_lockdown(&self, auth: &AuthContext, engaged: bool, reason: Option<&str>) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward lockdown");
+
+        let stmt = LogStatement::lockdown(auth, engaged, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_auth_failure(&self, route: &str, reason: &str, detail: &str, initiator_hint: Option<&str>) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward authentication failure");
+
+        let stmt = LogStatement::auth_failure(route, reason, detail, initiator_hint);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_auth_success(&self, auth: &AuthContext, route: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward authentication success");
+
+        let stmt = LogStatement::auth_success(auth, route);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_owner_notification(
+        &self,
+        reference: &str,
+        dataset: &str,
+        channel: &str,
+        recipient: &str,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward owner notification");
+
+        let stmt = LogStatement::owner_notification(reference, dataset, channel, recipient, error);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_federated_verdict(
+        &self,
+        reference: &str,
+        domain: &str,
+        remote_reference: Option<&str>,
+        allowed: bool,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward federated verdict");
+
+        let stmt = LogStatement::federated_verdict(reference, domain, remote_reference, allowed, error);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_policy_disclosure(&self, auth: &AuthContext, fields: &[String], withheld: &[String]) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward policy disclosure");
+
+        let stmt = LogStatement::policy_disclosure(auth, fields, withheld);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn flush(&self, _reference: &str) -> Result<(), AuditLoggerError> {
+        // Logging only returns once the log store confirmed the statement, so there's nothing left to wait for
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ReasonerConnectorAuditLogger for ForwardingLogger {
+    async fn log_reasoner_response(&self, reference: &str, response: &str, retries: u32) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward reasoner response");
+
+        let stmt = LogStatement::reasoner_response(reference, response, retries);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_session_summary(&self, summary: &SessionSummary) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward session summary");
+
+        let stmt = LogStatement::session_summary(summary);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_shadow_evaluation(&self, reference: &str, primary: &Value, shadow: &Value, agree: bool) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward shadow evaluation");
+
+        let stmt = LogStatement::shadow_evaluation(reference, primary, shadow, agree);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_payload_archived(&self, payload: &ArchivedPayload) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to forward archived payload");
+
+        let stmt = LogStatement::payload_archived(payload);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+}
@@ -1,6 +1,17 @@
+pub mod anchor;
+#[cfg(feature = "sqlite")]
+pub mod audit_store;
 pub mod auth;
+pub mod dispatch;
+pub mod encryption;
+pub mod forwarder;
 pub mod logger;
+#[cfg(feature = "sqlite")]
 pub mod models;
+pub mod retention;
+#[cfg(feature = "sqlite")]
 pub mod schema;
+pub mod secrets;
+#[cfg(feature = "sqlite")]
 pub mod sqlite;
 pub mod state;
@@ -1,18 +1,30 @@
 use std::error::Error;
+use std::ffi::OsString;
 use std::fmt::{Debug, Display, Formatter, Result as FResult};
-use std::path::PathBuf;
-
-use audit_logger::{AuditLogger, ConnectorWithContext, Error as AuditLoggerError, LogStatement, ReasonerConnectorAuditLogger};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use audit_logger::redact::Redactor;
+use audit_logger::{
+    ArchivedPayload, AuditLogger, ConnectorWithContext, Error as AuditLoggerError, LogStatement, ReasonerConnectorAuditLogger, RequestTimings,
+    SessionSummary,
+};
 use auth_resolver::AuthContext;
+use deliberation::ids::{IdGenerator, UuidV7Generator};
 use deliberation::spec::Verdict;
 use enum_debug::EnumDebug;
-use error_trace::ErrorTrace as _;
 use log::debug;
-use policy::Policy;
+use policy::{Policy, PolicyArchive};
+use serde_json::Value;
 use state_resolver::State;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
 use workflow::Workflow;
+use workflow::diagnostics::InlineStats;
+
+#[cfg(feature = "sqlite")]
+use crate::audit_store::SqliteAuditLogger;
+use crate::forwarder::ForwardingLogger;
 
 /***** HELPER MACROS *****/
 /// Wraps a [`write!`]-macro to return its error as a [`FileLoggerError`].
@@ -47,10 +59,24 @@ macro_rules! writeln_file {
     };
 }
 
+/// Calls the same method on whichever logger is chosen.
+macro_rules! dispatch {
+    ($this:expr, $logger:ident => $call:expr) => {
+        match $this {
+            AuditLogBackend::File($logger) => $call,
+            AuditLogBackend::Forwarding($logger) => $call,
+            #[cfg(feature = "sqlite")]
+            AuditLogBackend::Sqlite($logger) => $call,
+        }
+    };
+}
+
 /***** ERRORS *****/
 /// Defines errors originating from the [`FileLogger`].
 #[derive(Debug)]
 pub enum FileLoggerError {
+    /// Failed to lock the logfile.
+    FileLock { path: PathBuf, err: std::io::Error },
     /// Failed to create a new logfile.
     FileCreate { path: PathBuf, err: std::io::Error },
     /// Failed to open an existing logfile.
@@ -59,6 +85,8 @@ pub enum FileLoggerError {
     FileSeek { path: PathBuf, err: std::io::Error },
     /// Failed to flush the given logfile.
     FileShutdown { path: PathBuf, err: std::io::Error },
+    /// Failed to sync the given logfile to disk.
+    FileSync { path: PathBuf, err: std::io::Error },
     /// Failed to write to the logfile.
     FileWrite { path: PathBuf, err: std::io::Error },
     /// Failed to serialize a statement.
@@ -68,10 +96,12 @@ impl Display for FileLoggerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
         use FileLoggerError::*;
         match self {
+            FileLock { path, .. } => write!(f, "Failed to lock log file '{}'", path.display()),
             FileCreate { path, .. } => write!(f, "Failed to create new log file '{}'", path.display()),
             FileOpen { path, .. } => write!(f, "Failed to open existing log file '{}'", path.display()),
             FileSeek { path, .. } => write!(f, "Failed to seek in log file '{}'", path.display()),
             FileShutdown { path, .. } => write!(f, "Failed to flush log file '{}'", path.display()),
+            FileSync { path, .. } => write!(f, "Failed to sync log file '{}' to disk", path.display()),
             FileWrite { path, .. } => write!(f, "Failed to write to log file '{}'", path.display()),
             StatementSerialize { kind, .. } => write!(f, "Failed to serialize {kind}"),
         }
@@ -81,10 +111,12 @@ impl Error for FileLoggerError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         use FileLoggerError::*;
         match self {
+            FileLock { err, .. } => Some(err),
             FileCreate { err, .. } => Some(err),
             FileOpen { err, .. } => Some(err),
             FileSeek { err, .. } => Some(err),
             FileShutdown { err, .. } => Some(err),
+            FileSync { err, .. } => Some(err),
             FileWrite { err, .. } => Some(err),
             StatementSerialize { err, .. } => Some(err),
         }
@@ -92,6 +124,42 @@ impl Error for FileLoggerError {
 }
 
 /***** LIBRARY *****/
+/// An exclusive lock on an audit log file, which is released when dropped.
+///
+/// The [`FileLogger`] holds it while appending a statement, and the [`Anchorer`](crate::anchor::Anchorer) and
+/// [`Retainer`](crate::retention::Retainer) while reading or replacing the log and its anchors, such that no statement
+/// is lost when the log is rewritten. It is taken on a separate file next to the log (`<log>.lock`), because the log
+/// itself is replaced when rewritten. Since it is an OS-level file lock, it also holds across processes (e.g., between
+/// a running reasoner and `checker-client log retain`).
+#[derive(Debug)]
+pub struct LogLock {
+    /// The open lock file, which holds the lock for as long as it is open.
+    _file: std::fs::File,
+}
+impl LogLock {
+    /// Takes the lock on an audit log, waiting for whoever holds it now.
+    ///
+    /// # Arguments
+    /// - `log_path`: The path of the audit log to lock.
+    ///
+    /// # Returns
+    /// The LogLock, which holds the lock until it is dropped.
+    ///
+    /// # Errors
+    /// This function errors if the lock file could not be opened or locked.
+    pub async fn acquire(log_path: &Path) -> Result<Self, std::io::Error> {
+        let mut path: OsString = log_path.as_os_str().to_owned();
+        path.push(".lock");
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+            file.lock()?;
+            Ok(Self { _file: file })
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+}
+
 /// A mock version of the logger that simply ignores all logged statements.
 ///
 /// Just here for testing purposes.
@@ -114,6 +182,7 @@ impl AuditLogger for MockLogger {
         _reference: &str,
         _auth: &AuthContext,
         _policy: i64,
+        _policy_hashes: &[String],
         _state: &State,
         _workflow: &Workflow,
         _task: &str,
@@ -127,6 +196,7 @@ impl AuditLogger for MockLogger {
         _reference: &str,
         _auth: &AuthContext,
         _policy: i64,
+        _policy_hashes: &[String],
         _state: &State,
         _workflow: &Workflow,
         _data: &str,
@@ -141,6 +211,7 @@ impl AuditLogger for MockLogger {
         _reference: &str,
         _auth: &AuthContext,
         _policy: i64,
+        _policy_hashes: &[String],
         _state: &State,
         _workflow: &Workflow,
     ) -> Result<(), AuditLoggerError> {
@@ -148,11 +219,58 @@ impl AuditLogger for MockLogger {
         Ok(())
     }
 
+    async fn log_query_request(
+        &self,
+        _reference: &str,
+        _auth: &AuthContext,
+        _policy: i64,
+        _state: &State,
+        _query: &Value,
+    ) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_query_request");
+        Ok(())
+    }
+
     async fn log_verdict(&self, _reference: &str, _verdict: &Verdict) -> Result<(), AuditLoggerError> {
         println!("AUDIT LOG: log_verdict");
         Ok(())
     }
 
+    async fn log_request_timings(&self, _timings: &RequestTimings) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_request_timings");
+        Ok(())
+    }
+
+    async fn log_workflow_preprocessed(&self, _reference: &str, _stats: &InlineStats) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_workflow_preprocessed");
+        Ok(())
+    }
+
+    async fn log_reasoner_fallback(&self, _reference: &str, _behaviour: &str, _reason: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_reasoner_fallback");
+        Ok(())
+    }
+
+    async fn log_circuit_open(&self, _reference: &str, _behaviour: &str, _retry_after_ms: u64, _reason: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_circuit_open");
+        Ok(())
+    }
+
+    async fn log_verdict_transformed(&self, _reference: &str, _transformer: &str, _reason: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_verdict_transformed");
+        Ok(())
+    }
+
+    async fn log_prefilter_matched(&self, _reference: &str, _rule: &str, _effect: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_prefilter_matched");
+        Ok(())
+    }
+
+    async fn log_retrospective_evaluation(&self, _reference: &str, _as_of: &str, _state_snapshot: bool) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_retrospective_evaluation");
+        Ok(())
+    }
+
     async fn log_add_policy_request<C: ConnectorWithContext>(&self, _auth: &AuthContext, _policy: &Policy) -> Result<(), AuditLoggerError> {
         println!("AUDIT LOG: log_add_policy_request");
         Ok(())
@@ -168,18 +286,92 @@ impl AuditLogger for MockLogger {
         Ok(())
     }
 
+    async fn log_import_policies(&self, _auth: &AuthContext, _archive: &PolicyArchive) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_import_policies");
+        Ok(())
+    }
+
+    async fn log_lockdown(&self, _auth: &AuthContext, _engaged: bool, _reason: Option<&str>) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_lockdown");
+        Ok(())
+    }
+
+    async fn log_auth_failure(&self, _route: &str, _reason: &str, _detail: &str, _initiator_hint: Option<&str>) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_auth_failure");
+        Ok(())
+    }
+
+    async fn log_auth_success(&self, _auth: &AuthContext, _route: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_auth_success");
+        Ok(())
+    }
+
+    async fn log_owner_notification(
+        &self,
+        _reference: &str,
+        _dataset: &str,
+        _channel: &str,
+        _recipient: &str,
+        _error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_owner_notification");
+        Ok(())
+    }
+
+    async fn log_federated_verdict(
+        &self,
+        _reference: &str,
+        _domain: &str,
+        _remote_reference: Option<&str>,
+        _allowed: bool,
+        _error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_federated_verdict");
+        Ok(())
+    }
+
+    async fn log_policy_disclosure(&self, _auth: &AuthContext, _fields: &[String], _withheld: &[String]) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_policy_disclosure");
+        Ok(())
+    }
+
     async fn log_reasoner_context<C: ConnectorWithContext>(&self) -> Result<(), AuditLoggerError> {
         println!("AUDIT LOG: log_reasoner_context");
         Ok(())
     }
+
+    async fn log_effective_reasoner_context(&self, _context: &Value, _hash: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_effective_reasoner_context");
+        Ok(())
+    }
+
+    async fn flush(&self, _reference: &str) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: flush");
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl ReasonerConnectorAuditLogger for MockLogger {
-    async fn log_reasoner_response(&self, _reference: &str, _response: &str) -> Result<(), AuditLoggerError> {
+    async fn log_reasoner_response(&self, _reference: &str, _response: &str, _retries: u32) -> Result<(), AuditLoggerError> {
         println!("AUDIT LOG: log_reasoner_response");
         Ok(())
     }
+
+    async fn log_session_summary(&self, _summary: &SessionSummary) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_session_summary");
+        Ok(())
+    }
+
+    async fn log_shadow_evaluation(&self, _reference: &str, _primary: &Value, _shadow: &Value, _agree: bool) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_shadow_evaluation");
+        Ok(())
+    }
+
+    async fn log_payload_archived(&self, _payload: &ArchivedPayload) -> Result<(), AuditLoggerError> {
+        println!("AUDIT LOG: log_payload_archived");
+        Ok(())
+    }
 }
 
 /// A more serious version of a logger that logs to a file.
@@ -193,6 +385,11 @@ pub struct FileLogger {
 
     /// The path of the file to log to.
     path: PathBuf,
+
+    /// Redacts personal data from statements before they are written.
+    redactor: Redactor,
+    /// Generates the identifiers of statements.
+    ids:      Arc<dyn IdGenerator>,
 }
 impl FileLogger {
     /// Constructor for the FileLogger that initializes it pointing to the given file.
@@ -203,7 +400,35 @@ impl FileLogger {
     /// # Returns
     /// A new instance of self, ready for action.
     #[inline]
-    pub fn new(identifier: String, path: impl Into<PathBuf>) -> Self { Self { identifier, path: path.into() } }
+    pub fn new(identifier: String, path: impl Into<PathBuf>) -> Self {
+        Self { identifier, path: path.into(), redactor: Redactor::default(), ids: Arc::new(UuidV7Generator) }
+    }
+
+    /// Makes the FileLogger redact every statement before writing it.
+    ///
+    /// # Arguments
+    /// - `redactor`: The [`Redactor`] that pseudonymizes and/or drops personal data.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Sets how the identifiers of statements are generated.
+    ///
+    /// # Arguments
+    /// - `ids`: The [`IdGenerator`] to use. Defaults to a [`UuidV7Generator`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_id_generator(mut self, ids: impl 'static + IdGenerator) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
 
     /// Writes a log statement to the logging file.
     ///
@@ -213,6 +438,9 @@ impl FileLogger {
     /// # Errors
     /// This function errors if we failed to perform the logging completely (i.e., either write or flush).
     pub async fn log(&self, stmt: LogStatement<'_>) -> Result<(), FileLoggerError> {
+        // Step 0: Make sure nobody rewrites the log while we append to it
+        let _lock: LogLock = LogLock::acquire(&self.path).await.map_err(|err| FileLoggerError::FileLock { path: self.path.clone(), err })?;
+
         // Step 1: Open the log file
         let mut handle: File = if !self.path.exists() {
             debug!("Creating new log file at '{}'...", self.path.display());
@@ -237,12 +465,13 @@ impl FileLogger {
 
         // Write the message
         debug!("Writing {}-statement to logfile...", stmt.variant());
+        let stmt: LogStatement = self.redactor.redact(stmt);
         // Write who wrote it
         write_file!(self.path.clone(), &mut handle, "[{}]", self.identifier).await?;
         // Print the timestamp
         write_file!(self.path.clone(), &mut handle, "[{}]", chrono::Local::now().format("%Y-%m-%d %H:%M:%S")).await?;
         // Then write the logged message
-        match serde_json::to_string(&stmt) {
+        match serde_json::to_string(&stmt.versioned().with_id(self.ids.generate())) {
             Ok(message) => writeln_file!(self.path.clone(), &mut handle, " {message}").await?,
             Err(err) => return Err(FileLoggerError::StatementSerialize { kind: format!("{:?}", stmt.variant()), err }),
         }
@@ -257,6 +486,18 @@ impl FileLogger {
         // Done, a smashing success
         Ok(())
     }
+
+    /// Makes sure everything written to the logging file so far is on disk.
+    ///
+    /// # Errors
+    /// This function errors if we failed to open or sync the file.
+    pub async fn sync(&self) -> Result<(), FileLoggerError> {
+        let handle: File = match File::open(&self.path).await {
+            Ok(handle) => handle,
+            Err(err) => return Err(FileLoggerError::FileOpen { path: self.path.clone(), err }),
+        };
+        handle.sync_data().await.map_err(|err| FileLoggerError::FileSync { path: self.path.clone(), err })
+    }
 }
 #[async_trait::async_trait]
 impl AuditLogger for FileLogger {
@@ -265,6 +506,7 @@ impl AuditLogger for FileLogger {
         reference: &str,
         auth: &AuthContext,
         policy: i64,
+        policy_hashes: &[String],
         state: &State,
         workflow: &Workflow,
         task: &str,
@@ -272,8 +514,8 @@ impl AuditLogger for FileLogger {
         debug!("Handling request to log execute_task request");
 
         // Construct the full message that we want to log, then log it (simple as that)
-        let stmt: LogStatement = LogStatement::execute_task(reference, auth, policy, state, workflow, task);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        let stmt: LogStatement = LogStatement::execute_task(reference, auth, policy, policy_hashes, state, workflow, task);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_data_access_request(
@@ -281,6 +523,7 @@ impl AuditLogger for FileLogger {
         reference: &str,
         auth: &AuthContext,
         policy: i64,
+        policy_hashes: &[String],
         state: &State,
         workflow: &Workflow,
         data: &str,
@@ -289,8 +532,8 @@ impl AuditLogger for FileLogger {
         debug!("Handling request to log data_access request");
 
         // Construct the full message that we want to log, then log it (simple as that)
-        let stmt = LogStatement::asset_access(reference, auth, policy, state, workflow, data, task);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        let stmt = LogStatement::asset_access(reference, auth, policy, policy_hashes, state, workflow, data, task);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_validate_workflow_request(
@@ -298,14 +541,30 @@ impl AuditLogger for FileLogger {
         reference: &str,
         auth: &AuthContext,
         policy: i64,
+        policy_hashes: &[String],
         state: &State,
         workflow: &Workflow,
     ) -> Result<(), AuditLoggerError> {
         debug!("Handling request to log workflow_validate request");
 
         // Construct the full message that we want to log, then log it (simple as that)
-        let stmt = LogStatement::workflow_validate(reference, auth, policy, state, workflow);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        let stmt = LogStatement::workflow_validate(reference, auth, policy, policy_hashes, state, workflow);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_query_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        state: &State,
+        query: &Value,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log query request");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::query(reference, auth, policy, state, query);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_verdict(&self, reference: &str, verdict: &Verdict) -> Result<(), AuditLoggerError> {
@@ -313,7 +572,63 @@ impl AuditLogger for FileLogger {
 
         // Construct the full message that we want to log, then log it (simple as that)
         let stmt = LogStatement::reasoner_verdict(reference, verdict);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_request_timings(&self, timings: &RequestTimings) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log request timings");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::request_timings(timings);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_workflow_preprocessed(&self, reference: &str, stats: &InlineStats) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log workflow preprocessing");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::workflow_preprocessed(reference, stats);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_reasoner_fallback(&self, reference: &str, behaviour: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log reasoner fallback");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::reasoner_fallback(reference, behaviour, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_circuit_open(&self, reference: &str, behaviour: &str, retry_after_ms: u64, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log open circuit breaker");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::circuit_open(reference, behaviour, retry_after_ms, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_verdict_transformed(&self, reference: &str, transformer: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log verdict transformation");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::verdict_transformed(reference, transformer, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_prefilter_matched(&self, reference: &str, rule: &str, effect: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log prefilter match");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::prefilter_matched(reference, rule, effect);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_retrospective_evaluation(&self, reference: &str, as_of: &str, state_snapshot: bool) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log retrospective evaluation");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::retrospective_evaluation(reference, as_of, state_snapshot);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_reasoner_context<C: ConnectorWithContext>(&self) -> Result<(), AuditLoggerError> {
@@ -321,7 +636,15 @@ impl AuditLogger for FileLogger {
 
         // Construct the full message that we want to log, then log it (simple as that)
         let stmt: LogStatement = LogStatement::reasoner_context::<C>();
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_effective_reasoner_context(&self, context: &Value, hash: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log effective reasoner connector context");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt: LogStatement = LogStatement::effective_reasoner_context(context, hash);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_add_policy_request<C: ConnectorWithContext>(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
@@ -329,7 +652,7 @@ impl AuditLogger for FileLogger {
 
         // Construct the full message that we want to log, then log it (simple as that)
         let stmt: LogStatement = LogStatement::policy_add::<C>(auth, policy);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_set_active_version_policy(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
@@ -337,7 +660,7 @@ impl AuditLogger for FileLogger {
 
         // Construct the full message that we want to log, then log it (simple as that)
         let stmt = LogStatement::policy_activate(auth, policy);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 
     async fn log_deactivate_policy(&self, auth: &AuthContext) -> Result<(), AuditLoggerError> {
@@ -345,17 +668,295 @@ impl AuditLogger for FileLogger {
 
         // Construct the full message that we want to log, then log it (simple as that)
         let stmt = LogStatement::policy_deactivate(auth);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_import_policies(&self, auth: &AuthContext, archive: &PolicyArchive) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy import");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::policy_import(auth, archive);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_lockdown(&self, auth: &AuthContext, engaged: bool, reason: Option<&str>) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log lockdown");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::lockdown(auth, engaged, reason);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_auth_failure(&self, route: &str, reason: &str, detail: &str, initiator_hint: Option<&str>) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log authentication failure");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::auth_failure(route, reason, detail, initiator_hint);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_auth_success(&self, auth: &AuthContext, route: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log authentication success");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::auth_success(auth, route);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_owner_notification(
+        &self,
+        reference: &str,
+        dataset: &str,
+        channel: &str,
+        recipient: &str,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log owner notification");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::owner_notification(reference, dataset, channel, recipient, error);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_federated_verdict(
+        &self,
+        reference: &str,
+        domain: &str,
+        remote_reference: Option<&str>,
+        allowed: bool,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log federated verdict");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::federated_verdict(reference, domain, remote_reference, allowed, error);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_policy_disclosure(&self, auth: &AuthContext, fields: &[String], withheld: &[String]) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log policy disclosure");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::policy_disclosure(auth, fields, withheld);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn flush(&self, reference: &str) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to flush log for request '{reference}'");
+        self.sync().await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
     }
 }
 
 #[async_trait::async_trait]
 impl ReasonerConnectorAuditLogger for FileLogger {
-    async fn log_reasoner_response(&self, reference: &str, response: &str) -> Result<(), AuditLoggerError> {
+    async fn log_reasoner_response(&self, reference: &str, response: &str, retries: u32) -> Result<(), AuditLoggerError> {
         debug!("Handling request to log reasoner response");
 
         // Construct the full message that we want to log, then log it (simple as that)
-        let stmt = LogStatement::reasoner_response(reference, response);
-        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(format!("{}", err.trace())))
+        let stmt = LogStatement::reasoner_response(reference, response, retries);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_session_summary(&self, summary: &SessionSummary) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log session summary");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::session_summary(summary);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_shadow_evaluation(&self, reference: &str, primary: &Value, shadow: &Value, agree: bool) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log shadow evaluation");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::shadow_evaluation(reference, primary, shadow, agree);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+
+    async fn log_payload_archived(&self, payload: &ArchivedPayload) -> Result<(), AuditLoggerError> {
+        debug!("Handling request to log archived payload");
+
+        // Construct the full message that we want to log, then log it (simple as that)
+        let stmt = LogStatement::payload_archived(payload);
+        self.log(stmt).await.map_err(|err| AuditLoggerError::CouldNotDeliver(Box::new(err)))
+    }
+}
+
+/// Either of the audit loggers that can be chosen when starting the reasoner.
+#[derive(Clone)]
+pub enum AuditLogBackend {
+    /// Writes statements to a local file.
+    File(FileLogger),
+    /// Ships statements to a central log store.
+    Forwarding(ForwardingLogger),
+    /// Appends statements to a table in a SQLite database.
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteAuditLogger),
+}
+#[async_trait::async_trait]
+impl AuditLogger for AuditLogBackend {
+    async fn log_exec_task_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+        task: &str,
+    ) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_exec_task_request(reference, auth, policy, policy_hashes, state, workflow, task).await)
+    }
+
+    async fn log_data_access_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+        data: &str,
+        task: &Option<String>,
+    ) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_data_access_request(reference, auth, policy, policy_hashes, state, workflow, data, task).await)
+    }
+
+    async fn log_validate_workflow_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        policy_hashes: &[String],
+        state: &State,
+        workflow: &Workflow,
+    ) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_validate_workflow_request(reference, auth, policy, policy_hashes, state, workflow).await)
+    }
+
+    async fn log_query_request(
+        &self,
+        reference: &str,
+        auth: &AuthContext,
+        policy: i64,
+        state: &State,
+        query: &Value,
+    ) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_query_request(reference, auth, policy, state, query).await)
+    }
+
+    async fn log_verdict(&self, reference: &str, verdict: &Verdict) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_verdict(reference, verdict).await)
+    }
+
+    async fn log_request_timings(&self, timings: &RequestTimings) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_request_timings(timings).await)
+    }
+
+    async fn log_workflow_preprocessed(&self, reference: &str, stats: &InlineStats) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_workflow_preprocessed(reference, stats).await)
+    }
+
+    async fn log_reasoner_fallback(&self, reference: &str, behaviour: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_reasoner_fallback(reference, behaviour, reason).await)
+    }
+
+    async fn log_circuit_open(&self, reference: &str, behaviour: &str, retry_after_ms: u64, reason: &str) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_circuit_open(reference, behaviour, retry_after_ms, reason).await)
+    }
+
+    async fn log_verdict_transformed(&self, reference: &str, transformer: &str, reason: &str) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_verdict_transformed(reference, transformer, reason).await)
+    }
+
+    async fn log_prefilter_matched(&self, reference: &str, rule: &str, effect: &str) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_prefilter_matched(reference, rule, effect).await)
+    }
+
+    async fn log_retrospective_evaluation(&self, reference: &str, as_of: &str, state_snapshot: bool) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_retrospective_evaluation(reference, as_of, state_snapshot).await)
+    }
+
+    async fn log_reasoner_context<C: ConnectorWithContext>(&self) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_reasoner_context::<C>().await)
+    }
+
+    async fn log_effective_reasoner_context(&self, context: &Value, hash: &str) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_effective_reasoner_context(context, hash).await)
+    }
+
+    async fn log_add_policy_request<C: ConnectorWithContext>(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_add_policy_request::<C>(auth, policy).await)
+    }
+
+    async fn log_set_active_version_policy(&self, auth: &AuthContext, policy: &Policy) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_set_active_version_policy(auth, policy).await)
+    }
+
+    async fn log_deactivate_policy(&self, auth: &AuthContext) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_deactivate_policy(auth).await)
+    }
+
+    async fn log_import_policies(&self, auth: &AuthContext, archive: &PolicyArchive) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_import_policies(auth, archive).await)
+    }
+
+    async fn log_lockdown(&self, auth: &AuthContext, engaged: bool, reason: Option<&str>) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_lockdown(auth, engaged, reason).await)
+    }
+
+    async fn log_auth_failure(&self, route: &str, reason: &str, detail: &str, initiator_hint: Option<&str>) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_auth_failure(route, reason, detail, initiator_hint).await)
+    }
+
+    async fn log_auth_success(&self, auth: &AuthContext, route: &str) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_auth_success(auth, route).await)
+    }
+
+    async fn log_owner_notification(
+        &self,
+        reference: &str,
+        dataset: &str,
+        channel: &str,
+        recipient: &str,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_owner_notification(reference, dataset, channel, recipient, error).await)
+    }
+
+    async fn log_federated_verdict(
+        &self,
+        reference: &str,
+        domain: &str,
+        remote_reference: Option<&str>,
+        allowed: bool,
+        error: Option<&str>,
+    ) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_federated_verdict(reference, domain, remote_reference, allowed, error).await)
+    }
+
+    async fn log_policy_disclosure(&self, auth: &AuthContext, fields: &[String], withheld: &[String]) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_policy_disclosure(auth, fields, withheld).await)
+    }
+
+    async fn flush(&self, reference: &str) -> Result<(), AuditLoggerError> { dispatch!(self, logger => logger.flush(reference).await) }
+}
+
+#[async_trait::async_trait]
+impl ReasonerConnectorAuditLogger for AuditLogBackend {
+    async fn log_reasoner_response(&self, reference: &str, response: &str, retries: u32) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_reasoner_response(reference, response, retries).await)
+    }
+
+    async fn log_session_summary(&self, summary: &SessionSummary) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_session_summary(summary).await)
+    }
+
+    async fn log_shadow_evaluation(&self, reference: &str, primary: &Value, shadow: &Value, agree: bool) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_shadow_evaluation(reference, primary, shadow, agree).await)
+    }
+
+    async fn log_payload_archived(&self, payload: &ArchivedPayload) -> Result<(), AuditLoggerError> {
+        dispatch!(self, logger => logger.log_payload_archived(payload).await)
     }
 }
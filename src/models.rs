@@ -1,7 +1,7 @@
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
-use crate::schema::{active_version, policies};
+use crate::schema::{active_version, audit_log, duties, policies, policy_contents, usage_counters};
 
 #[derive(Queryable, Insertable, Selectable)]
 #[diesel(table_name = policies)]
@@ -13,6 +13,22 @@ pub struct SqlitePolicy {
     pub created_at: i64,
     pub content: String,
     pub reasoner_connector_context: String,
+    pub tags: String,
+    pub jurisdiction: Option<String>,
+    pub department: Option<String>,
+    /// The hashes of the contents of this version (see [`SqlitePolicyContent`]), as a JSON array. Versions stored before
+    /// contents were deduplicated have none, but keep their contents in `content` instead.
+    pub content_hashes: String,
+    /// The globally unique identifier of this version. Versions stored before these existed have none.
+    pub external_id: Option<String>,
+}
+
+/// A single [`PolicyContent`](::policy::PolicyContent), stored once no matter how many versions refer to it.
+#[derive(Queryable, Insertable, Selectable)]
+#[diesel(table_name = policy_contents)]
+pub struct SqlitePolicyContent {
+    pub hash:    String,
+    pub content: String,
 }
 
 #[derive(Queryable, Insertable, Selectable)]
@@ -25,6 +41,50 @@ pub struct SqliteActiveVersion {
     pub deactivated_by: Option<String>,
 }
 
+#[derive(Queryable, Insertable, Selectable)]
+#[diesel(table_name = duties)]
+pub struct SqliteDuty {
+    pub id: String,
+    pub verdict_reference: String,
+    pub holder: String,
+    pub duty: String,
+    pub created_at: NaiveDateTime,
+    pub fulfilled_at: Option<NaiveDateTime>,
+    pub report: Option<String>,
+}
+
+/// A single statement in the audit log, as read back from it.
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = audit_log)]
+pub struct SqliteAuditLogEntry {
+    pub id: i32,
+    pub identifier: String,
+    pub logged_at: NaiveDateTime,
+    pub reference: Option<String>,
+    pub kind: String,
+    pub statement: String,
+}
+
+/// A single statement to append to the audit log (its `id` is assigned by the database).
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewSqliteAuditLogEntry {
+    pub identifier: String,
+    pub logged_at: NaiveDateTime,
+    pub reference: Option<String>,
+    pub kind: String,
+    pub statement: String,
+}
+
+#[derive(Queryable, Insertable, Selectable)]
+#[diesel(table_name = usage_counters)]
+pub struct SqliteUsageCounter {
+    pub subject: String,
+    pub counter: String,
+    pub period:  String,
+    pub value:   i64,
+}
+
 impl SqliteActiveVersion {
     pub fn new(version: i64, activated_by: String) -> Self {
         Self { version, activated_by, activated_on: Utc::now().naive_local(), deactivated_by: None, deactivated_on: None }
@@ -0,0 +1,449 @@
+//! Moves audit log segments that outlived their retention to cold storage.
+//!
+//! How long statements must stay in the live audit log is configured per statement kind in a [`RetentionPolicy`].
+//! Since entries are anchored per segment (see [`anchor`](crate::anchor)), entries cannot be removed one by one without
+//! breaking the root of their segment. Instead, a [`Retainer`] moves whole segments: once every entry in a segment has
+//! outlived the window of its kind, the segment is written to [`ColdStorage`] as an [`ArchivedSegment`] (together with
+//! its anchor and a link to the segment archived before it) and removed from the live log. The anchor of the last
+//! archived segment stays in the anchors file as the head of the live segments.
+//!
+//! Only a prefix of the log is ever archived, such that [`verify_archive()`] can walk back from the head through the
+//! archive and check that the archived and live segments together are unbroken.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use audit_logger::merkle::{ArchivedSegment, AuditAnchor, ContinuityError, MerkleTree, SegmentLink, leaf_hash, live_offset, verify_continuity};
+use chrono::{Duration, NaiveDateTime};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use tokio::fs as tfs;
+use tokio::io::AsyncWriteExt as _;
+
+use crate::anchor::{AnchorError, Anchorer};
+use crate::logger::LogLock;
+
+/***** ERRORS *****/
+/// Defines errors originating from moving audit log segments to cold storage.
+#[derive(Debug)]
+pub enum RetentionError {
+    /// Failed to read the retention policy.
+    PolicyRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the retention policy.
+    PolicyParse { path: PathBuf, err: serde_yaml::Error },
+    /// Failed to lock the audit log.
+    LogLock { path: PathBuf, err: std::io::Error },
+    /// Failed to read the audit log.
+    LogRead { path: PathBuf, err: std::io::Error },
+    /// Failed to write the audit log.
+    LogWrite { path: PathBuf, err: std::io::Error },
+    /// Failed to read the anchors of the audit log.
+    Anchors { err: AnchorError },
+    /// Failed to write the anchors of the audit log.
+    AnchorsWrite { path: PathBuf, err: std::io::Error },
+    /// The archived entries were no longer at the start of the audit log when it was about to be rewritten.
+    LogChanged { path: PathBuf },
+    /// The entries of a live segment do not match its anchor.
+    SegmentMismatch { first: usize, size: usize },
+    /// Failed to serialize a segment or an anchor.
+    Serialize { err: serde_json::Error },
+    /// Failed to write a segment to a directory.
+    StorageWrite { path: PathBuf, err: std::io::Error },
+    /// Failed to upload a segment to an HTTP endpoint.
+    StorageUpload { url: String, err: reqwest::Error },
+    /// Failed to read an archived segment.
+    SegmentRead { location: String, err: Box<dyn 'static + Error + Send + Sync> },
+    /// Failed to parse an archived segment.
+    SegmentParse { location: String, err: serde_json::Error },
+    /// The archived and live segments are not continuous.
+    Continuity { err: ContinuityError },
+}
+impl Display for RetentionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use RetentionError::*;
+        match self {
+            PolicyRead { path, .. } => write!(f, "Failed to read retention policy '{}'", path.display()),
+            PolicyParse { path, .. } => write!(f, "Failed to parse retention policy '{}'", path.display()),
+            LogLock { path, .. } => write!(f, "Failed to lock audit log '{}'", path.display()),
+            LogRead { path, .. } => write!(f, "Failed to read audit log '{}'", path.display()),
+            LogWrite { path, .. } => write!(f, "Failed to write audit log '{}'", path.display()),
+            Anchors { .. } => write!(f, "Failed to read audit log anchors"),
+            AnchorsWrite { path, .. } => write!(f, "Failed to write anchors file '{}'", path.display()),
+            LogChanged { path } => {
+                write!(f, "Audit log '{}' changed while its segments were archived; leaving it as-is", path.display())
+            },
+            SegmentMismatch { first, size } => {
+                write!(f, "Entries {}-{} of the audit log do not match their anchor; refusing to archive them", first + 1, first + size)
+            },
+            Serialize { .. } => write!(f, "Failed to serialize audit log segment"),
+            StorageWrite { path, .. } => write!(f, "Failed to write audit log segment to '{}'", path.display()),
+            StorageUpload { url, .. } => write!(f, "Failed to upload audit log segment to '{url}'"),
+            SegmentRead { location, .. } => write!(f, "Failed to read archived audit log segment '{location}'"),
+            SegmentParse { location, .. } => write!(f, "Failed to parse archived audit log segment '{location}'"),
+            Continuity { .. } => write!(f, "Archived and live audit log segments are not continuous"),
+        }
+    }
+}
+impl Error for RetentionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use RetentionError::*;
+        match self {
+            PolicyRead { err, .. } => Some(err),
+            PolicyParse { err, .. } => Some(err),
+            LogLock { err, .. } => Some(err),
+            LogRead { err, .. } => Some(err),
+            LogWrite { err, .. } => Some(err),
+            Anchors { err } => Some(err),
+            AnchorsWrite { err, .. } => Some(err),
+            LogChanged { .. } => None,
+            SegmentMismatch { .. } => None,
+            Serialize { err } => Some(err),
+            StorageWrite { err, .. } => Some(err),
+            StorageUpload { err, .. } => Some(err),
+            SegmentRead { err, .. } => Some(&**err),
+            SegmentParse { err, .. } => Some(err),
+            Continuity { err } => Some(err),
+        }
+    }
+}
+
+/***** HELPERS *****/
+/// Reads the complete lines of the audit log, i.e., without the one the logger may be halfway writing.
+///
+/// # Returns
+/// The complete lines without their trailing newlines, and whatever follows them.
+async fn read_entries(path: &Path) -> Result<(Vec<String>, String), RetentionError> {
+    let raw: String = match tfs::read_to_string(path).await {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(RetentionError::LogRead { path: path.into(), err }),
+    };
+    let complete: usize = raw.rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+    Ok((raw[..complete].lines().map(String::from).collect(), raw[complete..].into()))
+}
+
+/// Replaces the contents of a file at once, by writing them to a temporary file next to it and renaming that over it.
+///
+/// This way, readers (and a crash halfway) see either the old or the new contents, but never a mix of both.
+async fn write_atomically(path: &Path, contents: String) -> Result<(), std::io::Error> {
+    let mut tmp: std::ffi::OsString = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp: PathBuf = tmp.into();
+    let mut handle = tfs::File::create(&tmp).await?;
+    handle.write_all(contents.as_bytes()).await?;
+    handle.sync_all().await?;
+    drop(handle);
+    tfs::rename(&tmp, path).await
+}
+
+/// Reads the kind of an audit log entry and when it was logged.
+///
+/// # Arguments
+/// - `entry`: The entry as written by the `FileLogger`, i.e., `[<identifier>][<timestamp>] <statement>`.
+///
+/// # Returns
+/// The kind of the statement and its (local) timestamp, or [`None`] if the line is not the start of an entry.
+fn entry_kind(entry: &str) -> Option<(String, NaiveDateTime)> {
+    let start: usize = entry.find("] {")?;
+    let logged_at: &str = &entry[entry[..start].rfind('[')? + 1..start];
+    let logged_at: NaiveDateTime = NaiveDateTime::parse_from_str(logged_at, "%Y-%m-%d %H:%M:%S").ok()?;
+    let stmt: serde_json::Value = serde_json::from_str(&entry[start + 2..]).ok()?;
+    Some((stmt.get("kind")?.as_str()?.into(), logged_at))
+}
+
+/***** AUXILLARY *****/
+/// Decides how long statements must stay in the live audit log, per statement kind.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RetentionPolicy {
+    /// The number of days statements of kinds not in `kinds` are kept, or [`None`] to keep them forever.
+    #[serde(default)]
+    pub default_days: Option<u64>,
+    /// The number of days statements are kept, by kind (e.g., `AUTH-SUCCESS`).
+    #[serde(default)]
+    pub kinds: BTreeMap<String, u64>,
+}
+impl RetentionPolicy {
+    /// Reads a RetentionPolicy from a YAML file.
+    ///
+    /// # Errors
+    /// This function errors if the file could not be read or parsed.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, RetentionError> {
+        let path: &Path = path.as_ref();
+        let raw: String = std::fs::read_to_string(path).map_err(|err| RetentionError::PolicyRead { path: path.into(), err })?;
+        serde_yaml::from_str(&raw).map_err(|err| RetentionError::PolicyParse { path: path.into(), err })
+    }
+
+    /// Returns how long statements of the given kind must be kept, or [`None`] if forever (or longer than we can count).
+    pub fn window(&self, kind: &str) -> Option<Duration> {
+        self.kinds.get(kind).copied().or(self.default_days).and_then(|days| Duration::try_days(i64::try_from(days).ok()?))
+    }
+
+    /// Returns whether every entry in a segment outlived its window.
+    ///
+    /// # Arguments
+    /// - `segment`: The lines of the segment. Lines that do not start an entry belong to the entry before them.
+    /// - `now`: The (local) time to compare against.
+    pub fn expired(&self, segment: &[impl AsRef<str>], now: NaiveDateTime) -> bool {
+        segment.iter().filter_map(|line| entry_kind(line.as_ref())).all(|(kind, logged_at)| {
+            self.window(&kind).and_then(|window| logged_at.checked_add_signed(window)).map(|until| until < now).unwrap_or(false)
+        })
+    }
+}
+
+/// Defines where archived segments are written to.
+#[derive(Clone, Debug)]
+pub enum ColdStorage {
+    /// Writes every segment as a file in a directory (e.g., a mounted bucket).
+    Dir(PathBuf),
+    /// PUTs every segment to a URL under a base URL.
+    ///
+    /// The requests are plain, unsigned HTTP `PUT`s, so the endpoint must accept them as such (e.g., a gateway in front of
+    /// an object store, or a bucket that allows anonymous uploads). Use [`ColdStorage::Dir`] with a mounted bucket to
+    /// archive to storage that requires signed requests, like most S3 buckets.
+    Http(String),
+}
+impl ColdStorage {
+    /// Writes a segment to the storage.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the segment in the storage.
+    /// - `segment`: The [`ArchivedSegment`] to write.
+    ///
+    /// # Returns
+    /// Where the segment was written to, i.e., a path or a URL.
+    ///
+    /// # Errors
+    /// This function errors if the segment could not be serialized or written.
+    pub async fn put(&self, name: &str, segment: &ArchivedSegment) -> Result<String, RetentionError> {
+        let raw: String = serde_json::to_string(segment).map_err(|err| RetentionError::Serialize { err })?;
+        match self {
+            Self::Dir(dir) => {
+                let path: PathBuf = dir.join(name);
+                tfs::create_dir_all(dir).await.map_err(|err| RetentionError::StorageWrite { path: dir.clone(), err })?;
+                write_atomically(&path, raw).await.map_err(|err| RetentionError::StorageWrite { path: path.clone(), err })?;
+                Ok(path.display().to_string())
+            },
+            Self::Http(base) => {
+                let url: String = format!("{}/{name}", base.trim_end_matches('/'));
+                reqwest::Client::new()
+                    .put(&url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(raw)
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(|err| RetentionError::StorageUpload { url: url.clone(), err })?;
+                Ok(url)
+            },
+        }
+    }
+
+    /// Reads an archived segment back.
+    ///
+    /// # Arguments
+    /// - `location`: Where the segment was written to, as returned by [`ColdStorage::put()`].
+    ///
+    /// # Errors
+    /// This function errors if the segment could not be read or parsed.
+    pub async fn fetch(location: &str) -> Result<ArchivedSegment, RetentionError> {
+        let raw: String = if location.starts_with("http://") || location.starts_with("https://") {
+            let res = reqwest::get(location).await.and_then(|res| res.error_for_status());
+            match res {
+                Ok(res) => res.text().await.map_err(|err| RetentionError::SegmentRead { location: location.into(), err: Box::new(err) })?,
+                Err(err) => return Err(RetentionError::SegmentRead { location: location.into(), err: Box::new(err) }),
+            }
+        } else {
+            tfs::read_to_string(location).await.map_err(|err| RetentionError::SegmentRead { location: location.into(), err: Box::new(err) })?
+        };
+        serde_json::from_str(&raw).map_err(|err| RetentionError::SegmentParse { location: location.into(), err })
+    }
+}
+impl Display for ColdStorage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        match self {
+            Self::Dir(path) => write!(f, "file:{}", path.display()),
+            Self::Http(url) => write!(f, "{url}"),
+        }
+    }
+}
+impl FromStr for ColdStorage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Http(s.into()))
+        } else if let Some(path) = s.strip_prefix("file:") {
+            Ok(Self::Dir(path.into()))
+        } else {
+            Err(format!("Unknown cold storage '{s}' (expected 'file:<DIR>' or an 'http(s)://' URL)"))
+        }
+    }
+}
+
+/***** LIBRARY *****/
+/// Moves the segments of the audit log that outlived their retention to cold storage.
+///
+/// Expired segments are uploaded without blocking the logger. Only rewriting the log and its anchors happens with the log
+/// locked (see [`LogLock`]), so statements appended meanwhile are kept. Both files are replaced atomically.
+#[derive(Clone, Debug)]
+pub struct Retainer {
+    /// The path of the audit log.
+    log_path: PathBuf,
+    /// The path of the anchors recorded for the audit log.
+    anchors_path: PathBuf,
+    /// How long statements must stay in the live log.
+    policy: RetentionPolicy,
+    /// Where to move expired segments to.
+    storage: ColdStorage,
+}
+
+impl Retainer {
+    /// Constructor for the Retainer.
+    ///
+    /// # Arguments
+    /// - `log_path`: The path of the audit log.
+    /// - `anchors_path`: The path of the anchors recorded for the audit log.
+    /// - `policy`: The [`RetentionPolicy`] that decides how long statements must stay in the live log.
+    /// - `storage`: The [`ColdStorage`] to move expired segments to.
+    ///
+    /// # Returns
+    /// A new Retainer.
+    #[inline]
+    pub fn new(log_path: impl Into<PathBuf>, anchors_path: impl Into<PathBuf>, policy: RetentionPolicy, storage: ColdStorage) -> Self {
+        Self { log_path: log_path.into(), anchors_path: anchors_path.into(), policy, storage }
+    }
+
+    /// Takes the [`LogLock`] on the audit log.
+    #[inline]
+    async fn lock(&self) -> Result<LogLock, RetentionError> {
+        LogLock::acquire(&self.log_path).await.map_err(|err| RetentionError::LogLock { path: self.log_path.clone(), err })
+    }
+
+    /// Moves every expired segment at the start of the live log to cold storage.
+    ///
+    /// Segments are only moved in order, so a segment that has not expired keeps every segment after it in the live log.
+    /// Entries that are not anchored yet are never moved.
+    ///
+    /// # Arguments
+    /// - `now`: The (local) time to decide expiry against.
+    ///
+    /// # Returns
+    /// The [`AuditAnchor`]s of the segments moved, with where they were moved to.
+    ///
+    /// # Errors
+    /// This function errors if the log or its anchors could not be read or written, if a segment does not match its
+    /// anchor, or if a segment could not be written to cold storage. Segments written before the error are moved.
+    pub async fn retain(&self, now: NaiveDateTime) -> Result<Vec<AuditAnchor>, RetentionError> {
+        // Take the log and its anchors as they are now; whatever is appended later is not ours to archive anyway
+        let lock: LogLock = self.lock().await?;
+        let (entries, _): (Vec<String>, String) = read_entries(&self.log_path).await?;
+        let anchors: Vec<AuditAnchor> =
+            Anchorer::new(&self.log_path, &self.anchors_path).anchors().await.map_err(|err| RetentionError::Anchors { err })?;
+        drop(lock);
+        let offset: usize = live_offset(&anchors);
+        let mut head: Option<AuditAnchor> = anchors.iter().rfind(|anchor| anchor.archived.is_some()).cloned();
+        let live: Vec<&AuditAnchor> = anchors.iter().filter(|anchor| anchor.archived.is_none()).collect();
+
+        // Move the expired segments, oldest first
+        let mut moved: Vec<AuditAnchor> = Vec::new();
+        let mut res: Result<(), RetentionError> = Ok(());
+        for anchor in &live {
+            let Some(segment) = anchor.first.checked_sub(offset).and_then(|start| entries.get(start..start + anchor.size)) else {
+                res = Err(RetentionError::SegmentMismatch { first: anchor.first, size: anchor.size });
+                break;
+            };
+            let root: String = hex::encode(MerkleTree::new(segment.iter().map(|entry| leaf_hash(entry.as_bytes())).collect()).root());
+            if !root.eq_ignore_ascii_case(&anchor.root) {
+                res = Err(RetentionError::SegmentMismatch { first: anchor.first, size: anchor.size });
+                break;
+            }
+            if !self.policy.expired(segment, now) {
+                debug!("Segment {}-{} has not expired yet; keeping it and everything after it", anchor.first + 1, anchor.end());
+                break;
+            }
+
+            let archived = ArchivedSegment {
+                anchor:   (*anchor).clone(),
+                previous: head.as_ref().and_then(|head| head.archived.clone().map(|location| SegmentLink { root: head.root.clone(), location })),
+                entries:  segment.to_vec(),
+            };
+            let location: String = match self.storage.put(&format!("segment-{}-{}.json", anchor.first + 1, anchor.end()), &archived).await {
+                Ok(location) => location,
+                Err(err) => {
+                    res = Err(err);
+                    break;
+                },
+            };
+            info!("Moved audit log segment {}-{} to '{location}'", anchor.first + 1, anchor.end());
+            let mut anchor: AuditAnchor = (*anchor).clone();
+            anchor.archived = Some(location);
+            moved.push(anchor.clone());
+            head = Some(anchor);
+        }
+        if moved.is_empty() {
+            return res.map(|_| moved);
+        }
+
+        // Rewrite with the log locked, re-reading both files to keep what the logger and the anchorer appended meanwhile
+        let _lock: LogLock = self.lock().await?;
+        let (entries_now, partial): (Vec<String>, String) = read_entries(&self.log_path).await?;
+        let anchors_now: Vec<AuditAnchor> =
+            Anchorer::new(&self.log_path, &self.anchors_path).anchors().await.map_err(|err| RetentionError::Anchors { err })?;
+        let end: usize = moved.last().map(AuditAnchor::end).unwrap_or(offset);
+        let kept: usize = end - offset;
+        if live_offset(&anchors_now) != offset || entries_now.get(..kept) != entries.get(..kept) {
+            return Err(RetentionError::LogChanged { path: self.log_path.clone() });
+        }
+
+        // Keep only the head of the archive next to the live segments, then drop the moved entries from the log
+        let mut raw_anchors: String = String::new();
+        for anchor in head.iter().chain(anchors_now.iter().filter(|anchor| anchor.archived.is_none() && anchor.first >= end)) {
+            raw_anchors.push_str(&serde_json::to_string(anchor).map_err(|err| RetentionError::Serialize { err })?);
+            raw_anchors.push('\n');
+        }
+        write_atomically(&self.anchors_path, raw_anchors)
+            .await
+            .map_err(|err| RetentionError::AnchorsWrite { path: self.anchors_path.clone(), err })?;
+        let mut log: String = entries_now[kept..].iter().map(|entry| format!("{entry}\n")).collect();
+        log.push_str(&partial);
+        write_atomically(&self.log_path, log).await.map_err(|err| RetentionError::LogWrite { path: self.log_path.clone(), err })?;
+        res.map(|_| moved)
+    }
+}
+
+/// Checks that the archived segments of the audit log and its live segments together are unbroken.
+///
+/// # Arguments
+/// - `log_path`: The path of the (live) audit log.
+/// - `anchors_path`: The path of the anchors recorded for the audit log.
+///
+/// # Returns
+/// The number of archived segments and the number of segments checked in total.
+///
+/// # Errors
+/// This function errors if the log, its anchors or any archived segment could not be read, or if they are not
+/// continuous (see [`verify_continuity()`]).
+pub async fn verify_archive(log_path: impl AsRef<Path>, anchors_path: impl AsRef<Path>) -> Result<(usize, usize), RetentionError> {
+    let (entries, _): (Vec<String>, String) = read_entries(log_path.as_ref()).await?;
+    let anchors: Vec<AuditAnchor> =
+        Anchorer::new(log_path.as_ref(), anchors_path.as_ref()).anchors().await.map_err(|err| RetentionError::Anchors { err })?;
+
+    // Walk back from the head through the archive
+    let mut archived: Vec<ArchivedSegment> = Vec::new();
+    let mut next: Option<String> = anchors.iter().rfind(|anchor| anchor.archived.is_some()).and_then(|head| head.archived.clone());
+    while let Some(location) = next {
+        debug!("Fetching archived audit log segment '{location}'...");
+        let segment: ArchivedSegment = ColdStorage::fetch(&location).await?;
+        // Links only ever point back; anything else is left for the continuity check to report
+        let backwards: bool = archived.last().map(|last: &ArchivedSegment| segment.anchor.first < last.anchor.first).unwrap_or(true);
+        next = segment.previous.as_ref().filter(|_| backwards).map(|link| link.location.clone());
+        archived.push(segment);
+    }
+    archived.reverse();
+
+    let checked: usize = verify_continuity(&archived, &anchors, &entries).map_err(|err| RetentionError::Continuity { err })?;
+    Ok((archived.len(), checked))
+}
@@ -10,6 +10,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        identifier -> Text,
+        logged_at -> Timestamp,
+        reference -> Nullable<Text>,
+        kind -> Text,
+        statement -> Text,
+    }
+}
+
+diesel::table! {
+    duties (id) {
+        id -> Text,
+        verdict_reference -> Text,
+        holder -> Text,
+        duty -> Text,
+        created_at -> Timestamp,
+        fulfilled_at -> Nullable<Timestamp>,
+        report -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     policies (version) {
         version -> BigInt,
@@ -19,9 +42,30 @@ diesel::table! {
         created_at -> BigInt,
         content -> Text,
         reasoner_connector_context -> Text,
+        tags -> Text,
+        jurisdiction -> Nullable<Text>,
+        department -> Nullable<Text>,
+        content_hashes -> Text,
+        external_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    policy_contents (hash) {
+        hash -> Text,
+        content -> Text,
+    }
+}
+
+diesel::table! {
+    usage_counters (subject, counter, period) {
+        subject -> Text,
+        counter -> Text,
+        period -> Text,
+        value -> BigInt,
     }
 }
 
 diesel::joinable!(active_version -> policies (version));
 
-diesel::allow_tables_to_appear_in_same_query!(active_version, policies,);
+diesel::allow_tables_to_appear_in_same_query!(active_version, audit_log, duties, policies, policy_contents, usage_counters,);
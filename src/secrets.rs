@@ -0,0 +1,258 @@
+//! Implements fetching secrets (such as signing keys and credentials) from a secrets manager or its fallbacks.
+//!
+//! Every secret has a name (e.g., `jwk-set-expert`). A [`SecretsProvider`] either knows a secret by that name or not;
+//! [`Secrets`] asks a list of providers in order, and returns the first secret found. Providers are:
+//! - [`VaultSecrets`], which reads the fields of a secret in the KV (version 2) engine of HashiCorp Vault;
+//! - [`EnvSecrets`], which reads environment variables (e.g., `POLICY_REASONER_SECRET_JWK_SET_EXPERT`); and
+//! - [`DirSecrets`], which reads files in a directory (e.g., `/run/secrets/jwk-set-expert`).
+//!
+//! Secrets are meant to be fetched once when the reasoner starts, so rotating them requires a restart.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use log::debug;
+use serde_json::Value;
+
+/***** ERRORS *****/
+/// Defines errors originating from fetching secrets.
+#[derive(Debug)]
+pub enum SecretsError {
+    /// Failed to send the request to Vault.
+    VaultRequest { url: String, err: reqwest::Error },
+    /// Vault answered with an unexpected response.
+    VaultResponse { url: String, reason: String },
+    /// Failed to read a secret file.
+    FileRead { path: PathBuf, err: std::io::Error },
+}
+impl Display for SecretsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use SecretsError::*;
+        match self {
+            VaultRequest { url, .. } => write!(f, "Failed to fetch secrets from Vault at '{url}'"),
+            VaultResponse { url, reason } => write!(f, "Unexpected response from Vault at '{url}': {reason}"),
+            FileRead { path, .. } => write!(f, "Failed to read secret file '{}'", path.display()),
+        }
+    }
+}
+impl Error for SecretsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use SecretsError::*;
+        match self {
+            VaultRequest { err, .. } => Some(err),
+            VaultResponse { .. } => None,
+            FileRead { err, .. } => Some(err),
+        }
+    }
+}
+
+/***** HELPERS *****/
+/// Reads a secret from a file, ignoring trailing whitespace (e.g., a newline).
+///
+/// # Arguments
+/// - `path`: The path of the file.
+///
+/// # Returns
+/// The secret, or [`None`] if the file does not exist.
+///
+/// # Errors
+/// This function errors if the file exists but could not be read.
+pub fn read_secret_file(path: &Path) -> Result<Option<Vec<u8>>, SecretsError> {
+    let mut secret: Vec<u8> = match fs::read(path) {
+        Ok(secret) => secret,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(SecretsError::FileRead { path: path.into(), err }),
+    };
+    while secret.last().map(|b| b.is_ascii_whitespace()).unwrap_or(false) {
+        secret.pop();
+    }
+    Ok(Some(secret))
+}
+
+/***** LIBRARY *****/
+/// Something that knows secrets by name.
+#[async_trait::async_trait]
+pub trait SecretsProvider: Send + Sync {
+    /// Fetches a secret.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the secret (e.g., `jwk-set-expert`).
+    ///
+    /// # Returns
+    /// The secret, or [`None`] if this provider does not know it.
+    ///
+    /// # Errors
+    /// This function errors if the provider could not be asked.
+    async fn secret(&self, name: &str) -> Result<Option<Vec<u8>>, SecretsError>;
+}
+
+/// Reads secrets from the fields of a single secret in the KV (version 2) secrets engine of HashiCorp Vault.
+#[derive(Clone, Debug)]
+pub struct VaultSecrets {
+    /// The URL of the secret, i.e., `<ADDR>/v1/<MOUNT>/data/<PATH>`.
+    url:    String,
+    /// The token to authenticate with.
+    token:  String,
+    /// The client to talk to Vault with.
+    client: reqwest::Client,
+}
+impl VaultSecrets {
+    /// Constructor for the VaultSecrets.
+    ///
+    /// # Arguments
+    /// - `addr`: The address of Vault (e.g., `https://vault.example.com:8200`).
+    /// - `token`: The token to authenticate with.
+    /// - `mount`: Where the KV secrets engine is mounted (e.g., `secret`).
+    /// - `path`: The path of the secret whose fields are the reasoner's secrets (e.g., `policy-reasoner`).
+    ///
+    /// # Returns
+    /// A new VaultSecrets.
+    pub fn new(addr: &str, token: impl Into<String>, mount: &str, path: &str) -> Self {
+        Self {
+            url:    format!("{}/v1/{}/data/{}", addr.trim_end_matches('/'), mount.trim_matches('/'), path.trim_matches('/')),
+            token:  token.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+#[async_trait::async_trait]
+impl SecretsProvider for VaultSecrets {
+    async fn secret(&self, name: &str) -> Result<Option<Vec<u8>>, SecretsError> {
+        debug!("Fetching secret '{name}' from Vault at '{}'...", self.url);
+        let res = self
+            .client
+            .get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(|err| SecretsError::VaultRequest { url: self.url.clone(), err })?;
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let res: Value = res
+            .error_for_status()
+            .map_err(|err| SecretsError::VaultRequest { url: self.url.clone(), err })?
+            .json()
+            .await
+            .map_err(|err| SecretsError::VaultRequest { url: self.url.clone(), err })?;
+
+        // The KV version 2 engine nests the fields of the secret under `data.data`
+        match res.pointer("/data/data").and_then(|data| data.get(name)) {
+            Some(Value::String(secret)) => Ok(Some(secret.as_bytes().to_vec())),
+            Some(Value::Null) | None => Ok(None),
+            Some(other) => Ok(Some(other.to_string().into_bytes())),
+        }
+    }
+}
+
+/// Reads secrets from environment variables.
+///
+/// The variable of a secret is the prefix followed by its name in uppercase, with dashes replaced by underscores.
+#[derive(Clone, Debug)]
+pub struct EnvSecrets {
+    /// The prefix of the variables (e.g., `POLICY_REASONER_SECRET_`).
+    prefix: String,
+}
+impl EnvSecrets {
+    /// Constructor for the EnvSecrets.
+    ///
+    /// # Arguments
+    /// - `prefix`: The prefix of the variables (e.g., `POLICY_REASONER_SECRET_`).
+    ///
+    /// # Returns
+    /// A new EnvSecrets.
+    #[inline]
+    pub fn new(prefix: impl Into<String>) -> Self { Self { prefix: prefix.into() } }
+}
+#[async_trait::async_trait]
+impl SecretsProvider for EnvSecrets {
+    async fn secret(&self, name: &str) -> Result<Option<Vec<u8>>, SecretsError> {
+        let var: String = format!("{}{}", self.prefix, name.to_uppercase().replace('-', "_"));
+        Ok(env::var_os(var).map(|secret| secret.into_encoded_bytes()))
+    }
+}
+
+/// Reads secrets from files in a directory, one per secret and named after it (e.g., a mounted Kubernetes secret).
+#[derive(Clone, Debug)]
+pub struct DirSecrets {
+    /// The directory with the secret files.
+    dir: PathBuf,
+}
+impl DirSecrets {
+    /// Constructor for the DirSecrets.
+    ///
+    /// # Arguments
+    /// - `dir`: The directory with the secret files (e.g., `/run/secrets`).
+    ///
+    /// # Returns
+    /// A new DirSecrets.
+    #[inline]
+    pub fn new(dir: impl Into<PathBuf>) -> Self { Self { dir: dir.into() } }
+}
+#[async_trait::async_trait]
+impl SecretsProvider for DirSecrets {
+    async fn secret(&self, name: &str) -> Result<Option<Vec<u8>>, SecretsError> { read_secret_file(&self.dir.join(name)) }
+}
+
+/// Asks a list of [`SecretsProvider`]s for secrets, in order.
+#[derive(Default)]
+pub struct Secrets {
+    /// The providers to ask.
+    providers: Vec<Box<dyn SecretsProvider>>,
+}
+impl Secrets {
+    /// Constructor for Secrets that doesn't know any secrets yet.
+    ///
+    /// # Returns
+    /// A new Secrets.
+    #[inline]
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a provider, which is asked after those added before it.
+    ///
+    /// # Arguments
+    /// - `provider`: The [`SecretsProvider`] to add.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_provider(mut self, provider: impl 'static + SecretsProvider) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Fetches a number of secrets at once.
+    ///
+    /// # Arguments
+    /// - `names`: The names of the secrets to fetch.
+    ///
+    /// # Returns
+    /// The secrets found by any of the providers, by name.
+    ///
+    /// # Errors
+    /// This function errors if any of the providers could not be asked.
+    pub async fn fetch(&self, names: &[&str]) -> Result<HashMap<String, Vec<u8>>, SecretsError> {
+        let mut secrets: HashMap<String, Vec<u8>> = HashMap::with_capacity(names.len());
+        for name in names {
+            if let Some(secret) = self.secret(name).await? {
+                secrets.insert((*name).into(), secret);
+            }
+        }
+        Ok(secrets)
+    }
+}
+#[async_trait::async_trait]
+impl SecretsProvider for Secrets {
+    async fn secret(&self, name: &str) -> Result<Option<Vec<u8>>, SecretsError> {
+        for provider in &self.providers {
+            if let Some(secret) = provider.secret(name).await? {
+                return Ok(Some(secret));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -1,16 +1,31 @@
 use std::future::Future;
-
-use ::policy::{Context, Policy, PolicyContent, PolicyDataAccess, PolicyDataError, PolicyVersion};
-use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use ::policy::{
+    Context, Policy, PolicyActivation, PolicyArchive, PolicyContent, PolicyDataAccess, PolicyDataError, PolicyMetadata, PolicyVersion,
+    PolicyVersionFilter,
+};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use deliberation::ids::{IdGenerator, UuidV7Generator};
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::result::Error;
 use diesel::sqlite::SqliteConnection;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
+use log::{info, warn};
+use state_resolver::counters::{Counter, UsageCounters, UsageCountersError};
+use state_resolver::duties::{DutiesError, Duty, DutyStore};
 use tokio::runtime::Handle;
 
-use crate::models::{SqliteActiveVersion, SqlitePolicy};
+use crate::encryption::PolicyCipher;
+use crate::models::{SqliteActiveVersion, SqliteDuty, SqlitePolicy, SqlitePolicyContent, SqliteUsageCounter};
+
+#[derive(Clone)]
 pub struct SqlitePolicyDataStore {
-    pool: Pool<ConnectionManager<SqliteConnection>>,
+    pool:   Pool<ConnectionManager<SqliteConnection>>,
+    /// Encrypts policy contents at rest, if configured.
+    cipher: Option<PolicyCipher>,
+    /// Generates the external identifiers of new versions.
+    ids:    Arc<dyn IdGenerator>,
 }
 
 struct SqlitePolicyDataStoreError {
@@ -34,13 +49,194 @@ impl From<SqlitePolicyDataStoreError> for PolicyDataError {
     fn from(value: SqlitePolicyDataStoreError) -> Self { PolicyDataError::GeneralError(value.msg) }
 }
 
+/// Reconstructs the [`PolicyMetadata`] of a version from its database columns.
+///
+/// Tags are stored as a JSON array in a single column.
+fn to_metadata(tags: &str, jurisdiction: Option<String>, department: Option<String>) -> PolicyMetadata {
+    PolicyMetadata { tags: serde_json::from_str::<Vec<String>>(tags).unwrap_or_default(), jurisdiction, department }
+}
+
+/// Converts a timestamp as stored in the `active_version` table (which is UTC) to a local one.
+fn to_local(timestamp: NaiveDateTime) -> DateTime<chrono::Local> { timestamp.and_utc().into() }
+
+/// Converts the contents of a version to the rows that store them, and the list of hashes by which the version refers to them.
+///
+/// The rows are encrypted if a `cipher` is given.
+fn to_content_models(contents: &[PolicyContent], cipher: Option<&PolicyCipher>) -> Result<(String, Vec<SqlitePolicyContent>), PolicyDataError> {
+    let mut models: Vec<SqlitePolicyContent> = Vec::with_capacity(contents.len());
+    for c in contents {
+        let hash: String = c.hash();
+        let mut content: String = serde_json::to_string(c).unwrap();
+        if let Some(cipher) = cipher {
+            content = cipher.encrypt(&hash, &content).map_err(|err| PolicyDataError::GeneralError(err.to_string()))?;
+        }
+        models.push(SqlitePolicyContent { hash, content });
+    }
+    let hashes: Vec<&str> = models.iter().map(|m| m.hash.as_str()).collect();
+    Ok((serde_json::to_string(&hashes).unwrap(), models))
+}
+
+/// Loads the contents a version refers to, decrypting them if needed and verifying that they match their hashes.
+fn load_contents(conn: &mut SqliteConnection, item: &SqlitePolicy, cipher: Option<&PolicyCipher>) -> Result<Vec<PolicyContent>, PolicyDataError> {
+    use crate::schema::policy_contents::dsl::{content, policy_contents};
+
+    let hashes: Vec<String> = serde_json::from_str(&item.content_hashes)
+        .map_err(|err| PolicyDataError::GeneralError(format!("Failed to parse content hashes of policy {}: {}", item.version, err)))?;
+    if hashes.is_empty() {
+        // Versions stored before contents were deduplicated carry them themselves
+        return serde_json::from_str::<Vec<PolicyContent>>(&item.content)
+            .map_err(|err| PolicyDataError::GeneralError(format!("Failed to parse content of policy {}: {}", item.version, err)));
+    }
+
+    let mut contents: Vec<PolicyContent> = Vec::with_capacity(hashes.len());
+    for hash in hashes {
+        let raw: String = policy_contents
+            .find(&hash)
+            .select(content)
+            .first(conn)
+            .map_err(|err| PolicyDataError::GeneralError(format!("Failed to load content '{}' of policy {}: {}", hash, item.version, err)))?;
+        let raw: String = PolicyCipher::decrypt(cipher, &hash, raw)
+            .map_err(|err| PolicyDataError::GeneralError(format!("Failed to load content '{}' of policy {}: {}", hash, item.version, err)))?;
+        let policy_content: PolicyContent = serde_json::from_str(&raw)
+            .map_err(|err| PolicyDataError::GeneralError(format!("Failed to parse content '{}' of policy {}: {}", hash, item.version, err)))?;
+        if policy_content.hash() != hash {
+            return Err(PolicyDataError::GeneralError(format!("Content '{}' of policy {} does not match its hash", hash, item.version)));
+        }
+        contents.push(policy_content);
+    }
+    Ok(contents)
+}
+
+/// Converts a row of the `policies` table to a [`Policy`], loading its contents.
+fn to_policy(conn: &mut SqliteConnection, item: SqlitePolicy, cipher: Option<&PolicyCipher>) -> Result<Policy, PolicyDataError> {
+    let content: Vec<PolicyContent> = load_contents(conn, &item, cipher)?;
+    let content_hashes: Vec<String> = content.iter().map(PolicyContent::hash).collect();
+    Ok(Policy {
+        description: item.description,
+        version: PolicyVersion {
+            creator: Some(item.creator),
+            created_at: DateTime::from_timestamp_micros(item.created_at).unwrap().into(),
+            version: Some(item.version),
+            version_description: item.version_description,
+            reasoner_connector_context: item.reasoner_connector_context,
+            metadata: to_metadata(&item.tags, item.jurisdiction, item.department),
+            content_hashes,
+            external_id: item.external_id,
+        },
+        content,
+    })
+}
+
 impl SqlitePolicyDataStore {
     pub fn new(database_url: &str) -> Self {
         let manager = ConnectionManager::<SqliteConnection>::new(database_url);
         // Refer to the `r2d2` documentation for more methods to use
         // when building a connection pool
         let pool = Pool::builder().test_on_check_out(true).build(manager).expect("Could not build connection pool");
-        Self { pool }
+        let this = Self { pool, cipher: None, ids: Arc::new(UuidV7Generator) };
+        match this.deduplicate_legacy_contents() {
+            Ok(0) => {},
+            Ok(n) => info!("Moved the contents of {n} policy version(s) to the deduplicated content store"),
+            Err(err) => warn!("Failed to move policy contents to the deduplicated content store: {err}"),
+        }
+        this
+    }
+
+    /// Sets how the external identifiers of new versions are generated.
+    ///
+    /// # Arguments
+    /// - `ids`: The [`IdGenerator`] to use. Defaults to a [`UuidV7Generator`].
+    ///
+    /// # Returns
+    /// Self for chaining.
+    #[inline]
+    pub fn with_id_generator(mut self, ids: impl 'static + IdGenerator) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
+
+    /// Makes the store encrypt policy contents at rest.
+    ///
+    /// Contents already stored in plaintext are encrypted right away.
+    ///
+    /// # Arguments
+    /// - `cipher`: The [`PolicyCipher`] to encrypt (and decrypt) the contents with.
+    ///
+    /// # Returns
+    /// Self for chaining.
+    pub fn with_encryption(mut self, cipher: PolicyCipher) -> Self {
+        self.cipher = Some(cipher);
+        match self.encrypt_plaintext_contents() {
+            Ok(0) => {},
+            Ok(n) => info!("Encrypted {n} policy content(s) that were stored in plaintext"),
+            Err(err) => warn!("Failed to encrypt policy contents stored in plaintext: {err}"),
+        }
+        self
+    }
+
+    /// Encrypts the contents that were stored before encryption was enabled.
+    ///
+    /// # Returns
+    /// The number of contents encrypted.
+    fn encrypt_plaintext_contents(&self) -> Result<usize, String> {
+        use crate::schema::policy_contents::dsl::{content, hash, policy_contents};
+        let Some(cipher) = &self.cipher else { return Ok(0) };
+        let mut conn = self.pool.get().map_err(|err| err.to_string())?;
+
+        conn.exclusive_transaction(|conn| {
+            let plaintext: Vec<(String, String)> = policy_contents.select((hash, content)).load(conn)?;
+            let mut encrypted: usize = 0;
+            for (h, c) in plaintext.into_iter().filter(|(_, c)| !PolicyCipher::is_encrypted(c)) {
+                let c: String = match cipher.encrypt(&h, &c) {
+                    Ok(c) => c,
+                    Err(err) => {
+                        warn!("Not encrypting policy content '{h}': {err}");
+                        continue;
+                    },
+                };
+                diesel::update(policy_contents.find(&h)).set(content.eq(c)).execute(conn)?;
+                encrypted += 1;
+            }
+            Ok(encrypted)
+        })
+        .map_err(|err: Error| err.to_string())
+    }
+
+    /// Moves the contents of versions stored before contents were deduplicated to the `policy_contents` table.
+    ///
+    /// # Returns
+    /// The number of versions of which the contents were moved.
+    fn deduplicate_legacy_contents(&self) -> Result<usize, String> {
+        use crate::schema::policies::dsl::{content, content_hashes, policies, version};
+        use crate::schema::policy_contents::dsl::policy_contents;
+        let mut conn = self.pool.get().map_err(|err| err.to_string())?;
+
+        conn.exclusive_transaction(|conn| {
+            let legacy: Vec<(i64, String)> =
+                policies.filter(content_hashes.eq("[]")).filter(content.ne("[]")).select((version, content)).load(conn)?;
+            let mut moved: usize = 0;
+            for (v, c) in legacy {
+                let contents: Vec<PolicyContent> = match serde_json::from_str(&c) {
+                    Ok(contents) => contents,
+                    Err(err) => {
+                        warn!("Not moving content of policy {v} to the deduplicated content store: failed to parse it: {err}");
+                        continue;
+                    },
+                };
+                let (hashes, models) = match to_content_models(&contents, self.cipher.as_ref()) {
+                    Ok(res) => res,
+                    Err(err) => {
+                        warn!("Not moving content of policy {v} to the deduplicated content store: {err}");
+                        continue;
+                    },
+                };
+                diesel::insert_or_ignore_into(policy_contents).values(&models).execute(conn)?;
+                diesel::update(policies.filter(version.eq(v))).set((content.eq("[]"), content_hashes.eq(hashes))).execute(conn)?;
+                moved += 1;
+            }
+            Ok(moved)
+        })
+        .map_err(|err: Error| err.to_string())
     }
 
     async fn _get_active(&self) -> Result<i64, PolicyDataError> {
@@ -84,20 +280,7 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
                     return Err(PolicyDataError::NotFound);
                 }
                 let item = r.remove(0);
-                let content = serde_json::from_str::<Vec<PolicyContent>>(item.content.as_str()).expect("error");
-                let created_at = DateTime::from_timestamp_micros(item.created_at).unwrap();
-                let policy = Policy {
-                    description: item.description,
-                    version: PolicyVersion {
-                        creator: Some(item.creator),
-                        created_at: created_at.into(),
-                        version: Some(item.version),
-                        version_description: item.version_description,
-                        reasoner_connector_context: item.reasoner_connector_context,
-                    },
-                    content,
-                };
-                return Ok(policy);
+                return to_policy(&mut conn, item, self.cipher.as_ref());
             },
             Err(err) => Err(match err {
                 Error::NotFound => PolicyDataError::NotFound,
@@ -133,7 +316,10 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
 
         // up to next version
         let next_version = latest_version + 1;
-        let str_content = serde_json::to_string(&version.content).unwrap();
+        // Contents are stored separately, once, by hash
+        let (hashes, content_models) = to_content_models(&version.content, self.cipher.as_ref())?;
+        version.version.content_hashes = version.content_hashes();
+        version.version.external_id = Some(self.ids.generate());
 
         let model = SqlitePolicy {
             description: version.description.clone(),
@@ -141,13 +327,19 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
             version_description: version.version.version_description.clone(),
             creator: context.initiator,
             created_at: version.version.created_at.timestamp_micros(),
-            content: str_content,
+            content: "[]".into(),
             reasoner_connector_context: version.version.reasoner_connector_context.clone(),
+            tags: serde_json::to_string(&version.version.metadata.tags).unwrap(),
+            jurisdiction: version.version.metadata.jurisdiction.clone(),
+            department: version.version.metadata.department.clone(),
+            content_hashes: hashes,
+            external_id: version.version.external_id.clone(),
         };
 
         let rt_handle: Handle = Handle::current();
         match tokio::task::spawn_blocking(move || {
             conn.exclusive_transaction(|conn| -> Result<Policy, SqlitePolicyDataStoreError> {
+                diesel::insert_or_ignore_into(crate::schema::policy_contents::dsl::policy_contents).values(&content_models).execute(conn)?;
                 let policy = match diesel::insert_into(policies).values(&model).execute(conn) {
                     Ok(_) => {
                         version.version.version = Some(next_version);
@@ -186,21 +378,7 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
                 }
 
                 let item: SqlitePolicy = r.remove(0);
-                let content = serde_json::from_str::<Vec<PolicyContent>>(item.content.as_str()).expect("error");
-                let created_at = DateTime::from_timestamp_micros(item.created_at).unwrap();
-                let policy = Policy {
-                    description: item.description,
-                    version: PolicyVersion {
-                        creator: Some(item.creator),
-                        created_at: created_at.into(),
-                        version: Some(item.version),
-                        version_description: item.version_description,
-                        reasoner_connector_context: item.reasoner_connector_context,
-                    },
-                    content,
-                };
-
-                return Ok(policy);
+                return to_policy(&mut conn, item, self.cipher.as_ref());
             },
             Err(err) => Err(match err {
                 Error::NotFound => PolicyDataError::NotFound,
@@ -209,14 +387,37 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
         }
     }
 
-    async fn get_versions(&self) -> Result<Vec<PolicyVersion>, PolicyDataError> {
-        use crate::schema::policies::dsl::{created_at, creator, policies, reasoner_connector_context, version, version_description};
+    async fn get_versions(&self, filter: &PolicyVersionFilter) -> Result<Vec<PolicyVersion>, PolicyDataError> {
+        use crate::schema::policies::dsl::{
+            content_hashes, created_at, creator, department, external_id, jurisdiction, policies, reasoner_connector_context, tags, version,
+            version_description,
+        };
         let mut conn = self.pool.get().unwrap();
 
-        match policies
+        // Filter on the simple columns in the database already
+        let mut query = policies.into_boxed();
+        if let Some(j) = &filter.jurisdiction {
+            query = query.filter(jurisdiction.eq(j.clone()));
+        }
+        if let Some(d) = &filter.department {
+            query = query.filter(department.eq(d.clone()));
+        }
+
+        match query
             .order_by(crate::schema::policies::dsl::created_at.desc())
-            .select((version, version_description, creator, created_at, reasoner_connector_context))
-            .load::<(i64, String, String, i64, String)>(&mut conn)
+            .select((
+                version,
+                version_description,
+                creator,
+                created_at,
+                reasoner_connector_context,
+                tags,
+                jurisdiction,
+                department,
+                content_hashes,
+                external_id,
+            ))
+            .load::<(i64, String, String, i64, String, String, Option<String>, Option<String>, String, Option<String>)>(&mut conn)
         {
             Ok(r) => {
                 let items: Vec<PolicyVersion> = r
@@ -227,7 +428,13 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
                         creator: Some(x.2),
                         created_at: DateTime::from_timestamp_micros(x.3).unwrap().into(),
                         reasoner_connector_context: x.4,
+                        metadata: to_metadata(&x.5, x.6, x.7),
+                        // Versions stored before contents were deduplicated have no hashes here
+                        content_hashes: serde_json::from_str(&x.8).unwrap_or_default(),
+                        external_id: x.9,
                     })
+                    // Tags are stored as JSON, so filter on them here
+                    .filter(|v| filter.matches(&v.metadata))
                     .collect();
 
                 return Ok(items);
@@ -245,6 +452,36 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
         self.get_version(av).await
     }
 
+    async fn get_active_at(&self, at: DateTime<chrono::Local>) -> Result<Policy, PolicyDataError> {
+        use crate::schema::active_version::dsl::{activated_on, active_version};
+        let mut conn = self.pool.get().unwrap();
+
+        // The `active_version` table is in UTC
+        let at: NaiveDateTime = at.naive_utc();
+        let av: SqliteActiveVersion = match active_version
+            .filter(activated_on.le(at))
+            .order_by(activated_on.desc())
+            .limit(1)
+            .select(SqliteActiveVersion::as_select())
+            .load(&mut conn)
+        {
+            Ok(mut r) => {
+                if r.len() != 1 {
+                    return Err(PolicyDataError::NotFound);
+                }
+
+                r.remove(0)
+            },
+            Err(err) => return Err(PolicyDataError::GeneralError(err.to_string())),
+        };
+
+        if av.deactivated_on.map(|deactivated| deactivated <= at).unwrap_or(false) {
+            return Err(PolicyDataError::NotFound);
+        }
+
+        self.get_version(av.version).await
+    }
+
     async fn set_active<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
         &self,
         version: i64,
@@ -312,4 +549,213 @@ impl PolicyDataAccess for SqlitePolicyDataStore {
         }
         .map_err(|err: SqlitePolicyDataStoreError| err.into())
     }
+
+    async fn export_store(&self) -> Result<PolicyArchive, PolicyDataError> {
+        use crate::schema::active_version::dsl::{activated_on, active_version};
+        use crate::schema::policies::dsl::{policies, version};
+        let mut conn = self.pool.get().unwrap();
+
+        let items: Vec<SqlitePolicy> = policies
+            .order_by(version.asc())
+            .select(SqlitePolicy::as_select())
+            .load(&mut conn)
+            .map_err(|err| PolicyDataError::GeneralError(err.to_string()))?;
+        let mut archived: Vec<Policy> = Vec::with_capacity(items.len());
+        for item in items {
+            archived.push(to_policy(&mut conn, item, self.cipher.as_ref())?);
+        }
+
+        let activations: Vec<PolicyActivation> = active_version
+            .order_by(activated_on.asc())
+            .select(SqliteActiveVersion::as_select())
+            .load(&mut conn)
+            .map_err(|err| PolicyDataError::GeneralError(err.to_string()))?
+            .into_iter()
+            .map(|av: SqliteActiveVersion| PolicyActivation {
+                version: av.version,
+                activated_on: to_local(av.activated_on),
+                activated_by: av.activated_by,
+                deactivated_on: av.deactivated_on.map(to_local),
+                deactivated_by: av.deactivated_by,
+            })
+            .collect();
+
+        Ok(PolicyArchive::new(archived, activations))
+    }
+
+    async fn import_store<F: 'static + Send + Future<Output = Result<(), PolicyDataError>>>(
+        &self,
+        archive: PolicyArchive,
+        context: Context,
+        transaction: impl 'static + Send + FnOnce(PolicyArchive) -> F,
+    ) -> Result<(), PolicyDataError> {
+        use crate::schema::active_version::dsl::active_version;
+        use crate::schema::policies::dsl::policies;
+        use crate::schema::policy_contents::dsl::policy_contents;
+
+        // Stored versions are immutable (and cannot be deleted), so the archive may only add versions or repeat them as-is
+        let stored: Vec<PolicyVersion> = self.get_versions(&PolicyVersionFilter::default()).await?;
+        let modified: Vec<i64> = archive.modified_versions(&stored);
+        if !modified.is_empty() {
+            return Err(PolicyDataError::GeneralError(format!(
+                "Archive would modify or drop stored policy version(s) {}, which are immutable",
+                modified.iter().map(i64::to_string).collect::<Vec<String>>().join(", ")
+            )));
+        }
+        let mut conn = self.pool.get().unwrap();
+
+        // Convert the versions new to us to database rows first, such that we don't fail halfway through the transaction
+        let mut policy_models: Vec<SqlitePolicy> = Vec::with_capacity(archive.policies.len());
+        let mut content_models: Vec<SqlitePolicyContent> = Vec::new();
+        for policy in &archive.policies {
+            let Some(version) = policy.version.version else {
+                return Err(PolicyDataError::GeneralError(format!("Archived policy '{}' has no version", policy.description)));
+            };
+            if stored.iter().any(|old| old.version == Some(version)) {
+                continue;
+            }
+            let (hashes, models) = to_content_models(&policy.content, self.cipher.as_ref())?;
+            content_models.extend(models);
+            policy_models.push(SqlitePolicy {
+                description: policy.description.clone(),
+                version,
+                version_description: policy.version.version_description.clone(),
+                creator: policy.version.creator.clone().unwrap_or_else(|| context.initiator.clone()),
+                created_at: policy.version.created_at.timestamp_micros(),
+                content: "[]".into(),
+                reasoner_connector_context: policy.version.reasoner_connector_context.clone(),
+                tags: serde_json::to_string(&policy.version.metadata.tags).unwrap(),
+                jurisdiction: policy.version.metadata.jurisdiction.clone(),
+                department: policy.version.metadata.department.clone(),
+                content_hashes: hashes,
+                // Keep the identifier a version already has, and give one to versions archived before these existed
+                external_id: policy.version.external_id.clone().or_else(|| Some(self.ids.generate())),
+            });
+        }
+        let activation_models: Vec<SqliteActiveVersion> = archive
+            .activations
+            .iter()
+            .map(|a| SqliteActiveVersion {
+                version: a.version,
+                activated_on: a.activated_on.naive_utc(),
+                activated_by: a.activated_by.clone(),
+                deactivated_on: a.deactivated_on.map(|d| d.naive_utc()),
+                deactivated_by: a.deactivated_by.clone(),
+            })
+            .collect();
+
+        let rt_handle: Handle = Handle::current();
+        match tokio::task::spawn_blocking(move || {
+            conn.exclusive_transaction(|conn| {
+                diesel::delete(active_version).execute(conn)?;
+                diesel::insert_or_ignore_into(policy_contents).values(&content_models).execute(conn)?;
+                diesel::insert_into(policies).values(&policy_models).execute(conn)?;
+                diesel::insert_into(active_version).values(&activation_models).execute(conn)?;
+
+                rt_handle.block_on(transaction(archive)).map_err(SqlitePolicyDataStoreError::from)?;
+
+                Ok(())
+            })
+        })
+        .await
+        {
+            Ok(res) => res,
+            Err(err) => panic!("Failed to complete task in transaction: {:?}", err),
+        }
+        .map_err(|err: SqlitePolicyDataStoreError| err.into())
+    }
+}
+
+#[async_trait::async_trait]
+impl UsageCounters for SqlitePolicyDataStore {
+    async fn increment(&self, subject: &str, counter: &str, period: &str, by: u64) -> Result<(), UsageCountersError> {
+        use crate::schema::usage_counters::dsl;
+        let mut conn = self.pool.get().map_err(|err| UsageCountersError(err.to_string()))?;
+
+        let model = SqliteUsageCounter { subject: subject.into(), counter: counter.into(), period: period.into(), value: by as i64 };
+        diesel::insert_into(dsl::usage_counters)
+            .values(&model)
+            .on_conflict((dsl::subject, dsl::counter, dsl::period))
+            .do_update()
+            .set(dsl::value.eq(dsl::value + by as i64))
+            .execute(&mut conn)
+            .map(|_| ())
+            .map_err(|err| UsageCountersError(err.to_string()))
+    }
+
+    async fn get_counters(&self, period: &str) -> Result<Vec<Counter>, UsageCountersError> {
+        use crate::schema::usage_counters::dsl;
+        let mut conn = self.pool.get().map_err(|err| UsageCountersError(err.to_string()))?;
+
+        let counters: Vec<SqliteUsageCounter> = dsl::usage_counters
+            .filter(dsl::period.eq(period))
+            .select(SqliteUsageCounter::as_select())
+            .load(&mut conn)
+            .map_err(|err| UsageCountersError(err.to_string()))?;
+        Ok(counters.into_iter().map(|c| Counter { subject: c.subject, counter: c.counter, period: c.period, value: c.value.max(0) as u64 }).collect())
+    }
+}
+
+impl From<SqliteDuty> for Duty {
+    fn from(value: SqliteDuty) -> Self {
+        Self {
+            id: value.id,
+            verdict_reference: value.verdict_reference,
+            holder: value.holder,
+            duty: value.duty,
+            created_at: value.created_at.and_utc().with_timezone(&Local),
+            fulfilled_at: value.fulfilled_at.map(|at| at.and_utc().with_timezone(&Local)),
+            report: value.report,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl DutyStore for SqlitePolicyDataStore {
+    async fn add_duty(&self, duty: Duty) -> Result<(), DutiesError> {
+        use crate::schema::duties::dsl;
+        let mut conn = self.pool.get().map_err(|err| DutiesError(err.to_string()))?;
+
+        let model = SqliteDuty {
+            id: duty.id,
+            verdict_reference: duty.verdict_reference,
+            holder: duty.holder,
+            duty: duty.duty,
+            created_at: duty.created_at.naive_utc(),
+            fulfilled_at: duty.fulfilled_at.map(|at| at.naive_utc()),
+            report: duty.report,
+        };
+        diesel::insert_into(dsl::duties).values(&model).execute(&mut conn).map(|_| ()).map_err(|err| DutiesError(err.to_string()))
+    }
+
+    async fn get_open_duties(&self, holder: Option<&str>) -> Result<Vec<Duty>, DutiesError> {
+        use crate::schema::duties::dsl;
+        let mut conn = self.pool.get().map_err(|err| DutiesError(err.to_string()))?;
+
+        let mut query = dsl::duties.filter(dsl::fulfilled_at.is_null()).order_by(dsl::created_at.asc()).into_boxed();
+        if let Some(holder) = holder {
+            query = query.filter(dsl::holder.eq(holder));
+        }
+        let duties: Vec<SqliteDuty> = query.select(SqliteDuty::as_select()).load(&mut conn).map_err(|err| DutiesError(err.to_string()))?;
+        Ok(duties.into_iter().map(Duty::from).collect())
+    }
+
+    async fn fulfil_duty(&self, id: &str, report: Option<String>) -> Result<Option<Duty>, DutiesError> {
+        use crate::schema::duties::dsl;
+        let mut conn = self.pool.get().map_err(|err| DutiesError(err.to_string()))?;
+
+        conn.exclusive_transaction(|conn| {
+            let Some(duty) = dsl::duties.find(id).select(SqliteDuty::as_select()).first(conn).optional()? else {
+                return Ok(None);
+            };
+            if duty.fulfilled_at.is_some() {
+                return Ok(Some(duty.into()));
+            }
+
+            let fulfilled_at: NaiveDateTime = Utc::now().naive_utc();
+            diesel::update(dsl::duties.find(id)).set((dsl::fulfilled_at.eq(fulfilled_at), dsl::report.eq(&report))).execute(conn)?;
+            Ok(Some(SqliteDuty { fulfilled_at: Some(fulfilled_at), report, ..duty }.into()))
+        })
+        .map_err(|err: Error| DutiesError(err.to_string()))
+    }
 }
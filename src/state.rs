@@ -18,6 +18,7 @@ use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FResult};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::RwLock;
 
 #[cfg(feature = "brane-api-resolver")]
 use ::{
@@ -30,16 +31,17 @@ use ::{
     reqwest::{Client, Request, Response, StatusCode},
     specifications::address::Address,
     specifications::data::DataInfo,
+    specifications::version::Version,
     state_resolver::StateResolverError,
     std::fs::File,
     uuid::Uuid,
-    workflow::{Dataset, User},
+    workflow::{Dataset, Image, PackageImage, User},
 };
 use async_trait::async_trait;
 use log::debug;
 use nested_cli_parser::map_parser::MapParser;
 use nested_cli_parser::{NestedCliParser, NestedCliParserHelpFormatter};
-use state_resolver::{State, StateResolver};
+use state_resolver::{State, StateResolver, StateResolverError};
 
 /***** CONSTANTS *****/
 /// The list of recognized keys for the arguments of the [`FileStateResolver`].
@@ -64,6 +66,10 @@ pub enum FileStateResolverError {
     CliDuplicatePath,
     /// The user did not tell us the path to the use case file.
     CliMissingPath,
+    /// Failed to read the configuration file with further nested arguments.
+    ConfigRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the configuration file with further nested arguments.
+    ConfigParse { path: PathBuf, err: nested_cli_parser::map_parser::Error },
     /// Failed to read a file.
     FileRead { path: PathBuf, err: std::io::Error },
     /// Failed to deserialize a file into JSON.
@@ -78,6 +84,10 @@ impl Display for FileStateResolverError {
             CliMissingPath => {
                 write!(f, "File path not specified (give it as either '--state-resolver \"p=...\"' or '--state-resolver \"path=...\"')")
             },
+            ConfigRead { path, .. } => write!(f, "Failed to read configuration file '{}'", path.display()),
+            ConfigParse { path, .. } => {
+                write!(f, "Failed to parse configuration file '{}' as CLI argument string for a FileStateResolver", path.display())
+            },
             FileRead { path, .. } => write!(f, "Failed to read file '{}'", path.display()),
             FileDeserialize { path, .. } => write!(f, "Failed to deserialize file '{}' as JSON", path.display()),
         }
@@ -90,11 +100,17 @@ impl Error for FileStateResolverError {
             CliArgumentsParse { err, .. } => Some(err),
             CliDuplicatePath => None,
             CliMissingPath => None,
+            ConfigRead { err, .. } => Some(err),
+            ConfigParse { err, .. } => Some(err),
             FileRead { err, .. } => Some(err),
             FileDeserialize { err, .. } => Some(err),
         }
     }
 }
+impl StateResolverError for FileStateResolverError {
+    #[inline]
+    fn try_as_unknown_use_case(&self) -> Option<&String> { None }
+}
 
 /// Defines a wrapper around a list of [`graphql_client::Error`]s.
 #[cfg(feature = "brane-api-resolver")]
@@ -233,10 +249,14 @@ impl StateResolverError for BraneApiResolverError {
 
 /***** LIBRARY *****/
 /// Defines a resolver that resolves from a static file.
+///
+/// The file is read again when the resolver is reloaded, together with the configuration file with further arguments (if any).
 #[derive(Debug)]
 pub struct FileStateResolver {
+    /// The nested arguments given to us, which are parsed again on a reload.
+    cli_args: String,
     /// The state read from the file.
-    state: State,
+    state:    RwLock<State>,
 }
 
 impl FileStateResolver {
@@ -252,14 +272,47 @@ impl FileStateResolver {
     /// This function may error if it failed to read the given file.
     #[inline]
     pub fn new(cli_args: String) -> Result<Self, FileStateResolverError> {
+        let state: State = Self::read_state(&cli_args)?;
+        Ok(Self { cli_args, state: RwLock::new(state) })
+    }
+
+    /// Parses the nested arguments and reads the state from the file they point to.
+    ///
+    /// # Arguments
+    /// - `cli_args`: A raw string with CLI arguments given to us by the `policy-reasoner` executable.
+    ///
+    /// # Returns
+    /// The [`State`] read from the file.
+    ///
+    /// # Errors
+    /// This function may error if it failed to parse the arguments, or to read the configuration file or the given file.
+    fn read_state(cli_args: &str) -> Result<State, FileStateResolverError> {
         // Parse the arguments using the [`MapParser`].
         debug!("Parsing nested arguments for FileStateResolver");
         let parser = MapParser::new(Self::cli_args());
-        let args: HashMap<String, Option<String>> = match parser.parse(&cli_args) {
+        let mut args: HashMap<String, Option<String>> = match parser.parse(cli_args) {
             Ok(args) => args,
-            Err(err) => return Err(FileStateResolverError::CliArgumentsParse { raw: cli_args, err }),
+            Err(err) => return Err(FileStateResolverError::CliArgumentsParse { raw: cli_args.into(), err }),
         };
 
+        // Add those in the configuration file, if any, unless given directly
+        if let Some(Some(config)) = args.get("config").cloned() {
+            let config: PathBuf = config.into();
+            debug!("Reading nested arguments for FileStateResolver from '{}'...", config.display());
+            let raw: String = match fs::read_to_string(&config) {
+                Ok(raw) => raw,
+                Err(err) => return Err(FileStateResolverError::ConfigRead { path: config, err }),
+            };
+            match parser.parse(raw.trim()) {
+                Ok(file_args) => {
+                    for (key, value) in file_args {
+                        args.entry(key).or_insert(value);
+                    }
+                },
+                Err(err) => return Err(FileStateResolverError::ConfigParse { path: config, err }),
+            }
+        }
+
         // See what to do with it
         let path: PathBuf = match args.get("path") {
             Some(Some(path)) => path.into(),
@@ -275,13 +328,10 @@ impl FileStateResolver {
 
         // Parse it as JSON
         debug!("Parsing input file '{}'...", path.display());
-        let state: State = match serde_json::from_str(&state) {
-            Ok(state) => state,
-            Err(err) => return Err(FileStateResolverError::FileDeserialize { path, err }),
-        };
-
-        // Build ourselves with it
-        Ok(Self { state })
+        match serde_json::from_str(&state) {
+            Ok(state) => Ok(state),
+            Err(err) => Err(FileStateResolverError::FileDeserialize { path, err }),
+        }
     }
 
     /// Returns the arguments necessary to build the parser for the FileStateResolver.
@@ -289,16 +339,24 @@ impl FileStateResolver {
     /// # Returns
     /// A vector of arguments appropriate to use to build a [`MapParser`].
     #[inline]
-    fn cli_args() -> [(char, &'static str, &'static str); 1] {
-        [(
-            'p',
-            "path",
-            concat!(
-                "The path to the file that we read the state from. Default: '",
-                env!("CARGO_MANIFEST_DIR"),
-                "/examples/eflint_reasonerconn/example-state.json'"
+    fn cli_args() -> [(char, &'static str, &'static str); 2] {
+        [
+            (
+                'p',
+                "path",
+                concat!(
+                    "The path to the file that we read the state from. Default: '",
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/examples/eflint_reasonerconn/example-state.json'"
+                ),
+            ),
+            (
+                'c',
+                "config",
+                "A file with further arguments (in the same syntax), which is read again when the reasoner is reloaded. Arguments given directly \
+                 take precedence. Default: none",
             ),
-        )]
+        ]
     }
 
     /// Returns a formatter that can be printed to understand the arguments to this resolver.
@@ -318,11 +376,17 @@ impl FileStateResolver {
 
 #[async_trait]
 impl StateResolver for FileStateResolver {
-    type Error = std::convert::Infallible;
+    type Error = FileStateResolverError;
 
-    async fn get_state(&self, _use_case: String) -> Result<State, Self::Error> {
+    async fn get_state(&self, use_case: String) -> Result<State, Self::Error> {
         // Simply return a clone of the internal one
-        Ok(self.state.clone())
+        Ok(State { use_case, ..self.state.read().unwrap_or_else(|err| err.into_inner()).clone() })
+    }
+
+    async fn reload(&self) -> Result<(), Self::Error> {
+        let state: State = Self::read_state(&self.cli_args)?;
+        *self.state.write().unwrap_or_else(|err| err.into_inner()) = state;
+        Ok(())
     }
 }
 
@@ -500,8 +564,8 @@ impl StateResolver for BraneApiResolver {
         };
         debug!("Retrieved {} datasets", datasets.len());
 
-        // Finally, retrieve the list of containers
-        let functions: Vec<Dataset> = {
+        // Finally, retrieve the list of containers (and the images implementing them)
+        let (functions, images): (Vec<Dataset>, Vec<PackageImage>) = {
             // Build the GraphQL file
             #[derive(GraphQLQuery)]
             #[graphql(schema_path = "src/graphql/api_schema.json", query_path = "src/graphql/search_packages.graphql", response_derives = "Debug")]
@@ -531,15 +595,44 @@ impl StateResolver for BraneApiResolver {
 
             // See if any data was returned
             if let Some(data) = graphql_response.data {
-                data.packages.into_iter().map(|package| Dataset { name: package.name, from: Some("<central>".into()) }).collect()
+                let mut images: Vec<PackageImage> = Vec::with_capacity(data.packages.len());
+                for package in &data.packages {
+                    let Some(digest) = &package.digest else { continue };
+                    match package.version.parse::<Version>() {
+                        Ok(version) => images.push(PackageImage {
+                            package: package.name.clone(),
+                            version,
+                            image: Image {
+                                digest:     digest.clone(),
+                                registry:   Some(address.to_string()),
+                                signatures: vec![],
+                                signed_by:  vec![],
+                            },
+                        }),
+                        Err(err) => warn!("Ignoring image of package '{}' with unparsable version '{}': {err}", package.name, package.version),
+                    }
+                }
+                (data.packages.into_iter().map(|package| Dataset { name: package.name, from: Some("<central>".into()) }).collect(), images)
             } else {
                 return Err(BraneApiResolverError::GraphQl { from: url, errs: graphql_response.errors.map(|errs| GraphQlErrors(errs)) });
             }
         };
-        debug!("Retrieved {} functions", functions.len());
+        debug!("Retrieved {} functions ({} with a known image)", functions.len(), images.len());
 
         // Done, return it as one set
-        let state = State { users, locations, datasets, functions };
+        let state = State {
+            users,
+            locations,
+            datasets,
+            functions,
+            images,
+            counters: vec![],
+            duties: vec![],
+            owners: vec![],
+            delegation: None,
+            exhaustive: None,
+            use_case,
+        };
         debug!("Complete state retrieved from '{address}': {state:#?}");
         Ok(state)
     }
@@ -12,12 +12,15 @@
 //!   Entrypoint to the `checker-client` binary.
 //
 
+mod profile;
+
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FResult};
 use std::fs::{self, File};
+use std::future::Future;
 use std::io::{BufRead as _, BufReader};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -25,13 +28,20 @@ use std::sync::Arc;
 use std::time::{self, Duration, SystemTime};
 
 use audit_logger::LogStatement;
+use audit_logger::merkle::{AuditAnchor, InclusionProof, MerkleTree, leaf_hash, live_offset};
+use audit_logger::redact::Redactor;
+use audit_logger::schema::{self, SCHEMA_VERSION};
+use audit_logger::subject::DataSubjectReport;
+use audit_logger::timings::LatencyReport;
+use base64ct::Encoding as _;
 use brane_ast::ast::Edge;
 use brane_ast::locations::Locations;
 use brane_ast::{CompileResult, ParserOptions, Workflow};
 use chrono::DateTime;
-use clap::{Parser, Subcommand};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory as _, FromArgMatches as _, Parser, Subcommand};
 use console::style;
-use deliberation::spec::{Verdict, WorkflowValidationRequest};
+use deliberation::spec::{Granularity, Priority, Verdict, WorkflowValidationRequest};
 use eflint_json::DisplayEFlint;
 use eflint_to_json::compile;
 use enum_debug::EnumDebug;
@@ -40,29 +50,22 @@ use hmac::{Hmac, Mac as _};
 use humanlog::{DebugMode, HumanLogger};
 use jwt::SignWithKey as _;
 use log::{LevelFilter, debug, error, info, trace as trace_log, warn};
-use policy::Policy;
+use policy::{Policy, PolicyMetadata};
+use policy_reasoner::audit_store::{AuditLogQuery, SqliteAuditLogger};
+use policy_reasoner::retention::{ColdStorage, Retainer, RetentionPolicy, verify_archive};
+use policy_reasoner_client::{CheckerClient, PolicyPush};
+use profile::{ClientConfig, Profile, ProfileError};
 use rand::Rng as _;
 use rand::distributions::Alphanumeric;
-use reqwest::blocking::{Client, Request, Response};
-use reqwest::{Method, StatusCode};
-use serde_json::value::RawValue;
+use serde::Serialize;
 use sha2::Sha256;
 use specifications::data::DataIndex;
 use specifications::package::PackageIndex;
-use srv::models::{AddPolicyPostModel, PolicyContentPostModel, SetVersionPostModel};
-
-/***** CONSTANTS *****/
-/// The key to use to create JWTs (for testing purposes only).
-const JWT_KEY: &[u8] = b"wL5hkXZpM929BXRCMgVt1GNdM3cSDovRZsU_mPaOPrNJ8x9TvOv9yb3Ps5GkIqdfCyXWM9HEzh0zNDvc_pA_BqAlLiCtlrSajDtCza42HQgWkE71ocWFB5yMkeVcDWaBwUcDm_lPiy-BdfGjmpdox8H7-mOQoieEMNt8hXQR5E7rA3PC9Ih8lma0pFtkRkuCDYyLmBH7geajvkTE77pB5YVUQ57Qm4uijpBus8083tN2UP-oCqBmpAfZ0BtyGY3oFlRk3sf_HwhSz2gFalYUuK8379hY4BOzuM80pIL18VHVzFgOwRI48RBCk21M5aoFiLMc5Gp9VTKKd9VxQNgExA";
-
-/// The checker path to the policy API's policy list request path.
-const POLICY_ADD_POLICY_PATH: (Method, &'static str) = (Method::POST, "v1/management/policies");
-/// The checker path to the policy API's set-active-policy request path.
-const POLICY_SET_ACTIVE_POLICY_PATH: (Method, &'static str) = (Method::PUT, "v1/management/policies/active");
-/// The checker path to the policy API's get-active-policy request path.
-const POLICY_GET_ACTIVE_POLICY_PATH: (Method, &'static str) = (Method::GET, "v1/management/policies/active");
-/// The checker path to the deliberation API's workflow check request path.
-const DELIB_WORKFLOW_VALIDATION_PATH: (Method, &'static str) = (Method::POST, "v1/deliberation/execute-workflow");
+use srv::archive::SignedPolicyArchive;
+use srv::coverage::{Coverage, CoverageReport, PatternCoverage};
+use srv::models::{ClonePolicyPostModel, ComposePostModel, ImportResultModel, PatchPolicyContentModel, PatchPolicyModel, SimulatePostModel};
+use srv::simulation::{RecordedQuestion, SimulationOutcome, SimulationReport};
+use workflow::lint::{self, Finding, LintConfig, LintLevel, LintSetting};
 
 /***** ERRORS *****/
 /// Defines errors that originate from parsing [`PolicyLanguage`]s.
@@ -81,6 +84,22 @@ impl Display for PolicyLanguageParseError {
 }
 impl Error for PolicyLanguageParseError {}
 
+/// Defines errors that originate from parsing [`PolicyContentArg`]s.
+#[derive(Debug)]
+enum PolicyContentArgParseError {
+    /// It's not of the form `REASONER=FILE`.
+    Malformed { raw: String },
+}
+impl Display for PolicyContentArgParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use PolicyContentArgParseError::*;
+        match self {
+            Malformed { raw } => write!(f, "Failed to parse '{raw}' as policy content (expected '<REASONER>=<FILE>')"),
+        }
+    }
+}
+impl Error for PolicyContentArgParseError {}
+
 /// Defines errors that originate from parsing [`PolicyLanguage`]s.
 #[derive(Debug)]
 enum WorkflowLanguageParseError {
@@ -99,9 +118,21 @@ impl Display for WorkflowLanguageParseError {
 }
 impl Error for WorkflowLanguageParseError {}
 
-/// Defines errors that originate from creating JSON Web Tokens.
+/// Defines errors that originate from finding or creating JSON Web Tokens.
 #[derive(Debug)]
 enum JwtError {
+    /// No token was given, and we weren't asked to mint one either.
+    Missing,
+    /// Failed to read the key set to mint a development token with.
+    KeySetRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the key set to mint a development token with.
+    KeySetParse { path: PathBuf, err: serde_json::Error },
+    /// The key set has no key we can mint a development token with.
+    KeySetEmpty { path: PathBuf },
+    /// The secret of the key to mint a development token with is not valid base64.
+    KeyDecode { path: PathBuf, kid: String, err: base64ct::Error },
+    /// The secret of the key to mint a development token with is not a valid HMAC key.
+    Key { path: PathBuf, kid: String, err: hmac::digest::InvalidLength },
     /// Failed to create/sign a token
     Create { err: jwt::Error },
 }
@@ -109,6 +140,12 @@ impl Display for JwtError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
         use JwtError::*;
         match self {
+            Missing => write!(f, "No JWT given; give one with '--jwt' or in a profile (see `profile add`)"),
+            KeySetRead { path, .. } => write!(f, "Failed to read key set '{}'", path.display()),
+            KeySetParse { path, .. } => write!(f, "Failed to parse key set '{}' as a JWK set", path.display()),
+            KeySetEmpty { path } => write!(f, "Key set '{}' has no HS256 key with a 'kid' to sign a development token with", path.display()),
+            KeyDecode { path, kid, .. } => write!(f, "Failed to decode secret of key '{kid}' in key set '{}' as URL-safe base64", path.display()),
+            Key { path, kid, .. } => write!(f, "Secret of key '{kid}' in key set '{}' is not a valid HMAC key", path.display()),
             Create { .. } => write!(f, "Failed to create new JWT"),
         }
     }
@@ -117,6 +154,12 @@ impl Error for JwtError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         use JwtError::*;
         match self {
+            Missing => None,
+            KeySetRead { err, .. } => Some(err),
+            KeySetParse { err, .. } => Some(err),
+            KeySetEmpty { .. } => None,
+            KeyDecode { err, .. } => Some(err),
+            Key { err, .. } => Some(err),
             Create { err } => Some(err),
         }
     }
@@ -143,6 +186,25 @@ impl FromStr for PolicyLanguage {
     }
 }
 
+/// Defines a policy content given on the command line, as `<REASONER>=<FILE>`.
+#[derive(Clone, Debug)]
+struct PolicyContentArg {
+    /// The reasoner the content is for.
+    reasoner: String,
+    /// The JSON file with the content.
+    path:     PathBuf,
+}
+impl FromStr for PolicyContentArg {
+    type Err = PolicyContentArgParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((reasoner, path)) if !reasoner.is_empty() && !path.is_empty() => Ok(Self { reasoner: reasoner.into(), path: path.into() }),
+            _ => Err(PolicyContentArgParseError::Malformed { raw: s.into() }),
+        }
+    }
+}
+
 /// Defines accepted workflow input languages.
 #[derive(Clone, Copy, Debug, EnumDebug, Eq, Hash, PartialEq)]
 enum WorkflowLanguage {
@@ -183,13 +245,36 @@ struct Arguments {
     address: String,
     /// The port of the checker to connect to.
     #[clap(short, long, global = true, default_value = "3030", help = "The port of the checker we're connecting to.")]
-    port:    u16,
+    port: u16,
     /// The name of the person submitting policies.
-    #[clap(short, long, global = true, help = "The name under which to submit policies. Chooses a random name if omitted.")]
-    name:    Option<String>,
+    #[clap(short, long, global = true, help = "The name to put in tokens minted with '--insecure-dev-token'. Chooses a random name if omitted.")]
+    name: Option<String>,
     /// A JWT that authenticates the user.
-    #[clap(short, long, global = true, help = "A JWT that is used to authenticate with the checker. Ignores '--name' if given.")]
-    jwt:     Option<String>,
+    #[clap(
+        short,
+        long,
+        global = true,
+        help = "A JWT that is used to authenticate with the checker. Required for commands that contact the checker, unless given by a profile."
+    )]
+    jwt: Option<String>,
+    /// The key set to mint a development token with.
+    #[clap(
+        long,
+        global = true,
+        value_name = "JWK_SET",
+        help = "INSECURE, FOR DEVELOPMENT ONLY. If no JWT is given, mints one signed with the first HS256 key in this JWK set (e.g., \
+                './examples/config/jwk_set_expert.json'). This only works against a checker that trusts the key set, which a real deployment never \
+                should."
+    )]
+    insecure_dev_token: Option<PathBuf>,
+    /// The profile to take defaults from.
+    #[clap(
+        long,
+        global = true,
+        help = "The profile in '~/.config/policy-reasoner/client.toml' to take the address, port, JWT and use-case from when they are not given. \
+                Uses the default profile if omitted."
+    )]
+    profile: Option<String>,
 
     /// The toplevel subcommand that decides what to do
     #[clap(subcommand)]
@@ -208,6 +293,69 @@ enum Subcommands {
     /// Audit log-related stuff
     #[clap(name = "log", about = "Groups commands for better understanding audit logs.")]
     Log(LogArguments),
+    /// Workflow-related stuff
+    #[clap(name = "workflow", alias = "wf", about = "Groups commands for inspecting workflows without contacting the checker.")]
+    Workflow(WorkflowArguments),
+    /// Profile-related stuff
+    #[clap(name = "profile", about = "Groups commands for managing the profiles in '~/.config/policy-reasoner/client.toml'.")]
+    Profile(ProfileArguments),
+}
+
+/// Defines arguments for the `checker-client profile` subcommand.
+#[derive(Debug, Parser)]
+struct ProfileArguments {
+    /// Subcommand further
+    #[clap(subcommand)]
+    action: ProfileSubcommands,
+}
+
+/// Defines nested subcommands for the `checker-client profile` subcommand.
+#[derive(Debug, Subcommand)]
+enum ProfileSubcommands {
+    /// Adds or updates a profile.
+    #[clap(
+        name = "add",
+        about = "Adds a profile, or updates it if it already exists. Takes the address and port from '--address' and '--port', and stores the token \
+                 given with '--jwt'."
+    )]
+    Add(ProfileAddArguments),
+    /// Lists all profiles.
+    #[clap(name = "list", about = "Lists all profiles.")]
+    List,
+    /// Shows a profile.
+    #[clap(name = "show", about = "Shows the settings of a profile.")]
+    Show(ProfileNameArguments),
+    /// Removes a profile.
+    #[clap(name = "remove", alias = "rm", about = "Removes a profile (and the token stored for it, if any).")]
+    Remove(ProfileNameArguments),
+    /// Makes a profile the default.
+    #[clap(name = "use", about = "Makes a profile the one used when no '--profile' is given.")]
+    Use(ProfileNameArguments),
+}
+
+/// Defines the arguments for the `checker-client profile add` subcommand.
+#[derive(Debug, Parser)]
+struct ProfileAddArguments {
+    /// The name of the profile.
+    #[clap(name = "NAME", help = "The name of the profile to add or update.")]
+    name:     String,
+    /// Where the JWT of the profile is kept.
+    #[clap(long, conflicts_with = "jwt", help = "The path to a file with the JWT to authenticate with, instead of storing one with '--jwt'.")]
+    jwt_path: Option<PathBuf>,
+    /// The use-case to report.
+    #[clap(long, help = "The use-case to report to the checker for `check` commands.")]
+    use_case: Option<String>,
+    /// Whether to make this the default profile.
+    #[clap(long, help = "If given, makes this the profile used when no '--profile' is given. The first profile always becomes the default.")]
+    default:  bool,
+}
+
+/// Defines the arguments for the `checker-client profile` subcommands that take just a name.
+#[derive(Debug, Parser)]
+struct ProfileNameArguments {
+    /// The name of the profile.
+    #[clap(name = "NAME", help = "The name of the profile.")]
+    name: String,
 }
 
 /// Defines arguments for the `checker-client policy` subcommand.
@@ -230,6 +378,44 @@ enum PolicySubcommands {
     /// Sets a policy  as active in the checker.
     #[clap(name = "set", about = "Makes a policy with the given version ID active in the checker.")]
     Set(PolicySetArguments),
+    /// Composes policy fragments into a new active policy.
+    #[clap(
+        name = "compose",
+        about = "Composes the policies with the given version IDs (e.g., one per department) into a new policy and makes it active."
+    )]
+    Compose(PolicyComposeArguments),
+    /// Clones a policy into a new version.
+    #[clap(name = "clone", about = "Adds a new policy version with the same contents and metadata as the one with the given version ID.")]
+    Clone(PolicyCloneArguments),
+    /// Derives a new policy version by changing only some of its contents.
+    #[clap(
+        name = "patch",
+        about = "Adds a new policy version that only replaces, adds or removes the given contents (e.g., only the POSIX user map) of the one with \
+                 the given version ID."
+    )]
+    Patch(PolicyPatchArguments),
+    /// Replays questions from the audit log against a policy.
+    #[clap(
+        name = "simulate",
+        about = "Replays questions recorded in an audit log against a (non-active) policy version and reports which verdicts would change."
+    )]
+    Simulate(PolicySimulateArguments),
+    /// Reports which parts of a policy are not exercised.
+    #[clap(
+        name = "coverage",
+        about = "Reports which rules of a policy never fired for the questions in an audit log, and which facts asserted by workflows no rule \
+                 depends on."
+    )]
+    Coverage(PolicyCoverageArguments),
+    /// Exports the complete policy store.
+    #[clap(name = "export", about = "Exports all policy versions, the activation history and their metadata as a signed archive.")]
+    Export(PolicyExportArguments),
+    /// Imports a complete policy store.
+    #[clap(
+        name = "import",
+        about = "Replaces all policy versions, the activation history and their metadata in the checker with those in a signed archive."
+    )]
+    Import(PolicyImportArguments),
 }
 
 /// Defines arguments for the `checker-client policy push` subcommand.
@@ -250,6 +436,24 @@ struct PolicyPushArguments {
     /// Whether we're using an external `eflint-to-json` executable or not.
     #[clap(short, long, help = "If given, does not download the Linux x86-64 'eflint-to-json' executable but instead uses the provided one.")]
     eflint_to_json_path: Option<PathBuf>,
+
+    /// Tags to attach to the policy.
+    #[clap(short, long = "tag", help = "A tag to attach to the new policy version. Can be given multiple times.")]
+    tags: Vec<String>,
+    /// The jurisdiction of the policy.
+    #[clap(long, help = "The jurisdiction (e.g., country or legal framework) the new policy version applies to.")]
+    jurisdiction: Option<String>,
+    /// The department owning the policy.
+    #[clap(long, help = "The department owning the new policy version.")]
+    department: Option<String>,
+
+    /// Whether to stream the policy to the checker.
+    #[clap(
+        long,
+        help = "If given, streams the (compiled) policy file to the checker as-is instead of reading it into memory first. Use this for very large \
+                policies. Note that the file is then only validated by the checker."
+    )]
+    stream: bool,
 }
 
 /// Defines arguments for the `checker-client policy get` subcommand.
@@ -268,6 +472,148 @@ struct PolicySetArguments {
     version: i64,
 }
 
+/// Defines arguments for the `checker-client policy compose` subcommand.
+#[derive(Debug, Parser)]
+struct PolicyComposeArguments {
+    /// The IDs of the policies to compose.
+    #[clap(name = "VERSIONS", required = true, help = "The IDs of the policy fragments to compose.")]
+    versions: Vec<i64>,
+
+    /// The description of the composed policy.
+    #[clap(short, long, help = "The description of the composed policy. Omit to generate one from the fragments' IDs.")]
+    description: Option<String>,
+}
+
+/// Defines arguments for the `checker-client policy clone` subcommand.
+#[derive(Debug, Parser)]
+struct PolicyCloneArguments {
+    /// The ID of the policy to clone.
+    #[clap(name = "VERSION", help = "The ID of the policy to clone.")]
+    version: i64,
+
+    /// The description of the clone.
+    #[clap(short, long, help = "The description of the clone. Omit to keep that of the original.")]
+    description: Option<String>,
+    /// The version description of the clone.
+    #[clap(short, long, help = "The version description of the clone. Omit to generate one from the original's ID.")]
+    version_description: Option<String>,
+}
+
+/// Defines arguments for the `checker-client policy patch` subcommand.
+#[derive(Debug, Parser)]
+struct PolicyPatchArguments {
+    /// The ID of the policy to patch.
+    #[clap(name = "VERSION", help = "The ID of the policy to derive the new version from.")]
+    version: i64,
+
+    /// The version description of the new version.
+    #[clap(short, long, help = "The version description of the new version.")]
+    version_description: String,
+    /// The description of the new version.
+    #[clap(short, long, help = "The description of the new version. Omit to keep that of the original.")]
+    description: Option<String>,
+    /// The contents to add or replace.
+    #[clap(
+        short,
+        long,
+        help = "A content to add, or to replace the content for the same reasoner with, as '<REASONER>=<FILE>' where the file is JSON. The reasoner \
+                version of the replaced content is kept. Can be given multiple times."
+    )]
+    content: Vec<PolicyContentArg>,
+    /// The reasoners whose contents to remove.
+    #[clap(short, long, help = "The reasoner whose content to remove. Can be given multiple times.")]
+    remove: Vec<String>,
+}
+
+/// Defines arguments for the `checker-client policy export` subcommand.
+#[derive(Debug, Parser)]
+struct PolicyExportArguments {
+    /// The file to write the archive to.
+    #[clap(short, long, default_value = "./policy-archive.json", help = "The path of the file to write the signed archive to.")]
+    output: PathBuf,
+}
+
+/// Defines arguments for the `checker-client policy import` subcommand.
+#[derive(Debug, Parser)]
+struct PolicyImportArguments {
+    /// The archive to import.
+    #[clap(name = "ARCHIVE", help = "The path of the signed archive to import, as written by 'policy export'.")]
+    path: PathBuf,
+}
+
+/// Defines arguments for the `checker-client policy simulate` subcommand.
+#[derive(Debug, Parser)]
+struct PolicySimulateArguments {
+    /// The ID of the policy to simulate.
+    #[clap(name = "VERSION", help = "The ID of the candidate policy to replay the questions against.")]
+    version: i64,
+
+    /// The audit log to read the questions from.
+    #[clap(short, long, default_value = "./audit-log.log", help = "The path to the audit log to read the questions from.")]
+    log:  PathBuf,
+    /// The first question to replay.
+    #[clap(short, long, help = "If given, only replays questions from the one with this reference ID onwards.")]
+    from: Option<String>,
+    /// The last question to replay.
+    #[clap(short, long, help = "If given, only replays questions up to (and including) the one with this reference ID.")]
+    to:   Option<String>,
+    /// Whether to output the raw report.
+    #[clap(long, help = "If given, prints the report as JSON instead of a human-friendly summary.")]
+    json: bool,
+}
+
+/// Defines arguments for the `checker-client policy coverage` subcommand.
+#[derive(Debug, Parser)]
+struct PolicyCoverageArguments {
+    /// The path to the policy file to analyse.
+    #[clap(name = "POLICY", help = "The path of the policy file to analyse.")]
+    path: PathBuf,
+
+    /// Determines the input language of the policy file.
+    #[clap(
+        short,
+        long,
+        default_value = "eflint",
+        help = "The language of the input file. Can be 'eflint' for eFLINT; or 'eflint_json' or 'eflint-json' for eFLINT JSON."
+    )]
+    language: PolicyLanguage,
+    /// Whether we're using an external `eflint-to-json` executable or not.
+    #[clap(short, long, help = "If given, does not download the Linux x86-64 'eflint-to-json' executable but instead uses the provided one.")]
+    eflint_to_json_path: Option<PathBuf>,
+
+    /// The audit log to read the questions from.
+    #[clap(
+        long,
+        help = "If given, analyses the questions recorded in this audit log. This is the only way to tell which rules fired, since it reads the \
+                reasoner's responses."
+    )]
+    log:     Option<PathBuf>,
+    /// The policy version whose questions to analyse.
+    #[clap(long, requires = "log", help = "If given, only analyses the questions in '--log' that were answered by the policy with this version ID.")]
+    version: Option<i64>,
+
+    /// The workflows to analyse.
+    #[clap(short, long = "workflow", help = "The path of a workflow file to analyse, besides those in '--log'. Can be given multiple times.")]
+    workflows: Vec<PathBuf>,
+    /// Determines the input language of the workflow files.
+    #[clap(
+        long,
+        default_value = "branescript",
+        help = "The language of the '--workflow' files. Can be 'bs', 'bscript' or 'branescript' for BraneScript; or 'wir' for the Brane WIR."
+    )]
+    workflow_language: WorkflowLanguage,
+    /// Determines the package index location.
+    #[clap(short='P', long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/packages"), help = "The location where the package index is read from. Note that this is read in test mode (i.e., `brane`'s default package index does not work)")]
+    packages: PathBuf,
+    /// Determines the data index location.
+    #[clap(short='D', long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/data"), help = "The location where the data index is read from. Note that this is read in test mode (i.e., `brane`'s default data index does not work)")]
+    data: PathBuf,
+
+    /// Whether to output the raw report.
+    #[clap(long, help = "If given, prints the report as JSON instead of a human-friendly summary.")]
+    json: bool,
+}
+
 /// Defines arguments for the `checker-client check` subcommand.
 #[derive(Debug, Parser)]
 struct CheckArguments {
@@ -281,6 +627,18 @@ struct CheckArguments {
     /// A user to designate as receiver of results.
     #[clap(short, long, global = true, help = "Determines who will be reported as receiving the final result of the submitted workflow.")]
     result_owner: Option<String>,
+    /// Whether to ask for an explanation of a denial.
+    #[clap(long, global = true, help = "If given, asks the checker to explain a denial with the facts and policy phrases causing it.")]
+    explain:      bool,
+    /// Whether to ask for the outcome of every task.
+    #[clap(long, global = true, help = "If given, asks the checker to annotate the verdict with whether every task of the workflow is allowed.")]
+    node_results: bool,
+    /// Whether to ask for the first reason for denial only.
+    #[clap(long, global = true, conflicts_with = "exhaustive", help = "If given, asks the checker to stop at the first reason for denial it finds.")]
+    fail_fast:    bool,
+    /// Whether to ask for all reasons for denial.
+    #[clap(long, global = true, help = "If given, asks the checker to look for all reasons for denial instead of only the first.")]
+    exhaustive:   bool,
 }
 
 /// Defines nested subcommands for the `checker-client check` subcommand.
@@ -314,12 +672,73 @@ struct CheckWorkflowArguments {
     data:     PathBuf,
 }
 
+/// Defines arguments for the `checker-client workflow` subcommand.
+#[derive(Debug, Parser)]
+struct WorkflowArguments {
+    /// Subcommand further
+    #[clap(subcommand)]
+    action: WorkflowSubcommands,
+}
+
+/// Defines nested subcommands for the `checker-client workflow` subcommand.
+#[derive(Debug, Subcommand)]
+enum WorkflowSubcommands {
+    /// Lints a workflow.
+    #[clap(name = "lint", about = "Checks a workflow for probable mistakes, using the same rules the checker runs before deliberating.")]
+    Lint(WorkflowLintArguments),
+}
+
+/// Defines arguments for the `checker-client workflow lint` subcommand.
+#[derive(Debug, Parser)]
+struct WorkflowLintArguments {
+    /// The path to the workflow file to lint.
+    #[clap(name = "WORKFLOW", help = "The path of the workflow file to lint.")]
+    path: PathBuf,
+
+    /// Determines the input language of the workflow file.
+    #[clap(
+        short,
+        long,
+        default_value = "branescript",
+        help = "The language of the input file. Can be 'bs', 'bscript' or 'branescript' for BraneScript; or 'wir' for the Brane WIR."
+    )]
+    language: WorkflowLanguage,
+    /// Determines the package index location.
+    #[clap(short='P', long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/packages"), help = "The location where the package index is read from. Note that this is read in test mode (i.e., `brane`'s default package index does not work)")]
+    packages: PathBuf,
+    /// Determines the data index location.
+    #[clap(short='D', long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/data"), help = "The location where the data index is read from. Note that this is read in test mode (i.e., `brane`'s default data index does not work)")]
+    data:     PathBuf,
+    /// Whether to plan the workflow first.
+    #[clap(long, help = "If given, \"trivially\" plans the workflow before linting it, like `check workflow` does.")]
+    plan:     bool,
+    /// The levels of the lint rules.
+    #[clap(
+        long = "lint",
+        value_delimiter = ',',
+        help = "Sets the level of a lint rule, given as '<RULE>=<LEVEL>' (see the checker's '--lint' option). Rules not given run at 'warn'. Can be \
+                given multiple times (or as a ','-separated list)."
+    )]
+    levels:   Vec<LintSetting>,
+    /// Whether to print the findings as JSON.
+    #[clap(long, help = "If given, prints the findings as a JSON list instead of as human-readable text.")]
+    json:     bool,
+}
+
 /// Defines arguments for the `checker-client log` subcommand.
 #[derive(Debug, Parser)]
 struct LogArguments {
     /// The audit log used
     #[clap(short, long, global = true, default_value = "./audit-log.log", help = "The path to the audit log to read.")]
     log: PathBuf,
+    /// The audit log database used instead
+    #[clap(
+        long,
+        global = true,
+        help = "If given, reads the audit log from this SQLite database (as written by a reasoner started with '--audit-db') instead of from \
+                '--log'."
+    )]
+    db:  Option<PathBuf>,
 
     /// Subcommand further
     #[clap(subcommand)]
@@ -332,6 +751,46 @@ enum LogSubcommands {
     /// Attempts to find the reasons why a policy was denied
     #[clap(name = "reason", about = "Reads the audit log to find reasons why the request with given reference ID is denied.")]
     Reason(LogReasonArguments),
+    /// Reports everything the audit log knows about a user or dataset
+    #[clap(
+        name = "subject",
+        about = "Reads the audit log to report all requests, verdicts and policy versions that touched the given user or dataset (e.g., for a data \
+                 subject access request)."
+    )]
+    Subject(LogSubjectArguments),
+    /// Reports how long questions took
+    #[clap(
+        name = "latency",
+        about = "Reads the audit log to report how long questions spent resolving their state, waiting for the reasoner and in the reasoner \
+                 connector (e.g., for capacity planning)."
+    )]
+    Latency(LogLatencyArguments),
+    /// Produces inclusion proofs for the entries of a request
+    #[clap(name = "prove", about = "Produces Merkle inclusion proofs for the audit log entries of the request with the given reference ID.")]
+    Prove(LogProveArguments),
+    /// Verifies inclusion proofs
+    #[clap(name = "verify-proof", about = "Verifies Merkle inclusion proofs produced by `log prove`, optionally against a published root.")]
+    VerifyProof(LogVerifyProofArguments),
+    /// Rewrites the log in the current schema
+    #[clap(
+        name = "migrate",
+        about = "Rewrites the statements in the audit log that were written in an older schema to the current one, re-anchoring the segments they \
+                 are in. The original log and anchors are kept next to them."
+    )]
+    Migrate(LogMigrateArguments),
+    /// Moves expired segments to cold storage
+    #[clap(
+        name = "retain",
+        about = "Moves the anchored segments of the audit log whose statements all outlived their retention to cold storage, removing them from the \
+                 live log. The anchor of the last moved segment is kept as the head of the live anchors."
+    )]
+    Retain(LogRetainArguments),
+    /// Verifies the archive against the live log
+    #[clap(
+        name = "verify-archive",
+        about = "Checks that the segments moved to cold storage by `log retain` and the live segments of the audit log together are unbroken."
+    )]
+    VerifyArchive(LogVerifyArchiveArguments),
 }
 
 /// Defines the arguments for the `checker-client log reason` subcommand.
@@ -342,48 +801,196 @@ struct LogReasonArguments {
     reference_id: String,
 }
 
+/// Defines the arguments for the `checker-client log subject` subcommand.
+#[derive(Debug, Parser)]
+struct LogSubjectArguments {
+    /// The identifier to search for.
+    #[clap(name = "SUBJECT", help = "The identifier of the user or dataset to report on.")]
+    subject: String,
+    /// The key used to pseudonymize the audit log, if any.
+    #[clap(
+        long,
+        help = "If given, the audit log is assumed to be pseudonymized with the key in this file, and the subject's pseudonym is searched for \
+                instead of its name."
+    )]
+    pseudonym_key: Option<PathBuf>,
+    /// Whether to output the raw report.
+    #[clap(long, help = "If given, prints the report as JSON instead of a human-friendly one.")]
+    json: bool,
+}
+
+/// Defines the arguments for the `checker-client log latency` subcommand.
+#[derive(Debug, Parser)]
+struct LogLatencyArguments {
+    /// Whether to output the raw report.
+    #[clap(long, help = "If given, prints the report as JSON instead of a human-friendly one.")]
+    json: bool,
+}
+
+/// Defines the arguments for the `checker-client log prove` subcommand.
+#[derive(Debug, Parser)]
+struct LogProveArguments {
+    /// The reference ID to prove the entries of.
+    #[clap(name = "REFERENCE_ID", help = "The reference ID of the request to prove the audit log entries of.")]
+    reference_id: String,
+    /// The anchors recorded for the audit log.
+    #[clap(long, default_value = "./audit-log.anchors", help = "The path to the anchors recorded for the audit log.")]
+    anchors: PathBuf,
+    /// Where to write the proofs to.
+    #[clap(short, long, help = "If given, writes the proofs as JSON to this file (to be checked with `log verify-proof`).")]
+    output: Option<PathBuf>,
+}
+
+/// Defines the arguments for the `checker-client log verify-proof` subcommand.
+#[derive(Debug, Parser)]
+struct LogVerifyProofArguments {
+    /// The file with the proofs.
+    #[clap(name = "PROOF", help = "The path to the proofs written by `log prove`.")]
+    proof: PathBuf,
+    /// The root published to the notary.
+    #[clap(short, long, help = "If given, also checks that the proofs lead to this (hex-encoded) Merkle root, as published to the notary.")]
+    root:  Option<String>,
+}
+
+/// Defines the arguments for the `checker-client log migrate` subcommand.
+#[derive(Debug, Parser)]
+struct LogMigrateArguments {
+    /// The anchors recorded for the audit log.
+    #[clap(long, default_value = "./audit-log.anchors", help = "The path to the anchors recorded for the audit log, if any.")]
+    anchors: PathBuf,
+}
+
+/// Defines the arguments for the `checker-client log retain` subcommand.
+#[derive(Debug, Parser)]
+struct LogRetainArguments {
+    /// The anchors recorded for the audit log.
+    #[clap(long, default_value = "./audit-log.anchors", help = "The path to the anchors recorded for the audit log.")]
+    anchors: PathBuf,
+    /// The retention policy.
+    #[clap(
+        long,
+        help = "The path to a YAML file with the retention policy: the number of days statements are kept by default ('default_days') and per \
+                statement kind ('kinds', e.g., 'AUTH-SUCCESS: 30'). Statements without a window are kept forever."
+    )]
+    policy:  PathBuf,
+    /// Where to move expired segments to.
+    #[clap(
+        short,
+        long,
+        help = "Where to move expired segments to: a directory ('file:<DIR>') or a base URL that accepts plain, unsigned PUT requests \
+                ('http(s)://...')."
+    )]
+    storage: ColdStorage,
+}
+
+/// Defines the arguments for the `checker-client log verify-archive` subcommand.
+#[derive(Debug, Parser)]
+struct LogVerifyArchiveArguments {
+    /// The anchors recorded for the audit log.
+    #[clap(long, default_value = "./audit-log.anchors", help = "The path to the anchors recorded for the audit log.")]
+    anchors: PathBuf,
+}
+
 /***** HELPER FUNCTIONS *****/
-/// Given a potentially given JWT, uses it or generates a new one.
+/// Runs a request to the checker (or anything else asynchronous) to completion, exiting if it fails.
 ///
 /// # Arguments
-/// - `name`: The name to embed in the JWT if we're generating one.
-/// - `jwt`: The JWT given by the user, or [`None`] if they didn't.
+/// - `req`: The request to run, e.g., as returned by a [`CheckerClient`] method.
 ///
 /// # Returns
-/// A new, already serialized (and encoded!) JSON web token.
-fn resolve_jwt(name: impl Into<String>, jwt: Option<String>) -> Result<String, JwtError> {
-    match jwt {
-        Some(jwt) => {
-            debug!("Using given JWT '{jwt}'");
-            Ok(jwt)
+/// The checker's (parsed) reply.
+fn run<T, E: Error>(req: impl Future<Output = Result<T, E>>) -> T {
+    let runtime: tokio::runtime::Runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            error!("{}", trace!(("Failed to create runtime for talking to the checker"), err));
+            std::process::exit(1);
         },
-        None => {
-            // Create a key from the internal one
-            let key: Hmac<Sha256> = match Hmac::new_from_slice(JWT_KEY) {
-                Ok(key) => key,
-                Err(err) => {
-                    error!("{}", trace!(("Failed to create HMAC key from private key"), err));
-                    std::process::exit(1);
-                },
-            };
+    };
+    match runtime.block_on(req) {
+        Ok(res) => res,
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    }
+}
 
-            // Generate the claims
-            let mut claims: BTreeMap<&str, String> = BTreeMap::new();
-            claims.insert("sub", "1234567890".into());
-            claims.insert("username", name.into());
-            claims.insert("iat", SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap().as_secs().to_string());
-            claims
-                .insert("exp", (SystemTime::now() + Duration::from_secs(24 * 3600)).duration_since(time::UNIX_EPOCH).unwrap().as_secs().to_string());
-
-            // Create a JWT with it
-            match claims.sign_with_key(&key) {
-                Ok(jwt) => {
-                    debug!("Using generated JWT '{jwt}'");
-                    Ok(jwt)
-                },
-                Err(err) => Err(JwtError::Create { err }),
-            }
+/// Prints something as (pretty) JSON, exiting if it cannot be serialized.
+///
+/// # Arguments
+/// - `value`: The thing to print.
+fn print_json(value: &impl Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(value) => println!("{value}"),
+        Err(err) => {
+            error!("{}", trace!(("Failed to serialize checker response to JSON"), err));
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Shows the checker's (parsed) reply to the user.
+///
+/// # Arguments
+/// - `reply`: The reply to show.
+fn print_reply(reply: &impl Serialize) {
+    println!("{}", style("Checker replied with:").bold());
+    print_json(reply);
+    println!();
+}
+
+/// Given a potentially given JWT, uses it or (if explicitly asked to) mints an insecure development token.
+///
+/// # Arguments
+/// - `name`: The name to embed in the JWT if we're minting one.
+/// - `jwt`: The JWT given by the user, or [`None`] if they didn't.
+/// - `dev_key_set`: The JWK set given with `--insecure-dev-token`, if any, of which the first HS256 key signs the minted token.
+///
+/// # Returns
+/// A new, already serialized (and encoded!) JSON web token.
+///
+/// # Errors
+/// This function errors if no JWT was given and we're not asked to mint one, or if minting one failed.
+fn resolve_jwt(name: impl Into<String>, jwt: Option<String>, dev_key_set: Option<&Path>) -> Result<String, JwtError> {
+    if let Some(jwt) = jwt {
+        debug!("Using given JWT '{jwt}'");
+        return Ok(jwt);
+    }
+    let Some(path) = dev_key_set else { return Err(JwtError::Missing) };
+    warn!("Minting an INSECURE development token with key set '{}'; never use this against a real deployment", path.display());
+
+    // Find the key to sign with (the checker finds it again by its ID)
+    let raw: String = fs::read_to_string(path).map_err(|err| JwtError::KeySetRead { path: path.into(), err })?;
+    let set: serde_json::Value = serde_json::from_str(&raw).map_err(|err| JwtError::KeySetParse { path: path.into(), err })?;
+    let Some((kid, secret)) = set.get("keys").and_then(serde_json::Value::as_array).into_iter().flatten().find_map(|key| {
+        if key.get("kty")?.as_str()? != "oct" || key.get("alg").and_then(serde_json::Value::as_str).unwrap_or("HS256") != "HS256" {
+            return None;
+        }
+        Some((key.get("kid")?.as_str()?.to_string(), key.get("k")?.as_str()?.to_string()))
+    }) else {
+        return Err(JwtError::KeySetEmpty { path: path.into() });
+    };
+    let secret: Vec<u8> = base64ct::Base64Url::decode_vec(&secret).map_err(|err| JwtError::KeyDecode { path: path.into(), kid: kid.clone(), err })?;
+    let key: Hmac<Sha256> = Hmac::new_from_slice(&secret).map_err(|err| JwtError::Key { path: path.into(), kid: kid.clone(), err })?;
+
+    // Generate the claims
+    let now: u64 = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap().as_secs();
+    let claims: serde_json::Value = serde_json::json!({
+        "sub": "1234567890",
+        "username": name.into(),
+        "iat": now,
+        "exp": now + Duration::from_secs(24 * 3600).as_secs(),
+    });
+
+    // Create a JWT with it
+    let header = jwt::Header { algorithm: jwt::AlgorithmType::Hs256, key_id: Some(kid), ..Default::default() };
+    match jwt::Token::new(header, claims).sign_with_key(&key) {
+        Ok(token) => {
+            debug!("Using minted JWT '{}'", token.as_str());
+            Ok(token.as_str().into())
         },
+        Err(err) => Err(JwtError::Create { err }),
     }
 }
 
@@ -534,21 +1141,625 @@ fn line_is_log_line(line: &str) -> Option<usize> {
     rem.chars().next().filter(|c| *c == ' ').map(|_| brack_pos + 1 + brack_pos2 + 2)
 }
 
-/***** ENTRYPOINT *****/
-fn main() {
-    // Parse the args
-    let args = Arguments::parse();
+/// Reads a workflow file as a Brane WIR, compiling it first if it is written in BraneScript.
+///
+/// Exits the process with an error if the file could not be read or compiled.
+///
+/// # Arguments
+/// - `path`: The path to the workflow file to read.
+/// - `language`: The [`WorkflowLanguage`] the file is written in.
+/// - `packages`: The location of the package index to compile against.
+/// - `data`: The location of the data index to compile against.
+///
+/// # Returns
+/// The read [`Workflow`].
+fn read_workflow(path: &Path, language: WorkflowLanguage, packages: &Path, data: &Path) -> Workflow {
+    // Read the data index (we'll need it for planning)
+    let dindex: DataIndex = brane_shr::utilities::create_data_index_from(data);
+
+    // Match on the input language
+    match language {
+        WorkflowLanguage::BraneScript => {
+            debug!("Compiling input file '{}' to a Brane WIR...", path.display());
+
+            // Open the input file
+            debug!("Reading input file '{}' as BraneScript", path.display());
+            let input: String = match fs::read_to_string(path) {
+                Ok(input) => input,
+                Err(err) => {
+                    error!("{}", trace!(("Failed to read input file '{}'", path.display()), err));
+                    std::process::exit(1);
+                },
+            };
 
-    // Setup the logger
-    if let Err(err) = HumanLogger::terminal(DebugMode::from_flags(args.trace, args.debug)).init() {
-        eprintln!("WARNING: Failed to setup logger: {err} (logging disabled for this session)");
-    }
-    info!("{} v{}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
+            // Open the package index, too
+            let pindex: PackageIndex = brane_shr::utilities::create_package_index_from(packages);
+
+            // Run the compiler
+            debug!("Running eflint-to-json compiler on '{}'...", path.display());
+            match brane_ast::compile_program(input.as_bytes(), &pindex, &dindex, &ParserOptions::bscript()) {
+                CompileResult::Workflow(workflow, warns) => {
+                    // Print warnings (if any)
+                    let spath: String = path.display().to_string();
+                    for warn in warns {
+                        warn.prettyprint(&spath, &input)
+                    }
+                    workflow
+                },
+                CompileResult::Err(errs) => {
+                    // Print the errors that occurred
+                    let spath: String = path.display().to_string();
+                    for err in errs {
+                        err.prettyprint(&spath, &input);
+                    }
+                    error!("Failed to compile input file '{spath}' (see output above)");
+                    std::process::exit(1);
+                },
+                CompileResult::Eof(err) => {
+                    // Print the errors that occurred
+                    let spath: String = path.display().to_string();
+                    err.prettyprint(&spath, input);
+                    error!("Failed to compile input file '{spath}' (see output above)");
+                    std::process::exit(1);
+                },
 
-    // Resolve the name
-    let name: Cow<str> = match args.name {
-        Some(name) => Cow::Owned(name),
-        None => Cow::Borrowed(names::three::usualcase::rand()),
+                CompileResult::Program(_, _) | CompileResult::Unresolved(_, _) => unreachable!(),
+            }
+        },
+        WorkflowLanguage::Wir => {
+            // Open the input file
+            debug!("Reading input file '{}' as Brane WIR...", path.display());
+            let input: String = match fs::read_to_string(path) {
+                Ok(input) => input,
+                Err(err) => {
+                    error!("{}", trace!(("Failed to read input file '{}'", path.display()), err));
+                    std::process::exit(1);
+                },
+            };
+
+            // Deserialize
+            match serde_json::from_str(&input) {
+                Ok(wir) => wir,
+                Err(err) => {
+                    error!("{}", trace!(("Failed to parse input file '{}' as Brane WIR", path.display()), err));
+                    std::process::exit(1);
+                },
+            }
+        },
+    }
+}
+
+/// Compiles a policy file to eFLINT JSON, if it isn't already.
+///
+/// Exits the process with an error if the policy could not be compiled.
+///
+/// # Arguments
+/// - `path`: The path to the policy file.
+/// - `language`: The [`PolicyLanguage`] the file is written in.
+/// - `eflint_to_json_path`: If given, uses this `eflint-to-json` executable instead of downloading it.
+///
+/// # Returns
+/// The path to the eFLINT JSON file, which is a temporary file if the policy had to be compiled.
+fn compile_policy<'p>(path: &'p Path, language: PolicyLanguage, eflint_to_json_path: Option<&Path>) -> Cow<'p, Path> {
+    match language {
+        PolicyLanguage::EFlint => {
+            let json_path: PathBuf = env::temp_dir()
+                .join(format!("policy-{}.json", rand::thread_rng().sample_iter(Alphanumeric).take(8).map(char::from).collect::<String>()));
+            debug!("Compiling input file '{}' to eFLINT JSON file '{}'...", path.display(), json_path.display());
+
+            // Open the output file
+            debug!("Creating output file '{}'...", json_path.display());
+            let handle: File = match File::create(&json_path) {
+                Ok(handle) => handle,
+                Err(err) => {
+                    error!("{}", trace!(("Failed to create output file '{}'", json_path.display()), err));
+                    std::process::exit(1);
+                },
+            };
+
+            // Run the compiler
+            debug!("Running eflint-to-json compiler on '{}'...", path.display());
+            if let Err(err) = compile(path, handle, eflint_to_json_path) {
+                error!("{}", trace!(("Failed to compile input file '{}'", path.display()), err));
+                std::process::exit(1);
+            };
+            Cow::Owned(json_path)
+        },
+        PolicyLanguage::EFlintJson => Cow::Borrowed(path),
+    }
+}
+
+/// "Trivially" plans all edges in a workflow, including those of its functions.
+///
+/// # Arguments
+/// - `wir`: The [`Workflow`] to plan.
+fn plan_workflow(wir: &mut Workflow) {
+    // Plan the main workflow
+    let mut graph: Arc<Vec<Edge>> = Arc::new(vec![]);
+    std::mem::swap(&mut graph, &mut wir.graph);
+    let mut graph: Vec<Edge> = Arc::into_inner(graph).unwrap();
+    plan_wir(&mut graph, (usize::MAX, 0), None);
+    let mut graph: Arc<Vec<Edge>> = Arc::new(graph);
+    std::mem::swap(&mut wir.graph, &mut graph);
+
+    // Plan the functions in the workflow
+    let mut funcs: Arc<HashMap<usize, Vec<Edge>>> = Arc::new(HashMap::new());
+    std::mem::swap(&mut funcs, &mut wir.funcs);
+    let mut funcs: HashMap<usize, Vec<Edge>> = Arc::into_inner(funcs).unwrap();
+    for (_, edges) in &mut funcs {
+        plan_wir(edges, (usize::MAX, 0), None);
+    }
+    let mut funcs: Arc<HashMap<usize, Vec<Edge>>> = Arc::new(funcs);
+    std::mem::swap(&mut wir.funcs, &mut funcs);
+}
+
+/// Reads all statements from an audit log, together with the timestamps at which they were logged.
+///
+/// Exits the process with an error if the log could not be read or parsed.
+///
+/// # Arguments
+/// - `path`: The path to the audit log to read.
+///
+/// # Returns
+/// The [`LogStatement`]s in the log and their timestamps, in order.
+fn read_timestamped_audit_log(path: &Path) -> Vec<(String, LogStatement<'static>)> {
+    // Open the log file
+    debug!("Opening log file '{}'...", path.display());
+    let handle: BufReader<File> = match File::open(path) {
+        Ok(handle) => BufReader::new(handle),
+        Err(err) => {
+            error!("{}", trace!(("Failed to open log file '{}'", path.display()), err));
+            std::process::exit(1);
+        },
+    };
+
+    // Separate the log into statements
+    debug!("Finding log statements...");
+    let mut buf: String = String::new();
+    let mut timestamp: String = String::new();
+    let mut statements: Vec<(String, LogStatement)> = Vec::new();
+    for line in handle.lines() {
+        // Unwrap the line
+        let line: String = match line {
+            Ok(line) => line,
+            Err(err) => {
+                error!("{}", trace!(("Failed to read line from '{}'", path.display()), err));
+                std::process::exit(1);
+            },
+        };
+
+        // See if the line begins with what we want
+        if let Some(start_pos) = line_is_log_line(&line) {
+            // Flush the buffer if we have any to flush
+            if !buf.is_empty() {
+                // Attempt to parse the non-intro part as a LogStatement
+                match schema::parse(&buf) {
+                    Ok(stmt) => {
+                        statements.push((timestamp.clone(), stmt));
+                    },
+                    Err(err) => {
+                        error!(
+                            "Failed to parse audit log line(s) as a log statement: {}\n\nLine(s):\n{}\n{}\n{}\n",
+                            err.trace(),
+                            (0..80).map(|_| '-').collect::<String>(),
+                            buf,
+                            (0..80).map(|_| '-').collect::<String>()
+                        );
+                        std::process::exit(1);
+                    },
+                };
+                // Clean the buffer to continue
+                buf.clear();
+            }
+
+            // Add the new line to the buffer, remembering when it was logged (i.e., the second bracketed part)
+            let intro: &str = line[..start_pos].trim_end().trim_end_matches(']');
+            timestamp = intro[intro.rfind('[').map(|pos| pos + 1).unwrap_or(0)..].to_string();
+            buf.push_str(&line[start_pos..]);
+        } else {
+            // Add to the buffer
+            buf.push('\n');
+            buf.push_str(&line);
+        }
+    }
+
+    // Parse the remainder of the buffer, too
+    if !buf.is_empty() {
+        // Attempt to parse the non-intro part as a LogStatement
+        match schema::parse(&buf) {
+            Ok(stmt) => {
+                statements.push((timestamp.clone(), stmt));
+            },
+            Err(err) => {
+                error!(
+                    "Failed to parse audit log line(s) as a log statement: {}\n\nLine(s):\n{}\n{}\n{}\n",
+                    err.trace(),
+                    (0..80).map(|_| '-').collect::<String>(),
+                    buf,
+                    (0..80).map(|_| '-').collect::<String>()
+                );
+                std::process::exit(1);
+            },
+        };
+    }
+
+    statements
+}
+
+/// Reads all statements from an audit log.
+///
+/// Exits the process with an error if the log could not be read or parsed.
+///
+/// # Arguments
+/// - `path`: The path to the audit log to read.
+///
+/// # Returns
+/// The [`LogStatement`]s in the log, in order.
+fn read_audit_log(path: &Path) -> Vec<LogStatement<'static>> { read_timestamped_audit_log(path).into_iter().map(|(_, stmt)| stmt).collect() }
+
+/// Reads the statements matching a query from an audit log database, together with the timestamps at which they were logged.
+///
+/// Exits the process with an error if the database could not be read or any statement in it could not be parsed.
+///
+/// # Arguments
+/// - `path`: The path to the database to read.
+/// - `query`: The [`AuditLogQuery`] that selects the statements to read.
+///
+/// # Returns
+/// The matching [`LogStatement`]s and their timestamps, in order.
+fn read_timestamped_audit_store(path: &Path, query: &AuditLogQuery) -> Vec<(String, LogStatement<'static>)> {
+    debug!("Querying audit log database '{}'...", path.display());
+    match SqliteAuditLogger::new(String::new(), path.display().to_string()).query(query) {
+        Ok(entries) => entries
+            .into_iter()
+            .map(|entry| (entry.logged_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string(), entry.statement))
+            .collect(),
+        Err(err) => {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Returns where the original of a file is kept when it is rewritten.
+///
+/// # Arguments
+/// - `path`: The path of the file to rewrite.
+///
+/// # Returns
+/// The path with `.orig` appended to it.
+fn original_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".orig");
+    name.into()
+}
+
+/// Rewrites the statements in an audit log that were written in an older schema to the current one.
+///
+/// Segments of the log that contain rewritten entries are re-anchored, remembering their original root such that the
+/// original entries (kept next to the log) can still be proven against any root that was published for them.
+///
+/// Exits the process with an error if the log or its anchors could not be read or written, if any statement could not be
+/// upgraded or if the log does not match its anchors (in which case migrating would hide that).
+///
+/// # Arguments
+/// - `path`: The path to the audit log to migrate.
+/// - `anchors_path`: The path to the anchors recorded for the audit log. May not exist if it was never anchored.
+fn migrate_audit_log(path: &Path, anchors_path: &Path) {
+    // Read the log as it is anchored, i.e., as complete lines
+    let raw: String = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) => {
+            error!("{}", trace!(("Failed to read log file '{}'", path.display()), err));
+            std::process::exit(1);
+        },
+    };
+    let original: Vec<&str> = raw.split_inclusive('\n').map(|line| line.strip_suffix('\n').unwrap_or(line)).collect();
+
+    // Upgrade the statements that need it
+    let mut entries: Vec<String> = Vec::with_capacity(original.len());
+    let mut upgraded: BTreeMap<u64, usize> = BTreeMap::new();
+    for (i, line) in original.iter().enumerate() {
+        let Some(start) = line_is_log_line(line) else {
+            entries.push(line.to_string());
+            continue;
+        };
+        let mut stmt: serde_json::Value = match serde_json::from_str(&line[start..]) {
+            Ok(stmt) => stmt,
+            Err(err) => {
+                error!("{}", trace!(("Failed to parse statement on line {} of log file '{}'", i + 1, path.display()), err));
+                std::process::exit(1);
+            },
+        };
+        let version: u64 = match schema::upgrade(&mut stmt) {
+            Ok(version) => version,
+            Err(err) => {
+                error!("{}", trace!(("Failed to upgrade statement on line {} of log file '{}'", i + 1, path.display()), err));
+                std::process::exit(1);
+            },
+        };
+        if version == SCHEMA_VERSION {
+            entries.push(line.to_string());
+            continue;
+        }
+        // Write it like the reasoner would have
+        let stmt: LogStatement = match schema::from_value(stmt) {
+            Ok(stmt) => stmt,
+            Err(err) => {
+                error!("{}", trace!(("Failed to upgrade statement on line {} of log file '{}'", i + 1, path.display()), err));
+                std::process::exit(1);
+            },
+        };
+        let message: String = match serde_json::to_string(&stmt.versioned()) {
+            Ok(message) => message,
+            Err(err) => {
+                error!("{}", trace!(("Failed to serialize statement on line {} of log file '{}'", i + 1, path.display()), err));
+                std::process::exit(1);
+            },
+        };
+        entries.push(format!("{}{message}", &line[..start]));
+        *upgraded.entry(version).or_default() += 1;
+    }
+    if upgraded.is_empty() {
+        println!("Audit log '{}' is already at schema version {}", path.display(), style(SCHEMA_VERSION).bold());
+        return;
+    }
+
+    // Re-anchor the segments with changed entries, but only if they weren't changed before
+    let mut anchors: Vec<AuditAnchor> = match fs::read_to_string(anchors_path) {
+        Ok(raw) => match raw.lines().filter(|line| !line.trim().is_empty()).map(serde_json::from_str).collect() {
+            Ok(anchors) => anchors,
+            Err(err) => {
+                error!("{}", trace!(("Failed to parse anchors file '{}'", anchors_path.display()), err));
+                std::process::exit(1);
+            },
+        },
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            error!("{}", trace!(("Failed to read anchors file '{}'", anchors_path.display()), err));
+            std::process::exit(1);
+        },
+    };
+    let root_of = |lines: &[&str]| hex::encode(MerkleTree::new(lines.iter().map(|line| leaf_hash(line.as_bytes())).collect()).root());
+    let offset: usize = live_offset(&anchors);
+    let mut reanchored: usize = 0;
+    for anchor in anchors.iter_mut().filter(|anchor| anchor.archived.is_none()) {
+        let segment = anchor.first.saturating_sub(offset)..anchor.end().saturating_sub(offset);
+        if segment.end > original.len() || !root_of(&original[segment.clone()]).eq_ignore_ascii_case(&anchor.root) {
+            error!("Entries {}-{} of log file '{}' do not match their anchor; refusing to migrate", segment.start + 1, segment.end, path.display());
+            std::process::exit(1);
+        }
+        if original[segment.clone()].iter().zip(&entries[segment.clone()]).all(|(old, new)| *old == new) {
+            continue;
+        }
+        let new: Vec<&str> = entries[segment].iter().map(String::as_str).collect();
+        let old: String = std::mem::replace(&mut anchor.root, root_of(&new));
+        anchor.migrated_from.get_or_insert(old);
+        reanchored += 1;
+    }
+
+    // Keep the originals, then write the migrated versions
+    let mut log: String = entries.join("\n");
+    if raw.ends_with('\n') {
+        log.push('\n');
+    }
+    let mut files: Vec<(&Path, String)> = vec![(path, log)];
+    if reanchored > 0 {
+        let mut raw_anchors: String = String::new();
+        for anchor in &anchors {
+            match serde_json::to_string(anchor) {
+                Ok(line) => {
+                    raw_anchors.push_str(&line);
+                    raw_anchors.push('\n');
+                },
+                Err(err) => {
+                    error!("{}", trace!(("Failed to serialize audit log anchor"), err));
+                    std::process::exit(1);
+                },
+            }
+        }
+        files.push((anchors_path, raw_anchors));
+    }
+    if let Some(original) = files.iter().map(|(path, _)| original_path(path)).find(|original| original.exists()) {
+        error!("'{}' already exists; move it away before migrating again", original.display());
+        std::process::exit(1);
+    }
+    for (path, contents) in files {
+        let original: PathBuf = original_path(path);
+        if let Err(err) = fs::copy(path, &original) {
+            error!("{}", trace!(("Failed to keep original of '{}' at '{}'", path.display(), original.display()), err));
+            std::process::exit(1);
+        }
+        if let Err(err) = fs::write(path, contents) {
+            error!("{}", trace!(("Failed to write migrated '{}'", path.display()), err));
+            std::process::exit(1);
+        }
+    }
+
+    for (version, count) in upgraded {
+        println!("Upgraded {} statement(s) from schema version {version} to {SCHEMA_VERSION}", style(count).bold());
+    }
+    println!("Re-anchored {} segment(s); the originals are kept with an '.orig' extension", style(reanchored).bold());
+}
+
+/// Returns whether an argument was given on the command line (rather than taken from its default).
+#[inline]
+fn given(matches: &ArgMatches, id: &str) -> bool { matches!(matches.value_source(id), Some(ValueSource::CommandLine)) }
+
+/// Fills in the arguments that were not given on the command line from the chosen profile, if any.
+///
+/// # Arguments
+/// - `args`: The [`Arguments`] to complete.
+/// - `matches`: The [`ArgMatches`] they were parsed from, to see what was given explicitly.
+///
+/// # Errors
+/// This function errors if the config could not be read, if the profile asked for does not exist or if its JWT could not be read.
+fn apply_profile(args: &mut Arguments, matches: &ArgMatches) -> Result<(), ProfileError> {
+    let path: PathBuf = match ClientConfig::path() {
+        Ok(path) => path,
+        // Without a config directory there can only be a problem if a profile was asked for
+        Err(err) if args.profile.is_some() => return Err(err),
+        Err(_) => return Ok(()),
+    };
+    let config: ClientConfig = ClientConfig::load(&path)?;
+    let Some(profile) = config.resolve(args.profile.as_deref(), &path)? else { return Ok(()) };
+    debug!("Using profile '{}'", args.profile.as_deref().or(config.default.as_deref()).unwrap_or_default());
+
+    if !given(matches, "address") {
+        if let Some(address) = &profile.address {
+            args.address = address.clone();
+        }
+    }
+    if !given(matches, "port") {
+        if let Some(port) = profile.port {
+            args.port = port;
+        }
+    }
+    if args.jwt.is_none() {
+        args.jwt = profile.jwt()?;
+    }
+    if let (Subcommands::Check(check), Some(check_matches)) = (&mut args.subcommand, matches.subcommand_matches("check")) {
+        if !given(check_matches, "use_case") {
+            if let Some(use_case) = &profile.use_case {
+                check.use_case = use_case.clone();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles the `checker-client profile` subcommands.
+///
+/// # Arguments
+/// - `args`: The [`Arguments`] given, of which the address, port and JWT are stored by `profile add`.
+/// - `matches`: The [`ArgMatches`] they were parsed from, to see what was given explicitly.
+/// - `profile`: The [`ProfileArguments`] of the subcommand.
+///
+/// # Errors
+/// This function errors if the config could not be read or written, or if a profile does not exist.
+fn handle_profile(args: &Arguments, matches: &ArgMatches, profile: &ProfileArguments) -> Result<(), ProfileError> {
+    let path: PathBuf = ClientConfig::path()?;
+    let mut config: ClientConfig = ClientConfig::load(&path)?;
+    match &profile.action {
+        ProfileSubcommands::Add(add) => {
+            info!("Handling `profile add` subcommand");
+
+            let entry: &mut Profile = config.profiles.entry(add.name.clone()).or_default();
+            if given(matches, "address") {
+                entry.address = Some(args.address.clone());
+            }
+            if given(matches, "port") {
+                entry.port = Some(args.port);
+            }
+            if let Some(jwt) = &args.jwt {
+                entry.jwt_path = Some(ClientConfig::store_jwt(&add.name, jwt)?);
+            } else if let Some(jwt_path) = &add.jwt_path {
+                entry.jwt_path = Some(jwt_path.clone());
+            }
+            if let Some(use_case) = &add.use_case {
+                entry.use_case = Some(use_case.clone());
+            }
+            if add.default || config.default.is_none() {
+                config.default = Some(add.name.clone());
+            }
+            config.save(&path)?;
+            println!("Saved profile {} to {}", style(&add.name).bold(), style(path.display()).bold());
+        },
+
+        ProfileSubcommands::List => {
+            info!("Handling `profile list` subcommand");
+
+            if config.profiles.is_empty() {
+                println!("No profiles in {}", style(path.display()).bold());
+            }
+            for (name, entry) in &config.profiles {
+                let default: bool = config.default.as_deref() == Some(name.as_str());
+                println!(
+                    "{} {} ({}:{})",
+                    if default { "*" } else { " " },
+                    style(name).bold(),
+                    entry.address.as_deref().unwrap_or("localhost"),
+                    entry.port.unwrap_or(3030)
+                );
+            }
+        },
+
+        ProfileSubcommands::Show(show) => {
+            info!("Handling `profile show` subcommand");
+
+            let Some(entry) = config.profiles.get(&show.name) else { return Err(ProfileError::Unknown { name: show.name.clone(), path }) };
+            println!("Profile {}:", style(&show.name).bold());
+            println!("  - address  : {}", entry.address.as_deref().unwrap_or("-"));
+            println!("  - port     : {}", entry.port.map(|port| port.to_string()).unwrap_or_else(|| "-".into()));
+            println!("  - JWT file : {}", entry.jwt_path.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "-".into()));
+            println!("  - use-case : {}", entry.use_case.as_deref().unwrap_or("-"));
+        },
+
+        ProfileSubcommands::Remove(remove) => {
+            info!("Handling `profile remove` subcommand");
+
+            let Some(entry) = config.profiles.remove(&remove.name) else { return Err(ProfileError::Unknown { name: remove.name.clone(), path }) };
+            if config.default.as_deref() == Some(remove.name.as_str()) {
+                config.default = None;
+            }
+            config.save(&path)?;
+            // Only remove tokens we stored ourselves
+            if let Some(jwt_path) = entry.jwt_path {
+                if ClientConfig::store_path(&remove.name).ok().as_ref() == Some(&jwt_path) {
+                    if let Err(err) = fs::remove_file(&jwt_path) {
+                        warn!("{}", trace!(("Failed to remove stored JWT '{}'", jwt_path.display()), err));
+                    }
+                }
+            }
+            println!("Removed profile {}", style(&remove.name).bold());
+        },
+
+        ProfileSubcommands::Use(select) => {
+            info!("Handling `profile use` subcommand");
+
+            if !config.profiles.contains_key(&select.name) {
+                return Err(ProfileError::Unknown { name: select.name.clone(), path });
+            }
+            config.default = Some(select.name.clone());
+            config.save(&path)?;
+            println!("Now using profile {} by default", style(&select.name).bold());
+        },
+    }
+    Ok(())
+}
+
+/***** ENTRYPOINT *****/
+fn main() {
+    // Parse the args
+    let matches: ArgMatches = Arguments::command().get_matches();
+    let mut args: Arguments = match Arguments::from_arg_matches(&matches) {
+        Ok(args) => args,
+        Err(err) => err.exit(),
+    };
+
+    // Setup the logger
+    if let Err(err) = HumanLogger::terminal(DebugMode::from_flags(args.trace, args.debug)).init() {
+        eprintln!("WARNING: Failed to setup logger: {err} (logging disabled for this session)");
+    }
+    info!("{} v{}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
+
+    // Manage profiles, or else use one
+    if let Subcommands::Profile(profile) = &args.subcommand {
+        if let Err(err) = handle_profile(&args, &matches, profile) {
+            error!("{}", err.trace());
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Err(err) = apply_profile(&mut args, &matches) {
+        error!("{}", err.trace());
+        std::process::exit(1);
+    }
+
+    // Resolve the name
+    let name: Cow<str> = match args.name {
+        Some(name) => Cow::Owned(name),
+        None => Cow::Borrowed(names::three::usualcase::rand()),
     };
     debug!("Working as '{name}'");
 
@@ -559,7 +1770,7 @@ fn main() {
                 info!("Handling `policy push` subcommand");
 
                 // Resolve the JWT
-                let jwt: String = match resolve_jwt(name, args.jwt) {
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
                     Ok(jwt) => jwt,
                     Err(err) => {
                         error!("{}", err.trace());
@@ -568,151 +1779,153 @@ fn main() {
                 };
 
                 // Match on the input language
-                let json_path: Cow<Path> = match push.language {
-                    PolicyLanguage::EFlint => {
-                        let json_path: PathBuf = env::temp_dir().join(format!(
-                            "policy-{}.json",
-                            rand::thread_rng().sample_iter(Alphanumeric).take(8).map(char::from).collect::<String>()
-                        ));
-                        debug!("Compiling input file '{}' to eFLINT JSON file '{}'...", push.path.display(), json_path.display());
-
-                        // Open the output file
-                        debug!("Creating output file '{}'...", json_path.display());
-                        let handle: File = match File::create(&json_path) {
-                            Ok(handle) => handle,
+                let json_path: Cow<Path> = compile_policy(&push.path, push.language, push.eflint_to_json_path.as_deref());
+
+                // Push it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let push = PolicyPush {
+                    version_description: "A test version of policy uploaded using the checker-client tool".into(),
+                    metadata: PolicyMetadata { tags: push.tags, jurisdiction: push.jurisdiction, department: push.department },
+                    stream: push.stream,
+                };
+                let policy: Policy = run(client.add_policy_file(&json_path, push));
+
+                // Show the response to the user
+                print_reply(&policy);
+            },
+
+            PolicySubcommands::Get(get) => {
+                info!("Handling `policy get` subcommand");
+
+                // Resolve the JWT
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
+                    Ok(jwt) => jwt,
+                    Err(err) => {
+                        error!("{}", err.trace());
+                        std::process::exit(1);
+                    },
+                };
+
+                // Get the active policy
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let policy: Policy = run(client.get_active_policy());
+
+                // EITHER: Show the policies as eFLINT or the policy as JSON
+                if get.eflint {
+                    // Parse the policies
+                    for (i, policy) in policy.content.into_iter().enumerate() {
+                        // Attempt to parse the embedded eFLINT
+                        debug!("Deserializing policy {i}...");
+                        let policy: eflint_json::spec::Request = match serde_json::from_str(policy.content.get()) {
+                            Ok(policy) => policy,
                             Err(err) => {
-                                error!("{}", trace!(("Failed to create output file '{}'", json_path.display()), err));
+                                error!("{}", trace!(("Failed to parse policy {i} in request as valid eFLINT JSON"), err));
                                 std::process::exit(1);
                             },
                         };
 
-                        // Run the compiler
-                        debug!("Running eflint-to-json compiler on '{}'...", push.path.display());
-                        if let Err(err) = compile(&push.path, handle, push.eflint_to_json_path.as_ref().map(|p| p.as_path())) {
-                            error!("{}", trace!(("Failed to compile input file '{}'", push.path.display()), err));
-                            std::process::exit(1);
-                        };
-                        Cow::Owned(json_path)
+                        // Show it to the user
+                        println!("{}", style(format!("Active checker policy {i}")).bold());
+                        println!("{:#}", policy.display_syntax());
+                        println!();
+                    }
+                } else {
+                    print_reply(&policy);
+                }
+            },
+
+            PolicySubcommands::Set(set) => {
+                info!("Handling `policy set` subcommand");
+
+                // Resolve the JWT
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
+                    Ok(jwt) => jwt,
+                    Err(err) => {
+                        error!("{}", err.trace());
+                        std::process::exit(1);
                     },
-                    PolicyLanguage::EFlintJson => Cow::Borrowed(&push.path),
                 };
 
-                // Open that file to send it
-                debug!("Opening policy file '{}'...", json_path.display());
-                let body: Vec<u8> = {
-                    // // Open the file
-                    // let handle: File = match File::open(&json_path) {
-                    //     Ok(handle) => handle,
-                    //     Err(err) => {
-                    //         error!("{}", Error::PolicyOpen { path: push.path, err }.trace());
-                    //         std::process::exit(1);
-                    //     },
-                    // };
-
-                    // // Read its metadata
-                    // let metadata: Metadata = match handle.metadata() {
-                    //     Ok(metadata) => metadata,
-                    //     Err(err) => {
-                    //         error!("{}", Error::PolicyMetadata { path: push.path, err }.trace());
-                    //         std::process::exit(1);
-                    //     },
-                    // };
-
-                    // // Put it in a request body
-                    // (Body::new(handle), metadata.len())
-
-                    // First, read the file in its entirety
-                    let policy: String = match fs::read_to_string(&json_path) {
-                        Ok(policy) => policy,
-                        Err(err) => {
-                            error!("{}", trace!(("Failed to read eFLINT JSON file '{}'", json_path.display()), err));
-                            std::process::exit(1);
-                        },
-                    };
-                    // Deserialize it to a raw JSON value
-                    let policy: Box<RawValue> = match serde_json::from_str(&policy) {
-                        Ok(policy) => policy,
-                        Err(err) => {
-                            error!("{}", trace!(("Failed to parse eFLINT JSON file as JSON '{}'", json_path.display()), err));
-                            std::process::exit(1);
-                        },
-                    };
+                // Send it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let policy: Policy = run(client.set_active_policy(set.version));
 
-                    // Wrap it in the request
-                    let request: AddPolicyPostModel = AddPolicyPostModel {
-                        description: None,
-                        version_description: "A test version of policy uploaded using the checker-client tool".into(),
-                        content: vec![PolicyContentPostModel { reasoner: "eflint".into(), reasoner_version: "0.1.0".into(), content: policy }],
-                    };
-                    // Re-serialize
-                    match serde_json::to_string(&request) {
-                        Ok(req) => req.into_bytes(),
-                        Err(err) => {
-                            error!("{}", trace!(("Failed to serialize checker add-policy request to JSON"), err));
-                            std::process::exit(1);
-                        },
-                    }
-                };
+                // Show the response to the user
+                print_reply(&policy);
+            },
+
+            PolicySubcommands::Export(export) => {
+                info!("Handling `policy export` subcommand");
 
-                // Build a request to the checker
-                let addr: String = format!("http://{}:{}/{}", args.address, args.port, POLICY_ADD_POLICY_PATH.1);
-                debug!("Building request to checker '{addr}'...");
-                let client: Client = Client::new();
-                let req: Request = match client
-                    .request(POLICY_ADD_POLICY_PATH.0, &addr)
-                    .header(reqwest::header::AUTHORIZATION, format!("Bearer {jwt}"))
-                    .header(reqwest::header::CONTENT_LENGTH, body.len())
-                    .body(body)
-                    .build()
-                {
-                    Ok(req) => req,
+                // Resolve the JWT
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
+                    Ok(jwt) => jwt,
                     Err(err) => {
-                        error!("{}", trace!(("Failed to build request to '{}:{}'", args.address, args.port), err));
+                        error!("{}", err.trace());
                         std::process::exit(1);
                     },
                 };
 
-                // Send it
-                debug!("Sending request to checker '{addr}'...");
-                let res: Response = match client.execute(req) {
-                    Ok(res) => res,
+                // Download the archive
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let archive: SignedPolicyArchive = run(client.export());
+
+                // Write the archive to the given file
+                let archive: String = match serde_json::to_string_pretty(&archive) {
+                    Ok(archive) => archive,
                     Err(err) => {
-                        error!("{}", trace!(("Failed to execute request to '{}:{}'", args.address, args.port), err));
+                        error!("{}", trace!(("Failed to serialize archive"), err));
                         std::process::exit(1);
                     },
                 };
-                let status: StatusCode = res.status();
-                if !status.is_success() {
-                    error!(
-                        "Request to '{}' failed with {} ({}){}",
-                        addr,
-                        status.as_u16(),
-                        status.canonical_reason().unwrap_or("???"),
-                        if let Ok(err) = res.text() {
-                            format!(
-                                "\n\nResponse:\n{}\n{}\n{}\n",
-                                (0..80).map(|_| '-').collect::<String>(),
-                                err,
-                                (0..80).map(|_| '-').collect::<String>()
-                            )
-                        } else {
-                            String::new()
-                        }
-                    );
+                if let Err(err) = fs::write(&export.output, archive) {
+                    error!("{}", trace!(("Failed to write archive to '{}'", export.output.display()), err));
                     std::process::exit(1);
                 }
+                println!("Exported policy store to {}", style(export.output.display()).bold());
+            },
+
+            PolicySubcommands::Import(import) => {
+                info!("Handling `policy import` subcommand");
+
+                // Resolve the JWT
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
+                    Ok(jwt) => jwt,
+                    Err(err) => {
+                        error!("{}", err.trace());
+                        std::process::exit(1);
+                    },
+                };
+
+                // Read the archive to send; the checker verifies its signature
+                debug!("Reading archive '{}'...", import.path.display());
+                let archive: SignedPolicyArchive = match fs::read_to_string(&import.path) {
+                    Ok(raw) => match serde_json::from_str(&raw) {
+                        Ok(archive) => archive,
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to parse archive '{}'", import.path.display()), err));
+                            std::process::exit(1);
+                        },
+                    },
+                    Err(err) => {
+                        error!("{}", trace!(("Failed to read archive '{}'", import.path.display()), err));
+                        std::process::exit(1);
+                    },
+                };
+
+                // Send it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let result: ImportResultModel = run(client.import(&archive));
 
                 // Show the response to the user
-                println!("{}", style("Checker replied with:").bold());
-                println!("{}", res.text().unwrap_or("<failed to get response body>".into()));
-                println!();
+                print_reply(&result);
             },
 
-            PolicySubcommands::Get(get) => {
-                info!("Handling `policy get` subcommand");
+            PolicySubcommands::Compose(compose) => {
+                info!("Handling `policy compose` subcommand");
 
                 // Resolve the JWT
-                let jwt: String = match resolve_jwt(name, args.jwt) {
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
                     Ok(jwt) => jwt,
                     Err(err) => {
                         error!("{}", err.trace());
@@ -720,106 +1933,87 @@ fn main() {
                     },
                 };
 
-                // Build a request to the checker
-                let addr: String = format!("http://{}:{}/{}", args.address, args.port, POLICY_GET_ACTIVE_POLICY_PATH.1);
-                debug!("Building request to checker '{addr}'...");
-                let client: Client = Client::new();
-                let req: Request = match client
-                    .request(POLICY_GET_ACTIVE_POLICY_PATH.0, &addr)
-                    .header(reqwest::header::AUTHORIZATION, format!("Bearer {jwt}"))
-                    .build()
-                {
-                    Ok(req) => req,
+                // Send it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let policy: Policy =
+                    run(client.compose_active_policy(&ComposePostModel { fragments: compose.versions, description: compose.description }));
+
+                // Show the response to the user
+                print_reply(&policy);
+            },
+
+            PolicySubcommands::Clone(clone) => {
+                info!("Handling `policy clone` subcommand");
+
+                // Resolve the JWT
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
+                    Ok(jwt) => jwt,
                     Err(err) => {
-                        error!("{}", trace!(("Failed to build request to '{}:{}'", args.address, args.port), err));
+                        error!("{}", err.trace());
                         std::process::exit(1);
                     },
                 };
 
-                // Send it
-                debug!("Sending request to checker '{addr}'...");
-                let res: Response = match client.execute(req) {
-                    Ok(res) => res,
+                // Send it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let body = ClonePolicyPostModel { description: clone.description, version_description: clone.version_description };
+                let policy: Policy = run(client.clone_policy(clone.version, &body));
+
+                // Show the response to the user
+                print_reply(&policy);
+            },
+
+            PolicySubcommands::Patch(patch) => {
+                info!("Handling `policy patch` subcommand");
+
+                // Resolve the JWT
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
+                    Ok(jwt) => jwt,
                     Err(err) => {
-                        error!("{}", trace!(("Failed to execute request to '{}:{}'", args.address, args.port), err));
+                        error!("{}", err.trace());
                         std::process::exit(1);
                     },
                 };
-                let status: StatusCode = res.status();
-                if !status.is_success() {
-                    error!(
-                        "Request to '{}' failed with {} ({}){}",
-                        addr,
-                        status.as_u16(),
-                        status.canonical_reason().unwrap_or("???"),
-                        if let Ok(err) = res.text() {
-                            format!(
-                                "\n\nResponse:\n{}\n{}\n{}\n",
-                                (0..80).map(|_| '-').collect::<String>(),
-                                err,
-                                (0..80).map(|_| '-').collect::<String>()
-                            )
-                        } else {
-                            String::new()
-                        }
-                    );
-                    std::process::exit(1);
-                }
 
-                // EITHER: Show the raw response or the parsed one
-                let text: Result<String, reqwest::Error> = res.text();
-                if get.eflint {
-                    // Parse the incoming request
-                    debug!("Parsing checker response...");
-                    let policy: Policy = match text {
-                        Ok(response) => match serde_json::from_str(&response) {
-                            Ok(policy) => policy,
-                            Err(err) => {
-                                error!(
-                                    "Failed to parse response text as Policy: {}\n\nResponse:\n{}\n{}\n{}\n",
-                                    err,
-                                    (0..80).map(|_| '-').collect::<String>(),
-                                    response,
-                                    (0..80).map(|_| '-').collect::<String>()
-                                );
-                                std::process::exit(1);
-                            },
-                        },
+                // Read the contents to change, checking they're JSON
+                let mut content: Vec<PatchPolicyContentModel> = Vec::with_capacity(patch.content.len());
+                for PolicyContentArg { reasoner, path } in patch.content {
+                    debug!("Reading content for reasoner '{reasoner}' from '{}'...", path.display());
+                    let raw: String = match fs::read_to_string(&path) {
+                        Ok(raw) => raw,
                         Err(err) => {
-                            error!("{}", trace!(("Failed to get response"), err));
+                            error!("{}", trace!(("Failed to read policy content '{}'", path.display()), err));
                             std::process::exit(1);
                         },
                     };
-
-                    // Next, parse the policies
-                    for (i, policy) in policy.content.into_iter().enumerate() {
-                        // Attempt to parse the embedded eFLINT
-                        debug!("Deserializing policy {i}...");
-                        let policy: eflint_json::spec::Request = match serde_json::from_str(policy.content.get()) {
-                            Ok(policy) => policy,
-                            Err(err) => {
-                                error!("{}", trace!(("Failed to parse policy {i} in request as valid eFLINT JSON"), err));
-                                std::process::exit(1);
-                            },
-                        };
-
-                        // Show it to the user
-                        println!("{}", style(format!("Active checker policy {i}")).bold());
-                        println!("{:#}", policy.display_syntax());
-                        println!();
+                    match serde_json::from_str(&raw) {
+                        Ok(raw) => content.push(PatchPolicyContentModel { reasoner, reasoner_version: None, content: raw }),
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to parse policy content '{}' as JSON", path.display()), err));
+                            std::process::exit(1);
+                        },
                     }
-                } else {
-                    println!("{}", style("Checker replied with:").bold());
-                    println!("{}", text.unwrap_or("<failed to get response body>".into()));
-                    println!();
                 }
+
+                // Send it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let body = PatchPolicyModel {
+                    description: patch.description,
+                    version_description: patch.version_description,
+                    content,
+                    remove: patch.remove,
+                };
+                let policy: Policy = run(client.patch_policy(patch.version, &body));
+
+                // Show the response to the user
+                print_reply(&policy);
             },
 
-            PolicySubcommands::Set(set) => {
-                info!("Handling `policy set` subcommand");
+            PolicySubcommands::Simulate(simulate) => {
+                info!("Handling `policy simulate` subcommand");
 
                 // Resolve the JWT
-                let jwt: String = match resolve_jwt(name, args.jwt) {
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
                     Ok(jwt) => jwt,
                     Err(err) => {
                         error!("{}", err.trace());
@@ -827,68 +2021,125 @@ fn main() {
                     },
                 };
 
-                // Create the request body to send
-                debug!("Generating policy request...");
-                let body: SetVersionPostModel = SetVersionPostModel { version: set.version };
-                let body: Vec<u8> = match serde_json::to_string(&body) {
-                    Ok(body) => body.into_bytes(),
-                    Err(err) => {
-                        error!("{}", trace!(("Failed to serialize checker set-policy request to JSON"), err));
-                        std::process::exit(1);
-                    },
-                };
+                // Collect the questions from the audit log
+                let statements: Vec<LogStatement> = read_audit_log(&simulate.log);
+                let questions: Vec<RecordedQuestion> =
+                    srv::simulation::collect_questions(&statements, simulate.from.as_deref(), simulate.to.as_deref());
+                if questions.is_empty() {
+                    warn!("No questions found in audit log '{}' (in the given range)", simulate.log.display());
+                }
+                debug!("Replaying {} question(s) against policy {}...", questions.len(), simulate.version);
+
+                // Send it to the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let report: SimulationReport = run(client.simulate_policy(simulate.version, &SimulatePostModel { questions }));
+                if simulate.json {
+                    print_json(&report);
+                    return;
+                }
 
-                // Build a request to the checker
-                let addr: String = format!("http://{}:{}/{}", args.address, args.port, POLICY_SET_ACTIVE_POLICY_PATH.1);
-                debug!("Building request to checker '{addr}'...");
-                let client: Client = Client::new();
-                let req: Request = match client
-                    .request(POLICY_SET_ACTIVE_POLICY_PATH.0, &addr)
-                    .header(reqwest::header::AUTHORIZATION, format!("Bearer {jwt}"))
-                    .header(reqwest::header::CONTENT_LENGTH, body.len())
-                    .body(body)
-                    .build()
-                {
-                    Ok(req) => req,
-                    Err(err) => {
-                        error!("{}", trace!(("Failed to build request to '{}:{}'", args.address, args.port), err));
+                // Show it to the user
+                println!("{}", style(format!("Simulation of policy {} against {} recorded question(s)", simulate.version, report.total)).bold());
+                println!("  {} unchanged", report.unchanged);
+                for (what, outcomes) in [
+                    (style("newly DENIED").bold().red(), &report.newly_denied),
+                    (style("newly AUTHORIZED").bold().green(), &report.newly_allowed),
+                    (style("without original verdict").bold().yellow(), &report.unknown),
+                    (style("failed to evaluate").bold().yellow(), &report.failed),
+                ] {
+                    println!("  {} {}", outcomes.len(), what);
+                    for outcome in outcomes {
+                        let SimulationOutcome { reference, reasons, error, .. } = outcome;
+                        println!("    - {}", style(reference).bold());
+                        for reason in reasons {
+                            println!("        {reason}");
+                        }
+                        if let Some(error) = error {
+                            println!("        {error}");
+                        }
+                    }
+                }
+                println!();
+            },
+
+            PolicySubcommands::Coverage(coverage) => {
+                info!("Handling `policy coverage` subcommand");
+                if coverage.log.is_none() && coverage.workflows.is_empty() {
+                    error!("Nothing to analyse the policy against (give '--log' and/or '--workflow')");
+                    std::process::exit(1);
+                }
+
+                // Read the policy
+                let json_path: Cow<Path> = compile_policy(&coverage.path, coverage.language, coverage.eflint_to_json_path.as_deref());
+                let policy: serde_json::Value = match fs::read_to_string(&json_path).map(|raw| serde_json::from_str(&raw)) {
+                    Ok(Ok(policy)) => policy,
+                    Ok(Err(err)) => {
+                        error!("{}", trace!(("Failed to parse policy file '{}' as eFLINT JSON", json_path.display()), err));
                         std::process::exit(1);
                     },
-                };
-
-                // Send it
-                debug!("Sending request to checker '{addr}'...");
-                let res: Response = match client.execute(req) {
-                    Ok(res) => res,
                     Err(err) => {
-                        error!("{}", trace!(("Failed to execute request to '{}:{}'", args.address, args.port), err));
+                        error!("{}", trace!(("Failed to read policy file '{}'", json_path.display()), err));
                         std::process::exit(1);
                     },
                 };
-                let status: StatusCode = res.status();
-                if !status.is_success() {
-                    error!(
-                        "Request to '{}' failed with {} ({}){}",
-                        addr,
-                        status.as_u16(),
-                        status.canonical_reason().unwrap_or("???"),
-                        if let Ok(err) = res.text() {
-                            format!(
-                                "\n\nResponse:\n{}\n{}\n{}\n",
-                                (0..80).map(|_| '-').collect::<String>(),
-                                err,
-                                (0..80).map(|_| '-').collect::<String>()
-                            )
-                        } else {
-                            String::new()
-                        }
-                    );
-                    std::process::exit(1);
+                let phrases: Vec<serde_json::Value> = srv::coverage::policy_phrases(&policy);
+                if phrases.is_empty() {
+                    warn!("Policy file '{}' has no eFLINT phrases", coverage.path.display());
                 }
 
-                // Show the response to the user
-                println!("{}", style("Checker replied with:").bold());
-                println!("{}", res.text().unwrap_or("<failed to get response body>".into()));
+                // Record the questions in the audit log first, then any other workflows
+                let mut analysis: Coverage = Coverage::new(&phrases);
+                if let Some(log) = &coverage.log {
+                    let statements: Vec<LogStatement> = read_audit_log(log);
+                    if analysis.record_log(&statements, coverage.version) == 0 {
+                        warn!("No questions found in audit log '{}' (for the given policy version)", log.display());
+                    }
+                }
+                for path in &coverage.workflows {
+                    let mut wir: Workflow = read_workflow(path, coverage.workflow_language, &coverage.packages, &coverage.data);
+                    // Compiling requires a user, but no rule can depend on who it is without a question
+                    if wir.user.is_none() {
+                        wir.user = Arc::new(Some("<unknown>".into()));
+                    }
+                    let workflow: workflow::Workflow = match workflow::Workflow::try_from(wir) {
+                        Ok(workflow) => workflow,
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to compile workflow '{}' to a checker workflow", path.display()), err));
+                            std::process::exit(1);
+                        },
+                    };
+                    analysis.record_workflow(&workflow);
+                }
+                let report: CoverageReport = analysis.report();
+                if coverage.json {
+                    print_json(&report);
+                    return;
+                }
+
+                // Show it to the user
+                println!(
+                    "{}",
+                    style(format!(
+                        "Coverage of policy '{}' by {} workflow(s) and {} reasoner response(s)",
+                        coverage.path.display(),
+                        report.workflows,
+                        report.responses
+                    ))
+                    .bold()
+                );
+                match &report.never_fired {
+                    Some(never_fired) => {
+                        println!("  {} of {} rule(s) {}", never_fired.len(), report.rules.len(), style("never fired").bold().yellow());
+                        for rule in report.rules.iter().filter(|rule| rule.fired == 0) {
+                            println!("    - {} ({})", style(&rule.name).bold(), rule.kind);
+                        }
+                    },
+                    None => println!("  {} rule(s), but no reasoner responses to tell which of them fired (give '--log')", report.rules.len()),
+                }
+                println!("  {} fact(s) asserted by workflows that {}", report.unconstrained.len(), style("no rule depends on").bold().yellow());
+                for PatternCoverage { fact, occurrences, examples } in &report.unconstrained {
+                    println!("    - {} ({} time(s), e.g., in {})", style(fact).bold(), occurrences, examples.join(", "));
+                }
                 println!();
             },
         },
@@ -898,7 +2149,7 @@ fn main() {
                 info!("Handling `check workflow` subcommand");
 
                 // Resolve the JWT
-                let jwt: String = match resolve_jwt(name, args.jwt) {
+                let jwt: String = match resolve_jwt(name, args.jwt, args.insecure_dev_token.as_deref()) {
                     Ok(jwt) => jwt,
                     Err(err) => {
                         error!("{}", err.trace());
@@ -906,100 +2157,9 @@ fn main() {
                     },
                 };
 
-                // Read the data index (we'll need it for planning)
-                let dindex: DataIndex = brane_shr::utilities::create_data_index_from(&wf.data);
-
-                // Match on the input language
-                let mut wir: Workflow = match wf.language {
-                    WorkflowLanguage::BraneScript => {
-                        debug!("Compiling input file '{}' to a Brane WIR...", wf.path.display());
-
-                        // Open the input file
-                        debug!("Reading input file '{}' as BraneScript", wf.path.display());
-                        let input: String = match fs::read_to_string(&wf.path) {
-                            Ok(input) => input,
-                            Err(err) => {
-                                error!("{}", trace!(("Failed to read input file '{}'", wf.path.display()), err));
-                                std::process::exit(1);
-                            },
-                        };
-
-                        // Open the package index, too
-                        let pindex: PackageIndex = brane_shr::utilities::create_package_index_from(&wf.packages);
-
-                        // Run the compiler
-                        debug!("Running eflint-to-json compiler on '{}'...", wf.path.display());
-                        match brane_ast::compile_program(input.as_bytes(), &pindex, &dindex, &ParserOptions::bscript()) {
-                            CompileResult::Workflow(workflow, warns) => {
-                                // Print warnings (if any)
-                                let spath: String = wf.path.display().to_string();
-                                for warn in warns {
-                                    warn.prettyprint(&spath, &input)
-                                }
-                                workflow
-                            },
-                            CompileResult::Err(errs) => {
-                                // Print the errors that occurred
-                                let spath: String = wf.path.display().to_string();
-                                for err in errs {
-                                    err.prettyprint(&spath, &input);
-                                }
-                                error!("Failed to compile input file '{spath}' (see output above)");
-                                std::process::exit(1);
-                            },
-                            CompileResult::Eof(err) => {
-                                // Print the errors that occurred
-                                let spath: String = wf.path.display().to_string();
-                                err.prettyprint(&spath, input);
-                                error!("Failed to compile input file '{spath}' (see output above)");
-                                std::process::exit(1);
-                            },
-
-                            CompileResult::Program(_, _) | CompileResult::Unresolved(_, _) => unreachable!(),
-                        }
-                    },
-                    WorkflowLanguage::Wir => {
-                        // Open the input file
-                        debug!("Reading input file '{}' as Brane WIR...", wf.path.display());
-                        let input: String = match fs::read_to_string(&wf.path) {
-                            Ok(input) => input,
-                            Err(err) => {
-                                error!("{}", trace!(("Failed to read input file '{}'", wf.path.display()), err));
-                                std::process::exit(1);
-                            },
-                        };
-
-                        // Deserialize
-                        match serde_json::from_str(&input) {
-                            Ok(wir) => wir,
-                            Err(err) => {
-                                error!("{}", trace!(("Failed to parse input file '{}' as Brane WIR", wf.path.display()), err));
-                                std::process::exit(1);
-                            },
-                        }
-                    },
-                };
-
-                // Trivially plan the workflow
-                {
-                    // Plan the main workflow
-                    let mut graph: Arc<Vec<Edge>> = Arc::new(vec![]);
-                    std::mem::swap(&mut graph, &mut wir.graph);
-                    let mut graph: Vec<Edge> = Arc::into_inner(graph).unwrap();
-                    plan_wir(&mut graph, (usize::MAX, 0), None);
-                    let mut graph: Arc<Vec<Edge>> = Arc::new(graph);
-                    std::mem::swap(&mut wir.graph, &mut graph);
-
-                    // Plan the functions in the workflow
-                    let mut funcs: Arc<HashMap<usize, Vec<Edge>>> = Arc::new(HashMap::new());
-                    std::mem::swap(&mut funcs, &mut wir.funcs);
-                    let mut funcs: HashMap<usize, Vec<Edge>> = Arc::into_inner(funcs).unwrap();
-                    for (_, edges) in &mut funcs {
-                        plan_wir(edges, (usize::MAX, 0), None);
-                    }
-                    let mut funcs: Arc<HashMap<usize, Vec<Edge>>> = Arc::new(funcs);
-                    std::mem::swap(&mut wir.funcs, &mut funcs);
-                }
+                // Read the workflow and trivially plan it
+                let mut wir: Workflow = read_workflow(&wf.path, wf.language, &wf.packages, &wf.data);
+                plan_workflow(&mut wir);
                 if log::max_level() >= LevelFilter::Debug {
                     let mut buf: Vec<u8> = Vec::new();
                     brane_ast::traversals::print::ast::do_traversal(&wir, &mut buf).unwrap();
@@ -1009,148 +2169,259 @@ fn main() {
                 // Also add a user
                 wir.user = Arc::new(Some(check.result_owner.unwrap_or_else(|| names::three::usualcase::rand().into())));
 
-                // Now put the workflow in a request and serialize it
-                let body: Vec<u8> = match serde_json::to_string(&WorkflowValidationRequest { use_case: check.use_case, workflow: wir }) {
-                    Ok(body) => body.into_bytes(),
-                    Err(err) => {
-                        error!("{}", trace!(("Failed to serialize given Brane WIR in a WorkflowValidationRequest to JSON"), err));
-                        std::process::exit(1);
+                // Ask the checker
+                let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+                let verdict: Verdict = run(client.execute_workflow(&WorkflowValidationRequest {
+                    use_case:     check.use_case,
+                    workflow:     wir,
+                    priority:     Priority::Interactive,
+                    explain:      check.explain,
+                    node_results: check.node_results,
+                    granularity:  if check.fail_fast {
+                        Some(Granularity::FailFast)
+                    } else if check.exhaustive {
+                        Some(Granularity::Exhaustive)
+                    } else {
+                        None
                     },
-                };
+                }));
 
-                // Build a request to the checker
-                let addr: String = format!("http://{}:{}/{}", args.address, args.port, DELIB_WORKFLOW_VALIDATION_PATH.1);
-                debug!("Building request to checker '{addr}'...");
-                let client: Client = Client::new();
-                let req: Request = match client
-                    .request(DELIB_WORKFLOW_VALIDATION_PATH.0, &addr)
-                    .header(reqwest::header::AUTHORIZATION, format!("Bearer {jwt}"))
-                    .header(reqwest::header::CONTENT_LENGTH, body.len())
-                    .body(body)
-                    .build()
-                {
-                    Ok(req) => req,
-                    Err(err) => {
-                        error!("{}", trace!(("Failed to build request to '{}:{}'", args.address, args.port), err));
-                        std::process::exit(1);
-                    },
-                };
+                // Show the response to the user
+                print_reply(&verdict);
+            },
+        },
+
+        Subcommands::Workflow(wf) => match wf.action {
+            WorkflowSubcommands::Lint(lint) => {
+                info!("Handling `workflow lint` subcommand");
 
-                // Send it
-                debug!("Sending request to checker '{addr}'...");
-                let res: Response = match client.execute(req) {
-                    Ok(res) => res,
+                // Read the workflow, planning it if told to
+                let mut wir: Workflow = read_workflow(&lint.path, lint.language, &lint.packages, &lint.data);
+                if lint.plan {
+                    plan_workflow(&mut wir);
+                }
+                // Compiling requires a user, but linting doesn't care who it is
+                if wir.user.is_none() {
+                    wir.user = Arc::new(Some("<unknown>".into()));
+                }
+
+                // Compile it to what the checker sees
+                let workflow: workflow::Workflow = match workflow::Workflow::try_from(wir) {
+                    Ok(workflow) => workflow,
                     Err(err) => {
-                        error!("{}", trace!(("Failed to execute request to '{}:{}'", args.address, args.port), err));
+                        error!("{}", trace!(("Failed to compile workflow '{}' to a checker workflow", lint.path.display()), err));
                         std::process::exit(1);
                     },
                 };
-                let status: StatusCode = res.status();
-                if !status.is_success() {
-                    error!(
-                        "Request to '{}' failed with {} ({}){}",
-                        addr,
-                        status.as_u16(),
-                        status.canonical_reason().unwrap_or("???"),
-                        if let Ok(err) = res.text() {
-                            format!(
-                                "\n\nResponse:\n{}\n{}\n{}\n",
-                                (0..80).map(|_| '-').collect::<String>(),
-                                err,
-                                (0..80).map(|_| '-').collect::<String>()
-                            )
-                        } else {
-                            String::new()
-                        }
-                    );
+
+                // Lint it
+                let config: LintConfig = lint.levels.into_iter().collect();
+                let findings: Vec<Finding> = workflow.lint(&config);
+                if lint.json {
+                    match serde_json::to_string_pretty(&findings) {
+                        Ok(findings) => println!("{findings}"),
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to serialize lint findings to JSON"), err));
+                            std::process::exit(1);
+                        },
+                    }
+                } else if findings.is_empty() {
+                    println!("{}", style("No lint findings.").bold());
+                } else {
+                    for finding in &findings {
+                        let level: String = match finding.level {
+                            LintLevel::Deny => style(finding.level).bold().red().to_string(),
+                            LintLevel::Warn | LintLevel::Allow => style(finding.level).bold().yellow().to_string(),
+                        };
+                        println!("{level}[{}]: {}", style(finding.rule).bold(), finding.message);
+                    }
+                }
+                if lint::denies(&findings) {
                     std::process::exit(1);
                 }
-
-                // Show the response to the user
-                println!("{}", style("Checker replied with:").bold());
-                println!("{}", res.text().unwrap_or("<failed to get response body>".into()));
-                println!();
             },
         },
 
         Subcommands::Log(log) => {
-            // Open the log file
-            debug!("Opening log file '{}'...", log.log.display());
-            let handle: BufReader<File> = match File::open(&log.log) {
-                Ok(handle) => BufReader::new(handle),
-                Err(err) => {
-                    error!("{}", trace!(("Failed to open log file '{}'", log.log.display()), err));
-                    std::process::exit(1);
-                },
-            };
-
-            // Separate the log into statements
-            debug!("Finding log statements...");
-            let mut buf: String = String::new();
-            let mut statements: Vec<LogStatement> = Vec::new();
-            for line in handle.lines() {
-                // Unwrap the line
-                let line: String = match line {
-                    Ok(line) => line,
-                    Err(err) => {
-                        error!("{}", trace!(("Failed to read line from '{}'", log.log.display()), err));
+            // Proofs are about the raw entries rather than the statements in them
+            match &log.action {
+                LogSubcommands::Prove(prove) => {
+                    info!("Handling `log prove` subcommand");
+                    if let Some(db) = &log.db {
+                        error!("Cannot prove entries in audit log database '{}'; only audit log files are anchored", db.display());
                         std::process::exit(1);
-                    },
-                };
+                    }
 
-                // See if the line begins with what we want
-                if let Some(start_pos) = line_is_log_line(&line) {
-                    // Flush the buffer if we have any to flush
-                    if !buf.is_empty() {
-                        // Attempt to parse the non-intro part as a LogStatement
-                        match serde_json::from_str(&buf) {
-                            Ok(stmt) => {
-                                statements.push(stmt);
+                    // Read the raw entries and the anchors
+                    let entries: Vec<String> = match fs::read_to_string(&log.log) {
+                        Ok(raw) => raw.lines().map(String::from).collect(),
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to read log file '{}'", log.log.display()), err));
+                            std::process::exit(1);
+                        },
+                    };
+                    let anchors: Vec<AuditAnchor> = match fs::read_to_string(&prove.anchors) {
+                        Ok(raw) => match raw.lines().filter(|line| !line.trim().is_empty()).map(serde_json::from_str).collect() {
+                            Ok(anchors) => anchors,
+                            Err(err) => {
+                                error!("{}", trace!(("Failed to parse anchors file '{}'", prove.anchors.display()), err));
+                                std::process::exit(1);
                             },
+                        },
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to read anchors file '{}'", prove.anchors.display()), err));
+                            std::process::exit(1);
+                        },
+                    };
+
+                    // Prove the entries
+                    let proofs: Vec<InclusionProof> = InclusionProof::for_reference(&entries, &anchors, &prove.reference_id);
+                    if proofs.is_empty() {
+                        println!(
+                            "No anchored entries for request '{}' were {} in the audit log",
+                            style(&prove.reference_id).bold(),
+                            style("found").bold().yellow()
+                        );
+                        return;
+                    }
+                    for proof in &proofs {
+                        println!(
+                            "Entry on line {} is {} in segment {}-{} with root {}",
+                            proof.line + 1,
+                            if proof.verify() { style("INCLUDED").bold().green() } else { style("NOT INCLUDED").bold().red() },
+                            proof.anchor.first + 1,
+                            proof.anchor.first + proof.anchor.size,
+                            style(&proof.anchor.root).bold()
+                        );
+                    }
+                    if let Some(output) = &prove.output {
+                        let raw: String = match serde_json::to_string_pretty(&proofs) {
+                            Ok(raw) => raw,
                             Err(err) => {
-                                error!(
-                                    "Failed to parse audit log line(s) as a log statement: {}\n\nLine(s):\n{}\n{}\n{}\n",
-                                    err,
-                                    (0..80).map(|_| '-').collect::<String>(),
-                                    buf,
-                                    (0..80).map(|_| '-').collect::<String>()
-                                );
+                                error!("{}", trace!(("Failed to serialize inclusion proofs"), err));
                                 std::process::exit(1);
                             },
                         };
-                        // Clean the buffer to continue
-                        buf.clear();
+                        if let Err(err) = fs::write(output, raw) {
+                            error!("{}", trace!(("Failed to write inclusion proofs to '{}'", output.display()), err));
+                            std::process::exit(1);
+                        }
+                        println!("Written {} proof(s) to {}", proofs.len(), style(output.display()).bold());
                     }
+                    return;
+                },
 
-                    // Add the new line to the buffer
-                    buf.push_str(&line[start_pos..]);
-                } else {
-                    // Add to the buffer
-                    buf.push('\n');
-                    buf.push_str(&line);
-                }
-            }
+                LogSubcommands::VerifyProof(verify) => {
+                    info!("Handling `log verify-proof` subcommand");
 
-            // Parse the remainder of the buffer, too
-            if !buf.is_empty() {
-                // Attempt to parse the non-intro part as a LogStatement
-                match serde_json::from_str(&buf) {
-                    Ok(stmt) => {
-                        statements.push(stmt);
-                    },
-                    Err(err) => {
+                    let proofs: Vec<InclusionProof> = match fs::read_to_string(&verify.proof) {
+                        Ok(raw) => match serde_json::from_str(&raw) {
+                            Ok(proofs) => proofs,
+                            Err(err) => {
+                                error!("{}", trace!(("Failed to parse proofs file '{}'", verify.proof.display()), err));
+                                std::process::exit(1);
+                            },
+                        },
+                        Err(err) => {
+                            error!("{}", trace!(("Failed to read proofs file '{}'", verify.proof.display()), err));
+                            std::process::exit(1);
+                        },
+                    };
+
+                    let mut valid: bool = true;
+                    for proof in &proofs {
+                        let ok: bool =
+                            proof.verify() && verify.root.as_ref().map(|root| root.eq_ignore_ascii_case(&proof.anchor.root)).unwrap_or(true);
+                        println!(
+                            "Entry on line {} of request '{}' is {}",
+                            proof.line + 1,
+                            style(&proof.reference).bold(),
+                            if ok { style("VALID").bold().green() } else { style("INVALID").bold().red() }
+                        );
+                        valid &= ok;
+                    }
+                    if !valid {
+                        std::process::exit(1);
+                    }
+                    return;
+                },
+
+                LogSubcommands::Migrate(migrate) => {
+                    info!("Handling `log migrate` subcommand");
+                    if let Some(db) = &log.db {
                         error!(
-                            "Failed to parse audit log line(s) as a log statement: {}\n\nLine(s):\n{}\n{}\n{}\n",
-                            err,
-                            (0..80).map(|_| '-').collect::<String>(),
-                            buf,
-                            (0..80).map(|_| '-').collect::<String>()
+                            "Cannot migrate audit log database '{}'; it is append-only, and its statements are upgraded when read instead",
+                            db.display()
                         );
                         std::process::exit(1);
-                    },
-                };
+                    }
+                    migrate_audit_log(&log.log, &migrate.anchors);
+                    return;
+                },
+
+                LogSubcommands::Retain(retain) => {
+                    info!("Handling `log retain` subcommand");
+                    if let Some(db) = &log.db {
+                        error!("Cannot retain audit log database '{}'; only audit log files are anchored", db.display());
+                        std::process::exit(1);
+                    }
+                    let policy: RetentionPolicy = match RetentionPolicy::from_path(&retain.policy) {
+                        Ok(policy) => policy,
+                        Err(err) => {
+                            error!("{}", err.trace());
+                            std::process::exit(1);
+                        },
+                    };
+                    let retainer = Retainer::new(&log.log, &retain.anchors, policy, retain.storage.clone());
+                    let moved: Vec<AuditAnchor> = run(retainer.retain(chrono::Local::now().naive_local()));
+                    for anchor in &moved {
+                        println!(
+                            "Moved segment {}-{} to {}",
+                            anchor.first + 1,
+                            anchor.end(),
+                            style(anchor.archived.as_deref().unwrap_or_default()).bold()
+                        );
+                    }
+                    println!("Moved {} segment(s) to {}", style(moved.len()).bold(), retain.storage);
+                    return;
+                },
+
+                LogSubcommands::VerifyArchive(verify) => {
+                    info!("Handling `log verify-archive` subcommand");
+                    if let Some(db) = &log.db {
+                        error!("Cannot verify archive of audit log database '{}'; only audit log files are anchored", db.display());
+                        std::process::exit(1);
+                    }
+                    let (archived, checked): (usize, usize) = run(verify_archive(&log.log, &verify.anchors));
+                    println!(
+                        "Audit log is {}: {} archived and {} live segment(s) are unbroken",
+                        style("CONTINUOUS").bold().green(),
+                        style(archived).bold(),
+                        style(checked - archived).bold()
+                    );
+                    return;
+                },
+
+                _ => {},
             }
 
+            let statements: Vec<(String, LogStatement)> = match &log.db {
+                Some(db) => {
+                    // Let the database find the verdicts we're looking for, if that's all we need
+                    let query: AuditLogQuery = match &log.action {
+                        LogSubcommands::Reason(reason) => AuditLogQuery {
+                            reference: Some(reason.reference_id.clone()),
+                            kind: Some("REASONER-VERDICT".into()),
+                            ..Default::default()
+                        },
+                        LogSubcommands::Latency(_) => AuditLogQuery { kind: Some("REQUEST-TIMINGS".into()), ..Default::default() },
+                        _ => AuditLogQuery::default(),
+                    };
+                    read_timestamped_audit_store(db, &query)
+                },
+                None => read_timestamped_audit_log(&log.log),
+            };
+
             // Now continue with the subcommand to parse the statements
             match log.action {
                 LogSubcommands::Reason(reason) => {
@@ -1158,7 +2429,7 @@ fn main() {
 
                     // Search statements for reasoner outputs
                     let mut found: bool = false;
-                    for stmt in statements {
+                    for (_, stmt) in statements {
                         if let LogStatement::ReasonerVerdict { reference, verdict } = stmt {
                             if reason.reference_id != reference {
                                 continue;
@@ -1182,7 +2453,130 @@ fn main() {
                         println!("Request '{}' was {} in the audit log", style(&reason.reference_id).bold(), style("not found").bold().yellow());
                     }
                 },
+
+                LogSubcommands::Subject(subject) => {
+                    info!("Handling `log subject` subcommand");
+
+                    // Resolve the subject's pseudonym if the log is pseudonymized
+                    let name: String = match &subject.pseudonym_key {
+                        Some(path) => match fs::read(path) {
+                            Ok(key) => {
+                                let key: &[u8] = key.trim_ascii_end();
+                                let pseudonym: String = Redactor::new(Some(key.to_vec()), false).pseudonym(&subject.subject);
+                                debug!("Searching for pseudonym '{pseudonym}' of '{}'", subject.subject);
+                                pseudonym
+                            },
+                            Err(err) => {
+                                error!("{}", trace!(("Failed to read pseudonym key file '{}'", path.display()), err));
+                                std::process::exit(1);
+                            },
+                        },
+                        None => subject.subject,
+                    };
+
+                    // Collect everything about the subject
+                    let report: DataSubjectReport =
+                        DataSubjectReport::new(name, statements.iter().map(|(timestamp, stmt)| (Some(timestamp.as_str()), stmt)));
+                    if subject.json {
+                        match serde_json::to_string_pretty(&report) {
+                            Ok(report) => println!("{report}"),
+                            Err(err) => {
+                                error!("{}", trace!(("Failed to serialize data subject report"), err));
+                                std::process::exit(1);
+                            },
+                        }
+                        return;
+                    }
+
+                    // Show it chronologically
+                    println!("Data subject report for {} ({})", style(&report.subject).bold(), log.log.display());
+                    println!();
+                    if report.events.is_empty() {
+                        println!("No requests touching {} were {} in the audit log", style(&report.subject).bold(), style("found").bold().yellow());
+                        return;
+                    }
+                    for event in &report.events {
+                        println!(
+                            "[{}] {} request '{}' by {} was {}",
+                            event.timestamp.as_deref().unwrap_or("????-??-?? ??:??:??"),
+                            event.kind,
+                            style(&event.reference).bold(),
+                            event.requested_by,
+                            match event.allowed {
+                                Some(true) => style("AUTHORIZED").bold().green(),
+                                Some(false) => style("DENIED").bold().red(),
+                                None => style("UNANSWERED").bold().yellow(),
+                            }
+                        );
+                        println!("  - involved as: {}", event.roles.iter().map(|role| role.to_string()).collect::<Vec<String>>().join(", "));
+                        println!("  - workflow: {}", event.workflow);
+                        if let Some(task) = &event.task {
+                            println!("  - task: {task}");
+                        }
+                        if let Some(data) = &event.data {
+                            println!("  - dataset: {data}");
+                        }
+                        println!("  - decided by policy version: {}", event.policy);
+                        if let Some(fallback) = &event.fallback {
+                            println!("  - reasoner unavailable; answered with fallback '{fallback}'");
+                        }
+                        for reason in &event.reasons {
+                            println!("  - reason for denial: {reason}");
+                        }
+                    }
+                    println!();
+
+                    // Show the policies involved
+                    println!("{}", style("Policy versions involved:").bold());
+                    for policy in &report.policies {
+                        println!("  - {} ({} request(s)){}", style(policy.version).bold(), policy.requests, match (
+                            &policy.description,
+                            &policy.version_description
+                        ) {
+                            (Some(desc), Some(vdesc)) => format!(": {desc} - {vdesc}"),
+                            _ => String::new(),
+                        });
+                    }
+                },
+
+                LogSubcommands::Latency(latency) => {
+                    info!("Handling `log latency` subcommand");
+
+                    let report: LatencyReport = LatencyReport::new(statements.iter().map(|(_, stmt)| stmt));
+                    if latency.json {
+                        match serde_json::to_string_pretty(&report) {
+                            Ok(report) => println!("{report}"),
+                            Err(err) => {
+                                error!("{}", trace!(("Failed to serialize latency report"), err));
+                                std::process::exit(1);
+                            },
+                        }
+                        return;
+                    }
+
+                    if report.questions == 0 {
+                        println!("No question timings were {} in the audit log", style("found").bold().yellow());
+                        return;
+                    }
+                    println!("Latency of {} question(s) ({})", style(report.questions).bold(), log.log.display());
+                    println!();
+                    println!("{:<18} {:>8} {:>10} {:>8} {:>8} {:>8} {:>8}", "PHASE", "SAMPLES", "MEAN (ms)", "P50", "P95", "P99", "MAX");
+                    for phase in &report.phases {
+                        println!(
+                            "{:<18} {:>8} {:>10.1} {:>8} {:>8} {:>8} {:>8}",
+                            phase.phase, phase.samples, phase.mean_ms, phase.p50_ms, phase.p95_ms, phase.p99_ms, phase.max_ms
+                        );
+                    }
+                },
+
+                LogSubcommands::Prove(_)
+                | LogSubcommands::VerifyProof(_)
+                | LogSubcommands::Migrate(_)
+                | LogSubcommands::Retain(_)
+                | LogSubcommands::VerifyArchive(_) => unreachable!(),
             }
         },
+
+        Subcommands::Profile(_) => unreachable!(),
     }
 }
@@ -0,0 +1,237 @@
+//! Implements named profiles for the `checker-client`, stored in `~/.config/policy-reasoner/client.toml`.
+//!
+//! A profile remembers which checker to talk to (address and port), where to find the JWT to authenticate with and
+//! which use-case to report, such that these need not be given on every call. Values given on the command line always
+//! win over those in the profile.
+//!
+//! Tokens stored with `checker-client profile add --jwt` are kept next to the config in a `credentials`
+//! directory, readable only by the current user.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/***** CONSTANTS *****/
+/// The name of the directory (in the user's config directory) where we keep our files.
+const CONFIG_DIR: &str = "policy-reasoner";
+/// The name of the config file (in [`CONFIG_DIR`]).
+const CONFIG_FILE: &str = "client.toml";
+/// The name of the directory (in [`CONFIG_DIR`]) where stored tokens are kept.
+const CREDENTIALS_DIR: &str = "credentials";
+
+/***** ERRORS *****/
+/// Defines errors that originate from reading or writing profiles.
+#[derive(Debug)]
+pub enum ProfileError {
+    /// Neither `$XDG_CONFIG_HOME` nor `$HOME` is set.
+    ConfigDirUnknown,
+    /// Failed to read the config file.
+    ConfigRead { path: PathBuf, err: std::io::Error },
+    /// Failed to parse the config file.
+    ConfigParse { path: PathBuf, err: toml::de::Error },
+    /// Failed to serialize the config file.
+    ConfigSerialize { path: PathBuf, err: toml::ser::Error },
+    /// Failed to write the config file.
+    ConfigWrite { path: PathBuf, err: std::io::Error },
+    /// Failed to create a directory for the config.
+    DirCreate { path: PathBuf, err: std::io::Error },
+    /// Failed to read the JWT of a profile.
+    JwtRead { path: PathBuf, err: std::io::Error },
+    /// Failed to store the JWT of a profile.
+    JwtWrite { path: PathBuf, err: std::io::Error },
+    /// The requested profile does not exist.
+    Unknown { name: String, path: PathBuf },
+}
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        use ProfileError::*;
+        match self {
+            ConfigDirUnknown => write!(f, "Cannot find config directory (neither $XDG_CONFIG_HOME nor $HOME is set)"),
+            ConfigRead { path, .. } => write!(f, "Failed to read client config file '{}'", path.display()),
+            ConfigParse { path, .. } => write!(f, "Failed to parse client config file '{}'", path.display()),
+            ConfigSerialize { path, .. } => write!(f, "Failed to serialize client config for file '{}'", path.display()),
+            ConfigWrite { path, .. } => write!(f, "Failed to write client config file '{}'", path.display()),
+            DirCreate { path, .. } => write!(f, "Failed to create directory '{}'", path.display()),
+            JwtRead { path, .. } => write!(f, "Failed to read JWT file '{}'", path.display()),
+            JwtWrite { path, .. } => write!(f, "Failed to write JWT file '{}'", path.display()),
+            Unknown { name, path } => write!(f, "Unknown profile '{name}' (not in '{}')", path.display()),
+        }
+    }
+}
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use ProfileError::*;
+        match self {
+            ConfigDirUnknown => None,
+            ConfigRead { err, .. } => Some(err),
+            ConfigParse { err, .. } => Some(err),
+            ConfigSerialize { err, .. } => Some(err),
+            ConfigWrite { err, .. } => Some(err),
+            DirCreate { err, .. } => Some(err),
+            JwtRead { err, .. } => Some(err),
+            JwtWrite { err, .. } => Some(err),
+            Unknown { .. } => None,
+        }
+    }
+}
+
+/***** HELPERS *****/
+/// Returns the directory where we keep our files.
+///
+/// # Errors
+/// This function errors if neither `$XDG_CONFIG_HOME` nor `$HOME` is set.
+fn config_dir() -> Result<PathBuf, ProfileError> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir).join(CONFIG_DIR));
+    }
+    match std::env::var_os("HOME").filter(|dir| !dir.is_empty()) {
+        Some(home) => Ok(PathBuf::from(home).join(".config").join(CONFIG_DIR)),
+        None => Err(ProfileError::ConfigDirUnknown),
+    }
+}
+
+/// Writes a file that only the current user may read.
+fn write_private(path: &Path, contents: &str) -> Result<(), std::io::Error> {
+    fs::write(path, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/***** LIBRARY *****/
+/// A named set of defaults for the `checker-client`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Profile {
+    /// The address of the checker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address:  Option<String>,
+    /// The port of the checker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port:     Option<u16>,
+    /// The path to a file with the JWT to authenticate with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt_path: Option<PathBuf>,
+    /// The use-case to report to the checker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_case: Option<String>,
+}
+impl Profile {
+    /// Reads the JWT of this profile, if it has one.
+    ///
+    /// Whitespace around the token (e.g., a trailing newline) is ignored.
+    ///
+    /// # Errors
+    /// This function errors if the JWT file could not be read.
+    pub fn jwt(&self) -> Result<Option<String>, ProfileError> {
+        let Some(path) = &self.jwt_path else { return Ok(None) };
+        match fs::read_to_string(path) {
+            Ok(jwt) => Ok(Some(jwt.trim().into())),
+            Err(err) => Err(ProfileError::JwtRead { path: path.clone(), err }),
+        }
+    }
+}
+
+/// The contents of the `client.toml` file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ClientConfig {
+    /// The profile to use if none is given on the command line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default:  Option<String>,
+    /// The profiles, by name.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+impl ClientConfig {
+    /// Returns the path of the config file.
+    ///
+    /// # Errors
+    /// This function errors if neither `$XDG_CONFIG_HOME` nor `$HOME` is set.
+    #[inline]
+    pub fn path() -> Result<PathBuf, ProfileError> { Ok(config_dir()?.join(CONFIG_FILE)) }
+
+    /// Reads the config file.
+    ///
+    /// # Arguments
+    /// - `path`: The path to read it from.
+    ///
+    /// # Returns
+    /// The config in the file, or an empty one if it does not exist yet.
+    ///
+    /// # Errors
+    /// This function errors if the file exists but could not be read or parsed.
+    pub fn load(path: &Path) -> Result<Self, ProfileError> {
+        let raw: String = match fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ProfileError::ConfigRead { path: path.into(), err }),
+        };
+        toml::from_str(&raw).map_err(|err| ProfileError::ConfigParse { path: path.into(), err })
+    }
+
+    /// Writes the config file, creating its directory if needed.
+    ///
+    /// # Arguments
+    /// - `path`: The path to write it to.
+    ///
+    /// # Errors
+    /// This function errors if the config could not be serialized or written.
+    pub fn save(&self, path: &Path) -> Result<(), ProfileError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| ProfileError::DirCreate { path: dir.into(), err })?;
+        }
+        let raw: String = toml::to_string_pretty(self).map_err(|err| ProfileError::ConfigSerialize { path: path.into(), err })?;
+        write_private(path, &raw).map_err(|err| ProfileError::ConfigWrite { path: path.into(), err })
+    }
+
+    /// Finds the profile to use.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the profile given on the command line, if any.
+    /// - `path`: The path of the config file, for error messages.
+    ///
+    /// # Returns
+    /// The profile called `name`, or else the default profile, or [`None`] if there is no default either.
+    ///
+    /// # Errors
+    /// This function errors if a profile was explicitly asked for but does not exist.
+    pub fn resolve(&self, name: Option<&str>, path: &Path) -> Result<Option<&Profile>, ProfileError> {
+        match name {
+            Some(name) => self.profiles.get(name).map(Some).ok_or_else(|| ProfileError::Unknown { name: name.into(), path: path.into() }),
+            None => Ok(self.default.as_ref().and_then(|name| self.profiles.get(name))),
+        }
+    }
+
+    /// Returns the path where the JWT of a profile is kept when stored with [`ClientConfig::store_jwt()`].
+    ///
+    /// # Errors
+    /// This function errors if neither `$XDG_CONFIG_HOME` nor `$HOME` is set.
+    #[inline]
+    pub fn store_path(name: &str) -> Result<PathBuf, ProfileError> { Ok(config_dir()?.join(CREDENTIALS_DIR).join(format!("{name}.jwt"))) }
+
+    /// Stores a JWT for a profile in the credentials directory.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the profile to store the token for.
+    /// - `jwt`: The token to store.
+    ///
+    /// # Returns
+    /// The path of the file it was stored in.
+    ///
+    /// # Errors
+    /// This function errors if the credentials directory could not be created or the token could not be written.
+    pub fn store_jwt(name: &str, jwt: &str) -> Result<PathBuf, ProfileError> {
+        let path: PathBuf = Self::store_path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| ProfileError::DirCreate { path: dir.into(), err })?;
+        }
+        write_private(&path, jwt.trim()).map_err(|err| ProfileError::JwtWrite { path: path.clone(), err })?;
+        Ok(path)
+    }
+}
@@ -21,10 +21,13 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use console::Style;
-use eflint_to_json::compile;
+use eflint_to_json::{compile, expand};
 use error_trace::ErrorTrace as _;
 use humanlog::{DebugMode, HumanLogger};
 use log::{debug, error, info};
+use policy::PolicyMetadata;
+use policy_reasoner_client::{CheckerClient, PolicyPush};
+use tokio::runtime::{Builder, Runtime};
 
 /***** ERRORS *****/
 /// Defines errors originating in the binary itself.
@@ -32,12 +35,18 @@ use log::{debug, error, info};
 enum Error {
     /// Failed to create the output file.
     FileCreate { path: PathBuf, err: std::io::Error },
+    /// Asked to push the compiled policy while writing it to stdout.
+    PushStdout,
+    /// Failed to start the runtime that pushes the compiled policy.
+    RuntimeCreate { err: std::io::Error },
 }
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
         use Error::*;
         match self {
             FileCreate { path, .. } => write!(f, "Failed to create output file '{}'", path.display()),
+            PushStdout => write!(f, "Cannot push the compiled policy when writing it to stdout (give '--output <PATH>' or omit it)"),
+            RuntimeCreate { .. } => write!(f, "Failed to create runtime for pushing the compiled policy"),
         }
     }
 }
@@ -46,6 +55,8 @@ impl error::Error for Error {
         use Error::*;
         match self {
             FileCreate { err, .. } => Some(err),
+            PushStdout => None,
+            RuntimeCreate { err } => Some(err),
         }
     }
 }
@@ -72,6 +83,49 @@ struct Arguments {
     )]
     output: Option<String>,
 
+    /// Whether to push the compiled policy to a checker.
+    #[clap(
+        long,
+        conflicts_with = "expand",
+        requires = "jwt",
+        help = "If given, pushes the compiled policy to the checker at '--address' and '--port' as a new policy version. The compiled policy is \
+                written to a temporary file unless '--output' is given."
+    )]
+    push: bool,
+    /// The address of the checker to push to.
+    #[clap(long, default_value = "localhost", help = "The address of the checker to push to with '--push'.")]
+    address: String,
+    /// The port of the checker to push to.
+    #[clap(long, default_value = "3030", help = "The port of the checker to push to with '--push'.")]
+    port: u16,
+    /// A JWT that authenticates the user with the checker.
+    #[clap(long, help = "A JWT that is used to authenticate with the checker when pushing with '--push'.")]
+    jwt: Option<String>,
+    /// The description of the pushed policy version.
+    #[clap(
+        long,
+        default_value = "A version of policy pushed using the policy-builder tool",
+        help = "The description of the policy version pushed with '--push'."
+    )]
+    version_description: String,
+    /// Tags to attach to the pushed policy version.
+    #[clap(long = "tag", help = "A tag to attach to the policy version pushed with '--push'. Can be given multiple times.")]
+    tags: Vec<String>,
+    /// The jurisdiction of the pushed policy version.
+    #[clap(long, help = "The jurisdiction (e.g., country or legal framework) the policy version pushed with '--push' applies to.")]
+    jurisdiction: Option<String>,
+    /// The department owning the pushed policy version.
+    #[clap(long, help = "The department owning the policy version pushed with '--push'.")]
+    department: Option<String>,
+    /// Whether to stream the policy to the checker.
+    #[clap(
+        long,
+        requires = "push",
+        help = "If given, streams the compiled policy to the checker as-is instead of reading it into memory first. Use this for very large \
+                policies. Note that the policy is then only validated by the checker."
+    )]
+    stream: bool,
+
     /// Overrides downloading to default location.
     #[clap(
         short,
@@ -80,6 +134,15 @@ struct Arguments {
                 `/tmp/eflint-to-json'."
     )]
     compiler: Option<PathBuf>,
+
+    /// Only expands the templates instead of compiling.
+    #[clap(
+        short,
+        long,
+        help = "If given, does not compile the file but writes it with all '#include's, '#require's and '#foreach' templates resolved. Useful to \
+                see what templates expand to."
+    )]
+    expand: bool,
 }
 
 /***** ENTRYPOINT *****/
@@ -95,33 +158,72 @@ fn main() {
 
     // Resolve the input file
     debug!("Resolving output file...");
-    let (output, output_dsc): (Box<dyn Write>, Cow<str>) = if let Some(output) = args.output {
-        if output != "-" {
-            let output_path: PathBuf = output.into();
-            match File::create(&output_path) {
-                Ok(handle) => (Box::new(handle), Cow::Owned(output_path.to_string_lossy().into())),
-                Err(err) => {
-                    error!("{}", Error::FileCreate { path: output_path, err }.trace());
-                    std::process::exit(1);
-                },
-            }
-        } else {
-            (Box::new(std::io::stdout()), "<stdout>".into())
+    let output_path: Option<PathBuf> = match args.output {
+        Some(output) if output == "-" && args.push => {
+            error!("{}", Error::PushStdout.trace());
+            std::process::exit(1);
+        },
+        Some(output) if output != "-" => Some(output.into()),
+        // When pushing, we need the compiled policy in a file
+        None if args.push => Some(std::env::temp_dir().join(format!("policy-{}.json", std::process::id()))),
+        _ => None,
+    };
+    let (output, output_dsc): (Box<dyn Write>, Cow<str>) = if let Some(output_path) = &output_path {
+        match File::create(output_path) {
+            Ok(handle) => (Box::new(handle), Cow::Owned(output_path.to_string_lossy().into())),
+            Err(err) => {
+                error!("{}", Error::FileCreate { path: output_path.clone(), err }.trace());
+                std::process::exit(1);
+            },
         }
     } else {
         (Box::new(std::io::stdout()), "<stdout>".into())
     };
 
     // Run the thing, then
-    if let Err(err) = compile(&args.path, output, args.compiler.as_ref().map(|c| c.as_path())) {
+    let res = if args.expand { expand(&args.path, output) } else { compile(&args.path, output, args.compiler.as_ref().map(|c| c.as_path())) };
+    if let Err(err) = res {
         error!("{}", err.trace());
         std::process::exit(1);
     }
 
     // Done
     println!(
-        "Successfully compiled {} to {}",
+        "Successfully {} {} to {}",
+        if args.expand { "expanded" } else { "compiled" },
         Style::new().bold().green().apply_to(args.path.display()),
         Style::new().bold().green().apply_to(output_dsc),
     );
+
+    // Push it to the checker if told to do so
+    if let (true, Some(output_path), Some(jwt)) = (args.push, output_path, args.jwt) {
+        info!("Pushing compiled policy to checker at '{}:{}'", args.address, args.port);
+        let runtime: Runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                error!("{}", Error::RuntimeCreate { err }.trace());
+                std::process::exit(1);
+            },
+        };
+        let client = CheckerClient::new(&args.address, args.port).with_token(jwt);
+        let push = PolicyPush {
+            version_description: args.version_description,
+            metadata: PolicyMetadata { tags: args.tags, jurisdiction: args.jurisdiction, department: args.department },
+            stream: args.stream,
+        };
+        match runtime.block_on(client.add_policy_file(&output_path, push)) {
+            Ok(policy) => {
+                println!(
+                    "Successfully pushed {} to {}",
+                    Style::new().bold().green().apply_to(args.path.display()),
+                    Style::new().bold().green().apply_to(format!("{}:{}", args.address, args.port))
+                );
+                println!("New policy version: {}", policy.version.version.map(|v| v.to_string()).unwrap_or_else(|| "<unknown>".into()));
+            },
+            Err(err) => {
+                error!("{}", err.trace());
+                std::process::exit(1);
+            },
+        }
+    }
 }